@@ -0,0 +1,67 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use intel8080::asm;
+use intel8080::emulator::Cpu;
+use intel8080::memory::basic_memory::BasicMemory;
+
+const STEPS: usize = 10_000;
+
+/* Memory operands through every register pair, plus the stack and XCHG */
+const MIXED: &str = "
+start:  LXI SP,$2400
+        LXI H,$2000
+        LXI D,$2100
+        LXI B,$0040
+loop:   LDAX D
+        ADD M
+        MOV M,A
+        STAX D
+        INX H
+        INX D
+        DAD B
+        XCHG
+        XCHG
+        PUSH H
+        POP H
+        DCX B
+        MOV A,C
+        ORA B
+        JNZ loop
+        JMP start
+";
+
+/* Register-only arithmetic, as a baseline without pair access */
+const REGISTERS: &str = "
+loop:   MOV B,A
+        ADD B
+        MOV C,A
+        SUB C
+        INR D
+        DCR E
+        XRA D
+        ANA E
+        JMP loop
+";
+
+fn run(c: &mut Criterion, name: &str, source: &str) {
+    let program = asm::assemble(source).unwrap();
+    let mut cpu = Cpu::new(Box::new(BasicMemory::new()));
+    cpu.load_program(0x0000, &program);
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            for _ in 0..STEPS {
+                black_box(cpu.step());
+            }
+        })
+    });
+}
+
+fn mixed_instructions(c: &mut Criterion) {
+    run(c, "mixed instructions", MIXED);
+}
+
+fn register_instructions(c: &mut Criterion) {
+    run(c, "register instructions", REGISTERS);
+}
+
+criterion_group!(benches, mixed_instructions, register_instructions);
+criterion_main!(benches);
@@ -0,0 +1,241 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/* One frame of instructions and port traffic, replayed against three ways the Cpu could hand port I/O to
+   the board. One opcode byte per executed instruction; IN ($DB) and OUT ($D3) are followed by the port and
+   the value read or written. Captured from frame 120 of the main crate's built-in test ROM with P1 fire
+   and left held, recording debug_snapshot() around each step_instruction(). The arcade program leans far
+   harder on the shift register; recapture from it the same way before revisiting the decision below */
+const FRAME: &[u8] = include_bytes!("data/frame.trace");
+const CYCLES_PER_FRAME: usize = 33_333;
+
+/* Decision: Box<dyn IoBus> for the production design.
+
+   On this trace, timed at -O, the latch model came to roughly 30-37us a frame, dyn IoBus 4-5us and the
+   closures 3us. The latch model's cost is the host polling get_output() after every one of the 33,333
+   ticks whether or not anything was written; the other two only pay on the handful of INs and OUTs. That
+   is about 0.2% of a frame either way, so the choice between the two fast ones is about the API: closures
+   win by a microsecond but put a type parameter on Cpu that every user, test and the no_std build carries,
+   while a trait object keeps Cpu a plain type and lets the board (shift register, sound latches, watchdog)
+   be one IoBus implementation owned by the machine */
+
+#[derive(Clone, Copy)]
+enum Step {
+    Op(u8),
+    In(u8),
+    Out(u8, u8),
+}
+
+fn decode(trace: &[u8]) -> Vec<Step> {
+    let mut steps = vec![];
+    let mut bytes = trace.iter().copied();
+    while let Some(opcode) = bytes.next() {
+        steps.push(match opcode {
+            0xDB => {
+                /* What IN reads comes from the board under test, so the captured value is skipped */
+                let port = bytes.next().unwrap();
+                bytes.next();
+                Step::In(port)
+            },
+            0xD3 => Step::Out(bytes.next().unwrap(), bytes.next().unwrap()),
+            _ => Step::Op(opcode),
+        });
+    }
+    return steps;
+}
+
+/* The Space Invaders side of the ports, identical for every strategy */
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Board {
+    inputs: [u8; 3],
+    shift: u16,
+    offset: u8,
+    port3: u8,
+    port5: u8,
+    watchdog_feeds: u32,
+}
+
+impl Board {
+    fn read(&self, port: u8) -> u8 {
+        match port {
+            3 => return (self.shift << self.offset >> 8) as u8,
+            _ => return self.inputs[port as usize % 3],
+        }
+    }
+
+    fn write(&mut self, port: u8, value: u8) {
+        match port {
+            2 => self.offset = value & 0x7,
+            3 => self.port3 = value,
+            4 => self.shift = (value as u16) << 8 | self.shift >> 8,
+            5 => self.port5 = value,
+            _ => self.watchdog_feeds += 1,
+        }
+    }
+}
+
+/* Stand-in for decoding and executing an instruction, the same work in every strategy */
+fn execute(a: u8, opcode: u8) -> u8 {
+    return a.rotate_left(1) ^ opcode;
+}
+
+/* Today's Cpu: IN reads latches the host refreshes, OUT leaves a value the host polls for after every tick */
+struct LatchCpu {
+    a: u8,
+    inputs: [u8; 8],
+    output: Option<(u8, u8)>,
+}
+
+fn run_latch(steps: &[Step], board: &mut Board, ticks_per_step: usize) -> u8 {
+    let mut cpu = LatchCpu { a: 0, inputs: [0; 8], output: None };
+    for port in 0..4 {
+        cpu.inputs[port] = board.read(port as u8);
+    }
+    for step in steps {
+        match *step {
+            Step::Op(opcode) => cpu.a = execute(cpu.a, opcode),
+            Step::In(port) => cpu.a = execute(cpu.a, cpu.inputs[port as usize & 7]),
+            Step::Out(port, value) => cpu.output = Some((port, value)),
+        }
+        for _ in 0..ticks_per_step {
+            if let Some((port, value)) = black_box(&mut cpu).output.take() {
+                board.write(port, value);
+                cpu.inputs[3] = board.read(3);
+            }
+        }
+    }
+    return cpu.a;
+}
+
+trait IoBus {
+    fn input(&mut self, port: u8) -> u8;
+    fn output(&mut self, port: u8, value: u8);
+}
+
+impl IoBus for Board {
+    fn input(&mut self, port: u8) -> u8 {
+        return self.read(port);
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        self.write(port, value);
+    }
+}
+
+struct DynCpu<'a> {
+    a: u8,
+    bus: Box<dyn IoBus + 'a>,
+}
+
+fn run_dyn(steps: &[Step], board: &mut Board) -> u8 {
+    let mut cpu = DynCpu { a: 0, bus: Box::new(board) };
+    for step in steps {
+        match *step {
+            Step::Op(opcode) => cpu.a = execute(cpu.a, opcode),
+            Step::In(port) => cpu.a = execute(cpu.a, cpu.bus.input(port)),
+            Step::Out(port, value) => cpu.bus.output(port, value),
+        }
+    }
+    return cpu.a;
+}
+
+impl<T: IoBus + ?Sized> IoBus for &mut T {
+    fn input(&mut self, port: u8) -> u8 {
+        return (**self).input(port);
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        (**self).output(port, value);
+    }
+}
+
+enum Access {
+    In(u8),
+    Out(u8, u8),
+}
+
+/* One closure for both directions, since IN and OUT need the same board mutably */
+struct GenericCpu<F: FnMut(Access) -> u8> {
+    a: u8,
+    io: F,
+}
+
+fn run_generic(steps: &[Step], board: &mut Board) -> u8 {
+    let mut cpu = GenericCpu {
+        a: 0,
+        io: |access| match access {
+            Access::In(port) => board.read(port),
+            Access::Out(port, value) => {
+                board.write(port, value);
+                0
+            },
+        },
+    };
+    for step in steps {
+        match *step {
+            Step::Op(opcode) => cpu.a = execute(cpu.a, opcode),
+            Step::In(port) => cpu.a = execute(cpu.a, (cpu.io)(Access::In(port))),
+            Step::Out(port, value) => {
+                (cpu.io)(Access::Out(port, value));
+            },
+        }
+    }
+    return cpu.a;
+}
+
+fn ticks_per_step(steps: &[Step]) -> usize {
+    return CYCLES_PER_FRAME / steps.len();
+}
+
+/* Design validation: every strategy has to leave the board and the accumulator exactly as the others do,
+   and reads of the shift register have to see every OUT before them. Runs ahead of the measurements, so a
+   broken strategy fails the bench rather than reporting a flattering time */
+fn validate(steps: &[Step]) {
+    let outs = steps.iter().filter(|step| matches!(step, Step::Out(..))).count();
+    assert!(outs > 0 && steps.iter().any(|step| matches!(step, Step::In(3))), "trace has no shift register traffic");
+    let mut results = vec![];
+    for run in [run_latch_frame, run_dyn_frame, run_generic_frame] {
+        let mut board = Board::default();
+        let a = run(steps, &mut board);
+        results.push((a, board));
+    }
+    assert_eq!(results[0], results[1], "latch and dyn IoBus disagree");
+    assert_eq!(results[0], results[2], "latch and closures disagree");
+
+    /* A read right after OUT 4 sees it in every strategy, including the latch model's refreshed input */
+    let probe = [Step::Out(4, 0xAB), Step::Out(4, 0xCD), Step::Out(2, 4), Step::In(3)];
+    let expected = execute(0, 0xDA);
+    assert_eq!(run_latch(&probe, &mut Board::default(), 1), expected);
+    assert_eq!(run_dyn(&probe, &mut Board::default()), expected);
+    assert_eq!(run_generic(&probe, &mut Board::default()), expected);
+}
+
+fn run_latch_frame(steps: &[Step], board: &mut Board) -> u8 {
+    return run_latch(steps, board, ticks_per_step(steps));
+}
+
+fn run_dyn_frame(steps: &[Step], board: &mut Board) -> u8 {
+    return run_dyn(steps, board);
+}
+
+fn run_generic_frame(steps: &[Step], board: &mut Board) -> u8 {
+    return run_generic(steps, board);
+}
+
+fn io_strategies(c: &mut Criterion) {
+    let steps = decode(FRAME);
+    validate(&steps);
+    let mut group = c.benchmark_group("port I/O per frame");
+    for (name, run) in [("latch+poll", run_latch_frame as fn(&[Step], &mut Board) -> u8), ("dyn IoBus", run_dyn_frame), ("closures", run_generic_frame)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut board = Board::default();
+                black_box(run(black_box(&steps), &mut board));
+                black_box(board);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, io_strategies);
+criterion_main!(benches);
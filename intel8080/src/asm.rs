@@ -0,0 +1,207 @@
+#[cfg(feature = "std")]
+use core::fmt;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use crate::instruction::{Instruction, OPCODES};
+
+/* A small two-pass assembler for tests and hand-written snippets, using the mnemonics of the OPCODES table:
+
+       ; comment
+       start:  LXI SP,$2400
+               MVI A,$05          ; numbers are $hex, 0xhex or decimal
+       loop:   DCR A
+               JNZ loop
+               ORG $0010          ; continue at this address, padding with zeros
+               DB $C9,$00
+
+   Output starts at address 0. */
+#[derive(Debug, PartialEq)]
+pub enum AsmError {
+    UnknownInstruction(usize, String),
+    BadOperand(usize, String),
+    UndefinedLabel(usize, String),
+    DuplicateLabel(usize, String),
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::UnknownInstruction(line, text) => write!(f, "line {}: unknown instruction {}", line, text),
+            AsmError::BadOperand(line, text) => write!(f, "line {}: bad operand {}", line, text),
+            AsmError::UndefinedLabel(line, label) => write!(f, "line {}: undefined label {}", line, label),
+            AsmError::DuplicateLabel(line, label) => write!(f, "line {}: duplicate label {}", line, label),
+        }
+    }
+}
+
+struct Line<'a> {
+    number: usize,
+    label: Option<&'a str>,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+const REGISTERS: [&str; 10] = ["A", "B", "C", "D", "E", "H", "L", "M", "SP", "PSW"];
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let lines = parse(source);
+    let mut labels: BTreeMap<&str, u16> = BTreeMap::new();
+    let mut address: usize = 0;
+    for line in &lines {
+        if let Some(label) = line.label {
+            if labels.insert(label, address as u16).is_some() {
+                return Err(AsmError::DuplicateLabel(line.number, label.to_string()));
+            }
+        }
+        address = match line.mnemonic.as_str() {
+            "" => address,
+            "ORG" => operand_value(line, 0, &labels, false)? as usize,
+            "DB" => address + line.operands.len(),
+            _ => address + find_instruction(line)?.size,
+        };
+    }
+
+    let mut bytes = Vec::new();
+    for line in &lines {
+        match line.mnemonic.as_str() {
+            "" => {},
+            "ORG" => {
+                let origin = operand_value(line, 0, &labels, true)? as usize;
+                if origin < bytes.len() {
+                    return Err(AsmError::BadOperand(line.number, line.operands[0].to_string()));
+                }
+                bytes.resize(origin, 0);
+            },
+            "DB" => {
+                for index in 0..line.operands.len() {
+                    bytes.push(byte_value(line, index, &labels)?);
+                }
+            },
+            _ => {
+                let instruction = find_instruction(line)?;
+                bytes.push(instruction.opcode);
+                let placeholder = instruction.label.split('_').skip(1).position(|operand| operand == "D8" || operand == "D16" || operand == "ADR");
+                if let Some(index) = placeholder {
+                    match instruction.size {
+                        2 => bytes.push(byte_value(line, index, &labels)?),
+                        _ => bytes.extend_from_slice(&operand_value(line, index, &labels, true)?.to_le_bytes()),
+                    }
+                }
+            },
+        }
+    }
+    return Ok(bytes);
+}
+
+fn parse(source: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    for (index, raw) in source.lines().enumerate() {
+        let mut text = raw.split(';').next().unwrap_or("").trim();
+        let mut label = None;
+        if let Some(colon) = text.find(':') {
+            label = Some(text[..colon].trim());
+            text = text[colon + 1..].trim();
+        }
+        let (mnemonic, rest) = match text.find(char::is_whitespace) {
+            Some(space) => (&text[..space], text[space..].trim()),
+            None => (text, ""),
+        };
+        let operands = if rest.is_empty() { Vec::new() } else { rest.split(',').map(|operand| operand.trim()).collect() };
+        lines.push(Line { number: index + 1, label, mnemonic: mnemonic.to_ascii_uppercase(), operands });
+    }
+    return lines;
+}
+
+fn is_register(operand: &str) -> bool {
+    return REGISTERS.iter().any(|register| register.eq_ignore_ascii_case(operand));
+}
+
+fn find_instruction(line: &Line) -> Result<&'static Instruction, AsmError> {
+    let found = OPCODES.iter().find(|instruction| {
+        if instruction.label.starts_with('*') {
+            return false;
+        }
+        let mut parts = instruction.label.split('_');
+        if parts.next() != Some(line.mnemonic.as_str()) {
+            return false;
+        }
+        let expected: Vec<&str> = parts.collect();
+        expected.len() == line.operands.len() && expected.iter().zip(&line.operands).all(|(expected, operand)| match *expected {
+            "D8" | "D16" | "ADR" => !is_register(operand),
+            literal => literal.eq_ignore_ascii_case(operand),
+        })
+    });
+    return found.ok_or_else(|| AsmError::UnknownInstruction(line.number, line.mnemonic.clone()));
+}
+
+/* Labels may be forward references, so they are only required to resolve in the second pass */
+fn operand_value(line: &Line, index: usize, labels: &BTreeMap<&str, u16>, resolve: bool) -> Result<u16, AsmError> {
+    let operand = match line.operands.get(index) {
+        Some(operand) => *operand,
+        None => return Err(AsmError::BadOperand(line.number, String::new())),
+    };
+    let number = if let Some(hex) = operand.strip_prefix('$').or_else(|| operand.strip_prefix("0x")) {
+        Some(u16::from_str_radix(hex, 16))
+    } else if operand.starts_with(|c: char| c.is_ascii_digit()) {
+        Some(operand.parse::<u16>())
+    } else {
+        None
+    };
+    match number {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(_)) => Err(AsmError::BadOperand(line.number, operand.to_string())),
+        None => match labels.get(operand) {
+            Some(address) => Ok(*address),
+            None if !resolve => Ok(0),
+            None => Err(AsmError::UndefinedLabel(line.number, operand.to_string())),
+        },
+    }
+}
+
+fn byte_value(line: &Line, index: usize, labels: &BTreeMap<&str, u16>) -> Result<u8, AsmError> {
+    let value = operand_value(line, index, labels, true)?;
+    if value > 0xFF {
+        return Err(AsmError::BadOperand(line.number, line.operands[index].to_string()));
+    }
+    return Ok(value as u8);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use crate::disassembler::disassemble;
+
+    #[test]
+    fn test_assemble_instructions() {
+        let bytes = assemble("LXI H,$2400\nmvi a, 16 ; comment\nMOV M,A\nRST 2\nPUSH PSW\nHLT").unwrap();
+        assert_eq!(bytes, vec![0x21, 0x00, 0x24, 0x3E, 0x10, 0x77, 0xD7, 0xF5, 0x76]);
+    }
+
+    #[test]
+    fn test_labels_and_org() {
+        let bytes = assemble("start: JMP handler\nORG $0008\nhandler: DCR A\nJNZ handler\nJMP start\nDB $01,0x02").unwrap();
+        assert_eq!(bytes, vec![0xC3, 0x08, 0x00, 0, 0, 0, 0, 0, 0x3D, 0xC2, 0x08, 0x00, 0xC3, 0x00, 0x00, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_round_trip_with_disassembler() {
+        for source in ["LXI SP,$2400", "ADI $10", "LDA $20F8", "XCHG", "DAA", "MOV A,M", "OUT $03"] {
+            let mut bytes = assemble(source).unwrap();
+            let size = bytes.len();
+            bytes.resize(3, 0);
+            assert_eq!(disassemble([bytes[0], bytes[1], bytes[2]]), (source.to_string(), size));
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(assemble("NOP\nFOO A"), Err(AsmError::UnknownInstruction(2, "FOO".to_string())));
+        assert_eq!(assemble("MVI A,$100"), Err(AsmError::BadOperand(1, "$100".to_string())));
+        assert_eq!(assemble("JMP nowhere"), Err(AsmError::UndefinedLabel(1, "nowhere".to_string())));
+        assert_eq!(assemble("a: NOP\na: NOP"), Err(AsmError::DuplicateLabel(2, "a".to_string())));
+        assert_eq!(assemble("MOV A"), Err(AsmError::UnknownInstruction(1, "MOV".to_string())));
+    }
+}
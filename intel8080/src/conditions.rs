@@ -1,4 +1,5 @@
-use std::fmt;
+#[cfg(feature = "std")]
+use core::fmt;
 
 pub enum ConditionName {
     Carry,
@@ -8,6 +9,24 @@ pub enum ConditionName {
     Parity,
 }
 
+/* PSW bits 7 to 0; None for the constant bits, which always print as '-' so the string keeps its width */
+#[cfg(feature = "std")]
+const PSW_LETTERS: [Option<char>; 8] = [Some('S'), Some('Z'), None, Some('A'), None, Some('P'), None, Some('C')];
+
+/* Even parity of every byte; only Conditions::set_from_result reads it */
+const PARITY: [bool; 256] = parity_table();
+
+const fn parity_table() -> [bool; 256] {
+    let mut table = [false; 256];
+    let mut value = 0;
+    while value < 256 {
+        table[value] = (value as u8).count_ones() % 2 == 0;
+        value += 1;
+    }
+    return table;
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct Conditions {
     carry: bool,
     aux: bool,
@@ -47,6 +66,13 @@ impl Conditions {
         }
     }
 
+    /* Sign, zero and parity of an 8-bit result */
+    pub fn set_from_result(&mut self, result: u8) {
+        self.zero = result == 0;
+        self.sign = result >= 0x80;
+        self.parity = PARITY[result as usize];
+    }
+
     pub fn as_bits(&self) -> u8 {
         let mut bits: u8 = 0b00000010;
         if self.carry {
@@ -67,6 +93,12 @@ impl Conditions {
         return bits;
     }
 
+    pub fn from_bits(bits: u8) -> Self {
+        let mut conditions = Conditions::new();
+        conditions.restore_from_bits(bits);
+        return conditions;
+    }
+
     pub fn restore_from_bits(&mut self, bits: u8) {
         if bits & 0b00000001 == 0b00000001 {
             self.carry = true;
@@ -96,9 +128,23 @@ impl Conditions {
     }
 }
 
+#[cfg(feature = "std")]
+/* "SZ-A-P-C" with '.' for each clear flag, always 8 characters; {:#} gives the verbose form */
 impl fmt::Display for Conditions {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "carry: {}, aux: {}, sign: {}, zero: {}, parity: {}", self.carry, self.aux, self.sign, self.zero, self.parity)
+        if f.alternate() {
+            return write!(f, "carry: {}, aux: {}, sign: {}, zero: {}, parity: {}", self.carry, self.aux, self.sign, self.zero, self.parity);
+        }
+        let bits = self.as_bits();
+        for (index, letter) in PSW_LETTERS.iter().enumerate() {
+            let glyph = match letter {
+                Some(letter) if bits & (0x80 >> index) != 0 => *letter,
+                Some(_) => '.',
+                None => '-',
+            };
+            write!(f, "{}", glyph)?;
+        }
+        return Ok(());
     }
 }
 
@@ -106,6 +152,25 @@ impl fmt::Display for Conditions {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parity_table() {
+        for value in 0..=255u8 {
+            assert_eq!(PARITY[value as usize], value.count_ones() % 2 == 0, "{:#04x}", value);
+        }
+    }
+
+    #[test]
+    fn test_set_from_result() {
+        let mut conditions = Conditions::new();
+        conditions.set(ConditionName::Carry, true);
+        conditions.set_from_result(0x00);
+        assert_eq!((conditions.zero, conditions.sign, conditions.parity, conditions.carry), (true, false, true, true));
+        conditions.set_from_result(0x80);
+        assert_eq!((conditions.zero, conditions.sign, conditions.parity), (false, true, false));
+        conditions.set_from_result(0x81);
+        assert_eq!((conditions.zero, conditions.sign, conditions.parity), (false, true, true));
+    }
+
     #[test]
     fn test_default_conditions() {
         let conditions = Conditions::new();
@@ -309,4 +374,32 @@ mod tests {
         assert_eq!(conditions.zero, true);
         assert_eq!(conditions.sign, true);
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_compact_display() {
+        assert_eq!(Conditions::new().to_string(), "..-.-.-.");
+        assert_eq!(Conditions::from_bits(0b11010111).to_string(), "SZ-A-P-C");
+        assert_eq!(Conditions::from_bits(0b01000001).to_string(), ".Z-.-.-C");
+        assert_eq!(Conditions::from_bits(0b10010100).to_string(), "S.-A-P-.");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_compact_display_constant_bits() {
+        /* Bits 5, 3 and 1 never change the string, which is always 8 characters */
+        for bits in 0..=255u8 {
+            let text = Conditions::from_bits(bits).to_string();
+            assert_eq!(text, Conditions::from_bits(bits & 0b11010101).to_string());
+            assert_eq!((text.len(), &text[2..3], &text[4..5], &text[6..7]), (8, "-", "-", "-"));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_alternate_display_is_verbose() {
+        let conditions = Conditions::from_bits(0b01000001);
+        assert_eq!(format!("{:#}", conditions), "carry: true, aux: false, sign: false, zero: true, parity: false");
+        assert_eq!(format!("[{}]", conditions), "[.Z-.-.-C]");
+    }
 }
\ No newline at end of file
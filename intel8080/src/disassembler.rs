@@ -0,0 +1,71 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::instruction::OPCODES;
+
+/// Disassembles the instruction starting at `bytes[0]`, returning its text and size in bytes.
+///
+/// Walking a byte slice:
+///
+/// ```
+/// use intel8080::disassembler::disassemble;
+///
+/// let program = [0x21, 0x00, 0x24, 0x3E, 0x10, 0x76];
+/// let mut listing = vec![];
+/// let mut pc = 0;
+/// while pc < program.len() {
+///     let mut bytes = [0; 3];
+///     for (i, byte) in program[pc..].iter().take(3).enumerate() {
+///         bytes[i] = *byte;
+///     }
+///     let (text, size) = disassemble(bytes);
+///     listing.push(text);
+///     pc += size;
+/// }
+/// assert_eq!(listing, ["LXI H,$2400", "MVI A,$10", "HLT"]);
+/// ```
+pub fn disassemble(bytes: [u8; 3]) -> (String, usize) {
+    let instruction = OPCODES[bytes[0] as usize];
+    let mut parts = instruction.label.split('_');
+    let mnemonic = parts.next().unwrap_or("");
+    let operands: Vec<String> = parts.map(|operand| match operand {
+        "D8" => format!("${:02X}", bytes[1]),
+        "D16" | "ADR" => format!("${:04X}", (bytes[2] as u16) << 8 | bytes[1] as u16),
+        other => String::from(other),
+    }).collect();
+    if operands.is_empty() {
+        return (String::from(mnemonic), instruction.size);
+    }
+    return (format!("{} {}", mnemonic, operands.join(",")), instruction.size);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_operands() {
+        assert_eq!(disassemble([0x00, 0xFF, 0xFF]), (String::from("NOP"), 1));
+        assert_eq!(disassemble([0xC9, 0x00, 0x00]), (String::from("RET"), 1));
+    }
+
+    #[test]
+    fn test_register_operands() {
+        assert_eq!(disassemble([0x41, 0x00, 0x00]), (String::from("MOV B,C"), 1));
+        assert_eq!(disassemble([0xF5, 0x00, 0x00]), (String::from("PUSH PSW"), 1));
+        assert_eq!(disassemble([0xC7, 0x00, 0x00]), (String::from("RST 0"), 1));
+    }
+
+    #[test]
+    fn test_immediate_operands() {
+        assert_eq!(disassemble([0x21, 0x00, 0x24]), (String::from("LXI H,$2400"), 3));
+        assert_eq!(disassemble([0x36, 0x7F, 0x00]), (String::from("MVI M,$7F"), 2));
+        assert_eq!(disassemble([0xC3, 0x34, 0x12]), (String::from("JMP $1234"), 3));
+        assert_eq!(disassemble([0xD3, 0x03, 0x00]), (String::from("OUT $03"), 2));
+    }
+
+    #[test]
+    fn test_undocumented() {
+        assert_eq!(disassemble([0xCB, 0x00, 0x10]), (String::from("*JMP $1000"), 3));
+    }
+}
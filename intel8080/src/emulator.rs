@@ -1,6 +1,11 @@
-use std::fmt;
+#[cfg(feature = "std")]
+use core::fmt;
+use alloc::boxed::Box;
 use crate::conditions;
+use crate::instruction;
+use crate::instruction::Branch;
 use crate::memory::Memory;
+use crate::profile::{Profile, ReadKind};
 
 enum Register {
     A,
@@ -20,6 +25,7 @@ enum Register16 {
     SP,
 }
 
+#[cfg(feature = "std")]
 impl fmt::Display for Register16 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let val = match self {
@@ -33,6 +39,21 @@ impl fmt::Display for Register16 {
     }
 }
 
+/// An Intel 8080 clocked one cycle per [`Cpu::tick`].
+///
+/// Running a hand-assembled snippet over [`BasicMemory`](crate::memory::basic_memory::BasicMemory):
+///
+/// ```
+/// use intel8080::emulator::Cpu;
+/// use intel8080::memory::basic_memory::BasicMemory;
+///
+/// let mut cpu = Cpu::new(Box::new(BasicMemory::new()));
+/// // MVI A,$05; ADI $03; HLT
+/// cpu.load_program(0x0100, &[0x3E, 0x05, 0xC6, 0x03, 0x76]);
+/// assert_eq!(cpu.step(), 7);
+/// assert_eq!(cpu.step(), 7);
+/// assert_eq!(cpu.get_state().a, 0x08);
+/// ```
 pub struct Cpu {
     a: u8,
     b: u8,
@@ -45,16 +66,113 @@ pub struct Cpu {
     sp: u16,
     conditions: conditions::Conditions,
     interrupt_enabled: bool,
-    memory: Box<Memory>,
+    memory: Box<dyn Memory>,
     wait_cycles: usize,
+    access_penalty: usize,
+    /* Latched interrupt request; a newer request replaces one not yet acknowledged */
     interrupt_opcode: Option<u8>,
+    /* EI takes effect only after the instruction that follows it */
+    interrupt_delay: bool,
     devices: [u8; 256],
     output: Option<(u8, u8)>,
     halted: bool,
+    profile: Option<Box<Profile>>,
+    /* Address of the last opcode fetched from memory; None until the first fetch after power-on or a reset */
+    previous_pc: Option<u16>,
+    unexpected_reset: Option<UnexpectedReset>,
+    read_only_write: Option<u16>,
+    #[cfg(test)]
+    flag_clobber_hook: Option<fn(&mut conditions::Conditions)>,
+}
+
+/// Execution reached 0x0000 from somewhere other than power-on or a reset: a wild jump, `RST 0`, or running
+/// off the end of memory.
+///
+/// ```
+/// use intel8080::emulator::{Cpu, UnexpectedReset};
+/// use intel8080::memory::basic_memory::BasicMemory;
+///
+/// let mut cpu = Cpu::new(Box::new(BasicMemory::new()));
+/// // NOP; RST 0
+/// cpu.load_program(0x0000, &[0x00, 0xC7]);
+/// cpu.step();
+/// assert_eq!(cpu.take_unexpected_reset(), None);
+/// cpu.step();
+/// cpu.step();
+/// assert_eq!(cpu.take_unexpected_reset(), Some(UnexpectedReset { from: 0x0001, opcode: 0xC7 }));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnexpectedReset {
+    /// The instruction executed just before, and its opcode.
+    pub from: u16,
+    pub opcode: u8,
+}
+
+/// A copy of the programmer-visible registers, for debuggers and save states.
+///
+/// ```
+/// use intel8080::emulator::Cpu;
+/// use intel8080::memory::basic_memory::BasicMemory;
+///
+/// let mut cpu = Cpu::new(Box::new(BasicMemory::new()));
+/// let mut state = cpu.get_state();
+/// state.pc = 0x1A32;
+/// state.h = 0x24;
+/// cpu.set_state(&state);
+/// assert_eq!(cpu.get_state(), state);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CpuState {
+    pub a: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub pc: u16,
+    pub sp: u16,
+    pub flags: u8,
+    pub interrupt_enabled: bool,
+    pub halted: bool,
+    pub wait_cycles: usize,
+}
+
+/// What one [`Cpu::tick_info`] did, for frontends, debuggers and tracers that need more than the registers.
+///
+/// ```
+/// use intel8080::emulator::Cpu;
+/// use intel8080::memory::basic_memory::BasicMemory;
+///
+/// let mut cpu = Cpu::new(Box::new(BasicMemory::new()));
+/// // JMP $0010
+/// cpu.load_program(0x0000, &[0xC3, 0x10, 0x00]);
+/// let info = cpu.step_info();
+/// assert_eq!((info.opcode, info.pc_before, info.pc_after, info.cycles), (Some(0xC3), 0x0000, 0x0010, 10));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepInfo {
+    /// Cycles the instruction started on this tick takes in all, or 1 for a tick that started none.
+    pub cycles: usize,
+    pub pc_before: u16,
+    pub pc_after: u16,
+    /// The instruction started on this tick; `None` while one is still in flight or the CPU stays halted.
+    pub opcode: Option<u8>,
+    /// The opcode was supplied by an acknowledged interrupt rather than fetched.
+    pub interrupt: bool,
+    /// Halted after this tick, with nothing left in flight.
+    pub halted: bool,
+}
+
+#[cfg(any(test, feature = "paranoid"))]
+struct FlagSnapshot {
+    a: u8,
+    pc: u16,
+    conditions: conditions::Conditions,
 }
 
 impl Cpu {
-    pub fn new(memory: Box<Memory>) -> Self {
+    pub fn new(memory: Box<dyn Memory>) -> Self {
         Cpu {
             a: 0,
             b: 0,
@@ -69,39 +187,115 @@ impl Cpu {
             interrupt_enabled: false,
             memory,
             wait_cycles: 0,
+            access_penalty: 0,
             interrupt_opcode: None,
+            interrupt_delay: false,
             devices: [0; 256],
             output: None,
             halted: false,
+            profile: None,
+            previous_pc: None,
+            unexpected_reset: None,
+            read_only_write: None,
+            #[cfg(test)]
+            flag_clobber_hook: None,
         }
     }
 
+    /// Advances one cycle. See [`Cpu::tick_info`] for what happened on it.
     pub fn tick(&mut self) {
+        self.tick_info();
+    }
+
+    /// Advances one cycle, starting the next instruction if the last one has finished.
+    pub fn tick_info(&mut self) -> StepInfo {
+        let mut info = StepInfo { cycles: 1, pc_before: self.pc, pc_after: self.pc, opcode: None, interrupt: false, halted: self.halted };
         if self.wait_cycles > 0 {
             self.wait_cycles = self.wait_cycles - 1;
-            return;
+            info.halted = self.halted && self.wait_cycles == 0;
+            return info;
         }
         
-        if !self.interrupt_enabled {
-            self.interrupt_opcode = None;
-        }
+        self.access_penalty = 0;
 
         let instruction: u8;
-        match self.interrupt_opcode {
+        match self.acknowledge_interrupt() {
             Some(x) => {
                 self.halted = false;
-                self.disable_interrupts();
-                self.interrupt_opcode = None;
                 instruction = x;
+                info.interrupt = true;
             },
             None => {
                 if self.halted {
-                    return;
+                    return info;
+                }
+                let pc = self.pc;
+                if let (0, Some(from)) = (pc, self.previous_pc) {
+                    self.unexpected_reset = Some(UnexpectedReset { from, opcode: self.memory.read(from) });
                 }
-                instruction = self.fetch_byte();
+                self.previous_pc = Some(pc);
+                self.pc = self.pc.wrapping_add(1);
+                instruction = self.bus_read_as(pc, ReadKind::Opcode);
             }
         }
-        self.wait_cycles = self.dispatch(instruction);
+        #[cfg(any(test, feature = "paranoid"))]
+        let before = FlagSnapshot { a: self.a, pc: self.pc, conditions: self.conditions };
+        self.wait_cycles = self.dispatch(instruction) + self.access_penalty;
+        #[cfg(test)]
+        if let Some(hook) = self.flag_clobber_hook {
+            hook(&mut self.conditions);
+        }
+        info = StepInfo { cycles: 1 + self.wait_cycles, pc_after: self.pc, opcode: Some(instruction), halted: self.halted && self.wait_cycles == 0, ..info };
+        #[cfg(any(test, feature = "paranoid"))]
+        self.validate_flags(&info, &before);
+        return info;
+    }
+
+    /* Paranoid mode: checks the flags left by an instruction against the OPCODES metadata */
+    #[cfg(any(test, feature = "paranoid"))]
+    fn validate_flags(&self, info: &StepInfo, before: &FlagSnapshot) {
+        let opcode = match info.opcode {
+            Some(opcode) => opcode,
+            None => return,
+        };
+        let affected = instruction::OPCODES[opcode as usize].flags;
+        let unaffected = !affected;
+        assert_eq!(before.conditions.as_bits() & unaffected, self.conditions.as_bits() & unaffected,
+            "opcode {:#04x} changed flags it does not affect", opcode);
+        if affected & instruction::FLAGS_SZAP != instruction::FLAGS_SZAP || affected == instruction::FLAGS_ALL {
+            return;
+        }
+        let result = self.flag_result_source(opcode, before);
+        let mut expected = conditions::Conditions::new();
+        expected.set_from_result(result);
+        for (name, flag) in [("sign", 0b10000000), ("zero", 0b01000000), ("parity", 0b00000100)] {
+            assert_eq!(self.conditions.as_bits() & flag, expected.as_bits() & flag,
+                "opcode {:#04x} set {} inconsistently with result {:#04x}", opcode, name, result);
+        }
+    }
+
+    #[cfg(any(test, feature = "paranoid"))]
+    fn flag_result_source(&self, opcode: u8, before: &FlagSnapshot) -> u8 {
+        match opcode {
+            0xb8..=0xbf => before.a.wrapping_sub(self.register_by_code(opcode & 0x07)),
+            0xfe => before.a.wrapping_sub(self.memory.read(before.pc)),
+            _ if opcode & 0xC6 == 0x04 => self.register_by_code((opcode >> 3) & 0x07),
+            _ => self.a,
+        }
+    }
+
+    #[cfg(any(test, feature = "paranoid"))]
+    fn register_by_code(&self, code: u8) -> u8 {
+        match code {
+            0 => self.b,
+            1 => self.c,
+            2 => self.d,
+            3 => self.e,
+            4 => self.h,
+            5 => self.l,
+            6 => self.memory.read(concat_u8(self.h, self.l)),
+            _ => self.a,
+        }
     }
 
     fn dispatch(&mut self, instruction: u8) -> usize {
@@ -291,57 +485,57 @@ impl Cpu {
             0xbd => self.cmp(Register::L),
             0xbe => self.cmpm(),
             0xbf => self.cmp(Register::A),
-            0xc0 => self.ret_conditional(conditions::ConditionName::Zero, false),
+            0xc0 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Zero, false)),
             0xc1 => self.pop(Register16::BC),
-            0xc2 => self.jmp_conditional(conditions::ConditionName::Zero, false),
+            0xc2 => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Zero, false)),
             0xc3 | 0xcB => self.jmp(),
-            0xc4 => self.call_conditional(conditions::ConditionName::Zero, false),
+            0xc4 => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Zero, false)),
             0xc5 => self.push(Register16::BC),
             0xc6 => self.adi(),
             0xc7 | 0xcf | 0xd7 | 0xdf | 0xe7 | 0xef | 0xf7 | 0xff => self.rst(instruction),
-            0xc8 => self.ret_conditional(conditions::ConditionName::Zero, true),
+            0xc8 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Zero, true)),
             0xc9 | 0xd9 => self.ret(),
-            0xca => self.jmp_conditional(conditions::ConditionName::Zero, true),
-            0xcc => self.call_conditional(conditions::ConditionName::Zero, true),
+            0xca => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Zero, true)),
+            0xcc => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Zero, true)),
             0xcd | 0xdd | 0xed | 0xfd => self.call(),
             0xce => self.aci(),
-            0xd0 => self.ret_conditional(conditions::ConditionName::Carry, false),
+            0xd0 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Carry, false)),
             0xd1 => self.pop(Register16::DE),
-            0xd2 => self.jmp_conditional(conditions::ConditionName::Carry, false),
+            0xd2 => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Carry, false)),
             0xd3 => self.device_out(),
-            0xd4 => self.call_conditional(conditions::ConditionName::Carry, false),
+            0xd4 => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Carry, false)),
             0xd5 => self.push(Register16::DE),
             0xd6 => self.sui(),
-            0xd8 => self.ret_conditional(conditions::ConditionName::Carry, true),
-            0xda => self.jmp_conditional(conditions::ConditionName::Carry, true),
+            0xd8 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Carry, true)),
+            0xda => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Carry, true)),
             0xdb => self.device_in(),
-            0xdc => self.call_conditional(conditions::ConditionName::Carry, true),
+            0xdc => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Carry, true)),
             0xde => self.sbi(),
-            0xe0 => self.ret_conditional(conditions::ConditionName::Parity, false),
+            0xe0 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Parity, false)),
             0xe1 => self.pop(Register16::HL),
-            0xe2 => self.jmp_conditional(conditions::ConditionName::Parity, false),
+            0xe2 => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Parity, false)),
             0xe3 => self.xthl(),
-            0xe4 => self.call_conditional(conditions::ConditionName::Parity, false),
+            0xe4 => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Parity, false)),
             0xe5 => self.push(Register16::HL),
             0xe6 => self.ani(),
-            0xe8 => self.ret_conditional(conditions::ConditionName::Parity, true),
+            0xe8 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Parity, true)),
             0xe9 => self.pchl(),
-            0xea => self.jmp_conditional(conditions::ConditionName::Parity, true),
+            0xea => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Parity, true)),
             0xeb => self.xchg(),
-            0xec => self.call_conditional(conditions::ConditionName::Parity, true),
+            0xec => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Parity, true)),
             0xee => self.xri(),
-            0xf0 => self.ret_conditional(conditions::ConditionName::Sign, false),
+            0xf0 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Sign, false)),
             0xf1 => self.pop(Register16::PSW),
-            0xf2 => self.jmp_conditional(conditions::ConditionName::Sign, false),
+            0xf2 => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Sign, false)),
             0xf3 => self.di(),
-            0xf4 => self.call_conditional(conditions::ConditionName::Sign, false),
+            0xf4 => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Sign, false)),
             0xf5 => self.push(Register16::PSW),
             0xf6 => self.ori(),
-            0xf8 => self.ret_conditional(conditions::ConditionName::Sign, true),
+            0xf8 => branch_wait(instruction, self.ret_conditional(conditions::ConditionName::Sign, true)),
             0xf9 => self.sphl(),
-            0xfa => self.jmp_conditional(conditions::ConditionName::Sign, true),
+            0xfa => branch_wait(instruction, self.jmp_conditional(conditions::ConditionName::Sign, true)),
             0xfb => self.ei(),
-            0xfc => self.call_conditional(conditions::ConditionName::Sign, true),
+            0xfc => branch_wait(instruction, self.call_conditional(conditions::ConditionName::Sign, true)),
             0xfe => self.cpi(),
         }
     }
@@ -361,14 +555,14 @@ impl Cpu {
     /* Length: 1, Cycles: 7, Flags: None*/
     fn stax(&mut self, register: Register16) -> usize {
         let addr = self.get_two_byte_register(&register);
-        self.memory.write(addr, self.a);
+        self.bus_write(addr, self.a);
         return 6; // 7 - 1
     }
 
     /* Length: 3, Cycles: 13, Flags: None*/
     fn sta(&mut self) -> usize {
         let immediate = self.fetch_two_bytes();
-        self.memory.write(immediate, self.a);
+        self.bus_write(immediate, self.a);
         return 12; // 13 - 1
     }
 
@@ -389,10 +583,7 @@ impl Cpu {
 
     /* Length 1, Cycles: 10, Flags SZAP */
     fn inrm(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
-        let result = self.add_sub_8bit(value, 1);
-        self.memory.write(addr, result as u8);
+        self.modify_hl(|cpu, value| cpu.add_sub_8bit(value, 1) as u8);
         return 9; // 10 - 1
     }
 
@@ -406,10 +597,7 @@ impl Cpu {
 
     /* Length 1, Cycles: 10, Flags: SZAP */
     fn dcrm(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
-        let result = self.add_sub_8bit(value, (1 as u8).wrapping_neg());
-        self.memory.write(addr, result as u8);
+        self.modify_hl(|cpu, value| cpu.add_sub_8bit(value, (1 as u8).wrapping_neg()) as u8);
         return 9; // 10 - 1
     }
 
@@ -423,8 +611,7 @@ impl Cpu {
     /* Length: 2, Cycles: 10, Flags: None */
     fn mvim(&mut self) -> usize {
         let value = self.fetch_byte();
-        let addr = self.get_two_byte_register(&Register16::HL);
-        self.memory.write(addr, value);
+        self.write_hl(value);
         return 9; // 10 - 1
     }
 
@@ -483,14 +670,14 @@ impl Cpu {
     /* Length: 1, Cycles: 7, Flags: None */
     fn ldax(&mut self, register: Register16) -> usize {
         let addr = self.get_two_byte_register(&register);
-        self.a = self.memory.read(addr);
+        self.a = self.bus_read(addr);
         return 6; // 7 - 1
     }
 
     /* Length: 3, Cycles: 13, Flags: None */
     fn lda(&mut self) -> usize {
         let immediate = self.fetch_two_bytes();
-        self.a = self.memory.read(immediate);
+        self.a = self.bus_read(immediate);
         return 12; // 13 - 1
     }
 
@@ -504,16 +691,16 @@ impl Cpu {
     /* Length: 3, Cycles: 16, Flags: None */
     fn shld(&mut self) -> usize {
         let immediate = self.fetch_two_bytes();
-        self.memory.write(immediate, self.l);
-        self.memory.write(immediate + 1, self.h);
+        self.bus_write(immediate, self.l);
+        self.bus_write(immediate + 1, self.h);
         return 15; // 16 - 1
     }
 
     /* Length: 3, Cycles: 16, Flags: None */
     fn lhld(&mut self) -> usize {
         let immediate = self.fetch_two_bytes();
-        self.l = self.memory.read(immediate);
-        self.h = self.memory.read(immediate + 1);
+        self.l = self.bus_read(immediate);
+        self.h = self.bus_read(immediate + 1);
         return 15; // 16 - 1
     }
 
@@ -544,17 +731,15 @@ impl Cpu {
 
     /* Length: 1, Cycles: 7, Flags: None */ 
     fn movm_load(&mut self, register: Register) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         self.set_one_byte_register(value, &register);
         return 6; // 7 - 1
     }
 
     /* Length: 1, Cycles: 7, Flags: None */ 
     fn movm(&mut self, register: Register) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
         let value = self.get_one_byte_register(&register);
-        self.memory.write(addr, value);
+        self.write_hl(value);
         return 6; // 7 - 1
     }
 
@@ -569,8 +754,7 @@ impl Cpu {
 
     /* Length 1, Cycles: 7, Flags: SZAPC */
     fn addm(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         let result = self.add_sub_8bit(self.a, value);
         self.conditions.set(conditions::ConditionName::Carry, result > u8::MAX.into());
         self.a = result as u8;
@@ -593,8 +777,7 @@ impl Cpu {
 
     /* Length: 1, Cycles: 7, Flags: SZAPC */
     fn adcm(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         let carry = if self.conditions.get(conditions::ConditionName::Carry) {
             1
         } else {
@@ -617,8 +800,7 @@ impl Cpu {
 
     /* Length: 1, Cycles: 7, Flags: SZAPC */
     fn subm(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         let result = self.add_sub_8bit(self.a, value.wrapping_neg());
         self.conditions.set(conditions::ConditionName::Carry, self.a < value);
         self.a = result as u8;
@@ -641,8 +823,7 @@ impl Cpu {
 
     /* Length: 1, Cycles: 7, Flags: SZAPC */
     fn sbbm(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         let mut carry: u8 = 0;
         if self.conditions.get(conditions::ConditionName::Carry) {
             carry = 1;
@@ -657,22 +838,15 @@ impl Cpu {
     fn ana(&mut self, register: Register) -> usize {
         let value = self.get_one_byte_register(&register);
         self.a = self.a & value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 3; // 4 - 1
     }
 
     /* Length: 1, Cycles: 7, Flags: SZAPC */
     fn anam(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         self.a = self.a & value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 6; // 7 - 1
     }
 
@@ -680,22 +854,15 @@ impl Cpu {
     fn xra(&mut self, register: Register) -> usize {
         let value = self.get_one_byte_register(&register);
         self.a = self.a ^ value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 3; // 4 - 1
     }
 
     /* Length: 1, Cycles: 7, Flags: SZAPC */
     fn xram(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         self.a = self.a ^ value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 6; // 7 - 1
     }
 
@@ -703,22 +870,15 @@ impl Cpu {
     fn ora(&mut self, register: Register) -> usize {
         let value = self.get_one_byte_register(&register);
         self.a = self.a | value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 3; // 4 - 1
     }
     
     /* Length: 1, Cycles: 7, Flags: SZAPC */
     fn oram(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         self.a = self.a | value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 6; // 7 - 1
     }
 
@@ -732,8 +892,7 @@ impl Cpu {
     
     /* Length: 1, Cycles: 7, Flags: SZAPC */
     fn cmpm(&mut self) -> usize {
-        let addr = self.get_two_byte_register(&Register16::HL);
-        let value = self.memory.read(addr);
+        let value = self.read_hl();
         let _result = self.add_sub_8bit(self.a, value.wrapping_neg());
         self.conditions.set(conditions::ConditionName::Carry, self.a < value);
         return 6; // 7 - 1
@@ -741,8 +900,8 @@ impl Cpu {
 
     /* Length: 1, Cycles: 10, Flags: None */
     fn pop(&mut self, register: Register16) -> usize {
-        let lsb = self.memory.read(self.sp);
-        let msb = self.memory.read(self.sp + 1);
+        let lsb = self.bus_read(self.sp);
+        let msb = self.bus_read(self.sp + 1);
         match register {
             Register16::BC => {
                 self.c = lsb;
@@ -770,20 +929,20 @@ impl Cpu {
     fn push(&mut self, register: Register16) -> usize {
         match register {
             Register16::BC => {
-                self.memory.write(self.sp - 2, self.c);
-                self.memory.write(self.sp - 1, self.b);
+                self.bus_write(self.sp - 2, self.c);
+                self.bus_write(self.sp - 1, self.b);
             },
             Register16::DE => {
-                self.memory.write(self.sp - 2, self.e);
-                self.memory.write(self.sp - 1, self.d);
+                self.bus_write(self.sp - 2, self.e);
+                self.bus_write(self.sp - 1, self.d);
             },
             Register16::HL => {
-                self.memory.write(self.sp - 2, self.l);
-                self.memory.write(self.sp - 1, self.h);
+                self.bus_write(self.sp - 2, self.l);
+                self.bus_write(self.sp - 1, self.h);
             },
             Register16::PSW => {
-                self.memory.write(self.sp - 2, self.conditions.as_bits());
-                self.memory.write(self.sp - 1, self.a);
+                self.bus_write(self.sp - 2, self.conditions.as_bits());
+                self.bus_write(self.sp - 1, self.a);
             },
             _ => panic!("Invalid PUSH register, exiting.")
         }
@@ -793,8 +952,8 @@ impl Cpu {
 
     fn rst(&mut self, opcode: u8) -> usize {
         let destination = ((opcode & 0b00111000)) as u16;
-        self.memory.write(self.sp - 1, (self.pc >> 8) as u8);
-        self.memory.write(self.sp - 2, (self.pc & 0xFF) as u8);
+        self.bus_write(self.sp - 1, (self.pc >> 8) as u8);
+        self.bus_write(self.sp - 2, (self.pc & 0xFF) as u8);
         self.sp = self.sp - 2;
         self.pc = destination;
         return 10; // 11 - 1
@@ -803,42 +962,42 @@ impl Cpu {
     /* Length: 3, Cycles: 17, Flags: None */
     fn call(&mut self) -> usize {
         let immediate = self.fetch_two_bytes();
-        self.memory.write(self.sp - 1, (self.pc >> 8) as u8);
-        self.memory.write(self.sp - 2, (self.pc & 0xFF) as u8);
+        self.bus_write(self.sp - 1, (self.pc >> 8) as u8);
+        self.bus_write(self.sp - 2, (self.pc & 0xFF) as u8);
         self.sp = self.sp - 2;
         self.pc = immediate;
         return 16; // 17 - 1
     }
 
-    fn call_conditional(&mut self, condition: conditions::ConditionName, value: bool) -> usize {
+    /* Length: 3, Cycles: 17/11, Flags: None */
+    fn call_conditional(&mut self, condition: conditions::ConditionName, value: bool) -> Branch {
         if self.conditions.get(condition) == value {
             let immediate = self.fetch_two_bytes();
-            self.memory.write(self.sp - 1, (self.pc >> 8) as u8);
-            self.memory.write(self.sp - 2, (self.pc & 0xFF) as u8);
+            self.bus_write(self.sp - 1, (self.pc >> 8) as u8);
+            self.bus_write(self.sp - 2, (self.pc & 0xFF) as u8);
             self.sp = self.sp - 2;
             self.pc = immediate;
-            return 16; // 17 - 1
-        } else {
-            self.pc = self.pc + 2;
-            return 10; // 11 - 1
+            return Branch::Taken;
         }
+        self.pc = self.pc + 2;
+        return Branch::NotTaken;
     }
 
     /* Length: 1, Cycles: 10, Flags: None */
     fn ret(&mut self) -> usize {
-        self.pc = concat_u8(self.memory.read(self.sp + 1), self.memory.read(self.sp));
+        self.pc = concat_u8(self.bus_read(self.sp + 1), self.bus_read(self.sp));
         self.sp = self.sp + 2;
         return 9; // 10 - 1
     }
 
     /* Length: 1, Cycles: 11/5, Flags: None */
-    fn ret_conditional(&mut self, condition: conditions::ConditionName, value: bool) -> usize {
+    fn ret_conditional(&mut self, condition: conditions::ConditionName, value: bool) -> Branch {
         if self.conditions.get(condition) == value {
-            self.pc = concat_u8(self.memory.read(self.sp + 1), self.memory.read(self.sp));
+            self.pc = concat_u8(self.bus_read(self.sp + 1), self.bus_read(self.sp));
             self.sp = self.sp + 2;
-            return 10; // 11 - 1
+            return Branch::Taken;
         }
-        return 4; // 5 - 1
+        return Branch::NotTaken;
     }
 
     /* Length: 3, Cycles: 10, Flags: None */
@@ -847,21 +1006,24 @@ impl Cpu {
         return 9; // 10 - 1
     }
 
-    fn jmp_conditional(&mut self, condition: conditions::ConditionName, value: bool) -> usize {
+    /* Length: 3, Cycles: 10, Flags: None */
+    fn jmp_conditional(&mut self, condition: conditions::ConditionName, value: bool) -> Branch {
         if self.conditions.get(condition) == value {
             self.pc = self.fetch_two_bytes();
-        } else {
-            self.pc = self.pc + 2;
+            return Branch::Taken;
         }
-        return 9; // 10 - 1
+        self.pc = self.pc + 2;
+        return Branch::NotTaken;
     }
 
-    /* Length: 1, Cycles: 18, Flags: None */
+    /* Length: 1, Cycles: 18, Flags: None
+       The datasheet's M-cycles after the fetch: read (SP), read (SP+1), write H to (SP+1), write L to (SP) */
     fn xthl(&mut self) -> usize {
-        let lval = self.memory.read(self.sp);
-        let hval = self.memory.read(self.sp + 1);
-        self.memory.write(self.sp, self.l);
-        self.memory.write(self.sp + 1, self.h);
+        let high_addr = self.sp.wrapping_add(1);
+        let lval = self.bus_read(self.sp);
+        let hval = self.bus_read(high_addr);
+        self.bus_write(high_addr, self.h);
+        self.bus_write(self.sp, self.l);
         self.l = lval;
         self.h = hval;
         return 17; // 18 - 1
@@ -907,6 +1069,7 @@ impl Cpu {
     /* Length: 1, Cycles: 4, Flags: None */
     fn ei(&mut self) -> usize {
         self.enable_interrupts();
+        self.interrupt_delay = true;
         return 3;
     }
 
@@ -927,9 +1090,7 @@ impl Cpu {
         let value = self.fetch_byte();
         let result = (self.a as u16) + (value as u16);
         let lsb = result as u8;
-        self.conditions.set(conditions::ConditionName::Zero, lsb == 0);
-        self.conditions.set(conditions::ConditionName::Sign, lsb >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, lsb.count_ones() % 2 == 0);
+        self.conditions.set_from_result(lsb);
         self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_add(self.a, value));
         self.conditions.set(conditions::ConditionName::Carry, result > 0xFF);
         self.a = lsb;
@@ -946,9 +1107,7 @@ impl Cpu {
         let value = self.fetch_byte();
         let result = (self.a as u16) + ((value + carry) as u16);
         let lsb = result as u8;
-        self.conditions.set(conditions::ConditionName::Zero, lsb == 0);
-        self.conditions.set(conditions::ConditionName::Sign, lsb >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, lsb.count_ones() % 2 == 0);
+        self.conditions.set_from_result(lsb);
         self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_add(self.a, value));
         self.conditions.set(conditions::ConditionName::Carry, result > 0xFF);
         self.a = lsb;
@@ -960,9 +1119,7 @@ impl Cpu {
         let value = self.fetch_byte();
         let result = (self.a as u16) + (value.wrapping_neg() as u16);
         let lsb = result as u8;
-        self.conditions.set(conditions::ConditionName::Zero, lsb == 0);
-        self.conditions.set(conditions::ConditionName::Sign, lsb >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, lsb.count_ones() % 2 == 0);
+        self.conditions.set_from_result(lsb);
         self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_sub(self.a, value));
         self.conditions.set(conditions::ConditionName::Carry, self.a < value);
         self.a = lsb;
@@ -978,9 +1135,7 @@ impl Cpu {
         let value = self.fetch_byte();
         let result = (self.a as u16) + (value.wrapping_neg() as u16 + carry.wrapping_neg() as u16);
         let lsb = result as u8;
-        self.conditions.set(conditions::ConditionName::Zero, lsb == 0);
-        self.conditions.set(conditions::ConditionName::Sign, lsb >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, lsb.count_ones() % 2 == 0);
+        self.conditions.set_from_result(lsb);
         self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_sub(self.a, value));
         self.conditions.set(conditions::ConditionName::Carry, self.a < value);
         self.a = lsb;
@@ -991,10 +1146,7 @@ impl Cpu {
     fn ani(&mut self) -> usize {
         let value = self.fetch_byte();
         self.a = self.a & value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 6; // 7 - 1
     }
 
@@ -1002,10 +1154,7 @@ impl Cpu {
     fn xri(&mut self) -> usize {
         let value = self.fetch_byte();
         self.a = self.a ^ value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 6; // 7 - 1
     }
 
@@ -1013,10 +1162,7 @@ impl Cpu {
     fn ori(&mut self) -> usize {
         let value = self.fetch_byte();
         self.a = self.a | value;
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
-        self.conditions.set(conditions::ConditionName::Carry, false);
+        self.set_logic_flags();
         return 6; // 7 - 1
     }
 
@@ -1025,9 +1171,7 @@ impl Cpu {
         let value = self.fetch_byte();
         let result = (self.a as u16) + (value.wrapping_neg() as u16);
         let lsb = result as u8;
-        self.conditions.set(conditions::ConditionName::Zero, lsb == 0);
-        self.conditions.set(conditions::ConditionName::Sign, lsb >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, lsb.count_ones() % 2 == 0);
+        self.conditions.set_from_result(lsb);
         self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_sub(self.a, value));
         self.conditions.set(conditions::ConditionName::Carry, self.a < value);
         return 6; // 7 - 1
@@ -1035,19 +1179,20 @@ impl Cpu {
 
     /* Length: 1, Cycles: 4, Flags: SZAPC */
     fn daa(&mut self) -> usize {
+        /* Both corrections are decided from the original accumulator; the carry is only ever set, never cleared */
+        let mut correction = 0;
+        let mut carry = self.conditions.get(conditions::ConditionName::Carry);
         if self.a & 0x0F > 9 || self.conditions.get(conditions::ConditionName::Auxillary) {
-            self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_add(self.a, 6));
-            self.a = self.a + 6;
+            correction |= 0x06;
         }
-        if (self.a & 0xF0) >> 4 > 9 || self.conditions.get(conditions::ConditionName::Carry) {
-            let mut upper_nibble = (self.a & 0xF0) >> 4;
-            upper_nibble += 6;
-            self.conditions.set(conditions::ConditionName::Carry, upper_nibble > 0xF);
-            self.a = (upper_nibble << 4) | (self.a & 0x0F);
+        if self.a > 0x99 || carry {
+            correction |= 0x60;
+            carry = true;
         }
-        self.conditions.set(conditions::ConditionName::Zero, self.a == 0);
-        self.conditions.set(conditions::ConditionName::Sign, self.a >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, self.a.count_ones() % 2 == 0);
+        self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_add(self.a, correction));
+        self.conditions.set(conditions::ConditionName::Carry, carry);
+        self.a = self.a.wrapping_add(correction);
+        self.conditions.set_from_result(self.a);
         return 3; // 4 - 1
     }
 
@@ -1059,15 +1204,69 @@ impl Cpu {
         self.interrupt_enabled = false;
     }
 
+    /* The enable check and the consume happen together at the instruction boundary, so a request latched while
+       interrupts are off is dropped rather than run once some later path looks at the latch */
+    fn acknowledge_interrupt(&mut self) -> Option<u8> {
+        if self.interrupt_delay {
+            self.interrupt_delay = false;
+            return None;
+        }
+        let request = self.interrupt_opcode.take()?;
+        if !self.interrupt_enabled {
+            return None;
+        }
+        self.disable_interrupts();
+        return Some(request);
+    }
+
+    fn bus_read(&mut self, addr: u16) -> u8 {
+        return self.bus_read_as(addr, ReadKind::Data);
+    }
+
+    fn bus_read_as(&mut self, addr: u16, kind: ReadKind) -> u8 {
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record_read(addr, kind);
+        }
+        self.access_penalty += self.memory.access_cycles(addr, false) as usize;
+        return self.memory.read(addr);
+    }
+
+    fn bus_write(&mut self, addr: u16, data: u8) {
+        self.access_penalty += self.memory.access_cycles(addr, true) as usize;
+        if self.memory.read_only(addr) {
+            self.read_only_write = Some(addr);
+        }
+        self.memory.write(addr, data);
+    }
+
+    /* The M operand: memory at HL, through the bus like any other access */
+    fn read_hl(&mut self) -> u8 {
+        let addr = self.get_two_byte_register(&Register16::HL);
+        return self.bus_read(addr);
+    }
+
+    fn write_hl(&mut self, value: u8) {
+        let addr = self.get_two_byte_register(&Register16::HL);
+        self.bus_write(addr, value);
+    }
+
+    /* One read and one write of the same address; f may set flags */
+    fn modify_hl(&mut self, f: impl FnOnce(&mut Self, u8) -> u8) {
+        let addr = self.get_two_byte_register(&Register16::HL);
+        let value = self.bus_read(addr);
+        let result = f(self, value);
+        self.bus_write(addr, result);
+    }
+
     fn fetch_byte(&mut self) -> u8 {
         let pc = self.pc;
         self.pc = self.pc + 1;
-        return self.memory.read(pc);
+        return self.bus_read_as(pc, ReadKind::Operand);
     }
 
     fn fetch_two_bytes(&mut self) -> u16 {
-        let lsb = self.memory.read(self.pc);
-        let msb = self.memory.read(self.pc + 1);
+        let lsb = self.bus_read_as(self.pc, ReadKind::Operand);
+        let msb = self.bus_read_as(self.pc + 1, ReadKind::Operand);
         self.pc = self.pc + 2;
         return concat_u8(msb, lsb);
     }
@@ -1128,16 +1327,46 @@ impl Cpu {
         }
     }
 
+    /* AND, XOR and OR: S, Z and P from the accumulator, carry cleared */
+    fn set_logic_flags(&mut self) {
+        self.conditions.set_from_result(self.a);
+        self.conditions.set(conditions::ConditionName::Carry, false);
+    }
+
     fn add_sub_8bit(&mut self, v1: u8, v2: u8) -> u16 {
         let result = (v1 as u16) + (v2 as u16);
         let lsb = result as u8;
-        self.conditions.set(conditions::ConditionName::Zero, lsb == 0);
-        self.conditions.set(conditions::ConditionName::Sign, lsb >= 0x80);
-        self.conditions.set(conditions::ConditionName::Parity, lsb.count_ones() % 2 == 0);
+        self.conditions.set_from_result(lsb);
         self.conditions.set(conditions::ConditionName::Auxillary, check_half_carry_add(v1, v2));
         return result;
     }
 
+    /// Copies `program` into memory at `address` and jumps there.
+    pub fn load_program(&mut self, address: u16, program: &[u8]) {
+        for (offset, byte) in program.iter().enumerate() {
+            self.memory.write(address.wrapping_add(offset as u16), *byte);
+        }
+        self.pc = address;
+        self.previous_pc = None;
+    }
+
+    /// Runs one whole instruction, returning the cycles it took.
+    pub fn step(&mut self) -> usize {
+        return self.step_info().cycles;
+    }
+
+    /// Finishes any instruction in flight, then runs exactly one more to completion (or one halted cycle).
+    pub fn step_info(&mut self) -> StepInfo {
+        while self.wait_cycles > 0 {
+            self.tick();
+        }
+        let info = self.tick_info();
+        while self.wait_cycles > 0 {
+            self.tick();
+        }
+        return info;
+    }
+
     pub fn receive_interrupt(&mut self, interrupt: u8) {
         self.interrupt_opcode = Some(interrupt);
     }
@@ -1159,11 +1388,129 @@ impl Cpu {
         }
         return vram;    
     }
+
+    pub fn get_state(&self) -> CpuState {
+        CpuState {
+            a: self.a,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            h: self.h,
+            l: self.l,
+            pc: self.pc,
+            sp: self.sp,
+            flags: self.conditions.as_bits(),
+            interrupt_enabled: self.interrupt_enabled,
+            halted: self.halted,
+            wait_cycles: self.wait_cycles,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &CpuState) {
+        self.a = state.a;
+        self.b = state.b;
+        self.c = state.c;
+        self.d = state.d;
+        self.e = state.e;
+        self.h = state.h;
+        self.l = state.l;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.conditions.restore_from_bits(state.flags);
+        self.interrupt_enabled = state.interrupt_enabled;
+        self.halted = state.halted;
+        self.wait_cycles = state.wait_cycles;
+        self.interrupt_opcode = None;
+        self.interrupt_delay = false;
+        self.output = None;
+        self.previous_pc = None;
+    }
+
+    /// The program counter, without copying the rest of [`CpuState`].
+    pub fn pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    /// The stack pointer, without copying the rest of [`CpuState`].
+    pub fn sp(&self) -> u16 {
+        return self.sp;
+    }
+
+    /// Whether a HLT is waiting for an interrupt, without copying the rest of [`CpuState`].
+    pub fn halted(&self) -> bool {
+        return self.halted;
+    }
+
+    /// Whether an interrupt request would be acknowledged, without copying the rest of [`CpuState`].
+    pub fn interrupts_enabled(&self) -> bool {
+        return self.interrupt_enabled;
+    }
+
+    /// Whether the next [`Cpu::tick`] starts a new instruction (or takes an interrupt) rather than finishing
+    /// one, and the CPU isn't halted.
+    pub fn at_instruction_boundary(&self) -> bool {
+        return self.wait_cycles == 0 && !self.halted;
+    }
+
+    /// Whether an interrupt request is latched and not yet acknowledged.
+    pub fn interrupt_pending(&self) -> bool {
+        return self.interrupt_opcode.is_some();
+    }
+
+    /// The most recent [`UnexpectedReset`] since the last call, if any.
+    pub fn take_unexpected_reset(&mut self) -> Option<UnexpectedReset> {
+        return self.unexpected_reset.take();
+    }
+
+    /// The address of the most recent write the program made to [read-only](Memory::read_only) memory since
+    /// the last call, if any. Writes through [`Cpu::write_memory`] aren't the program's and don't count.
+    pub fn take_read_only_write(&mut self) -> Option<u16> {
+        return self.read_only_write.take();
+    }
+
+    /* Starts counting executions and operand/data reads per address; off by default as it costs a check per read */
+    pub fn enable_profiling(&mut self) {
+        if self.profile.is_none() {
+            self.profile = Some(Box::new(Profile::new()));
+        }
+    }
+
+    pub fn disable_profiling(&mut self) -> Option<Profile> {
+        return self.profile.take().map(|profile| *profile);
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        return self.profile.as_deref();
+    }
+
+    pub fn read_memory(&self, addr: u16) -> u8 {
+        return self.memory.read(addr);
+    }
+
+    pub fn write_memory(&mut self, addr: u16, data: u8) {
+        self.memory.write(addr, data);
+    }
+
+    pub fn get_ram(&self) -> [u8; 1_024] {
+        let mut ram: [u8; 1_024] = [0; 1_024];
+        for i in 0..1_024 {
+            ram[i] = self.memory.read((0x2000 + i) as u16);
+        }
+        return ram;
+    }
 }
 
+#[cfg(feature = "std")]
 impl fmt::Display for Cpu {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\ta: {}\n\tb: {}\n\tc: {}\n\td: {}\n\te: {}\n\th: {}\n\tl: {}\n\tconditions: {}\n\tpc: {}\n\tsp: {}\n\tmemory[0]: {}\n", self.a, self.b, self.c, self.d, self.e, self.h, self.l, self.conditions, self.pc, self.sp, self.memory.read(0))
+        write!(f, "\ta: {}\n\tb: {}\n\tc: {}\n\td: {}\n\te: {}\n\th: {}\n\tl: {}\n", self.a, self.b, self.c, self.d, self.e, self.h, self.l)?;
+        if f.alternate() {
+            write!(f, "\tconditions: {:#}\n", self.conditions)?;
+        } else {
+            write!(f, "\tconditions: {}\n", self.conditions)?;
+        }
+        write!(f, "\tpc: {}\n\tsp: {}\n\tmemory[0]: {}\n", self.pc, self.sp, self.memory.read(0))
     }
 }
 
@@ -1183,6 +1530,11 @@ fn check_half_carry_sub(v1: u8, v2: u8) -> bool {
     return result == 0x10;
 }
 
+/* Conditional instructions charge the OPCODES timing for the path taken, less the fetch tick */
+fn branch_wait(opcode: u8, branch: Branch) -> usize {
+    return instruction::OPCODES[opcode as usize].cycles_for(branch) - 1;
+}
+
 fn concat_u8(high: u8, low: u8) -> u16 {
     return ((high as u16) << 8) | low as u16;
 }
@@ -1196,6 +1548,7 @@ fn split_u16(val: u16) -> (u8, u8) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec::Vec;
 
     #[test]
     fn test_default_cpu() {
@@ -1237,6 +1590,31 @@ mod tests {
         assert_eq!(cpu.conditions.get(conditions::ConditionName::Auxillary), true);
     }
 
+    #[test]
+    fn test_daa_keeps_carry_and_wraps() {
+        let memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        let mut cpu = Cpu::new(memory);
+        /* $99 + $01 = $9A, which adjusts to $00 with carry */
+        cpu.a = 0x9A;
+        cpu.daa();
+        assert_eq!(cpu.a, 0x00);
+        assert_eq!(cpu.conditions.get(conditions::ConditionName::Carry), true);
+        assert_eq!(cpu.conditions.get(conditions::ConditionName::Zero), true);
+        /* $90 + $90 = $20 with carry, which adjusts to $80 and keeps the carry */
+        cpu.a = 0x20;
+        cpu.conditions.set(conditions::ConditionName::Carry, true);
+        cpu.conditions.set(conditions::ConditionName::Auxillary, false);
+        cpu.daa();
+        assert_eq!(cpu.a, 0x80);
+        assert_eq!(cpu.conditions.get(conditions::ConditionName::Carry), true);
+        /* Must not overflow the accumulator */
+        cpu.a = 0xFF;
+        cpu.conditions.set(conditions::ConditionName::Carry, false);
+        cpu.daa();
+        assert_eq!(cpu.a, 0x65);
+        assert_eq!(cpu.conditions.get(conditions::ConditionName::Carry), true);
+    }
+
     #[test]
     fn test_lxi_bc() {
         let mut memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
@@ -3206,8 +3584,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Zero, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Zero, false);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Zero, false);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3219,8 +3597,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Zero, false);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Zero, false);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
     }
@@ -3233,8 +3611,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Zero, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Zero, true);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Zero, true);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
 
@@ -3247,8 +3625,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Zero, true);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Zero, true);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3261,8 +3639,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Carry, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Carry, false);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Carry, false);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3274,8 +3652,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Carry, false);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Carry, false);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
     }
@@ -3288,8 +3666,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Carry, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Carry, true);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Carry, true);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
     }
@@ -3301,8 +3679,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Carry, true);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Carry, true);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3315,8 +3693,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Parity, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Parity, false);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Parity, false);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3328,8 +3706,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Parity, false);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Parity, false);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
     }
@@ -3342,8 +3720,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Parity, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Parity, true);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Parity, true);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
     }
@@ -3355,8 +3733,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Parity, true);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Parity, true);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3369,8 +3747,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Sign, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Sign, false);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Sign, false);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3382,8 +3760,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Sign, false);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Sign, false);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
     }
@@ -3396,8 +3774,8 @@ mod tests {
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
         cpu.conditions.set(crate::conditions::ConditionName::Sign, true);
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Sign, true);
-        assert_eq!(wait_cycles, 10);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Sign, true);
+        assert_eq!(branch, Branch::Taken);
         assert_eq!(cpu.pc, 0x0201);
         assert_eq!(cpu.sp, 0x2400);
     }
@@ -3409,8 +3787,8 @@ mod tests {
         memory.write(0x23FF, 2);
         let mut cpu = Cpu::new(memory);
         cpu.sp = cpu.sp - 2;
-        let wait_cycles = cpu.ret_conditional(crate::conditions::ConditionName::Sign, true);
-        assert_eq!(wait_cycles, 4);
+        let branch = cpu.ret_conditional(crate::conditions::ConditionName::Sign, true);
+        assert_eq!(branch, Branch::NotTaken);
         assert_eq!(cpu.pc, 0);
         assert_eq!(cpu.sp, 0x23FE);
     }
@@ -3610,6 +3988,55 @@ mod tests {
         assert_eq!(interrupt_opcode, 0b00000001);
     }
 
+    #[test]
+    fn test_interrupt_between_ei_and_di_is_not_taken() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        // EI; DI; NOP; HLT
+        cpu.load_program(0, &[0xFB, 0xF3, 0x00, 0x76]);
+        cpu.step();
+        cpu.receive_interrupt(0xCF);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.pc, 0x0003);
+        assert_eq!(cpu.sp, 0x2400);
+        assert!(!cpu.interrupt_enabled);
+        /* Dropped, not held over until interrupts come back on */
+        cpu.interrupt_enabled = true;
+        cpu.step();
+        assert!(cpu.halted);
+        assert_eq!(cpu.sp, 0x2400);
+    }
+
+    #[test]
+    fn test_interrupt_taken_one_instruction_after_ei() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        // EI; NOP; NOP
+        cpu.load_program(0, &[0xFB, 0x00, 0x00]);
+        cpu.step();
+        cpu.receive_interrupt(0xCF);
+        cpu.step();
+        assert_eq!(cpu.pc, 0x0002);
+        assert_eq!(cpu.step(), 11);
+        assert_eq!(cpu.pc, 0x0008);
+        assert_eq!(cpu.read_memory(0x23FE), 0x02);
+        assert!(!cpu.interrupt_enabled);
+        assert_eq!(cpu.interrupt_opcode, None);
+    }
+
+    #[test]
+    fn test_interrupt_received_while_disabled_is_dropped() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        // DI; NOP; EI; NOP; NOP
+        cpu.load_program(0, &[0xF3, 0x00, 0xFB, 0x00, 0x00]);
+        cpu.step();
+        cpu.receive_interrupt(0xCF);
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert_eq!(cpu.pc, 0x0005);
+        assert_eq!(cpu.sp, 0x2400);
+    }
+
     #[test]
     fn test_disable_interrupt() {
         let memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
@@ -3628,6 +4055,358 @@ mod tests {
         assert_eq!(cpu.interrupt_enabled, true);
     }
 
+    #[test]
+    fn test_get_ram() {
+        let mut memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        memory.write(0x2000, 1);
+        memory.write(0x23FF, 2);
+        memory.write(0x2400, 3);
+        let cpu = Cpu::new(memory);
+        let ram = cpu.get_ram();
+        assert_eq!(ram[0], 1);
+        assert_eq!(ram[1_023], 2);
+    }
+
+    #[test]
+    fn test_paranoid_accepts_valid_flags() {
+        let mut memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        memory.write(0, 0x80); // ADD B
+        memory.write(1, 0x41); // MOV B,C
+        memory.write(2, 0x3d); // DCR A
+        memory.write(3, 0xfe); // CPI 0xFF
+        memory.write(4, 0xff);
+        let mut cpu = Cpu::new(memory);
+        cpu.b = 0x01;
+        for _ in 0..4 {
+            cpu.wait_cycles = 0;
+            cpu.tick();
+        }
+        assert_eq!(cpu.pc, 5);
+        assert_eq!(cpu.conditions.get(conditions::ConditionName::Carry), true);
+    }
+
+    #[test]
+    #[should_panic(expected = "changed flags it does not affect")]
+    fn test_paranoid_detects_mov_touching_flags() {
+        let mut memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        memory.write(0, 0x41); // MOV B,C
+        let mut cpu = Cpu::new(memory);
+        cpu.flag_clobber_hook = Some(|conditions| conditions.set(conditions::ConditionName::Carry, true));
+        cpu.tick();
+    }
+
+    #[test]
+    #[should_panic(expected = "set zero inconsistently")]
+    fn test_paranoid_detects_wrong_zero_flag() {
+        let mut memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        memory.write(0, 0x80); // ADD B
+        let mut cpu = Cpu::new(memory);
+        cpu.flag_clobber_hook = Some(|conditions| conditions.set(conditions::ConditionName::Zero, false));
+        cpu.tick();
+    }
+
+    #[test]
+    #[should_panic(expected = "changed flags it does not affect")]
+    fn test_paranoid_detects_inr_touching_carry() {
+        let mut memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        memory.write(0, 0x04); // INR B
+        let mut cpu = Cpu::new(memory);
+        cpu.flag_clobber_hook = Some(|conditions| conditions.set(conditions::ConditionName::Carry, true));
+        cpu.tick();
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        let mut cpu = Cpu::new(memory);
+        cpu.a = 0x12;
+        cpu.h = 0x34;
+        cpu.pc = 0x1A32;
+        cpu.conditions.set(conditions::ConditionName::Carry, true);
+        let state = cpu.get_state();
+        assert_eq!(state.flags, 0b00000011);
+        let mut restored = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        restored.set_state(&state);
+        assert_eq!(restored.get_state(), state);
+    }
+
+    struct VramPenaltyMemory {
+        inner: crate::memory::basic_memory::BasicMemory,
+    }
+
+    impl Memory for VramPenaltyMemory {
+        fn read(&self, addr: u16) -> u8 {
+            return self.inner.read(addr);
+        }
+
+        fn write(&mut self, addr: u16, data: u8) {
+            self.inner.write(addr, data);
+        }
+
+        fn access_cycles(&self, addr: u16, _is_write: bool) -> u8 {
+            if addr >= 0x2400 && addr < 0x4000 {
+                return 1;
+            }
+            return 0;
+        }
+    }
+
+    fn cycles_until_halt(mut memory: Box<dyn Memory>) -> usize {
+        // LXI H,$2400; MVI M,$AA; MOV A,M; INX H; MVI M,$BB; LDA $2000; HLT
+        let program = [0x21, 0x00, 0x24, 0x36, 0xAA, 0x7E, 0x23, 0x36, 0xBB, 0x3A, 0x00, 0x20, 0x76];
+        for (addr, byte) in program.iter().enumerate() {
+            memory.write(addr as u16, *byte);
+        }
+        let mut cpu = Cpu::new(memory);
+        let mut cycles = 0;
+        while !cpu.halted {
+            cpu.tick();
+            cycles += 1;
+        }
+        return cycles;
+    }
+
+    #[test]
+    fn test_access_cycles_stretch_instructions() {
+        let base = cycles_until_halt(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        let stretched = cycles_until_halt(Box::new(VramPenaltyMemory { inner: crate::memory::basic_memory::BasicMemory::new() }));
+        assert_eq!(base, 10 + 10 + 7 + 5 + 10 + 13 + 1);
+        assert_eq!(stretched, base + 3);
+    }
+
+    /* Logs every bus access the CPU reports through access_cycles */
+    struct AccessLogMemory {
+        inner: crate::memory::basic_memory::BasicMemory,
+        log: alloc::rc::Rc<core::cell::RefCell<Vec<(u16, bool)>>>,
+    }
+
+    impl Memory for AccessLogMemory {
+        fn read(&self, addr: u16) -> u8 {
+            return self.inner.read(addr);
+        }
+
+        fn write(&mut self, addr: u16, data: u8) {
+            self.inner.write(addr, data);
+        }
+
+        fn access_cycles(&self, addr: u16, is_write: bool) -> u8 {
+            self.log.borrow_mut().push((addr, is_write));
+            return 0;
+        }
+    }
+
+    #[test]
+    fn test_inr_m_reads_and_writes_once() {
+        let log = alloc::rc::Rc::new(core::cell::RefCell::new(Vec::new()));
+        let mut memory = AccessLogMemory { inner: crate::memory::basic_memory::BasicMemory::new(), log: log.clone() };
+        // INR M
+        memory.write(0, 0x34);
+        memory.write(0x2010, 0x7F);
+        let mut cpu = Cpu::new(Box::new(memory));
+        cpu.h = 0x20;
+        cpu.l = 0x10;
+        cpu.tick();
+        assert_eq!(*log.borrow(), [(0x0000, false), (0x2010, false), (0x2010, true)]);
+        assert_eq!(cpu.memory.read(0x2010), 0x80);
+        assert!(cpu.conditions.get(conditions::ConditionName::Sign));
+    }
+
+    #[test]
+    fn test_xthl_bus_order_and_cycles() {
+        let log = alloc::rc::Rc::new(core::cell::RefCell::new(Vec::new()));
+        let mut memory = AccessLogMemory { inner: crate::memory::basic_memory::BasicMemory::new(), log: log.clone() };
+        memory.write(0, instruction::XTHL);
+        memory.write(0x23FE, 0x0A);
+        memory.write(0x23FF, 0x0B);
+        let mut cpu = Cpu::new(Box::new(memory));
+        cpu.sp = 0x23FE;
+        cpu.h = 0x01;
+        cpu.l = 0x02;
+        let info = cpu.tick_info();
+        assert_eq!(*log.borrow(), [(0x0000, false), (0x23FE, false), (0x23FF, false), (0x23FF, true), (0x23FE, true)]);
+        assert_eq!((info.cycles, instruction::OPCODES[instruction::XTHL as usize].cycles), (18, 18));
+        assert_eq!((cpu.h, cpu.l, cpu.memory.read(0x23FF), cpu.memory.read(0x23FE)), (0x0B, 0x0A, 0x01, 0x02));
+
+        /* The stack pointer wraps like the address bus */
+        log.borrow_mut().clear();
+        cpu.sp = 0xFFFF;
+        cpu.pc = 0;
+        while !cpu.at_instruction_boundary() {
+            cpu.tick();
+        }
+        cpu.tick();
+        assert_eq!(log.borrow()[1..], [(0xFFFF, false), (0x0000, false), (0x0000, true), (0xFFFF, true)]);
+    }
+
+    /* The game's AddDelta score routine (DE = BCD delta, score at $20F8 low byte first), followed by the
+       bonus-life check that compares the score's high byte against $15 and flags a bonus at $20FA */
+    const SCORE_ROUTINE: &str = "
+        add:    LXI H,$20F8
+                MOV A,M
+                ADD E
+                DAA
+                MOV M,A
+                INX H
+                MOV A,M
+                ADC D
+                DAA
+                MOV M,A
+        check:  LDA $20F9
+                CPI $15
+                JC done
+                MVI A,$01
+                STA $20FA
+        done:   HLT
+    ";
+
+    fn add_score(cpu: &mut Cpu, delta: u16) {
+        let mut state = cpu.get_state();
+        state.pc = 0;
+        state.halted = false;
+        state.d = (delta >> 8) as u8;
+        state.e = delta as u8;
+        cpu.set_state(&state);
+        while !cpu.halted {
+            cpu.step();
+        }
+    }
+
+    fn bcd_score(cpu: &Cpu) -> u32 {
+        let (high, low) = (cpu.read_memory(0x20F9) as u32, cpu.read_memory(0x20F8) as u32);
+        return (high >> 4) * 1_000 + (high & 0xF) * 100 + (low >> 4) * 10 + (low & 0xF);
+    }
+
+    #[test]
+    fn test_score_routine_keeps_valid_bcd() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        cpu.load_program(0, &crate::asm::assemble(SCORE_ROUTINE).unwrap());
+        let mut expected = 0;
+        for _ in 0..1_000 {
+            for (delta, points) in [(0x0050, 50), (0x0100, 100), (0x0030, 30)] {
+                add_score(&mut cpu, delta);
+                /* Four BCD digits wrap from 9999 back to 0000 */
+                expected = (expected + points) % 10_000;
+                for addr in [0x20F8, 0x20F9] {
+                    let byte = cpu.read_memory(addr);
+                    assert!(byte & 0x0F <= 9 && byte >> 4 <= 9, "invalid BCD {:02X} at score {}", byte, expected);
+                }
+                assert_eq!(bcd_score(&cpu), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bonus_life_at_1500() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        cpu.load_program(0, &crate::asm::assemble(SCORE_ROUTINE).unwrap());
+        cpu.write_memory(0x20F9, 0x14);
+        cpu.write_memory(0x20F8, 0x00);
+        for _ in 0..9 {
+            add_score(&mut cpu, 0x0010);
+            assert_eq!(cpu.read_memory(0x20FA), 0, "bonus awarded early at {}", bcd_score(&cpu));
+        }
+        assert_eq!(bcd_score(&cpu), 1_490);
+        add_score(&mut cpu, 0x0010);
+        assert_eq!(bcd_score(&cpu), 1_500);
+        assert_eq!(cpu.read_memory(0x20FA), 1);
+    }
+
+    #[test]
+    fn test_profile_classifies_reads() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        // MVI B,$02; LXI H,$0010; MOV A,M; DCR B; JNZ $0005; HLT
+        cpu.load_program(0, &[0x06, 0x02, 0x21, 0x10, 0x00, 0x7E, 0x05, 0xC2, 0x05, 0x00, 0x76]);
+        assert!(cpu.profile().is_none());
+        cpu.enable_profiling();
+        while !cpu.halted {
+            cpu.step();
+        }
+        let profile = cpu.disable_profiling().unwrap();
+        assert!(cpu.profile().is_none());
+        assert_eq!(profile.executions(0x0000), 1);
+        assert_eq!(profile.executions(0x0005), 2);
+        assert_eq!(profile.executions(0x000A), 1);
+        assert!(profile.read_as_operand(0x0001) && !profile.read_as_data(0x0001));
+        assert!(profile.read_as_operand(0x0009));
+        assert!(profile.read_as_data(0x0010) && !profile.read_as_operand(0x0010));
+        assert_eq!(profile.executions(0x0010), 0);
+    }
+
+    fn cycles_for_next_instruction(cpu: &mut Cpu) -> usize {
+        cpu.tick();
+        let mut cycles = 1;
+        while cpu.wait_cycles > 0 {
+            cpu.tick();
+            cycles += 1;
+        }
+        return cycles;
+    }
+
+    fn branch_cpu(program: &[u8], zero: bool) -> Cpu {
+        let mut memory = Box::new(crate::memory::basic_memory::BasicMemory::new());
+        memory.write(0x23FE, 0x34);
+        memory.write(0x23FF, 0x12);
+        let mut cpu = Cpu::new(memory);
+        cpu.load_program(0, program);
+        cpu.sp = 0x23FE;
+        cpu.conditions.set(crate::conditions::ConditionName::Zero, zero);
+        return cpu;
+    }
+
+    #[test]
+    fn test_conditional_jump_timing() {
+        // JNZ $0010
+        let mut cpu = branch_cpu(&[0xC2, 0x10, 0x00], false);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 10);
+        assert_eq!(cpu.pc, 0x0010);
+        let mut cpu = branch_cpu(&[0xC2, 0x10, 0x00], true);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 10);
+        assert_eq!(cpu.pc, 0x0003);
+        // JZ $0010
+        let mut cpu = branch_cpu(&[0xCA, 0x10, 0x00], true);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 10);
+        assert_eq!(cpu.pc, 0x0010);
+        let mut cpu = branch_cpu(&[0xCA, 0x10, 0x00], false);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 10);
+        assert_eq!(cpu.pc, 0x0003);
+    }
+
+    #[test]
+    fn test_conditional_call_timing() {
+        // CNZ $0010
+        let mut cpu = branch_cpu(&[0xC4, 0x10, 0x00], false);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 17);
+        assert_eq!((cpu.pc, cpu.sp), (0x0010, 0x23FC));
+        let mut cpu = branch_cpu(&[0xC4, 0x10, 0x00], true);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 11);
+        assert_eq!((cpu.pc, cpu.sp), (0x0003, 0x23FE));
+        // CZ $0010
+        let mut cpu = branch_cpu(&[0xCC, 0x10, 0x00], true);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 17);
+        assert_eq!((cpu.pc, cpu.sp), (0x0010, 0x23FC));
+        let mut cpu = branch_cpu(&[0xCC, 0x10, 0x00], false);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 11);
+        assert_eq!((cpu.pc, cpu.sp), (0x0003, 0x23FE));
+    }
+
+    #[test]
+    fn test_conditional_return_timing() {
+        // RNZ
+        let mut cpu = branch_cpu(&[0xC0], false);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 11);
+        assert_eq!((cpu.pc, cpu.sp), (0x1234, 0x2400));
+        let mut cpu = branch_cpu(&[0xC0], true);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 5);
+        assert_eq!((cpu.pc, cpu.sp), (0x0001, 0x23FE));
+        // RZ
+        let mut cpu = branch_cpu(&[0xC8], true);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 11);
+        assert_eq!((cpu.pc, cpu.sp), (0x1234, 0x2400));
+        let mut cpu = branch_cpu(&[0xC8], false);
+        assert_eq!(cycles_for_next_instruction(&mut cpu), 5);
+        assert_eq!((cpu.pc, cpu.sp), (0x0001, 0x23FE));
+    }
+
     #[test]
     fn test_concat_u8() {
         let high = 0x0A;
@@ -3643,4 +4422,111 @@ mod tests {
         assert_eq!(high, 0x0A);
         assert_eq!(low, 0x0B);
     }
+
+    const WILD_JUMP: &str = "
+            LXI SP,$2400
+            JMP $FFF0
+    ";
+
+    #[test]
+    fn test_fall_through_nops_into_zero_is_trapped() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        cpu.load_program(0, &crate::asm::assemble(WILD_JUMP).unwrap());
+        cpu.step();
+        assert_eq!(cpu.take_unexpected_reset(), None);
+        /* JMP, then sixteen NOPs up to $FFFF before wrapping */
+        for _ in 0..17 {
+            cpu.step();
+            assert_eq!(cpu.take_unexpected_reset(), None);
+        }
+        assert_eq!(cpu.get_state().pc, 0x0000);
+        cpu.step();
+        assert_eq!(cpu.take_unexpected_reset(), Some(UnexpectedReset { from: 0xFFFF, opcode: 0x00 }));
+        assert_eq!(cpu.take_unexpected_reset(), None);
+    }
+
+    #[test]
+    fn test_reset_to_zero_is_not_trapped() {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        cpu.load_program(0, &crate::asm::assemble(WILD_JUMP).unwrap());
+        cpu.step();
+        let mut state = cpu.get_state();
+        state.pc = 0x0000;
+        cpu.set_state(&state);
+        cpu.step();
+        assert_eq!(cpu.take_unexpected_reset(), None);
+    }
+
+    #[test]
+    fn test_write_to_read_only_memory_is_reported() {
+        let mut rom = [0; 8_192];
+        let program = crate::asm::assemble("
+            LXI H,$1000
+            MVI M,$55
+            STA $2000
+            HLT
+        ").unwrap();
+        rom[..program.len()].copy_from_slice(&program);
+        let mut cpu = Cpu::new(Box::new(crate::memory::space_invaders_memory::SpaceInvadersMemory::new(rom)));
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.take_read_only_write(), Some(0x1000));
+        assert_eq!(cpu.take_read_only_write(), None);
+        cpu.step();
+        assert_eq!(cpu.take_read_only_write(), None);
+        cpu.write_memory(0x0000, 0xFF);
+        assert_eq!(cpu.take_read_only_write(), None);
+    }
+
+    fn step_program(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new(Box::new(crate::memory::basic_memory::BasicMemory::new()));
+        cpu.load_program(0, program);
+        return cpu;
+    }
+
+    #[test]
+    fn test_step_info_normal_instruction() {
+        // MVI A,$05
+        let mut cpu = step_program(&[0x3E, 0x05]);
+        let info = cpu.tick_info();
+        assert_eq!(info, StepInfo { cycles: 7, pc_before: 0x0000, pc_after: 0x0002, opcode: Some(0x3E), interrupt: false, halted: false });
+        /* The rest of its cycles start nothing */
+        let waiting = cpu.tick_info();
+        assert_eq!(waiting, StepInfo { cycles: 1, pc_before: 0x0002, pc_after: 0x0002, opcode: None, interrupt: false, halted: false });
+    }
+
+    #[test]
+    fn test_step_info_taken_branch() {
+        // ORI $01; JNZ $0010; JZ $0020
+        let mut cpu = step_program(&[0xF6, 0x01, 0xC2, 0x10, 0x00]);
+        cpu.step();
+        let info = cpu.step_info();
+        assert_eq!((info.opcode, info.pc_before, info.pc_after, info.cycles), (Some(0xC2), 0x0002, 0x0010, 10));
+    }
+
+    #[test]
+    fn test_step_info_interrupt_acknowledge() {
+        // EI; NOP; NOP
+        let mut cpu = step_program(&[0xFB, 0x00, 0x00]);
+        cpu.step();
+        cpu.step();
+        cpu.receive_interrupt(0xCF);
+        let info = cpu.step_info();
+        assert_eq!(info, StepInfo { cycles: 11, pc_before: 0x0002, pc_after: 0x0008, opcode: Some(0xCF), interrupt: true, halted: false });
+    }
+
+    #[test]
+    fn test_step_info_halted() {
+        // HLT
+        let mut cpu = step_program(&[0x76]);
+        let info = cpu.tick_info();
+        assert_eq!((info.opcode, info.halted, info.pc_after), (Some(0x76), false, 0x0001));
+        for _ in 2..info.cycles {
+            assert!(!cpu.tick_info().halted);
+        }
+        assert!(cpu.tick_info().halted);
+        let idle = cpu.step_info();
+        assert_eq!(idle, StepInfo { cycles: 1, pc_before: 0x0001, pc_after: 0x0001, opcode: None, interrupt: false, halted: true });
+        assert_eq!(cpu.step(), 1);
+    }
 }
\ No newline at end of file
@@ -1,22 +1,57 @@
-/*
-use std::fmt;
-use std::collections::HashMap;
+#[cfg(feature = "std")]
+use core::fmt;
 
+pub const FLAGS_NONE: u8 = 0b00000000;
+pub const FLAGS_C: u8 = 0b00000001;
+pub const FLAGS_SZAP: u8 = 0b11010100;
+pub const FLAGS_SZAPC: u8 = 0b11010101;
+pub const FLAGS_ALL: u8 = 0b11111111;
+
+/* Which way a conditional jump, call or return went */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Branch {
+    Taken,
+    NotTaken,
+}
+
+#[derive(Clone, Copy)]
 pub struct Instruction {
-    opcode: u8,
-    size: usize,
-    cycles: usize,
-    label: String,
+    pub opcode: u8,
+    pub size: usize,
+    /* Taken timing for conditional instructions */
+    pub cycles: usize,
+    pub cycles_not_taken: usize,
+    pub label: &'static str,
+    pub flags: u8,
 }
 
 impl Instruction {
-    pub fn new(opcode: u8, size: size, cycles: usize, label: String) {
-        Self { opcode, size, cycles, label: label.into()}
+    pub const fn new(opcode: u8, size: usize, cycles: usize, label: &'static str, flags: u8) -> Self {
+        Self { opcode, size, cycles, cycles_not_taken: cycles, label, flags }
+    }
+
+    pub const fn conditional(opcode: u8, size: usize, taken: usize, not_taken: usize, label: &'static str, flags: u8) -> Self {
+        Self { opcode, size, cycles: taken, cycles_not_taken: not_taken, label, flags }
+    }
+
+    pub fn cycles_for(&self, branch: Branch) -> usize {
+        match branch {
+            Branch::Taken => self.cycles,
+            Branch::NotTaken => self.cycles_not_taken,
+        }
+    }
+
+    pub fn affects_flags(&self) -> bool {
+        return self.flags != FLAGS_NONE;
     }
 }
 
+#[cfg(feature = "std")]
 impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.cycles_not_taken != self.cycles {
+            return write!(f, "{} {} | {}/{} cycles", self.opcode, self.label, self.cycles, self.cycles_not_taken);
+        }
         write!(f, "{} {} | {} cycles", self.opcode, self.label, self.cycles)
     }
 }
@@ -266,254 +301,297 @@ pub const CM_ADR: u8 = 0xfc;
 pub const CPI_D8: u8 = 0xfe;
 pub const RST_7: u8 = 0xff;
 
-lazy_static! {
-    static ref INSTRUCTIONS: HashMap<u8, Instruction> = {
-        let mut hm = HashMap<u8, Instruction>::new();
-        hm.insert(NOP, Instruction::new(0x00, 1, 4, "NOP"));
-        hm.insert(LXI_B_D16, Instruction::new(0x01, 3, 10, "LXI_B_D16"));
-        hm.insert(STAX_B, Instruction::new(0x02, 1, 7, "STAX_B"));
-        hm.insert(INX_B, Instruction::new(0x03, 1, 5, "INX_B"));
-        hm.insert(INR_B, Instruction::new(0x04, 1, 5, "INR_B"));
-        hm.insert(DCR_B, Instruction::new(0x05, 1, 5, "DCR_B"));
-        hm.insert(MVI_B_D8, Instruction::new(0x06, 2, 7, "MVI_B_D8"));
-        hm.insert(RLC, Instruction::new(0x07, 1, 4, "RLC"));
-        hm.insert(DAD_B, Instruction::new(0x09, 1, 10, "DAD_B"));
-        hm.insert(LDAX_B, Instruction::new(0x0a, 1, 7, "LDAX_B"));
-        hm.insert(DCX_B, Instruction::new(0x0b, 1, 5, "DCX_B"));
-        hm.insert(INR_C, Instruction::new(0x0c, 1, 5, "INR_C"));
-        hm.insert(DCR_C, Instruction::new(0x0d, 1, 5, "DCR_C"));
-        hm.insert(MVI_C_D8, Instruction::new(0x0e, 2, 7, "MVI_C_D8"));
-        hm.insert(RRC, Instruction::new(0x0f, 1, 4, "RRC"));
-        hm.insert(LXI_D_D16, Instruction::new(0x11, 3, 10, "LXI_D_D16"));
-        hm.insert(STAX_D, Instruction::new(0x12, 1, 7, "STAX_D"));
-        hm.insert(INX_D, Instruction::new(0x13, 1, 5, "INX_D"));
-        hm.insert(INR_D, Instruction::new(0x14, 1, 5, "INR_D"));
-        hm.insert(DCR_D, Instruction::new(0x15, 1, 5, "DCR_D"));
-        hm.insert(MVI_D_D8, Instruction::new(0x16, 2, 7, "MVI_D_D8"));
-        hm.insert(RAL, Instruction::new(0x17, 1, 4, "RAL"));
-        hm.insert(DAD_D, Instruction::new(0x19, 1, 10, "DAD_D"));
-        hm.insert(LDAX_D, Instruction::new(0x1a, 1, 7, "LDAX_D"));
-        hm.insert(DCX_D, Instruction::new(0x1b, 1, 5, "DCX_D"));
-        hm.insert(INR_E, Instruction::new(0x1c, 1, 5, "INR_E"));
-        hm.insert(DCR_E, Instruction::new(0x1d, 1, 5, "DCR_E"));
-        hm.insert(MVI_E_D8, Instruction::new(0x1e, 2, 7, "MVI_E_D8"));
-        hm.insert(RAR, Instruction::new(0x1f, 1, 4, "RAR"));
-        hm.insert(LXI_H_D16, Instruction::new(0x21, 3, 10, "LXI_H_D16"));
-        hm.insert(SHLD_ADR, Instruction::new(0x22, 3, 16, "SHLD_ADR"));
-        hm.insert(INX_H, Instruction::new(0x23, 1, 5, "INX_H"));
-        hm.insert(INR_H, Instruction::new(0x24, 1, 5, "INR_H"));
-        hm.insert(DCR_H, Instruction::new(0x25, 1, 5, "DCR_H"));
-        hm.insert(MVI_H_D8, Instruction::new(0x26, 2, 7, "MVI_H_D8"));
-        hm.insert(DAA, Instruction::new(0x27, 1, 4, "DAA"));
-        hm.insert(DAD_H, Instruction::new(0x29, 1, 10, "DAD_H"));
-        hm.insert(LHLD_ADR, Instruction::new(0x2a, 3, 16, "LHLD_ADR"));
-        hm.insert(DCX_H, Instruction::new(0x2b, 1, 5, "DCX_H"));
-        hm.insert(INR_L, Instruction::new(0x2c, 1, 5, "INR_L"));
-        hm.insert(DCR_L, Instruction::new(0x2d, 1, 5, "DCR_L"));
-        hm.insert(MVI_L_D8, Instruction::new(0x2e, 2, 7, "MVI_L_D8"));
-        hm.insert(CMA, Instruction::new(0x2f, 1, 4, "CMA"));
-        hm.insert(LXI_SP_D16, Instruction::new(0x31, 3, 10, "LXI_SP_D16"));
-        hm.insert(STA_ADR, Instruction::new(0x32, 3, 13, "STA_ADR"));
-        hm.insert(INX_SP, Instruction::new(0x33, 1, 5, "INX_SP"));
-        hm.insert(INR_M, Instruction::new(0x34, 1, 10, "INR_M"));
-        hm.insert(DCR_M, Instruction::new(0x35, 1, 10, "DCR_M"));
-        hm.insert(MVI_M_D8, Instruction::new(0x36, 2, 10, "MVI_M_D8"));
-        hm.insert(STC, Instruction::new(0x37, 1, 4, "STC"));
-        hm.insert(DAD_SP, Instruction::new(0x39, 1, 10, "DAD_SP"));
-        hm.insert(LDA_ADR, Instruction::new(0x3a, 3, 13, "LDA_ADR"));
-        hm.insert(DCX_SP, Instruction::new(0x3b, 1, 5, "DCX_SP"));
-        hm.insert(INR_A, Instruction::new(0x3c, 1, 5, "INR_A"));
-        hm.insert(DCR_A, Instruction::new(0x3d, 1, 5, "DCR_A"));
-        hm.insert(MVI_A_D8, Instruction::new(0x3e, 2, 7, "MVI_A_D8"));
-        hm.insert(CMC, Instruction::new(0x3f, 1, 4, "CMC"));
-        hm.insert(MOV_B_B, Instruction::new(0x40, 1, 5, "MOV_B_B"));
-        hm.insert(MOV_B_C, Instruction::new(0x41, 1, 5, "MOV_B_C"));
-        hm.insert(MOV_B_D, Instruction::new(0x42, 1, 5, "MOV_B_D"));
-        hm.insert(MOV_B_E, Instruction::new(0x43, 1, 5, "MOV_B_E"));
-        hm.insert(MOV_B_H, Instruction::new(0x44, 1, 5, "MOV_B_H"));
-        hm.insert(MOV_B_L, Instruction::new(0x45, 1, 5, "MOV_B_L"));
-        hm.insert(MOV_B_M, Instruction::new(0x46, 1, 7, "MOV_B_M"));
-        hm.insert(MOV_B_A, Instruction::new(0x47, 1, 5, "MOV_B_A"));
-        hm.insert(MOV_C_B, Instruction::new(0x48, 1, 5, "MOV_C_B"));
-        hm.insert(MOV_C_C, Instruction::new(0x49, 1, 5, "MOV_C_C"));
-        hm.insert(MOV_C_D, Instruction::new(0x4a, 1, 5, "MOV_C_D"));
-        hm.insert(MOV_C_E, Instruction::new(0x4b, 1, 5, "MOV_C_E"));
-        hm.insert(MOV_C_H, Instruction::new(0x4c, 1, 5, "MOV_C_H"));
-        hm.insert(MOV_C_L, Instruction::new(0x4d, 1, 5, "MOV_C_L"));
-        hm.insert(MOV_C_M, Instruction::new(0x4e, 1, 7, "MOV_C_M"));
-        hm.insert(MOV_C_A, Instruction::new(0x4f, 1, 5, "MOV_C_A"));
-        hm.insert(MOV_D_B, Instruction::new(0x50, 1, 5, "MOV_D_B"));
-        hm.insert(MOV_D_C, Instruction::new(0x51, 1, 5, "MOV_D_C"));
-        hm.insert(MOV_D_D, Instruction::new(0x52, 1, 5, "MOV_D_D"));
-        hm.insert(MOV_D_E, Instruction::new(0x53, 1, 5, "MOV_D_E"));
-        hm.insert(MOV_D_H, Instruction::new(0x54, 1, 5, "MOV_D_H"));
-        hm.insert(MOV_D_L, Instruction::new(0x55, 1, 5, "MOV_D_L"));
-        hm.insert(MOV_D_M, Instruction::new(0x56, 1, 7, "MOV_D_M"));
-        hm.insert(MOV_D_A, Instruction::new(0x57, 1, 5, "MOV_D_A"));
-        hm.insert(MOV_E_B, Instruction::new(0x58, 1, 5, "MOV_E_B"));
-        hm.insert(MOV_E_C, Instruction::new(0x59, 1, 5, "MOV_E_C"));
-        hm.insert(MOV_E_D, Instruction::new(0x5a, 1, 5, "MOV_E_D"));
-        hm.insert(MOV_E_E, Instruction::new(0x5b, 1, 5, "MOV_E_E"));
-        hm.insert(MOV_E_H, Instruction::new(0x5c, 1, 5, "MOV_E_H"));
-        hm.insert(MOV_E_L, Instruction::new(0x5d, 1, 5, "MOV_E_L"));
-        hm.insert(MOV_E_M, Instruction::new(0x5e, 1, 7, "MOV_E_M"));
-        hm.insert(MOV_E_A, Instruction::new(0x5f, 1, 5, "MOV_E_A"));
-        hm.insert(MOV_H_B, Instruction::new(0x60, 1, 5, "MOV_H_B"));
-        hm.insert(MOV_H_C, Instruction::new(0x61, 1, 5, "MOV_H_C"));
-        hm.insert(MOV_H_D, Instruction::new(0x62, 1, 5, "MOV_H_D"));
-        hm.insert(MOV_H_E, Instruction::new(0x63, 1, 5, "MOV_H_E"));
-        hm.insert(MOV_H_H, Instruction::new(0x64, 1, 5, "MOV_H_H"));
-        hm.insert(MOV_H_L, Instruction::new(0x65, 1, 5, "MOV_H_L"));
-        hm.insert(MOV_H_M, Instruction::new(0x66, 1, 7, "MOV_H_M"));
-        hm.insert(MOV_H_A, Instruction::new(0x67, 1, 5, "MOV_H_A"));
-        hm.insert(MOV_L_B, Instruction::new(0x68, 1, 5, "MOV_L_B"));
-        hm.insert(MOV_L_C, Instruction::new(0x69, 1, 5, "MOV_L_C"));
-        hm.insert(MOV_L_D, Instruction::new(0x6a, 1, 5, "MOV_L_D"));
-        hm.insert(MOV_L_E, Instruction::new(0x6b, 1, 5, "MOV_L_E"));
-        hm.insert(MOV_L_H, Instruction::new(0x6c, 1, 5, "MOV_L_H"));
-        hm.insert(MOV_L_L, Instruction::new(0x6d, 1, 5, "MOV_L_L"));
-        hm.insert(MOV_L_M, Instruction::new(0x6e, 1, 7, "MOV_L_M"));
-        hm.insert(MOV_L_A, Instruction::new(0x6f, 1, 5, "MOV_L_A"));
-        hm.insert(MOV_M_B, Instruction::new(0x70, 1, 7, "MOV_M_B"));
-        hm.insert(MOV_M_C, Instruction::new(0x71, 1, 7, "MOV_M_C"));
-        hm.insert(MOV_M_D, Instruction::new(0x72, 1, 7, "MOV_M_D"));
-        hm.insert(MOV_M_E, Instruction::new(0x73, 1, 7, "MOV_M_E"));
-        hm.insert(MOV_M_H, Instruction::new(0x74, 1, 7, "MOV_M_H"));
-        hm.insert(MOV_M_L, Instruction::new(0x75, 1, 7, "MOV_M_L"));
-        hm.insert(HLT, Instruction::new(0x76, 1, 7, "HLT"));
-        hm.insert(MOV_M_A, Instruction::new(0x77, 1, 7, "MOV_M_A"));
-        hm.insert(MOV_A_B, Instruction::new(0x78, 1, 5, "MOV_A_B"));
-        hm.insert(MOV_A_C, Instruction::new(0x79, 1, 5, "MOV_A_C"));
-        hm.insert(MOV_A_D, Instruction::new(0x7a, 1, 5, "MOV_A_D"));
-        hm.insert(MOV_A_E, Instruction::new(0x7b, 1, 5, "MOV_A_E"));
-        hm.insert(MOV_A_H, Instruction::new(0x7c, 1, 5, "MOV_A_H"));
-        hm.insert(MOV_A_L, Instruction::new(0x7d, 1, 5, "MOV_A_L"));
-        hm.insert(MOV_A_M, Instruction::new(0x7e, 1, 7, "MOV_A_M"));
-        hm.insert(MOV_A_A, Instruction::new(0x7f, 1, 5, "MOV_A_A"));
-        hm.insert(ADD_B, Instruction::new(0x80, 1, 4, "ADD_B"));
-        hm.insert(ADD_C, Instruction::new(0x81, 1, 4, "ADD_C"));
-        hm.insert(ADD_D, Instruction::new(0x82, 1, 4, "ADD_D"));
-        hm.insert(ADD_E, Instruction::new(0x83, 1, 4, "ADD_E"));
-        hm.insert(ADD_H, Instruction::new(0x84, 1, 4, "ADD_H"));
-        hm.insert(ADD_L, Instruction::new(0x85, 1, 4, "ADD_L"));
-        hm.insert(ADD_M, Instruction::new(0x86, 1, 7, "ADD_M"));
-        hm.insert(ADD_A, Instruction::new(0x87, 1, 4, "ADD_A"));
-        hm.insert(ADC_B, Instruction::new(0x88, 1, 4, "ADC_B"));
-        hm.insert(ADC_C, Instruction::new(0x89, 1, 4, "ADC_C"));
-        hm.insert(ADC_D, Instruction::new(0x8a, 1, 4, "ADC_D"));
-        hm.insert(ADC_E, Instruction::new(0x8b, 1, 4, "ADC_E"));
-        hm.insert(ADC_H, Instruction::new(0x8c, 1, 4, "ADC_H"));
-        hm.insert(ADC_L, Instruction::new(0x8d, 1, 4, "ADC_L"));
-        hm.insert(ADC_M, Instruction::new(0x8e, 1, 7, "ADC_M"));
-        hm.insert(ADC_A, Instruction::new(0x8f, 1, 4, "ADC_A"));
-        hm.insert(SUB_B, Instruction::new(0x90, 1, 4, "SUB_B"));
-        hm.insert(SUB_C, Instruction::new(0x91, 1, 4, "SUB_C"));
-        hm.insert(SUB_D, Instruction::new(0x92, 1, 4, "SUB_D"));
-        hm.insert(SUB_E, Instruction::new(0x93, 1, 4, "SUB_E"));
-        hm.insert(SUB_H, Instruction::new(0x94, 1, 4, "SUB_H"));
-        hm.insert(SUB_L, Instruction::new(0x95, 1, 4, "SUB_L"));
-        hm.insert(SUB_M, Instruction::new(0x96, 1, 7, "SUB_M"));
-        hm.insert(SUB_A, Instruction::new(0x97, 1, 4, "SUB_A"));
-        hm.insert(SBB_B, Instruction::new(0x98, 1, 4, "SBB_B"));
-        hm.insert(SBB_C, Instruction::new(0x99, 1, 4, "SBB_C"));
-        hm.insert(SBB_D, Instruction::new(0x9a, 1, 4, "SBB_D"));
-        hm.insert(SBB_E, Instruction::new(0x9b, 1, 4, "SBB_E"));
-        hm.insert(SBB_H, Instruction::new(0x9c, 1, 4, "SBB_H"));
-        hm.insert(SBB_L, Instruction::new(0x9d, 1, 4, "SBB_L"));
-        hm.insert(SBB_M, Instruction::new(0x9e, 1, 7, "SBB_M"));
-        hm.insert(SBB_A, Instruction::new(0x9f, 1, 4, "SBB_A"));
-        hm.insert(ANA_B, Instruction::new(0xa0, 1, 4, "ANA_B"));
-        hm.insert(ANA_C, Instruction::new(0xa1, 1, 4, "ANA_C"));
-        hm.insert(ANA_D, Instruction::new(0xa2, 1, 4, "ANA_D"));
-        hm.insert(ANA_E, Instruction::new(0xa3, 1, 4, "ANA_E"));
-        hm.insert(ANA_H, Instruction::new(0xa4, 1, 4, "ANA_H"));
-        hm.insert(ANA_L, Instruction::new(0xa5, 1, 4, "ANA_L"));
-        hm.insert(ANA_M, Instruction::new(0xa6, 1, 7, "ANA_M"));
-        hm.insert(ANA_A, Instruction::new(0xa7, 1, 4, "ANA_A"));
-        hm.insert(XRA_B, Instruction::new(0xa8, 1, 4, "XRA_B"));
-        hm.insert(XRA_C, Instruction::new(0xa9, 1, 4, "XRA_C"));
-        hm.insert(XRA_D, Instruction::new(0xaa, 1, 4, "XRA_D"));
-        hm.insert(XRA_E, Instruction::new(0xab, 1, 4, "XRA_E"));
-        hm.insert(XRA_H, Instruction::new(0xac, 1, 4, "XRA_H"));
-        hm.insert(XRA_L, Instruction::new(0xad, 1, 4, "XRA_L"));
-        hm.insert(XRA_M, Instruction::new(0xae, 1, 7, "XRA_M"));
-        hm.insert(XRA_A, Instruction::new(0xaf, 1, 4, "XRA_A"));
-        hm.insert(ORA_B, Instruction::new(0xb0, 1, 4, "ORA_B"));
-        hm.insert(ORA_C, Instruction::new(0xb1, 1, 4, "ORA_C"));
-        hm.insert(ORA_D, Instruction::new(0xb2, 1, 4, "ORA_D"));
-        hm.insert(ORA_E, Instruction::new(0xb3, 1, 4, "ORA_E"));
-        hm.insert(ORA_H, Instruction::new(0xb4, 1, 4, "ORA_H"));
-        hm.insert(ORA_L, Instruction::new(0xb5, 1, 4, "ORA_L"));
-        hm.insert(ORA_M, Instruction::new(0xb6, 1, 7, "ORA_M"));
-        hm.insert(ORA_A, Instruction::new(0xb7, 1, 4, "ORA_A"));
-        hm.insert(CMP_B, Instruction::new(0xb8, 1, 4, "CMP_B"));
-        hm.insert(CMP_C, Instruction::new(0xb9, 1, 4, "CMP_C"));
-        hm.insert(CMP_D, Instruction::new(0xba, 1, 4, "CMP_D"));
-        hm.insert(CMP_E, Instruction::new(0xbb, 1, 4, "CMP_E"));
-        hm.insert(CMP_H, Instruction::new(0xbc, 1, 4, "CMP_H"));
-        hm.insert(CMP_L, Instruction::new(0xbd, 1, 4, "CMP_L"));
-        hm.insert(CMP_M, Instruction::new(0xbe, 1, 7, "CMP_M"));
-        hm.insert(CMP_A, Instruction::new(0xbf, 1, 4, "CMP_A"));
-        hm.insert(RNZ, Instruction::new(0xc0, 1, 5, "RNZ"));
-        hm.insert(POP_B, Instruction::new(0xc1, 1, 10, "POP_B"));
-        hm.insert(JNZ_ADR, Instruction::new(0xc2, 3, 10, "JNZ_ADR"));
-        hm.insert(JMP_ADR, Instruction::new(0xc3, 3, 10, "JMP_ADR"));
-        hm.insert(CNZ_ADR, Instruction::new(0xc4, 3, 11, "CNZ_ADR"));
-        hm.insert(PUSH_B, Instruction::new(0xc5, 1, 11, "PUSH_B"));
-        hm.insert(ADI_D8, Instruction::new(0xc6, 2, 7, "ADI_D8"));
-        hm.insert(RST_0, Instruction::new(0xc7, 1, 11, "RST_0"));
-        hm.insert(RZ, Instruction::new(0xc8, 1, 5, "RZ"));
-        hm.insert(RET, Instruction::new(0xc9, 1, 10, "RET"));
-        hm.insert(JZ_ADR, Instruction::new(0xca, 3, 10, "JZ_ADR"));
-        hm.insert(CZ_ADR, Instruction::new(0xcc, 3, 11, "CZ_ADR"));
-        hm.insert(CALL_ADR, Instruction::new(0xcd, 3, 17, "CALL_ADR"));
-        hm.insert(ACI_D8, Instruction::new(0xce, 2, 7, "ACI_D8"));
-        hm.insert(RST_1, Instruction::new(0xcf, 1, 11, "RST_1"));
-        hm.insert(RNC, Instruction::new(0xd0, 1, 5, "RNC"));
-        hm.insert(POP_D, Instruction::new(0xd1, 1, 10, "POP_D"));
-        hm.insert(JNC_ADR, Instruction::new(0xd2, 3, 10, "JNC_ADR"));
-        hm.insert(OUT_D8, Instruction::new(0xd3, 2, 10, "OUT_D8"));
-        hm.insert(CNC_ADR, Instruction::new(0xd4, 3, 11, "CNC_ADR"));
-        hm.insert(PUSH_D, Instruction::new(0xd5, 1, 11, "PUSH_D"));
-        hm.insert(SUI_D8, Instruction::new(0xd6, 2, 7, "SUI_D8"));
-        hm.insert(RST_2, Instruction::new(0xd7, 1, 11, "RST_2"));
-        hm.insert(RC, Instruction::new(0xd8, 1, 5, "RC"));
-        hm.insert(JC_ADR, Instruction::new(0xda, 3, 10, "JC_ADR"));
-        hm.insert(IN_D8, Instruction::new(0xdb, 2, 10, "IN_D8"));
-        hm.insert(CC_ADR, Instruction::new(0xdc, 3, 11, "CC_ADR"));
-        hm.insert(SBI_D8, Instruction::new(0xde, 2, 7, "SBI_D8"));
-        hm.insert(RST_3, Instruction::new(0xdf, 1, 11, "RST_3"));
-        hm.insert(RPO, Instruction::new(0xe0, 1, 5, "RPO"));
-        hm.insert(POP_H, Instruction::new(0xe1, 1, 10, "POP_H"));
-        hm.insert(JPO_ADR, Instruction::new(0xe2, 3, 10, "JPO_ADR"));
-        hm.insert(XTHL, Instruction::new(0xe3, 1, 18, "XTHL"));
-        hm.insert(CPO_ADR, Instruction::new(0xe4, 3, 11, "CPO_ADR"));
-        hm.insert(PUSH_H, Instruction::new(0xe5, 1, 11, "PUSH_H"));
-        hm.insert(ANI_D8, Instruction::new(0xe6, 2, 7, "ANI_D8"));
-        hm.insert(RST_4, Instruction::new(0xe7, 1, 11, "RST_4"));
-        hm.insert(RPE, Instruction::new(0xe8, 1, 5, "RPE"));
-        hm.insert(PCHL, Instruction::new(0xe9, 1, 5, "PCHL"));
-        hm.insert(JPE_ADR, Instruction::new(0xea, 3, 10, "JPE_ADR"));
-        hm.insert(XCHG, Instruction::new(0xeb, 1, 5, "XCHG"));
-        hm.insert(CPE_ADR, Instruction::new(0xec, 3, 11, "CPE_ADR"));
-        hm.insert(XRI_D8, Instruction::new(0xee, 2, 7, "XRI_D8"));
-        hm.insert(RST_5, Instruction::new(0xef, 1, 11, "RST_5"));
-        hm.insert(RP, Instruction::new(0xf0, 1, 5, "RP"));
-        hm.insert(POP_PSW, Instruction::new(0xf1, 1, 10, "POP_PSW"));
-        hm.insert(JP_ADR, Instruction::new(0xf2, 3, 10, "JP_ADR"));
-        hm.insert(DI, Instruction::new(0xf3, 1, 4, "DI"));
-        hm.insert(CP_ADR, Instruction::new(0xf4, 3, 11, "CP_ADR"));
-        hm.insert(PUSH_PSW, Instruction::new(0xf5, 1, 11, "PUSH_PSW"));
-        hm.insert(ORI_D8, Instruction::new(0xf6, 2, 7, "ORI_D8"));
-        hm.insert(RST_6, Instruction::new(0xf7, 1, 11, "RST_6"));
-        hm.insert(RM, Instruction::new(0xf8, 1, 5, "RM"));
-        hm.insert(SPHL, Instruction::new(0xf9, 1, 5, "SPHL"));
-        hm.insert(JM_ADR, Instruction::new(0xfa, 3, 10, "JM_ADR"));
-        hm.insert(EI, Instruction::new(0xfb, 1, 4, "EI"));
-        hm.insert(CM_ADR, Instruction::new(0xfc, 3, 11, "CM_ADR"));
-        hm.insert(CPI_D8, Instruction::new(0xfe, 2, 7, "CPI_D8"));
-        hm.insert(RST_7, Instruction::new(0xff, 1, 11, "RST_7"));        
-        hm
-    };
+/* Flags are PSW bit masks (S Z 0 A 0 P 1 C) of the conditions each opcode may change. Undocumented aliases are prefixed with '*'. */
+pub const OPCODES: [Instruction; 256] = [
+    Instruction::new(0x00, 1, 4, "NOP", FLAGS_NONE),
+    Instruction::new(0x01, 3, 10, "LXI_B_D16", FLAGS_NONE),
+    Instruction::new(0x02, 1, 7, "STAX_B", FLAGS_NONE),
+    Instruction::new(0x03, 1, 5, "INX_B", FLAGS_NONE),
+    Instruction::new(0x04, 1, 5, "INR_B", FLAGS_SZAP),
+    Instruction::new(0x05, 1, 5, "DCR_B", FLAGS_SZAP),
+    Instruction::new(0x06, 2, 7, "MVI_B_D8", FLAGS_NONE),
+    Instruction::new(0x07, 1, 4, "RLC", FLAGS_C),
+    Instruction::new(0x08, 1, 4, "*NOP", FLAGS_NONE),
+    Instruction::new(0x09, 1, 10, "DAD_B", FLAGS_C),
+    Instruction::new(0x0a, 1, 7, "LDAX_B", FLAGS_NONE),
+    Instruction::new(0x0b, 1, 5, "DCX_B", FLAGS_NONE),
+    Instruction::new(0x0c, 1, 5, "INR_C", FLAGS_SZAP),
+    Instruction::new(0x0d, 1, 5, "DCR_C", FLAGS_SZAP),
+    Instruction::new(0x0e, 2, 7, "MVI_C_D8", FLAGS_NONE),
+    Instruction::new(0x0f, 1, 4, "RRC", FLAGS_C),
+    Instruction::new(0x10, 1, 4, "*NOP", FLAGS_NONE),
+    Instruction::new(0x11, 3, 10, "LXI_D_D16", FLAGS_NONE),
+    Instruction::new(0x12, 1, 7, "STAX_D", FLAGS_NONE),
+    Instruction::new(0x13, 1, 5, "INX_D", FLAGS_NONE),
+    Instruction::new(0x14, 1, 5, "INR_D", FLAGS_SZAP),
+    Instruction::new(0x15, 1, 5, "DCR_D", FLAGS_SZAP),
+    Instruction::new(0x16, 2, 7, "MVI_D_D8", FLAGS_NONE),
+    Instruction::new(0x17, 1, 4, "RAL", FLAGS_C),
+    Instruction::new(0x18, 1, 4, "*NOP", FLAGS_NONE),
+    Instruction::new(0x19, 1, 10, "DAD_D", FLAGS_C),
+    Instruction::new(0x1a, 1, 7, "LDAX_D", FLAGS_NONE),
+    Instruction::new(0x1b, 1, 5, "DCX_D", FLAGS_NONE),
+    Instruction::new(0x1c, 1, 5, "INR_E", FLAGS_SZAP),
+    Instruction::new(0x1d, 1, 5, "DCR_E", FLAGS_SZAP),
+    Instruction::new(0x1e, 2, 7, "MVI_E_D8", FLAGS_NONE),
+    Instruction::new(0x1f, 1, 4, "RAR", FLAGS_C),
+    Instruction::new(0x20, 1, 4, "*NOP", FLAGS_NONE),
+    Instruction::new(0x21, 3, 10, "LXI_H_D16", FLAGS_NONE),
+    Instruction::new(0x22, 3, 16, "SHLD_ADR", FLAGS_NONE),
+    Instruction::new(0x23, 1, 5, "INX_H", FLAGS_NONE),
+    Instruction::new(0x24, 1, 5, "INR_H", FLAGS_SZAP),
+    Instruction::new(0x25, 1, 5, "DCR_H", FLAGS_SZAP),
+    Instruction::new(0x26, 2, 7, "MVI_H_D8", FLAGS_NONE),
+    Instruction::new(0x27, 1, 4, "DAA", FLAGS_SZAPC),
+    Instruction::new(0x28, 1, 4, "*NOP", FLAGS_NONE),
+    Instruction::new(0x29, 1, 10, "DAD_H", FLAGS_C),
+    Instruction::new(0x2a, 3, 16, "LHLD_ADR", FLAGS_NONE),
+    Instruction::new(0x2b, 1, 5, "DCX_H", FLAGS_NONE),
+    Instruction::new(0x2c, 1, 5, "INR_L", FLAGS_SZAP),
+    Instruction::new(0x2d, 1, 5, "DCR_L", FLAGS_SZAP),
+    Instruction::new(0x2e, 2, 7, "MVI_L_D8", FLAGS_NONE),
+    Instruction::new(0x2f, 1, 4, "CMA", FLAGS_NONE),
+    Instruction::new(0x30, 1, 4, "*NOP", FLAGS_NONE),
+    Instruction::new(0x31, 3, 10, "LXI_SP_D16", FLAGS_NONE),
+    Instruction::new(0x32, 3, 13, "STA_ADR", FLAGS_NONE),
+    Instruction::new(0x33, 1, 5, "INX_SP", FLAGS_NONE),
+    Instruction::new(0x34, 1, 10, "INR_M", FLAGS_SZAP),
+    Instruction::new(0x35, 1, 10, "DCR_M", FLAGS_SZAP),
+    Instruction::new(0x36, 2, 10, "MVI_M_D8", FLAGS_NONE),
+    Instruction::new(0x37, 1, 4, "STC", FLAGS_C),
+    Instruction::new(0x38, 1, 4, "*NOP", FLAGS_NONE),
+    Instruction::new(0x39, 1, 10, "DAD_SP", FLAGS_C),
+    Instruction::new(0x3a, 3, 13, "LDA_ADR", FLAGS_NONE),
+    Instruction::new(0x3b, 1, 5, "DCX_SP", FLAGS_NONE),
+    Instruction::new(0x3c, 1, 5, "INR_A", FLAGS_SZAP),
+    Instruction::new(0x3d, 1, 5, "DCR_A", FLAGS_SZAP),
+    Instruction::new(0x3e, 2, 7, "MVI_A_D8", FLAGS_NONE),
+    Instruction::new(0x3f, 1, 4, "CMC", FLAGS_C),
+    Instruction::new(0x40, 1, 5, "MOV_B_B", FLAGS_NONE),
+    Instruction::new(0x41, 1, 5, "MOV_B_C", FLAGS_NONE),
+    Instruction::new(0x42, 1, 5, "MOV_B_D", FLAGS_NONE),
+    Instruction::new(0x43, 1, 5, "MOV_B_E", FLAGS_NONE),
+    Instruction::new(0x44, 1, 5, "MOV_B_H", FLAGS_NONE),
+    Instruction::new(0x45, 1, 5, "MOV_B_L", FLAGS_NONE),
+    Instruction::new(0x46, 1, 7, "MOV_B_M", FLAGS_NONE),
+    Instruction::new(0x47, 1, 5, "MOV_B_A", FLAGS_NONE),
+    Instruction::new(0x48, 1, 5, "MOV_C_B", FLAGS_NONE),
+    Instruction::new(0x49, 1, 5, "MOV_C_C", FLAGS_NONE),
+    Instruction::new(0x4a, 1, 5, "MOV_C_D", FLAGS_NONE),
+    Instruction::new(0x4b, 1, 5, "MOV_C_E", FLAGS_NONE),
+    Instruction::new(0x4c, 1, 5, "MOV_C_H", FLAGS_NONE),
+    Instruction::new(0x4d, 1, 5, "MOV_C_L", FLAGS_NONE),
+    Instruction::new(0x4e, 1, 7, "MOV_C_M", FLAGS_NONE),
+    Instruction::new(0x4f, 1, 5, "MOV_C_A", FLAGS_NONE),
+    Instruction::new(0x50, 1, 5, "MOV_D_B", FLAGS_NONE),
+    Instruction::new(0x51, 1, 5, "MOV_D_C", FLAGS_NONE),
+    Instruction::new(0x52, 1, 5, "MOV_D_D", FLAGS_NONE),
+    Instruction::new(0x53, 1, 5, "MOV_D_E", FLAGS_NONE),
+    Instruction::new(0x54, 1, 5, "MOV_D_H", FLAGS_NONE),
+    Instruction::new(0x55, 1, 5, "MOV_D_L", FLAGS_NONE),
+    Instruction::new(0x56, 1, 7, "MOV_D_M", FLAGS_NONE),
+    Instruction::new(0x57, 1, 5, "MOV_D_A", FLAGS_NONE),
+    Instruction::new(0x58, 1, 5, "MOV_E_B", FLAGS_NONE),
+    Instruction::new(0x59, 1, 5, "MOV_E_C", FLAGS_NONE),
+    Instruction::new(0x5a, 1, 5, "MOV_E_D", FLAGS_NONE),
+    Instruction::new(0x5b, 1, 5, "MOV_E_E", FLAGS_NONE),
+    Instruction::new(0x5c, 1, 5, "MOV_E_H", FLAGS_NONE),
+    Instruction::new(0x5d, 1, 5, "MOV_E_L", FLAGS_NONE),
+    Instruction::new(0x5e, 1, 7, "MOV_E_M", FLAGS_NONE),
+    Instruction::new(0x5f, 1, 5, "MOV_E_A", FLAGS_NONE),
+    Instruction::new(0x60, 1, 5, "MOV_H_B", FLAGS_NONE),
+    Instruction::new(0x61, 1, 5, "MOV_H_C", FLAGS_NONE),
+    Instruction::new(0x62, 1, 5, "MOV_H_D", FLAGS_NONE),
+    Instruction::new(0x63, 1, 5, "MOV_H_E", FLAGS_NONE),
+    Instruction::new(0x64, 1, 5, "MOV_H_H", FLAGS_NONE),
+    Instruction::new(0x65, 1, 5, "MOV_H_L", FLAGS_NONE),
+    Instruction::new(0x66, 1, 7, "MOV_H_M", FLAGS_NONE),
+    Instruction::new(0x67, 1, 5, "MOV_H_A", FLAGS_NONE),
+    Instruction::new(0x68, 1, 5, "MOV_L_B", FLAGS_NONE),
+    Instruction::new(0x69, 1, 5, "MOV_L_C", FLAGS_NONE),
+    Instruction::new(0x6a, 1, 5, "MOV_L_D", FLAGS_NONE),
+    Instruction::new(0x6b, 1, 5, "MOV_L_E", FLAGS_NONE),
+    Instruction::new(0x6c, 1, 5, "MOV_L_H", FLAGS_NONE),
+    Instruction::new(0x6d, 1, 5, "MOV_L_L", FLAGS_NONE),
+    Instruction::new(0x6e, 1, 7, "MOV_L_M", FLAGS_NONE),
+    Instruction::new(0x6f, 1, 5, "MOV_L_A", FLAGS_NONE),
+    Instruction::new(0x70, 1, 7, "MOV_M_B", FLAGS_NONE),
+    Instruction::new(0x71, 1, 7, "MOV_M_C", FLAGS_NONE),
+    Instruction::new(0x72, 1, 7, "MOV_M_D", FLAGS_NONE),
+    Instruction::new(0x73, 1, 7, "MOV_M_E", FLAGS_NONE),
+    Instruction::new(0x74, 1, 7, "MOV_M_H", FLAGS_NONE),
+    Instruction::new(0x75, 1, 7, "MOV_M_L", FLAGS_NONE),
+    Instruction::new(0x76, 1, 7, "HLT", FLAGS_NONE),
+    Instruction::new(0x77, 1, 7, "MOV_M_A", FLAGS_NONE),
+    Instruction::new(0x78, 1, 5, "MOV_A_B", FLAGS_NONE),
+    Instruction::new(0x79, 1, 5, "MOV_A_C", FLAGS_NONE),
+    Instruction::new(0x7a, 1, 5, "MOV_A_D", FLAGS_NONE),
+    Instruction::new(0x7b, 1, 5, "MOV_A_E", FLAGS_NONE),
+    Instruction::new(0x7c, 1, 5, "MOV_A_H", FLAGS_NONE),
+    Instruction::new(0x7d, 1, 5, "MOV_A_L", FLAGS_NONE),
+    Instruction::new(0x7e, 1, 7, "MOV_A_M", FLAGS_NONE),
+    Instruction::new(0x7f, 1, 5, "MOV_A_A", FLAGS_NONE),
+    Instruction::new(0x80, 1, 4, "ADD_B", FLAGS_SZAPC),
+    Instruction::new(0x81, 1, 4, "ADD_C", FLAGS_SZAPC),
+    Instruction::new(0x82, 1, 4, "ADD_D", FLAGS_SZAPC),
+    Instruction::new(0x83, 1, 4, "ADD_E", FLAGS_SZAPC),
+    Instruction::new(0x84, 1, 4, "ADD_H", FLAGS_SZAPC),
+    Instruction::new(0x85, 1, 4, "ADD_L", FLAGS_SZAPC),
+    Instruction::new(0x86, 1, 7, "ADD_M", FLAGS_SZAPC),
+    Instruction::new(0x87, 1, 4, "ADD_A", FLAGS_SZAPC),
+    Instruction::new(0x88, 1, 4, "ADC_B", FLAGS_SZAPC),
+    Instruction::new(0x89, 1, 4, "ADC_C", FLAGS_SZAPC),
+    Instruction::new(0x8a, 1, 4, "ADC_D", FLAGS_SZAPC),
+    Instruction::new(0x8b, 1, 4, "ADC_E", FLAGS_SZAPC),
+    Instruction::new(0x8c, 1, 4, "ADC_H", FLAGS_SZAPC),
+    Instruction::new(0x8d, 1, 4, "ADC_L", FLAGS_SZAPC),
+    Instruction::new(0x8e, 1, 7, "ADC_M", FLAGS_SZAPC),
+    Instruction::new(0x8f, 1, 4, "ADC_A", FLAGS_SZAPC),
+    Instruction::new(0x90, 1, 4, "SUB_B", FLAGS_SZAPC),
+    Instruction::new(0x91, 1, 4, "SUB_C", FLAGS_SZAPC),
+    Instruction::new(0x92, 1, 4, "SUB_D", FLAGS_SZAPC),
+    Instruction::new(0x93, 1, 4, "SUB_E", FLAGS_SZAPC),
+    Instruction::new(0x94, 1, 4, "SUB_H", FLAGS_SZAPC),
+    Instruction::new(0x95, 1, 4, "SUB_L", FLAGS_SZAPC),
+    Instruction::new(0x96, 1, 7, "SUB_M", FLAGS_SZAPC),
+    Instruction::new(0x97, 1, 4, "SUB_A", FLAGS_SZAPC),
+    Instruction::new(0x98, 1, 4, "SBB_B", FLAGS_SZAPC),
+    Instruction::new(0x99, 1, 4, "SBB_C", FLAGS_SZAPC),
+    Instruction::new(0x9a, 1, 4, "SBB_D", FLAGS_SZAPC),
+    Instruction::new(0x9b, 1, 4, "SBB_E", FLAGS_SZAPC),
+    Instruction::new(0x9c, 1, 4, "SBB_H", FLAGS_SZAPC),
+    Instruction::new(0x9d, 1, 4, "SBB_L", FLAGS_SZAPC),
+    Instruction::new(0x9e, 1, 7, "SBB_M", FLAGS_SZAPC),
+    Instruction::new(0x9f, 1, 4, "SBB_A", FLAGS_SZAPC),
+    Instruction::new(0xa0, 1, 4, "ANA_B", FLAGS_SZAPC),
+    Instruction::new(0xa1, 1, 4, "ANA_C", FLAGS_SZAPC),
+    Instruction::new(0xa2, 1, 4, "ANA_D", FLAGS_SZAPC),
+    Instruction::new(0xa3, 1, 4, "ANA_E", FLAGS_SZAPC),
+    Instruction::new(0xa4, 1, 4, "ANA_H", FLAGS_SZAPC),
+    Instruction::new(0xa5, 1, 4, "ANA_L", FLAGS_SZAPC),
+    Instruction::new(0xa6, 1, 7, "ANA_M", FLAGS_SZAPC),
+    Instruction::new(0xa7, 1, 4, "ANA_A", FLAGS_SZAPC),
+    Instruction::new(0xa8, 1, 4, "XRA_B", FLAGS_SZAPC),
+    Instruction::new(0xa9, 1, 4, "XRA_C", FLAGS_SZAPC),
+    Instruction::new(0xaa, 1, 4, "XRA_D", FLAGS_SZAPC),
+    Instruction::new(0xab, 1, 4, "XRA_E", FLAGS_SZAPC),
+    Instruction::new(0xac, 1, 4, "XRA_H", FLAGS_SZAPC),
+    Instruction::new(0xad, 1, 4, "XRA_L", FLAGS_SZAPC),
+    Instruction::new(0xae, 1, 7, "XRA_M", FLAGS_SZAPC),
+    Instruction::new(0xaf, 1, 4, "XRA_A", FLAGS_SZAPC),
+    Instruction::new(0xb0, 1, 4, "ORA_B", FLAGS_SZAPC),
+    Instruction::new(0xb1, 1, 4, "ORA_C", FLAGS_SZAPC),
+    Instruction::new(0xb2, 1, 4, "ORA_D", FLAGS_SZAPC),
+    Instruction::new(0xb3, 1, 4, "ORA_E", FLAGS_SZAPC),
+    Instruction::new(0xb4, 1, 4, "ORA_H", FLAGS_SZAPC),
+    Instruction::new(0xb5, 1, 4, "ORA_L", FLAGS_SZAPC),
+    Instruction::new(0xb6, 1, 7, "ORA_M", FLAGS_SZAPC),
+    Instruction::new(0xb7, 1, 4, "ORA_A", FLAGS_SZAPC),
+    Instruction::new(0xb8, 1, 4, "CMP_B", FLAGS_SZAPC),
+    Instruction::new(0xb9, 1, 4, "CMP_C", FLAGS_SZAPC),
+    Instruction::new(0xba, 1, 4, "CMP_D", FLAGS_SZAPC),
+    Instruction::new(0xbb, 1, 4, "CMP_E", FLAGS_SZAPC),
+    Instruction::new(0xbc, 1, 4, "CMP_H", FLAGS_SZAPC),
+    Instruction::new(0xbd, 1, 4, "CMP_L", FLAGS_SZAPC),
+    Instruction::new(0xbe, 1, 7, "CMP_M", FLAGS_SZAPC),
+    Instruction::new(0xbf, 1, 4, "CMP_A", FLAGS_SZAPC),
+    Instruction::conditional(0xc0, 1, 11, 5, "RNZ", FLAGS_NONE),
+    Instruction::new(0xc1, 1, 10, "POP_B", FLAGS_NONE),
+    Instruction::conditional(0xc2, 3, 10, 10, "JNZ_ADR", FLAGS_NONE),
+    Instruction::new(0xc3, 3, 10, "JMP_ADR", FLAGS_NONE),
+    Instruction::conditional(0xc4, 3, 17, 11, "CNZ_ADR", FLAGS_NONE),
+    Instruction::new(0xc5, 1, 11, "PUSH_B", FLAGS_NONE),
+    Instruction::new(0xc6, 2, 7, "ADI_D8", FLAGS_SZAPC),
+    Instruction::new(0xc7, 1, 11, "RST_0", FLAGS_NONE),
+    Instruction::conditional(0xc8, 1, 11, 5, "RZ", FLAGS_NONE),
+    Instruction::new(0xc9, 1, 10, "RET", FLAGS_NONE),
+    Instruction::conditional(0xca, 3, 10, 10, "JZ_ADR", FLAGS_NONE),
+    Instruction::new(0xcb, 3, 10, "*JMP_ADR", FLAGS_NONE),
+    Instruction::conditional(0xcc, 3, 17, 11, "CZ_ADR", FLAGS_NONE),
+    Instruction::new(0xcd, 3, 17, "CALL_ADR", FLAGS_NONE),
+    Instruction::new(0xce, 2, 7, "ACI_D8", FLAGS_SZAPC),
+    Instruction::new(0xcf, 1, 11, "RST_1", FLAGS_NONE),
+    Instruction::conditional(0xd0, 1, 11, 5, "RNC", FLAGS_NONE),
+    Instruction::new(0xd1, 1, 10, "POP_D", FLAGS_NONE),
+    Instruction::conditional(0xd2, 3, 10, 10, "JNC_ADR", FLAGS_NONE),
+    Instruction::new(0xd3, 2, 10, "OUT_D8", FLAGS_NONE),
+    Instruction::conditional(0xd4, 3, 17, 11, "CNC_ADR", FLAGS_NONE),
+    Instruction::new(0xd5, 1, 11, "PUSH_D", FLAGS_NONE),
+    Instruction::new(0xd6, 2, 7, "SUI_D8", FLAGS_SZAPC),
+    Instruction::new(0xd7, 1, 11, "RST_2", FLAGS_NONE),
+    Instruction::conditional(0xd8, 1, 11, 5, "RC", FLAGS_NONE),
+    Instruction::new(0xd9, 1, 10, "*RET", FLAGS_NONE),
+    Instruction::conditional(0xda, 3, 10, 10, "JC_ADR", FLAGS_NONE),
+    Instruction::new(0xdb, 2, 10, "IN_D8", FLAGS_NONE),
+    Instruction::conditional(0xdc, 3, 17, 11, "CC_ADR", FLAGS_NONE),
+    Instruction::new(0xdd, 3, 17, "*CALL_ADR", FLAGS_NONE),
+    Instruction::new(0xde, 2, 7, "SBI_D8", FLAGS_SZAPC),
+    Instruction::new(0xdf, 1, 11, "RST_3", FLAGS_NONE),
+    Instruction::conditional(0xe0, 1, 11, 5, "RPO", FLAGS_NONE),
+    Instruction::new(0xe1, 1, 10, "POP_H", FLAGS_NONE),
+    Instruction::conditional(0xe2, 3, 10, 10, "JPO_ADR", FLAGS_NONE),
+    Instruction::new(0xe3, 1, 18, "XTHL", FLAGS_NONE),
+    Instruction::conditional(0xe4, 3, 17, 11, "CPO_ADR", FLAGS_NONE),
+    Instruction::new(0xe5, 1, 11, "PUSH_H", FLAGS_NONE),
+    Instruction::new(0xe6, 2, 7, "ANI_D8", FLAGS_SZAPC),
+    Instruction::new(0xe7, 1, 11, "RST_4", FLAGS_NONE),
+    Instruction::conditional(0xe8, 1, 11, 5, "RPE", FLAGS_NONE),
+    Instruction::new(0xe9, 1, 5, "PCHL", FLAGS_NONE),
+    Instruction::conditional(0xea, 3, 10, 10, "JPE_ADR", FLAGS_NONE),
+    Instruction::new(0xeb, 1, 5, "XCHG", FLAGS_NONE),
+    Instruction::conditional(0xec, 3, 17, 11, "CPE_ADR", FLAGS_NONE),
+    Instruction::new(0xed, 3, 17, "*CALL_ADR", FLAGS_NONE),
+    Instruction::new(0xee, 2, 7, "XRI_D8", FLAGS_SZAPC),
+    Instruction::new(0xef, 1, 11, "RST_5", FLAGS_NONE),
+    Instruction::conditional(0xf0, 1, 11, 5, "RP", FLAGS_NONE),
+    Instruction::new(0xf1, 1, 10, "POP_PSW", FLAGS_ALL),
+    Instruction::conditional(0xf2, 3, 10, 10, "JP_ADR", FLAGS_NONE),
+    Instruction::new(0xf3, 1, 4, "DI", FLAGS_NONE),
+    Instruction::conditional(0xf4, 3, 17, 11, "CP_ADR", FLAGS_NONE),
+    Instruction::new(0xf5, 1, 11, "PUSH_PSW", FLAGS_NONE),
+    Instruction::new(0xf6, 2, 7, "ORI_D8", FLAGS_SZAPC),
+    Instruction::new(0xf7, 1, 11, "RST_6", FLAGS_NONE),
+    Instruction::conditional(0xf8, 1, 11, 5, "RM", FLAGS_NONE),
+    Instruction::new(0xf9, 1, 5, "SPHL", FLAGS_NONE),
+    Instruction::conditional(0xfa, 3, 10, 10, "JM_ADR", FLAGS_NONE),
+    Instruction::new(0xfb, 1, 4, "EI", FLAGS_NONE),
+    Instruction::conditional(0xfc, 3, 17, 11, "CM_ADR", FLAGS_NONE),
+    Instruction::new(0xfd, 3, 17, "*CALL_ADR", FLAGS_NONE),
+    Instruction::new(0xfe, 2, 7, "CPI_D8", FLAGS_SZAPC),
+    Instruction::new(0xff, 1, 11, "RST_7", FLAGS_NONE),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcodes_indexed_by_opcode() {
+        for (index, instruction) in OPCODES.iter().enumerate() {
+            assert_eq!(instruction.opcode as usize, index);
+        }
+    }
+
+    #[test]
+    fn test_opcode_metadata() {
+        assert_eq!(OPCODES[LXI_H_D16 as usize].size, 3);
+        assert_eq!(OPCODES[LXI_H_D16 as usize].cycles, 10);
+        assert_eq!(OPCODES[XTHL as usize].cycles, 18);
+        assert_eq!(OPCODES[RNZ as usize].cycles_for(Branch::Taken), 11);
+        assert_eq!(OPCODES[RNZ as usize].cycles_for(Branch::NotTaken), 5);
+        assert_eq!(OPCODES[CZ_ADR as usize].cycles_for(Branch::Taken), 17);
+        assert_eq!(OPCODES[CZ_ADR as usize].cycles_for(Branch::NotTaken), 11);
+        assert_eq!(OPCODES[JNZ_ADR as usize].cycles_for(Branch::NotTaken), 10);
+        assert_eq!(OPCODES[RET as usize].cycles_for(Branch::NotTaken), 10);
+        assert_eq!(OPCODES[0xdd].label, "*CALL_ADR");
+    }
+
+    #[test]
+    fn test_opcode_flags() {
+        assert_eq!(OPCODES[MOV_B_C as usize].affects_flags(), false);
+        assert_eq!(OPCODES[INR_M as usize].flags, FLAGS_SZAP);
+        assert_eq!(OPCODES[CPI_D8 as usize].flags, FLAGS_SZAPC);
+        assert_eq!(OPCODES[DAD_SP as usize].flags, FLAGS_C);
+        assert_eq!(OPCODES[POP_PSW as usize].flags, FLAGS_ALL);
+    }
 }
-*/
\ No newline at end of file
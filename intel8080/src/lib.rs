@@ -1,3 +1,11 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod emulator;
 pub mod memory;
-pub mod conditions;
\ No newline at end of file
+pub mod conditions;
+pub mod instruction;
+pub mod disassembler;
+pub mod asm;
+pub mod profile;
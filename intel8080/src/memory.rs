@@ -1,72 +1,47 @@
-pub struct Memory {
-    memory: [u8; 65_536],   
-}
-
-impl Memory {
-    pub fn new(rom: [u8; 8_192]) -> Self {
-        let mut memory = [0; 65_536];
-        for addr in 0..8_192 {
-            memory[addr] = rom[addr];
-        }
-        Self {
-            memory,
-        }
-    }
-
-    pub fn read(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => return self.memory[addr as usize],
-            0x4000..=0x5FFF => return  self.memory[(addr - 0x2000) as usize],
-            0x6000..=0x7FFF => return  self.memory[(addr - 0x4000) as usize],
-            0x8000..=0x9FFF => return  self.memory[(addr - 0x6000) as usize],
-            0xA000..=0xBFFF => return  self.memory[(addr - 0x8000) as usize],
-            0xC000..=0xDFFF => return  self.memory[(addr - 0xA000) as usize],
-            0xE000..=0xFFFF => return  self.memory[(addr - 0xC000) as usize],
-        }
+pub mod basic_memory;
+pub mod space_invaders_memory;
+
+/// The CPU's view of the 16-bit address space.
+///
+/// A 1KB RAM at $0000 with open-bus reads everywhere else:
+///
+/// ```
+/// use intel8080::emulator::Cpu;
+/// use intel8080::memory::Memory;
+///
+/// struct SmallRam([u8; 1_024]);
+///
+/// impl Memory for SmallRam {
+///     fn read(&self, addr: u16) -> u8 {
+///         self.0.get(addr as usize).copied().unwrap_or(0xFF)
+///     }
+///
+///     fn write(&mut self, addr: u16, data: u8) {
+///         if let Some(byte) = self.0.get_mut(addr as usize) {
+///             *byte = data;
+///         }
+///     }
+/// }
+///
+/// let mut cpu = Cpu::new(Box::new(SmallRam([0; 1_024])));
+/// // LDA $8000; STA $0200; HLT
+/// cpu.load_program(0x0000, &[0x3A, 0x00, 0x80, 0x32, 0x00, 0x02, 0x76]);
+/// cpu.step();
+/// cpu.step();
+/// assert_eq!(cpu.read_memory(0x0200), 0xFF);
+/// ```
+pub trait Memory {
+    fn read(&self, addr: u16) -> u8;
+
+    fn write(&mut self, addr: u16, data: u8);
+
+    /* Extra cycles an access stalls the CPU for, e.g. contended video memory */
+    fn access_cycles(&self, _addr: u16, _is_write: bool) -> u8 {
+        return 0;
     }
 
-    pub fn write(&mut self, addr: u16, data: u8) {
-        match addr {
-            0x0000..=0x1FFF => return,
-            0x2000..=0x3FFF => self.memory[addr as usize] = data,
-            0x4000..=0x5FFF => return,
-            0x6000..=0x7FFF => self.memory[(addr - 0x4000) as usize] = data,
-            0x8000..=0x9FFF => return,
-            0xA000..=0xBFFF => self.memory[(addr - 0x8000) as usize] = data,
-            0xC000..=0xDFFF => return,
-            0xE000..=0xFFFF => self.memory[(addr - 0xC000) as usize] = data,
-        }
+    /* Whether writes to the address are ignored, as by ROM; only used to report them */
+    fn read_only(&self, _addr: u16) -> bool {
+        return false;
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_read() {
-        let mut memory = Memory::new([0; 8_192]);
-        assert_eq!(memory.read(0x0000), 0x0);
-    }
-
-    #[test]
-    fn test_write() {
-        let mut memory = Memory::new([0; 8_192]);
-        memory.write(0x2000, 0x1);
-        assert_eq!(memory.read(0x2000), 0x1);
-    }
-
-    #[test]
-    fn test_write_readonly() {
-        let mut memory = Memory::new([0; 8_192]);
-        memory.write(0x0, 0x1);
-        assert_eq!(memory.read(0x0), 0x0);
-    }
-
-    #[test]
-    fn test_mirror() {
-        let mut memory = Memory::new([0; 8_192]);
-        memory.write(0x2000, 0x1);
-        assert_eq!(memory.read(0x6000), 0x1);
-    }
-}
\ No newline at end of file
@@ -0,0 +1,73 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/* Which part of an instruction a memory read served */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadKind {
+    Opcode,
+    Operand,
+    Data,
+}
+
+const OPERAND: u8 = 0b01;
+const DATA: u8 = 0b10;
+
+/// Per-address execution counts and read marks over the whole 64KB address space.
+///
+/// ```
+/// use intel8080::emulator::Cpu;
+/// use intel8080::memory::basic_memory::BasicMemory;
+///
+/// let mut cpu = Cpu::new(Box::new(BasicMemory::new()));
+/// // LDA $0010; HLT
+/// cpu.load_program(0x0000, &[0x3A, 0x10, 0x00, 0x76]);
+/// cpu.enable_profiling();
+/// cpu.step();
+/// let profile = cpu.profile().unwrap();
+/// assert_eq!(profile.executions(0x0000), 1);
+/// assert!(profile.read_as_operand(0x0001));
+/// assert!(profile.read_as_data(0x0010));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    executions: Vec<u32>,
+    reads: Vec<u8>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Profile {
+            executions: vec![0; 0x10000],
+            reads: vec![0; 0x10000],
+        }
+    }
+
+    pub fn record_read(&mut self, addr: u16, kind: ReadKind) {
+        match kind {
+            ReadKind::Opcode => {
+                let count = &mut self.executions[addr as usize];
+                *count = count.saturating_add(1);
+            },
+            ReadKind::Operand => self.reads[addr as usize] |= OPERAND,
+            ReadKind::Data => self.reads[addr as usize] |= DATA,
+        }
+    }
+
+    pub fn executions(&self, addr: u16) -> u32 {
+        return self.executions[addr as usize];
+    }
+
+    pub fn read_as_operand(&self, addr: u16) -> bool {
+        return self.reads[addr as usize] & OPERAND != 0;
+    }
+
+    pub fn read_as_data(&self, addr: u16) -> bool {
+        return self.reads[addr as usize] & DATA != 0;
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
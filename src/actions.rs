@@ -0,0 +1,272 @@
+use std::fmt;
+use crate::settings::{self, Control};
+use crate::window_size;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    CommandPalette,
+    TogglePause,
+    Step,
+    ToggleFastForward,
+    ToggleCoinBlip,
+    ToggleRandomizeRam,
+    ToggleHighContrast,
+    ToggleStarfield,
+    ToggleStartLamps,
+    ToggleStretchShortPresses,
+    ToggleHighScoreScreenshots,
+    ToggleDipSwitches,
+    ToggleControls,
+    SwapPlayers,
+    ToggleSettings,
+    /* A registry toggle without an action of its own, by setting id */
+    ToggleSetting(&'static str),
+    ToggleStats,
+    TogglePerformance,
+    ToggleMacros,
+    ToggleSoundTimeline,
+    ToggleSoundTest,
+    ToggleRoutines,
+    ToggleCoverage,
+    ExportListing,
+    ToggleInputLog,
+    ToggleLatency,
+    ToggleBreakOnReset,
+    ToggleInfiniteLives,
+    CopyDiagnostic,
+    ResizeWindow(u32),
+    ToggleFullscreen,
+    SaveSlot(usize),
+    LoadSlot(usize),
+    LoadStatePicker,
+    ToggleAbout,
+    Quit,
+}
+
+const RESIZE_IDS: [&str; 3] = ["view.resize_2x", "view.resize_3x", "view.resize_4x"];
+const RESIZE_LABELS: [&str; 3] = ["Resize window to 2×", "Resize window to 3×", "Resize window to 4×"];
+const SLOT_KEYS: [&str; 10] = ["F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10"];
+const SAVE_SLOT_IDS: [&str; 10] = [
+    "state.save_1", "state.save_2", "state.save_3", "state.save_4", "state.save_5",
+    "state.save_6", "state.save_7", "state.save_8", "state.save_9", "state.save_10",
+];
+const SAVE_SLOT_LABELS: [&str; 10] = [
+    "Save state slot 1", "Save state slot 2", "Save state slot 3", "Save state slot 4", "Save state slot 5",
+    "Save state slot 6", "Save state slot 7", "Save state slot 8", "Save state slot 9", "Save state slot 10",
+];
+const LOAD_SLOT_IDS: [&str; 10] = [
+    "state.load_1", "state.load_2", "state.load_3", "state.load_4", "state.load_5",
+    "state.load_6", "state.load_7", "state.load_8", "state.load_9", "state.load_10",
+];
+const LOAD_SLOT_LABELS: [&str; 10] = [
+    "Load state slot 1", "Load state slot 2", "Load state slot 3", "Load state slot 4", "Load state slot 5",
+    "Load state slot 6", "Load state slot 7", "Load state slot 8", "Load state slot 9", "Load state slot 10",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shortcut {
+    pub ctrl: bool,
+    pub shift: bool,
+    /* egui key name, e.g. "F5" or "P" */
+    pub key: &'static str,
+}
+
+impl Shortcut {
+    pub const fn key(key: &'static str) -> Self {
+        Self { ctrl: false, shift: false, key }
+    }
+
+    pub const fn ctrl(key: &'static str) -> Self {
+        Self { ctrl: true, shift: false, key }
+    }
+
+    pub const fn shift(key: &'static str) -> Self {
+        Self { ctrl: false, shift: true, key }
+    }
+
+    pub const fn ctrl_shift(key: &'static str) -> Self {
+        Self { ctrl: true, shift: true, key }
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActionInfo {
+    pub action: Action,
+    pub id: &'static str,
+    pub label: &'static str,
+    pub shortcut: Option<Shortcut>,
+}
+
+/* Every user-invocable action, defined once for the menus, hotkeys and command palette */
+pub struct ActionRegistry {
+    actions: Vec<ActionInfo>,
+}
+
+impl ActionRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self { actions: vec![] };
+        registry.register(Action::CommandPalette, "view.command_palette", "Command palette", Some(Shortcut::ctrl_shift("P")));
+        registry.register(Action::TogglePause, "debug.pause", "Pause / resume", Some(Shortcut::ctrl("P")));
+        registry.register(Action::Step, "debug.step", "Step instruction", Some(Shortcut::ctrl("N")));
+        registry.register(Action::ToggleFastForward, "debug.fast_forward", "Fast forward (4×)", None);
+        registry.register(Action::ToggleCoinBlip, "options.coin_blip", "Coin insert blip", None);
+        registry.register(Action::ToggleRandomizeRam, "options.randomize_ram", "Randomize RAM at power-on (next launch)", None);
+        registry.register(Action::ToggleHighContrast, "options.high_contrast", "High-contrast interface", None);
+        registry.register(Action::ToggleStarfield, "options.starfield", "Starfield background (non-authentic)", None);
+        registry.register(Action::ToggleStartLamps, "options.start_lamps", "Start button lamps", None);
+        registry.register(Action::ToggleStretchShortPresses, "options.stretch_short_presses", "Never drop short presses", None);
+        registry.register(Action::ToggleHighScoreScreenshots, "options.high_score_screenshots", "Screenshot each new high score", None);
+        registry.register(Action::ToggleDipSwitches, "options.dip_switches", "DIP switches…", None);
+        registry.register(Action::ToggleControls, "options.controls", "Controls…", None);
+        registry.register(Action::SwapPlayers, "options.swap_players", "Swap player 1 and 2 keys", None);
+        registry.register(Action::ToggleSettings, "options.settings", "Settings…", None);
+        /* Labelled by the setting's string key rather than English text */
+        for info in settings::SETTINGS.iter().filter(|info| matches!(info.control, Control::Toggle(_))) {
+            registry.register(Action::ToggleSetting(info.id), info.id, info.label, None);
+        }
+        registry.register(Action::ToggleStats, "view.stats", "Stats", None);
+        registry.register(Action::TogglePerformance, "view.performance", "Performance", None);
+        registry.register(Action::ToggleMacros, "view.macros", "Macros", None);
+        for (i, scale) in window_size::RESIZE_SCALES.into_iter().enumerate() {
+            registry.register(Action::ResizeWindow(scale), RESIZE_IDS[i], RESIZE_LABELS[i], None);
+        }
+        registry.register(Action::ToggleFullscreen, "view.fullscreen", "Fullscreen", Some(Shortcut::key("F11")));
+        registry.register(Action::ToggleSoundTimeline, "debug.sound_timeline", "Sound timeline", None);
+        registry.register(Action::ToggleSoundTest, "debug.sound_test", "Sound test", None);
+        registry.register(Action::ToggleRoutines, "debug.routines", "Routines", None);
+        registry.register(Action::ToggleCoverage, "debug.coverage", "ROM coverage", None);
+        registry.register(Action::ExportListing, "debug.export_listing", "Export listing…", None);
+        registry.register(Action::ToggleInputLog, "debug.input_log", "Input log", None);
+        registry.register(Action::ToggleLatency, "debug.latency", "Measure input latency", None);
+        registry.register(Action::ToggleBreakOnReset, "debug.break_on_reset", "Break on unexpected reset", None);
+        registry.register(Action::ToggleInfiniteLives, "debug.infinite_lives", "Infinite lives", None);
+        registry.register(Action::CopyDiagnostic, "debug.copy_diagnostic", "Copy full diagnostic", None);
+        for slot in 0..SLOT_KEYS.len() {
+            registry.register(Action::SaveSlot(slot + 1), SAVE_SLOT_IDS[slot], SAVE_SLOT_LABELS[slot], Some(Shortcut::shift(SLOT_KEYS[slot])));
+        }
+        for slot in 0..SLOT_KEYS.len() {
+            registry.register(Action::LoadSlot(slot + 1), LOAD_SLOT_IDS[slot], LOAD_SLOT_LABELS[slot], Some(Shortcut::key(SLOT_KEYS[slot])));
+        }
+        registry.register(Action::LoadStatePicker, "state.picker", "Load state…", None);
+        registry.register(Action::ToggleAbout, "app.about", "About…", None);
+        registry.register(Action::Quit, "app.quit", "Quit", Some(Shortcut::key("Escape")));
+        return registry;
+    }
+
+    fn register(&mut self, action: Action, id: &'static str, label: &'static str, shortcut: Option<Shortcut>) {
+        debug_assert!(self.actions.iter().all(|info| info.id != id && info.action != action));
+        self.actions.push(ActionInfo { action, id, label, shortcut });
+    }
+
+    pub fn all(&self) -> &[ActionInfo] {
+        return &self.actions;
+    }
+
+    pub fn get(&self, action: Action) -> &ActionInfo {
+        return self.actions.iter().find(|info| info.action == action).expect("action not registered");
+    }
+
+    /* Actions whose (localized) label fuzzily matches the query, best match first */
+    pub fn search(&self, query: &str, label: impl Fn(&ActionInfo) -> String) -> Vec<&ActionInfo> {
+        let mut matches: Vec<(i32, usize, &ActionInfo)> = self.actions.iter().enumerate()
+            .filter_map(|(index, info)| fuzzy_score(query, &label(info)).map(|score| (score, index, info)))
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        return matches.into_iter().map(|(_, _, info)| info).collect();
+    }
+}
+
+/* Case-insensitive subsequence match; consecutive characters and word starts score higher, gaps lower */
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        let found = (position..text.len()).find(|&i| text[i] == wanted)?;
+        score += 1;
+        if previous.map_or(false, |p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - position) as i32;
+        previous = Some(found);
+        position = found + 1;
+    }
+    return Some(score);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_unique_and_lookup() {
+        let registry = ActionRegistry::new();
+        assert_eq!(registry.get(Action::Step).id, "debug.step");
+        assert_eq!(registry.get(Action::ToggleStats).label, "Stats");
+        assert_eq!(registry.get(Action::SaveSlot(10)).shortcut, Some(Shortcut::shift("F10")));
+        assert_eq!(registry.get(Action::LoadSlot(1)).id, "state.load_1");
+        assert_eq!(registry.get(Action::ResizeWindow(3)).id, "view.resize_3x");
+        assert_eq!(registry.get(Action::ToggleSetting("video.vignette")).label, "video.vignette");
+        for (i, a) in registry.all().iter().enumerate() {
+            for b in &registry.all()[i + 1..] {
+                assert_ne!(a.id, b.id);
+                assert_ne!(a.action, b.action);
+                assert!(a.shortcut.is_none() || a.shortcut != b.shortcut);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shortcut_display() {
+        assert_eq!(Shortcut::ctrl_shift("P").to_string(), "Ctrl+Shift+P");
+        assert_eq!(Shortcut::key("F5").to_string(), "F5");
+        assert_eq!(Shortcut::shift("F10").to_string(), "Shift+F10");
+    }
+
+    #[test]
+    fn test_fuzzy_subsequence() {
+        assert!(fuzzy_score("stp", "Step instruction").is_some());
+        assert!(fuzzy_score("STEP", "Step instruction").is_some());
+        assert_eq!(fuzzy_score("xyz", "Step instruction"), None);
+        assert_eq!(fuzzy_score("pets", "Step"), None);
+        assert_eq!(fuzzy_score("", "Step"), Some(0));
+    }
+
+    #[test]
+    fn test_fuzzy_prefers_consecutive_and_word_starts() {
+        let consecutive = fuzzy_score("sta", "Stats").unwrap();
+        let scattered = fuzzy_score("sta", "Sound timeline").unwrap_or(i32::MIN);
+        assert!(consecutive > scattered);
+        assert!(fuzzy_score("t", "a t").unwrap() > fuzzy_score("t", "aat").unwrap());
+        assert!(fuzzy_score("ct", "Coin timer").unwrap() > fuzzy_score("ct", "Cxxxxt").unwrap());
+    }
+
+    #[test]
+    fn test_search_orders_by_score() {
+        let registry = ActionRegistry::new();
+        let english = |info: &ActionInfo| info.label.to_string();
+        let results: Vec<Action> = registry.search("step", english).iter().map(|info| info.action).collect();
+        assert_eq!(results.first(), Some(&Action::Step));
+        assert!(registry.search("zzzz", english).is_empty());
+        assert_eq!(registry.search("", english).len(), registry.all().len());
+        let renamed: Vec<Action> = registry.search("salir", |info| if info.action == Action::Quit { "Salir".to_string() } else { info.label.to_string() })
+            .iter().map(|info| info.action).collect();
+        assert_eq!(renamed, vec![Action::Quit]);
+    }
+}
@@ -0,0 +1,62 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(test)]
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/* The process allocator: System, counting live bytes for the soak's memory cap and, in test builds,
+   allocations per thread for allocation-budget tests */
+pub struct CountingAllocator;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(test)]
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+            /* Thread-locals may already be gone while a thread shuts down */
+            #[cfg(test)]
+            ALLOCATIONS.try_with(|count| count.set(count.get() + 1)).ok();
+        }
+        return ptr;
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+pub fn live_bytes() -> usize {
+    return LIVE_BYTES.load(Ordering::Relaxed);
+}
+
+/* Allocations made by the calling thread so far */
+#[cfg(test)]
+pub fn thread_allocations() -> usize {
+    return ALLOCATIONS.with(|count| count.get());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_live_bytes_and_allocations() {
+        let before = thread_allocations();
+        let buffer = vec![0u8; 1 << 20];
+        assert_eq!(thread_allocations(), before + 1);
+        /* Other test threads allocate concurrently, so only a lower bound holds */
+        assert!(live_bytes() >= buffer.len());
+        drop(buffer);
+        assert_eq!(thread_allocations(), before + 1);
+    }
+}
@@ -0,0 +1,167 @@
+// Conditions the emulator normally tolerates without a word, collected in strict mode so CI runs of the
+// golden and soak tests can fail on them. Reports are JSON for the scripts that read CI logs.
+use std::fmt;
+
+/* Anomalies kept per sink; a program stuck misbehaving would otherwise report every instruction */
+pub const MAX_ANOMALIES: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /* Harmless on the cabinet, but the ROM or the host isn't doing what we assume it does */
+    Warning,
+    /* The game itself has gone wrong */
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnomalyKind {
+    RomWrite,
+    UnmappedInput,
+    UnmappedOutput,
+    UndocumentedOpcode,
+    UnexpectedReset,
+    StackOutOfBounds,
+    /* Reported by the host around the core, not by the machine */
+    DroppedSoundEvents,
+    DroppedFrames,
+}
+
+impl AnomalyKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnomalyKind::RomWrite => "rom_write",
+            AnomalyKind::UnmappedInput => "unmapped_input",
+            AnomalyKind::UnmappedOutput => "unmapped_output",
+            AnomalyKind::UndocumentedOpcode => "undocumented_opcode",
+            AnomalyKind::UnexpectedReset => "unexpected_reset",
+            AnomalyKind::StackOutOfBounds => "stack_out_of_bounds",
+            AnomalyKind::DroppedSoundEvents => "dropped_sound_events",
+            AnomalyKind::DroppedFrames => "dropped_frames",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            AnomalyKind::UnexpectedReset | AnomalyKind::StackOutOfBounds => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    pub frame: u64,
+    /* The CPU cycle, counted from power-on, the instruction started on */
+    pub cycle: u64,
+    /* The instruction that caused it */
+    pub pc: u16,
+    pub description: String,
+}
+
+impl Anomaly {
+    pub fn to_json(&self) -> String {
+        return format!(
+            "{{\"kind\":\"{}\",\"severity\":\"{}\",\"frame\":{},\"cycle\":{},\"pc\":{},\"description\":{}}}",
+            self.kind.name(), self.kind.severity().name(), self.frame, self.cycle, self.pc, json_string(&self.description)
+        );
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} at frame {}, cycle {}, PC ${:04X}: {}", self.kind.severity().name(), self.kind.name(), self.frame, self.cycle, self.pc, self.description)
+    }
+}
+
+/* Collects anomalies in the order they happened, up to MAX_ANOMALIES, counting the rest */
+#[derive(Default)]
+pub struct AnomalySink {
+    anomalies: Vec<Anomaly>,
+    overflow: u64,
+}
+
+impl AnomalySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&mut self, anomaly: Anomaly) {
+        if self.anomalies.len() == MAX_ANOMALIES {
+            self.overflow += 1;
+            return;
+        }
+        self.anomalies.push(anomaly);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.anomalies.is_empty();
+    }
+
+    pub fn anomalies(&self) -> &[Anomaly] {
+        return &self.anomalies;
+    }
+
+    /* Anomalies seen after the sink filled up */
+    pub fn overflow(&self) -> u64 {
+        return self.overflow;
+    }
+}
+
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anomaly(kind: AnomalyKind) -> Anomaly {
+        return Anomaly { kind, frame: 12, cycle: 400_000, pc: 0x1A32, description: "LDA \"x\"\n".to_string() };
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            anomaly(AnomalyKind::RomWrite).to_json(),
+            "{\"kind\":\"rom_write\",\"severity\":\"warning\",\"frame\":12,\"cycle\":400000,\"pc\":6706,\"description\":\"LDA \\\"x\\\"\\u000a\"}"
+        );
+        assert!(anomaly(AnomalyKind::UnexpectedReset).to_json().starts_with("{\"kind\":\"unexpected_reset\",\"severity\":\"error\","));
+    }
+
+    #[test]
+    fn test_sink_keeps_the_first_and_counts_the_rest() {
+        let mut sink = AnomalySink::new();
+        assert!(sink.is_empty());
+        sink.report(anomaly(AnomalyKind::StackOutOfBounds));
+        for _ in 1..MAX_ANOMALIES + 3 {
+            sink.report(anomaly(AnomalyKind::UndocumentedOpcode));
+        }
+        assert_eq!((sink.anomalies().len(), sink.overflow()), (MAX_ANOMALIES, 3));
+        assert_eq!(sink.anomalies()[0].kind, AnomalyKind::StackOutOfBounds);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(anomaly(AnomalyKind::UnmappedInput).to_string(), "warning unmapped_input at frame 12, cycle 400000, PC $1A32: LDA \"x\"\n");
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+use crate::game_state::GameState;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunState {
+    Running,
+    IdlePaused,
+}
+
+pub struct IdlePolicy {
+    pub timeout: Option<Duration>,
+}
+
+impl IdlePolicy {
+    pub fn from_minutes(minutes: u32) -> Self {
+        let timeout = if minutes == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(minutes as u64 * 60))
+        };
+        Self { timeout }
+    }
+}
+
+impl RunState {
+    pub fn next(self, policy: &IdlePolicy, idle_for: Duration, game_state: GameState, input_received: bool) -> RunState {
+        match self {
+            RunState::IdlePaused => {
+                if input_received {
+                    RunState::Running
+                } else {
+                    RunState::IdlePaused
+                }
+            },
+            RunState::Running => {
+                match policy.timeout {
+                    Some(timeout) if game_state == GameState::Attract && idle_for >= timeout => RunState::IdlePaused,
+                    _ => RunState::Running,
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_minutes() {
+        assert_eq!(IdlePolicy::from_minutes(0).timeout, None);
+        assert_eq!(IdlePolicy::from_minutes(5).timeout, Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn test_running_before_timeout() {
+        let policy = IdlePolicy::from_minutes(5);
+        let state = RunState::Running.next(&policy, Duration::from_secs(299), GameState::Attract, false);
+        assert_eq!(state, RunState::Running);
+    }
+
+    #[test]
+    fn test_pause_after_timeout_in_attract() {
+        let policy = IdlePolicy::from_minutes(5);
+        let state = RunState::Running.next(&policy, Duration::from_secs(300), GameState::Attract, false);
+        assert_eq!(state, RunState::IdlePaused);
+    }
+
+    #[test]
+    fn test_no_pause_while_playing() {
+        let policy = IdlePolicy::from_minutes(5);
+        let state = RunState::Running.next(&policy, Duration::from_secs(3_600), GameState::Playing, false);
+        assert_eq!(state, RunState::Running);
+    }
+
+    #[test]
+    fn test_no_pause_when_disabled() {
+        let policy = IdlePolicy::from_minutes(0);
+        let state = RunState::Running.next(&policy, Duration::from_secs(3_600), GameState::Attract, false);
+        assert_eq!(state, RunState::Running);
+    }
+
+    #[test]
+    fn test_input_resumes() {
+        let policy = IdlePolicy::from_minutes(5);
+        let state = RunState::IdlePaused.next(&policy, Duration::from_secs(3_600), GameState::Attract, false);
+        assert_eq!(state, RunState::IdlePaused);
+        let state = state.next(&policy, Duration::ZERO, GameState::Attract, true);
+        assert_eq!(state, RunState::Running);
+    }
+}
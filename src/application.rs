@@ -1,224 +1,2511 @@
 use eframe::egui::*;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem;
 use std::thread;
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::time::{Duration, Instant};
-use crate::audio::AudioHandler;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use intel8080::instruction;
+use crate::actions::{Action, ActionInfo, ActionRegistry};
+use crate::app_state::{IdlePolicy, RunState};
+use crate::audio;
+use crate::audio_queue::AudioCommand;
+use crate::av_sync::{self, Stamp, StampReceiver, SyncMonitor};
+use crate::cheats;
+use crate::coverage::{self, ByteClass, CoverageMap};
+use crate::debug::{DebugCommand, DebugSnapshot};
+use crate::debug_budget::{self, DebugBufferBudget};
+use crate::diagnostic::{self, BuildInfo};
+use crate::displays::{self, Monitor, MonitorSource, SavedMonitor, SystemMonitors, Target};
+use crate::emulator_core::EmulatorCore;
+use crate::file_access;
+use crate::frame_exchange::FrameExchange;
+use crate::frame_pacing::{self, FrameScheduler};
+use crate::game_state::{GameState, StartLamps};
+#[cfg(feature = "gamepad")]
+use crate::gamepad::Gamepads;
+use crate::health::HealthFlags;
+use crate::hotkeys::{Binding, Chord, ControlPreset, Focus, HotkeyManager, KeyBindings};
+use crate::input::InputManager;
+use crate::input_sources::{self, PadAssignments};
+use crate::input_log::Transition;
+use crate::io_log::IoLogEntry;
+use crate::key_capture::{CaptureOutcome, KeyCapture};
+use crate::latency::LatencyMeter;
+use crate::listing::{self, ListingOptions};
+use crate::machine::{self, DipSwitch, DipSwitches, InputShaper, InputState, LogicalInput};
+use crate::memory_access;
+use crate::macros::{self, MacroPlayer, MacroRecorder};
+use crate::platform;
+use crate::png;
+use crate::ram_map;
+use crate::renderer::Backend;
+use crate::repaint::{Consumer, RepaintScheduler};
+use crate::romset::{self, Normalized};
+use crate::run_mode::{self, AudioPolicy, InputSource, Mode, ModeCommand, SchedulerPolicy};
+use crate::save_slots::{self, SlotCommand, SlotHeader, Thumbnail};
+use crate::settings::{self, Control, Performance, Section, SettingInfo, Settings};
+use crate::sound_latch::{self, SOUNDS};
+use crate::sound_timeline::{SoundEvent, SoundTimeline};
+use crate::starfield::Starfield;
+#[cfg(feature = "http-state")]
+use crate::state_server::{self, GameSnapshot, StateServer};
+use crate::symbols::{self, SymbolTable};
+use crate::shared_control::{ControlCommand, SharedControl};
+#[cfg(feature = "tracing")]
+use crate::span_stats;
+use crate::span_stats::traced;
+use crate::state_watcher::{StateEvent, StateWatcher};
+use crate::stats::{self, OverrunPolicy, OverrunVerdict, PerfStats, SessionStats};
+use crate::strings::{Language, Message, Strings};
+use crate::test_rom;
+use crate::theme;
+use crate::toast::Toasts;
+use crate::video::{self, FrameBlender, Palette, Rgba, VideoAdjust, VideoOptions};
+use crate::wave_stats::{self, WaveRecord, WaveSample, WaveStats, WaveWatcher};
+use crate::window_size::{self, Chrome};
+use crate::workers::{Outcome, Task, WorkerPool};
 
-const SCREEN_WIDTH: usize = 256;
-const SCREEN_HEIGHT: usize = 224;
-const SCALE: usize = 2;
+pub const APP_NAME: &str = "Space Invaders Emulator";
+const SCREEN_WIDTH: usize = video::WIDTH;
+const SCREEN_HEIGHT: usize = video::HEIGHT;
+pub const SCALE: usize = 2;
 const FRAME_BUFFER_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
-const ROM_SIZE: usize = 8_192;
 const WHITE: Color32 = Color32::WHITE;
-const BLACK: Color32 = Color32::BLACK;
 const GREEN: Color32 = Color32::GREEN;
-const RED: Color32 = Color32::RED;
+const LAMP_LIT: Color32 = Color32::from_rgb(255, 196, 0);
+const LAMP_DARK: Color32 = Color32::from_gray(70);
+const SOUND_HISTORY_FRAMES: u64 = 600;
+const FAST_FORWARD_MULTIPLIER: u32 = 4;
+const TURBO_SPEED: u32 = 2;
+const COVERAGE_PUBLISH_FRAMES: u64 = 30;
+const COVERAGE_REPORT_FILE: &str = "coverage.txt";
+const SCREENSHOT_DIR: &str = "screenshots";
+const UNEXPECTED_RESET_NOTICE: &str = "debug.unexpected_reset";
+const BREAKPOINT_NOTICE: &str = "routines.breakpoint_hit";
+const TIMELINE_ROW_HEIGHT: f32 = 16.0;
+const TOAST_REFRESH: Duration = Duration::from_millis(100);
+const PAUSED_REFRESH: Duration = Duration::from_millis(250);
+const STATS_REFRESH: Duration = Duration::from_millis(250);
+const TIMELINE_REFRESH: Duration = Duration::from_millis(100);
+const COVERAGE_REFRESH: Duration = Duration::from_millis(500);
+const TASKS_REFRESH: Duration = Duration::from_millis(100);
+/* How often the monitor list is re-read, to notice a fullscreen display being unplugged */
+const MONITOR_POLL: Duration = Duration::from_secs(2);
+/* How long the status bar keeps warning after the last frame that raised a health flag */
+const HEALTH_WARNING_HOLD: Duration = Duration::from_secs(3);
+const WORKER_THREADS: usize = 2;
+const TIMELINE_FRAME_WIDTH: f32 = 2.0;
+const ROUTINE_LISTING_LINES: usize = 48;
+
+/* The set zipped as MAME keeps it, then single-file dumps, tried in that order when invaders.h isn't there */
+const ARCHIVE_ROM_NAME: &str = "invaders.zip";
+const COMBINED_ROM_NAMES: [&str; 2] = ["invaders.rom", "invaders.bin"];
+
+pub fn load_rom() -> Result<[u8; machine::ROM_SIZE], Message> {
+    let normalized = load_normalized_rom()?;
+    let strings = Strings::new(Language::English);
+    for normalization in &normalized.applied {
+        println!("ROM: {}", strings.message(&normalization.message()));
+    }
+    return Ok(normalized.rom);
+}
+
+/* IPS patches in order, each all or nothing; one note per patch with how many records it applied */
+pub fn apply_patches(rom: &mut [u8; machine::ROM_SIZE], paths: &[String]) -> Result<Vec<Message>, Message> {
+    let mut notes = vec![];
+    for path in paths {
+        let data = file_access::read_for_message(path)?;
+        let records = romset::parse_ips(path, &data)?.apply(path, rom)?;
+        notes.push(Message::new("rom.patched", vec![path.clone(), records.to_string()]));
+    }
+    return Ok(notes);
+}
+
+pub fn load_normalized_rom() -> Result<Normalized, Message> {
+    let read = |name: &str| file_access::read_for_message(name);
+    if !Path::new(romset::PART_NAMES[0]).exists() {
+        if Path::new(ARCHIVE_ROM_NAME).exists() {
+            return load_archive(Path::new(ARCHIVE_ROM_NAME));
+        }
+        if let Some(name) = COMBINED_ROM_NAMES.iter().find(|name| Path::new(name).exists()) {
+            return romset::normalize_combined(name, &read(name)?);
+        }
+    }
+    let mut parts: [Vec<u8>; 4] = Default::default();
+    for (index, name) in romset::PART_NAMES.iter().enumerate() {
+        parts[index] = read(name)?;
+    }
+    return romset::normalize_parts(&parts);
+}
+
+/* Only zip is read; a 7z needs an LZMA decoder this build doesn't carry */
+pub fn load_archive(path: &Path) -> Result<Normalized, Message> {
+    let file = path.display().to_string();
+    let is_zip = path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("zip"));
+    if !is_zip {
+        return Err(Message::new("rom.archive_unsupported", vec![file]));
+    }
+    let data = file_access::read_for_message(path)?;
+    return romset::resolve_archive(&file, &data);
+}
+
+fn handle_slot_command(core: &mut EmulatorCore, command: SlotCommand, data_dir: &Path, pixels: &[Color32]) -> Message {
+    match command {
+        SlotCommand::Save(slot) => {
+            let rgba: Vec<[u8; 4]> = pixels.iter().map(|pixel| pixel.to_array()).collect();
+            let header = SlotHeader {
+                timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+                rom_checksum: core.rom_checksum(),
+                thumbnail: Thumbnail::downscale(&rgba, SCREEN_WIDTH * SCALE, SCREEN_HEIGHT * SCALE, save_slots::THUMBNAIL_FACTOR),
+            };
+            let bytes = save_slots::encode(&header, &core.save_state());
+            let result = std::fs::create_dir_all(data_dir).and_then(|_| std::fs::write(save_slots::slot_path(data_dir, slot), bytes));
+            match result {
+                Ok(_) => Message::new("slots.saved", vec![slot.to_string()]),
+                Err(error) => Message::new("slots.save_failed", vec![slot.to_string(), error.to_string()]),
+            }
+        },
+        SlotCommand::Load(slot) => {
+            let bytes = match std::fs::read(save_slots::slot_path(data_dir, slot)) {
+                Ok(bytes) => bytes,
+                Err(_) => return Message::new("slots.is_empty", vec![slot.to_string()]),
+            };
+            match save_slots::decode(&bytes, core.rom_checksum()) {
+                Ok(loaded) => {
+                    core.load_state(&loaded.state);
+                    if loaded.rom_mismatch {
+                        Message::new("slots.loaded_rom_mismatch", vec![slot.to_string()])
+                    } else {
+                        Message::new("slots.loaded", vec![slot.to_string()])
+                    }
+                },
+                Err(error) => Message::new("slots.load_failed", vec![slot.to_string(), error.to_string()]),
+            }
+        },
+    }
+}
+
+fn coverage_color(class: ByteClass) -> Color32 {
+    match class {
+        ByteClass::Executed => Color32::from_rgb(80, 220, 80),
+        ByteClass::Operand => Color32::from_rgb(40, 130, 40),
+        ByteClass::Data => Color32::from_rgb(80, 140, 255),
+        ByteClass::Untouched => Color32::from_gray(40),
+    }
+}
+
+fn has_render_context(cc: &eframe::CreationContext<'_>, renderer: Backend) -> bool {
+    match renderer {
+        Backend::Glow => cc.gl.is_some(),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => cc.wgpu_render_state.is_some(),
+        #[cfg(not(feature = "wgpu"))]
+        Backend::Wgpu => false,
+    }
+}
+
+fn frame_image(pixels: &[Rgba]) -> Arc<ColorImage> {
+    return Arc::new(ColorImage { size: [SCREEN_WIDTH * SCALE, SCREEN_HEIGHT * SCALE], pixels: scale_frame(pixels) });
+}
+
+pub fn scale_frame(pixels: &[Rgba]) -> Vec<Color32> {
+    let mut scaled = Vec::with_capacity(FRAME_BUFFER_SIZE * SCALE * SCALE);
+    for row in pixels.chunks(SCREEN_WIDTH) {
+        let current_row: Vec<Color32> = row.iter()
+            .flat_map(|&[r, g, b, _]| std::iter::repeat(Color32::from_rgb(r, g, b)).take(SCALE))
+            .collect();
+        for _ in 0..SCALE {
+            scaled.extend_from_slice(&current_row);
+        }
+    }
+    return scaled;
+}
 
 pub struct App {
-    frame_buffer: Arc<Mutex<Box<Vec<Color32>>>>,
-    device1: Arc<Mutex<u8>>,
-    device2: Arc<Mutex<u8>>,
+    frame_buffer: Arc<FrameExchange<ColorImage>>,
+    display_texture: Option<(TextureHandle, u64)>,
+    control: Arc<SharedControl>,
+    ui_ready: Option<Sender<()>>,
+    ram: Vec<u8>,
+    ram_reply: Option<Receiver<Vec<u8>>>,
+    /* The registers the diagnostic was asked for, waiting on the memory around their PC */
+    diagnostic_reply: Option<(DebugSnapshot, Range<u16>, Receiver<Vec<u8>>)>,
+    /* VBlank and sound-start stamps from the emulation and audio threads */
+    av_stamps: Option<(StampReceiver, StampReceiver)>,
+    av_sync: SyncMonitor,
+    state_watcher: StateWatcher,
+    toasts: Toasts,
+    /* Exports, so the UI thread never encodes or writes a file itself */
+    workers: WorkerPool,
+    settings: Settings,
+    input_manager: InputManager,
+    pad_assignments: PadAssignments,
+    /* None when the host has no gamepad support */
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<Gamepads>,
+    wave_watcher: WaveWatcher,
+    wave_stats: WaveStats,
+    /* The last finished game, shown until closed */
+    game_summary: Option<Vec<WaveRecord>>,
+    run_state: RunState,
+    session_stats: SessionStats,
+    perf_stats: Arc<Mutex<PerfStats>>,
+    /* Copies of shared data, re-read only when the repaint scheduler says a panel is due */
+    perf_view: PerfStats,
+    timeline_view: Option<SoundTimeline>,
+    coverage_view: Option<(CoverageMap, TextureHandle)>,
+    repaint: RepaintScheduler,
+    overrun_policy: OverrunPolicy,
+    show_performance: bool,
+    show_settings: bool,
+    settings_query: String,
+    confirm_reset_all: bool,
+    last_update: Instant,
+    show_stats: bool,
+    frame_counter: Arc<AtomicU64>,
+    /* Health flags raised since the UI last looked, ORed together by the emulation thread */
+    health: Arc<AtomicU8>,
+    recent_health: Option<(HealthFlags, Instant)>,
+    key_bindings: KeyBindings,
+    hotkeys: HotkeyManager,
+    hotkey_warning: Option<String>,
+    macro_recorder: Option<MacroRecorder>,
+    new_macro_name: String,
+    macro_key_capture: KeyCapture,
+    show_controls: bool,
+    show_about: bool,
+    renderer: Backend,
+    /* --strict: every anomaly the core or the host around it tolerates is printed on stdout as JSON */
+    strict: bool,
+    control_key_capture: KeyCapture,
+    controls_warning: Option<String>,
+    applied_high_contrast: Option<bool>,
+    show_macros: bool,
+    debug_snapshot: Arc<Mutex<Option<DebugSnapshot>>>,
+    debug_paused: bool,
+    fast_forward: bool,
+    /* The turbo key is held this update */
+    turbo: bool,
+    rom_error: Option<Message>,
+    sound_timeline: Arc<Mutex<SoundTimeline>>,
+    debug_budget: DebugBufferBudget,
+    show_sound_timeline: bool,
+    show_sound_test: bool,
+    /* Labels and the ROM they describe, when it is the original program */
+    routines: Option<(SymbolTable, Vec<u8>)>,
+    /* The running ROM, empty until one is loaded */
+    rom: Vec<u8>,
+    /* Base set, patches and resulting checksum, for issue reports */
+    rom_identity: String,
+    show_routines: bool,
+    routines_by_name: bool,
+    routine_selected: Option<u16>,
+    /* Routine entries with their breakpoint box ticked */
+    routine_breakpoints: Vec<u16>,
+    coverage: Arc<Mutex<Option<CoverageMap>>>,
+    /* Some while measuring input latency */
+    latency: Arc<Mutex<Option<LatencyMeter>>>,
+    show_latency: bool,
+    /* Session only, like the other debug aids */
+    infinite_lives: bool,
+    show_coverage: bool,
+    show_input_log: bool,
+    show_dip_switches: bool,
+    /* The switches the running machine sees; settings.dip_switches may be ahead of it until a reset */
+    live_dip_switches: DipSwitches,
+    actions: ActionRegistry,
+    palette_open: bool,
+    palette_query: String,
+    palette_selected: usize,
+    notice_rx: Option<Receiver<Message>>,
+    data_dir: PathBuf,
+    show_state_picker: bool,
+    slot_headers: Vec<Option<SlotHeader>>,
+    slot_textures: Vec<Option<TextureHandle>>,
+    strings: Strings,
+    game_pixel_points: f32,
+    chrome: Chrome,
+    monitor_size: Option<Vec2>,
+    native_pixels_per_point: Option<f32>,
+    monitors: Vec<Monitor>,
+    monitors_polled: Option<Instant>,
+    /* The display the window went fullscreen on, None when windowed or fullscreen wherever it was */
+    fullscreen_on: Option<Monitor>,
+    fullscreen: bool,
+    /* Sent a frame after the move, once the window is on the chosen monitor */
+    fullscreen_pending: bool,
+    /* Where the window was before being moved for fullscreen, in points */
+    windowed_position: Option<Pos2>,
+    checked_on_screen: bool,
+    /* With the port it was asked for, so a port change restarts it */
+    #[cfg(feature = "http-state")]
+    state_server: Option<(u16, StateServer)>,
+    #[cfg(feature = "http-state")]
+    state_published: Instant,
 }
 
 impl Default for App {
     fn default() -> Self {
+        let debug_budget = DebugBufferBudget::new(debug_budget::megabytes(debug_budget::DEFAULT_BUDGET_MB));
+        let sound_timeline = SoundTimeline::new(SOUND_HISTORY_FRAMES).with_budget(debug_budget.register("sound_timeline", 2, mem::size_of::<SoundEvent>()));
         Self {
-            frame_buffer: Arc::new(Mutex::new(Box::new(vec![Color32::BLACK; FRAME_BUFFER_SIZE * SCALE * SCALE]))),
-            device1: Arc::new(Mutex::new(0)),
-            device2: Arc::new(Mutex::new(0)),
+            frame_buffer: Arc::new(FrameExchange::new(ColorImage::new([SCREEN_WIDTH * SCALE, SCREEN_HEIGHT * SCALE], Color32::BLACK))),
+            display_texture: None,
+            control: Arc::new(SharedControl::new()),
+            ui_ready: None,
+            ram: vec![0; ram_map::WORK_RAM_SIZE],
+            ram_reply: None,
+            diagnostic_reply: None,
+            av_stamps: None,
+            av_sync: SyncMonitor::new(),
+            state_watcher: StateWatcher::new(),
+            toasts: Toasts::new(),
+            workers: WorkerPool::new(WORKER_THREADS),
+            settings: Settings::default(),
+            input_manager: InputManager::new(),
+            pad_assignments: PadAssignments::new(),
+            #[cfg(feature = "gamepad")]
+            gamepads: Gamepads::new().map_err(|error| println!("Warning: gamepads are unavailable: {}", error)).ok(),
+            wave_watcher: WaveWatcher::new(),
+            wave_stats: WaveStats::new(),
+            game_summary: None,
+            run_state: RunState::Running,
+            session_stats: SessionStats::new(),
+            perf_stats: Arc::new(Mutex::new(PerfStats::default())),
+            perf_view: PerfStats::default(),
+            timeline_view: None,
+            coverage_view: None,
+            repaint: RepaintScheduler::new(),
+            overrun_policy: OverrunPolicy::new(),
+            show_performance: false,
+            show_settings: false,
+            settings_query: String::new(),
+            confirm_reset_all: false,
+            last_update: Instant::now(),
+            show_stats: false,
+            frame_counter: Arc::new(AtomicU64::new(0)),
+            health: Arc::new(AtomicU8::new(0)),
+            recent_health: None,
+            key_bindings: KeyBindings::default(),
+            hotkeys: HotkeyManager::new(&ActionRegistry::new(), &KeyBindings::default(), &[]),
+            hotkey_warning: None,
+            macro_recorder: None,
+            new_macro_name: String::new(),
+            macro_key_capture: KeyCapture::default(),
+            show_controls: false,
+            show_about: false,
+            renderer: Backend::Glow,
+            strict: false,
+            control_key_capture: KeyCapture::default(),
+            controls_warning: None,
+            applied_high_contrast: None,
+            show_macros: false,
+            debug_snapshot: Arc::new(Mutex::new(None)),
+            debug_paused: false,
+            fast_forward: false,
+            turbo: false,
+            rom_error: None,
+            sound_timeline: Arc::new(Mutex::new(sound_timeline)),
+            debug_budget,
+            show_sound_timeline: false,
+            show_sound_test: false,
+            routines: None,
+            rom: vec![],
+            rom_identity: String::new(),
+            show_routines: false,
+            routines_by_name: true,
+            routine_selected: None,
+            routine_breakpoints: vec![],
+            coverage: Arc::new(Mutex::new(None)),
+            latency: Arc::new(Mutex::new(None)),
+            show_latency: false,
+            infinite_lives: false,
+            show_coverage: false,
+            show_input_log: false,
+            show_dip_switches: false,
+            live_dip_switches: DipSwitches::default(),
+            actions: ActionRegistry::new(),
+            palette_open: false,
+            palette_query: String::new(),
+            palette_selected: 0,
+            notice_rx: None,
+            data_dir: eframe::storage_dir(APP_NAME).unwrap_or_else(|| PathBuf::from(".")),
+            show_state_picker: false,
+            slot_headers: vec![],
+            slot_textures: vec![],
+            strings: Strings::new(Language::English),
+            game_pixel_points: SCALE as f32,
+            chrome: Chrome::default(),
+            monitor_size: None,
+            native_pixels_per_point: None,
+            monitors: vec![],
+            monitors_polled: None,
+            fullscreen_on: None,
+            fullscreen: false,
+            fullscreen_pending: false,
+            windowed_position: None,
+            checked_on_screen: false,
+            #[cfg(feature = "http-state")]
+            state_server: None,
+            #[cfg(feature = "http-state")]
+            state_published: Instant::now(),
         }
     }
 }
 
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let app = App::default();
-        let ctx_clone = cc.egui_ctx.clone();
-        let frame_buffer_clone = app.frame_buffer.clone();
-        let device1 = app.device1.clone();
-        let device2 = app.device2.clone();
+    pub fn new(cc: &eframe::CreationContext<'_>, builtin_rom: bool, strict: bool, renderer: Backend, patches: &[String]) -> Self {
+        let mut app = App::default();
+        app.renderer = renderer;
+        app.strict = strict;
+        println!("Renderer: {}", renderer.name());
+        if !has_render_context(cc, renderer) {
+            println!("Warning: the {} renderer started without a graphics context; the window may stay blank. Try --renderer with the other backend.", renderer.name());
+        }
+        if let Some(storage) = cc.storage {
+            app.settings = eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+        }
+        app.strings = Strings::new(app.settings.language);
+        app.apply_controls();
+        app.debug_budget.set_total(debug_budget::megabytes(app.settings.debug_buffer_budget_mb));
+        let input_log_budget = app.debug_budget.register("input_log", 1, mem::size_of::<Transition>());
+        app.input_manager.set_log_budget(input_log_budget);
+        let rom = if builtin_rom { Ok(Normalized { rom: test_rom::rom(), applied: vec![] }) } else { load_normalized_rom() };
+        let rom = rom.and_then(|mut normalized| {
+            let base = normalized.rom;
+            let notes = apply_patches(&mut normalized.rom, patches)?;
+            return Ok((normalized, base, notes));
+        });
+        match rom {
+            Ok((normalized, base, notes)) => {
+                for note in normalized.applied.iter().map(|normalization| normalization.message()).chain(notes) {
+                    let note = app.strings.message(&note);
+                    println!("ROM: {}", note);
+                    app.toasts.push(note);
+                }
+                app.start(&cc.egui_ctx, normalized.rom);
+                let names: Vec<String> = patches.iter().map(|path| Path::new(path).file_name().map_or(path.clone(), |name| name.to_string_lossy().into_owned())).collect();
+                app.rom_identity = romset::describe(&base, &names, save_slots::rom_checksum(&normalized.rom));
+            },
+            Err(error) => app.rom_error = Some(error),
+        }
+        return app;
+    }
 
-        std::thread::spawn(move || {
-            let mut rom = [0; ROM_SIZE];
-            let rom_paths: [&str; 4] = ["invaders.h", "invaders.g", "invaders.f", "invaders.e"];
-            for i in 0..4 {
-                let data = std::fs::read(rom_paths[i]).unwrap();
-                for (pos, e) in data.iter().enumerate() {
-                    rom[(i * 2048) + pos] = *e;
+    /* A set dropped on the ROM error screen; a failure replaces the error shown */
+    fn load_dropped_archive(&mut self, ctx: &egui::Context, path: &Path) {
+        match load_archive(path) {
+            Ok(normalized) => {
+                for note in normalized.applied.iter().map(|normalization| normalization.message()) {
+                    let note = self.strings.message(&note);
+                    println!("ROM: {}", note);
+                    self.toasts.push(note);
                 }
-            }
-            let memory = Box::new(intel8080::memory::Memory::new(rom));
-            let mut c = intel8080::emulator::Cpu::new(memory);
+                self.rom_error = None;
+                self.start(ctx, normalized.rom);
+                self.rom_identity = romset::describe(&normalized.rom, &[], save_slots::rom_checksum(&normalized.rom));
+            },
+            Err(error) => self.rom_error = Some(error),
+        }
+    }
+
+    fn start(&mut self, ctx: &egui::Context, rom: [u8; machine::ROM_SIZE]) {
+        let ctx_clone = ctx.clone();
+        let frame_buffer_clone = self.frame_buffer.clone();
+        let control = self.control.clone();
+        let frame_counter = self.frame_counter.clone();
+        let health = self.health.clone();
+        let perf_stats = self.perf_stats.clone();
+        let debug_snapshot = self.debug_snapshot.clone();
+        let data_dir = self.data_dir.clone();
+        let sound_timeline = self.sound_timeline.clone();
+        let coverage = self.coverage.clone();
+        let latency = self.latency.clone();
+
+        self.live_dip_switches = self.settings.dip_switches;
+        let initial_input = InputState::with_dip_switches(self.live_dip_switches);
+        self.control.set_input(&initial_input);
+        let io_log_budget = self.debug_budget.register("io_log", 4, mem::size_of::<IoLogEntry>());
+        let rom_checksum = save_slots::rom_checksum(&rom);
+        if let Some(set) = romset::identify(&rom) {
+            println!("Recognised ROM set: {} (quirks {:?})", set.name, set.quirks);
+        }
+        self.routines = symbols::for_rom(&rom).map(|table| (table, rom.to_vec()));
+        self.routine_breakpoints.clear();
+        self.rom = rom.to_vec();
+        self.rom_identity = romset::describe(&rom, &[], rom_checksum);
+        let randomize_ram = self.settings.randomize_ram;
+        let strict = self.strict;
+        self.control.set_video(self.video_options());
+        self.control.set_volume(self.settings.volume);
+        self.control.set_idle_skip(self.settings.performance.idle_skip);
+        self.send_debug_command(DebugCommand::BreakOnUnexpectedReset(self.settings.break_on_unexpected_reset));
+        let (ui_ready_tx, ui_ready_rx) = mpsc::channel();
+        self.ui_ready = Some(ui_ready_tx);
+        let (notice_tx, notice_rx) = mpsc::channel::<Message>();
+        self.notice_rx = Some(notice_rx);
 
-            let mut shift_register: u16 = 0;
-            let mut shift_register_offest: u8 = 0;
+        let epoch = Instant::now();
+        let (vblank_stamps, vblank_rx) = av_sync::channel(av_sync::VIDEO_CAPACITY, epoch);
+        let (sound_stamps, sound_rx) = av_sync::channel(av_sync::AUDIO_CAPACITY, epoch);
+        self.av_stamps = Some((vblank_rx, sound_rx));
+        self.av_sync = SyncMonitor::new();
+        let mut audio = audio::spawn(self.settings.volume, sound_stamps);
+        let prefer_performance_cores = self.settings.performance.prefer_performance_cores;
 
-            let mut audio_handler = AudioHandler::try_new();
-            let mut last_device3: u8 = 0b00000000;
-            let mut last_device5: u8 = 0b00000000;
-            let mut start = Instant::now();
+        /* The CPU's memory is a Box<dyn Memory>, which isn't Send, so the core is built on the thread that runs it */
+        std::thread::spawn(move || {
+            let mut core = EmulatorCore::new(rom, initial_input);
+            core.set_io_log_budget(io_log_budget);
+            if randomize_ram {
+                core.randomize_ram();
+            }
+            core.set_strict(strict);
+            /* Counted so far, so each frame reports only what is new */
+            let (mut sound_events_dropped, mut anomalies_printed) = (0, 0);
+            if ui_ready_rx.recv().is_err() {
+                return;
+            }
+            /* Hints only; the thread runs the same either way */
+            if let Err(error) = platform::raise_thread_priority() {
+                println!("Warning: could not raise the emulation thread's priority: {}", error);
+            }
+            if prefer_performance_cores {
+                if let Err(error) = platform::prefer_performance_cores() {
+                    println!("Warning: could not move the emulation thread to performance cores: {}", error);
+                }
+            }
+            let mut macro_player: Option<MacroPlayer> = None;
+            let mut mode = Mode::Running;
+            let mut idle = false;
+            let mut break_on_unexpected_reset = false;
+            let mut volume = control.volume();
+            let mut speed = 1;
+            let mut input_shaper = InputShaper::default();
+            let mut scheduler = FrameScheduler::new(Instant::now());
+            let starfield = Starfield::new(core.seed(), video::WIDTH, video::HEIGHT);
+            let mut background = vec![video::BLACK; video::WIDTH * video::HEIGHT];
+            let mut palette_options = VideoOptions::default();
+            let mut palette = Palette::new(&palette_options.adjust, palette_options.monochrome);
+            let mut blender = FrameBlender::new();
+            /* Native pixels for the current VRAM; a frame that doesn't follow the last one rendered (after a load or a step) isn't blended */
+            let mut render = |core: &EmulatorCore, options: VideoOptions, follows_previous: bool| {
+                if (options.adjust, options.monochrome) != (palette_options.adjust, palette_options.monochrome) {
+                    palette = Palette::new(&options.adjust, options.monochrome);
+                    palette_options = options;
+                }
+                let mut pixels = palette.convert(&core.vram());
+                if options.starfield {
+                    starfield.render(core.frame(), &mut background);
+                    video::composite(&mut pixels, &background);
+                }
+                if !follows_previous {
+                    blender.reset();
+                }
+                blender.apply(&mut pixels, options.adjust.blend);
+                return pixels;
+            };
             loop {
-                let mut tick = 0;
-                while tick < 33333 {
-                    if tick == 16667 {
-                        c.receive_interrupt(0xCF);
-                    }
-                    c.tick();
-                    match c.get_output() {
-                        Some(x) => {
-                            let (device, value) = x;
-                            match device {
-                                0x2 => {
-                                    shift_register_offest = value & 0x07;
-                                },
-                                0x3 => {
-                                    match audio_handler {
-                                        Some(ref mut ah) => {
-                                            if value & 0b00000001 == 0b00000001 && last_device3 & 0b00000001 != 0b00000001{
-                                                ah.play_sound(0);
-                                            }
-                                            if value & 0b00000010 == 0b00000010 && last_device3 & 0b00000010 != 0b00000010 {
-                                                ah.play_sound(1);
-                                            }
-                                            if value & 0b00000100 == 0b00000100 && last_device3 & 0b00000100 != 0b00000100 {
-                                                ah.play_sound(2);
-                                            }
-                                            if value & 0b00001000 == 0b00001000 && last_device3 & 0b00001000 != 0b00001000 {
-                                                ah.play_sound(3);
-                                            }
-                                            last_device3 = value;
-                                        },
-                                        None => {}
+                let mut memory_requests = vec![];
+                let commands = traced!("control_commands", control.take_commands());
+                for command in commands {
+                    match command {
+                        ControlCommand::Mode(command) => {
+                            run_mode::apply(&mut mode, command);
+                        },
+                        ControlCommand::Debug(command) => {
+                            match command {
+                                DebugCommand::Coverage(enabled) => {
+                                    if enabled {
+                                        core.enable_coverage();
+                                    } else {
+                                        core.disable_coverage();
                                     }
+                                    *coverage.lock().unwrap() = core.coverage();
                                 },
-                                0x4 => {
-                                    shift_register = ((value as u16) << 8) | (shift_register >> 8);
-                                },
-                                0x5 => {
-                                    match audio_handler {
-                                        Some(ref mut ah) => {
-                                            if value & 0b00000001 == 0b00000001 && last_device5 & 0b00000001 != 0b00000001 {
-                                                ah.play_sound(4);
-                                            }
-                                            if value & 0b00000010 == 0b00000010 && last_device5 & 0b00000010 != 0b00000010 {
-                                                ah.play_sound(5);
-                                            }
-                                            if value & 0b00000100 == 0b00000100 && last_device5 & 0b00000100 != 0b00000100 {
-                                                ah.play_sound(6);
-                                            }
-                                            if value & 0b00001000 == 0b00001000 && last_device5 & 0b00001000 != 0b00001000 {
-                                                ah.play_sound(7);
-                                            }
-                                            if value & 0b00010000 == 0b00010000 && last_device5 & 0b00010000 != 0b00010000 {
-                                                ah.play_sound(8);
-                                            }
-                                            last_device5 = value;
-                                        },
-                                        None => {}
+                                DebugCommand::ExportCoverage => {
+                                    if let Some(report) = core.coverage_report() {
+                                        let path = data_dir.join(COVERAGE_REPORT_FILE);
+                                        let notice = match std::fs::create_dir_all(&data_dir).and_then(|_| std::fs::write(&path, report)) {
+                                            Ok(()) => Message::new("coverage.exported", vec![path.display().to_string()]),
+                                            Err(error) => Message::new("coverage.export_failed", vec![error.to_string()]),
+                                        };
+                                        notice_tx.send(notice).ok();
                                     }
                                 },
-                                0x6 => {}, //OUT 6  Watchdog not implemented.
-                                _ => panic!("Invalid OUT device number.")
+                                DebugCommand::BreakOnUnexpectedReset(enabled) => break_on_unexpected_reset = enabled,
+                                DebugCommand::Breakpoints(addresses) => core.set_breakpoints(addresses),
                             }
                         },
-                        None => {}
-                    }
-                    c.set_input(0, 0b10001111);
-                    c.set_input(1, device1.lock().unwrap().clone());
-                    c.set_input(2, device2.lock().unwrap().clone());
-                    c.set_input(3, (shift_register >> (8 - shift_register_offest)) as u8);
-                    tick += 1;
-                }
-                c.receive_interrupt(0xD7);
-                
-                let vram = c.get_vram();
-                let mut rows: Vec<Vec<Color32>> = vec![];
-                let mut current_row: Vec<Color32> = vec![];
-                for index in 0..7_168 {
-                    for offset in 0..8 {
-                        let val = vram[index] >> offset & 0x1;
-                        let adjusted_ypos = (index * 8 + offset) % 256;
-                        if val == 1 {
-                            let mut color = WHITE;
-                            if adjusted_ypos < 80 {
-                                color = GREEN;
+                        ControlCommand::Slot(command) => {
+                            let image = frame_buffer_clone.latest().frame;
+                            notice_tx.send(handle_slot_command(&mut core, command, &data_dir, &image.pixels)).ok();
+                            frame_buffer_clone.replace(frame_image(&render(&core, control.video(), false)));
+                            ctx_clone.request_repaint();
+                        },
+                        ControlCommand::PlayMacro(recorded) => {
+                            if run_mode::apply(&mut mode, ModeCommand::PlayMovie) {
+                                macro_player = Some(MacroPlayer::new(&recorded, core.frame()));
                             }
-                            if adjusted_ypos > 200 && adjusted_ypos <= 220 {
-                                color = RED;
+                        },
+                        ControlCommand::Memory(request) => memory_requests.push(request),
+                        ControlCommand::SoundTest { sound, play } => {
+                            audio.send(AudioCommand::Sound(SoundEvent { frame: core.frame(), sound, active: play, sound_test: true }));
+                        },
+                        ControlCommand::PowerCycle => {
+                            for event in core.power_cycle().sound_events {
+                                audio.send(AudioCommand::Sound(event));
                             }
-                            for _ in 0..SCALE {
-                                current_row.push(color);
+                            frame_counter.store(core.frame(), Ordering::Relaxed);
+                            frame_buffer_clone.replace(frame_image(&render(&core, control.video(), false)));
+                            ctx_clone.request_repaint();
+                        },
+                    }
+                }
+                memory_access::service(&mut core, memory_requests);
+                if control.paused() != idle {
+                    idle = control.paused();
+                    run_mode::apply(&mut mode, if idle { ModeCommand::IdlePause } else { ModeCommand::IdleResume });
+                }
+                if let Mode::Stepping { instructions } = mode {
+                    for _ in 0..instructions {
+                        core.step_instruction();
+                    }
+                    frame_buffer_clone.replace(frame_image(&render(&core, control.video(), false)));
+                    ctx_clone.request_repaint();
+                    run_mode::apply(&mut mode, ModeCommand::StepFinished);
+                }
+                if mode.input_source() != InputSource::Movie {
+                    macro_player = None;
+                }
+                *debug_snapshot.lock().unwrap() = if mode.debugger_attached() { Some(core.debug_snapshot()) } else { None };
+                if mode.scheduler() == SchedulerPolicy::Suspended {
+                    thread::sleep(Duration::from_millis(16));
+                    scheduler.reset(Instant::now());
+                    continue;
+                }
+                let turbo = mode.allows_turbo() && (control.turbo() || macro_player.as_ref().map_or(false, |player| player.turbo()));
+                scheduler.set_speed(if turbo { TURBO_SPEED } else { 1 }, Instant::now());
+                /* Before this tick's sound, so the mixer thins it at the new speed */
+                if mode.frames_per_tick() * scheduler.speed() != speed {
+                    speed = mode.frames_per_tick() * scheduler.speed();
+                    audio.send(AudioCommand::Speed(speed as f32));
+                }
+                /* The core turns it off itself while coverage is recording */
+                core.set_idle_skip(control.idle_skip() && matches!(mode, Mode::FastForward { .. }) && !break_on_unexpected_reset);
+                for _ in 0..mode.frames_per_tick() {
+                    let live_input = control.input();
+                    let frame_input = match (mode.input_source(), macro_player.as_mut()) {
+                        (InputSource::Movie, Some(player)) => macros::merge(&live_input, &player.input_at(core.frame())),
+                        _ => live_input,
+                    };
+                    if macro_player.as_ref().map_or(false, |player| player.finished()) {
+                        macro_player = None;
+                        run_mode::apply(&mut mode, ModeCommand::MovieFinished);
+                    }
+                    core.set_input(input_shaper.shape(&frame_input));
+                    let output = core.step_frame();
+                    frame_counter.store(core.frame(), Ordering::Relaxed);
+                    health.fetch_or(output.health.bits(), Ordering::Relaxed);
+                    /* The VBlank that just ended the frame its sounds were stamped with */
+                    if let Some(vblank) = core.last_vblank() {
+                        vblank_stamps.push(Stamp { frame: core.frame() - 1, at: vblank });
+                    }
+                    if let Some(meter) = latency.lock().unwrap().as_mut() {
+                        meter.watch_shot(core.read_memory(ram_map::PLAYER_SHOT_STATUS), Instant::now(), core.last_vblank());
+                    }
+                    if let Some(reset) = core.take_unexpected_reset() {
+                        let from = format!("${:04X} ({})", reset.from, instruction::OPCODES[reset.opcode as usize].label);
+                        println!("Warning: execution reached $0000 from {} in frame {} without a reset", from, core.frame());
+                        if break_on_unexpected_reset && run_mode::apply(&mut mode, ModeCommand::Pause) {
+                            notice_tx.send(Message::new(UNEXPECTED_RESET_NOTICE, vec![from])).ok();
+                        }
+                    }
+                    /* The frame stopped short; the rest of it runs when play resumes */
+                    if let Some(address) = core.take_breakpoint_hit() {
+                        if run_mode::apply(&mut mode, ModeCommand::Pause) {
+                            notice_tx.send(Message::new(BREAKPOINT_NOTICE, vec![format!("${:04X}", address)])).ok();
+                        }
+                        break;
+                    }
+                    if core.frame() % COVERAGE_PUBLISH_FRAMES == 0 {
+                        if let Some(map) = core.coverage() {
+                            *coverage.lock().unwrap() = Some(map);
+                        }
+                    }
+                    if mode.records_sound_timeline() {
+                        let mut sound_timeline = sound_timeline.lock().unwrap();
+                        for event in &output.sound_events {
+                            sound_timeline.push(*event);
+                        }
+                        sound_timeline.advance(core.frame());
+                    }
+                    /* Muted frames still send releases so a looping sound can't hang */
+                    let muted = mode.audio() == AudioPolicy::Mute;
+                    traced!("audio_send", {
+                        for event in output.sound_events.iter().filter(|event| !muted || !event.active) {
+                            audio.send(AudioCommand::Sound(*event));
+                        }
+                    });
+                }
+                if control.take_blip() {
+                    audio.send(AudioCommand::Blip { frame: core.frame() });
+                }
+                if control.volume() != volume {
+                    volume = control.volume();
+                    audio.send(AudioCommand::Volume(volume));
+                }
+                traced!("audio_flush", audio.flush());
+
+                /* Publish right after VBlank; the UI presents at a fixed phase after it */
+                let options = control.video();
+                let present = !options.half_rate || core.frame() % 2 == 0;
+                /* Blending needs every native frame, presented or not */
+                if present || options.adjust.blend {
+                    let pixels = render(&core, options, true);
+                    if present {
+                        traced!("frame_slot_swap", frame_buffer_clone.publish(frame_image(&pixels), core.last_vblank()));
+                        ctx_clone.request_repaint_after(frame_pacing::PRESENT_DELAY);
+                    }
+                }
+
+                let now = Instant::now();
+                if let Some(dropped) = scheduler.advance(now) {
+                    println!("Emulation stalled for {:?} (host sleep?), resynchronizing instead of catching up.", dropped);
+                    perf_stats.lock().unwrap().record_resync(dropped);
+                    core.report_dropped_frames((dropped.as_micros() / frame_pacing::FRAME_PERIOD.as_micros()) as u64);
+                }
+                {
+                    let mut perf_stats = perf_stats.lock().unwrap();
+                    perf_stats.record_frame(scheduler.sleep_time(now).is_zero());
+                    perf_stats.audio_coalesced = audio.coalesced();
+                    perf_stats.audio_dropped = audio.dropped();
+                }
+                core.report_dropped_sound_events(audio.dropped() - sound_events_dropped);
+                sound_events_dropped = audio.dropped();
+                if let Some(sink) = core.anomalies() {
+                    for anomaly in &sink.anomalies()[anomalies_printed..] {
+                        println!("{}", anomaly.to_json());
+                    }
+                    anomalies_printed = sink.anomalies().len();
+                }
+                thread::sleep(scheduler.sleep_time(Instant::now()));
+            }
+        });
+    }
+}
+
+impl App {
+    fn execute(&mut self, ctx: &egui::Context, action: Action) {
+        match action {
+            Action::CommandPalette => {
+                self.palette_open = !self.palette_open;
+                self.palette_query.clear();
+                self.palette_selected = 0;
+            },
+            Action::TogglePause => self.toggle_debug_pause(),
+            Action::ToggleFastForward => self.toggle_fast_forward(),
+            Action::Step => {
+                if self.debug_paused {
+                    self.control.send(ControlCommand::Mode(ModeCommand::Step(1)));
+                }
+            },
+            Action::ToggleCoinBlip => self.settings.coin_blip = !self.settings.coin_blip,
+            Action::ToggleRandomizeRam => self.settings.randomize_ram = !self.settings.randomize_ram,
+            Action::ToggleHighContrast => self.settings.high_contrast = !self.settings.high_contrast,
+            Action::ToggleStarfield => self.settings.starfield = !self.settings.starfield,
+            Action::ToggleStartLamps => self.settings.start_lamps = !self.settings.start_lamps,
+            Action::ToggleStretchShortPresses => self.settings.stretch_short_presses = !self.settings.stretch_short_presses,
+            Action::ToggleHighScoreScreenshots => self.settings.high_score_screenshots = !self.settings.high_score_screenshots,
+            Action::ToggleInputLog => self.show_input_log = !self.show_input_log,
+            Action::ToggleLatency => {
+                self.show_latency = !self.show_latency;
+                *self.latency.lock().unwrap() = if self.show_latency { Some(LatencyMeter::new()) } else { None };
+            },
+            Action::ToggleInfiniteLives => self.infinite_lives = !self.infinite_lives,
+            Action::ToggleBreakOnReset => {
+                self.settings.break_on_unexpected_reset = !self.settings.break_on_unexpected_reset;
+                self.send_debug_command(DebugCommand::BreakOnUnexpectedReset(self.settings.break_on_unexpected_reset));
+            },
+            Action::ToggleDipSwitches => self.show_dip_switches = !self.show_dip_switches,
+            Action::ToggleControls => self.show_controls = !self.show_controls,
+            Action::SwapPlayers => {
+                self.settings.controls.swap_players();
+                self.apply_controls();
+            },
+            Action::ToggleStats => self.show_stats = !self.show_stats,
+            Action::ToggleMacros => self.show_macros = !self.show_macros,
+            Action::ToggleSoundTimeline => self.show_sound_timeline = !self.show_sound_timeline,
+            Action::ToggleSoundTest => self.show_sound_test = !self.show_sound_test,
+            Action::ToggleRoutines => self.show_routines = !self.show_routines,
+            Action::TogglePerformance => self.show_performance = !self.show_performance,
+            Action::ToggleSettings => self.show_settings = !self.show_settings,
+            Action::ToggleSetting(id) => {
+                if let Control::Toggle(field) = settings::info(id).control {
+                    let value = field(&mut self.settings);
+                    *value = !*value;
+                }
+            },
+            Action::ToggleCoverage => {
+                self.show_coverage = !self.show_coverage;
+                self.send_debug_command(DebugCommand::Coverage(self.show_coverage));
+            },
+            Action::ExportListing => self.export_listing(),
+            Action::CopyDiagnostic => {
+                if let Some(snapshot) = *self.debug_snapshot.lock().unwrap() {
+                    let range = diagnostic::range_around(snapshot.cpu.pc);
+                    self.diagnostic_reply = Some((snapshot, range.clone(), self.control.request_read(range)));
+                }
+            },
+            Action::SaveSlot(slot) => self.send_slot_command(SlotCommand::Save(slot)),
+            Action::LoadSlot(slot) => self.send_slot_command(SlotCommand::Load(slot)),
+            Action::LoadStatePicker => {
+                self.show_state_picker = true;
+                self.refresh_slot_headers(ctx);
+            },
+            Action::ResizeWindow(scale) => self.resize_window(ctx, scale),
+            Action::ToggleFullscreen => {
+                if self.fullscreen {
+                    self.leave_fullscreen(ctx);
+                } else {
+                    self.enter_fullscreen(ctx);
+                }
+            },
+            Action::ToggleAbout => self.show_about = !self.show_about,
+            Action::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+        }
+    }
+
+    /* Sizes the window so every game pixel covers exactly scale x scale physical pixels */
+    fn resize_window(&mut self, ctx: &egui::Context, requested: u32) {
+        let resize = window_size::fit(requested, self.chrome, ctx.pixels_per_point(), self.monitor_size.map(|size| [size.x, size.y]));
+        self.game_pixel_points = resize.game_pixel_points;
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(Vec2::from(resize.inner_size)));
+        if resize.scale != requested {
+            self.toasts.push(self.strings.tr_with("game.resize_adjusted", &[&requested.to_string(), &resize.scale.to_string()]));
+        }
+    }
+
+    /* Moves the window onto the chosen display first, since winit fullscreens on the monitor the window is on */
+    fn enter_fullscreen(&mut self, ctx: &egui::Context) {
+        let target = displays::choose(self.settings.fullscreen_display.as_ref(), &SystemMonitors);
+        if let (Target::Fallback(primary), Some(saved)) = (&target, &self.settings.fullscreen_display) {
+            self.toasts.push(self.strings.tr_with("display.fallback", &[&saved.name, &primary.name]));
+        }
+        self.fullscreen = true;
+        self.fullscreen_on = target.monitor().cloned();
+        self.monitors_polled = Some(Instant::now());
+        let Some(monitor) = &self.fullscreen_on else {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+            return;
+        };
+        let position = displays::window_position(monitor, self.points_per_unit(ctx));
+        ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(Pos2::from(position)));
+        self.fullscreen_pending = true;
+    }
+
+    fn leave_fullscreen(&mut self, ctx: &egui::Context) {
+        self.fullscreen = false;
+        self.fullscreen_on = None;
+        self.fullscreen_pending = false;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+        /* Back where it was, unless that place has gone with an unplugged monitor */
+        if let Some(position) = self.windowed_position.take() {
+            if displays::on_screen([position.x, position.y], &self.monitors, self.points_per_unit(ctx)) {
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(position));
+            }
+        }
+    }
+
+    fn points_per_unit(&self, ctx: &egui::Context) -> f32 {
+        let pixels_per_point = ctx.pixels_per_point();
+        return displays::points_per_unit(pixels_per_point, self.native_pixels_per_point.unwrap_or(pixels_per_point));
+    }
+
+    /* Re-reads the monitors now and then: a fullscreen display that has gone hands over to the primary, and a
+       window restored onto a display that is no longer there is brought back */
+    fn watch_monitors(&mut self, ctx: &egui::Context, window_position: Option<Pos2>, window_fullscreen: bool) {
+        if self.fullscreen_pending {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+            self.fullscreen_pending = false;
+            self.monitors_polled = Some(Instant::now());
+        }
+        if !self.fullscreen {
+            self.windowed_position = window_position.or(self.windowed_position);
+        }
+        /* Entering fullscreen restarts the interval, giving the window time to get there before its state is believed */
+        if self.monitors_polled.map_or(false, |polled| polled.elapsed() < MONITOR_POLL) {
+            return;
+        }
+        self.monitors_polled = Some(Instant::now());
+        /* Left through the OS rather than the action, e.g. macOS's green button */
+        if self.fullscreen && !window_fullscreen {
+            self.fullscreen = false;
+            self.fullscreen_on = None;
+        }
+        self.monitors = SystemMonitors.monitors();
+        if self.monitors.is_empty() {
+            return;
+        }
+        let points_per_unit = self.points_per_unit(ctx);
+        if let Some(monitor) = &self.fullscreen_on {
+            if !self.monitors.contains(monitor) {
+                let primary = self.monitors.iter().find(|monitor| monitor.primary).unwrap_or(&self.monitors[0]);
+                self.toasts.push(self.strings.tr_with("display.fallback", &[&monitor.name, &primary.name]));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(Pos2::from(displays::window_position(primary, points_per_unit))));
+                self.fullscreen_on = Some(primary.clone());
+                self.fullscreen_pending = true;
+            }
+        }
+        if !self.checked_on_screen {
+            if let Some(position) = window_position {
+                self.checked_on_screen = true;
+                if !displays::on_screen([position.x, position.y], &self.monitors, points_per_unit) {
+                    let primary = self.monitors.iter().find(|monitor| monitor.primary).unwrap_or(&self.monitors[0]);
+                    ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(Pos2::from(displays::window_position(primary, points_per_unit))));
+                }
+            }
+        }
+    }
+
+    fn monitor_label(&self, monitor: &SavedMonitor, primary: bool) -> String {
+        let [width, height] = monitor.size.map(|length| length.to_string());
+        let [x, y] = monitor.position.map(|coordinate| coordinate.to_string());
+        let key = if primary { "display.monitor_primary" } else { "display.monitor" };
+        return self.strings.tr_with(key, &[&monitor.name, &width, &height, &x, &y]);
+    }
+
+    /* Applies hot-plug changes, then reports what the assigned pads hold */
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepads(&mut self) -> Vec<LogicalInput> {
+        let Some(gamepads) = self.gamepads.as_mut() else {
+            return vec![];
+        };
+        for event in gamepads.poll() {
+            for notice in self.pad_assignments.apply(event) {
+                self.toasts.push(self.strings.message(&notice));
+            }
+        }
+        return gamepads.held(&self.pad_assignments);
+    }
+
+    /* Watches see work RAM as of the last serviced read; a new read goes out once the previous one is answered */
+    fn poll_ram(&mut self) -> Vec<u8> {
+        if let Some(reply) = &self.ram_reply {
+            match reply.try_recv() {
+                Ok(ram) => {
+                    self.ram = ram;
+                    self.ram_reply = None;
+                },
+                Err(TryRecvError::Empty) => {},
+                Err(TryRecvError::Disconnected) => self.ram_reply = None,
+            }
+        }
+        if self.ram_reply.is_none() {
+            let start = ram_map::WORK_RAM_START;
+            self.ram_reply = Some(self.control.request_read(start..start + ram_map::WORK_RAM_SIZE as u16));
+        }
+        return self.ram.clone();
+    }
+
+    /* Copies the full report once the emulation thread has read the memory around PC */
+    fn poll_diagnostic(&mut self, ctx: &egui::Context) {
+        let bytes = match self.diagnostic_reply.as_ref().map(|(_, _, reply)| reply.try_recv()) {
+            Some(Ok(bytes)) => bytes,
+            None | Some(Err(TryRecvError::Empty)) => return,
+            Some(Err(TryRecvError::Disconnected)) => {
+                self.diagnostic_reply = None;
+                return;
+            },
+        };
+        let Some((snapshot, range, _)) = self.diagnostic_reply.take() else {
+            return;
+        };
+        let build = BuildInfo { app: APP_NAME, version: env!("CARGO_PKG_VERSION"), renderer: self.renderer.name(), rom: &self.rom_identity };
+        let sections = [
+            ("Registers", diagnostic::registers(&snapshot)),
+            ("Memory around PC", diagnostic::hex_dump(range.start, &bytes)),
+        ];
+        let report = diagnostic::full_report(&build, self.frame_counter.load(Ordering::Relaxed), &sections);
+        ctx.output_mut(|output| output.copied_text = report);
+        self.toasts.push(self.strings.tr("debug.diagnostic_copied").to_string());
+    }
+
+    /* Follows the setting, so enabling it in the settings file starts it at launch; publishing is a swap under a lock */
+    #[cfg(feature = "http-state")]
+    fn update_state_server(&mut self, ram: &[u8], frame: u64, now: Instant) {
+        let port = self.settings.state_server_port;
+        let wanted = self.settings.state_server.then_some(port);
+        if self.state_server.as_ref().map(|(port, _)| *port) != wanted {
+            self.state_server = None;
+            if wanted.is_some() {
+                match StateServer::start(port) {
+                    Ok(server) => {
+                        self.toasts.push(self.strings.tr_with("state_server.listening", &[&server.addr().to_string()]));
+                        self.state_server = Some((port, server));
+                    },
+                    Err(error) => {
+                        self.toasts.push(self.strings.tr_with("state_server.failed", &[&port.to_string(), &error.to_string()]));
+                        self.settings.state_server = false;
+                    },
+                }
+            }
+        }
+        let Some((_, server)) = &self.state_server else {
+            return;
+        };
+        if now.saturating_duration_since(self.state_published) < state_server::PUBLISH_INTERVAL {
+            return;
+        }
+        self.state_published = now;
+        let paused = self.run_state == RunState::IdlePaused || self.debug_paused;
+        server.publish(GameSnapshot::from_ram(ram, frame, paused), Some((SCREEN_WIDTH, SCREEN_HEIGHT, Arc::new(self.native_frame()))));
+    }
+
+    /* The latest published frame at one pixel per game pixel */
+    fn native_frame(&self) -> Vec<Rgba> {
+        let image = self.frame_buffer.latest().frame;
+        let image = &image;
+        return (0..SCREEN_HEIGHT)
+            .flat_map(|y| (0..SCREEN_WIDTH).map(move |x| image.pixels[y * SCALE * SCREEN_WIDTH * SCALE + x * SCALE].to_array()))
+            .collect();
+    }
+
+    /* Automatic, so only the outcome is toasted */
+    fn save_high_score_screenshot(&mut self, score: u32) {
+        let dir = self.data_dir.join(SCREENSHOT_DIR);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let path = dir.join(format!("hiscore-{}-{}.png", score, timestamp));
+        let pixels = self.native_frame();
+        self.workers.submit(Message::new("tasks.screenshot", vec![]), move |_| {
+            let bytes = png::encode(&pixels, SCREEN_WIDTH, SCREEN_HEIGHT);
+            return match std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, bytes)) {
+                Ok(_) => Outcome::Done(Message::new("game.high_score_screenshot", vec![score.to_string(), path.display().to_string()])),
+                Err(error) => Outcome::Failed(Message::new("game.high_score_screenshot_failed", vec![score.to_string(), error.to_string()])),
+            };
+        });
+    }
+
+    /* Labelled when the ROM has symbols, with tables marked as data once coverage has seen them read. Written
+       straight to the file entry by entry, so it can be cancelled part way; a cancelled listing is deleted */
+    fn export_listing(&mut self) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let data_dir = self.data_dir.clone();
+        let path = self.data_dir.join(format!("listing-{}.asm", timestamp));
+        let rom = self.rom.clone();
+        let symbols = self.routines.as_ref().map(|(table, _)| table.clone());
+        let coverage = self.coverage.lock().unwrap().clone();
+        self.submit_task(Message::new("tasks.listing", vec![]), move |task| {
+            let options = ListingOptions { symbols: symbols.as_ref(), coverage: coverage.as_ref() };
+            let written = std::fs::create_dir_all(&data_dir).and_then(|_| {
+                let mut file = BufWriter::new(File::create(&path)?);
+                let finished = listing::export_listing_with_progress(&mut file, &rom, options, |fraction| {
+                    task.set_progress(fraction);
+                    return !task.cancelled();
+                })?;
+                file.flush()?;
+                Ok(finished)
+            });
+            return match written {
+                Ok(true) => Outcome::Done(Message::new("listing.exported", vec![path.display().to_string()])),
+                Ok(false) => {
+                    std::fs::remove_file(&path).ok();
+                    Outcome::Cancelled
+                },
+                Err(error) => Outcome::Failed(Message::new("listing.export_failed", vec![error.to_string()])),
+            };
+        });
+    }
+
+    /* For exports the user asked for: toasted when they start and again when they finish */
+    fn submit_task(&mut self, label: Message, job: impl FnOnce(&Task) -> Outcome + Send + 'static) {
+        self.toasts.push(self.strings.tr_with("tasks.started", &[&self.strings.message(&label)]));
+        self.workers.submit(label, job);
+    }
+
+    fn poll_workers(&mut self) {
+        for finished in self.workers.take_finished() {
+            let message = match finished.outcome {
+                Outcome::Done(message) | Outcome::Failed(message) => self.strings.message(&message),
+                Outcome::Cancelled => self.strings.tr_with("tasks.cancelled", &[&self.strings.message(&finished.label)]),
+            };
+            self.toasts.push(message);
+        }
+    }
+
+    /* Queued and running exports with their progress, each cancellable */
+    fn tasks_menu(&mut self, ui: &mut Ui) {
+        let tasks = self.workers.tasks();
+        if tasks.is_empty() {
+            return;
+        }
+        self.repaint.every(Consumer::Tasks, TASKS_REFRESH, Instant::now());
+        ui.menu_button(self.strings.tr_with("tasks.menu", &[&tasks.len().to_string()]), |ui| {
+            for task in &tasks {
+                ui.horizontal(|ui| {
+                    ui.label(self.strings.message(&task.label));
+                    let progress = egui::ProgressBar::new(task.progress).desired_width(120.0);
+                    ui.add(if task.started { progress.show_percentage() } else { progress.text(self.strings.tr("tasks.queued")) });
+                    if ui.add_enabled(!task.cancelling, egui::Button::new(self.strings.tr("tasks.cancel"))).clicked() {
+                        self.workers.cancel(task.id);
+                    }
+                });
+            }
+        });
+    }
+
+    fn show_game_summary_window(&mut self, ctx: &egui::Context) {
+        let Some(waves) = &self.game_summary else {
+            return;
+        };
+        let strings = &self.strings;
+        let mut open = true;
+        let mut export = false;
+        egui::Window::new(strings.tr("summary.title")).open(&mut open).show(ctx, |ui| {
+            egui::Grid::new("game_summary").striped(true).show(ui, |ui| {
+                for key in ["summary.wave", "summary.time", "summary.shots", "summary.hits", "summary.accuracy", "summary.deaths", "summary.lives"] {
+                    ui.label(RichText::new(strings.tr(key)).strong());
+                }
+                ui.end_row();
+                for wave in waves {
+                    ui.label(wave.wave.to_string());
+                    ui.label(stats::format_duration(wave.duration()));
+                    ui.label(wave.shots.to_string());
+                    ui.label(wave.hits.to_string());
+                    ui.label(wave.accuracy().map(|accuracy| format!("{:.0}%", accuracy)).unwrap_or_else(|| "—".to_string()));
+                    ui.label(wave.deaths.to_string());
+                    ui.label(wave.lives_remaining.to_string());
+                    ui.end_row();
+                }
+            });
+            export = ui.button(strings.tr("summary.export")).clicked();
+        });
+        if export {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            let data_dir = self.data_dir.clone();
+            let path = self.data_dir.join(format!("waves-{}.csv", timestamp));
+            let csv = wave_stats::to_csv(waves);
+            self.submit_task(Message::new("tasks.waves", vec![]), move |_| {
+                return match std::fs::create_dir_all(&data_dir).and_then(|_| std::fs::write(&path, csv)) {
+                    Ok(_) => Outcome::Done(Message::new("summary.exported", vec![path.display().to_string()])),
+                    Err(error) => Outcome::Failed(Message::new("summary.export_failed", vec![error.to_string()])),
+                };
+            });
+        }
+        if !open {
+            self.game_summary = None;
+        }
+    }
+
+    /* What the game is being played with, then the CPU line while the debugger is attached; clicking the sources opens Controls */
+    fn show_status_bar(&mut self, ctx: &egui::Context) {
+        let mut sources = vec![input_sources::InputSource::keyboard(self.strings.tr("input.keyboard"))];
+        sources.extend(self.pad_assignments.pads().iter().cloned());
+        let sources: Vec<String> = sources.iter().map(|source| source.label()).collect();
+        let snapshot = *self.debug_snapshot.lock().unwrap();
+        let raised = HealthFlags::from_bits(self.health.swap(0, Ordering::Relaxed));
+        if !raised.is_empty() {
+            let held = self.recent_health.filter(|(_, at)| at.elapsed() < HEALTH_WARNING_HOLD).map_or(raised, |(flags, _)| flags | raised);
+            self.recent_health = Some((held, Instant::now()));
+        }
+        let warning = self.recent_health.filter(|(_, at)| at.elapsed() < HEALTH_WARNING_HOLD).map(|(flags, _)| flags);
+        let mut open_controls = false;
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(flags) = warning {
+                    let details: Vec<&str> = flags.iter().map(|flag| self.strings.tr(flag.label())).collect();
+                    let icon = RichText::new("⚠").color(Color32::YELLOW);
+                    ui.label(icon).on_hover_text(format!("{}\n{}", self.strings.tr("health.title"), details.join("\n")));
+                    ui.separator();
+                }
+                let label = egui::Label::new(sources.join("  ·  ")).sense(Sense::click());
+                open_controls = ui.add(label).on_hover_text(self.strings.tr("input.open_controls")).clicked();
+                if let Some(snapshot) = snapshot {
+                    ui.separator();
+                    ui.label(RichText::new(snapshot.status_line()).monospace());
+                    if ui.small_button(self.strings.tr("debug.copy")).on_hover_text(self.strings.tr("debug.copy_registers")).clicked() {
+                        ui.output_mut(|output| output.copied_text = diagnostic::registers(&snapshot));
+                    }
+                }
+            });
+        });
+        if open_controls {
+            self.show_controls = true;
+        }
+    }
+
+    fn show_start_lamps(&self, ctx: &egui::Context, lamps: StartLamps) {
+        egui::TopBottomPanel::bottom("start_lamps").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (key, lit) in [("game.one_player_lamp", lamps.one_player), ("game.two_player_lamp", lamps.two_player)] {
+                    let name = self.strings.tr(key);
+                    let text = RichText::new(name).monospace().strong();
+                    let response = ui.label(if lit { text.color(LAMP_LIT) } else { text.color(LAMP_DARK) });
+                    let description = self.strings.tr_with(if lit { "a11y.lamp_lit" } else { "a11y.lamp_dark" }, &[name]);
+                    response.widget_info(|| WidgetInfo::labeled(WidgetType::Label, &description));
+                }
+            });
+        });
+    }
+
+    /* On-screen log of recent input transitions by frame; * marks a press released within one frame */
+    fn paint_input_log(&self, ui: &Ui, rect: Rect) {
+        let log = self.input_manager.log();
+        let mut lines: Vec<(String, Color32)> = log.transitions()
+            .map(|t| (format!("{:>7} {} {}{}", t.frame, t.input.label(), if t.pressed { "down" } else { "up" }, if t.short { " *" } else { "" }), WHITE))
+            .collect();
+        if log.dropped() > 0 {
+            lines.push((self.strings.tr_with("input_log.dropped", &[&log.dropped().to_string()]), Color32::YELLOW));
+        }
+        if log.stretched() > 0 {
+            lines.push((self.strings.tr_with("input_log.stretched", &[&log.stretched().to_string()]), WHITE));
+        }
+        let font = FontId::monospace(11.0);
+        for (row, (text, color)) in lines.into_iter().enumerate() {
+            let position = rect.left_top() + Vec2::new(4.0, 4.0 + row as f32 * 13.0);
+            ui.painter().text(position, Align2::LEFT_TOP, text, font.clone(), color);
+        }
+    }
+
+    /* Rolling averages from the latency meter, top right of the game */
+    fn paint_latency(&self, ui: &Ui, rect: Rect) {
+        let averages = self.latency.lock().unwrap().as_ref().and_then(|meter| meter.averages());
+        let lines = match averages {
+            Some(averages) => vec![
+                self.strings.tr_with("latency.emulation", &[&format!("{:.1}", averages.key_to_emulation.as_secs_f64() * 1000.0)]),
+                self.strings.tr_with("latency.present", &[&format!("{:.1}", averages.key_to_present.as_secs_f64() * 1000.0)]),
+                self.strings.tr_with("latency.samples", &[&averages.samples.to_string()]),
+            ],
+            None => vec![self.strings.tr("latency.waiting").to_string()],
+        };
+        let font = FontId::monospace(11.0);
+        for (row, text) in lines.into_iter().enumerate() {
+            let position = rect.right_top() + Vec2::new(-4.0, 4.0 + row as f32 * 13.0);
+            ui.painter().text(position, Align2::RIGHT_TOP, text, font.clone(), Color32::YELLOW);
+        }
+    }
+
+    fn action_checked(&self, action: Action) -> Option<bool> {
+        match action {
+            Action::TogglePause => Some(self.debug_paused),
+            Action::ToggleFastForward => Some(self.fast_forward),
+            Action::ToggleCoinBlip => Some(self.settings.coin_blip),
+            Action::ToggleRandomizeRam => Some(self.settings.randomize_ram),
+            Action::ToggleHighContrast => Some(self.settings.high_contrast),
+            Action::ToggleStarfield => Some(self.settings.starfield),
+            Action::ToggleStartLamps => Some(self.settings.start_lamps),
+            Action::ToggleStretchShortPresses => Some(self.settings.stretch_short_presses),
+            Action::ToggleHighScoreScreenshots => Some(self.settings.high_score_screenshots),
+            Action::ToggleInputLog => Some(self.show_input_log),
+            Action::ToggleLatency => Some(self.show_latency),
+            Action::ToggleBreakOnReset => Some(self.settings.break_on_unexpected_reset),
+            Action::ToggleInfiniteLives => Some(self.infinite_lives),
+            Action::ToggleDipSwitches => Some(self.show_dip_switches),
+            Action::ToggleControls => Some(self.show_controls),
+            Action::ToggleAbout => Some(self.show_about),
+            Action::ToggleStats => Some(self.show_stats),
+            Action::ToggleMacros => Some(self.show_macros),
+            Action::ToggleSoundTimeline => Some(self.show_sound_timeline),
+            Action::ToggleSoundTest => Some(self.show_sound_test),
+            Action::ToggleRoutines => Some(self.show_routines),
+            Action::TogglePerformance => Some(self.show_performance),
+            Action::ToggleSettings => Some(self.show_settings),
+            Action::ToggleCoverage => Some(self.show_coverage),
+            Action::ToggleFullscreen => Some(self.fullscreen),
+            Action::ToggleSetting(_) | Action::CommandPalette | Action::Step | Action::SwapPlayers | Action::ExportListing | Action::CopyDiagnostic | Action::ResizeWindow(_) | Action::SaveSlot(_) | Action::LoadSlot(_) | Action::LoadStatePicker | Action::Quit => None,
+        }
+    }
+
+    fn action_enabled(&self, action: Action) -> bool {
+        match action {
+            Action::Step => self.debug_paused,
+            Action::ExportListing => !self.rom.is_empty(),
+            Action::CopyDiagnostic => self.debug_snapshot.lock().unwrap().is_some(),
+            _ => true,
+        }
+    }
+
+    fn action_label(&self, info: &ActionInfo) -> String {
+        if let Action::ToggleSetting(_) = info.action {
+            return self.strings.tr_with("settings.toggle", &[self.strings.tr(info.label)]);
+        }
+        return self.strings.tr(&format!("action.{}", info.id)).to_string();
+    }
+
+    fn action_menu_item(&mut self, ui: &mut Ui, action: Action) {
+        let info = *self.actions.get(action);
+        let enabled = self.action_enabled(action);
+        let clicked = match self.action_checked(action) {
+            Some(mut checked) => {
+                let label = match info.shortcut {
+                    Some(shortcut) => format!("{} ({})", self.action_label(&info), shortcut),
+                    None => self.action_label(&info),
+                };
+                ui.add_enabled(enabled, egui::Checkbox::new(&mut checked, label)).clicked()
+            },
+            None => {
+                let mut button = egui::Button::new(self.action_label(&info));
+                if let Some(shortcut) = info.shortcut {
+                    button = button.shortcut_text(shortcut.to_string());
+                }
+                let clicked = ui.add_enabled(enabled, button).clicked();
+                if clicked {
+                    ui.close_menu();
+                }
+                clicked
+            },
+        };
+        if clicked {
+            let ctx = ui.ctx().clone();
+            self.execute(&ctx, action);
+        }
+    }
+
+    fn rebuild_hotkeys(&mut self) {
+        self.hotkeys = HotkeyManager::new(&self.actions, &self.key_bindings, &self.settings.macros);
+    }
+
+    /* The input manager reads key_bindings every frame, so control changes take effect on the next one */
+    fn apply_controls(&mut self) {
+        self.key_bindings = KeyBindings::from_controls(&self.settings.controls);
+        self.rebuild_hotkeys();
+    }
+
+    fn keyboard_focus(&self, ctx: &egui::Context) -> Focus {
+        if self.palette_open || self.macro_key_capture.capturing().is_some() || self.control_key_capture.capturing().is_some() {
+            return Focus::Modal;
+        }
+        if ctx.memory(|m| m.focus().is_some()) {
+            return Focus::Widget;
+        }
+        return Focus::Game;
+    }
+
+    fn binding_name(&self, binding: Binding) -> String {
+        match binding {
+            Binding::Action(action) => self.action_label(self.actions.get(action)),
+            Binding::Macro(index) => self.settings.macros.get(index).map(|recorded| recorded.name.clone()).unwrap_or_default(),
+            Binding::Game(input) => input.label().to_string(),
+            Binding::Turbo => self.strings.tr("controls.turbo").to_string(),
+        }
+    }
+
+    fn handle_hotkeys(&mut self, ctx: &egui::Context, focus: Focus) {
+        let pressed: Vec<(Key, Modifiers)> = ctx.input(|i| i.events.iter().filter_map(|e| match e {
+            Event::Key { key, pressed: true, repeat: false, modifiers, .. } => Some((*key, *modifiers)),
+            _ => None,
+        }).collect());
+        for (key, modifiers) in pressed {
+            let chord = Chord { ctrl: modifiers.command, shift: modifiers.shift, key: key.name().to_string() };
+            match self.hotkeys.route(&chord, focus) {
+                Some(Binding::Action(action)) => {
+                    ctx.input_mut(|i| i.consume_key(modifiers, key));
+                    self.execute(ctx, action);
+                },
+                Some(Binding::Macro(index)) => {
+                    if self.macro_recorder.is_none() {
+                        self.control.send(ControlCommand::PlayMacro(self.settings.macros[index].clone()));
+                    }
+                },
+                Some(Binding::Game(_)) | Some(Binding::Turbo) | None => {},
+            }
+        }
+    }
+
+    fn show_command_palette(&mut self, ctx: &egui::Context) {
+        let matches: Vec<Action> = self.actions.search(&self.palette_query, |info| self.action_label(info)).iter().map(|info| info.action).collect();
+        let (up, down, enter, escape) = ctx.input_mut(|i| (
+            i.consume_key(Modifiers::NONE, Key::ArrowUp),
+            i.consume_key(Modifiers::NONE, Key::ArrowDown),
+            i.consume_key(Modifiers::NONE, Key::Enter),
+            i.consume_key(Modifiers::NONE, Key::Escape) || i.consume_key(Modifiers { ctrl: true, shift: true, ..Default::default() }, Key::P),
+        ));
+        if escape {
+            self.palette_open = false;
+            return;
+        }
+        if down && self.palette_selected + 1 < matches.len() {
+            self.palette_selected += 1;
+        }
+        if up {
+            self.palette_selected = self.palette_selected.saturating_sub(1);
+        }
+
+        let mut chosen = None;
+        egui::Window::new(self.strings.tr("palette.title"))
+            .title_bar(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_TOP, [0.0, 40.0])
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut self.palette_query);
+                let description = self.strings.tr("a11y.palette_query");
+                response.widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, description));
+                response.request_focus();
+                if response.changed() {
+                    self.palette_selected = 0;
+                }
+                for (index, action) in matches.iter().enumerate() {
+                    let info = self.actions.get(*action);
+                    let label = self.action_label(info);
+                    ui.horizontal(|ui| {
+                        let response = ui.selectable_label(index == self.palette_selected, label);
+                        if index == self.palette_selected && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            chosen = Some(*action);
+                        }
+                        if let Some(shortcut) = info.shortcut {
+                            ui.weak(shortcut.to_string());
+                        }
+                    });
+                }
+            });
+        if enter {
+            chosen = matches.get(self.palette_selected).copied();
+        }
+        if let Some(action) = chosen {
+            self.palette_open = false;
+            if action != Action::CommandPalette {
+                self.execute(ctx, action);
+            }
+        }
+    }
+
+    fn send_debug_command(&self, command: DebugCommand) {
+        self.control.send(ControlCommand::Debug(command));
+    }
+
+    fn send_slot_command(&self, command: SlotCommand) {
+        self.control.send(ControlCommand::Slot(command));
+    }
+
+    fn refresh_slot_headers(&mut self, ctx: &egui::Context) {
+        self.slot_headers = (1..=save_slots::SLOT_COUNT).map(|slot| {
+            let bytes = std::fs::read(save_slots::slot_path(&self.data_dir, slot)).ok()?;
+            save_slots::decode_header(&bytes).ok()
+        }).collect();
+        self.slot_textures = self.slot_headers.iter().enumerate().map(|(index, header)| {
+            header.as_ref().map(|header| {
+                let thumbnail = &header.thumbnail;
+                let image = ColorImage::from_rgba_unmultiplied([thumbnail.width as usize, thumbnail.height as usize], &thumbnail.rgba);
+                ctx.load_texture(format!("slot{}", index + 1), image, TextureOptions::LINEAR)
+            })
+        }).collect();
+    }
+
+    fn show_state_picker_window(&mut self, ctx: &egui::Context) {
+        if !self.show_state_picker {
+            return;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut chosen = None;
+        let strings = &self.strings;
+        egui::Window::new(strings.tr("slots.title")).open(&mut self.show_state_picker).show(ctx, |ui| {
+            egui::Grid::new("slot_grid").spacing([12.0, 12.0]).show(ui, |ui| {
+                for (index, header) in self.slot_headers.iter().enumerate() {
+                    ui.vertical(|ui| {
+                        match (header, &self.slot_textures[index]) {
+                            (Some(header), Some(texture)) => {
+                                let size = Vec2::new(header.thumbnail.height as f32, header.thumbnail.width as f32);
+                                let thumbnail = egui::Image::from_texture(texture).rotate(-std::f32::consts::FRAC_PI_2, Vec2::splat(0.5)).fit_to_exact_size(size);
+                                if ui.add(egui::ImageButton::new(thumbnail)).clicked() {
+                                    chosen = Some(index + 1);
+                                }
+                                let (age_key, count) = save_slots::age(now, header.timestamp);
+                                ui.label(strings.tr_with("slots.slot", &[&(index + 1).to_string()]));
+                                ui.weak(strings.tr_with(age_key, &[&count.to_string()]));
+                            },
+                            _ => {
+                                ui.label(strings.tr_with("slots.slot", &[&(index + 1).to_string()]));
+                                ui.weak(strings.tr("slots.empty"));
+                            },
+                        }
+                    });
+                    if (index + 1) % 5 == 0 {
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+        if let Some(slot) = chosen {
+            self.show_state_picker = false;
+            self.send_slot_command(SlotCommand::Load(slot));
+        }
+    }
+
+    fn toggle_debug_pause(&mut self) {
+        self.debug_paused = !self.debug_paused;
+        /* Pausing drops fast forward; resuming comes back at normal speed */
+        self.fast_forward = false;
+        self.control.send(ControlCommand::Mode(if self.debug_paused { ModeCommand::Pause } else { ModeCommand::Resume }));
+    }
+
+    fn toggle_fast_forward(&mut self) {
+        if self.debug_paused {
+            return;
+        }
+        self.fast_forward = !self.fast_forward;
+        self.control.send(ControlCommand::Mode(if self.fast_forward { ModeCommand::FastForward(FAST_FORWARD_MULTIPLIER) } else { ModeCommand::NormalSpeed }));
+    }
+
+    fn video_options(&self) -> VideoOptions {
+        let performance = self.settings.performance;
+        VideoOptions {
+            starfield: self.settings.starfield && !performance.disable_visual_mods,
+            monochrome: performance.monochrome,
+            half_rate: performance.half_rate_present,
+            adjust: self.settings.video,
+        }
+    }
+
+    fn check_overruns(&mut self, now: Instant) {
+        let perf = *self.perf_stats.lock().unwrap();
+        match self.overrun_policy.sample(now, &perf, self.settings.performance.auto_degrade) {
+            OverrunVerdict::Keep => {},
+            OverrunVerdict::Warn => {
+                self.toasts.push_link(self.strings.tr("performance.falling_behind"), self.strings.tr("performance.open"), Action::TogglePerformance);
+            },
+            OverrunVerdict::Degrade => {
+                self.settings.performance.degrade();
+                self.toasts.push_link(self.strings.tr("performance.degraded"), self.strings.tr("performance.open"), Action::TogglePerformance);
+            },
+        }
+    }
+
+    fn show_performance_window(&mut self, ctx: &egui::Context) {
+        let strings = &self.strings;
+        let performance = &mut self.settings.performance;
+        let budget_mb = &mut self.settings.debug_buffer_budget_mb;
+        let debug_budget = &self.debug_budget;
+        let perf = self.perf_view;
+        let av_sync = &self.av_sync;
+        egui::Window::new(strings.tr("performance.title")).open(&mut self.show_performance).show(ctx, |ui| {
+            ui.label(strings.tr_with("performance.late_frames", &[&perf.late_frames.to_string(), &perf.frames.to_string()]));
+            match av_sync.offset_ms() {
+                Some(offset) => {
+                    let text = strings.tr_with("performance.av_offset", &[&format!("{:+.1}", offset)]);
+                    if av_sync.drifting() {
+                        ui.colored_label(Color32::YELLOW, format!("⚠ {}", text)).on_hover_text(strings.tr("performance.av_drifting"));
+                    } else {
+                        ui.label(text);
+                    }
+                },
+                None => {
+                    ui.label(strings.tr("performance.av_offset_none"));
+                },
+            }
+            ui.separator();
+            ui.checkbox(&mut performance.disable_visual_mods, strings.tr("performance.disable_visual_mods"));
+            ui.checkbox(&mut performance.monochrome, strings.tr("performance.monochrome"));
+            ui.checkbox(&mut performance.half_rate_present, strings.tr("performance.half_rate_present"));
+            ui.separator();
+            ui.checkbox(&mut performance.auto_degrade, strings.tr("performance.auto_degrade"));
+            ui.checkbox(&mut performance.prefer_performance_cores, strings.tr("performance.prefer_performance_cores"));
+            ui.checkbox(&mut performance.idle_skip, strings.tr("performance.idle_skip"));
+            if ui.button(strings.tr("performance.reset")).clicked() {
+                *performance = Performance {
+                    auto_degrade: performance.auto_degrade,
+                    prefer_performance_cores: performance.prefer_performance_cores,
+                    idle_skip: performance.idle_skip,
+                    ..Default::default()
+                };
+            }
+            ui.separator();
+            ui.label(strings.tr("performance.debug_buffers"));
+            let budget = egui::DragValue::new(budget_mb).clamp_range(1..=4_096).suffix(" MiB").prefix(format!("{} ", strings.tr("performance.debug_budget")));
+            if ui.add(budget).changed() {
+                debug_budget.set_total(debug_budget::megabytes(*budget_mb));
+            }
+            egui::Grid::new("debug_buffers").show(ui, |ui| {
+                for usage in debug_budget.usage() {
+                    ui.label(strings.tr(&format!("debug_budget.{}", usage.name)));
+                    ui.label(strings.tr_with("performance.buffer_usage", &[&(usage.used / 1024).to_string(), &(usage.limit / 1024).to_string()]));
+                    ui.end_row();
+                }
+            });
+            #[cfg(feature = "tracing")]
+            {
+                ui.separator();
+                ui.label(strings.tr("performance.sections"));
+                let report = span_stats::GLOBAL.lock().map(|stats| stats.report()).unwrap_or_default();
+                ui.label(RichText::new(report).monospace());
+            }
+        });
+    }
+
+    /* Every registered setting under its section, narrowed by the search box */
+    fn show_settings_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_settings;
+        let mut reset = None;
+        let mut reset_all = false;
+        let before = (self.settings.language, self.settings.debug_buffer_budget_mb);
+        egui::Window::new(self.strings.tr("settings.title")).open(&mut open).vscroll(true).show(ctx, |ui| {
+            let hint = self.strings.tr("settings.search").to_string();
+            let response = ui.add(egui::TextEdit::singleline(&mut self.settings_query).hint_text(hint.clone()));
+            response.widget_info(|| WidgetInfo::labeled(WidgetType::TextEdit, &hint));
+            let rows: Vec<&SettingInfo> = settings::search(&self.settings_query, |key| self.strings.tr(key)).into_iter()
+                .filter(|info| cfg!(feature = "http-state") || !info.id.starts_with("state_server"))
+                /* Nothing to pick from where the displays can't be listed, as on Linux */
+                .filter(|info| !self.monitors.is_empty() || info.id != "fullscreen_display")
+                .collect();
+            if rows.is_empty() {
+                ui.weak(self.strings.tr("settings.no_matches"));
+            }
+            for section in Section::ALL {
+                if !rows.iter().any(|info| info.section == section) {
+                    continue;
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.strong(self.strings.tr(section.label()));
+                    if ui.small_button(self.strings.tr("settings.reset_section")).clicked() {
+                        reset = Some(section);
+                    }
+                });
+                for info in rows.iter().filter(|info| info.section == section) {
+                    self.setting_row(ui, info);
+                }
+            }
+            ui.separator();
+            if ui.button(self.strings.tr("settings.reset_all")).clicked() {
+                self.confirm_reset_all = true;
+            }
+        });
+        self.show_settings = open;
+        if self.confirm_reset_all {
+            egui::Window::new(self.strings.tr("settings.reset_all")).collapsible(false).resizable(false).anchor(Align2::CENTER_CENTER, [0.0, 0.0]).show(ctx, |ui| {
+                ui.label(self.strings.tr("settings.confirm_reset_all"));
+                ui.horizontal(|ui| {
+                    if ui.button(self.strings.tr("settings.confirm")).clicked() {
+                        reset_all = true;
+                    }
+                    if ui.button(self.strings.tr("settings.cancel")).clicked() {
+                        self.confirm_reset_all = false;
+                    }
+                });
+            });
+        }
+        if reset_all {
+            self.settings = Settings::default();
+            self.confirm_reset_all = false;
+        }
+        if let Some(section) = reset {
+            self.settings.reset_section(section);
+        }
+        if reset_all || reset.is_some() || before != (self.settings.language, self.settings.debug_buffer_budget_mb) {
+            self.apply_settings();
+        }
+        if reset_all {
+            self.toasts.push(self.strings.tr("settings.all_reset").to_string());
+        }
+        if let Some(section) = reset {
+            let name = self.strings.tr(section.label()).to_string();
+            self.toasts.push(self.strings.tr_with("settings.reset_done", &[&name]));
+        }
+    }
+
+    /* Pushes the settings that are only read when they change */
+    fn apply_settings(&mut self) {
+        self.strings = Strings::new(self.settings.language);
+        self.apply_controls();
+        self.debug_budget.set_total(debug_budget::megabytes(self.settings.debug_buffer_budget_mb));
+        self.send_debug_command(DebugCommand::BreakOnUnexpectedReset(self.settings.break_on_unexpected_reset));
+    }
+
+    fn setting_row(&mut self, ui: &mut Ui, info: &SettingInfo) {
+        let label = self.strings.tr(info.label).to_string();
+        match info.control {
+            Control::Toggle(field) => {
+                ui.checkbox(field(&mut self.settings), label);
+            },
+            Control::Slider { field, min, max } => {
+                ui.add(egui::Slider::new(field(&mut self.settings), min..=max).text(label));
+            },
+            Control::Whole { field, min, max, suffix } => {
+                ui.add(egui::DragValue::new(field(&mut self.settings)).clamp_range(min..=max).suffix(suffix).prefix(format!("{} ", label)));
+            },
+            Control::Port(field) => {
+                ui.add(egui::DragValue::new(field(&mut self.settings)).prefix(format!("{} ", label)));
+            },
+            Control::Language => {
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    for language in Language::ALL {
+                        ui.radio_value(&mut self.settings.language, language, language.native_name());
+                    }
+                });
+            },
+            Control::FullscreenDisplay => {
+                let current = match &self.settings.fullscreen_display {
+                    None => self.strings.tr("display.follow_window").to_string(),
+                    Some(saved) if self.monitors.iter().any(|monitor| saved.is(monitor)) => self.monitor_label(saved, false),
+                    Some(saved) => self.strings.tr_with("display.disconnected", &[&self.monitor_label(saved, false)]),
+                };
+                let mut chosen = self.settings.fullscreen_display.clone();
+                egui::ComboBox::from_label(label).selected_text(current).show_ui(ui, |ui| {
+                    ui.selectable_value(&mut chosen, None, self.strings.tr("display.follow_window"));
+                    for monitor in &self.monitors {
+                        let saved = SavedMonitor::new(monitor);
+                        let text = self.monitor_label(&saved, monitor.primary);
+                        ui.selectable_value(&mut chosen, Some(saved), text);
+                    }
+                });
+                self.settings.fullscreen_display = chosen;
+            },
+            Control::Action(action) => self.action_menu_item(ui, action),
+        }
+        ui.label(RichText::new(self.strings.tr(info.description)).weak().small());
+    }
+
+    fn show_routines_window(&mut self, ctx: &egui::Context) {
+        let strings = &self.strings;
+        let (by_name, selected, breakpoints) = (&mut self.routines_by_name, &mut self.routine_selected, &mut self.routine_breakpoints);
+        let mut breakpoints_changed = false;
+        egui::Window::new(strings.tr("routines.title")).open(&mut self.show_routines).show(ctx, |ui| {
+            let Some((table, rom)) = &self.routines else {
+                ui.label(strings.tr("routines.unavailable"));
+                return;
+            };
+            ui.horizontal(|ui| {
+                ui.radio_value(by_name, true, strings.tr("routines.by_name"));
+                ui.radio_value(by_name, false, strings.tr("routines.by_address"));
+            });
+            ui.separator();
+            ui.horizontal_top(|ui| {
+                egui::ScrollArea::vertical().id_source("routine_list").max_height(320.0).show(ui, |ui| {
+                    let symbols = if *by_name { table.by_name() } else { table.by_address().iter().collect() };
+                    for symbol in symbols {
+                        ui.horizontal(|ui| {
+                            let mut armed = breakpoints.contains(&symbol.address);
+                            let response = ui.checkbox(&mut armed, "").on_hover_text(strings.tr("routines.breakpoint"));
+                            response.widget_info(|| WidgetInfo::selected(WidgetType::Checkbox, armed, strings.tr_with("routines.breakpoint_at", &[&symbol.name])));
+                            if response.changed() {
+                                breakpoints.retain(|address| *address != symbol.address);
+                                if armed {
+                                    breakpoints.push(symbol.address);
+                                }
+                                breakpoints_changed = true;
                             }
-                        } else {
-                            for _ in 0..SCALE {
-                                current_row.push(BLACK);
+                            let label = RichText::new(format!("${:04X}  {}", symbol.address, symbol.name)).monospace();
+                            if ui.selectable_label(*selected == Some(symbol.address), label).clicked() {
+                                *selected = Some(symbol.address);
                             }
-                        }
+                        });
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical().id_source("routine_code").max_height(320.0).show(ui, |ui| {
+                    match selected {
+                        Some(address) => {
+                            ui.label(RichText::new(symbols::listing(rom, table, *address, ROUTINE_LISTING_LINES).join("\n")).monospace());
+                        },
+                        None => {
+                            ui.label(strings.tr("routines.select"));
+                        },
+                    }
+                });
+            });
+        });
+        if breakpoints_changed {
+            self.send_debug_command(DebugCommand::Breakpoints(self.routine_breakpoints.clone()));
+        }
+    }
+
+    fn show_coverage_window(&mut self, ctx: &egui::Context) {
+        if !self.show_coverage {
+            self.coverage_view = None;
+            return;
+        }
+        if self.repaint.every(Consumer::Coverage, COVERAGE_REFRESH, Instant::now()) {
+            self.coverage_view = self.coverage.lock().unwrap().clone().map(|map| {
+                let pixels = map.classes().iter().map(|class| coverage_color(*class)).collect();
+                let image = ColorImage { size: [coverage::STRIP_WIDTH, map.strip_rows()], pixels };
+                let texture = ctx.load_texture("coverage", image, TextureOptions::NEAREST);
+                (map, texture)
+            });
+        }
+        let strings = &self.strings;
+        let mut export = false;
+        egui::Window::new(strings.tr("coverage.title")).open(&mut self.show_coverage).show(ctx, |ui| {
+            let Some((map, texture)) = &self.coverage_view else {
+                ui.label(strings.tr("coverage.waiting"));
+                return;
+            };
+            ui.label(strings.tr_with("coverage.summary", &[&format!("{:.1}", map.percent_touched())]));
+            let legend = [
+                (ByteClass::Executed, "coverage.executed"),
+                (ByteClass::Operand, "coverage.operand"),
+                (ByteClass::Data, "coverage.data"),
+                (ByteClass::Untouched, "coverage.untouched"),
+            ];
+            for (class, key) in legend {
+                ui.colored_label(coverage_color(class), strings.tr_with(key, &[&map.count(class).to_string()]));
+            }
+            let size = Vec2::new(coverage::STRIP_WIDTH as f32 * 3.0, map.strip_rows() as f32 * 3.0);
+            let response = ui.add(egui::Image::from_texture(texture).fit_to_exact_size(size));
+            response.widget_info(|| WidgetInfo::labeled(WidgetType::Other, strings.tr("a11y.coverage_strip")));
+            export = ui.button(strings.tr("coverage.export")).clicked();
+        });
+        if export {
+            self.send_debug_command(DebugCommand::ExportCoverage);
+        }
+        if !self.show_coverage {
+            self.send_debug_command(DebugCommand::Coverage(false));
+        }
+    }
+
+    /* The summary and bit table are decoded back from the port 2 value the program reads */
+    fn show_dip_switch_window(&mut self, ctx: &egui::Context) {
+        let strings = &self.strings;
+        let dip_switches = &mut self.settings.dip_switches;
+        let pending = self.live_dip_switches.pending_reset(dip_switches);
+        let badge = |ui: &mut Ui, switch: DipSwitch| {
+            if pending.contains(&switch) {
+                ui.colored_label(Color32::YELLOW, strings.tr("dip.after_reset"));
+            }
+        };
+        let mut reset = false;
+        egui::Window::new(strings.tr("dip.title")).open(&mut self.show_dip_switches).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(strings.tr("dip.lives"));
+                for lives in machine::MIN_LIVES..=machine::MAX_LIVES {
+                    ui.radio_value(&mut dip_switches.lives, lives, lives.to_string());
+                }
+                badge(ui, DipSwitch::Lives);
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut dip_switches.bonus_life_at_1000, strings.tr("dip.bonus_at_1000"));
+                badge(ui, DipSwitch::BonusLife);
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut dip_switches.coin_info, strings.tr("dip.coin_info"));
+                badge(ui, DipSwitch::CoinInfo);
+            });
+            let port2 = InputState::with_dip_switches(*dip_switches).port2();
+            let decoded = DipSwitches::from_port2(port2);
+            let coin_info = strings.tr(if decoded.coin_info { "dip.shown" } else { "dip.hidden" });
+            ui.strong(strings.tr_with("dip.summary", &[&decoded.lives.to_string(), &decoded.bonus_life_score().to_string(), coin_info]));
+            ui.label(strings.tr("dip.takes_effect"));
+            if !pending.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::YELLOW, strings.tr("dip.reset_needed"));
+                    reset = ui.button(strings.tr("dip.reset_now")).clicked();
+                });
+            }
+            ui.collapsing(strings.tr("dip.port_bits"), |ui| {
+                egui::Grid::new("port2_bits").striped(true).show(ui, |ui| {
+                    for (mask, meaning) in machine::PORT2_BITS {
+                        ui.monospace(format!("{:08b}", mask));
+                        ui.monospace(format!("{:08b}", port2 & mask));
+                        ui.label(meaning);
+                        ui.end_row();
                     }
-                    if current_row.len() == SCREEN_WIDTH * SCALE {
-                        for _ in 0..SCALE {
-                            rows.push(current_row.clone());
+                });
+            });
+        });
+        if reset {
+            self.live_dip_switches = self.settings.dip_switches;
+            self.control.send(ControlCommand::PowerCycle);
+        }
+    }
+
+    fn show_controls_window(&mut self, ctx: &egui::Context) {
+        if self.control_key_capture.capturing().is_some() {
+            let pressed = ctx.input(|i| i.events.iter().find_map(|e| match e {
+                Event::Key { key, pressed: true, .. } => Some(*key),
+                _ => None,
+            }));
+            if let Some(key) = pressed {
+                if let CaptureOutcome::Bound(row, name) = self.control_key_capture.handle_key(key.name()) {
+                    /* The row after the cabinet controls is the turbo key */
+                    let binding = LogicalInput::ALL.get(row).map_or(Binding::Turbo, |input| Binding::Game(*input));
+                    match self.hotkeys.check(&Chord::key(&name), binding) {
+                        Some(existing) => {
+                            self.controls_warning = Some(self.strings.tr_with("hotkeys.conflict", &[&name, &self.binding_name(existing)]));
+                        },
+                        None => {
+                            match binding {
+                                Binding::Game(input) => self.settings.controls.bind(input, Some(name)),
+                                _ => self.settings.controls.turbo = Some(name),
+                            }
+                            self.controls_warning = None;
+                            self.apply_controls();
+                        },
+                    }
+                }
+            }
+        }
+
+        let mut changed = false;
+        let refocus = self.control_key_capture.take_refocus();
+        let strings = &self.strings;
+        let controls = &mut self.settings.controls;
+        let capture = &mut self.control_key_capture;
+        let warning = &self.controls_warning;
+        egui::Window::new(strings.tr("controls.title")).open(&mut self.show_controls).show(ctx, |ui| {
+            for preset in ControlPreset::ALL {
+                if ui.radio(controls.preset == preset, strings.tr(preset.name_key())).clicked() && controls.preset != preset {
+                    controls.preset = preset;
+                    changed = true;
+                }
+            }
+            ui.horizontal(|ui| {
+                if ui.button(strings.tr("controls.swap")).clicked() {
+                    controls.swap_players();
+                    changed = true;
+                }
+                if ui.button(strings.tr("controls.reset")).clicked() {
+                    controls.reset();
+                    changed = true;
+                }
+            });
+            if let Some(warning) = warning {
+                ui.colored_label(Color32::YELLOW, warning);
+            }
+            ui.separator();
+            egui::Grid::new("control_keys").striped(true).show(ui, |ui| {
+                for (row, input) in LogicalInput::ALL.iter().enumerate() {
+                    ui.label(input.label());
+                    let binding = if capture.capturing() == Some(row) {
+                        strings.tr("macros.press_key").to_string()
+                    } else {
+                        controls.key(*input).unwrap_or(strings.tr("macros.unbound")).to_string()
+                    };
+                    let description = strings.tr_with("a11y.binding", &[input.label(), &binding]);
+                    let response = ui.button(binding);
+                    response.widget_info(|| WidgetInfo::labeled(WidgetType::Button, &description));
+                    if refocus == Some(row) {
+                        response.request_focus();
+                    }
+                    if response.clicked() {
+                        capture.start(row);
+                    }
+                    if ui.small_button(strings.tr("controls.clear")).clicked() {
+                        controls.bind(*input, None);
+                        changed = true;
+                    }
+                    ui.end_row();
+                }
+                let row = LogicalInput::ALL.len();
+                let name = strings.tr("controls.turbo");
+                ui.label(name).on_hover_text(strings.tr("controls.turbo_help"));
+                let binding = if capture.capturing() == Some(row) {
+                    strings.tr("macros.press_key").to_string()
+                } else {
+                    controls.turbo.as_deref().unwrap_or(strings.tr("macros.unbound")).to_string()
+                };
+                let description = strings.tr_with("a11y.binding", &[name, &binding]);
+                let response = ui.button(binding);
+                response.widget_info(|| WidgetInfo::labeled(WidgetType::Button, &description));
+                if refocus == Some(row) {
+                    response.request_focus();
+                }
+                if response.clicked() {
+                    capture.start(row);
+                }
+                if ui.small_button(strings.tr("controls.clear")).clicked() {
+                    controls.turbo = None;
+                    changed = true;
+                }
+                ui.end_row();
+            });
+        });
+        if changed {
+            self.controls_warning = None;
+            self.apply_controls();
+        }
+    }
+
+    fn show_sound_timeline_window(&mut self, ctx: &egui::Context) {
+        if !self.show_sound_timeline {
+            self.timeline_view = None;
+            return;
+        }
+        if self.repaint.every(Consumer::SoundTimeline, TIMELINE_REFRESH, Instant::now()) || self.timeline_view.is_none() {
+            self.timeline_view = Some(self.sound_timeline.lock().unwrap().clone());
+        }
+        let Some(timeline) = &self.timeline_view else {
+            return;
+        };
+        let strings = &self.strings;
+        egui::Window::new(strings.tr("sound.title")).open(&mut self.show_sound_timeline).show(ctx, |ui| {
+            ui.horizontal_top(|ui| {
+                ui.vertical(|ui| {
+                    ui.spacing_mut().item_spacing.y = 0.0;
+                    for sound in 0..sound_latch::SOUND_COUNT {
+                        ui.add_sized([90.0, TIMELINE_ROW_HEIGHT], egui::Label::new(strings.tr(&format!("sound.{}", sound)).to_string()));
+                    }
+                });
+                egui::ScrollArea::horizontal().stick_to_right(true).show(ui, |ui| {
+                    let size = Vec2::new(timeline.history_frames() as f32 * TIMELINE_FRAME_WIDTH, sound_latch::SOUND_COUNT as f32 * TIMELINE_ROW_HEIGHT);
+                    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+                    let origin = response.rect.min;
+                    let oldest = timeline.oldest_frame();
+                    let x = |frame: u64| origin.x + (frame.saturating_sub(oldest)) as f32 * TIMELINE_FRAME_WIDTH;
+                    painter.rect_filled(response.rect, 0.0, Color32::from_gray(20));
+                    for sound in 0..sound_latch::SOUND_COUNT {
+                        let top = origin.y + sound as f32 * TIMELINE_ROW_HEIGHT;
+                        for (start, end) in timeline.spans(sound) {
+                            let span = Rect::from_min_max(Pos2::new(x(start), top + 4.0), Pos2::new(x(end).max(x(start) + 1.0), top + TIMELINE_ROW_HEIGHT - 4.0));
+                            painter.rect_filled(span, 2.0, Color32::from_rgb(60, 90, 160));
+                        }
+                        for frame in timeline.marks(sound) {
+                            painter.line_segment([Pos2::new(x(frame), top + 1.0), Pos2::new(x(frame), top + TIMELINE_ROW_HEIGHT - 1.0)], Stroke::new(1.5, GREEN));
                         }
-                        current_row = vec![];
                     }
+                });
+            });
+        });
+    }
+
+    fn show_sound_test_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_sound_test;
+        let mut command = None;
+        egui::Window::new(self.strings.tr("sound_test.title")).open(&mut open).show(ctx, |ui| {
+            egui::Grid::new("sound_test").striped(true).show(ui, |ui| {
+                for sound in SOUNDS {
+                    ui.label(self.strings.tr(&format!("sound.{}", sound.index)).to_string());
+                    ui.weak(format!("{}.wav", sound.index));
+                    if ui.button(self.strings.tr("sound_test.play").to_string()).clicked() {
+                        command = Some(ControlCommand::SoundTest { sound: sound.index, play: true });
+                    }
+                    if sound.looping && ui.button(self.strings.tr("sound_test.stop").to_string()).clicked() {
+                        command = Some(ControlCommand::SoundTest { sound: sound.index, play: false });
+                    }
+                    ui.end_row();
                 }
+            });
+        });
+        self.show_sound_test = open;
+        if let Some(command) = command {
+            self.control.send(command);
+        }
+    }
+
+    fn update_macros(&mut self, ctx: &egui::Context, input_state: &InputState) {
+        let frame = self.frame_counter.load(Ordering::Relaxed);
+        if let Some(recorder) = self.macro_recorder.as_mut() {
+            recorder.record(frame, input_state);
+            recorder.record_turbo(frame, self.turbo);
+        }
 
-                let time_spent = start.elapsed().as_micros();
-                if time_spent < 16667 as u128 {
-                    thread::sleep(Duration::from_micros(16667 - time_spent as u64))
+        if self.macro_key_capture.capturing().is_some() {
+            let pressed = ctx.input(|i| i.events.iter().find_map(|e| match e {
+                Event::Key { key, pressed: true, .. } => Some(*key),
+                _ => None,
+            }));
+            if let Some(key) = pressed {
+                if let CaptureOutcome::Bound(index, name) = self.macro_key_capture.handle_key(key.name()) {
+                    match self.hotkeys.check(&Chord::key(&name), Binding::Macro(index)) {
+                        Some(existing) => {
+                            self.hotkey_warning = Some(self.strings.tr_with("hotkeys.conflict", &[&name, &self.binding_name(existing)]));
+                        },
+                        None => {
+                            self.settings.macros[index].key = Some(name);
+                            self.hotkey_warning = None;
+                            self.rebuild_hotkeys();
+                        },
+                    }
                 }
-                *frame_buffer_clone.lock().unwrap() = Box::new(rows.concat());
-                ctx_clone.request_repaint();
-                start = Instant::now();
+            }
+        }
+        let mut warnings: Vec<String> = self.hotkey_warning.iter().cloned().collect();
+        for conflict in self.hotkeys.conflicts() {
+            let (chord, rejected, existing) = (conflict.chord.to_string(), self.binding_name(conflict.rejected), self.binding_name(conflict.existing));
+            warnings.push(self.strings.tr_with("hotkeys.ignored", &[&chord, &rejected, &existing]));
+        }
+
+        let mut delete = None;
+        let refocus = self.macro_key_capture.take_refocus();
+        let strings = &self.strings;
+        egui::Window::new(strings.tr("macros.title")).open(&mut self.show_macros).show(ctx, |ui| {
+            match self.macro_recorder.take() {
+                Some(recorder) => {
+                    ui.label(strings.tr("macros.recording"));
+                    if ui.button(strings.tr("macros.stop")).clicked() {
+                        let name = if self.new_macro_name.is_empty() {
+                            strings.tr_with("macros.default_name", &[&(self.settings.macros.len() + 1).to_string()])
+                        } else {
+                            std::mem::take(&mut self.new_macro_name)
+                        };
+                        self.settings.macros.push(recorder.finish(frame, name));
+                    } else {
+                        self.macro_recorder = Some(recorder);
+                    }
+                },
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.new_macro_name);
+                        if ui.button(strings.tr("macros.record")).clicked() {
+                            self.macro_recorder = Some(MacroRecorder::start(frame));
+                        }
+                    });
+                },
+            }
+            for warning in &warnings {
+                ui.colored_label(Color32::YELLOW, warning);
+            }
+            ui.separator();
+            for (index, recorded) in self.settings.macros.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut recorded.name);
+                    ui.label(strings.tr_with("macros.frames", &[&recorded.length().to_string()]));
+                    let binding = if self.macro_key_capture.capturing() == Some(index) {
+                        strings.tr("macros.press_key").to_string()
+                    } else {
+                        recorded.key.clone().unwrap_or(strings.tr("macros.unbound").to_string())
+                    };
+                    let description = strings.tr_with("a11y.binding", &[&recorded.name, &binding]);
+                    let response = ui.button(binding);
+                    response.widget_info(|| WidgetInfo::labeled(WidgetType::Button, &description));
+                    if refocus == Some(index) {
+                        response.request_focus();
+                    }
+                    if response.clicked() {
+                        self.macro_key_capture.start(index);
+                    }
+                    if ui.button(strings.tr("macros.delete")).clicked() {
+                        delete = Some(index);
+                    }
+                });
             }
         });
-        return app;
+        if let Some(index) = delete {
+            self.settings.macros.remove(index);
+            self.macro_key_capture.remove_row(index);
+            self.rebuild_hotkeys();
+        }
     }
 }
 
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.add_space(50.0);
-            let image = ColorImage { size: [SCREEN_WIDTH * SCALE, SCREEN_HEIGHT * SCALE], pixels: *self.frame_buffer.lock().unwrap().clone(), };
-            let texture = ctx.load_texture("display", image, TextureOptions::LINEAR);
-            let rotated_image = egui::Image::from_texture(&texture).rotate(-1.5708, Vec2::splat(0.5));
-            ui.add(rotated_image);
-            if ctx.input(|i| i.key_pressed(Key::Escape)) {
-                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        let (monitor_size, native_pixels_per_point, window_position, window_fullscreen) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (viewport.monitor_size, viewport.native_pixels_per_point, viewport.outer_rect.map(|rect| rect.min), viewport.fullscreen.unwrap_or(false))
+        });
+        self.monitor_size = monitor_size;
+        self.native_pixels_per_point = native_pixels_per_point;
+        self.watch_monitors(ctx, window_position, window_fullscreen);
+        if self.applied_high_contrast != Some(self.settings.high_contrast) {
+            ctx.set_visuals(theme::visuals(self.settings.high_contrast));
+            self.applied_high_contrast = Some(self.settings.high_contrast);
+        }
+        if let Some(error) = self.rom_error.clone() {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.heading(self.strings.tr("rom.error_title"));
+                ui.label(self.strings.message(&error));
+                ui.label(self.strings.tr("rom.drop_archive"));
+                if ui.button(self.strings.tr("rom.run_builtin")).clicked() {
+                    self.rom_error = None;
+                    self.start(ctx, test_rom::rom());
+                }
+            });
+            let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|file| file.path.clone()));
+            if let Some(path) = dropped {
+                self.load_dropped_archive(ctx, &path);
             }
-            let mut device1_bits = 0b00001000;
-            let mut device2_bits = 0b00000000;
-            if ctx.input(|i| i.key_pressed(Key::Space)) {
-                device1_bits = device1_bits | 0b00000001;
+            return;
+        }
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button(self.strings.tr("menu.file").to_string(), |ui| {
+                    self.action_menu_item(ui, Action::ToggleAbout);
+                    self.action_menu_item(ui, Action::Quit);
+                });
+                ui.menu_button(self.strings.tr("menu.options").to_string(), |ui| {
+                    self.action_menu_item(ui, Action::ToggleSettings);
+                    ui.separator();
+                    self.action_menu_item(ui, Action::ToggleCoinBlip);
+                    let volume = self.strings.tr("menu.volume").to_string();
+                    ui.add(egui::Slider::new(&mut self.settings.volume, 0.0..=1.0).text(volume));
+                    let idle_pause = self.strings.tr("menu.idle_pause").to_string();
+                    ui.add(egui::Slider::new(&mut self.settings.idle_pause_minutes, 0..=60).text(idle_pause));
+                    self.action_menu_item(ui, Action::ToggleRandomizeRam);
+                    self.action_menu_item(ui, Action::ToggleHighContrast);
+                    ui.menu_button(self.strings.tr("menu.video").to_string(), |ui| {
+                        let strings = &self.strings;
+                        let video = &mut self.settings.video;
+                        ui.add(egui::Slider::new(&mut video.brightness, -1.0..=1.0).text(strings.tr("video.brightness").to_string()));
+                        ui.add(egui::Slider::new(&mut video.contrast, 0.0..=2.0).text(strings.tr("video.contrast").to_string()));
+                        ui.checkbox(&mut video.invert, strings.tr("video.invert").to_string());
+                        ui.checkbox(&mut video.vignette, strings.tr("video.vignette").to_string());
+                        ui.checkbox(&mut video.blend, strings.tr("video.blend").to_string());
+                        if ui.button(strings.tr("video.reset").to_string()).clicked() {
+                            *video = VideoAdjust::default();
+                        }
+                    });
+                    self.action_menu_item(ui, Action::ToggleStarfield);
+                    self.action_menu_item(ui, Action::ToggleStartLamps);
+                    self.action_menu_item(ui, Action::ToggleStretchShortPresses);
+                    self.action_menu_item(ui, Action::ToggleHighScoreScreenshots);
+                    self.action_menu_item(ui, Action::ToggleDipSwitches);
+                    self.action_menu_item(ui, Action::ToggleControls);
+                    self.action_menu_item(ui, Action::SwapPlayers);
+                    #[cfg(feature = "http-state")]
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.settings.state_server, self.strings.tr("state_server.enable").to_string());
+                        ui.add(egui::DragValue::new(&mut self.settings.state_server_port).prefix(format!("{} ", self.strings.tr("state_server.port"))));
+                    });
+                    ui.menu_button(self.strings.tr("menu.language").to_string(), |ui| {
+                        for language in Language::ALL {
+                            if ui.radio(self.settings.language == language, language.native_name()).clicked() {
+                                self.settings.language = language;
+                                self.strings = Strings::new(language);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+                ui.menu_button(self.strings.tr("menu.view").to_string(), |ui| {
+                    self.action_menu_item(ui, Action::CommandPalette);
+                    ui.separator();
+                    self.action_menu_item(ui, Action::ToggleStats);
+                    self.action_menu_item(ui, Action::TogglePerformance);
+                    self.action_menu_item(ui, Action::ToggleMacros);
+                    ui.separator();
+                    for scale in window_size::RESIZE_SCALES {
+                        self.action_menu_item(ui, Action::ResizeWindow(scale));
+                    }
+                    self.action_menu_item(ui, Action::ToggleFullscreen);
+                });
+                ui.menu_button(self.strings.tr("menu.state").to_string(), |ui| {
+                    self.action_menu_item(ui, Action::LoadStatePicker);
+                    ui.menu_button(self.strings.tr("menu.save").to_string(), |ui| {
+                        for slot in 1..=save_slots::SLOT_COUNT {
+                            self.action_menu_item(ui, Action::SaveSlot(slot));
+                        }
+                    });
+                    ui.menu_button(self.strings.tr("menu.load").to_string(), |ui| {
+                        for slot in 1..=save_slots::SLOT_COUNT {
+                            self.action_menu_item(ui, Action::LoadSlot(slot));
+                        }
+                    });
+                });
+                ui.menu_button(self.strings.tr("menu.debug").to_string(), |ui| {
+                    self.action_menu_item(ui, Action::TogglePause);
+                    self.action_menu_item(ui, Action::Step);
+                    self.action_menu_item(ui, Action::ToggleFastForward);
+                    ui.separator();
+                    self.action_menu_item(ui, Action::ToggleSoundTimeline);
+                    self.action_menu_item(ui, Action::ToggleSoundTest);
+                    self.action_menu_item(ui, Action::ToggleRoutines);
+                    self.action_menu_item(ui, Action::ToggleCoverage);
+                    self.action_menu_item(ui, Action::ExportListing);
+                    self.action_menu_item(ui, Action::ToggleInputLog);
+                    self.action_menu_item(ui, Action::ToggleLatency);
+                    ui.separator();
+                    self.action_menu_item(ui, Action::ToggleBreakOnReset);
+                    self.action_menu_item(ui, Action::CopyDiagnostic);
+                    ui.separator();
+                    self.action_menu_item(ui, Action::ToggleInfiniteLives);
+                });
+                self.tasks_menu(ui);
+            });
+        });
+        let focus = self.keyboard_focus(ctx);
+        if self.palette_open {
+            self.show_command_palette(ctx);
+        } else {
+            /* While capturing a binding every key, Escape and F1..F10 included, belongs to the capture row */
+            self.handle_hotkeys(ctx, focus);
+        }
+        #[cfg(feature = "gamepad")]
+        let pads = self.poll_gamepads();
+        #[cfg(not(feature = "gamepad"))]
+        let pads = vec![];
+        self.poll_diagnostic(ctx);
+        /* Drained every update whether or not the offset is shown, so the rings never hold stale stamps */
+        if let Some((vblanks, sounds)) = &self.av_stamps {
+            self.av_sync.poll(vblanks, sounds);
+        }
+        self.show_status_bar(ctx);
+        self.poll_workers();
+
+        let frame = self.frame_counter.load(Ordering::Relaxed);
+        self.live_dip_switches = self.live_dip_switches.apply_hot(&self.settings.dip_switches);
+        let input_state = self.input_manager.read(ctx, self.live_dip_switches, &self.key_bindings, focus, frame, self.settings.stretch_short_presses, &pads);
+        self.turbo = self.input_manager.held(ctx, self.key_bindings.turbo(), focus);
+        self.control.set_turbo(self.turbo);
+        if self.show_latency {
+            if let Some(meter) = self.latency.lock().unwrap().as_mut() {
+                for fire in [LogicalInput::P1Fire, LogicalInput::P2Fire] {
+                    if let Some(at) = self.input_manager.pressed_at(fire) {
+                        meter.key_down(at);
+                    }
+                }
             }
-            if ctx.input(|i| i.key_pressed(Key::Num1)) {
-                device1_bits = device1_bits | 0b00000100;
+        }
+        self.update_macros(ctx, &input_state);
+        let ram = self.poll_ram();
+        let game_state = GameState::from_ram(&ram);
+        if self.infinite_lives && game_state == GameState::Playing {
+            for (addr, bytes) in cheats::infinite_lives(&ram) {
+                self.control.queue_write(addr, bytes);
             }
-            if ctx.input(|i| i.key_pressed(Key::Num2)) {
-                device1_bits = device1_bits | 0b00000010;
+        }
+        for event in self.wave_watcher.update(WaveSample::from_ram(&ram, game_state)) {
+            if let Some(waves) = self.wave_stats.apply(frame, event) {
+                self.game_summary = Some(waves).filter(|waves| !waves.is_empty());
             }
-            if ctx.input(|i| i.key_pressed(Key::W)) {
-                device1_bits = device1_bits | 0b00010000;
+        }
+        if self.settings.start_lamps {
+            self.show_start_lamps(ctx, StartLamps::from_ram(&ram));
+        }
+        let events = self.state_watcher.update(&ram, frame);
+        for event in events {
+            match event {
+                StateEvent::CreditAdded(count) => {
+                    self.toasts.push(self.strings.tr_with("game.credit_added", &[&count.to_string()]));
+                    if self.settings.coin_blip {
+                        self.control.request_blip();
+                    }
+                },
+                StateEvent::NewHighScore(score) => {
+                    if self.settings.high_score_screenshots {
+                        self.save_high_score_screenshot(score);
+                    }
+                },
+            }
+        }
+
+        let now = Instant::now();
+        let policy = IdlePolicy::from_minutes(self.settings.idle_pause_minutes);
+        self.run_state = self.run_state.next(&policy, self.input_manager.idle_for(now), game_state, self.input_manager.received_input());
+        let paused = self.run_state == RunState::IdlePaused;
+        self.control.set_paused(paused);
+        #[cfg(feature = "http-state")]
+        self.update_state_server(&ram, frame, now);
+        if !paused && game_state == GameState::Playing {
+            /* A gap this long means the host slept; don't count it as play time */
+            self.session_stats.add_play_time(now.saturating_duration_since(self.last_update).min(frame_pacing::RESYNC_THRESHOLD));
+        }
+        self.last_update = now;
+
+        let notices: Vec<Message> = self.notice_rx.as_ref().map(|rx| rx.try_iter().collect()).unwrap_or_default();
+        if !notices.is_empty() && self.show_state_picker {
+            self.refresh_slot_headers(ctx);
+        }
+        for notice in notices {
+            /* The emulation thread paused itself; keep the debugger controls in step */
+            if notice.key == UNEXPECTED_RESET_NOTICE || notice.key == BREAKPOINT_NOTICE {
+                self.debug_paused = true;
+                self.fast_forward = false;
+            }
+            self.toasts.push(self.strings.message(&notice));
+        }
+        self.check_overruns(now);
+        if let Some(action) = self.toasts.show(ctx) {
+            self.execute(ctx, action);
+        }
+        if !self.toasts.is_empty() {
+            self.repaint.every(Consumer::Toasts, TOAST_REFRESH, now);
+        }
+        if (self.show_stats || self.show_performance) && self.repaint.every(Consumer::Stats, STATS_REFRESH, now) {
+            self.perf_view = *self.perf_stats.lock().unwrap();
+        }
+        self.show_state_picker_window(ctx);
+        self.show_performance_window(ctx);
+        self.show_settings_window(ctx);
+        self.show_coverage_window(ctx);
+        self.show_dip_switch_window(ctx);
+        self.show_controls_window(ctx);
+        self.show_game_summary_window(ctx);
+        egui::Window::new(self.strings.tr("about.title")).open(&mut self.show_about).resizable(false).show(ctx, |ui| {
+            ui.label(RichText::new(APP_NAME).strong());
+            ui.label(self.strings.tr_with("about.version", &[env!("CARGO_PKG_VERSION")]));
+            ui.label(self.strings.tr_with("about.renderer", &[self.renderer.name()]));
+            if !self.rom_identity.is_empty() {
+                ui.label(self.strings.tr_with("about.rom", &[&self.rom_identity]));
             }
-            if ctx.input(|i| i.key_pressed(Key::A)) {
-                device1_bits = device1_bits | 0b00100000;
+        });
+        self.control.set_volume(self.settings.volume);
+        self.control.set_idle_skip(self.settings.performance.idle_skip);
+        self.control.set_video(self.video_options());
+        self.show_sound_timeline_window(ctx);
+        self.show_sound_test_window(ctx);
+        self.show_routines_window(ctx);
+        egui::Window::new(self.strings.tr("stats.title")).open(&mut self.show_stats).show(ctx, |ui| {
+            ui.label(self.strings.tr_with("stats.play_time", &[&stats::format_duration(self.session_stats.play_time())]));
+            let perf = self.perf_view;
+            if perf.resyncs > 0 {
+                ui.label(self.strings.tr_with("stats.dropped_time", &[&stats::format_duration(perf.dropped_time), &perf.resyncs.to_string()]));
             }
-            if ctx.input(|i| i.key_pressed(Key::D)) {
-                device1_bits = device1_bits | 0b01000000;
+            if perf.audio_coalesced > 0 || perf.audio_dropped > 0 {
+                ui.label(self.strings.tr_with("stats.audio_backlog", &[&perf.audio_coalesced.to_string(), &perf.audio_dropped.to_string()]));
             }
-            if ctx.input(|i| i.key_pressed(Key::ArrowLeft)) {
-                device2_bits = device2_bits | 0b00100000;
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.chrome = Chrome::measure(ctx.screen_rect().size().into(), ui.available_size().into());
+            let latest = traced!("frame_slot_read", self.frame_buffer.latest());
+            let vblank = latest.vblank;
+            /* At half rate the emulation thread's own repaint requests set the pace */
+            if !self.settings.performance.half_rate_present {
+                let now = Instant::now();
+                if let Some(delay) = vblank.and_then(|vblank| frame_pacing::next_present_delay(vblank, now)) {
+                    self.repaint.at(Consumer::Game, now + delay);
+                }
             }
-            if ctx.input(|i| i.key_pressed(Key::ArrowRight)) {
-                device2_bits = device2_bits | 0b01000000;
+            /* The texture is kept across repaints and only handed a new frame's Arc, so the pixels are copied once, on upload */
+            let texture = match self.display_texture.take() {
+                Some((mut texture, serial)) => {
+                    if serial != latest.serial {
+                        traced!("texture_update", texture.set(ImageData::Color(latest.frame), TextureOptions::LINEAR));
+                    }
+                    texture
+                },
+                None => ctx.load_texture("display", ImageData::Color(latest.frame), TextureOptions::LINEAR),
+            };
+            self.display_texture = Some((texture.clone(), latest.serial));
+            /* Rotation doesn't change an image's layout size, so lay out the upright area and paint the sideways texture into it */
+            let (rect, response) = ui.allocate_exact_size(Vec2::new(window_size::GAME_WIDTH, window_size::GAME_HEIGHT) * self.game_pixel_points, Sense::hover());
+            egui::Image::from_texture(&texture).rotate(-std::f32::consts::FRAC_PI_2, Vec2::splat(0.5))
+                .paint_at(ui, Rect::from_center_size(rect.center(), Vec2::new(rect.height(), rect.width())));
+            let description = self.strings.tr(if paused || self.debug_paused { "a11y.game_paused" } else { "a11y.game_running" });
+            response.widget_info(|| WidgetInfo::labeled(WidgetType::Other, description));
+            if paused {
+                ui.painter().rect_filled(response.rect, 0.0, Color32::from_black_alpha(160));
+                ui.painter().text(response.rect.center(), Align2::CENTER_CENTER, self.strings.tr("game.paused"), FontId::proportional(20.0), WHITE);
+                self.repaint.every(Consumer::PausedOverlay, PAUSED_REFRESH, Instant::now());
+            } else if self.turbo && !self.debug_paused && !self.fast_forward {
+                let position = response.rect.center_bottom() - Vec2::new(0.0, 8.0);
+                ui.painter().text(position, Align2::CENTER_BOTTOM, self.strings.tr("game.turbo"), FontId::monospace(14.0), Color32::YELLOW);
             }
-            if ctx.input(|i| i.key_pressed(Key::ArrowUp)) {
-                device2_bits = device2_bits | 0b00010000;
+            if self.show_input_log {
+                self.paint_input_log(ui, response.rect);
             }
-            *self.device1.lock().unwrap() = device1_bits;
-            *self.device2.lock().unwrap() = device2_bits;
+            if self.show_latency {
+                /* eframe 0.24 has no post-present callback, so handing egui the frame is the last point we see */
+                if let Some(meter) = self.latency.lock().unwrap().as_mut() {
+                    meter.presented(Instant::now(), vblank);
+                }
+                self.paint_latency(ui, response.rect);
+            }
+            self.control.set_input(&input_state);
         });
+        /* Tab is egui's focus traversal as well as the default turbo key; holding it in game must not walk into the menus */
+        if self.turbo && focus == Focus::Game {
+            if let Some(id) = ctx.memory(|m| m.focus()) {
+                ctx.memory_mut(|m| m.surrender_focus(id));
+            }
+        }
+        if let Some(delay) = self.repaint.next_delay(Instant::now()) {
+            ctx.request_repaint_after(delay);
+        }
+        if let Some(ui_ready) = self.ui_ready.take() {
+            ui_ready.send(()).ok();
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,17 +1,44 @@
-use std::fs::File;
-use std::io::BufReader;
-use rodio::{source::Source, source::Buffered,  Decoder, OutputStream, Sink};
+use std::io::Cursor;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use rodio::{source::Source, source::SineWave, source::UniformSourceIterator, Decoder, OutputStream, Sink};
+use crate::audio_queue::{AudioCommand, AudioSender, QUEUE_CAPACITY};
+use crate::av_sync::{self, Stamp, StampReceiver, StampSender};
+use crate::file_access::{self, ReadError};
+use crate::mixer::{self, Mixer};
+use crate::sound_latch::{SOUNDS, SOUND_COUNT};
 
-type BufferedWav = Buffered<Decoder<BufReader<File>>>;
+pub mod synth;
 
-pub struct AudioHandler {
-    sounds: Vec<Option<BufferedWav>>,
+const MIX_CHUNK: usize = 512;
+
+/* All rodio objects live on this thread, so a stalled driver call can only delay sound, never a frame.
+   The output stamps each game sound's frame when it starts mixing it, for av_sync */
+pub fn spawn(volume: f32, started: StampSender) -> AudioSender {
+    let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+    thread::spawn(move || {
+        let Some(mut audio_handler) = AudioHandler::try_new(started) else {
+            return;
+        };
+        audio_handler.apply(AudioCommand::Volume(volume));
+        for command in rx {
+            audio_handler.apply(command);
+        }
+    });
+    return AudioSender::new(tx);
+}
+
+struct AudioHandler {
+    mixer: Arc<Mutex<Mixer>>,
     _stream: OutputStream,
-    sinks: [Option<Sink>; 9],
+    blip_sink: Option<Sink>,
+    /* Frames of sounds handed to the mixer, for the output to stamp when it mixes them */
+    triggered: StampSender,
 }
 
 impl AudioHandler {
-    pub fn try_new() -> Option<Self> {
+    fn try_new(started: StampSender) -> Option<Self> {
         let (stream, stream_handle) = match OutputStream::try_default() {
             Ok(res) => res,
             Err(error) => {
@@ -19,37 +46,110 @@ impl AudioHandler {
                 return None;
             }
         };
-        let mut sounds: Vec<Option<BufferedWav>> = vec![];
-        let mut sinks: [Option<Sink>; 9] = Default::default();
-        for i in 0..9 {
-            let file = File::open(format!("{}.wav", i));
-            if file.is_ok() {
-                let file = BufReader::new(file.unwrap());
-                let source = Decoder::new(file).unwrap();
-                sounds.push(Some(source.buffered()));
-                sinks[i] = Some(Sink::try_new(&stream_handle).unwrap());
-            } else {
-                println!("Unable to load {}.wav, skipping.", i);
-                sounds.push(None);
-                sinks[i] = None;
+        let mut sounds: Vec<Option<Vec<f32>>> = vec![];
+        for i in 0..SOUND_COUNT {
+            match file_access::read(format!("{}.wav", i)) {
+                Ok(data) => {
+                    let source = Decoder::new(Cursor::new(data)).unwrap();
+                    sounds.push(Some(UniformSourceIterator::<_, f32>::new(source, 1, mixer::SAMPLE_RATE).collect()));
+                },
+                /* The usual case without a sample set; only a file that is there but can't be read is worth a warning */
+                Err(ReadError::Missing(_)) => {
+                    println!("Unable to load {}.wav, using the synthesized sound.", i);
+                    sounds.push(Some(synth::sound(i)));
+                },
+                Err(error) => {
+                    file_access::warn_once(&error);
+                    sounds.push(Some(synth::sound(i)));
+                },
             }
         }
-        Some(Self { sounds, _stream: stream, sinks, })
-    }
-
-    pub fn play_sound(&mut self, sound: usize) {
-        match &self.sounds[sound] {
-            Some(x) => {
-                match &self.sinks[sound] {
-                    Some(s) => {
-                        if s.empty() {
-                            s.append(x.clone());
-                        }
-                    }
-                    None => {}
+        let mut mixer = Mixer::new(sounds);
+        for sound in SOUNDS.iter().filter(|sound| sound.looping) {
+            mixer.set_looping(sound.index);
+        }
+        let mixer = Arc::new(Mutex::new(mixer));
+        let (triggered, triggers) = av_sync::channel(av_sync::AUDIO_CAPACITY, Instant::now());
+        let sink = Sink::try_new(&stream_handle).ok()?;
+        sink.append(MixerSource { mixer: Arc::clone(&mixer), buffer: vec![0.0; MIX_CHUNK], position: MIX_CHUNK, triggers, started });
+        sink.detach();
+        let blip_sink = Sink::try_new(&stream_handle).ok();
+        Some(Self { mixer, _stream: stream, blip_sink, triggered })
+    }
+
+    fn apply(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::Sound(event) if event.active => {
+                self.mixer.lock().unwrap().trigger(event.sound);
+                /* The sound test isn't tied to a presented frame */
+                if !event.sound_test {
+                    self.triggered.push(Stamp { frame: event.frame, at: Instant::now() });
+                }
+            },
+            AudioCommand::Sound(event) => self.mixer.lock().unwrap().release(event.sound),
+            AudioCommand::Blip { .. } => self.play_blip(),
+            AudioCommand::Volume(volume) => {
+                self.mixer.lock().unwrap().set_volume(volume);
+                if let Some(sink) = &self.blip_sink {
+                    sink.set_volume(volume);
+                }
+            },
+            AudioCommand::Speed(speed) => self.mixer.lock().unwrap().set_speed(speed),
+        }
+    }
+
+    fn play_blip(&mut self) {
+        match &self.blip_sink {
+            Some(s) => {
+                if s.empty() {
+                    s.append(SineWave::new(1_760.0).take_duration(Duration::from_millis(40)).amplify(0.2));
                 }
             },
             None => {}
         }
     }
-}
\ No newline at end of file
+}
+
+/* Endless source pulling chunks from the shared mixer */
+struct MixerSource {
+    mixer: Arc<Mutex<Mixer>>,
+    buffer: Vec<f32>,
+    position: usize,
+    triggers: StampReceiver,
+    started: StampSender,
+}
+
+impl Iterator for MixerSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position == self.buffer.len() {
+            /* Taken before rendering: a sound is triggered in the mixer before it is queued here, so this chunk plays it */
+            while let Some(trigger) = self.triggers.pop() {
+                self.started.push(Stamp { frame: trigger.frame, at: Instant::now() });
+            }
+            self.mixer.lock().unwrap().render(&mut self.buffer);
+            self.position = 0;
+        }
+        self.position += 1;
+        Some(self.buffer[self.position - 1])
+    }
+}
+
+impl Source for MixerSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        mixer::SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
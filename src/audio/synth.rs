@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+use crate::mixer::SAMPLE_RATE;
+use crate::rng::Rng;
+use crate::sound_latch::SOUND_COUNT;
+
+/* Rough stand-ins for the cabinet's analog sound circuits, used when n.wav is missing */
+const NOISE_SEED: u64 = 0x0000_1978_0009;
+const MARCH_HZ: [f32; 4] = [110.0, 98.0, 87.0, 82.0];
+
+pub fn sound(index: usize) -> Vec<f32> {
+    match index {
+        0 => ufo(),
+        1 => shot(),
+        2 => player_death(),
+        3 => invader_killed(),
+        4..=7 => march(MARCH_HZ[index - 4]),
+        8 => ufo_hit(),
+        9 => extended_play(),
+        _ => vec![],
+    }
+}
+
+fn render(seconds: f32, mut sample: impl FnMut(f32) -> f32) -> Vec<f32> {
+    let len = (seconds * SAMPLE_RATE as f32) as usize;
+    return (0..len).map(|i| sample(i as f32 / SAMPLE_RATE as f32).clamp(-1.0, 1.0)).collect();
+}
+
+fn square(phase: f32) -> f32 {
+    return if phase.fract() < 0.5 { 1.0 } else { -1.0 };
+}
+
+fn noise(rng: &mut Rng) -> f32 {
+    return rng.next_u8() as f32 / 127.5 - 1.0;
+}
+
+/* Two whole wobbles so the loop point is seamless */
+fn ufo() -> Vec<f32> {
+    let mut phase = 0.0;
+    return render(0.25, |t| {
+        phase += (700.0 + 250.0 * (t * 8.0 * std::f32::consts::TAU).sin()) / SAMPLE_RATE as f32;
+        0.3 * (phase * std::f32::consts::TAU).sin()
+    });
+}
+
+fn shot() -> Vec<f32> {
+    let mut phase = 0.0;
+    return render(0.3, |t| {
+        phase += (1_400.0 - 3_600.0 * t) / SAMPLE_RATE as f32;
+        0.25 * square(phase) * (1.0 - t / 0.3)
+    });
+}
+
+fn player_death() -> Vec<f32> {
+    let mut rng = Rng::new(NOISE_SEED);
+    return render(1.0, |t| 0.5 * noise(&mut rng) * (1.0 - t).powi(2));
+}
+
+fn invader_killed() -> Vec<f32> {
+    let mut rng = Rng::new(NOISE_SEED);
+    return render(0.3, |t| 0.5 * noise(&mut rng) * (1.0 - t / 0.3).powi(3));
+}
+
+fn march(hz: f32) -> Vec<f32> {
+    return render(0.1, |t| 0.5 * square(t * hz) * (1.0 - t / 0.1));
+}
+
+fn ufo_hit() -> Vec<f32> {
+    let mut phase = 0.0;
+    return render(1.0, |t| {
+        let hz = if (t * 16.0) as u32 % 2 == 0 { 1_100.0 } else { 700.0 };
+        phase += hz / SAMPLE_RATE as f32;
+        0.3 * square(phase) * (1.0 - t)
+    });
+}
+
+/* Eight short beeps, the cabinet's chime for a bonus base */
+fn extended_play() -> Vec<f32> {
+    return render(0.8, |t| if (t * 20.0) as u32 % 2 == 0 { 0.3 * square(t * 1_200.0) } else { 0.0 });
+}
+
+pub fn write_wav(path: &Path, samples: &[f32]) -> Result<(), hound::Error> {
+    let spec = hound::WavSpec { channels: 1, sample_rate: SAMPLE_RATE, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    return writer.finalize();
+}
+
+/* Writes 0.wav..9.wav into dir, ready to be edited and placed next to the ROM */
+pub fn generate_samples(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    std::fs::create_dir_all(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    let mut written = vec![];
+    for index in 0..SOUND_COUNT {
+        let path = dir.join(format!("{}.wav", index));
+        write_wav(&path, &sound(index)).map_err(|error| format!("{}: {}", path.display(), error))?;
+        written.push(path);
+    }
+    return Ok(written);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+    use rodio::{Decoder, Source};
+
+    #[test]
+    fn test_sounds_are_audible_and_in_range() {
+        for index in 0..SOUND_COUNT {
+            let samples = sound(index);
+            assert!(!samples.is_empty(), "sound {}", index);
+            assert!(samples.iter().all(|s| (-1.0..=1.0).contains(s)), "sound {}", index);
+            assert!(samples.iter().any(|s| s.abs() > 0.1), "sound {}", index);
+            assert_eq!(samples, sound(index));
+        }
+        assert!(sound(SOUND_COUNT).is_empty());
+    }
+
+    #[test]
+    fn test_generated_files_decode_to_the_same_length() {
+        let dir = std::env::temp_dir().join(format!("si_samples_{}", std::process::id()));
+        let written = generate_samples(&dir).unwrap();
+        assert_eq!(written.len(), SOUND_COUNT);
+        for (index, path) in written.iter().enumerate() {
+            assert_eq!(path.file_name().unwrap().to_str().unwrap(), format!("{}.wav", index));
+            let decoder = Decoder::new(BufReader::new(File::open(path).unwrap())).unwrap();
+            assert_eq!((decoder.channels(), decoder.sample_rate()), (1, SAMPLE_RATE));
+            let decoded: Vec<i16> = decoder.collect();
+            let expected = sound(index);
+            assert_eq!(decoded.len(), expected.len(), "sound {}", index);
+            let peak = expected.iter().map(|s| (s * i16::MAX as f32) as i16).max_by_key(|s| s.unsigned_abs()).unwrap();
+            assert!(decoded.contains(&peak));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::mpsc::{SyncSender, TrySendError};
+use crate::sound_latch::SOUND_COUNT;
+use crate::sound_timeline::SoundEvent;
+
+pub const QUEUE_CAPACITY: usize = 64;
+/* Sound that would start this many frames late is dropped rather than played out of sync */
+pub const STALE_FRAMES: u64 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioCommand {
+    Sound(SoundEvent),
+    Blip { frame: u64 },
+    Volume(f32),
+    /* Emulated frames per real frame, for mixer::speed_action */
+    Speed(f32),
+}
+
+impl AudioCommand {
+    /* Commands in the same slot supersede each other while waiting. A sound's start and stop get a slot
+       each, so a stop can't swallow the start of a one-shot sound that hasn't been played yet */
+    fn slot(&self) -> usize {
+        match self {
+            AudioCommand::Sound(event) => event.sound * 2 + event.active as usize,
+            AudioCommand::Blip { .. } => SOUND_COUNT * 2,
+            AudioCommand::Volume(_) => SOUND_COUNT * 2 + 1,
+            AudioCommand::Speed(_) => SOUND_COUNT * 2 + 2,
+        }
+    }
+
+    fn frame(&self) -> Option<u64> {
+        match self {
+            AudioCommand::Sound(event) => Some(event.frame),
+            AudioCommand::Blip { frame } => Some(*frame),
+            AudioCommand::Volume(_) | AudioCommand::Speed(_) => None,
+        }
+    }
+}
+
+/* Emulation side of the audio channel; never blocks. Commands the audio thread has no room for wait
+   here, where a newer command for the same sound or setting replaces the waiting one and anything
+   older than STALE_FRAMES is dropped. */
+pub struct AudioSender {
+    tx: SyncSender<AudioCommand>,
+    pending: Vec<AudioCommand>,
+    newest_frame: u64,
+    coalesced: u64,
+    dropped: u64,
+}
+
+impl AudioSender {
+    pub fn new(tx: SyncSender<AudioCommand>) -> Self {
+        Self {
+            tx,
+            pending: vec![],
+            newest_frame: 0,
+            coalesced: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn send(&mut self, command: AudioCommand) {
+        if let Some(frame) = command.frame() {
+            self.newest_frame = self.newest_frame.max(frame);
+        }
+        if let Some(index) = self.pending.iter().position(|waiting| waiting.slot() == command.slot()) {
+            self.pending.remove(index);
+            self.coalesced += 1;
+        }
+        self.pending.push(command);
+        self.flush();
+    }
+
+    pub fn flush(&mut self) {
+        let newest_frame = self.newest_frame;
+        let before = self.pending.len();
+        self.pending.retain(|command| command.frame().map_or(true, |frame| frame + STALE_FRAMES >= newest_frame));
+        self.dropped += (before - self.pending.len()) as u64;
+        while !self.pending.is_empty() {
+            match self.tx.try_send(self.pending[0]) {
+                Ok(()) => {
+                    self.pending.remove(0);
+                },
+                Err(TrySendError::Full(_)) => return,
+                /* No audio device: nothing will ever play, so there is nothing to count */
+                Err(TrySendError::Disconnected(_)) => {
+                    self.pending.clear();
+                    return;
+                },
+            }
+        }
+    }
+
+    pub fn coalesced(&self) -> u64 {
+        return self.coalesced;
+    }
+
+    pub fn dropped(&self) -> u64 {
+        return self.dropped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver};
+
+    fn sound(frame: u64, sound: usize, active: bool) -> AudioCommand {
+        AudioCommand::Sound(SoundEvent { frame, sound, active, sound_test: false })
+    }
+
+    fn queue(capacity: usize) -> (AudioSender, Receiver<AudioCommand>) {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        (AudioSender::new(tx), rx)
+    }
+
+    #[test]
+    fn test_commands_arrive_in_order() {
+        let (mut sender, rx) = queue(QUEUE_CAPACITY);
+        sender.send(sound(1, 0, true));
+        sender.send(AudioCommand::Blip { frame: 1 });
+        sender.send(sound(2, 0, false));
+        let received: Vec<AudioCommand> = rx.try_iter().collect();
+        assert_eq!(received, vec![sound(1, 0, true), AudioCommand::Blip { frame: 1 }, sound(2, 0, false)]);
+        assert_eq!((sender.coalesced(), sender.dropped()), (0, 0));
+    }
+
+    #[test]
+    fn test_full_channel_coalesces_per_sound_and_edge() {
+        let (mut sender, rx) = queue(1);
+        sender.send(sound(1, 3, true));
+        /* The channel is full from here on */
+        sender.send(sound(1, 4, true));
+        sender.send(sound(2, 4, false));
+        sender.send(sound(2, 5, true));
+        sender.send(sound(3, 5, true));
+        sender.send(AudioCommand::Volume(0.5));
+        sender.send(AudioCommand::Volume(0.25));
+        assert_eq!(sender.coalesced(), 2);
+
+        let mut received = vec![];
+        while let Ok(command) = rx.try_recv() {
+            received.push(command);
+            sender.flush();
+        }
+        /* Sound 4's trigger survives the stop that followed it */
+        assert_eq!(received, vec![sound(1, 3, true), sound(1, 4, true), sound(2, 4, false), sound(3, 5, true), AudioCommand::Volume(0.25)]);
+    }
+
+    #[test]
+    fn test_stale_events_are_dropped() {
+        let (mut sender, rx) = queue(1);
+        sender.send(sound(10, 1, true));
+        sender.send(sound(10, 2, true));
+        sender.send(AudioCommand::Volume(0.5));
+        sender.send(sound(10 + STALE_FRAMES, 3, true));
+        assert_eq!(sender.dropped(), 0);
+        sender.send(sound(11 + STALE_FRAMES, 4, true));
+        assert_eq!(sender.dropped(), 1);
+
+        let mut received = vec![];
+        while let Ok(command) = rx.try_recv() {
+            received.push(command);
+            sender.flush();
+        }
+        assert_eq!(received, vec![sound(10, 1, true), AudioCommand::Volume(0.5), sound(10 + STALE_FRAMES, 3, true), sound(11 + STALE_FRAMES, 4, true)]);
+    }
+
+    #[test]
+    fn test_disconnected_never_blocks() {
+        let (mut sender, rx) = queue(1);
+        drop(rx);
+        for frame in 0..100 {
+            sender.send(sound(frame, 0, frame % 2 == 0));
+        }
+        assert_eq!((sender.coalesced(), sender.dropped()), (0, 0));
+    }
+}
@@ -0,0 +1,255 @@
+// Audio/video sync instrumentation. The emulation thread stamps each frame's VBlank and the audio
+// output stamps the moment it starts mixing a frame's sounds; both go through fixed-size lock-free
+// rings, so neither real-time side ever waits on the UI that compares them.
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/* Sound further than this from its picture is called out in the Performance window */
+pub const DRIFT_WARNING: Duration = Duration::from_millis(50);
+pub const VIDEO_CAPACITY: usize = 128;
+pub const AUDIO_CAPACITY: usize = 64;
+/* Offsets averaged into the reported figure, about half a second of busy play */
+const OFFSET_WINDOW: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stamp {
+    pub frame: u64,
+    pub at: Instant,
+}
+
+/* Single producer, single consumer. Slots are atomics too, so the ring needs no unsafe: the producer
+   fills a slot and then publishes it by moving head (Release); the consumer sees head (Acquire) before
+   reading the slot, and hands it back by moving tail. Head and tail only grow; the slot is the counter
+   modulo the capacity */
+struct Ring {
+    epoch: Instant,
+    frames: Box<[AtomicU64]>,
+    nanos: Box<[AtomicU64]>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+/* Not Clone, so there is only ever one producer and one consumer per ring */
+pub struct StampSender(Arc<Ring>);
+pub struct StampReceiver(Arc<Ring>);
+
+/* Instants are kept as nanoseconds after the epoch; give every ring that will be compared the same one */
+pub fn channel(capacity: usize, epoch: Instant) -> (StampSender, StampReceiver) {
+    let slots = || (0..capacity).map(|_| AtomicU64::new(0)).collect::<Box<[AtomicU64]>>();
+    let ring = Arc::new(Ring { epoch, frames: slots(), nanos: slots(), head: AtomicUsize::new(0), tail: AtomicUsize::new(0) });
+    return (StampSender(Arc::clone(&ring)), StampReceiver(ring));
+}
+
+impl StampSender {
+    /* False, dropping the stamp, when the consumer has fallen a whole ring behind */
+    pub fn push(&self, stamp: Stamp) -> bool {
+        let ring = &self.0;
+        let head = ring.head.load(Ordering::Relaxed);
+        if head - ring.tail.load(Ordering::Acquire) == ring.frames.len() {
+            return false;
+        }
+        let slot = head % ring.frames.len();
+        ring.frames[slot].store(stamp.frame, Ordering::Relaxed);
+        ring.nanos[slot].store(stamp.at.saturating_duration_since(ring.epoch).as_nanos() as u64, Ordering::Relaxed);
+        ring.head.store(head + 1, Ordering::Release);
+        return true;
+    }
+}
+
+impl StampReceiver {
+    pub fn pop(&self) -> Option<Stamp> {
+        let ring = &self.0;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        if tail == ring.head.load(Ordering::Acquire) {
+            return None;
+        }
+        let slot = tail % ring.frames.len();
+        let stamp = Stamp {
+            frame: ring.frames[slot].load(Ordering::Relaxed),
+            at: ring.epoch + Duration::from_nanos(ring.nanos[slot].load(Ordering::Relaxed)),
+        };
+        ring.tail.store(tail + 1, Ordering::Release);
+        return Some(stamp);
+    }
+}
+
+/* Pairs each frame's sound stamps with its VBlank stamp and keeps a rolling mean of the gap */
+pub struct SyncMonitor {
+    /* Recent VBlanks, oldest first */
+    presented: VecDeque<Stamp>,
+    /* Sound stamps whose VBlank hasn't come through yet */
+    waiting: VecDeque<Stamp>,
+    /* Milliseconds, positive when the sound came after the picture */
+    offsets: VecDeque<f64>,
+}
+
+impl SyncMonitor {
+    pub fn new() -> Self {
+        Self { presented: VecDeque::new(), waiting: VecDeque::new(), offsets: VecDeque::new() }
+    }
+
+    /* Takes in everything both rings hold */
+    pub fn poll(&mut self, video: &StampReceiver, audio: &StampReceiver) {
+        while let Some(stamp) = video.pop() {
+            self.record_video(stamp);
+        }
+        while let Some(stamp) = audio.pop() {
+            self.record_audio(stamp);
+        }
+    }
+
+    pub fn record_video(&mut self, stamp: Stamp) {
+        if self.presented.len() == VIDEO_CAPACITY {
+            self.presented.pop_front();
+        }
+        self.presented.push_back(stamp);
+        for sound in std::mem::take(&mut self.waiting) {
+            self.record_audio(sound);
+        }
+    }
+
+    pub fn record_audio(&mut self, stamp: Stamp) {
+        let Some(picture) = self.presented.iter().rev().find(|picture| picture.frame == stamp.frame) else {
+            /* Frames older than every VBlank kept will never be matched */
+            let stale = self.presented.front().map_or(false, |oldest| stamp.frame < oldest.frame);
+            if !stale && self.waiting.len() < AUDIO_CAPACITY {
+                self.waiting.push_back(stamp);
+            }
+            return;
+        };
+        let offset = match stamp.at.checked_duration_since(picture.at) {
+            Some(late) => late.as_secs_f64() * 1_000.0,
+            None => -(picture.at.duration_since(stamp.at).as_secs_f64() * 1_000.0),
+        };
+        if self.offsets.len() == OFFSET_WINDOW {
+            self.offsets.pop_front();
+        }
+        self.offsets.push_back(offset);
+    }
+
+    /* Mean of the recent offsets in milliseconds; None until a sound has been matched */
+    pub fn offset_ms(&self) -> Option<f64> {
+        if self.offsets.is_empty() {
+            return None;
+        }
+        return Some(self.offsets.iter().sum::<f64>() / self.offsets.len() as f64);
+    }
+
+    pub fn drifting(&self) -> bool {
+        return self.offset_ms().map_or(false, |offset| offset.abs() > DRIFT_WARNING.as_secs_f64() * 1_000.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(epoch: Instant, frame: u64, ms: u64) -> Stamp {
+        return Stamp { frame, at: epoch + Duration::from_millis(ms) };
+    }
+
+    #[test]
+    fn test_ring_order_and_capacity() {
+        let epoch = Instant::now();
+        let (tx, rx) = channel(4, epoch);
+        assert_eq!(rx.pop(), None);
+        for frame in 0..4 {
+            assert!(tx.push(stamp(epoch, frame, frame * 16)));
+        }
+        assert!(!tx.push(stamp(epoch, 4, 64)));
+        assert_eq!(rx.pop(), Some(stamp(epoch, 0, 0)));
+        /* Freed slots are reused, wrapping round the ring */
+        assert!(tx.push(stamp(epoch, 5, 80)));
+        let frames: Vec<u64> = std::iter::from_fn(|| rx.pop()).map(|stamp| stamp.frame).collect();
+        assert_eq!(frames, [1, 2, 3, 5]);
+        assert_eq!(rx.pop(), None);
+    }
+
+    #[test]
+    fn test_ring_across_threads() {
+        let epoch = Instant::now();
+        let (tx, rx) = channel(8, epoch);
+        let producer = std::thread::spawn(move || {
+            for frame in 0..10_000 {
+                while !tx.push(stamp(epoch, frame, frame)) {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < 10_000 {
+            match rx.pop() {
+                Some(received) => {
+                    assert_eq!(received, stamp(epoch, expected, expected));
+                    expected += 1;
+                },
+                None => std::thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn test_offset_is_mean_gap_to_the_same_frame() {
+        let epoch = Instant::now();
+        let mut monitor = SyncMonitor::new();
+        assert_eq!(monitor.offset_ms(), None);
+        for frame in 0..10 {
+            monitor.record_video(stamp(epoch, frame, 100 + frame * 16));
+        }
+        monitor.record_audio(stamp(epoch, 3, 100 + 3 * 16 + 10));
+        monitor.record_audio(stamp(epoch, 7, 100 + 7 * 16 + 30));
+        assert!((monitor.offset_ms().unwrap() - 20.0).abs() < 1e-6);
+        assert!(!monitor.drifting());
+        /* Sound ahead of its picture counts against the mean */
+        monitor.record_audio(stamp(epoch, 9, 100 + 9 * 16 - 40));
+        assert!(monitor.offset_ms().unwrap().abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sound_before_its_vblank_arrives_waits() {
+        let epoch = Instant::now();
+        let mut monitor = SyncMonitor::new();
+        monitor.record_video(stamp(epoch, 0, 0));
+        monitor.record_audio(stamp(epoch, 1, 90));
+        assert_eq!(monitor.offset_ms(), None);
+        monitor.record_video(stamp(epoch, 1, 16));
+        assert!((monitor.offset_ms().unwrap() - 74.0).abs() < 1e-6);
+        assert!(monitor.drifting());
+        /* Older than anything kept: never matched, never held */
+        monitor.record_audio(stamp(epoch, 0, 20));
+        monitor.record_video(stamp(epoch, 2, 32));
+        assert_eq!(monitor.waiting.len(), 0);
+    }
+
+    #[test]
+    fn test_window_rolls() {
+        let epoch = Instant::now();
+        let mut monitor = SyncMonitor::new();
+        monitor.record_video(stamp(epoch, 0, 0));
+        for _ in 0..OFFSET_WINDOW {
+            monitor.record_audio(stamp(epoch, 0, 80));
+        }
+        assert!(monitor.drifting());
+        for _ in 0..OFFSET_WINDOW {
+            monitor.record_audio(stamp(epoch, 0, 5));
+        }
+        assert!((monitor.offset_ms().unwrap() - 5.0).abs() < 1e-6);
+        assert!(!monitor.drifting());
+    }
+
+    #[test]
+    fn test_poll_drains_both_rings() {
+        let epoch = Instant::now();
+        let (video_tx, video_rx) = channel(VIDEO_CAPACITY, epoch);
+        let (audio_tx, audio_rx) = channel(AUDIO_CAPACITY, epoch);
+        audio_tx.push(stamp(epoch, 4, 120));
+        video_tx.push(stamp(epoch, 4, 100));
+        let mut monitor = SyncMonitor::new();
+        monitor.poll(&video_rx, &audio_rx);
+        assert!((monitor.offset_ms().unwrap() - 20.0).abs() < 1e-6);
+        assert_eq!((video_rx.pop(), audio_rx.pop()), (None, None));
+    }
+}
@@ -0,0 +1,29 @@
+/* Cheats work on the watched copy of work RAM and change the game only through queued writes, so they
+   land at a frame boundary like any other memory access from the UI */
+use crate::ram_map;
+
+/* What infinite lives keeps each player's reserve at */
+pub const INFINITE_LIVES_SHIPS: u8 = 3;
+
+/* The writes that put back any ships lost since the last top-up; empty when nothing is short */
+pub fn infinite_lives(ram: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    return [ram_map::P1_SHIPS, ram_map::P2_SHIPS].into_iter()
+        .filter(|addr| ram_map::read(ram, *addr) < INFINITE_LIVES_SHIPS)
+        .map(|addr| (addr, vec![INFINITE_LIVES_SHIPS]))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infinite_lives_tops_up_only_short_players() {
+        let mut ram = vec![0; ram_map::WORK_RAM_SIZE];
+        ram[(ram_map::P1_SHIPS - ram_map::WORK_RAM_START) as usize] = 1;
+        ram[(ram_map::P2_SHIPS - ram_map::WORK_RAM_START) as usize] = 5;
+        assert_eq!(infinite_lives(&ram), [(ram_map::P1_SHIPS, vec![INFINITE_LIVES_SHIPS])]);
+        ram[(ram_map::P1_SHIPS - ram_map::WORK_RAM_START) as usize] = INFINITE_LIVES_SHIPS;
+        assert!(infinite_lives(&ram).is_empty());
+    }
+}
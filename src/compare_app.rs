@@ -0,0 +1,84 @@
+use eframe::egui::*;
+use std::time::Duration;
+use crate::application;
+use crate::hotkeys::{Focus, KeyBindings};
+use crate::input::InputManager;
+use crate::lockstep::Lockstep;
+use crate::machine::DipSwitches;
+use crate::settings::Settings;
+use crate::strings::Strings;
+use crate::video::{self, Palette, VideoAdjust};
+
+const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+const IN_STEP: Color32 = Color32::from_rgb(0, 200, 0);
+const DIVERGED: Color32 = Color32::from_rgb(230, 40, 40);
+
+/* Both cores are stepped on the UI thread: comparison runs are about determinism, not smooth pacing */
+pub struct CompareApp {
+    lockstep: Lockstep,
+    names: [String; 2],
+    paused: bool,
+    input_manager: InputManager,
+    key_bindings: KeyBindings,
+    stretch_short_presses: bool,
+    palette: Palette,
+    strings: Strings,
+}
+
+impl CompareApp {
+    pub fn new(cc: &eframe::CreationContext<'_>, lockstep: Lockstep, names: [String; 2]) -> Self {
+        let settings: Settings = cc.storage.and_then(|storage| eframe::get_value(storage, eframe::APP_KEY)).unwrap_or_default();
+        Self {
+            lockstep,
+            names,
+            paused: false,
+            input_manager: InputManager::new(),
+            key_bindings: KeyBindings::default(),
+            stretch_short_presses: settings.stretch_short_presses,
+            palette: Palette::new(&VideoAdjust::default(), false),
+            strings: Strings::new(settings.language),
+        }
+    }
+}
+
+impl eframe::App for CompareApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        /* Keyboard only: both cores get the same input, and gamepads aren't polled here */
+        let input = self.input_manager.read(ctx, DipSwitches::default(), &self.key_bindings, Focus::Game, self.lockstep.steps(), self.stretch_short_presses, &[]);
+        if !self.paused && !self.lockstep.step(&input) {
+            self.paused = true;
+        }
+        egui::TopBottomPanel::top("compare_status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let (text, color) = match self.lockstep.divergence() {
+                    Some(frame) => (self.strings.tr_with("compare.diverged", &[&frame.to_string()]), DIVERGED),
+                    None => (self.strings.tr_with("compare.in_step", &[&self.lockstep.steps().to_string()]), IN_STEP),
+                };
+                ui.label(RichText::new(text).strong().color(color));
+                let [left, right] = self.lockstep.checksums();
+                ui.label(RichText::new(format!("{:016X} {:016X}", left, right)).monospace());
+                let toggle = if self.paused { "compare.resume" } else { "compare.pause" };
+                let enabled = self.lockstep.divergence().is_none();
+                if ui.add_enabled(enabled, egui::Button::new(self.strings.tr(toggle))).clicked() {
+                    self.paused = !self.paused;
+                }
+            });
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (index, core) in self.lockstep.cores().iter().enumerate() {
+                    ui.vertical(|ui| {
+                        ui.label(&self.names[index]);
+                        let pixels = application::scale_frame(&self.palette.convert(&core.vram()));
+                        let image = ColorImage { size: [video::WIDTH * application::SCALE, video::HEIGHT * application::SCALE], pixels };
+                        let texture = ctx.load_texture(format!("compare_{}", index), image, TextureOptions::LINEAR);
+                        ui.add(egui::Image::from_texture(&texture).rotate(-std::f32::consts::FRAC_PI_2, Vec2::splat(0.5)));
+                    });
+                }
+            });
+        });
+        if !self.paused {
+            ctx.request_repaint_after(FRAME_INTERVAL);
+        }
+    }
+}
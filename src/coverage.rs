@@ -0,0 +1,162 @@
+use std::fmt::Write;
+use std::ops::Range;
+use intel8080::disassembler;
+use intel8080::profile::Profile;
+
+pub const STRIP_WIDTH: usize = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteClass {
+    Untouched,
+    Data,
+    Operand,
+    Executed,
+}
+
+/* Classification of every ROM byte; an opcode fetch beats an operand fetch beats a data read */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageMap {
+    classes: Vec<ByteClass>,
+}
+
+impl CoverageMap {
+    pub fn from_profile(profile: &Profile, rom_size: usize) -> Self {
+        let classes = (0..rom_size)
+            .map(|addr| {
+                let addr = addr as u16;
+                if profile.executions(addr) > 0 {
+                    ByteClass::Executed
+                } else if profile.read_as_operand(addr) {
+                    ByteClass::Operand
+                } else if profile.read_as_data(addr) {
+                    ByteClass::Data
+                } else {
+                    ByteClass::Untouched
+                }
+            })
+            .collect();
+        Self { classes }
+    }
+
+    pub fn classes(&self) -> &[ByteClass] {
+        return &self.classes;
+    }
+
+    pub fn count(&self, class: ByteClass) -> usize {
+        return self.classes.iter().filter(|c| **c == class).count();
+    }
+
+    /* Executed, operand or data, as a share of the whole ROM */
+    pub fn percent_touched(&self) -> f32 {
+        if self.classes.is_empty() {
+            return 0.0;
+        }
+        return 100.0 * (self.classes.len() - self.count(ByteClass::Untouched)) as f32 / self.classes.len() as f32;
+    }
+
+    /* One cell per byte, STRIP_WIDTH bytes per row */
+    pub fn strip_rows(&self) -> usize {
+        return (self.classes.len() + STRIP_WIDTH - 1) / STRIP_WIDTH;
+    }
+
+    pub fn unreached_regions(&self) -> Vec<Range<usize>> {
+        let mut regions = vec![];
+        let mut start = None;
+        for (addr, class) in self.classes.iter().enumerate() {
+            match (class, start) {
+                (ByteClass::Untouched, None) => start = Some(addr),
+                (ByteClass::Untouched, Some(_)) => {},
+                (_, Some(from)) => {
+                    regions.push(from..addr);
+                    start = None;
+                },
+                (_, None) => {},
+            }
+        }
+        if let Some(from) = start {
+            regions.push(from..self.classes.len());
+        }
+        return regions;
+    }
+
+    pub fn report(&self, rom: &[u8]) -> String {
+        let mut report = String::new();
+        writeln!(report, "ROM coverage: {:.1}% touched", self.percent_touched()).ok();
+        writeln!(report, "  executed  {:>5} bytes", self.count(ByteClass::Executed)).ok();
+        writeln!(report, "  operand   {:>5} bytes", self.count(ByteClass::Operand)).ok();
+        writeln!(report, "  data      {:>5} bytes", self.count(ByteClass::Data)).ok();
+        writeln!(report, "  untouched {:>5} bytes", self.count(ByteClass::Untouched)).ok();
+        for region in self.unreached_regions() {
+            writeln!(report, "\nUnreached ${:04X}-${:04X} ({} bytes)", region.start, region.end - 1, region.len()).ok();
+            let mut addr = region.start;
+            while addr < region.end {
+                let bytes = [0, 1, 2].map(|i| rom.get(addr + i).copied().unwrap_or(0));
+                let (instruction, length) = disassembler::disassemble(bytes);
+                writeln!(report, "  ${:04X}  {}", addr, instruction).ok();
+                addr += length.max(1);
+            }
+        }
+        return report;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intel8080::asm;
+    use crate::emulator_core::EmulatorCore;
+    use crate::machine::{self, InputState};
+
+    /* The loop reads its table through HL, so the table is data while the routine after it never runs */
+    const PROGRAM: &str = "
+        start:  LXI H,table
+                MVI B,3
+        loop:   MOV A,M
+                INX H
+                DCR B
+                JNZ loop
+        idle:   JMP idle
+        table:  DB 1,2,3,4
+        unused: MVI A,$FF
+                RET
+    ";
+
+    fn covered() -> (Vec<u8>, CoverageMap) {
+        let program = asm::assemble(PROGRAM).unwrap();
+        let mut rom = [0; machine::ROM_SIZE];
+        rom[..program.len()].copy_from_slice(&program);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.enable_coverage();
+        core.step_frame();
+        let map = core.coverage().unwrap();
+        return (program, map);
+    }
+
+    #[test]
+    fn test_classifies_code_operands_and_tables() {
+        let (_, map) = covered();
+        let classes = map.classes();
+        assert_eq!(classes.len(), machine::ROM_SIZE);
+        /* LXI H,table */
+        assert_eq!(&classes[0..3], &[ByteClass::Executed, ByteClass::Operand, ByteClass::Operand]);
+        /* MOV A,M executes, JNZ loop has two operand bytes */
+        assert_eq!(classes[5], ByteClass::Executed);
+        assert_eq!(&classes[8..11], &[ByteClass::Executed, ByteClass::Operand, ByteClass::Operand]);
+        /* Three of the four table entries are read */
+        assert_eq!(&classes[14..18], &[ByteClass::Data, ByteClass::Data, ByteClass::Data, ByteClass::Untouched]);
+        assert_eq!(&classes[18..21], &[ByteClass::Untouched; 3]);
+        assert_eq!(map.count(ByteClass::Executed), 7);
+    }
+
+    #[test]
+    fn test_unreached_regions_and_report() {
+        let (program, map) = covered();
+        let regions = map.unreached_regions();
+        assert_eq!(regions, vec![17..machine::ROM_SIZE]);
+        assert_eq!(map.strip_rows(), 64);
+        let report = map.report(&program);
+        assert!(report.contains("Unreached $0011-$1FFF"));
+        assert!(report.contains("  $0012  MVI A,$FF"));
+        assert!(report.contains("  $0014  RET"));
+    }
+}
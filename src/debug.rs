@@ -0,0 +1,45 @@
+use intel8080::conditions::Conditions;
+use intel8080::disassembler;
+use intel8080::emulator::CpuState;
+use crate::shift_register::ShiftRegister;
+
+pub enum DebugCommand {
+    Coverage(bool),
+    ExportCoverage,
+    BreakOnUnexpectedReset(bool),
+    /* Routine entries to pause at, replacing the previous set */
+    Breakpoints(Vec<u16>),
+}
+
+/* A consistent view of the CPU, and the shift register IN 3 reads, between instructions; published by the emulation thread */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebugSnapshot {
+    pub cpu: CpuState,
+    pub next_bytes: [u8; 3],
+    pub shift_register: ShiftRegister,
+}
+
+impl DebugSnapshot {
+    pub fn status_line(&self) -> String {
+        let cpu = &self.cpu;
+        let (instruction, _) = disassembler::disassemble(self.next_bytes);
+        format!("PC=${:04X}  {:<12} A={:02X} BC={:02X}{:02X} DE={:02X}{:02X} HL={:02X}{:02X} SP={:04X}  {}  SHIFT={:04X}/{}",
+            cpu.pc, instruction, cpu.a, cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l, cpu.sp, Conditions::from_bits(cpu.flags),
+            self.shift_register.raw(), self.shift_register.offset())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line() {
+        let snapshot = DebugSnapshot {
+            cpu: CpuState { a: 0, b: 0, c: 0, d: 0, e: 0, h: 0x24, l: 0x00, pc: 0x1A32, sp: 0x23FE, flags: 0b01000011, interrupt_enabled: false, halted: false, wait_cycles: 0 },
+            next_bytes: [0x21, 0x00, 0x24],
+            shift_register: ShiftRegister::from_raw(0xABCD, 3),
+        };
+        assert_eq!(snapshot.status_line(), "PC=$1A32  LXI H,$2400  A=00 BC=0000 DE=0000 HL=2400 SP=23FE  .Z-.-.-C  SHIFT=ABCD/3");
+    }
+}
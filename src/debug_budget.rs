@@ -0,0 +1,226 @@
+// One memory cap for all the debugging buffers. Each buffer registers with a weight and its entry size and
+// is given a share of the cap, counted in entries, in proportion to its weight among the buffers currently
+// registered. A buffer drops its oldest entries to stay inside its share; the share is handed back to the
+// others when the buffer's last handle is dropped.
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+pub const DEFAULT_BUDGET_MB: u32 = 256;
+
+pub fn megabytes(mb: u32) -> usize {
+    return mb as usize * 1024 * 1024;
+}
+
+struct Inner {
+    total: usize,
+    buffers: Vec<Weak<Share>>,
+}
+
+struct Share {
+    name: &'static str,
+    weight: usize,
+    entry_size: usize,
+    max_entries: AtomicUsize,
+    used: AtomicUsize,
+    budget: Weak<Mutex<Inner>>,
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        if let Some(inner) = self.budget.upgrade() {
+            rebalance(&inner);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferUsage {
+    pub name: &'static str,
+    pub used: usize,
+    /* The share, in bytes */
+    pub limit: usize,
+}
+
+/* Cheap to clone; every clone is the same registry */
+#[derive(Clone)]
+pub struct DebugBufferBudget {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl DebugBufferBudget {
+    pub fn new(total: usize) -> Self {
+        Self { inner: Arc::new(Mutex::new(Inner { total, buffers: vec![] })) }
+    }
+
+    /* Buffers over their new share shrink on their next push */
+    pub fn set_total(&self, total: usize) {
+        self.inner.lock().unwrap().total = total;
+        rebalance(&self.inner);
+    }
+
+    pub fn register(&self, name: &'static str, weight: usize, entry_size: usize) -> BudgetShare {
+        let share = Arc::new(Share {
+            name,
+            weight: weight.max(1),
+            entry_size: entry_size.max(1),
+            max_entries: AtomicUsize::new(0),
+            used: AtomicUsize::new(0),
+            budget: Arc::downgrade(&self.inner),
+        });
+        self.inner.lock().unwrap().buffers.push(Arc::downgrade(&share));
+        rebalance(&self.inner);
+        return BudgetShare { share };
+    }
+
+    /* In registration order */
+    pub fn usage(&self) -> Vec<BufferUsage> {
+        let live: Vec<Arc<Share>> = self.inner.lock().unwrap().buffers.iter().filter_map(Weak::upgrade).collect();
+        return live.iter().map(|share| BufferUsage {
+            name: share.name,
+            used: share.used.load(Ordering::Relaxed),
+            limit: share.max_entries.load(Ordering::Relaxed) * share.entry_size,
+        }).collect();
+    }
+}
+
+/* The live shares are collected under the lock but released after it, since dropping the last handle
+   to one re-enters here */
+fn rebalance(inner: &Mutex<Inner>) {
+    let live: Vec<Arc<Share>>;
+    {
+        let Ok(mut inner) = inner.lock() else {
+            return;
+        };
+        inner.buffers.retain(|share| share.strong_count() > 0);
+        live = inner.buffers.iter().filter_map(Weak::upgrade).collect();
+        let weights: usize = live.iter().map(|share| share.weight).sum();
+        for share in &live {
+            let bytes = (inner.total as u128 * share.weight as u128 / weights as u128) as usize;
+            share.max_entries.store(bytes / share.entry_size, Ordering::Relaxed);
+        }
+    }
+    drop(live);
+}
+
+/* A buffer's handle on its share; clones share it */
+#[derive(Clone)]
+pub struct BudgetShare {
+    share: Arc<Share>,
+}
+
+impl BudgetShare {
+    pub fn max_entries(&self) -> usize {
+        return self.share.max_entries.load(Ordering::Relaxed);
+    }
+
+    pub fn record(&self, entries: usize) {
+        self.share.used.store(entries * self.share.entry_size, Ordering::Relaxed);
+    }
+}
+
+/* Appends within the buffer's own capacity and its share, dropping the oldest entries first. With no room
+   for even one entry the buffer is emptied and the entry discarded */
+pub fn push_bounded<T>(entries: &mut VecDeque<T>, entry: T, capacity: usize, budget: Option<&BudgetShare>) {
+    let limit = budget.map_or(capacity, |share| share.max_entries().min(capacity));
+    if limit == 0 {
+        entries.clear();
+    } else {
+        while entries.len() >= limit {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+    if let Some(share) = budget {
+        share.record(entries.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Stand-in buffer whose entries are entry_size bytes each */
+    struct Synthetic {
+        entries: VecDeque<u32>,
+        budget: BudgetShare,
+    }
+
+    impl Synthetic {
+        fn new(budget: &DebugBufferBudget, name: &'static str, weight: usize, entry_size: usize) -> Self {
+            Self { entries: VecDeque::new(), budget: budget.register(name, weight, entry_size) }
+        }
+
+        fn fill(&mut self, count: u32) {
+            for value in 0..count {
+                push_bounded(&mut self.entries, value, usize::MAX, Some(&self.budget));
+            }
+        }
+    }
+
+    fn limits(budget: &DebugBufferBudget) -> Vec<(&'static str, usize)> {
+        return budget.usage().iter().map(|usage| (usage.name, usage.limit)).collect();
+    }
+
+    #[test]
+    fn test_shares_follow_weights() {
+        let budget = DebugBufferBudget::new(1_000);
+        let trace = Synthetic::new(&budget, "trace", 3, 10);
+        assert_eq!(limits(&budget), [("trace", 1_000)]);
+        let io = Synthetic::new(&budget, "io", 1, 8);
+        /* 750 bytes is 75 ten-byte entries; 250 bytes is 31 whole eight-byte ones */
+        assert_eq!((trace.budget.max_entries(), io.budget.max_entries()), (75, 31));
+        assert_eq!(limits(&budget), [("trace", 750), ("io", 248)]);
+        budget.set_total(2_000);
+        assert_eq!(limits(&budget), [("trace", 1_500), ("io", 496)]);
+    }
+
+    #[test]
+    fn test_dropped_buffer_returns_its_share() {
+        let budget = DebugBufferBudget::new(1_000);
+        let trace = Synthetic::new(&budget, "trace", 1, 10);
+        let io = Synthetic::new(&budget, "io", 1, 10);
+        assert_eq!(trace.budget.max_entries(), 50);
+        drop(io);
+        assert_eq!(limits(&budget), [("trace", 1_000)]);
+        assert_eq!(trace.budget.max_entries(), 100);
+    }
+
+    #[test]
+    fn test_buffer_keeps_newest_within_share() {
+        let budget = DebugBufferBudget::new(1_000);
+        let mut trace = Synthetic::new(&budget, "trace", 1, 100);
+        trace.fill(25);
+        assert_eq!(trace.entries, (15..25).collect::<VecDeque<u32>>());
+        assert_eq!(budget.usage()[0].used, 1_000);
+    }
+
+    #[test]
+    fn test_shrinks_oldest_under_pressure() {
+        let budget = DebugBufferBudget::new(1_000);
+        let mut trace = Synthetic::new(&budget, "trace", 1, 100);
+        trace.fill(10);
+        /* A second buffer halves the share; the first gives up its oldest entries on its next push */
+        let mut io = Synthetic::new(&budget, "io", 1, 100);
+        push_bounded(&mut trace.entries, 10, usize::MAX, Some(&trace.budget));
+        assert_eq!(trace.entries, (6..11).collect::<VecDeque<u32>>());
+        io.fill(3);
+        let used: Vec<usize> = budget.usage().iter().map(|usage| usage.used).collect();
+        assert_eq!(used, [500, 300]);
+        assert!(budget.usage().iter().map(|usage| usage.used).sum::<usize>() <= 1_000);
+    }
+
+    #[test]
+    fn test_capacity_and_empty_share() {
+        let mut entries = VecDeque::new();
+        for value in 0..5 {
+            push_bounded(&mut entries, value, 3, None);
+        }
+        assert_eq!(entries, [2, 3, 4]);
+        let budget = DebugBufferBudget::new(50);
+        let mut tiny = Synthetic::new(&budget, "tiny", 1, 100);
+        tiny.fill(4);
+        assert!(tiny.entries.is_empty());
+        assert_eq!(budget.usage()[0].used, 0);
+    }
+}
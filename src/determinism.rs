@@ -0,0 +1,131 @@
+// Determinism check: the same ROM, seed and input must give the same machine every frame.
+use std::fmt;
+use crate::emulator_core::EmulatorCore;
+use crate::machine::{self, InputShaper, InputState, LogicalInput};
+use crate::rng::Rng;
+
+/* Both cores are seeded and power on with randomized RAM, so the seeded paths are covered too */
+pub const SEED: u64 = 0x5EED_1978;
+
+#[derive(Debug, PartialEq)]
+pub struct DivergenceReport {
+    /* Frames run when the cores first differed, counting from 1 */
+    pub frame: u64,
+    /* SaveState::diff of the left core against the right */
+    pub diff: Vec<String>,
+}
+
+impl fmt::Display for DivergenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cores diverged after frame {}:", self.frame)?;
+        for line in &self.diff {
+            write!(f, "\n  {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/* Runs two independent cores for frames frames on the same input script, comparing RAM and CPU state
+   after every frame. Frame i gets inputs[i], or the last entry once the script runs out */
+pub fn verify_determinism(rom: [u8; machine::ROM_SIZE], inputs: &[InputState], frames: u64) -> Result<(), DivergenceReport> {
+    return verify_cores(power_on(rom), power_on(rom), inputs, frames);
+}
+
+/* The same, with idle skip on in the right core only */
+pub fn verify_idle_skip(rom: [u8; machine::ROM_SIZE], inputs: &[InputState], frames: u64) -> Result<(), DivergenceReport> {
+    let mut skipping = power_on(rom);
+    skipping.set_idle_skip(true);
+    return verify_cores(power_on(rom), skipping, inputs, frames);
+}
+
+fn power_on(rom: [u8; machine::ROM_SIZE]) -> EmulatorCore {
+    let mut core = EmulatorCore::new(rom, InputState::default());
+    core.set_seed(SEED);
+    core.randomize_ram();
+    return core;
+}
+
+pub fn verify_cores(mut left: EmulatorCore, mut right: EmulatorCore, inputs: &[InputState], frames: u64) -> Result<(), DivergenceReport> {
+    let mut shaper = InputShaper::default();
+    for frame in 0..frames {
+        let raw = inputs.get(frame as usize).or(inputs.last()).copied().unwrap_or_default();
+        let input = shaper.shape(&raw);
+        for core in [&mut left, &mut right] {
+            core.set_input(input);
+            core.step_frame();
+        }
+        if left.ram_checksum() != right.ram_checksum() || left.debug_snapshot().cpu != right.debug_snapshot().cpu {
+            return Err(DivergenceReport { frame: frame + 1, diff: left.save_state().diff(&right.save_state()) });
+        }
+    }
+    return Ok(());
+}
+
+/* Seeded input that coins up, starts games and moves and fires, changing every few frames */
+pub fn scripted_inputs(seed: u64, frames: u64) -> Vec<InputState> {
+    let mut rng = Rng::new(seed);
+    let mut input = InputState::default();
+    let mut inputs = vec![];
+    for _ in 0..frames {
+        for logical in LogicalInput::ALL {
+            let odds = match logical {
+                LogicalInput::Tilt => continue,
+                LogicalInput::Coin | LogicalInput::P1Start | LogicalInput::P2Start => 300,
+                _ => 10,
+            };
+            if rng.next_u64() % odds == 0 {
+                input.set(logical, !input.get(logical));
+            }
+        }
+        inputs.push(input);
+    }
+    return inputs;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rom;
+
+    #[test]
+    fn test_builtin_rom_is_deterministic() {
+        assert_eq!(verify_determinism(test_rom::rom(), &scripted_inputs(1, 300), 300), Ok(()));
+    }
+
+    #[test]
+    fn test_idle_skip_is_bit_identical() {
+        assert_eq!(verify_idle_skip(test_rom::rom(), &scripted_inputs(SEED, 600), 600), Ok(()));
+    }
+
+    #[test]
+    fn test_divergence_reports_frame_and_diff() {
+        /* Same ROM and input, different power-on RAM */
+        let mut left = EmulatorCore::new(test_rom::rom(), InputState::default());
+        left.set_seed(SEED);
+        left.randomize_ram();
+        let mut right = EmulatorCore::new(test_rom::rom(), InputState::default());
+        right.set_seed(SEED + 1);
+        right.randomize_ram();
+        let report = verify_cores(left, right, &[], 600).unwrap_err();
+        assert!(report.frame <= 600);
+        assert!(!report.diff.is_empty());
+        assert!(report.to_string().starts_with(&format!("cores diverged after frame {}:\n  ", report.frame)), "{}", report);
+    }
+
+    #[test]
+    fn test_script_is_seeded() {
+        let inputs = scripted_inputs(7, 50);
+        assert_eq!(inputs, scripted_inputs(7, 50));
+        assert_ne!(inputs, scripted_inputs(8, 50));
+        assert!(inputs.iter().all(|input| !input.get(LogicalInput::Tilt)));
+    }
+
+    /* cargo test -- --ignored; an hour of emulated play */
+    #[test]
+    #[ignore]
+    fn test_long_run_is_deterministic() {
+        let frames = 60 * 60 * 60;
+        let result = verify_determinism(test_rom::rom(), &scripted_inputs(SEED, frames), frames);
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+    }
+}
@@ -0,0 +1,125 @@
+// Plain-text reports of the debugger's state for bug reports. The layout is fixed and in English
+// whatever the interface language, so triage scripts can parse what users paste.
+use std::ops::Range;
+use intel8080::conditions::Conditions;
+use intel8080::disassembler;
+use crate::debug::DebugSnapshot;
+
+pub const DUMP_BYTES_PER_LINE: usize = 16;
+/* Lines of memory dumped before and after the one holding PC */
+const DUMP_CONTEXT_LINES: u16 = 2;
+
+pub struct BuildInfo<'a> {
+    pub app: &'a str,
+    pub version: &'a str,
+    pub renderer: &'a str,
+    pub rom: &'a str,
+}
+
+pub fn header(build: &BuildInfo, frame: u64) -> String {
+    let rom = if build.rom.is_empty() { "unknown" } else { build.rom };
+    return format!("{} {}\nRenderer: {}\nROM:      {}\nFrame:    {}\n", build.app, build.version, build.renderer, rom, frame);
+}
+
+pub fn registers(snapshot: &DebugSnapshot) -> String {
+    let cpu = &snapshot.cpu;
+    let (instruction, _) = disassembler::disassemble(snapshot.next_bytes);
+    let [b0, b1, b2] = snapshot.next_bytes;
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    return format!(
+        "PC  {:04X}   SP  {:04X}\nA   {:02X}     F   {:02X}  {}\nBC  {:02X}{:02X}   DE  {:02X}{:02X}   HL  {:02X}{:02X}\nInterrupts enabled: {:<3}  Halted: {}\nShift register: {:04X}  offset {}\nNext: {:02X} {:02X} {:02X}  {}\n",
+        cpu.pc, cpu.sp, cpu.a, cpu.flags, Conditions::from_bits(cpu.flags), cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l,
+        yes_no(cpu.interrupt_enabled), yes_no(cpu.halted), snapshot.shift_register.raw(), snapshot.shift_register.offset(), b0, b1, b2, instruction);
+}
+
+/* Whole dump lines around the address, clipped to the address space */
+pub fn range_around(addr: u16) -> Range<u16> {
+    let line = DUMP_BYTES_PER_LINE as u16;
+    let start = (addr - addr % line).saturating_sub(DUMP_CONTEXT_LINES * line);
+    let end = (addr - addr % line).saturating_add((DUMP_CONTEXT_LINES + 1) * line);
+    return start..end;
+}
+
+/* Address-prefixed rows of hex with the printable ASCII alongside, as hex editors show them */
+pub fn hex_dump(start: u16, bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for (row, chunk) in bytes.chunks(DUMP_BYTES_PER_LINE).enumerate() {
+        let addr = start.wrapping_add((row * DUMP_BYTES_PER_LINE) as u16);
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = chunk.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+        text.push_str(&format!("{:04X}  {:<width$}  |{}|\n", addr, hex.join(" "), ascii, width = DUMP_BYTES_PER_LINE * 3 - 1));
+    }
+    return text;
+}
+
+/* Everything in one paste: the header, then each section under a === title === line */
+pub fn full_report(build: &BuildInfo, frame: u64, sections: &[(&str, String)]) -> String {
+    let mut text = header(build, frame);
+    for (title, body) in sections {
+        text.push_str(&format!("\n=== {} ===\n{}", title, body));
+    }
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intel8080::emulator::CpuState;
+    use crate::shift_register::ShiftRegister;
+
+    const BUILD: BuildInfo = BuildInfo { app: "Space Invaders Emulator", version: "0.3.0", renderer: "glow", rom: "invaders (MAME set)" };
+
+    fn snapshot() -> DebugSnapshot {
+        return DebugSnapshot {
+            cpu: CpuState { a: 0x3C, b: 0x01, c: 0x02, d: 0x20, e: 0x10, h: 0x24, l: 0x00, pc: 0x1A32, sp: 0x23FE, flags: 0b01000011, interrupt_enabled: true, halted: false, wait_cycles: 0 },
+            next_bytes: [0x21, 0x00, 0x24],
+            shift_register: ShiftRegister::from_raw(0xAB00, 2),
+        };
+    }
+
+    #[test]
+    fn test_header() {
+        assert_eq!(header(&BUILD, 1234), "Space Invaders Emulator 0.3.0\nRenderer: glow\nROM:      invaders (MAME set)\nFrame:    1234\n");
+        let unknown = BuildInfo { rom: "", ..BUILD };
+        assert!(header(&unknown, 0).contains("ROM:      unknown\n"));
+    }
+
+    #[test]
+    fn test_registers() {
+        assert_eq!(registers(&snapshot()), concat!(
+            "PC  1A32   SP  23FE\n",
+            "A   3C     F   43  .Z-.-.-C\n",
+            "BC  0102   DE  2010   HL  2400\n",
+            "Interrupts enabled: yes  Halted: no\n",
+            "Shift register: AB00  offset 2\n",
+            "Next: 21 00 24  LXI H,$2400\n",
+        ));
+    }
+
+    #[test]
+    fn test_range_around() {
+        assert_eq!(range_around(0x1A32), 0x1A10..0x1A60);
+        assert_eq!(range_around(0x0005), 0x0000..0x0030);
+        assert_eq!(range_around(0xFFF0), 0xFFD0..0xFFFF);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let bytes: Vec<u8> = (0x40..0x40 + 20).collect();
+        assert_eq!(hex_dump(0x2000, &bytes), concat!(
+            "2000  40 41 42 43 44 45 46 47 48 49 4A 4B 4C 4D 4E 4F  |@ABCDEFGHIJKLMNO|\n",
+            "2010  50 51 52 53                                      |PQRS|\n",
+        ));
+        assert_eq!(hex_dump(0x0000, &[0x00, 0x20, 0x7F]), "0000  00 20 7F                                         |. .|\n");
+    }
+
+    #[test]
+    fn test_full_report() {
+        let report = full_report(&BUILD, 7, &[("Registers", "PC  0000\n".to_string()), ("Memory", "0000  00\n".to_string())]);
+        assert_eq!(report, concat!(
+            "Space Invaders Emulator 0.3.0\nRenderer: glow\nROM:      invaders (MAME set)\nFrame:    7\n",
+            "\n=== Registers ===\nPC  0000\n",
+            "\n=== Memory ===\n0000  00\n",
+        ));
+    }
+}
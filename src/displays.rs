@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use crate::platform;
+
+/* How far inside the chosen monitor the window's corner is put before going fullscreen, in desktop units */
+const WINDOW_INSET: i32 = 32;
+
+/* One connected display, in desktop coordinates: physical pixels on Windows, points on macOS */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Monitor {
+    pub name: String,
+    pub position: [i32; 2],
+    pub size: [u32; 2],
+    pub primary: bool,
+}
+
+impl Monitor {
+    fn contains(&self, point: [i32; 2]) -> bool {
+        return (0..2).all(|axis| point[axis] >= self.position[axis] && point[axis] < self.position[axis] + self.size[axis] as i32);
+    }
+}
+
+/* Where the monitor list comes from, so the choice below can be tested without a windowing system */
+pub trait MonitorSource {
+    fn monitors(&self) -> Vec<Monitor>;
+}
+
+pub struct SystemMonitors;
+
+impl MonitorSource for SystemMonitors {
+    fn monitors(&self) -> Vec<Monitor> {
+        return platform::monitors();
+    }
+}
+
+/* The fullscreen display as persisted. Name and geometry are both kept: Windows renumbers its display
+   names when one is unplugged, and two identical projectors share a name on macOS */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedMonitor {
+    pub name: String,
+    pub position: [i32; 2],
+    pub size: [u32; 2],
+}
+
+impl SavedMonitor {
+    pub fn new(monitor: &Monitor) -> Self {
+        Self { name: monitor.name.clone(), position: monitor.position, size: monitor.size }
+    }
+
+    pub fn is(&self, monitor: &Monitor) -> bool {
+        return self.name == monitor.name && self.position == monitor.position && self.size == monitor.size;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /* No display chosen, or none could be listed: fullscreen on whichever monitor the window is on */
+    Current,
+    Chosen(Monitor),
+    /* The chosen display is gone; the primary stands in */
+    Fallback(Monitor),
+}
+
+impl Target {
+    pub fn monitor(&self) -> Option<&Monitor> {
+        match self {
+            Target::Current => None,
+            Target::Chosen(monitor) | Target::Fallback(monitor) => Some(monitor),
+        }
+    }
+}
+
+/* The exact display if it is still there, else one with the same name, else one with the same geometry,
+   else the primary */
+pub fn choose(saved: Option<&SavedMonitor>, source: &impl MonitorSource) -> Target {
+    let Some(saved) = saved else {
+        return Target::Current;
+    };
+    let monitors = source.monitors();
+    let found = monitors.iter().find(|monitor| saved.is(monitor))
+        .or_else(|| monitors.iter().find(|monitor| monitor.name == saved.name))
+        .or_else(|| monitors.iter().find(|monitor| monitor.position == saved.position && monitor.size == saved.size));
+    if let Some(monitor) = found {
+        return Target::Chosen(monitor.clone());
+    }
+    return match monitors.iter().find(|monitor| monitor.primary).or(monitors.first()) {
+        Some(primary) => Target::Fallback(primary.clone()),
+        None => Target::Current,
+    };
+}
+
+/* Egui points per desktop unit. Egui-winit scales outer positions by the egui scale; on macOS winit then
+   divides by the window's backing scale to get back to the points Core Graphics reports */
+pub fn points_per_unit(pixels_per_point: f32, native_pixels_per_point: f32) -> f32 {
+    if platform::MONITORS_IN_POINTS {
+        return native_pixels_per_point / pixels_per_point;
+    }
+    return 1.0 / pixels_per_point;
+}
+
+/* Outer window position, in points, that puts the window on the monitor so fullscreen opens there */
+pub fn window_position(monitor: &Monitor, points_per_unit: f32) -> [f32; 2] {
+    return [0, 1].map(|axis| (monitor.position[axis] + WINDOW_INSET) as f32 * points_per_unit);
+}
+
+/* Whether a window whose corner is at this point (in points) can be seen; with no monitor list there is
+   nothing to check against */
+pub fn on_screen(position: [f32; 2], monitors: &[Monitor], points_per_unit: f32) -> bool {
+    let point = position.map(|coordinate| (coordinate / points_per_unit).round() as i32);
+    return monitors.is_empty() || monitors.iter().any(|monitor| monitor.contains(point));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeMonitors(Vec<Monitor>);
+
+    impl MonitorSource for FakeMonitors {
+        fn monitors(&self) -> Vec<Monitor> {
+            return self.0.clone();
+        }
+    }
+
+    fn monitor(name: &str, position: [i32; 2], size: [u32; 2], primary: bool) -> Monitor {
+        return Monitor { name: name.to_string(), position, size, primary };
+    }
+
+    /* A laptop panel with a projector to its left */
+    fn desk() -> FakeMonitors {
+        return FakeMonitors(vec![
+            monitor("\\\\.\\DISPLAY1", [0, 0], [2560, 1600], true),
+            monitor("\\\\.\\DISPLAY2", [-1920, 200], [1920, 1080], false),
+        ]);
+    }
+
+    #[test]
+    fn test_nothing_saved_uses_current() {
+        assert_eq!(choose(None, &desk()), Target::Current);
+        assert_eq!(Target::Current.monitor(), None);
+    }
+
+    #[test]
+    fn test_saved_monitor_found() {
+        let projector = desk().0[1].clone();
+        let target = choose(Some(&SavedMonitor::new(&projector)), &desk());
+        assert_eq!(target, Target::Chosen(projector.clone()));
+        assert_eq!(target.monitor(), Some(&projector));
+    }
+
+    #[test]
+    fn test_moved_or_renamed_monitor_still_found() {
+        /* Rearranged in the display settings: same name, new place */
+        let mut saved = SavedMonitor::new(&desk().0[1]);
+        saved.position = [2560, 0];
+        assert_eq!(choose(Some(&saved), &desk()), Target::Chosen(desk().0[1].clone()));
+        /* Renumbered after a replug: same place, new name */
+        let mut saved = SavedMonitor::new(&desk().0[1]);
+        saved.name = "\\\\.\\DISPLAY3".to_string();
+        assert_eq!(choose(Some(&saved), &desk()), Target::Chosen(desk().0[1].clone()));
+    }
+
+    #[test]
+    fn test_exact_match_beats_shared_name() {
+        let twins = FakeMonitors(vec![
+            monitor("Projector", [0, 0], [1920, 1080], true),
+            monitor("Projector", [1920, 0], [1920, 1080], false),
+        ]);
+        let saved = SavedMonitor::new(&twins.0[1]);
+        assert_eq!(choose(Some(&saved), &twins), Target::Chosen(twins.0[1].clone()));
+    }
+
+    #[test]
+    fn test_unplugged_monitor_falls_back_to_primary() {
+        let saved = SavedMonitor::new(&desk().0[1]);
+        let laptop_only = FakeMonitors(vec![desk().0[0].clone()]);
+        assert_eq!(choose(Some(&saved), &laptop_only), Target::Fallback(desk().0[0].clone()));
+        /* No monitor flagged primary: the first listed */
+        let secondary = monitor("HDMI", [0, 0], [1280, 720], false);
+        assert_eq!(choose(Some(&saved), &FakeMonitors(vec![secondary.clone()])), Target::Fallback(secondary));
+        /* Nothing listed at all */
+        assert_eq!(choose(Some(&saved), &FakeMonitors(vec![])), Target::Current);
+    }
+
+    #[test]
+    fn test_window_position() {
+        let projector = &desk().0[1];
+        assert_eq!(window_position(projector, 1.0), [-1888.0, 232.0]);
+        /* At 2 physical pixels per point the same corner is half as many points away */
+        assert_eq!(window_position(projector, 0.5), [-944.0, 116.0]);
+        assert_eq!(points_per_unit(2.0, 2.0), if platform::MONITORS_IN_POINTS { 1.0 } else { 0.5 });
+    }
+
+    #[test]
+    fn test_on_screen() {
+        let monitors = desk().0;
+        assert!(on_screen([100.0, 100.0], &monitors, 1.0));
+        assert!(on_screen([-1000.0, 500.0], &monitors, 1.0));
+        /* Above the projector, where the laptop panel doesn't reach */
+        assert!(!on_screen([-1000.0, 100.0], &monitors, 1.0));
+        assert!(!on_screen([1300.0, 100.0], &monitors, 0.5));
+        assert!(on_screen([5000.0, 5000.0], &[], 1.0));
+    }
+}
@@ -0,0 +1,1204 @@
+use std::ops::RangeInclusive;
+use std::time::Instant;
+use serde::Deserialize;
+use intel8080::emulator::{Cpu, CpuState, StepInfo, UnexpectedReset};
+use intel8080::instruction;
+use intel8080::memory::space_invaders_memory::SpaceInvadersMemory;
+use crate::anomaly::{Anomaly, AnomalyKind, AnomalySink};
+use crate::coverage::CoverageMap;
+use crate::debug::DebugSnapshot;
+use crate::event_scheduler::{Event, EventScheduler};
+use crate::health::HealthFlags;
+use crate::debug_budget::BudgetShare;
+use crate::io_log::{IoLog, IoLogEntry};
+use crate::machine::{self, InputState};
+use crate::rng::{self, Rng};
+use crate::romset::{self, IdleLoop, Quirks};
+use crate::save_slots;
+use crate::save_state::{self, SaveState};
+use crate::shift_register::ShiftRegister;
+use crate::sound_latch::{LatchEdges, SoundLatch};
+use crate::sound_timeline::SoundEvent;
+
+pub const CYCLES_PER_FRAME: usize = 33_333;
+pub const CYCLES_PER_HALF_FRAME: usize = 16_667;
+const MID_SCREEN_INTERRUPT: u8 = 0xCF;
+const VBLANK_INTERRUPT: u8 = 0xD7;
+const IO_LOG_CAPACITY: usize = 4_096;
+/* VBlanks without an OUT 6 before the watchdog resets the CPU */
+const WATCHDOG_FRAMES: u64 = 255;
+/* Work RAM, where the game keeps its stack; SP = $2400 is an empty stack at the top */
+pub const STACK_BOUNDS: RangeInclusive<u16> = 0x2000..=0x2400;
+
+/* Where the two interrupts fall, in CPU cycles; adjustable so timing experiments can run side by side */
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Timing {
+    pub cycles_per_frame: usize,
+    pub mid_screen_cycle: usize,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            cycles_per_frame: CYCLES_PER_FRAME,
+            mid_screen_cycle: CYCLES_PER_HALF_FRAME,
+        }
+    }
+}
+
+pub struct FrameOutput {
+    pub sound_events: Vec<SoundEvent>,
+    pub health: HealthFlags,
+}
+
+/* The last time the CPU was at the start of an idle loop */
+struct IdleMark {
+    cpu: CpuState,
+    at: u64,
+    activity: u64,
+}
+
+/* The Space Invaders board without a frontend: CPU, ports, shift register and interrupts */
+pub struct EmulatorCore {
+    cpu: Cpu,
+    input: InputState,
+    shift_register: ShiftRegister,
+    port3: SoundLatch,
+    port5: SoundLatch,
+    frame: u64,
+    events: EventScheduler,
+    io_log: IoLog,
+    seed: u64,
+    rng: Rng,
+    rom_checksum: u64,
+    quirks: Quirks,
+    timing: Timing,
+    /* Host time of the most recent emulated VBlank; not part of the save state */
+    last_vblank: Option<Instant>,
+    idle_loop: IdleLoop,
+    idle_skip: bool,
+    idle_mark: Option<IdleMark>,
+    /* Counts OUTs and fired events, so an idle pass can tell nothing else happened during it */
+    activity: u64,
+    /* Flags raised so far this frame */
+    health: HealthFlags,
+    /* The frame in which execution last left the ROM, while it stays out */
+    outside_rom_since: Option<u64>,
+    /* An interrupt has been taken, so one arriving with interrupts disabled means a handler ran long */
+    interrupts_used: bool,
+    /* Where the stack pointer goes back to when the running interrupt handler returns; None outside one */
+    handler_return_sp: Option<u16>,
+    unexpected_reset: Option<UnexpectedReset>,
+    /* Some in strict mode */
+    anomalies: Option<AnomalySink>,
+    /* Addresses step_frame stops at when an instruction is about to start there */
+    breakpoints: Vec<u16>,
+    /* The breakpoint last stopped at, passed over once so running on doesn't stop there again straight away */
+    passing_breakpoint: Option<u16>,
+    breakpoint_hit: Option<u16>,
+}
+
+impl EmulatorCore {
+    pub fn new(rom: [u8; machine::ROM_SIZE], input: InputState) -> Self {
+        let rom_checksum = save_slots::rom_checksum(&rom);
+        let quirks = romset::quirks_for(&rom);
+        let memory = Box::new(SpaceInvadersMemory::new(rom));
+        let (port3, port5) = romset::sound_latches(quirks);
+        let mut core = Self {
+            cpu: Cpu::new(memory),
+            input,
+            shift_register: ShiftRegister::new(),
+            port3,
+            port5,
+            frame: 0,
+            events: EventScheduler::new(),
+            io_log: IoLog::new(IO_LOG_CAPACITY),
+            seed: rng::DEFAULT_SEED,
+            rng: Rng::new(rng::DEFAULT_SEED),
+            rom_checksum,
+            quirks,
+            timing: Timing::default(),
+            last_vblank: None,
+            idle_loop: romset::idle_loop(quirks),
+            idle_skip: false,
+            idle_mark: None,
+            activity: 0,
+            health: HealthFlags::default(),
+            outside_rom_since: None,
+            interrupts_used: false,
+            handler_return_sp: None,
+            unexpected_reset: None,
+            anomalies: None,
+            breakpoints: vec![],
+            passing_breakpoint: None,
+            breakpoint_hit: None,
+        };
+        core.schedule_video(0);
+        core.apply_input();
+        return core;
+    }
+
+    pub fn set_input(&mut self, input: InputState) {
+        self.input = input;
+        self.idle_mark = None;
+        self.apply_input();
+    }
+
+    /* Skips the passes of an idle loop that can't see an interrupt; the machine ends up exactly where running them would leave it */
+    pub fn set_idle_skip(&mut self, enabled: bool) {
+        self.idle_skip = enabled;
+        self.idle_mark = None;
+    }
+
+    /* Strict mode collects every anomaly the machine would otherwise shrug off, for CI runs to fail on;
+       turning it on or off starts a fresh sink */
+    pub fn set_strict(&mut self, strict: bool) {
+        self.cpu.take_read_only_write();
+        self.anomalies = strict.then(AnomalySink::new);
+    }
+
+    pub fn anomalies(&self) -> Option<&AnomalySink> {
+        return self.anomalies.as_ref();
+    }
+
+    /* Idle skip jumps over instructions, so it stays off while any are set */
+    pub fn set_breakpoints(&mut self, breakpoints: Vec<u16>) {
+        self.breakpoints = breakpoints;
+        self.passing_breakpoint = None;
+    }
+
+    /* Stops short of the frame's end when a breakpoint is reached; take_breakpoint_hit says where */
+    pub fn step_frame(&mut self) -> FrameOutput {
+        let mut output = FrameOutput { sound_events: vec![], health: HealthFlags::default() };
+        let frame = self.frame;
+        while self.frame == frame {
+            if self.at_breakpoint() {
+                break;
+            }
+            if self.idle_skip && self.breakpoints.is_empty() {
+                self.skip_idle();
+            }
+            self.tick(&mut output);
+        }
+        output.health = self.take_health();
+        return output;
+    }
+
+    /* Finishes any instruction in flight, then runs exactly one more to completion */
+    pub fn step_instruction(&mut self) -> FrameOutput {
+        let mut output = FrameOutput { sound_events: vec![], health: HealthFlags::default() };
+        let mut info = self.tick(&mut output);
+        while info.opcode.is_none() && !info.halted {
+            info = self.tick(&mut output);
+        }
+        for _ in 1..info.cycles {
+            self.tick(&mut output);
+        }
+        output.health = self.take_health();
+        return output;
+    }
+
+    /* As the cabinet's power switch would: a fresh CPU, RAM and schedule on the same ROM. The seed and the
+       debugging aids carry over; the output releases any sound the latches were holding */
+    pub fn power_cycle(&mut self) -> FrameOutput {
+        let mut output = FrameOutput { sound_events: vec![], health: HealthFlags::default() };
+        let edges = self.port3.write(0);
+        self.push_sound_edges(edges, &mut output);
+        let edges = self.port5.write(0);
+        self.push_sound_edges(edges, &mut output);
+        let rom: [u8; machine::ROM_SIZE] = std::array::from_fn(|addr| self.cpu.read_memory(addr as u16));
+        let profiling = self.cpu.profile().is_some();
+        self.cpu = Cpu::new(Box::new(SpaceInvadersMemory::new(rom)));
+        if profiling {
+            self.cpu.enable_profiling();
+        }
+        self.shift_register = ShiftRegister::new();
+        (self.port3, self.port5) = romset::sound_latches(self.quirks);
+        self.frame = 0;
+        self.events = EventScheduler::new();
+        self.rng = Rng::new(self.seed);
+        self.last_vblank = None;
+        self.idle_mark = None;
+        self.unexpected_reset = None;
+        self.passing_breakpoint = None;
+        self.breakpoint_hit = None;
+        self.reset_health();
+        self.schedule_video(0);
+        self.apply_input();
+        return output;
+    }
+
+    pub fn debug_snapshot(&self) -> DebugSnapshot {
+        let cpu = self.cpu.get_state();
+        let mut next_bytes = [0; 3];
+        for (i, byte) in next_bytes.iter_mut().enumerate() {
+            *byte = self.cpu.read_memory(cpu.pc.wrapping_add(i as u16));
+        }
+        DebugSnapshot { cpu, next_bytes, shift_register: self.shift_register }
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.cpu.enable_profiling();
+    }
+
+    pub fn disable_coverage(&mut self) {
+        self.cpu.disable_profiling();
+    }
+
+    pub fn coverage(&self) -> Option<CoverageMap> {
+        return self.cpu.profile().map(|profile| CoverageMap::from_profile(profile, machine::ROM_SIZE));
+    }
+
+    pub fn coverage_report(&self) -> Option<String> {
+        let rom: Vec<u8> = (0..machine::ROM_SIZE).map(|addr| self.cpu.read_memory(addr as u16)).collect();
+        return self.coverage().map(|map| map.report(&rom));
+    }
+
+    #[cfg(feature = "compare")]
+    pub fn set_timing(&mut self, timing: Timing) {
+        let frame_start = self.events.find(Event::VBlank).map_or(0, |vblank| vblank.due - self.timing.cycles_per_frame as u64);
+        for event in [Event::MidScreen, Event::VBlank] {
+            if let Some(pending) = self.events.find(event) {
+                self.events.cancel(pending.id);
+            }
+        }
+        self.timing = timing;
+        self.schedule_video(frame_start);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    /* Power-on RAM contents are undefined on real hardware; fill them from the seeded generator */
+    pub fn randomize_ram(&mut self) {
+        for i in 0..save_state::RAM_SIZE {
+            let value = self.rng.next_u8();
+            self.cpu.write_memory(save_state::RAM_START + i as u16, value);
+        }
+    }
+
+    /* FNV-1a over work RAM and VRAM */
+    pub fn ram_checksum(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for i in 0..save_state::RAM_SIZE {
+            hash ^= self.cpu.read_memory(save_state::RAM_START + i as u16) as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+        return hash;
+    }
+
+    pub fn rom_checksum(&self) -> u64 {
+        return self.rom_checksum;
+    }
+
+    pub fn frame(&self) -> u64 {
+        return self.frame;
+    }
+
+    pub fn last_vblank(&self) -> Option<Instant> {
+        return self.last_vblank;
+    }
+
+    /* Execution wandered into 0x0000 without a reset since the last call; see UnexpectedReset */
+    pub fn take_unexpected_reset(&mut self) -> Option<UnexpectedReset> {
+        return self.unexpected_reset.take();
+    }
+
+    /* The breakpoint step_frame stopped at since the last call */
+    pub fn take_breakpoint_hit(&mut self) -> Option<u16> {
+        return self.breakpoint_hit.take();
+    }
+
+    #[cfg(test)]
+    pub fn shift_register(&self) -> ShiftRegister {
+        return self.shift_register;
+    }
+
+    pub fn set_io_log_budget(&mut self, budget: BudgetShare) {
+        self.io_log.set_budget(budget);
+    }
+
+    #[cfg(test)]
+    pub fn io_log(&self) -> &IoLog {
+        return &self.io_log;
+    }
+
+    pub fn save_state(&self) -> SaveState {
+        let ram = (0..save_state::RAM_SIZE).map(|i| self.cpu.read_memory(save_state::RAM_START + i as u16)).collect();
+        SaveState {
+            cpu: self.cpu.get_state(),
+            ram,
+            shift_register: self.shift_register.raw(),
+            shift_register_offset: self.shift_register.offset(),
+            last_port3: self.port3.value(),
+            last_port5: self.port5.value(),
+            frame: self.frame,
+            seed: self.seed,
+            rng_state: self.rng.state(),
+            scheduler: self.events.state(),
+        }
+    }
+
+    pub fn load_state(&mut self, state: &SaveState) {
+        self.cpu.set_state(&state.cpu);
+        for (i, value) in state.ram.iter().enumerate() {
+            self.cpu.write_memory(save_state::RAM_START + i as u16, *value);
+        }
+        self.shift_register = ShiftRegister::from_raw(state.shift_register, state.shift_register_offset);
+        self.port3.restore(state.last_port3);
+        self.port5.restore(state.last_port5);
+        self.frame = state.frame;
+        self.events.restore(&state.scheduler);
+        self.seed = state.seed;
+        self.rng = Rng::from_state(state.rng_state);
+        self.idle_mark = None;
+        self.reset_health();
+        /* States are taken between frames, just after the VBlank request was latched; set_state drops
+           latched requests, so raise it again or the game misses a VBlank (dropped as usual if disabled) */
+        if state.frame > 0 {
+            self.cpu.receive_interrupt(VBLANK_INTERRUPT);
+        }
+        self.apply_input();
+    }
+
+    pub fn read_memory(&self, addr: u16) -> u8 {
+        return self.cpu.read_memory(addr);
+    }
+
+    pub fn write_memory(&mut self, addr: u16, value: u8) {
+        self.cpu.write_memory(addr, value);
+        self.idle_mark = None;
+    }
+
+    pub fn vram(&self) -> [u8; 7_168] {
+        return self.cpu.get_vram();
+    }
+
+    pub fn ram(&self) -> [u8; 1_024] {
+        return self.cpu.get_ram();
+    }
+
+    fn apply_input(&mut self) {
+        self.cpu.set_input(0, self.input.port0());
+        self.cpu.set_input(1, romset::port1(&self.input, self.quirks));
+        self.cpu.set_input(2, self.input.port2());
+        self.cpu.set_input(3, self.shift_register.read());
+    }
+
+    fn tick(&mut self, output: &mut FrameOutput) -> StepInfo {
+        let info = self.cpu.tick_info();
+        if info.opcode.is_some() {
+            self.watch_instruction(&info);
+        }
+        match self.cpu.get_output() {
+            Some((device, value)) => self.write_port(info.pc_before, device, value, output),
+            None => {}
+        }
+        self.events.advance(1);
+        while let Some(pending) = self.events.pop_due() {
+            self.fire(pending.event);
+        }
+        return info;
+    }
+
+    /* The checks that look at every instruction: where it ran from and where it left the stack */
+    fn watch_instruction(&mut self, info: &StepInfo) {
+        if info.interrupt {
+            self.interrupts_used = true;
+            /* The RST has pushed the return address; a nested interrupt counts as the outer handler's time */
+            self.handler_return_sp.get_or_insert(self.cpu.sp().wrapping_add(2));
+        } else {
+            if info.pc_before as usize >= machine::ROM_SIZE {
+                self.outside_rom_since.get_or_insert(self.frame);
+            } else {
+                self.outside_rom_since = None;
+            }
+            /* Popping the return address ends the handler */
+            if self.handler_return_sp.map_or(false, |return_sp| self.cpu.sp() >= return_sp) {
+                self.handler_return_sp = None;
+            }
+        }
+        let sp = self.cpu.sp();
+        if !STACK_BOUNDS.contains(&sp) {
+            /* Once a frame is enough to find the culprit */
+            if self.anomalies.is_some() && !self.health.contains(HealthFlags::STACK_OUT_OF_BOUNDS) {
+                let description = format!("SP ${:04X} outside ${:04X}-${:04X}", sp, STACK_BOUNDS.start(), STACK_BOUNDS.end());
+                self.report_anomaly_at(info.pc_before, AnomalyKind::StackOutOfBounds, description);
+            }
+            self.health.insert(HealthFlags::STACK_OUT_OF_BOUNDS);
+        }
+        if let Some(reset) = self.cpu.take_unexpected_reset() {
+            let description = format!("execution reached $0000 from ${:04X} ({})", reset.from, instruction::OPCODES[reset.opcode as usize].label);
+            self.report_anomaly_at(reset.from, AnomalyKind::UnexpectedReset, description);
+            self.unexpected_reset = Some(reset);
+        }
+        if self.anomalies.is_some() {
+            self.watch_strict(info);
+        }
+    }
+
+    /* The checks only strict mode makes: what the instruction did that the board quietly ignores */
+    fn watch_strict(&mut self, info: &StepInfo) {
+        let pc = info.pc_before;
+        if let Some(addr) = self.cpu.take_read_only_write() {
+            self.report_anomaly_at(pc, AnomalyKind::RomWrite, format!("write to read-only ${:04X}", addr));
+        }
+        /* An interrupt's RST comes from the bus, not from memory at PC */
+        let Some(opcode) = info.opcode.filter(|_| !info.interrupt) else {
+            return;
+        };
+        let label = instruction::OPCODES[opcode as usize].label;
+        if label.starts_with('*') {
+            self.report_anomaly_at(pc, AnomalyKind::UndocumentedOpcode, format!("${:02X} {}", opcode, label));
+        }
+        /* apply_input drives ports 0 to 3; nothing answers the rest */
+        if opcode == 0xDB {
+            let port = self.cpu.read_memory(pc.wrapping_add(1));
+            if port > 3 {
+                self.report_anomaly_at(pc, AnomalyKind::UnmappedInput, format!("IN {}", port));
+            }
+        }
+    }
+
+    /* Sound commands the audio thread fell too far behind to play; only strict mode keeps a record */
+    pub fn report_dropped_sound_events(&mut self, count: u64) {
+        if count > 0 {
+            let pc = self.cpu.pc();
+            self.report_anomaly_at(pc, AnomalyKind::DroppedSoundEvents, format!("{} sound events dropped", count));
+        }
+    }
+
+    /* Frames skipped when emulation resynchronized after a stall instead of catching up */
+    pub fn report_dropped_frames(&mut self, count: u64) {
+        if count > 0 {
+            let pc = self.cpu.pc();
+            self.report_anomaly_at(pc, AnomalyKind::DroppedFrames, format!("{} frames dropped", count));
+        }
+    }
+
+    fn report_anomaly_at(&mut self, pc: u16, kind: AnomalyKind, description: String) {
+        let (frame, cycle) = (self.frame, self.events.now());
+        if let Some(sink) = self.anomalies.as_mut() {
+            sink.report(Anomaly { kind, frame, cycle, pc, description });
+        }
+    }
+
+    /* A video interrupt coming due while the game is still inside the handler for the last one: dropped if
+       interrupts are still disabled, and over the half frame's cycle budget either way */
+    fn watch_interrupt(&mut self) {
+        if self.interrupts_used && !self.cpu.interrupts_enabled() {
+            self.health.insert(HealthFlags::INTERRUPT_DROPPED);
+        }
+        if self.handler_return_sp.is_some() {
+            self.health.insert(HealthFlags::CYCLE_OVERRUN);
+        }
+    }
+
+    /* This frame's flags, with the checks that look at where the frame left the CPU */
+    fn take_health(&mut self) -> HealthFlags {
+        let mut health = std::mem::take(&mut self.health);
+        if self.cpu.halted() && !self.cpu.interrupts_enabled() {
+            health.insert(HealthFlags::WEDGED);
+        }
+        if self.outside_rom_since.map_or(false, |since| self.frame - since > 1) {
+            health.insert(HealthFlags::PC_OUTSIDE_ROM);
+        }
+        /* Less than a frame left: unless the next one feeds it, the watchdog fires during it */
+        if self.events.find(Event::Watchdog).map_or(false, |pending| pending.due - self.events.now() < self.timing.cycles_per_frame as u64) {
+            health.insert(HealthFlags::WATCHDOG);
+        }
+        return health;
+    }
+
+    fn reset_health(&mut self) {
+        self.health = HealthFlags::default();
+        self.outside_rom_since = None;
+        self.interrupts_used = false;
+        self.handler_return_sp = None;
+    }
+
+    /* A JMP to itself, or the set's documented wait loop if the bytes there are still that loop */
+    fn is_idle_loop(&self, pc: u16) -> bool {
+        let read = |addr: u16| self.cpu.read_memory(addr);
+        let jmp_to_self = read(pc) == 0xC3 && u16::from_le_bytes([read(pc.wrapping_add(1)), read(pc.wrapping_add(2))]) == pc;
+        return jmp_to_self || (pc == self.idle_loop.start && self.idle_loop.matches(read));
+    }
+
+    /* Called between ticks. Each time the CPU is back at the start of an idle loop, compares it with the
+       last time: the same registers, no OUT, no event and no interrupt waiting means the pass in between
+       was the loop spinning on unchanged RAM, and every further pass will be too until the next event.
+       Those passes are skipped whole, stopping at the last start before the event, so nothing else in
+       the machine can tell. Coverage counts every pass, so it turns the skip off */
+    fn at_breakpoint(&mut self) -> bool {
+        if self.breakpoints.is_empty() || !self.cpu.at_instruction_boundary() {
+            return false;
+        }
+        let pc = self.cpu.pc();
+        if !self.breakpoints.contains(&pc) {
+            self.passing_breakpoint = None;
+            return false;
+        }
+        if self.passing_breakpoint == Some(pc) {
+            return false;
+        }
+        self.passing_breakpoint = Some(pc);
+        self.breakpoint_hit = Some(pc);
+        return true;
+    }
+
+    fn skip_idle(&mut self) {
+        let pc = self.cpu.pc();
+        if !self.cpu.at_instruction_boundary() || self.cpu.interrupt_pending() || self.cpu.profile().is_some() || !self.is_idle_loop(pc) {
+            return;
+        }
+        let cpu = self.cpu.get_state();
+        let now = self.events.now();
+        if let (Some(mark), Some(next_due)) = (&self.idle_mark, self.events.next_due()) {
+            if mark.cpu == cpu && mark.activity == self.activity && now > mark.at {
+                let pass = now - mark.at;
+                let passes = next_due.saturating_sub(now + 1) / pass;
+                self.events.advance(passes * pass);
+            }
+        }
+        self.idle_mark = Some(IdleMark { cpu, at: self.events.now(), activity: self.activity });
+    }
+
+    /* The mid-screen interrupt for the frame starting at frame_start, unless the beam is already past it, and the VBlank ending it */
+    fn schedule_video(&mut self, frame_start: u64) {
+        let mid_screen = frame_start + self.timing.mid_screen_cycle as u64;
+        if mid_screen >= self.events.now() {
+            self.events.schedule_at(mid_screen, Event::MidScreen);
+        }
+        self.events.schedule_at(frame_start + self.timing.cycles_per_frame as u64, Event::VBlank);
+    }
+
+    fn fire(&mut self, event: Event) {
+        self.activity += 1;
+        match event {
+            Event::MidScreen => {
+                self.watch_interrupt();
+                self.cpu.receive_interrupt(MID_SCREEN_INTERRUPT);
+            },
+            Event::VBlank => {
+                self.watch_interrupt();
+                self.cpu.receive_interrupt(VBLANK_INTERRUPT);
+                self.frame += 1;
+                self.last_vblank = Some(Instant::now());
+                self.schedule_video(self.events.now());
+            },
+            Event::Watchdog => {
+                /* The reset line only touches the CPU; RAM, the shift register and the sound latches keep their contents */
+                let mut cpu = self.cpu.get_state();
+                cpu.pc = 0;
+                cpu.interrupt_enabled = false;
+                cpu.halted = false;
+                cpu.wait_cycles = 0;
+                self.cpu.set_state(&cpu);
+                self.health.insert(HealthFlags::WATCHDOG);
+            },
+        }
+    }
+
+    /* The watchdog arms on the first OUT 6, so bare test programs that never feed it are left alone */
+    fn feed_watchdog(&mut self) {
+        let timeout = WATCHDOG_FRAMES * self.timing.cycles_per_frame as u64;
+        match self.events.find(Event::Watchdog) {
+            Some(pending) => {
+                self.events.reschedule(pending.id, self.events.now() + timeout);
+            },
+            None => {
+                self.events.schedule_in(timeout, Event::Watchdog);
+            },
+        }
+    }
+
+    /* All sounds raised by one OUT start in the same frame */
+    fn push_sound_edges(&self, edges: LatchEdges, output: &mut FrameOutput) {
+        for sound in edges.rising {
+            output.sound_events.push(SoundEvent { frame: self.frame, sound, active: true, sound_test: false });
+        }
+        for sound in edges.falling {
+            output.sound_events.push(SoundEvent { frame: self.frame, sound, active: false, sound_test: false });
+        }
+    }
+
+    fn write_port(&mut self, pc: u16, device: u8, value: u8, output: &mut FrameOutput) {
+        self.activity += 1;
+        match device {
+            0x2 => {
+                self.shift_register.set_offset(value);
+            },
+            0x3 => {
+                let edges = self.port3.write(value);
+                self.push_sound_edges(edges, output);
+            },
+            0x4 => {
+                self.shift_register.write(value);
+            },
+            0x5 => {
+                let edges = self.port5.write(value);
+                self.push_sound_edges(edges, output);
+            },
+            0x6 => {
+                self.feed_watchdog();
+            },
+            /* Nothing on the board decodes it, so the write goes nowhere */
+            _ => {
+                self.report_anomaly_at(pc, AnomalyKind::UnmappedOutput, format!("OUT {} with ${:02X}", device, value));
+                return;
+            }
+        }
+        self.io_log.push(IoLogEntry { frame: self.frame, port: device, value, shift_register: self.shift_register.raw() });
+        self.cpu.set_input(3, self.shift_register.read());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::DipSwitches;
+    use crate::ram_map;
+
+    fn rom_with_program(program: &[u8]) -> [u8; machine::ROM_SIZE] {
+        let mut rom = [0; machine::ROM_SIZE];
+        rom[..program.len()].copy_from_slice(program);
+        return rom;
+    }
+
+    #[test]
+    fn test_step_frames_headlessly() {
+        let input = InputState { coin: true, ..Default::default() };
+        let mut core = EmulatorCore::new(crate::test_rom::rom(), input);
+        let output = core.step_frame();
+        assert_eq!(core.frame(), 1);
+        assert!(output.health.is_empty());
+        /* The built-in program starts its next sound every 32 frames */
+        let started: Vec<usize> = (0..32)
+            .flat_map(|_| core.step_frame().sound_events)
+            .filter(|event| event.active)
+            .map(|event| event.sound)
+            .collect();
+        assert_eq!(started, [1]);
+    }
+
+    #[test]
+    fn test_first_in2_reflects_dip_switches() {
+        // IN 2; STA $2000; HLT
+        let rom = rom_with_program(&[0xDB, 0x02, 0x32, 0x00, 0x20, 0x76]);
+        let dip_switches = DipSwitches { lives: 5, bonus_life_at_1000: true, coin_info: false };
+        let mut core = EmulatorCore::new(rom, InputState::with_dip_switches(dip_switches));
+        core.step_frame();
+        assert_eq!(core.ram()[0], 0b10001010);
+    }
+
+    #[test]
+    fn test_in0_reads_fixed_bits_and_player_1_controls() {
+        // IN 0; STA $2000; HLT
+        let rom = rom_with_program(&[0xDB, 0x00, 0x32, 0x00, 0x20, 0x76]);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.step_frame();
+        assert_eq!(core.ram()[0], machine::PORT0_FIXED);
+        let mut core = EmulatorCore::new(rom, InputState { p1_left: true, p2_fire: true, ..Default::default() });
+        core.step_frame();
+        assert_eq!(core.ram()[0], machine::PORT0_FIXED | machine::PORT0_P1_LEFT);
+    }
+
+    /* Needs the arcade ROMs: past the power-up self-test, which samples port 0, the game reaches attract
+       mode and takes coins, and each press held over several frames is one pulse and one credit */
+    #[test]
+    #[ignore = "needs the arcade ROMs; run with cargo test -- --ignored"]
+    fn test_boot_and_one_credit_per_coin_press() {
+        let rom = crate::application::load_rom().expect("the arcade ROMs");
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        let mut shaper = machine::InputShaper::default();
+        let mut credits = vec![];
+        for frame in 0..600 {
+            let input = InputState { coin: frame >= 120 && frame % 120 < 10, ..Default::default() };
+            core.set_input(shaper.shape(&input));
+            core.step_frame();
+            if frame % 120 == 119 {
+                credits.push(ram_map::bcd_to_decimal(ram_map::read(&core.ram(), ram_map::CREDITS)));
+            }
+        }
+        assert_eq!(credits, vec![0, 1, 2, 3, 4]);
+    }
+
+    /* Needs the arcade ROMs: a fresh machine with each lives setting, coined up and started, should deal that many ships */
+    #[test]
+    #[ignore = "needs the arcade ROMs; run with cargo test -- --ignored"]
+    fn test_lives_dip_sets_ship_count() {
+        let rom = crate::application::load_rom().expect("the arcade ROMs");
+        for lives in machine::MIN_LIVES..=machine::MAX_LIVES {
+            let dip_switches = DipSwitches { lives, ..Default::default() };
+            let mut core = EmulatorCore::new(rom, InputState::with_dip_switches(dip_switches));
+            let mut shaper = machine::InputShaper::default();
+            for frame in 0..600 {
+                let input = InputState { coin: frame == 120, p1_start: (240..250).contains(&frame), ..InputState::with_dip_switches(dip_switches) };
+                core.set_input(shaper.shape(&input));
+                core.step_frame();
+            }
+            assert_eq!(ram_map::read(&core.ram(), ram_map::P1_SHIPS), lives, "lives DIP {}", lives);
+        }
+    }
+
+    #[test]
+    fn test_sound_rising_edges() {
+        // MVI A,$03; OUT 3; OUT 3; MVI A,$11; OUT 5; HLT
+        let rom = rom_with_program(&[0x3E, 0x03, 0xD3, 0x03, 0xD3, 0x03, 0x3E, 0x11, 0xD3, 0x05, 0x76]);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        let output = core.step_frame();
+        let started: Vec<usize> = output.sound_events.iter().filter(|event| event.active).map(|event| event.sound).collect();
+        assert_eq!(started, vec![0, 1, 4, 8]);
+    }
+
+    #[test]
+    fn test_sound_events_record_both_edges() {
+        // MVI A,$01; OUT 3; XRA A; OUT 3; HLT
+        let rom = rom_with_program(&[0x3E, 0x01, 0xD3, 0x03, 0xAF, 0xD3, 0x03, 0x76]);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        let output = core.step_frame();
+        let edges: Vec<(usize, bool)> = output.sound_events.iter().map(|e| (e.sound, e.active)).collect();
+        assert_eq!(edges, vec![(0, true), (0, false)]);
+    }
+
+    #[test]
+    fn test_vblank_timestamped() {
+        let mut core = EmulatorCore::new(rom_with_program(&[0x76]), InputState::default());
+        assert_eq!(core.last_vblank(), None);
+        let before = Instant::now();
+        core.step_frame();
+        let first = core.last_vblank().unwrap();
+        assert!(first >= before && first <= Instant::now());
+        core.step_frame();
+        assert!(core.last_vblank().unwrap() >= first);
+    }
+
+    #[test]
+    fn test_shift_register() {
+        // MVI A,$AB; OUT 4; MVI A,$CD; OUT 4; MVI A,$04; OUT 2; IN 3; STA $2000; HLT
+        let rom = rom_with_program(&[0x3E, 0xAB, 0xD3, 0x04, 0x3E, 0xCD, 0xD3, 0x04, 0x3E, 0x04, 0xD3, 0x02, 0xDB, 0x03, 0x32, 0x00, 0x20, 0x76]);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.step_frame();
+        assert_eq!(core.ram()[0], 0xDA);
+        assert_eq!(core.shift_register().raw(), 0xCDAB);
+        let logged: Vec<(u8, u16)> = core.io_log().entries().map(|e| (e.port, e.shift_register)).collect();
+        assert_eq!(logged, vec![(0x4, 0xAB00), (0x4, 0xCDAB), (0x2, 0xCDAB)]);
+    }
+
+    #[test]
+    fn test_same_seed_is_deterministic_with_random_ram() {
+        // LXI H,$2000; loop: MOV A,M; ADD L; MOV M,A; INX H; MOV A,H; CPI $24; JNZ loop; JMP $0000
+        let rom = rom_with_program(&[0x21, 0x00, 0x20, 0x7E, 0x85, 0x77, 0x23, 0x7C, 0xFE, 0x24, 0xC2, 0x03, 0x00, 0xC3, 0x00, 0x00]);
+        let mut cores: Vec<EmulatorCore> = (0..2).map(|_| {
+            let mut core = EmulatorCore::new(rom, InputState::default());
+            core.set_seed(1_978);
+            core.randomize_ram();
+            core
+        }).collect();
+        for frame in 0..300 {
+            let input = InputState { p1_fire: frame % 7 == 0, ..Default::default() };
+            for core in cores.iter_mut() {
+                core.set_input(input);
+                core.step_frame();
+            }
+        }
+        assert_eq!(cores[0].ram_checksum(), cores[1].ram_checksum());
+
+        let mut other = EmulatorCore::new(rom, InputState::default());
+        other.set_seed(1_979);
+        other.randomize_ram();
+        let mut reference = EmulatorCore::new(rom, InputState::default());
+        reference.set_seed(1_978);
+        reference.randomize_ram();
+        assert_ne!(other.ram_checksum(), reference.ram_checksum());
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        // MVI A,$AB; OUT 4; MVI A,$02; OUT 2; INR B; JMP $0008
+        let rom = rom_with_program(&[0x3E, 0xAB, 0xD3, 0x04, 0x3E, 0x02, 0xD3, 0x02, 0x04, 0xC3, 0x08, 0x00]);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.step_frame();
+        let state = core.save_state();
+        core.step_frame();
+        let expected = core.save_state();
+
+        let mut restored = EmulatorCore::new(rom, InputState::default());
+        restored.load_state(&SaveState::from_bytes(&state.to_bytes()).unwrap());
+        assert_eq!(restored.shift_register(), ShiftRegister::from_raw(0xAB00, 2));
+        restored.step_frame();
+        assert_eq!(restored.save_state(), expected);
+    }
+
+    #[test]
+    fn test_load_state_keeps_pending_vblank() {
+        let rom = crate::test_rom::rom();
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        for _ in 0..10 {
+            core.step_frame();
+        }
+        let state = core.save_state();
+        let mut restored = EmulatorCore::new(rom, InputState::default());
+        restored.load_state(&state);
+        for _ in 0..10 {
+            core.step_frame();
+            restored.step_frame();
+        }
+        assert_eq!(restored.ram_checksum(), core.ram_checksum());
+    }
+
+    /* LDA $2000; INR A; STA $2000; OUT 6, then either JMP $0009 (fed once) or JMP $0007 (fed forever); counts CPU resets in RAM */
+    fn watchdog_rom(feed_forever: bool) -> [u8; machine::ROM_SIZE] {
+        let target = if feed_forever { 0x07 } else { 0x09 };
+        return rom_with_program(&[0x3A, 0x00, 0x20, 0x3C, 0x32, 0x00, 0x20, 0xD3, 0x06, 0xC3, target, 0x00]);
+    }
+
+    #[test]
+    fn test_watchdog_resets_unfed_cpu() {
+        let mut starved = EmulatorCore::new(watchdog_rom(false), InputState::default());
+        let mut fed = EmulatorCore::new(watchdog_rom(true), InputState::default());
+        for _ in 0..WATCHDOG_FRAMES - 1 {
+            starved.step_frame();
+            fed.step_frame();
+        }
+        assert_eq!((starved.ram()[0], fed.ram()[0]), (1, 1));
+        for _ in 0..2 {
+            starved.step_frame();
+            fed.step_frame();
+        }
+        assert_eq!((starved.ram()[0], fed.ram()[0]), (2, 1));
+        assert!(starved.take_unexpected_reset().is_none());
+    }
+
+    #[test]
+    fn test_save_state_keeps_watchdog_deadline() {
+        let rom = watchdog_rom(false);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        for _ in 0..100 {
+            core.step_frame();
+        }
+        let mut restored = EmulatorCore::new(rom, InputState::default());
+        restored.load_state(&SaveState::from_bytes(&core.save_state().to_bytes()).unwrap());
+        for _ in 0..200 {
+            core.step_frame();
+            restored.step_frame();
+        }
+        assert_eq!((core.ram()[0], restored.ram()[0]), (2, 2));
+        assert_eq!(restored.save_state(), core.save_state());
+    }
+
+    #[test]
+    fn test_idle_skip_stops_short_of_the_next_event() {
+        // idle: JMP idle
+        let mut core = EmulatorCore::new(rom_with_program(&[0xC3, 0x00, 0x00]), InputState::default());
+        let mut output = FrameOutput { sound_events: vec![], health: HealthFlags::default() };
+        core.skip_idle();
+        assert_eq!(core.events.now(), 0);
+        for _ in 0..10 {
+            core.tick(&mut output);
+        }
+        /* Whole ten-cycle passes up to the last loop start before the mid-screen interrupt at 16,667 */
+        core.skip_idle();
+        assert_eq!(core.events.now(), 16_660);
+        core.enable_coverage();
+        for _ in 0..10 {
+            core.tick(&mut output);
+        }
+        core.skip_idle();
+        assert_eq!(core.events.now(), 16_670);
+    }
+
+    #[test]
+    fn test_idle_skip_matches_running_the_loop() {
+        /* Starved of OUT 6, so the watchdog resets it out of its JMP-to-self every 255 frames */
+        let mut plain = EmulatorCore::new(watchdog_rom(false), InputState::default());
+        let mut skipping = EmulatorCore::new(watchdog_rom(false), InputState::default());
+        skipping.set_idle_skip(true);
+        for _ in 0..600 {
+            plain.step_frame();
+            skipping.step_frame();
+            assert_eq!(skipping.save_state(), plain.save_state());
+        }
+        assert_eq!(plain.ram()[0], 3);
+    }
+
+    #[test]
+    fn test_step_instruction() {
+        // LXI H,$2400; MVI A,$01; HLT
+        let rom = rom_with_program(&[0x21, 0x00, 0x24, 0x3E, 0x01, 0x76]);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        assert_eq!(core.debug_snapshot().next_bytes, [0x21, 0x00, 0x24]);
+        core.step_instruction();
+        let snapshot = core.debug_snapshot();
+        assert_eq!(snapshot.cpu.pc, 0x0003);
+        assert_eq!(snapshot.cpu.h, 0x24);
+        assert_eq!(snapshot.cpu.wait_cycles, 0);
+        core.step_instruction();
+        assert_eq!(core.debug_snapshot().cpu.a, 0x01);
+        assert_eq!(core.frame(), 0);
+        core.step_frame();
+        assert_eq!(core.frame(), 1);
+    }
+
+    /* Stands in for the game's extra ship check, which reads the bonus switch each time it awards */
+    const AWARD_PROGRAM: &str = "
+        start:  LXI SP,$2400
+        wait:   LDA $2000
+                ORA A
+                JZ wait
+                IN 2
+                ANI $08
+                MVI A,$15
+                JZ store
+                MVI A,$10
+        store:  STA $2001
+                XRA A
+                STA $2000
+                JMP wait
+    ";
+
+    #[test]
+    fn test_bonus_switch_flipped_mid_game_applies_at_next_award() {
+        let rom = rom_with_program(&intel8080::asm::assemble(AWARD_PROGRAM).unwrap());
+        let mut live = DipSwitches::default();
+        let mut core = EmulatorCore::new(rom, InputState::with_dip_switches(live));
+        let award = |core: &mut EmulatorCore| {
+            core.write_memory(0x2000, 1);
+            core.step_frame();
+            return core.read_memory(0x2001);
+        };
+        assert_eq!(award(&mut core), 0x15);
+        /* The player raises the ship count and lowers the bonus score together while the game runs */
+        let wanted = DipSwitches { lives: 5, bonus_life_at_1000: true, ..live };
+        live = live.apply_hot(&wanted);
+        core.set_input(InputState::with_dip_switches(live));
+        assert_eq!(award(&mut core), 0x10);
+        assert_eq!(live.pending_reset(&wanted), vec![machine::DipSwitch::Lives]);
+        assert_eq!(DipSwitches::from_port2(core.input.port2()).lives, 3);
+    }
+
+    #[test]
+    fn test_power_cycle_restarts_and_releases_sounds() {
+        // MVI A,$01; OUT 3; JMP $0004
+        let rom = rom_with_program(&[0x3E, 0x01, 0xD3, 0x03, 0xC3, 0x04, 0x00]);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.set_seed(7);
+        core.enable_coverage();
+        let started = core.step_frame();
+        assert!(started.sound_events.iter().any(|event| event.active));
+        core.write_memory(0x2100, 0xAA);
+        core.step_frame();
+
+        let output = core.power_cycle();
+        assert_eq!(output.sound_events.len(), 1);
+        assert!(!output.sound_events[0].active);
+        assert_eq!(core.frame(), 0);
+        assert_eq!(core.debug_snapshot().cpu.pc, 0x0000);
+        assert_eq!(core.read_memory(0x2100), 0);
+        assert_eq!(core.seed(), 7);
+        assert!(core.coverage().is_some());
+        assert_eq!(core.power_cycle().sound_events, vec![]);
+        assert_eq!(core.step_frame().sound_events.len(), 1);
+        assert_eq!(core.frame(), 1);
+    }
+
+    /* Both interrupt handlers re-enable interrupts and return, as the game's do */
+    const HEALTHY_PROGRAM: &str = "
+                LXI SP,$2400
+                EI
+        loop:   JMP loop
+                ORG $0008
+                EI
+                RET
+                ORG $0010
+                EI
+                RET
+    ";
+
+    fn health_core(program: &str) -> EmulatorCore {
+        return EmulatorCore::new(rom_with_program(&intel8080::asm::assemble(program).unwrap()), InputState::default());
+    }
+
+    #[test]
+    fn test_healthy_program_raises_nothing() {
+        let mut core = health_core(HEALTHY_PROGRAM);
+        for _ in 0..10 {
+            assert_eq!(core.step_frame().health, HealthFlags::default());
+        }
+    }
+
+    #[test]
+    fn test_halt_with_interrupts_disabled_is_wedged() {
+        let mut core = health_core("DI\nHLT");
+        assert_eq!(core.step_frame().health, HealthFlags::WEDGED);
+        /* Halted with interrupts on is just waiting for the next one */
+        let mut core = health_core(&HEALTHY_PROGRAM.replace("loop:   JMP loop", "loop:   HLT\nJMP loop"));
+        assert!(!core.step_frame().health.contains(HealthFlags::WEDGED));
+    }
+
+    #[test]
+    fn test_pc_outside_rom_for_more_than_a_frame() {
+        let mut core = health_core("JMP $2100\nback: JMP back");
+        /* JMP $2100 in RAM */
+        for (i, byte) in [0xC3, 0x00, 0x21].into_iter().enumerate() {
+            core.write_memory(0x2100 + i as u16, byte);
+        }
+        assert!(core.step_frame().health.is_empty());
+        assert_eq!(core.step_frame().health, HealthFlags::PC_OUTSIDE_ROM);
+        assert_eq!(core.step_frame().health, HealthFlags::PC_OUTSIDE_ROM);
+        /* JMP back, into the ROM */
+        core.write_memory(0x2101, 0x03);
+        core.write_memory(0x2102, 0x00);
+        assert!(core.step_frame().health.is_empty());
+    }
+
+    #[test]
+    fn test_stack_outside_bounds() {
+        let mut core = health_core("LXI SP,$4000\nloop: JMP loop");
+        assert_eq!(core.step_frame().health, HealthFlags::STACK_OUT_OF_BOUNDS);
+        let mut core = health_core(HEALTHY_PROGRAM);
+        assert!(core.step_frame().health.is_empty());
+    }
+
+    #[test]
+    fn test_watchdog_flagged_before_and_when_it_fires() {
+        let mut core = EmulatorCore::new(watchdog_rom(false), InputState::default());
+        let flagged: Vec<u64> = (1..=WATCHDOG_FRAMES + 1)
+            .filter(|_| core.step_frame().health.contains(HealthFlags::WATCHDOG))
+            .collect();
+        /* The frame that leaves less than a frame to go, then the one it fires in */
+        assert_eq!(flagged, [WATCHDOG_FRAMES, WATCHDOG_FRAMES + 1]);
+        assert_eq!(core.ram()[0], 2);
+        let mut fed = EmulatorCore::new(watchdog_rom(true), InputState::default());
+        assert!((0..WATCHDOG_FRAMES + 1).all(|_| !fed.step_frame().health.contains(HealthFlags::WATCHDOG)));
+    }
+
+    #[test]
+    fn test_handler_without_ei_drops_interrupt() {
+        /* The mid-screen handler returns without EI, so the VBlank finds interrupts still off */
+        let mut core = health_core(&HEALTHY_PROGRAM.replacen("EI\n                RET", "RET", 1));
+        assert_eq!(core.step_frame().health, HealthFlags::INTERRUPT_DROPPED);
+        /* A program that never enables interrupts isn't using them, so nothing is dropped */
+        let mut core = health_core("LXI SP,$2400\nloop: JMP loop");
+        assert!(core.step_frame().health.is_empty());
+    }
+
+    #[test]
+    fn test_handler_running_past_the_next_interrupt_overruns() {
+        /* The mid-screen handler re-enables interrupts first, so the VBlank is taken rather than dropped,
+           then spins ~98k cycles */
+        let mut core = health_core("
+                LXI SP,$2400
+                EI
+        loop:   JMP loop
+                ORG $0008
+                JMP slow
+                ORG $0010
+                EI
+                RET
+        slow:   EI
+                LXI B,$1000
+        wait:   DCX B
+                MOV A,B
+                ORA C
+                JNZ wait
+                RET
+        ");
+        let health = core.step_frame().health;
+        assert!(health.contains(HealthFlags::CYCLE_OVERRUN));
+        assert!(!health.contains(HealthFlags::INTERRUPT_DROPPED));
+    }
+
+    fn strict_kinds(program: &str) -> Vec<AnomalyKind> {
+        let mut core = health_core(program);
+        core.set_strict(true);
+        core.step_frame();
+        return core.anomalies().unwrap().anomalies().iter().map(|anomaly| anomaly.kind).collect();
+    }
+
+    #[test]
+    fn test_each_core_anomaly_is_caught() {
+        assert_eq!(strict_kinds("STA $0100\nloop: JMP loop"), [AnomalyKind::RomWrite]);
+        assert_eq!(strict_kinds("IN 7\nIN 3\nloop: JMP loop"), [AnomalyKind::UnmappedInput]);
+        assert_eq!(strict_kinds("OUT 7\nOUT 6\nloop: JMP loop"), [AnomalyKind::UnmappedOutput]);
+        assert_eq!(strict_kinds("DB $08\nloop: JMP loop"), [AnomalyKind::UndocumentedOpcode]);
+        assert_eq!(strict_kinds("LXI SP,$4000\nloop: JMP loop"), [AnomalyKind::StackOutOfBounds]);
+        /* Round once, then stop */
+        let reset = "LDA $2000\nORA A\nJNZ done\nINR A\nSTA $2000\nRST 0\ndone: JMP done";
+        assert_eq!(strict_kinds(reset), [AnomalyKind::UnexpectedReset]);
+    }
+
+    #[test]
+    fn test_anomaly_carries_its_context() {
+        let mut core = health_core("NOP\nSTA $0100\nloop: JMP loop");
+        core.step_frame();
+        core.set_strict(true);
+        core.step_frame();
+        assert!(core.anomalies().unwrap().is_empty());
+        let mut core = health_core("NOP\nSTA $0100\nloop: JMP loop");
+        core.set_strict(true);
+        core.step_frame();
+        assert_eq!(core.anomalies().unwrap().anomalies(), [Anomaly {
+            kind: AnomalyKind::RomWrite, frame: 0, cycle: 4, pc: 0x0001, description: "write to read-only $0100".to_string(),
+        }]);
+    }
+
+    #[test]
+    fn test_host_drops_are_caught() {
+        let mut core = health_core("NOP\nloop: JMP loop");
+        core.set_strict(true);
+        core.step_frame();
+        core.report_dropped_sound_events(0);
+        core.report_dropped_sound_events(3);
+        core.report_dropped_frames(120);
+        let kinds: Vec<AnomalyKind> = core.anomalies().unwrap().anomalies().iter().map(|anomaly| anomaly.kind).collect();
+        assert_eq!(kinds, [AnomalyKind::DroppedSoundEvents, AnomalyKind::DroppedFrames]);
+        assert_eq!(core.anomalies().unwrap().anomalies()[1].description, "120 frames dropped");
+    }
+
+    #[test]
+    fn test_lenient_by_default() {
+        let mut core = health_core("STA $0100\nDB $08\nIN 7\nOUT 7\nRST 0");
+        core.step_frame();
+        assert!(core.anomalies().is_none());
+        /* The unexpected reset is still handed out either way */
+        assert!(core.take_unexpected_reset().is_some());
+    }
+
+    #[test]
+    fn test_breakpoint_stops_at_routine_entry() {
+        let mut core = health_core("NOP\nentry: INR B\nJMP entry");
+        core.set_breakpoints(vec![0x0001]);
+        core.step_frame();
+        assert_eq!((core.take_breakpoint_hit(), core.frame(), core.debug_snapshot().cpu.pc), (Some(0x0001), 0, 0x0001));
+        assert_eq!(core.take_breakpoint_hit(), None);
+        /* Running on passes the entry once, then stops there on the next time round */
+        core.step_frame();
+        assert_eq!((core.take_breakpoint_hit(), core.frame(), core.debug_snapshot().cpu.b), (Some(0x0001), 0, 1));
+        core.set_breakpoints(vec![]);
+        core.step_frame();
+        assert_eq!((core.take_breakpoint_hit(), core.frame()), (None, 1));
+    }
+
+    /* Needs the arcade ROMs: a minute of attract mode touches nothing the board ignores, which says the
+       memory map and port decoding match what the game expects */
+    #[test]
+    #[ignore = "needs the arcade ROMs; run with cargo test -- --ignored"]
+    fn test_attract_mode_runs_without_anomalies() {
+        let rom = crate::application::load_rom().expect("the arcade ROMs");
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.set_strict(true);
+        for _ in 0..3_600 {
+            core.step_frame();
+        }
+        let sink = core.anomalies().unwrap();
+        assert!(sink.is_empty(), "{:?}", sink.anomalies());
+    }
+}
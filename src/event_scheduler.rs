@@ -0,0 +1,219 @@
+/* Things the board does at a given emulated cycle rather than in response to an instruction */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    MidScreen,
+    VBlank,
+    Watchdog,
+}
+
+impl Event {
+    const ALL: [Event; 3] = [Event::MidScreen, Event::VBlank, Event::Watchdog];
+
+    pub fn to_byte(self) -> u8 {
+        return self as u8;
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Event> {
+        return Event::ALL.get(byte as usize).copied();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EventId(pub u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pending {
+    pub id: EventId,
+    /* Absolute emulated cycle */
+    pub due: u64,
+    pub event: Event,
+}
+
+/* Everything needed to carry the schedule through a save state */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchedulerState {
+    pub now: u64,
+    pub next_id: u64,
+    /* In firing order */
+    pub pending: Vec<Pending>,
+}
+
+/* Emulated-time clock with events at absolute cycle deadlines. Events come out of pop_due in deadline
+   order, and events due on the same cycle in the order they were scheduled (a rescheduled event counts
+   as scheduled anew) */
+pub struct EventScheduler {
+    now: u64,
+    next_id: u64,
+    pending: Vec<Pending>,
+}
+
+impl EventScheduler {
+    pub fn new() -> Self {
+        Self { now: 0, next_id: 0, pending: vec![] }
+    }
+
+    pub fn now(&self) -> u64 {
+        return self.now;
+    }
+
+    pub fn advance(&mut self, cycles: u64) {
+        self.now += cycles;
+    }
+
+    pub fn schedule_at(&mut self, due: u64, event: Event) -> EventId {
+        let id = EventId(self.next_id);
+        self.next_id += 1;
+        self.insert(Pending { id, due, event });
+        return id;
+    }
+
+    pub fn schedule_in(&mut self, delay: u64, event: Event) -> EventId {
+        return self.schedule_at(self.now + delay, event);
+    }
+
+    /* Returns whether the event was still pending */
+    #[cfg_attr(not(feature = "compare"), allow(dead_code))]
+    pub fn cancel(&mut self, id: EventId) -> bool {
+        return self.remove(id).is_some();
+    }
+
+    pub fn reschedule(&mut self, id: EventId, due: u64) -> bool {
+        match self.remove(id) {
+            Some(pending) => {
+                self.insert(Pending { due, ..pending });
+                return true;
+            },
+            None => return false,
+        }
+    }
+
+    /* The earliest pending instance of an event */
+    pub fn find(&self, event: Event) -> Option<Pending> {
+        return self.pending.iter().find(|pending| pending.event == event).copied();
+    }
+
+    /* The deadline of the next event to fire */
+    pub fn next_due(&self) -> Option<u64> {
+        return self.pending.first().map(|pending| pending.due);
+    }
+
+    /* Takes the next event due at or before the current cycle; call until None after advancing */
+    pub fn pop_due(&mut self) -> Option<Pending> {
+        if self.pending.first()?.due > self.now {
+            return None;
+        }
+        return Some(self.pending.remove(0));
+    }
+
+    pub fn state(&self) -> SchedulerState {
+        return SchedulerState { now: self.now, next_id: self.next_id, pending: self.pending.clone() };
+    }
+
+    pub fn restore(&mut self, state: &SchedulerState) {
+        self.now = state.now;
+        self.next_id = state.next_id;
+        self.pending = state.pending.clone();
+    }
+
+    /* After everything due at or before the new deadline, so same-cycle ties keep scheduling order */
+    fn insert(&mut self, pending: Pending) {
+        let index = self.pending.partition_point(|other| other.due <= pending.due);
+        self.pending.insert(index, pending);
+    }
+
+    fn remove(&mut self, id: EventId) -> Option<Pending> {
+        let index = self.pending.iter().position(|pending| pending.id == id)?;
+        return Some(self.pending.remove(index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(scheduler: &mut EventScheduler) -> Vec<(u64, Event)> {
+        let mut fired = vec![];
+        while let Some(pending) = scheduler.pop_due() {
+            fired.push((pending.due, pending.event));
+        }
+        return fired;
+    }
+
+    #[test]
+    fn test_events_fire_in_deadline_order() {
+        let mut scheduler = EventScheduler::new();
+        scheduler.schedule_at(300, Event::Watchdog);
+        scheduler.schedule_at(100, Event::MidScreen);
+        scheduler.schedule_in(200, Event::VBlank);
+        scheduler.advance(99);
+        assert_eq!(drain(&mut scheduler), vec![]);
+        scheduler.advance(1);
+        assert_eq!(drain(&mut scheduler), vec![(100, Event::MidScreen)]);
+        /* Late events still come out, oldest first */
+        scheduler.advance(500);
+        assert_eq!(drain(&mut scheduler), vec![(200, Event::VBlank), (300, Event::Watchdog)]);
+    }
+
+    #[test]
+    fn test_same_cycle_ties_keep_scheduling_order() {
+        let mut scheduler = EventScheduler::new();
+        let vblank = scheduler.schedule_at(50, Event::VBlank);
+        scheduler.schedule_at(50, Event::MidScreen);
+        scheduler.schedule_at(50, Event::Watchdog);
+        scheduler.advance(50);
+        assert_eq!(drain(&mut scheduler), vec![(50, Event::VBlank), (50, Event::MidScreen), (50, Event::Watchdog)]);
+
+        let first = scheduler.schedule_at(80, Event::VBlank);
+        scheduler.schedule_at(80, Event::MidScreen);
+        /* Rescheduling onto the same cycle moves it behind what is already there */
+        assert!(scheduler.reschedule(first, 80));
+        assert!(!scheduler.reschedule(vblank, 80));
+        scheduler.advance(30);
+        assert_eq!(drain(&mut scheduler), vec![(80, Event::MidScreen), (80, Event::VBlank)]);
+    }
+
+    #[test]
+    fn test_cancel_and_reschedule() {
+        let mut scheduler = EventScheduler::new();
+        let watchdog = scheduler.schedule_at(100, Event::Watchdog);
+        let vblank = scheduler.schedule_at(200, Event::VBlank);
+        assert_eq!(scheduler.next_due(), Some(100));
+        assert!(scheduler.cancel(watchdog));
+        assert!(!scheduler.cancel(watchdog));
+        assert!(scheduler.reschedule(vblank, 50));
+        assert_eq!(scheduler.next_due(), Some(50));
+        assert_eq!(scheduler.find(Event::VBlank).map(|pending| (pending.id, pending.due)), Some((vblank, 50)));
+        assert_eq!(scheduler.find(Event::Watchdog), None);
+        scheduler.advance(200);
+        assert_eq!(drain(&mut scheduler), vec![(50, Event::VBlank)]);
+        assert_eq!(scheduler.next_due(), None);
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut scheduler = EventScheduler::new();
+        scheduler.schedule_at(40, Event::MidScreen);
+        scheduler.schedule_at(40, Event::VBlank);
+        scheduler.schedule_at(10, Event::Watchdog);
+        scheduler.advance(25);
+        assert_eq!(drain(&mut scheduler), vec![(10, Event::Watchdog)]);
+        let state = scheduler.state();
+
+        let mut restored = EventScheduler::new();
+        restored.restore(&state);
+        assert_eq!(restored.now(), 25);
+        /* New ids don't collide with restored ones */
+        let id = restored.schedule_at(40, Event::Watchdog);
+        assert!(state.pending.iter().all(|pending| pending.id != id));
+        restored.advance(15);
+        assert_eq!(drain(&mut restored), vec![(40, Event::MidScreen), (40, Event::VBlank), (40, Event::Watchdog)]);
+    }
+
+    #[test]
+    fn test_event_bytes() {
+        for event in Event::ALL {
+            assert_eq!(Event::from_byte(event.to_byte()), Some(event));
+        }
+        assert_eq!(Event::from_byte(3), None);
+    }
+}
@@ -0,0 +1,153 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::platform;
+use crate::strings::{Language, Message, Strings};
+
+/* Paths already reported by warn_once, so a loader retried from the error screen or by a headless run
+   doesn't repeat itself */
+static WARNED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/* Why a file the user supplies could not be used. Missing and unreadable are kept apart because the fixes
+   differ: a missing file needs copying in, an unreadable one needs its permissions changed, or on macOS
+   its download quarantine lifted */
+#[derive(Debug)]
+pub enum ReadError {
+    Missing(PathBuf),
+    Unreadable { path: PathBuf, error: io::Error, quarantined: bool },
+}
+
+impl ReadError {
+    pub fn new(path: &Path, error: io::Error) -> Self {
+        let path = full_path(path);
+        if error.kind() == io::ErrorKind::NotFound {
+            return ReadError::Missing(path);
+        }
+        /* Gatekeeper refuses quarantined files with EPERM, which std reports as PermissionDenied too */
+        let quarantined = error.kind() == io::ErrorKind::PermissionDenied && platform::quarantined(&path);
+        return ReadError::Unreadable { path, error, quarantined };
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            ReadError::Missing(path) | ReadError::Unreadable { path, .. } => path,
+        }
+    }
+
+    pub fn message(&self) -> Message {
+        let path = self.path().display().to_string();
+        match self {
+            ReadError::Missing(_) => Message::new("file.missing", vec![path]),
+            ReadError::Unreadable { error, quarantined: false, .. } => Message::new("file.unreadable", vec![path, error.to_string()]),
+            ReadError::Unreadable { error, quarantined: true, .. } => Message::new("file.quarantined", vec![path, error.to_string()]),
+        }
+    }
+}
+
+/* Relative names are shown against the working directory, so the message says exactly where we looked */
+pub fn full_path(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    return std::env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_path_buf());
+}
+
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<u8>, ReadError> {
+    let path = path.as_ref();
+    return std::fs::read(path).map_err(|error| ReadError::new(path, error));
+}
+
+/* Logs the failure the first time this path fails; returns whether it did */
+pub fn warn_once(error: &ReadError) -> bool {
+    {
+        let mut warned = WARNED.lock().unwrap();
+        if warned.iter().any(|path| path == error.path()) {
+            return false;
+        }
+        warned.push(error.path().to_path_buf());
+    }
+    println!("Warning: {}", Strings::new(Language::English).message(&error.message()));
+    return true;
+}
+
+/* For the ROM loaders, whose errors are shown on the error screen as well as logged */
+pub fn read_for_message(path: impl AsRef<Path>) -> Result<Vec<u8>, Message> {
+    return read(path).map_err(|error| {
+        warn_once(&error);
+        error.message()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        return std::env::temp_dir().join(format!("si_file_access_{}_{}", std::process::id(), name));
+    }
+
+    #[test]
+    fn test_missing_names_the_full_path() {
+        let error = read("no_such_rom.h").unwrap_err();
+        assert!(matches!(error, ReadError::Missing(_)));
+        assert!(error.path().is_absolute());
+        assert!(error.path().ends_with("no_such_rom.h"));
+        let message = error.message();
+        assert_eq!((message.key, message.args.len()), ("file.missing", 1));
+        assert_eq!(message.args[0], error.path().display().to_string());
+    }
+
+    #[test]
+    fn test_readable_file() {
+        let path = temp_path("readable");
+        std::fs::write(&path, [1, 2, 3]).unwrap();
+        assert_eq!(read(&path).unwrap(), vec![1, 2, 3]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permission_denied_is_unreadable() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_path("denied");
+        std::fs::write(&path, [0; 16]).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let result = read(&path);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        /* Root reads through any mode, so there is nothing to check when the tests run as root */
+        let Err(error) = result else {
+            return;
+        };
+        match &error {
+            ReadError::Unreadable { path: denied, error, quarantined } => {
+                assert_eq!(denied, &path);
+                assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+                assert!(!quarantined);
+            },
+            other => panic!("{:?}", other),
+        }
+        let message = error.message();
+        assert_eq!(message.key, "file.unreadable");
+        assert_eq!(message.args[0], path.display().to_string());
+        assert_eq!(message.args[1], io::Error::from_raw_os_error(13).to_string());
+    }
+
+    #[test]
+    fn test_quarantine_message() {
+        let error = ReadError::Unreadable { path: PathBuf::from("/tmp/invaders.zip"), error: io::Error::from_raw_os_error(1), quarantined: true };
+        let message = error.message();
+        assert_eq!(message.key, "file.quarantined");
+        assert_eq!(message.args[0], "/tmp/invaders.zip");
+        let text = Strings::new(Language::English).message(&message);
+        assert!(text.contains("xattr -d com.apple.quarantine \"/tmp/invaders.zip\""), "{}", text);
+    }
+
+    #[test]
+    fn test_warns_once_per_path() {
+        let error = read(temp_path("warn_once")).unwrap_err();
+        assert!(warn_once(&error));
+        assert!(!warn_once(&error));
+        assert!(warn_once(&read(temp_path("warn_once_other")).unwrap_err()));
+    }
+}
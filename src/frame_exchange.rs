@@ -0,0 +1,100 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/* Hands finished frames from the emulation thread to the UI. Publishing swaps an Arc under the lock and
+   reading clones one, so pixels are never copied on the way; the serial tells the UI whether its texture
+   is already up to date */
+pub struct FrameExchange<T> {
+    slot: Mutex<Published<T>>,
+}
+
+pub struct Published<T> {
+    pub frame: Arc<T>,
+    /* Host time of the emulated VBlank that completed this frame */
+    pub vblank: Option<Instant>,
+    pub serial: u64,
+}
+
+/* Derived Clone would ask for T: Clone */
+impl<T> Clone for Published<T> {
+    fn clone(&self) -> Self {
+        Self { frame: Arc::clone(&self.frame), vblank: self.vblank, serial: self.serial }
+    }
+}
+
+impl<T> FrameExchange<T> {
+    pub fn new(initial: T) -> Self {
+        Self { slot: Mutex::new(Published { frame: Arc::new(initial), vblank: None, serial: 0 }) }
+    }
+
+    pub fn publish(&self, frame: Arc<T>, vblank: Option<Instant>) {
+        let mut slot = self.slot.lock().unwrap();
+        let serial = slot.serial + 1;
+        *slot = Published { frame, vblank, serial };
+    }
+
+    /* Replaces the pixels without moving the VBlank, e.g. after single-stepping */
+    pub fn replace(&self, frame: Arc<T>) {
+        let mut slot = self.slot.lock().unwrap();
+        slot.frame = frame;
+        slot.serial += 1;
+    }
+
+    pub fn latest(&self) -> Published<T> {
+        return self.slot.lock().unwrap().clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc_stats;
+
+    /* Counted per thread, so tests running in parallel don't see each other's */
+    fn allocations(f: impl FnOnce()) -> usize {
+        let before = alloc_stats::thread_allocations();
+        f();
+        return alloc_stats::thread_allocations() - before;
+    }
+
+    /* Stand-in for the scaled frame: 448x512 pixels */
+    fn frame(value: u32) -> Vec<u32> {
+        return vec![value; 448 * 512];
+    }
+
+    #[test]
+    fn test_reader_sees_latest_frame_by_serial() {
+        let exchange = FrameExchange::new(frame(0));
+        let first = exchange.latest();
+        assert_eq!(first.serial, 0);
+        let now = Instant::now();
+        exchange.publish(Arc::new(frame(1)), Some(now));
+        let second = exchange.latest();
+        assert_eq!((second.serial, second.vblank, second.frame[0]), (1, Some(now), 1));
+        exchange.replace(Arc::new(frame(2)));
+        let third = exchange.latest();
+        assert_eq!((third.serial, third.vblank, third.frame[0]), (2, Some(now), 2));
+    }
+
+    #[test]
+    fn test_simulated_frame_allocation_budget() {
+        let exchange = FrameExchange::new(frame(0));
+        let mut uploaded = 0;
+        for value in 1..10 {
+            /* Emulation thread: the pixel buffer and its Arc */
+            let produced = allocations(|| exchange.publish(Arc::new(frame(value)), None));
+            assert!(produced <= 2, "publishing allocated {} times", produced);
+            /* UI update: take the latest frame and decide whether to upload it, without copying */
+            let consumed = allocations(|| {
+                let latest = exchange.latest();
+                if latest.serial != uploaded {
+                    uploaded = latest.serial;
+                    assert_eq!(latest.frame[0], value);
+                }
+                let again = exchange.latest();
+                assert!(Arc::ptr_eq(&latest.frame, &again.frame));
+            });
+            assert_eq!(consumed, 0);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::time::{Duration, Instant};
+
+pub const FRAME_PERIOD: Duration = Duration::from_micros(16_667);
+/* Present this long after an emulated VBlank so the new frame is already in the slot despite compute jitter */
+pub const PRESENT_DELAY: Duration = Duration::from_millis(4);
+/* Stop scheduling presents once no frame has completed for this long, e.g. while paused */
+const STALE_AFTER: Duration = Duration::from_millis(100);
+
+/* How long the UI should wait before presenting the next frame, keeping a fixed phase to the last VBlank.
+   The frame in the slot is assumed to be on screen already, so the earliest target is one period on. */
+pub fn next_present_delay(vblank: Instant, now: Instant) -> Option<Duration> {
+    let since_vblank = now.saturating_duration_since(vblank);
+    if since_vblank > STALE_AFTER {
+        return None;
+    }
+    let periods = match since_vblank.checked_sub(PRESENT_DELAY) {
+        Some(after_present) => after_present.as_micros() / FRAME_PERIOD.as_micros() + 1,
+        None => 1,
+    };
+    let target = vblank + PRESENT_DELAY + FRAME_PERIOD * periods as u32;
+    return Some(target - now);
+}
+
+/* Overruns longer than this are a host suspend or debugger stop, not slow frames, and are not caught up */
+pub const RESYNC_THRESHOLD: Duration = Duration::from_millis(250);
+
+/* Drift-free frame deadlines: a late frame is caught up by running the following ones without sleeping */
+pub struct FrameScheduler {
+    deadline: Instant,
+    /* Frames per FRAME_PERIOD; turbo paces real frames faster rather than running several per tick */
+    speed: u32,
+}
+
+impl FrameScheduler {
+    pub fn new(now: Instant) -> Self {
+        Self { deadline: now, speed: 1 }
+    }
+
+    pub fn speed(&self) -> u32 {
+        return self.speed;
+    }
+
+    pub fn period(&self) -> Duration {
+        return FRAME_PERIOD / self.speed;
+    }
+
+    /* Re-anchors the pending deadline so the fraction of a frame left (or the frames owed, when behind)
+       carries over at the new period: no burst of catch-up frames on a slow-down, no stall on a speed-up */
+    pub fn set_speed(&mut self, speed: u32, now: Instant) {
+        let speed = speed.max(1);
+        if speed == self.speed {
+            return;
+        }
+        self.deadline = if self.deadline >= now {
+            now + (self.deadline - now) * self.speed / speed
+        } else {
+            now - (now - self.deadline) * self.speed / speed
+        };
+        self.speed = speed;
+    }
+
+    pub fn reset(&mut self, now: Instant) {
+        self.deadline = now;
+    }
+
+    /* Moves to the next frame's deadline; on a discontinuity resyncs to now and returns the time dropped */
+    pub fn advance(&mut self, now: Instant) -> Option<Duration> {
+        self.deadline += self.period();
+        let overrun = now.saturating_duration_since(self.deadline);
+        if overrun > RESYNC_THRESHOLD {
+            self.deadline = now;
+            return Some(overrun);
+        }
+        return None;
+    }
+
+    pub fn sleep_time(&self, now: Instant) -> Duration {
+        return self.deadline.saturating_duration_since(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_keeps_phase() {
+        let vblank = Instant::now();
+        assert_eq!(next_present_delay(vblank, vblank), Some(PRESENT_DELAY + FRAME_PERIOD));
+        let just_presented = vblank + PRESENT_DELAY;
+        assert_eq!(next_present_delay(vblank, just_presented), Some(FRAME_PERIOD));
+        let late = vblank + PRESENT_DELAY + Duration::from_millis(20);
+        assert_eq!(next_present_delay(vblank, late), Some(FRAME_PERIOD * 2 - Duration::from_millis(20)));
+        assert_eq!(next_present_delay(vblank, vblank + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn test_deadline_does_not_drift() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        let mut now = start;
+        for _ in 0..600 {
+            assert_eq!(scheduler.advance(now + Duration::from_millis(3)), None);
+            now += scheduler.sleep_time(now);
+        }
+        assert_eq!(now - start, FRAME_PERIOD * 600);
+    }
+
+    #[test]
+    fn test_normal_overrun_catches_up() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        let mut now = start + Duration::from_millis(60);
+        let mut frames_without_sleep = 0;
+        loop {
+            assert_eq!(scheduler.advance(now), None);
+            let sleep = scheduler.sleep_time(now);
+            if sleep > Duration::ZERO {
+                break;
+            }
+            frames_without_sleep += 1;
+            now += Duration::from_millis(1);
+        }
+        assert_eq!(frames_without_sleep, 3);
+    }
+
+    #[test]
+    fn test_discontinuity_resyncs() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        let wake = start + Duration::from_secs(3_600);
+        let dropped = scheduler.advance(wake).unwrap();
+        assert_eq!(dropped, Duration::from_secs(3_600) - FRAME_PERIOD);
+        assert_eq!(scheduler.sleep_time(wake), Duration::ZERO);
+        assert_eq!(scheduler.advance(wake), None);
+        assert_eq!(scheduler.sleep_time(wake), FRAME_PERIOD);
+    }
+
+    #[test]
+    fn test_speed_up_mid_frame_does_not_stall() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        assert_eq!(scheduler.advance(start), None);
+        let mut now = start + FRAME_PERIOD / 2;
+        scheduler.set_speed(2, now);
+        /* Half a normal frame was left, which is a quarter of one at double speed */
+        assert_eq!(scheduler.sleep_time(now), FRAME_PERIOD / 4);
+        now += scheduler.sleep_time(now);
+        for _ in 0..120 {
+            assert_eq!(scheduler.advance(now + Duration::from_millis(1)), None);
+            let sleep = scheduler.sleep_time(now + Duration::from_millis(1));
+            assert_eq!(sleep, FRAME_PERIOD / 2 - Duration::from_millis(1));
+            now += Duration::from_millis(1) + sleep;
+        }
+        assert_eq!(now - start, FRAME_PERIOD / 2 + FRAME_PERIOD / 4 + FRAME_PERIOD / 2 * 120);
+    }
+
+    #[test]
+    fn test_slow_down_mid_frame_does_not_burst() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        scheduler.set_speed(2, start);
+        assert_eq!(scheduler.advance(start), None);
+        assert_eq!(scheduler.sleep_time(start), FRAME_PERIOD / 2);
+        let mut now = start + FRAME_PERIOD / 4;
+        scheduler.set_speed(1, now);
+        assert_eq!(scheduler.sleep_time(now), FRAME_PERIOD / 2);
+        now += scheduler.sleep_time(now);
+        for _ in 0..60 {
+            assert_eq!(scheduler.advance(now), None);
+            assert_eq!(scheduler.sleep_time(now), FRAME_PERIOD);
+            now += FRAME_PERIOD;
+        }
+    }
+
+    #[test]
+    fn test_speed_change_keeps_frames_owed() {
+        let start = Instant::now();
+        let mut scheduler = FrameScheduler::new(start);
+        let mut now = start + FRAME_PERIOD * 3;
+        scheduler.set_speed(2, now);
+        scheduler.set_speed(2, now);
+        let mut frames_without_sleep = 0;
+        loop {
+            assert_eq!(scheduler.advance(now), None);
+            if scheduler.sleep_time(now) > Duration::ZERO {
+                break;
+            }
+            frames_without_sleep += 1;
+            now += Duration::from_micros(1);
+        }
+        assert_eq!(frames_without_sleep, 3);
+        scheduler.set_speed(0, now);
+        assert_eq!(scheduler.period(), FRAME_PERIOD);
+    }
+
+    /* Emulated VBlanks jitter with compute time; every present should show exactly one new frame at a steady interval */
+    #[test]
+    fn test_simulated_presents_are_stable() {
+        let start = Instant::now();
+        let jitter = |frame: u32| Duration::from_micros((frame as u64 * 7_919) % 2_000);
+        let vblanks: Vec<Instant> = (0..300).map(|frame| start + FRAME_PERIOD * frame + jitter(frame)).collect();
+        let latest_frame = |now: Instant| vblanks.iter().rposition(|vblank| *vblank <= now).unwrap();
+
+        let mut now = vblanks[0] + PRESENT_DELAY;
+        let mut presents: Vec<(Instant, usize)> = vec![];
+        while now < vblanks[290] {
+            let frame = latest_frame(now);
+            presents.push((now, frame));
+            now += next_present_delay(vblanks[frame], now).unwrap();
+        }
+        for pair in presents.windows(2) {
+            let ((previous, previous_frame), (current, frame)) = (pair[0], pair[1]);
+            assert_eq!(frame, previous_frame + 1);
+            let interval = current - previous;
+            assert!(interval > FRAME_PERIOD - Duration::from_millis(2) && interval < FRAME_PERIOD + Duration::from_millis(2), "{:?}", interval);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::ram_map;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameState {
+    Attract,
+    Playing,
+}
+
+impl GameState {
+    /* Bit 0 of the game mode byte is set from a start button press until the last base is lost */
+    pub fn from_ram(ram: &[u8]) -> Self {
+        if ram_map::read(ram, ram_map::GAME_MODE) & 0x01 == 0x01 {
+            return GameState::Playing;
+        }
+        return GameState::Attract;
+    }
+}
+
+/* The cabinet lit a start button whenever pressing it would start a game */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StartLamps {
+    pub one_player: bool,
+    pub two_player: bool,
+}
+
+impl StartLamps {
+    pub fn from_ram(ram: &[u8]) -> Self {
+        if ram_map::read(ram, ram_map::GAME_MODE) & 0x01 == 0x01 {
+            return Self { one_player: false, two_player: false };
+        }
+        let credits = ram_map::bcd_to_decimal(ram_map::read(ram, ram_map::CREDITS));
+        Self {
+            one_player: credits >= 1,
+            two_player: credits >= 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(game_mode: u8) -> [u8; ram_map::WORK_RAM_SIZE] {
+        let mut ram = [0; ram_map::WORK_RAM_SIZE];
+        ram[(ram_map::GAME_MODE - ram_map::WORK_RAM_START) as usize] = game_mode;
+        return ram;
+    }
+
+    #[test]
+    fn test_detect_playing() {
+        assert_eq!(GameState::from_ram(&snapshot(0x01)), GameState::Playing);
+        assert_eq!(GameState::from_ram(&snapshot(0x00)), GameState::Attract);
+        /* Only bit 0 counts */
+        assert_eq!(GameState::from_ram(&snapshot(0x02)), GameState::Attract);
+    }
+
+    fn lamps(game_mode: u8, credits: u8) -> StartLamps {
+        let mut ram = snapshot(game_mode);
+        ram[(ram_map::CREDITS - ram_map::WORK_RAM_START) as usize] = credits;
+        return StartLamps::from_ram(&ram);
+    }
+
+    #[test]
+    fn test_start_lamps_follow_credits() {
+        assert_eq!(lamps(0x00, 0x00), StartLamps { one_player: false, two_player: false });
+        assert_eq!(lamps(0x00, 0x01), StartLamps { one_player: true, two_player: false });
+        assert_eq!(lamps(0x00, 0x02), StartLamps { one_player: true, two_player: true });
+        /* Credits are BCD, so 99 is $99 */
+        assert_eq!(lamps(0x00, 0x99), StartLamps { one_player: true, two_player: true });
+        assert_eq!(lamps(0x00, 0x10), StartLamps { one_player: true, two_player: true });
+    }
+
+    #[test]
+    fn test_start_lamps_dark_during_play() {
+        assert_eq!(lamps(0x01, 0x02), StartLamps { one_player: false, two_player: false });
+    }
+}
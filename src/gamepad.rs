@@ -0,0 +1,59 @@
+// Gamepads through gilrs. Pads only add to what the keyboard holds: the D-pad or left stick moves, South
+// fires and Start starts for the pad's player, and Select on any assigned pad drops a coin.
+use gilrs::{Axis, Button, EventType, Gilrs};
+use crate::input_sources::{PadAssignments, Player, SourceEvent};
+use crate::machine::LogicalInput;
+
+const STICK_DEADZONE: f32 = 0.5;
+
+pub struct Gamepads {
+    gilrs: Gilrs,
+    /* Pads open before the first poll, reported as connections then */
+    startup: Vec<SourceEvent>,
+}
+
+impl Gamepads {
+    pub fn new() -> Result<Self, gilrs::Error> {
+        let gilrs = Gilrs::new()?;
+        let startup = gilrs.gamepads().map(|(id, pad)| SourceEvent::Connected { id: id.into(), name: pad.name().to_string() }).collect();
+        return Ok(Self { gilrs, startup });
+    }
+
+    /* Drains the host's events, which also brings the button state up to date */
+    pub fn poll(&mut self) -> Vec<SourceEvent> {
+        let mut events = std::mem::take(&mut self.startup);
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    events.push(SourceEvent::Connected { id: event.id.into(), name: self.gilrs.gamepad(event.id).name().to_string() });
+                },
+                EventType::Disconnected => events.push(SourceEvent::Disconnected { id: event.id.into() }),
+                _ => {},
+            }
+        }
+        return events;
+    }
+
+    pub fn held(&self, assignments: &PadAssignments) -> Vec<LogicalInput> {
+        let mut held = vec![];
+        for (id, pad) in self.gilrs.gamepads() {
+            let Some(player) = assignments.player_of(id.into()) else {
+                continue;
+            };
+            let (left, right, fire, start) = match player {
+                Player::One => (LogicalInput::P1Left, LogicalInput::P1Right, LogicalInput::P1Fire, LogicalInput::P1Start),
+                Player::Two => (LogicalInput::P2Left, LogicalInput::P2Right, LogicalInput::P2Fire, LogicalInput::P2Start),
+            };
+            let stick = pad.value(Axis::LeftStickX);
+            let controls = [
+                (left, pad.is_pressed(Button::DPadLeft) || stick < -STICK_DEADZONE),
+                (right, pad.is_pressed(Button::DPadRight) || stick > STICK_DEADZONE),
+                (fire, pad.is_pressed(Button::South)),
+                (start, pad.is_pressed(Button::Start)),
+                (LogicalInput::Coin, pad.is_pressed(Button::Select)),
+            ];
+            held.extend(controls.into_iter().filter(|(_, down)| *down).map(|(logical, _)| logical));
+        }
+        return held;
+    }
+}
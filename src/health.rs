@@ -0,0 +1,106 @@
+use std::ops::{BitOr, BitOrAssign};
+
+/* What the core's sanity checks saw during a frame, one bit per check, so a frontend can warn without
+   watching the CPU itself */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HealthFlags(u8);
+
+impl HealthFlags {
+    /* Halted with interrupts disabled: only a reset gets the CPU going again */
+    pub const WEDGED: HealthFlags = HealthFlags(1 << 0);
+    /* Running from outside the ROM for more than a whole frame */
+    pub const PC_OUTSIDE_ROM: HealthFlags = HealthFlags(1 << 1);
+    /* An instruction left the stack pointer outside the configured stack */
+    pub const STACK_OUT_OF_BOUNDS: HealthFlags = HealthFlags(1 << 2);
+    /* The watchdog will fire within the next frame unless it is fed, or went unfed long enough to reset the CPU */
+    pub const WATCHDOG: HealthFlags = HealthFlags(1 << 3);
+    /* An interrupt arrived while the game still had interrupts disabled from the last one, and was dropped */
+    pub const INTERRUPT_DROPPED: HealthFlags = HealthFlags(1 << 4);
+    /* A video interrupt came due while the handler for the last one was still running: that half frame's
+       work overran its cycle budget, even if the handler had re-enabled interrupts and nothing was dropped */
+    pub const CYCLE_OVERRUN: HealthFlags = HealthFlags(1 << 5);
+    pub const ALL: [HealthFlags; 6] = [HealthFlags::WEDGED, HealthFlags::PC_OUTSIDE_ROM, HealthFlags::STACK_OUT_OF_BOUNDS, HealthFlags::WATCHDOG, HealthFlags::INTERRUPT_DROPPED, HealthFlags::CYCLE_OVERRUN];
+
+    pub fn bits(&self) -> u8 {
+        return self.0;
+    }
+
+    /* Unknown bits are dropped */
+    pub fn from_bits(bits: u8) -> Self {
+        let known = HealthFlags::ALL.iter().fold(0, |mask, flag| mask | flag.0);
+        return Self(bits & known);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.0 == 0;
+    }
+
+    pub fn contains(&self, other: HealthFlags) -> bool {
+        return self.0 & other.0 == other.0;
+    }
+
+    pub fn insert(&mut self, other: HealthFlags) {
+        self.0 |= other.0;
+    }
+
+    /* The single flags set, in ALL's order */
+    pub fn iter(&self) -> impl Iterator<Item = HealthFlags> + '_ {
+        return HealthFlags::ALL.into_iter().filter(|flag| self.contains(*flag));
+    }
+
+    /* String key describing a single flag */
+    pub fn label(&self) -> &'static str {
+        match *self {
+            HealthFlags::WEDGED => "health.wedged",
+            HealthFlags::PC_OUTSIDE_ROM => "health.pc_outside_rom",
+            HealthFlags::STACK_OUT_OF_BOUNDS => "health.stack_out_of_bounds",
+            HealthFlags::WATCHDOG => "health.watchdog",
+            HealthFlags::INTERRUPT_DROPPED => "health.interrupt_dropped",
+            HealthFlags::CYCLE_OVERRUN => "health.cycle_overrun",
+            _ => "health.title",
+        }
+    }
+}
+
+impl BitOr for HealthFlags {
+    type Output = HealthFlags;
+
+    fn bitor(self, other: HealthFlags) -> HealthFlags {
+        return HealthFlags(self.0 | other.0);
+    }
+}
+
+impl BitOrAssign for HealthFlags {
+    fn bitor_assign(&mut self, other: HealthFlags) {
+        self.insert(other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strings::{Language, Strings};
+
+    #[test]
+    fn test_flags() {
+        let mut flags = HealthFlags::default();
+        assert!(flags.is_empty());
+        flags |= HealthFlags::WATCHDOG;
+        flags.insert(HealthFlags::WEDGED);
+        assert!(flags.contains(HealthFlags::WEDGED | HealthFlags::WATCHDOG));
+        assert!(!flags.contains(HealthFlags::WEDGED | HealthFlags::INTERRUPT_DROPPED));
+        assert_eq!(flags.iter().collect::<Vec<_>>(), [HealthFlags::WEDGED, HealthFlags::WATCHDOG]);
+        assert_eq!(HealthFlags::from_bits(flags.bits() | 0x80), flags);
+    }
+
+    #[test]
+    fn test_every_flag_is_described() {
+        for language in Language::ALL {
+            let strings = Strings::new(language);
+            for flag in HealthFlags::ALL {
+                assert_ne!(flag.label(), "health.title");
+                assert_ne!(strings.tr(flag.label()), flag.label());
+            }
+        }
+    }
+}
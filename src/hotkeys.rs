@@ -0,0 +1,395 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use crate::actions::{Action, ActionRegistry, Shortcut};
+use crate::machine::LogicalInput;
+use crate::macros::Macro;
+
+/* A key plus modifiers, using egui key names */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chord {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub key: String,
+}
+
+impl Chord {
+    pub fn key(key: &str) -> Self {
+        Self { ctrl: false, shift: false, key: key.to_string() }
+    }
+}
+
+impl From<Shortcut> for Chord {
+    fn from(shortcut: Shortcut) -> Self {
+        Self { ctrl: shortcut.ctrl, shift: shortcut.shift, key: shortcut.key.to_string() }
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/* The controls each player's hand is on; the cabinet has a separate fire/left/right set per player */
+const PLAYER_PAIRS: [(LogicalInput, LogicalInput); 3] = [
+    (LogicalInput::P1Fire, LogicalInput::P2Fire),
+    (LogicalInput::P1Left, LogicalInput::P2Left),
+    (LogicalInput::P1Right, LogicalInput::P2Right),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlPreset {
+    #[default]
+    RightHanded,
+    LeftHanded,
+    ArrowsOnly,
+}
+
+impl ControlPreset {
+    pub const ALL: [ControlPreset; 3] = [ControlPreset::RightHanded, ControlPreset::LeftHanded, ControlPreset::ArrowsOnly];
+
+    /* Key into the [controls] strings */
+    pub fn name_key(&self) -> &'static str {
+        match self {
+            ControlPreset::RightHanded => "controls.right_handed",
+            ControlPreset::LeftHanded => "controls.left_handed",
+            ControlPreset::ArrowsOnly => "controls.arrows_only",
+        }
+    }
+
+    fn keys(&self) -> Vec<(LogicalInput, &'static str)> {
+        let (p1, p2) = match self {
+            ControlPreset::RightHanded => (Some(["W", "A", "D"]), Some(["ArrowUp", "ArrowLeft", "ArrowRight"])),
+            ControlPreset::LeftHanded => (Some(["ArrowUp", "ArrowLeft", "ArrowRight"]), Some(["W", "A", "D"])),
+            /* One player on the arrows; player 2 is left unbound */
+            ControlPreset::ArrowsOnly => (Some(["ArrowUp", "ArrowLeft", "ArrowRight"]), None),
+        };
+        let mut keys = vec![(LogicalInput::Coin, "Space"), (LogicalInput::P1Start, "1"), (LogicalInput::P2Start, "2")];
+        for (index, (p1_input, p2_input)) in PLAYER_PAIRS.iter().enumerate() {
+            if let Some(p1) = p1 {
+                keys.push((*p1_input, p1[index]));
+            }
+            if let Some(p2) = p2 {
+                keys.push((*p2_input, p2[index]));
+            }
+        }
+        return keys;
+    }
+
+    fn key(&self, input: LogicalInput) -> Option<&'static str> {
+        return self.keys().into_iter().find(|(bound, _)| *bound == input).map(|(_, key)| key);
+    }
+}
+
+/* A user's change to one control under one preset; key None leaves the control unbound */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyOverride {
+    pub preset: ControlPreset,
+    pub input: LogicalInput,
+    pub key: Option<String>,
+}
+
+pub const DEFAULT_TURBO_KEY: &str = "Tab";
+
+/* What is saved in the settings: the preset by name, plus overrides kept per preset so switching away
+   and back doesn't lose them. The turbo key is not a cabinet control and is the same under every preset */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub preset: ControlPreset,
+    pub overrides: Vec<KeyOverride>,
+    pub turbo: Option<String>,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        Self { preset: ControlPreset::default(), overrides: vec![], turbo: Some(DEFAULT_TURBO_KEY.to_string()) }
+    }
+}
+
+impl ControlSettings {
+    pub fn key(&self, input: LogicalInput) -> Option<&str> {
+        return match self.overrides.iter().find(|o| o.preset == self.preset && o.input == input) {
+            Some(key_override) => key_override.key.as_deref(),
+            None => self.preset.key(input),
+        };
+    }
+
+    /* Binds input under the current preset; binding a preset's own key drops the override */
+    pub fn bind(&mut self, input: LogicalInput, key: Option<String>) {
+        let preset = self.preset;
+        self.overrides.retain(|o| !(o.preset == preset && o.input == input));
+        if key.as_deref() != preset.key(input) {
+            self.overrides.push(KeyOverride { preset, input, key });
+        }
+    }
+
+    /* Gives player 1 player 2's keys and the reverse, as overrides on the current preset */
+    pub fn swap_players(&mut self) {
+        for (p1, p2) in PLAYER_PAIRS {
+            let (p1_key, p2_key) = (self.key(p1).map(str::to_string), self.key(p2).map(str::to_string));
+            self.bind(p1, p2_key);
+            self.bind(p2, p1_key);
+        }
+    }
+
+    /* Back to the preset's own keys */
+    pub fn reset(&mut self) {
+        let preset = self.preset;
+        self.overrides.retain(|o| o.preset != preset);
+        self.turbo = Some(DEFAULT_TURBO_KEY.to_string());
+    }
+}
+
+/* Keys for the cabinet controls */
+pub struct KeyBindings {
+    keys: Vec<(LogicalInput, String)>,
+    turbo: Option<String>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        return Self::from_controls(&ControlSettings::default());
+    }
+}
+
+impl KeyBindings {
+    pub fn from_controls(controls: &ControlSettings) -> Self {
+        let keys = LogicalInput::ALL.iter().filter_map(|input| controls.key(*input).map(|key| (*input, key.to_string()))).collect();
+        return Self { keys, turbo: controls.turbo.clone() };
+    }
+
+    #[cfg(test)]
+    pub fn key(&self, input: LogicalInput) -> Option<&str> {
+        return self.keys.iter().find(|(bound, _)| *bound == input).map(|(_, key)| key.as_str());
+    }
+
+    pub fn all(&self) -> &[(LogicalInput, String)] {
+        return &self.keys;
+    }
+
+    pub fn turbo(&self) -> Option<&str> {
+        return self.turbo.as_deref();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Action(Action),
+    Macro(usize),
+    Game(LogicalInput),
+    /* Held rather than pressed; see InputManager::held */
+    Turbo,
+}
+
+/* Where keyboard input is going this frame */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Focus {
+    /* No widget has focus and no dialog is open: the player is playing */
+    Game,
+    /* A widget such as a text field has keyboard focus */
+    Widget,
+    /* The command palette or a key capture owns the keyboard */
+    Modal,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub chord: Chord,
+    pub existing: Binding,
+    pub rejected: Binding,
+}
+
+/* Owns every key registration: UI actions, macro hotkeys and cabinet controls */
+pub struct HotkeyManager {
+    bindings: Vec<(Chord, Binding)>,
+    conflicts: Vec<Conflict>,
+}
+
+impl HotkeyManager {
+    pub fn new(actions: &ActionRegistry, keys: &KeyBindings, macros: &[Macro]) -> Self {
+        let mut manager = Self { bindings: vec![], conflicts: vec![] };
+        for info in actions.all() {
+            if let Some(shortcut) = info.shortcut {
+                manager.register(shortcut.into(), Binding::Action(info.action));
+            }
+        }
+        for (input, key) in keys.all() {
+            manager.register(Chord::key(key), Binding::Game(*input));
+        }
+        if let Some(key) = keys.turbo() {
+            manager.register(Chord::key(key), Binding::Turbo);
+        }
+        for (index, recorded) in macros.iter().enumerate() {
+            if let Some(key) = &recorded.key {
+                manager.register(Chord::key(key), Binding::Macro(index));
+            }
+        }
+        return manager;
+    }
+
+    /* Keeps the first registration of a chord; later ones are recorded as conflicts and not routed */
+    fn register(&mut self, chord: Chord, binding: Binding) {
+        match self.check(&chord, binding) {
+            Some(existing) => self.conflicts.push(Conflict { chord, existing, rejected: binding }),
+            None => self.bindings.push((chord, binding)),
+        }
+    }
+
+    /* What `chord` is already bound to, ignoring `binding` itself so rebinding to the same key is fine */
+    pub fn check(&self, chord: &Chord, binding: Binding) -> Option<Binding> {
+        return self.bindings.iter().find(|(bound, existing)| bound == chord && *existing != binding).map(|(_, existing)| *existing);
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        return &self.conflicts;
+    }
+
+    pub fn route(&self, chord: &Chord, focus: Focus) -> Option<Binding> {
+        let binding = self.bindings.iter().find(|(bound, _)| bound == chord).map(|(_, binding)| *binding)?;
+        let allowed = match (binding, focus) {
+            (_, Focus::Modal) => false,
+            /* A focused text field takes bare and shifted keys as typing; only Ctrl chords still reach actions */
+            (Binding::Action(_), Focus::Widget) => chord.ctrl,
+            (Binding::Action(_), Focus::Game) => true,
+            (Binding::Macro(_), focus) | (Binding::Game(_), focus) | (Binding::Turbo, focus) => focus == Focus::Game,
+        };
+        return if allowed { Some(binding) } else { None };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(key: Option<&str>) -> Macro {
+        Macro { name: "m".to_string(), key: key.map(|key| key.to_string()), steps: vec![], speed: vec![] }
+    }
+
+    #[test]
+    fn test_default_bindings_do_not_conflict() {
+        let manager = HotkeyManager::new(&ActionRegistry::new(), &KeyBindings::default(), &[]);
+        assert!(manager.conflicts().is_empty());
+        assert_eq!(manager.route(&Chord::key("W"), Focus::Game), Some(Binding::Game(LogicalInput::P1Fire)));
+        assert_eq!(manager.route(&Chord::key(DEFAULT_TURBO_KEY), Focus::Game), Some(Binding::Turbo));
+        assert_eq!(manager.route(&Chord::key(DEFAULT_TURBO_KEY), Focus::Widget), None);
+    }
+
+    #[test]
+    fn test_turbo_key_is_shared_across_presets() {
+        let mut controls = ControlSettings::default();
+        controls.turbo = Some("T".to_string());
+        controls.preset = ControlPreset::LeftHanded;
+        assert_eq!(KeyBindings::from_controls(&controls).turbo(), Some("T"));
+        controls.bind(LogicalInput::Coin, Some("T".to_string()));
+        let manager = HotkeyManager::new(&ActionRegistry::new(), &KeyBindings::from_controls(&controls), &[]);
+        assert_eq!(manager.conflicts(), &[Conflict { chord: Chord::key("T"), existing: Binding::Game(LogicalInput::Coin), rejected: Binding::Turbo }]);
+        controls.reset();
+        assert_eq!(controls, ControlSettings { preset: ControlPreset::LeftHanded, ..ControlSettings::default() });
+    }
+
+    #[test]
+    fn test_macro_conflicting_with_game_key() {
+        let macros = [recorded(Some("W")), recorded(Some("M"))];
+        let manager = HotkeyManager::new(&ActionRegistry::new(), &KeyBindings::default(), &macros);
+        assert_eq!(manager.conflicts(), &[Conflict { chord: Chord::key("W"), existing: Binding::Game(LogicalInput::P1Fire), rejected: Binding::Macro(0) }]);
+        assert_eq!(manager.route(&Chord::key("M"), Focus::Game), Some(Binding::Macro(1)));
+    }
+
+    #[test]
+    fn test_check_at_bind_time() {
+        let macros = [recorded(Some("M"))];
+        let manager = HotkeyManager::new(&ActionRegistry::new(), &KeyBindings::default(), &macros);
+        assert_eq!(manager.check(&Chord::key("F1"), Binding::Macro(0)), Some(Binding::Action(Action::LoadSlot(1))));
+        assert_eq!(manager.check(&Chord::key("M"), Binding::Macro(0)), None);
+        assert_eq!(manager.check(&Chord::key("M"), Binding::Macro(1)), Some(Binding::Macro(0)));
+        let shifted = Chord { ctrl: false, shift: true, key: "F1".to_string() };
+        assert_eq!(manager.check(&shifted, Binding::Macro(0)), Some(Binding::Action(Action::SaveSlot(1))));
+        assert_eq!(manager.check(&Chord::key("K"), Binding::Macro(0)), None);
+    }
+
+    #[test]
+    fn test_routing_follows_focus() {
+        let macros = [recorded(Some("M"))];
+        let manager = HotkeyManager::new(&ActionRegistry::new(), &KeyBindings::default(), &macros);
+        let pause = Chord::from(Shortcut::ctrl("P"));
+        assert_eq!(manager.route(&pause, Focus::Game), Some(Binding::Action(Action::TogglePause)));
+        assert_eq!(manager.route(&pause, Focus::Widget), Some(Binding::Action(Action::TogglePause)));
+        assert_eq!(manager.route(&pause, Focus::Modal), None);
+        /* Escape in a text field must not quit */
+        let quit = Chord::key("Escape");
+        assert_eq!(manager.route(&quit, Focus::Game), Some(Binding::Action(Action::Quit)));
+        assert_eq!(manager.route(&quit, Focus::Widget), None);
+        assert_eq!(manager.route(&Chord::from(Shortcut::shift("F1")), Focus::Widget), None);
+        for focus in [Focus::Widget, Focus::Modal] {
+            assert_eq!(manager.route(&Chord::key("W"), focus), None);
+            assert_eq!(manager.route(&Chord::key("M"), focus), None);
+        }
+        assert_eq!(manager.route(&Chord::key("Q"), Focus::Game), None);
+    }
+
+    fn player_keys(controls: &ControlSettings) -> Vec<Option<&str>> {
+        return PLAYER_PAIRS.iter().flat_map(|(p1, p2)| [controls.key(*p1), controls.key(*p2)]).collect();
+    }
+
+    #[test]
+    fn test_presets_apply() {
+        let mut controls = ControlSettings::default();
+        assert_eq!(player_keys(&controls), vec![Some("W"), Some("ArrowUp"), Some("A"), Some("ArrowLeft"), Some("D"), Some("ArrowRight")]);
+        controls.preset = ControlPreset::LeftHanded;
+        assert_eq!(player_keys(&controls), vec![Some("ArrowUp"), Some("W"), Some("ArrowLeft"), Some("A"), Some("ArrowRight"), Some("D")]);
+        controls.preset = ControlPreset::ArrowsOnly;
+        assert_eq!(player_keys(&controls), vec![Some("ArrowUp"), None, Some("ArrowLeft"), None, Some("ArrowRight"), None]);
+        let bindings = KeyBindings::from_controls(&controls);
+        assert_eq!(bindings.key(LogicalInput::P1Fire), Some("ArrowUp"));
+        assert_eq!(bindings.key(LogicalInput::P2Fire), None);
+        assert_eq!(bindings.key(LogicalInput::Coin), Some("Space"));
+        for preset in ControlPreset::ALL {
+            let controls = ControlSettings { preset, ..ControlSettings::default() };
+            let manager = HotkeyManager::new(&ActionRegistry::new(), &KeyBindings::from_controls(&controls), &[]);
+            assert!(manager.conflicts().is_empty(), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn test_swap_players() {
+        let mut controls = ControlSettings::default();
+        controls.swap_players();
+        assert_eq!(player_keys(&controls), vec![Some("ArrowUp"), Some("W"), Some("ArrowLeft"), Some("A"), Some("ArrowRight"), Some("D")]);
+        assert_eq!(controls.key(LogicalInput::P1Start), Some("1"));
+        /* Swapping back lands on the preset's own keys, leaving no overrides behind */
+        controls.swap_players();
+        assert_eq!(controls, ControlSettings::default());
+
+        controls.preset = ControlPreset::ArrowsOnly;
+        controls.swap_players();
+        assert_eq!(player_keys(&controls), vec![None, Some("ArrowUp"), None, Some("ArrowLeft"), None, Some("ArrowRight")]);
+    }
+
+    #[test]
+    fn test_override_survives_preset_switch() {
+        let mut controls = ControlSettings::default();
+        controls.bind(LogicalInput::P1Fire, Some("S".to_string()));
+        assert_eq!(controls.key(LogicalInput::P1Fire), Some("S"));
+        controls.preset = ControlPreset::LeftHanded;
+        assert_eq!(controls.key(LogicalInput::P1Fire), Some("ArrowUp"));
+        controls.preset = ControlPreset::RightHanded;
+        assert_eq!(controls.key(LogicalInput::P1Fire), Some("S"));
+        assert_eq!(KeyBindings::from_controls(&controls).key(LogicalInput::P1Fire), Some("S"));
+        controls.reset();
+        assert_eq!(controls.key(LogicalInput::P1Fire), Some("W"));
+        assert!(controls.overrides.is_empty());
+    }
+
+    #[test]
+    fn test_chord_display() {
+        assert_eq!(Chord::from(Shortcut::ctrl_shift("P")).to_string(), "Ctrl+Shift+P");
+        assert_eq!(Chord::key("W").to_string(), "W");
+    }
+}
@@ -0,0 +1,150 @@
+use eframe::egui::*;
+use std::time::{Duration, Instant};
+use crate::debug_budget::BudgetShare;
+use crate::hotkeys::{Focus, KeyBindings};
+use crate::input_log::InputLog;
+use crate::machine::{DipSwitches, InputState, LogicalInput};
+
+/* Every key egui 0.24 knows, to turn the names saved by Key::name back into keys */
+const KEYS: [Key; 73] = [
+    Key::ArrowDown, Key::ArrowLeft, Key::ArrowRight, Key::ArrowUp, Key::Escape, Key::Tab, Key::Backspace,
+    Key::Enter, Key::Space, Key::Insert, Key::Delete, Key::Home, Key::End, Key::PageUp, Key::PageDown,
+    Key::Minus, Key::PlusEquals, Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6,
+    Key::Num7, Key::Num8, Key::Num9, Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V,
+    Key::W, Key::X, Key::Y, Key::Z, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+    Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15, Key::F16, Key::F17, Key::F18,
+    Key::F19, Key::F20,
+];
+
+/* Key::name in reverse; bindings are stored by name */
+pub fn key_from_name(name: &str) -> Option<Key> {
+    return KEYS.iter().copied().find(|key| key.name() == name);
+}
+
+pub struct InputManager {
+    last_input: Instant,
+    received_input: bool,
+    log: InputLog,
+    /* Controls whose key went down during the last read, and when it was seen */
+    presses: Vec<(LogicalInput, Instant)>,
+}
+
+impl InputManager {
+    pub fn new() -> Self {
+        Self {
+            last_input: Instant::now(),
+            received_input: false,
+            log: InputLog::new(),
+            presses: vec![],
+        }
+    }
+
+    /* Reports what is held right now; pulse shaping per input kind happens once per emulated frame.
+       Cabinet controls only reach the game while it has keyboard focus. frame is the emulated frame
+       counter, used to spot presses released before the game could sample them. pads is what the gamepads
+       hold; nothing is typed into a pad, so it reaches the game whatever has focus */
+    pub fn read(&mut self, ctx: &egui::Context, dip_switches: DipSwitches, bindings: &KeyBindings, focus: Focus, frame: u64, stretch_short_presses: bool, pads: &[LogicalInput]) -> InputState {
+        let key_event = ctx.input(|i| i.events.iter().any(|e| matches!(e, Event::Key { pressed: true, .. })));
+        if key_event {
+            self.note_input(Instant::now());
+        } else {
+            self.received_input = false;
+        }
+
+        if !pads.is_empty() {
+            self.note_input(Instant::now());
+        }
+
+        let mut input = InputState::with_dip_switches(dip_switches);
+        for logical in pads {
+            input.set(*logical, true);
+        }
+        self.presses.clear();
+        if focus != Focus::Game {
+            return self.log.update(frame, &input, &[], stretch_short_presses);
+        }
+        let mut taps: Vec<LogicalInput> = vec![];
+        for (logical, name) in bindings.all() {
+            if let Some(key) = key_from_name(name) {
+                input.set(*logical, input.get(*logical) || ctx.input(|i| i.key_down(key)));
+                /* Pressed and released within one UI update: key_down never saw it */
+                let edge = |down: bool| ctx.input(|i| i.events.iter().any(|e| matches!(e, Event::Key { key: k, pressed, .. } if *k == key && *pressed == down)));
+                if edge(true) && edge(false) {
+                    taps.push(*logical);
+                }
+                if ctx.input(|i| i.events.iter().any(|e| matches!(e, Event::Key { key: k, pressed: true, repeat: false, .. } if *k == key))) {
+                    self.presses.push((*logical, Instant::now()));
+                }
+            }
+        }
+        return self.log.update(frame, &input, &taps, stretch_short_presses);
+    }
+
+    /* A hold-to-use key such as turbo; like the cabinet controls it only counts while the game has focus.
+       Bound without modifiers, so Shift+Tab still reaches the menus when turbo is on Tab */
+    pub fn held(&self, ctx: &egui::Context, name: Option<&str>, focus: Focus) -> bool {
+        if focus != Focus::Game {
+            return false;
+        }
+        return name.and_then(key_from_name).map_or(false, |key| ctx.input(|i| i.key_down(key) && !i.modifiers.shift && !i.modifiers.command));
+    }
+
+    pub fn set_log_budget(&mut self, budget: BudgetShare) {
+        self.log.set_budget(budget);
+    }
+
+    pub fn log(&self) -> &InputLog {
+        return &self.log;
+    }
+
+    pub fn pressed_at(&self, input: LogicalInput) -> Option<Instant> {
+        return self.presses.iter().find(|(pressed, _)| *pressed == input).map(|(_, at)| *at);
+    }
+
+    pub fn note_input(&mut self, now: Instant) {
+        self.last_input = now;
+        self.received_input = true;
+    }
+
+    pub fn received_input(&self) -> bool {
+        return self.received_input;
+    }
+
+    pub fn idle_for(&self, now: Instant) -> Duration {
+        return now.saturating_duration_since(self.last_input);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_for() {
+        let mut input = InputManager::new();
+        let start = Instant::now();
+        input.note_input(start);
+        assert_eq!(input.idle_for(start + Duration::from_secs(90)), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_note_input_resets_idle() {
+        let mut input = InputManager::new();
+        let start = Instant::now();
+        input.note_input(start);
+        input.note_input(start + Duration::from_secs(60));
+        assert_eq!(input.received_input(), true);
+        assert_eq!(input.idle_for(start + Duration::from_secs(90)), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_key_from_name_round_trips() {
+        for key in KEYS {
+            assert_eq!(key_from_name(key.name()), Some(key));
+        }
+        assert_eq!(key_from_name("Down"), Some(Key::ArrowDown));
+        assert_eq!(key_from_name("Plus"), Some(Key::PlusEquals));
+        assert_eq!(key_from_name("NoSuchKey"), None);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+use crate::debug_budget::{self, BudgetShare};
+use crate::machine::{InputState, LogicalInput};
+
+pub const LOG_LENGTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transition {
+    pub frame: u64,
+    pub input: LogicalInput,
+    pub pressed: bool,
+    /* Released before the emulated frame counter moved on since the press */
+    pub short: bool,
+}
+
+/* Logical input transitions stamped with the emulated frame, and the short presses the game never saw.
+   The frame counter is published right after the emulation thread samples input and runs a frame, so
+   a press and release seen under the same count fell between two samples */
+pub struct InputLog {
+    transitions: VecDeque<Transition>,
+    raw: InputState,
+    pressed_frame: [Option<u64>; LogicalInput::ALL.len()],
+    hold_until: [Option<u64>; LogicalInput::ALL.len()],
+    dropped: u32,
+    stretched: u32,
+    budget: Option<BudgetShare>,
+}
+
+impl InputLog {
+    pub fn new() -> Self {
+        Self {
+            transitions: VecDeque::with_capacity(LOG_LENGTH),
+            raw: InputState::default(),
+            pressed_frame: [None; LogicalInput::ALL.len()],
+            hold_until: [None; LogicalInput::ALL.len()],
+            dropped: 0,
+            stretched: 0,
+            budget: None,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: BudgetShare) {
+        self.budget = Some(budget);
+    }
+
+    /* raw is what is held now and taps what was pressed and released again since the last call; returns the
+       input to hand the game, with short presses held over when stretching */
+    pub fn update(&mut self, frame: u64, raw: &InputState, taps: &[LogicalInput], stretch: bool) -> InputState {
+        let mut reported = *raw;
+        for (index, logical) in LogicalInput::ALL.iter().enumerate() {
+            let held = raw.get(*logical);
+            let was_held = self.raw.get(*logical);
+            let tapped = !held && !was_held && taps.contains(logical);
+            if (held && !was_held) || tapped {
+                self.push(Transition { frame, input: *logical, pressed: true, short: false });
+                self.pressed_frame[index] = Some(frame);
+            }
+            if (!held && was_held) || tapped {
+                self.release(index, *logical, frame, stretch);
+            }
+            match self.hold_until[index] {
+                Some(until) if frame <= until => reported.set(*logical, true),
+                Some(_) => self.hold_until[index] = None,
+                None => {},
+            }
+        }
+        self.raw = *raw;
+        return reported;
+    }
+
+    fn release(&mut self, index: usize, logical: LogicalInput, frame: u64, stretch: bool) {
+        let short = self.pressed_frame[index].take() == Some(frame);
+        self.push(Transition { frame, input: logical, pressed: false, short });
+        if !short {
+            return;
+        }
+        if stretch {
+            /* The press may have landed after this frame's sample, so hold it through the next one as well */
+            self.hold_until[index] = Some(frame + 1);
+            self.stretched += 1;
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    fn push(&mut self, transition: Transition) {
+        debug_budget::push_bounded(&mut self.transitions, transition, LOG_LENGTH, self.budget.as_ref());
+    }
+
+    pub fn transitions(&self) -> impl Iterator<Item = &Transition> {
+        return self.transitions.iter();
+    }
+
+    pub fn dropped(&self) -> u32 {
+        return self.dropped;
+    }
+
+    pub fn stretched(&self) -> u32 {
+        return self.stretched;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fire(held: bool) -> InputState {
+        return InputState { p1_fire: held, ..Default::default() };
+    }
+
+    #[test]
+    fn test_short_press_is_dropped_without_stretch() {
+        let mut log = InputLog::new();
+        assert!(log.update(10, &fire(true), &[], false).p1_fire);
+        assert!(!log.update(10, &fire(false), &[], false).p1_fire);
+        assert_eq!(log.dropped(), 1);
+        let transitions: Vec<Transition> = log.transitions().copied().collect();
+        assert_eq!(transitions, vec![
+            Transition { frame: 10, input: LogicalInput::P1Fire, pressed: true, short: false },
+            Transition { frame: 10, input: LogicalInput::P1Fire, pressed: false, short: true },
+        ]);
+    }
+
+    #[test]
+    fn test_press_across_frames_is_not_short() {
+        let mut log = InputLog::new();
+        log.update(10, &fire(true), &[], true);
+        assert!(!log.update(11, &fire(false), &[], true).p1_fire);
+        assert_eq!((log.dropped(), log.stretched()), (0, 0));
+        assert!(!log.transitions().any(|t| t.short));
+    }
+
+    #[test]
+    fn test_short_press_is_stretched_through_next_frame() {
+        let mut log = InputLog::new();
+        log.update(10, &fire(true), &[], true);
+        assert!(log.update(10, &fire(false), &[], true).p1_fire);
+        assert!(log.update(11, &fire(false), &[], true).p1_fire);
+        assert!(!log.update(12, &fire(false), &[], true).p1_fire);
+        assert!(!log.update(13, &fire(false), &[], true).p1_fire);
+        assert_eq!((log.dropped(), log.stretched()), (0, 1));
+    }
+
+    #[test]
+    fn test_tap_within_one_update() {
+        let mut log = InputLog::new();
+        assert!(!log.update(5, &fire(false), &[LogicalInput::P1Fire], false).p1_fire);
+        assert_eq!(log.dropped(), 1);
+        assert_eq!(log.transitions().count(), 2);
+
+        let mut log = InputLog::new();
+        assert!(log.update(5, &fire(false), &[LogicalInput::P1Fire], true).p1_fire);
+        assert_eq!(log.stretched(), 1);
+        /* A tap reported while the key is still down again is just a press */
+        let mut log = InputLog::new();
+        assert!(log.update(5, &fire(true), &[LogicalInput::P1Fire], false).p1_fire);
+        assert_eq!((log.dropped(), log.transitions().count()), (0, 1));
+    }
+
+    #[test]
+    fn test_log_keeps_recent_transitions() {
+        let mut log = InputLog::new();
+        for frame in 0..LOG_LENGTH as u64 {
+            log.update(frame * 2, &fire(true), &[], false);
+            log.update(frame * 2 + 1, &fire(false), &[], false);
+        }
+        assert_eq!(log.transitions().count(), LOG_LENGTH);
+        assert_eq!(log.transitions().last().unwrap().frame, LOG_LENGTH as u64 * 2 - 1);
+        assert_eq!(log.dropped(), 0);
+    }
+}
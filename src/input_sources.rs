@@ -0,0 +1,178 @@
+// What the game is being played with: the keyboard, always, and whatever gamepads the host reports. Pads
+// take the free player slots in the order they connect; when player 1's pad goes away player 2's moves up,
+// and a pad that connected while both slots were taken fills the one left over.
+use crate::strings::Message;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    const ALL: [Player; 2] = [Player::One, Player::Two];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Player::One => "P1",
+            Player::Two => "P2",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceKind {
+    Keyboard,
+    /* The host's id for the pad, stable while it stays connected */
+    Gamepad(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputSource {
+    pub kind: SourceKind,
+    /* As the host names the device */
+    pub name: String,
+    pub player: Option<Player>,
+}
+
+impl InputSource {
+    /* The keyboard drives both players through the bindings, so it isn't assigned one */
+    pub fn keyboard(name: &str) -> Self {
+        Self { kind: SourceKind::Keyboard, name: name.to_string(), player: None }
+    }
+
+    /* "Keyboard", "Xbox Controller #1 → P1"; a pad without a slot has no arrow */
+    pub fn label(&self) -> String {
+        let name = match self.kind {
+            SourceKind::Keyboard => self.name.clone(),
+            SourceKind::Gamepad(id) => format!("{} #{}", self.name, id + 1),
+        };
+        match self.player {
+            Some(player) => format!("{} → {}", name, player.label()),
+            None => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+pub enum SourceEvent {
+    Connected { id: usize, name: String },
+    Disconnected { id: usize },
+}
+
+#[derive(Default)]
+pub struct PadAssignments {
+    /* In connection order */
+    pads: Vec<InputSource>,
+}
+
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+impl PadAssignments {
+    pub fn new() -> Self {
+        Self { pads: vec![] }
+    }
+
+    pub fn pads(&self) -> &[InputSource] {
+        return &self.pads;
+    }
+
+    pub fn player_of(&self, id: usize) -> Option<Player> {
+        return self.pads.iter().find(|pad| pad.kind == SourceKind::Gamepad(id)).and_then(|pad| pad.player);
+    }
+
+    /* Returns a notice for every pad that changed player because another went away */
+    pub fn apply(&mut self, event: SourceEvent) -> Vec<Message> {
+        match event {
+            SourceEvent::Connected { id, name } => {
+                /* Hosts repeat the connection for pads already open at startup */
+                if !self.pads.iter().any(|pad| pad.kind == SourceKind::Gamepad(id)) {
+                    let player = self.free_player();
+                    self.pads.push(InputSource { kind: SourceKind::Gamepad(id), name, player });
+                }
+                return vec![];
+            },
+            SourceEvent::Disconnected { id } => {
+                let Some(index) = self.pads.iter().position(|pad| pad.kind == SourceKind::Gamepad(id)) else {
+                    return vec![];
+                };
+                let gone = self.pads.remove(index);
+                let mut notices = vec![];
+                if gone.player == Some(Player::One) {
+                    if let Some(pad) = self.pads.iter_mut().find(|pad| pad.player == Some(Player::Two)) {
+                        pad.player = Some(Player::One);
+                        notices.push(Message::new("input.pad_reassigned", vec![pad.label()]));
+                    }
+                }
+                while let Some(player) = self.free_player() {
+                    let Some(pad) = self.pads.iter_mut().find(|pad| pad.player.is_none()) else {
+                        break;
+                    };
+                    pad.player = Some(player);
+                    notices.push(Message::new("input.pad_reassigned", vec![pad.label()]));
+                }
+                return notices;
+            },
+        }
+    }
+
+    fn free_player(&self) -> Option<Player> {
+        return Player::ALL.into_iter().find(|player| !self.pads.iter().any(|pad| pad.player == Some(*player)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(assignments: &mut PadAssignments, id: usize, name: &str) -> Vec<Message> {
+        return assignments.apply(SourceEvent::Connected { id, name: name.to_string() });
+    }
+
+    fn labels(assignments: &PadAssignments) -> Vec<String> {
+        return assignments.pads().iter().map(InputSource::label).collect();
+    }
+
+    #[test]
+    fn test_pads_take_slots_in_connection_order() {
+        let mut assignments = PadAssignments::new();
+        assert!(connect(&mut assignments, 0, "Xbox Controller").is_empty());
+        connect(&mut assignments, 1, "DualShock");
+        connect(&mut assignments, 2, "Arcade Stick");
+        /* A repeated connection changes nothing */
+        connect(&mut assignments, 0, "Xbox Controller");
+        assert_eq!(labels(&assignments), ["Xbox Controller #1 → P1", "DualShock #2 → P2", "Arcade Stick #3"]);
+        assert_eq!((assignments.player_of(1), assignments.player_of(2), assignments.player_of(9)), (Some(Player::Two), None, None));
+        assert_eq!(InputSource::keyboard("Keyboard").label(), "Keyboard");
+    }
+
+    #[test]
+    fn test_player_2_moves_up_when_player_1_disconnects() {
+        let mut assignments = PadAssignments::new();
+        connect(&mut assignments, 0, "Xbox Controller");
+        connect(&mut assignments, 1, "DualShock");
+        let notices = assignments.apply(SourceEvent::Disconnected { id: 0 });
+        assert_eq!(notices, [Message::new("input.pad_reassigned", vec!["DualShock #2 → P1".to_string()])]);
+        assert_eq!(labels(&assignments), ["DualShock #2 → P1"]);
+        /* Coming back, the first pad takes the free slot rather than its old one */
+        connect(&mut assignments, 0, "Xbox Controller");
+        assert_eq!(labels(&assignments), ["DualShock #2 → P1", "Xbox Controller #1 → P2"]);
+    }
+
+    #[test]
+    fn test_spare_pad_fills_a_vacated_slot() {
+        let mut assignments = PadAssignments::new();
+        connect(&mut assignments, 0, "A");
+        connect(&mut assignments, 1, "B");
+        connect(&mut assignments, 2, "C");
+        let notices = assignments.apply(SourceEvent::Disconnected { id: 0 });
+        let args: Vec<Vec<String>> = notices.iter().map(|notice| notice.args.clone()).collect();
+        assert_eq!(args, [vec!["B #2 → P1".to_string()], vec!["C #3 → P2".to_string()]]);
+        /* A pad that finds both slots taken waits for one */
+        connect(&mut assignments, 3, "D");
+        assert_eq!(assignments.player_of(3), None);
+        assert_eq!(assignments.apply(SourceEvent::Disconnected { id: 2 }).len(), 1);
+        assert_eq!(labels(&assignments), ["B #2 → P1", "D #4 → P2"]);
+        assert!(assignments.apply(SourceEvent::Disconnected { id: 2 }).is_empty());
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+use crate::debug_budget::{self, BudgetShare};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IoLogEntry {
+    pub frame: u64,
+    pub port: u8,
+    pub value: u8,
+    pub shift_register: u16,
+}
+
+pub struct IoLog {
+    entries: VecDeque<IoLogEntry>,
+    capacity: usize,
+    budget: Option<BudgetShare>,
+}
+
+impl IoLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            budget: None,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: BudgetShare) {
+        self.budget = Some(budget);
+    }
+
+    pub fn push(&mut self, entry: IoLogEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        debug_budget::push_bounded(&mut self.entries, entry, self.capacity, self.budget.as_ref());
+    }
+
+    #[cfg(test)]
+    pub fn entries(&self) -> impl Iterator<Item = &IoLogEntry> {
+        self.entries.iter()
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(port: u8) -> IoLogEntry {
+        IoLogEntry { frame: 0, port, value: 0, shift_register: 0 }
+    }
+
+    #[test]
+    fn test_push() {
+        let mut log = IoLog::new(4);
+        log.push(entry(2));
+        assert_eq!(log.len(), 1);
+        assert_eq!(log.entries().next(), Some(&entry(2)));
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let mut log = IoLog::new(2);
+        log.push(entry(2));
+        log.push(entry(3));
+        log.push(entry(4));
+        let ports: Vec<u8> = log.entries().map(|e| e.port).collect();
+        assert_eq!(ports, vec![3, 4]);
+    }
+}
@@ -0,0 +1,112 @@
+/* Keyboard handling for the "press a key" binding rows. Tab is never captured so focus traversal keeps
+   working, Escape cancels, and focus goes back to the row's button when capture ends. */
+#[derive(Default)]
+pub struct KeyCapture {
+    capturing: Option<usize>,
+    refocus: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CaptureOutcome {
+    Bound(usize, String),
+    Cancelled,
+    /* Not consumed, e.g. Tab moving focus on to the next widget */
+    PassThrough,
+}
+
+impl KeyCapture {
+    pub fn start(&mut self, row: usize) {
+        self.capturing = Some(row);
+        self.refocus = None;
+    }
+
+    pub fn capturing(&self) -> Option<usize> {
+        return self.capturing;
+    }
+
+    /* Handles a key press by its egui name while capturing */
+    pub fn handle_key(&mut self, key: &str) -> CaptureOutcome {
+        let row = match self.capturing {
+            Some(row) => row,
+            None => return CaptureOutcome::PassThrough,
+        };
+        match key {
+            "Tab" => {
+                self.capturing = None;
+                CaptureOutcome::PassThrough
+            },
+            "Escape" => {
+                self.finish(row);
+                CaptureOutcome::Cancelled
+            },
+            _ => {
+                self.finish(row);
+                CaptureOutcome::Bound(row, key.to_string())
+            },
+        }
+    }
+
+    /* A row was removed; forget it and shift later rows down */
+    pub fn remove_row(&mut self, row: usize) {
+        let shift = |current: Option<usize>| match current {
+            Some(current) if current == row => None,
+            Some(current) if current > row => Some(current - 1),
+            other => other,
+        };
+        self.capturing = shift(self.capturing);
+        self.refocus = shift(self.refocus);
+    }
+
+    /* The row whose button should take keyboard focus this frame, if any */
+    pub fn take_refocus(&mut self) -> Option<usize> {
+        return self.refocus.take();
+    }
+
+    fn finish(&mut self, row: usize) {
+        self.capturing = None;
+        self.refocus = Some(row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binds_and_returns_focus() {
+        let mut capture = KeyCapture::default();
+        assert_eq!(capture.handle_key("F"), CaptureOutcome::PassThrough);
+        capture.start(2);
+        assert_eq!(capture.handle_key("F"), CaptureOutcome::Bound(2, "F".to_string()));
+        assert_eq!(capture.capturing(), None);
+        assert_eq!(capture.take_refocus(), Some(2));
+        assert_eq!(capture.take_refocus(), None);
+    }
+
+    #[test]
+    fn test_escape_cancels_and_returns_focus() {
+        let mut capture = KeyCapture::default();
+        capture.start(0);
+        assert_eq!(capture.handle_key("Escape"), CaptureOutcome::Cancelled);
+        assert_eq!(capture.take_refocus(), Some(0));
+    }
+
+    #[test]
+    fn test_tab_moves_focus_on() {
+        let mut capture = KeyCapture::default();
+        capture.start(1);
+        assert_eq!(capture.handle_key("Tab"), CaptureOutcome::PassThrough);
+        assert_eq!(capture.capturing(), None);
+        assert_eq!(capture.take_refocus(), None);
+    }
+
+    #[test]
+    fn test_removed_rows() {
+        let mut capture = KeyCapture::default();
+        capture.start(3);
+        capture.remove_row(1);
+        assert_eq!(capture.capturing(), Some(2));
+        capture.remove_row(2);
+        assert_eq!(capture.capturing(), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/* Measurements averaged over */
+pub const WINDOW: usize = 16;
+/* A press the game ignores (a shot already in flight, or no game running) is dropped after this */
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub key_to_emulation: Duration,
+    pub key_to_present: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Averages {
+    pub key_to_emulation: Duration,
+    pub key_to_present: Duration,
+    pub samples: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Pending {
+    pressed: Instant,
+    /* When the emulation first saw the shot, and the VBlank ending that frame */
+    shot: Option<(Instant, Option<Instant>)>,
+}
+
+/* Input-to-photon latency as far as software can see it, one fire press at a time: the UI thread
+   reports the press and each frame it presents, the emulation thread reports the player shot status
+   after every frame */
+pub struct LatencyMeter {
+    pending: Option<Pending>,
+    shot_active: bool,
+    samples: VecDeque<Sample>,
+}
+
+impl LatencyMeter {
+    pub fn new() -> Self {
+        Self { pending: None, shot_active: false, samples: VecDeque::new() }
+    }
+
+    /* A press while another is still being followed is ignored, unless that one has timed out */
+    pub fn key_down(&mut self, at: Instant) {
+        match self.pending {
+            Some(pending) if at.saturating_duration_since(pending.pressed) < TIMEOUT => {},
+            _ => self.pending = Some(Pending { pressed: at, shot: None }),
+        }
+    }
+
+    /* Player shot status read after an emulated frame; only a shot starting counts */
+    pub fn watch_shot(&mut self, status: u8, at: Instant, vblank: Option<Instant>) {
+        let started = status != 0 && !self.shot_active;
+        self.shot_active = status != 0;
+        if !started {
+            return;
+        }
+        if let Some(pending) = self.pending.as_mut() {
+            if pending.shot.is_none() {
+                if at.saturating_duration_since(pending.pressed) < TIMEOUT {
+                    pending.shot = Some((at, vblank));
+                } else {
+                    self.pending = None;
+                }
+            }
+        }
+    }
+
+    /* The UI presented a frame; vblank is the one it was published after */
+    pub fn presented(&mut self, at: Instant, vblank: Option<Instant>) {
+        let Some(pending) = self.pending else {
+            return;
+        };
+        let Some((seen, shot_vblank)) = pending.shot else {
+            return;
+        };
+        /* An older frame, published before the one with the shot */
+        if vblank < shot_vblank {
+            return;
+        }
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            key_to_emulation: seen.saturating_duration_since(pending.pressed),
+            key_to_present: at.saturating_duration_since(pending.pressed),
+        });
+        self.pending = None;
+    }
+
+    pub fn averages(&self) -> Option<Averages> {
+        let samples = self.samples.len();
+        if samples == 0 {
+            return None;
+        }
+        let key_to_emulation = self.samples.iter().map(|sample| sample.key_to_emulation).sum::<Duration>() / samples as u32;
+        let key_to_present = self.samples.iter().map(|sample| sample.key_to_present).sum::<Duration>() / samples as u32;
+        return Some(Averages { key_to_emulation, key_to_present, samples });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        return Duration::from_millis(millis);
+    }
+
+    /* Press at t+press, shot seen at t+seen in the frame ending at vblank, presented at t+present */
+    fn measure(meter: &mut LatencyMeter, t: Instant, press: u64, seen: u64, present: u64) {
+        meter.key_down(t + ms(press));
+        meter.watch_shot(0, t + ms(press + 1), Some(t + ms(press)));
+        meter.watch_shot(1, t + ms(seen), Some(t + ms(seen)));
+        meter.presented(t + ms(present), Some(t + ms(seen)));
+        meter.watch_shot(0, t + ms(present + 1), Some(t + ms(present)));
+    }
+
+    #[test]
+    fn test_single_measurement() {
+        let t = Instant::now();
+        let mut meter = LatencyMeter::new();
+        assert_eq!(meter.averages(), None);
+        measure(&mut meter, t, 0, 20, 30);
+        assert_eq!(meter.averages(), Some(Averages { key_to_emulation: ms(20), key_to_present: ms(30), samples: 1 }));
+    }
+
+    #[test]
+    fn test_rolling_average() {
+        let t = Instant::now();
+        let mut meter = LatencyMeter::new();
+        measure(&mut meter, t, 0, 10, 20);
+        measure(&mut meter, t, 1_000, 1_030, 1_040);
+        assert_eq!(meter.averages(), Some(Averages { key_to_emulation: ms(20), key_to_present: ms(30), samples: 2 }));
+        for i in 0..WINDOW as u64 {
+            measure(&mut meter, t, 2_000 + i * 1_000, 2_016 + i * 1_000, 2_032 + i * 1_000);
+        }
+        /* The first two have rolled out of the window */
+        assert_eq!(meter.averages(), Some(Averages { key_to_emulation: ms(16), key_to_present: ms(32), samples: WINDOW }));
+    }
+
+    #[test]
+    fn test_stale_frame_and_shot_in_flight() {
+        let t = Instant::now();
+        let mut meter = LatencyMeter::new();
+        /* A shot already moving when the key goes down doesn't count, nor does a second press */
+        meter.watch_shot(2, t, Some(t));
+        meter.key_down(t + ms(5));
+        meter.key_down(t + ms(8));
+        meter.watch_shot(2, t + ms(16), Some(t + ms(16)));
+        meter.presented(t + ms(20), Some(t + ms(16)));
+        assert_eq!(meter.averages(), None);
+        meter.watch_shot(0, t + ms(33), Some(t + ms(33)));
+        meter.watch_shot(1, t + ms(50), Some(t + ms(50)));
+        /* A frame published before the shot's */
+        meter.presented(t + ms(52), Some(t + ms(33)));
+        assert_eq!(meter.averages(), None);
+        meter.presented(t + ms(60), Some(t + ms(50)));
+        assert_eq!(meter.averages(), Some(Averages { key_to_emulation: ms(45), key_to_present: ms(55), samples: 1 }));
+    }
+
+    #[test]
+    fn test_ignored_press_times_out() {
+        let t = Instant::now();
+        let mut meter = LatencyMeter::new();
+        meter.key_down(t);
+        meter.watch_shot(0, t + ms(16), None);
+        /* Long after: the game started a shot on its own, e.g. in the attract mode */
+        meter.watch_shot(1, t + TIMEOUT + ms(16), None);
+        meter.presented(t + TIMEOUT + ms(20), None);
+        assert_eq!(meter.averages(), None);
+        meter.watch_shot(0, t + ms(2_000), None);
+        measure(&mut meter, t, 3_000, 3_016, 3_030);
+        assert_eq!(meter.averages().map(|averages| averages.samples), Some(1));
+    }
+}
@@ -0,0 +1,234 @@
+// Annotated disassembly of a whole ROM: symbols as labels, DB lines for data, and cross-references from
+// a static scan of CALL and JMP operands. The listing is text for reading, not for reassembling.
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use intel8080::disassembler;
+use intel8080::instruction::OPCODES;
+use crate::coverage::{ByteClass, CoverageMap};
+use crate::symbols::SymbolTable;
+
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Clone, Copy, Default)]
+pub struct ListingOptions<'a> {
+    /* Labels, and the DATA ranges from the symbol file */
+    pub symbols: Option<&'a SymbolTable>,
+    /* Bytes the program only ever read as data are listed as data */
+    pub coverage: Option<&'a CoverageMap>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryKind {
+    Code(String),
+    Data,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub kind: EntryKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reference {
+    Call,
+    Jump,
+}
+
+/* Every instruction or data run in address order */
+pub struct Listing<'a> {
+    rom: &'a [u8],
+    options: ListingOptions<'a>,
+    addr: usize,
+}
+
+impl<'a> Listing<'a> {
+    pub fn new(rom: &'a [u8], options: ListingOptions<'a>) -> Self {
+        Self { rom, options, addr: 0 }
+    }
+
+    fn is_data(&self, addr: usize) -> bool {
+        let declared = self.options.symbols.map_or(false, |table| table.is_data(addr as u16));
+        let read = self.options.coverage.map_or(false, |map| map.classes().get(addr) == Some(&ByteClass::Data));
+        return declared || read;
+    }
+
+    fn is_label(&self, addr: usize) -> bool {
+        return self.options.symbols.map_or(false, |table| table.name_at(addr as u16).is_some());
+    }
+
+    fn data(&mut self, start: usize, end: usize) -> Entry {
+        self.addr = end;
+        return Entry { address: start as u16, bytes: self.rom[start..end].to_vec(), kind: EntryKind::Data };
+    }
+}
+
+impl<'a> Iterator for Listing<'a> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        let start = self.addr;
+        if start >= self.rom.len() {
+            return None;
+        }
+        if self.is_data(start) {
+            /* A label starts a new line so it can be printed above its byte */
+            let mut end = start + 1;
+            while end < self.rom.len() && end - start < DATA_PER_LINE && self.is_data(end) && !self.is_label(end) {
+                end += 1;
+            }
+            return Some(self.data(start, end));
+        }
+        let bytes = [0, 1, 2].map(|i| self.rom.get(start + i).copied().unwrap_or(0));
+        let (instruction, length) = disassembler::disassemble(bytes);
+        let end = start + length.max(1);
+        /* An instruction cut off by the end of the ROM or running into data is really a stray byte */
+        if end > self.rom.len() || (start + 1..end).any(|addr| self.is_data(addr)) {
+            return Some(self.data(start, start + 1));
+        }
+        self.addr = end;
+        return Some(Entry { address: start as u16, bytes: self.rom[start..end].to_vec(), kind: EntryKind::Code(instruction) });
+    }
+}
+
+/* Branch target -> (kind, source) for every CALL, JMP and conditional form whose target is in the ROM */
+pub fn cross_references(rom: &[u8], options: ListingOptions) -> BTreeMap<u16, Vec<(Reference, u16)>> {
+    let mut references: BTreeMap<u16, Vec<(Reference, u16)>> = BTreeMap::new();
+    for entry in Listing::new(rom, options) {
+        if !matches!(entry.kind, EntryKind::Code(_)) || entry.bytes.len() != 3 {
+            continue;
+        }
+        let label = OPCODES[entry.bytes[0] as usize].label.trim_start_matches('*');
+        let reference = match label.strip_suffix("_ADR").and_then(|mnemonic| mnemonic.chars().next()) {
+            Some('C') => Reference::Call,
+            Some('J') => Reference::Jump,
+            _ => continue,
+        };
+        let target = u16::from_le_bytes([entry.bytes[1], entry.bytes[2]]);
+        if (target as usize) < rom.len() {
+            references.entry(target).or_default().push((reference, entry.address));
+        }
+    }
+    return references;
+}
+
+pub fn export_listing(writer: &mut impl Write, rom: &[u8], options: ListingOptions) -> io::Result<()> {
+    return export_listing_with_progress(writer, rom, options, |_| true).map(|_| ());
+}
+
+/* Calls progress with the fraction of the ROM written after each entry and stops, returning Ok(false), as soon as it does */
+pub fn export_listing_with_progress(writer: &mut impl Write, rom: &[u8], options: ListingOptions, mut progress: impl FnMut(f32) -> bool) -> io::Result<bool> {
+    let references = cross_references(rom, options);
+    let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ");
+    for entry in Listing::new(rom, options) {
+        if let Some(name) = options.symbols.and_then(|table| table.name_at(entry.address)) {
+            writeln!(writer, "{}:", name)?;
+        }
+        for (reference, comment) in [(Reference::Call, "called from"), (Reference::Jump, "jumped to from")] {
+            let sources: Vec<String> = references.get(&entry.address).into_iter().flatten()
+                .filter(|(kind, _)| *kind == reference)
+                .map(|(_, source)| format!("${:04X}", source))
+                .collect();
+            if !sources.is_empty() {
+                writeln!(writer, "  ; {} {}", comment, sources.join(", "))?;
+            }
+        }
+        match &entry.kind {
+            EntryKind::Code(instruction) => writeln!(writer, "  ${:04X}  {:<8}  {}", entry.address, hex(&entry.bytes), instruction)?,
+            EntryKind::Data => {
+                let values: Vec<String> = entry.bytes.iter().map(|byte| format!("${:02X}", byte)).collect();
+                writeln!(writer, "  ${:04X}  {:<8}  DB {}", entry.address, "", values.join(","))?;
+            },
+        }
+        if !progress((entry.address as usize + entry.bytes.len()) as f32 / rom.len() as f32) {
+            return Ok(false);
+        }
+    }
+    return Ok(true);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intel8080::asm;
+    use crate::emulator_core::EmulatorCore;
+    use crate::machine::{self, InputState};
+
+    const PROGRAM: &str = "
+        start:  CALL print
+                JNZ start
+                CALL print
+        idle:   JMP idle
+        print:  LXI H,text
+                RET
+        text:   DB $48,$49,$21
+    ";
+
+    #[test]
+    fn test_golden_listing() {
+        let rom = asm::assemble(PROGRAM).unwrap();
+        let table = SymbolTable::parse("0000 Start\n000C Print\n0010 Text\nDATA 0010 0012\n").unwrap();
+        let mut out = vec![];
+        export_listing(&mut out, &rom, ListingOptions { symbols: Some(&table), coverage: None }).unwrap();
+        let expected = "\
+Start:
+  ; jumped to from $0003
+  $0000  CD 0C 00  CALL $000C
+  $0003  C2 00 00  JNZ $0000
+  $0006  CD 0C 00  CALL $000C
+  ; jumped to from $0009
+  $0009  C3 09 00  JMP $0009
+Print:
+  ; called from $0000, $0006
+  $000C  21 10 00  LXI H,$0010
+  $000F  C9        RET
+Text:
+  $0010            DB $48,$49,$21
+";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_progress_stops_the_export() {
+        let rom = asm::assemble(PROGRAM).unwrap();
+        let mut fractions = vec![];
+        let mut out = vec![];
+        let finished = export_listing_with_progress(&mut out, &rom, ListingOptions::default(), |fraction| {
+            fractions.push(fraction);
+            return fractions.len() < 2;
+        }).unwrap();
+        assert!(!finished);
+        /* CALL and JNZ, three bytes each, of nineteen */
+        assert_eq!(fractions, [3.0 / 19.0, 6.0 / 19.0]);
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_entries_split_at_labels_and_the_end() {
+        let table = SymbolTable::parse("0002 Second\nDATA 0000 0003\n").unwrap();
+        /* The LXI at $0004 would read past the end of the ROM */
+        let rom = [1, 2, 3, 4, 0x21, 0x00];
+        let entries: Vec<Entry> = Listing::new(&rom, ListingOptions { symbols: Some(&table), coverage: None }).collect();
+        let bytes: Vec<&[u8]> = entries.iter().map(|entry| entry.bytes.as_slice()).collect();
+        assert_eq!(bytes, vec![&[1, 2][..], &[3, 4], &[0x21], &[0x00]]);
+        assert_eq!(entries[3].kind, EntryKind::Code("NOP".to_string()));
+        assert!(entries[..3].iter().all(|entry| entry.kind == EntryKind::Data));
+    }
+
+    #[test]
+    fn test_coverage_marks_tables_as_data() {
+        let program = asm::assemble("LXI H,table\nMOV A,M\nidle: JMP idle\ntable: DB $3E,$01\n").unwrap();
+        let mut rom = [0; machine::ROM_SIZE];
+        rom[..program.len()].copy_from_slice(&program);
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.enable_coverage();
+        core.step_frame();
+        let map = core.coverage().unwrap();
+        let entries: Vec<Entry> = Listing::new(&program, ListingOptions { symbols: None, coverage: Some(&map) }).collect();
+        /* Only the first table byte is read, so only it becomes data */
+        assert_eq!(entries[3], Entry { address: 7, bytes: vec![0x3E], kind: EntryKind::Data });
+        assert_eq!(entries[4], Entry { address: 8, bytes: vec![0x01], kind: EntryKind::Data });
+        assert_eq!(entries.len(), 5);
+    }
+}
@@ -0,0 +1,176 @@
+// Compare mode: two cores fed identical input, frame by frame, until their RAM first differs.
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::emulator_core::{EmulatorCore, Timing};
+use crate::machine::{self, InputShaper, InputState};
+use crate::save_slots;
+
+/* One side of a comparison, read from a TOML file:
+
+       state = "slot_1.sav"        # optional, start from this save slot instead of power-on
+       [timing]
+       cycles_per_frame = 33333
+       mid_screen_cycle = 16667 */
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CoreVariant {
+    pub timing: Timing,
+    pub state: Option<PathBuf>,
+}
+
+impl CoreVariant {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let mut variant: CoreVariant = toml::from_str(&text).map_err(|error| error.to_string())?;
+        /* A relative state path is next to the variant file */
+        if let (Some(state), Some(dir)) = (&variant.state, path.parent()) {
+            variant.state = Some(dir.join(state));
+        }
+        return Ok(variant);
+    }
+
+    pub fn build(&self, rom: [u8; machine::ROM_SIZE]) -> Result<EmulatorCore, String> {
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.set_timing(self.timing);
+        if let Some(path) = &self.state {
+            let bytes = std::fs::read(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+            let slot = save_slots::decode(&bytes, core.rom_checksum()).map_err(|error| format!("{}: {}", path.display(), error))?;
+            if slot.rom_mismatch {
+                return Err(format!("{}: saved from a different ROM", path.display()));
+            }
+            core.load_state(&slot.state);
+        }
+        return Ok(core);
+    }
+}
+
+pub struct Lockstep {
+    cores: [EmulatorCore; 2],
+    shaper: InputShaper,
+    steps: u64,
+    divergence: Option<u64>,
+}
+
+impl Lockstep {
+    pub fn new(left: EmulatorCore, right: EmulatorCore) -> Self {
+        Self {
+            cores: [left, right],
+            shaper: InputShaper::default(),
+            steps: 0,
+            divergence: None,
+        }
+    }
+
+    /* Steps both cores one frame with the same shaped input; does nothing once they have diverged */
+    pub fn step(&mut self, raw: &InputState) -> bool {
+        if self.divergence.is_some() {
+            return false;
+        }
+        let input = self.shaper.shape(raw);
+        for core in self.cores.iter_mut() {
+            core.set_input(input);
+            core.step_frame();
+        }
+        self.steps += 1;
+        if !self.equal() {
+            self.divergence = Some(self.steps);
+        }
+        return true;
+    }
+
+    pub fn equal(&self) -> bool {
+        let [left, right] = self.checksums();
+        return left == right;
+    }
+
+    pub fn checksums(&self) -> [u64; 2] {
+        return [self.cores[0].ram_checksum(), self.cores[1].ram_checksum()];
+    }
+
+    /* Lockstep frames run so far, counted from the start of the comparison */
+    pub fn steps(&self) -> u64 {
+        return self.steps;
+    }
+
+    /* The lockstep frame whose RAM first differed */
+    pub fn divergence(&self) -> Option<u64> {
+        return self.divergence;
+    }
+
+    pub fn cores(&self) -> &[EmulatorCore; 2] {
+        return &self.cores;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_slots::{SlotHeader, Thumbnail};
+    use crate::test_rom;
+
+    fn run(lockstep: &mut Lockstep, frames: u64) {
+        for _ in 0..frames {
+            lockstep.step(&InputState::default());
+        }
+    }
+
+    #[test]
+    fn test_identical_variants_stay_in_lockstep() {
+        let variant = CoreVariant::default();
+        let mut lockstep = Lockstep::new(variant.build(test_rom::rom()).unwrap(), variant.build(test_rom::rom()).unwrap());
+        run(&mut lockstep, 120);
+        assert_eq!(lockstep.steps(), 120);
+        assert!(lockstep.equal());
+        assert_eq!(lockstep.divergence(), None);
+        assert_eq!(lockstep.cores()[0].frame(), lockstep.cores()[1].frame());
+    }
+
+    #[test]
+    fn test_divergence_stops_both_cores() {
+        let short = CoreVariant { timing: Timing { cycles_per_frame: 30_000, ..Default::default() }, state: None };
+        let mut lockstep = Lockstep::new(CoreVariant::default().build(test_rom::rom()).unwrap(), short.build(test_rom::rom()).unwrap());
+        run(&mut lockstep, 600);
+        let divergence = lockstep.divergence().expect("shorter frames never changed RAM");
+        assert_eq!(lockstep.steps(), divergence);
+        assert!(!lockstep.equal());
+        assert!(!lockstep.step(&InputState::default()));
+        assert_eq!(lockstep.steps(), divergence);
+    }
+
+    #[test]
+    fn test_same_input_reaches_both_cores() {
+        let variant = CoreVariant::default();
+        let mut lockstep = Lockstep::new(variant.build(test_rom::rom()).unwrap(), variant.build(test_rom::rom()).unwrap());
+        let fire = InputState { p1_fire: true, coin: true, ..Default::default() };
+        for _ in 0..10 {
+            lockstep.step(&fire);
+        }
+        assert!(lockstep.equal());
+        assert_eq!(lockstep.cores()[0].ram(), lockstep.cores()[1].ram());
+    }
+
+    #[test]
+    fn test_variant_file_with_state() {
+        let dir = std::env::temp_dir().join(format!("si_lockstep_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut saved = EmulatorCore::new(test_rom::rom(), InputState::default());
+        for _ in 0..30 {
+            saved.step_frame();
+        }
+        let header = SlotHeader { timestamp: 0, rom_checksum: saved.rom_checksum(), thumbnail: Thumbnail { width: 0, height: 0, rgba: vec![] } };
+        std::fs::write(dir.join("later.sav"), save_slots::encode(&header, &saved.save_state())).unwrap();
+        std::fs::write(dir.join("later.toml"), "state = \"later.sav\"\n[timing]\nmid_screen_cycle = 16000\n").unwrap();
+
+        let variant = CoreVariant::load(&dir.join("later.toml")).unwrap();
+        assert_eq!(variant.timing, Timing { cycles_per_frame: Timing::default().cycles_per_frame, mid_screen_cycle: 16_000 });
+        assert_eq!(variant.state, Some(dir.join("later.sav")));
+        let core = variant.build(test_rom::rom()).unwrap();
+        assert_eq!(core.frame(), 30);
+        assert_eq!(core.ram_checksum(), saved.ram_checksum());
+
+        let mut other_rom = test_rom::rom();
+        other_rom[machine::ROM_SIZE - 1] = 0xFF;
+        assert!(variant.build(other_rom).err().unwrap().contains("different ROM"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,536 @@
+use serde::{Deserialize, Serialize};
+use crate::video::{self, OverlayMap, OverlayRect};
+
+pub const ROM_SIZE: usize = 8_192;
+
+/* Input port bit maps, after the board's input wiring as documented by Computer Archeology
+   (https://computerarcheology.com/Arcade/SpaceInvaders/Hardware.html, "Input ports").
+   Port 0 is mostly fixed by the board: bit 0 is DIP switch 4, sampled once at power-up as the
+   self-test request; bits 1-3 are pulled high; bits 4-6 carry the same player 1 controls as port 1;
+   bit 7 is tied high through the port 7 demux line. Space Invaders never reads the controls here */
+pub const PORT0_DIP4: u8 = 0b00000001;
+pub const PORT0_PULLED_HIGH: u8 = 0b10001110;
+pub const PORT0_FIXED: u8 = PORT0_DIP4 | PORT0_PULLED_HIGH;
+pub const PORT0_P1_FIRE: u8 = 0b00010000;
+pub const PORT0_P1_LEFT: u8 = 0b00100000;
+pub const PORT0_P1_RIGHT: u8 = 0b01000000;
+
+/* Port 1: bit 0 is the coin switch, high only while the coin mechanism is cycling, which InputShaper
+   reproduces as a short pulse; bit 3 is tied high and bit 7 is not connected */
+pub const PORT1_CREDIT: u8 = 0b00000001;
+pub const PORT1_P2_START: u8 = 0b00000010;
+pub const PORT1_P1_START: u8 = 0b00000100;
+pub const PORT1_TIED_HIGH: u8 = 0b00001000;
+pub const PORT1_P1_FIRE: u8 = 0b00010000;
+pub const PORT1_P1_LEFT: u8 = 0b00100000;
+pub const PORT1_P1_RIGHT: u8 = 0b01000000;
+
+pub const PORT2_LIVES: u8 = 0b00000011;
+pub const PORT2_TILT: u8 = 0b00000100;
+pub const PORT2_BONUS_AT_1000: u8 = 0b00001000;
+pub const PORT2_P2_FIRE: u8 = 0b00010000;
+pub const PORT2_P2_LEFT: u8 = 0b00100000;
+pub const PORT2_P2_RIGHT: u8 = 0b01000000;
+pub const PORT2_COIN_INFO_HIDDEN: u8 = 0b10000000;
+
+/* Port 2 per the operator's manual DIP switch settings. The switches are only read when the program
+   resets or a game starts; the rest are live controls */
+pub const PORT2_BITS: [(u8, &str); 7] = [
+    (PORT2_LIVES, "Ships per game: 00 = 3, 01 = 4, 10 = 5, 11 = 6"),
+    (PORT2_TILT, "Tilt switch"),
+    (PORT2_BONUS_AT_1000, "Extra ship: 0 = at 1500 points, 1 = at 1000 points"),
+    (PORT2_P2_FIRE, "Player 2 fire"),
+    (PORT2_P2_LEFT, "Player 2 left"),
+    (PORT2_P2_RIGHT, "Player 2 right"),
+    (PORT2_COIN_INFO_HIDDEN, "Coin info on the demo screen: 0 = shown, 1 = hidden"),
+];
+/* The cabinet's coloured strips: green over the bottom of the upright screen, where the player's base,
+   the shields and the ships left are drawn, and red over the lane the UFO crosses */
+pub fn overlay() -> OverlayMap {
+    return OverlayMap { rects: vec![
+        OverlayRect { x: 0, y: 0, width: 80, height: video::HEIGHT, color: video::GREEN },
+        OverlayRect { x: 201, y: 0, width: 20, height: video::HEIGHT, color: video::RED },
+    ] };
+}
+
+pub const MIN_LIVES: u8 = 3;
+pub const MAX_LIVES: u8 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DipSwitches {
+    pub lives: u8,
+    pub bonus_life_at_1000: bool,
+    pub coin_info: bool,
+}
+
+impl DipSwitches {
+    pub fn from_port2(bits: u8) -> Self {
+        Self {
+            lives: MIN_LIVES + (bits & PORT2_LIVES),
+            bonus_life_at_1000: bits & PORT2_BONUS_AT_1000 != 0,
+            coin_info: bits & PORT2_COIN_INFO_HIDDEN == 0,
+        }
+    }
+
+    pub fn port2_bits(&self) -> u8 {
+        let mut bits = (self.lives.clamp(MIN_LIVES, MAX_LIVES) - MIN_LIVES) & PORT2_LIVES;
+        if self.bonus_life_at_1000 {
+            bits = bits | PORT2_BONUS_AT_1000;
+        }
+        if !self.coin_info {
+            bits = bits | PORT2_COIN_INFO_HIDDEN;
+        }
+        return bits;
+    }
+
+    pub fn bonus_life_score(&self) -> u32 {
+        return if self.bonus_life_at_1000 { 1_000 } else { 1_500 };
+    }
+}
+
+/* Whether a switch can change under a running game. The program copies the ship count when a game starts, so
+   a new count would leave the game in progress at odds with the switches until a reset. The bonus switch
+   is read each time the score is checked for the extra ship, and the coin info switch each time the demo
+   screen is drawn, so those take effect at the next read */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DipApplication {
+    HotSwappable,
+    RequiresReset,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DipSwitch {
+    Lives,
+    BonusLife,
+    CoinInfo,
+}
+
+impl DipSwitch {
+    pub const ALL: [DipSwitch; 3] = [DipSwitch::Lives, DipSwitch::BonusLife, DipSwitch::CoinInfo];
+
+    pub fn application(&self) -> DipApplication {
+        match self {
+            DipSwitch::Lives => DipApplication::RequiresReset,
+            DipSwitch::BonusLife | DipSwitch::CoinInfo => DipApplication::HotSwappable,
+        }
+    }
+}
+
+impl DipSwitches {
+    fn copy_switch(&mut self, from: &DipSwitches, switch: DipSwitch) {
+        match switch {
+            DipSwitch::Lives => self.lives = from.lives,
+            DipSwitch::BonusLife => self.bonus_life_at_1000 = from.bonus_life_at_1000,
+            DipSwitch::CoinInfo => self.coin_info = from.coin_info,
+        }
+    }
+
+    pub fn differs(&self, other: &DipSwitches, switch: DipSwitch) -> bool {
+        let mut copy = *self;
+        copy.copy_switch(other, switch);
+        return copy != *self;
+    }
+
+    /* What the running machine sees once the settings ask for `wanted`: hot-swappable switches follow at
+       once, the others keep their running position */
+    pub fn apply_hot(&self, wanted: &DipSwitches) -> DipSwitches {
+        let mut live = *self;
+        for switch in DipSwitch::ALL.into_iter().filter(|switch| switch.application() == DipApplication::HotSwappable) {
+            live.copy_switch(wanted, switch);
+        }
+        return live;
+    }
+
+    /* Switches `wanted` sets differently that only a reset brings in */
+    pub fn pending_reset(&self, wanted: &DipSwitches) -> Vec<DipSwitch> {
+        return DipSwitch::ALL.into_iter()
+            .filter(|switch| switch.application() == DipApplication::RequiresReset && self.differs(wanted, *switch))
+            .collect();
+    }
+}
+
+impl Default for DipSwitches {
+    fn default() -> Self {
+        Self {
+            lives: 3,
+            bonus_life_at_1000: false,
+            coin_info: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LogicalInput {
+    Coin,
+    P1Start,
+    P2Start,
+    P1Fire,
+    P1Left,
+    P1Right,
+    P2Fire,
+    P2Left,
+    P2Right,
+    Tilt,
+}
+
+impl LogicalInput {
+    pub const ALL: [LogicalInput; 10] = [
+        LogicalInput::Coin,
+        LogicalInput::P1Start,
+        LogicalInput::P2Start,
+        LogicalInput::P1Fire,
+        LogicalInput::P1Left,
+        LogicalInput::P1Right,
+        LogicalInput::P2Fire,
+        LogicalInput::P2Left,
+        LogicalInput::P2Right,
+        LogicalInput::Tilt,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogicalInput::Coin => "Coin",
+            LogicalInput::P1Start => "1P Start",
+            LogicalInput::P2Start => "2P Start",
+            LogicalInput::P1Fire => "P1 Fire",
+            LogicalInput::P1Left => "P1 Left",
+            LogicalInput::P1Right => "P1 Right",
+            LogicalInput::P2Fire => "P2 Fire",
+            LogicalInput::P2Left => "P2 Left",
+            LogicalInput::P2Right => "P2 Right",
+            LogicalInput::Tilt => "Tilt",
+        }
+    }
+
+    pub fn kind(&self) -> InputKind {
+        match self {
+            LogicalInput::Coin => InputKind::PulseFrames(COIN_PULSE_FRAMES),
+            LogicalInput::Tilt => InputKind::PulseFrames(TILT_PULSE_FRAMES),
+            _ => InputKind::Level,
+        }
+    }
+}
+
+/* How a held control reaches the ports: movement and fire are sampled every frame, while the
+   coin and tilt switches are momentary contacts the program expects to see for a few frames */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputKind {
+    Level,
+    PulseFrames(u8),
+}
+
+pub const COIN_PULSE_FRAMES: u8 = 4;
+pub const TILT_PULSE_FRAMES: u8 = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputState {
+    pub coin: bool,
+    pub p1_start: bool,
+    pub p2_start: bool,
+    pub p1_fire: bool,
+    pub p1_left: bool,
+    pub p1_right: bool,
+    pub p2_fire: bool,
+    pub p2_left: bool,
+    pub p2_right: bool,
+    pub tilt: bool,
+    pub dip_switches: DipSwitches,
+}
+
+impl InputState {
+    pub fn with_dip_switches(dip_switches: DipSwitches) -> Self {
+        Self {
+            dip_switches,
+            ..Default::default()
+        }
+    }
+
+    pub fn get(&self, input: LogicalInput) -> bool {
+        match input {
+            LogicalInput::Coin => self.coin,
+            LogicalInput::P1Start => self.p1_start,
+            LogicalInput::P2Start => self.p2_start,
+            LogicalInput::P1Fire => self.p1_fire,
+            LogicalInput::P1Left => self.p1_left,
+            LogicalInput::P1Right => self.p1_right,
+            LogicalInput::P2Fire => self.p2_fire,
+            LogicalInput::P2Left => self.p2_left,
+            LogicalInput::P2Right => self.p2_right,
+            LogicalInput::Tilt => self.tilt,
+        }
+    }
+
+    pub fn set(&mut self, input: LogicalInput, pressed: bool) {
+        match input {
+            LogicalInput::Coin => self.coin = pressed,
+            LogicalInput::P1Start => self.p1_start = pressed,
+            LogicalInput::P2Start => self.p2_start = pressed,
+            LogicalInput::P1Fire => self.p1_fire = pressed,
+            LogicalInput::P1Left => self.p1_left = pressed,
+            LogicalInput::P1Right => self.p1_right = pressed,
+            LogicalInput::P2Fire => self.p2_fire = pressed,
+            LogicalInput::P2Left => self.p2_left = pressed,
+            LogicalInput::P2Right => self.p2_right = pressed,
+            LogicalInput::Tilt => self.tilt = pressed,
+        }
+    }
+
+    /* The whole state in one word, so it can be shared through a single atomic */
+    pub fn to_bits(&self) -> u32 {
+        let mut bits = 0;
+        for (index, logical) in LogicalInput::ALL.iter().enumerate() {
+            if self.get(*logical) {
+                bits |= 1 << index;
+            }
+        }
+        let dip_switches = &self.dip_switches;
+        bits |= ((dip_switches.lives.clamp(3, 6) - 3) as u32) << 16;
+        bits |= (dip_switches.bonus_life_at_1000 as u32) << 18;
+        bits |= (dip_switches.coin_info as u32) << 19;
+        return bits;
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        let dip_switches = DipSwitches {
+            lives: 3 + (bits >> 16 & 0b11) as u8,
+            bonus_life_at_1000: bits >> 18 & 1 != 0,
+            coin_info: bits >> 19 & 1 != 0,
+        };
+        let mut input = Self::with_dip_switches(dip_switches);
+        for (index, logical) in LogicalInput::ALL.iter().enumerate() {
+            input.set(*logical, bits >> index & 1 != 0);
+        }
+        return input;
+    }
+
+    pub fn port0(&self) -> u8 {
+        let mut bits = PORT0_FIXED;
+        if self.p1_fire {
+            bits = bits | PORT0_P1_FIRE;
+        }
+        if self.p1_left {
+            bits = bits | PORT0_P1_LEFT;
+        }
+        if self.p1_right {
+            bits = bits | PORT0_P1_RIGHT;
+        }
+        return bits;
+    }
+
+    pub fn port1(&self) -> u8 {
+        let mut bits = PORT1_TIED_HIGH;
+        if self.coin {
+            bits = bits | PORT1_CREDIT;
+        }
+        if self.p2_start {
+            bits = bits | PORT1_P2_START;
+        }
+        if self.p1_start {
+            bits = bits | PORT1_P1_START;
+        }
+        if self.p1_fire {
+            bits = bits | PORT1_P1_FIRE;
+        }
+        if self.p1_left {
+            bits = bits | PORT1_P1_LEFT;
+        }
+        if self.p1_right {
+            bits = bits | PORT1_P1_RIGHT;
+        }
+        return bits;
+    }
+
+    pub fn port2(&self) -> u8 {
+        let mut bits = self.dip_switches.port2_bits();
+        if self.tilt {
+            bits = bits | PORT2_TILT;
+        }
+        if self.p2_fire {
+            bits = bits | PORT2_P2_FIRE;
+        }
+        if self.p2_left {
+            bits = bits | PORT2_P2_LEFT;
+        }
+        if self.p2_right {
+            bits = bits | PORT2_P2_RIGHT;
+        }
+        return bits;
+    }
+}
+
+/* Turns raw held controls into per-frame port inputs according to each input's kind */
+#[derive(Clone, Debug, Default)]
+pub struct InputShaper {
+    held: InputState,
+    remaining: [u8; LogicalInput::ALL.len()],
+}
+
+impl InputShaper {
+    pub fn shape(&mut self, raw: &InputState) -> InputState {
+        let mut shaped = *raw;
+        for (index, logical) in LogicalInput::ALL.iter().enumerate() {
+            if let InputKind::PulseFrames(frames) = logical.kind() {
+                if raw.get(*logical) && !self.held.get(*logical) {
+                    self.remaining[index] = frames;
+                }
+                shaped.set(*logical, self.remaining[index] > 0);
+                self.remaining[index] = self.remaining[index].saturating_sub(1);
+            }
+        }
+        self.held = *raw;
+        return shaped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_ports() {
+        let input = InputState::default();
+        assert_eq!(input.port0(), 0b10001111);
+        assert_eq!(input.port1(), 0b00001000);
+        assert_eq!(input.port2(), 0b00000000);
+    }
+
+    #[test]
+    fn test_port0_mirrors_player_1_controls() {
+        let input = InputState { coin: true, p1_start: true, p1_fire: true, p1_right: true, ..Default::default() };
+        assert_eq!(input.port0(), PORT0_FIXED | PORT0_P1_FIRE | PORT0_P1_RIGHT);
+        /* Only the controls move; the wired bits match port 1's layout for the shared controls */
+        assert_eq!(input.port0() & !PORT0_FIXED, input.port1() & (PORT1_P1_FIRE | PORT1_P1_LEFT | PORT1_P1_RIGHT));
+    }
+
+    #[test]
+    fn test_port1_buttons() {
+        let input = InputState { coin: true, p1_start: true, p1_fire: true, p1_right: true, ..Default::default() };
+        assert_eq!(input.port1(), 0b01011101);
+    }
+
+    #[test]
+    fn test_port2_dip_switches() {
+        let dip_switches = DipSwitches { lives: 6, bonus_life_at_1000: true, coin_info: false };
+        let input = InputState::with_dip_switches(dip_switches);
+        assert_eq!(input.port2(), 0b10001011);
+    }
+
+    #[test]
+    fn test_port2_bit_table() {
+        let mut covered = 0;
+        for (mask, _) in PORT2_BITS {
+            assert_eq!(covered & mask, 0);
+            covered = covered | mask;
+        }
+        assert_eq!(covered, 0xFF);
+        let lives: Vec<u8> = (MIN_LIVES..=MAX_LIVES).map(|lives| DipSwitches { lives, ..Default::default() }.port2_bits() & PORT2_LIVES).collect();
+        assert_eq!(lives, vec![0b00, 0b01, 0b10, 0b11]);
+        assert_eq!(DipSwitches { lives: 9, ..Default::default() }.port2_bits() & PORT2_LIVES, 0b11);
+        assert_eq!(DipSwitches { bonus_life_at_1000: true, ..Default::default() }.port2_bits(), PORT2_BONUS_AT_1000);
+        assert_eq!(DipSwitches { coin_info: false, ..Default::default() }.port2_bits(), PORT2_COIN_INFO_HIDDEN);
+        assert_eq!(DipSwitches::default().bonus_life_score(), 1_500);
+    }
+
+    #[test]
+    fn test_dip_classification() {
+        let applications: Vec<DipApplication> = DipSwitch::ALL.iter().map(DipSwitch::application).collect();
+        assert_eq!(applications, vec![DipApplication::RequiresReset, DipApplication::HotSwappable, DipApplication::HotSwappable]);
+    }
+
+    #[test]
+    fn test_hot_switches_apply_and_the_rest_wait_for_reset() {
+        let live = DipSwitches::default();
+        let wanted = DipSwitches { lives: 5, bonus_life_at_1000: true, coin_info: false };
+        let applied = live.apply_hot(&wanted);
+        assert_eq!(applied, DipSwitches { lives: 3, bonus_life_at_1000: true, coin_info: false });
+        assert_eq!(applied.port2_bits(), PORT2_BONUS_AT_1000 | PORT2_COIN_INFO_HIDDEN);
+        assert_eq!(applied.pending_reset(&wanted), vec![DipSwitch::Lives]);
+        /* Hot changes never prompt for a reset, even before they are applied */
+        assert_eq!(live.pending_reset(&DipSwitches { bonus_life_at_1000: true, ..live }), vec![]);
+        /* Changing the count back before resetting leaves nothing pending */
+        let reverted = DipSwitches { lives: 3, ..wanted };
+        assert_eq!(applied.apply_hot(&reverted), applied);
+        assert_eq!(applied.pending_reset(&reverted), vec![]);
+    }
+
+    #[test]
+    fn test_dip_switches_decode_from_port2() {
+        for lives in MIN_LIVES..=MAX_LIVES {
+            for (bonus_life_at_1000, coin_info) in [(false, false), (false, true), (true, false), (true, true)] {
+                let dip_switches = DipSwitches { lives, bonus_life_at_1000, coin_info };
+                let input = InputState { tilt: true, p2_fire: true, p2_right: true, ..InputState::with_dip_switches(dip_switches) };
+                assert_eq!(DipSwitches::from_port2(input.port2()), dip_switches);
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_set() {
+        let mut input = InputState::default();
+        for logical in LogicalInput::ALL {
+            assert_eq!(input.get(logical), false);
+            input.set(logical, true);
+            assert_eq!(input.get(logical), true);
+        }
+        assert_eq!(input.port1(), 0b01111111);
+        assert_eq!(input.port2(), 0b01110100);
+    }
+
+    #[test]
+    fn test_bits_round_trip() {
+        let mut input = InputState::with_dip_switches(DipSwitches { lives: 6, bonus_life_at_1000: true, coin_info: false });
+        assert_eq!(InputState::from_bits(input.to_bits()), input);
+        for logical in LogicalInput::ALL {
+            input.set(logical, true);
+            assert_eq!(InputState::from_bits(input.to_bits()), input);
+        }
+        assert_eq!(InputState::from_bits(InputState::default().to_bits()).dip_switches.lives, 3);
+    }
+
+    #[test]
+    fn test_port2_player2() {
+        let input = InputState { p2_fire: true, p2_left: true, tilt: true, ..Default::default() };
+        assert_eq!(input.port2(), 0b00110100);
+    }
+
+    fn shape_coin(shaper: &mut InputShaper, held: &[bool]) -> Vec<bool> {
+        held.iter().map(|&coin| shaper.shape(&InputState { coin, ..Default::default() }).coin).collect()
+    }
+
+    #[test]
+    fn test_coin_pulses_for_fixed_frames() {
+        let mut shaper = InputShaper::default();
+        /* Held for eight frames, still only four frames of coin */
+        let pulse = shape_coin(&mut shaper, &[true; 8]);
+        assert_eq!(pulse, vec![true, true, true, true, false, false, false, false]);
+
+        /* A one-frame tap is stretched to the full pulse */
+        let pulse = shape_coin(&mut shaper, &[false, true, false, false, false, false]);
+        assert_eq!(pulse, vec![false, true, true, true, true, false]);
+    }
+
+    #[test]
+    fn test_tilt_pulses_and_retriggers() {
+        let mut shaper = InputShaper::default();
+        let mut tilt = |held| shaper.shape(&InputState { tilt: held, ..Default::default() }).tilt;
+        let pulse: Vec<bool> = [true, true, false, true, false, false, false, false].into_iter().map(&mut tilt).collect();
+        assert_eq!(pulse, vec![true, true, true, true, true, true, true, false]);
+    }
+
+    #[test]
+    fn test_level_inputs_pass_through() {
+        let mut shaper = InputShaper::default();
+        let dip_switches = DipSwitches { lives: 5, ..Default::default() };
+        for frame in 0..20 {
+            let raw = InputState { p1_left: frame % 3 == 0, p1_fire: true, p2_right: frame > 10, dip_switches, ..Default::default() };
+            assert_eq!(shaper.shape(&raw), raw);
+        }
+    }
+
+    #[test]
+    fn test_input_kinds() {
+        assert_eq!(LogicalInput::Coin.kind(), InputKind::PulseFrames(4));
+        assert_eq!(LogicalInput::P1Start.kind(), InputKind::Level);
+        assert_eq!(LogicalInput::P1Left.kind(), InputKind::Level);
+    }
+}
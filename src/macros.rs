@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+use crate::machine::{InputState, LogicalInput};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MacroStep {
+    pub frame: u64,
+    pub input: LogicalInput,
+    pub pressed: bool,
+}
+
+/* Turbo held or let go; replayed so a movie keeps the pace it was recorded at */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpeedStep {
+    pub frame: u64,
+    pub turbo: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    pub key: Option<String>,
+    pub steps: Vec<MacroStep>,
+    /* Older macros have none */
+    #[serde(default)]
+    pub speed: Vec<SpeedStep>,
+}
+
+impl Macro {
+    pub fn length(&self) -> u64 {
+        return self.steps.last().map(|s| s.frame).unwrap_or(0);
+    }
+}
+
+pub struct MacroRecorder {
+    start_frame: u64,
+    last: InputState,
+    steps: Vec<MacroStep>,
+    turbo: bool,
+    speed: Vec<SpeedStep>,
+}
+
+impl MacroRecorder {
+    pub fn start(frame: u64) -> Self {
+        Self {
+            start_frame: frame,
+            last: InputState::default(),
+            steps: vec![],
+            turbo: false,
+            speed: vec![],
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, input: &InputState) {
+        for logical in LogicalInput::ALL {
+            let pressed = input.get(logical);
+            if pressed != self.last.get(logical) {
+                self.steps.push(MacroStep { frame: frame.saturating_sub(self.start_frame), input: logical, pressed });
+            }
+        }
+        self.last = *input;
+    }
+
+    pub fn record_turbo(&mut self, frame: u64, turbo: bool) {
+        if turbo != self.turbo {
+            self.speed.push(SpeedStep { frame: frame.saturating_sub(self.start_frame), turbo });
+            self.turbo = turbo;
+        }
+    }
+
+    /* Releases anything still held so playback never leaves an input stuck, or the pace raised */
+    pub fn finish(mut self, frame: u64, name: String) -> Macro {
+        self.record(frame, &InputState::default());
+        self.record_turbo(frame, false);
+        Macro { name, key: None, steps: self.steps, speed: self.speed }
+    }
+}
+
+pub struct MacroPlayer {
+    steps: Vec<MacroStep>,
+    speed: Vec<SpeedStep>,
+    start_frame: u64,
+    cursor: usize,
+    speed_cursor: usize,
+    state: InputState,
+    turbo: bool,
+}
+
+impl MacroPlayer {
+    pub fn new(recorded: &Macro, start_frame: u64) -> Self {
+        Self {
+            steps: recorded.steps.clone(),
+            speed: recorded.speed.clone(),
+            start_frame,
+            cursor: 0,
+            speed_cursor: 0,
+            state: InputState::default(),
+            turbo: false,
+        }
+    }
+
+    pub fn input_at(&mut self, frame: u64) -> InputState {
+        let offset = frame.saturating_sub(self.start_frame);
+        while self.cursor < self.steps.len() && self.steps[self.cursor].frame <= offset {
+            let step = self.steps[self.cursor];
+            self.state.set(step.input, step.pressed);
+            self.cursor += 1;
+        }
+        while self.speed_cursor < self.speed.len() && self.speed[self.speed_cursor].frame <= offset {
+            self.turbo = self.speed[self.speed_cursor].turbo;
+            self.speed_cursor += 1;
+        }
+        return self.state;
+    }
+
+    /* Whether the recording had turbo held at the frame last passed to input_at */
+    pub fn turbo(&self) -> bool {
+        return self.turbo;
+    }
+
+    pub fn finished(&self) -> bool {
+        return self.cursor == self.steps.len() && self.speed_cursor == self.speed.len();
+    }
+}
+
+/* Live input wins: a player's macro directions are dropped while that player steers live */
+pub fn merge(live: &InputState, played: &InputState) -> InputState {
+    let mut merged = *live;
+    for logical in LogicalInput::ALL {
+        let steering = match logical {
+            LogicalInput::P1Left | LogicalInput::P1Right => live.p1_left || live.p1_right,
+            LogicalInput::P2Left | LogicalInput::P2Right => live.p2_left || live.p2_right,
+            _ => false,
+        };
+        if !steering && played.get(logical) {
+            merged.set(logical, true);
+        }
+    }
+    return merged;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(inputs: &[LogicalInput]) -> InputState {
+        let mut state = InputState::default();
+        for input in inputs {
+            state.set(*input, true);
+        }
+        return state;
+    }
+
+    fn coin_then_start() -> Macro {
+        let mut recorder = MacroRecorder::start(100);
+        recorder.record(100, &pressed(&[LogicalInput::Coin]));
+        recorder.record(102, &InputState::default());
+        recorder.record(130, &pressed(&[LogicalInput::P1Start]));
+        recorder.finish(132, "coin and start".to_string())
+    }
+
+    #[test]
+    fn test_record_deltas() {
+        let recorded = coin_then_start();
+        assert_eq!(recorded.steps, vec![
+            MacroStep { frame: 0, input: LogicalInput::Coin, pressed: true },
+            MacroStep { frame: 2, input: LogicalInput::Coin, pressed: false },
+            MacroStep { frame: 30, input: LogicalInput::P1Start, pressed: true },
+            MacroStep { frame: 32, input: LogicalInput::P1Start, pressed: false },
+        ]);
+        assert_eq!(recorded.length(), 32);
+    }
+
+    #[test]
+    fn test_record_ignores_unchanged_frames() {
+        let mut recorder = MacroRecorder::start(0);
+        recorder.record(1, &pressed(&[LogicalInput::P1Fire]));
+        recorder.record(2, &pressed(&[LogicalInput::P1Fire]));
+        recorder.record(3, &pressed(&[LogicalInput::P1Fire]));
+        let recorded = recorder.finish(4, "fire".to_string());
+        assert_eq!(recorded.steps.len(), 2);
+    }
+
+    #[test]
+    fn test_playback_timing() {
+        let mut player = MacroPlayer::new(&coin_then_start(), 1_000);
+        assert_eq!(player.input_at(1_000).coin, true);
+        assert_eq!(player.input_at(1_001).coin, true);
+        assert_eq!(player.input_at(1_002).coin, false);
+        assert_eq!(player.input_at(1_029).p1_start, false);
+        assert_eq!(player.input_at(1_030).p1_start, true);
+        assert_eq!(player.finished(), false);
+        assert_eq!(player.input_at(1_032), InputState::default());
+        assert_eq!(player.finished(), true);
+    }
+
+    #[test]
+    fn test_playback_catches_up_skipped_frames() {
+        let mut player = MacroPlayer::new(&coin_then_start(), 0);
+        assert_eq!(player.input_at(31).p1_start, true);
+        assert_eq!(player.input_at(31).coin, false);
+    }
+
+    #[test]
+    fn test_turbo_recorded_and_replayed() {
+        let mut recorder = MacroRecorder::start(50);
+        recorder.record(50, &pressed(&[LogicalInput::P1Fire]));
+        recorder.record_turbo(51, true);
+        recorder.record_turbo(52, true);
+        recorder.record_turbo(60, false);
+        recorder.record_turbo(70, true);
+        let recorded = recorder.finish(80, "turbo".to_string());
+        assert_eq!(recorded.speed, vec![
+            SpeedStep { frame: 1, turbo: true },
+            SpeedStep { frame: 10, turbo: false },
+            SpeedStep { frame: 20, turbo: true },
+            SpeedStep { frame: 30, turbo: false },
+        ]);
+
+        let mut player = MacroPlayer::new(&recorded, 0);
+        player.input_at(0);
+        assert!(!player.turbo());
+        player.input_at(1);
+        assert!(player.turbo());
+        player.input_at(15);
+        assert!(!player.turbo());
+        player.input_at(29);
+        assert!(player.turbo());
+        assert!(!player.finished());
+        player.input_at(30);
+        assert!(!player.turbo());
+        assert!(player.finished());
+    }
+
+    #[test]
+    fn test_merge_combines_inputs() {
+        let merged = merge(&pressed(&[LogicalInput::P1Fire]), &pressed(&[LogicalInput::Coin]));
+        assert_eq!(merged, pressed(&[LogicalInput::P1Fire, LogicalInput::Coin]));
+    }
+
+    #[test]
+    fn test_merge_live_wins_on_conflict() {
+        let merged = merge(&pressed(&[LogicalInput::P1Left]), &pressed(&[LogicalInput::P1Right, LogicalInput::P2Right]));
+        assert_eq!(merged, pressed(&[LogicalInput::P1Left, LogicalInput::P2Right]));
+    }
+}
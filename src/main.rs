@@ -1,19 +1,402 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod actions;
+mod alloc_stats;
+mod anomaly;
+mod app_state;
 mod application;
 mod audio;
+mod audio_queue;
+mod av_sync;
+mod cheats;
+#[cfg(feature = "compare")]
+mod compare_app;
+mod coverage;
+mod debug;
+mod debug_budget;
+mod determinism;
+mod diagnostic;
+mod displays;
+mod emulator_core;
+mod event_scheduler;
+mod file_access;
+mod frame_exchange;
+mod frame_pacing;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod game_state;
+mod health;
+mod hotkeys;
+mod input;
+mod input_log;
+mod input_sources;
+mod io_log;
+mod key_capture;
+mod latency;
+mod listing;
+#[cfg(feature = "compare")]
+mod lockstep;
+mod machine;
+mod macros;
+mod memory_access;
+mod mixer;
+mod ml_export;
+mod platform;
+mod png;
+mod rack;
+mod ram_map;
+mod renderer;
+mod repaint;
+mod romset;
+mod run_mode;
+mod rng;
+mod save_slots;
+mod save_state;
+mod settings;
+mod shared_control;
+mod shift_register;
+mod soak;
+mod sound_latch;
+mod sound_timeline;
+mod span_stats;
+mod starfield;
+#[cfg(feature = "http-state")]
+mod state_server;
+mod state_watcher;
+mod stats;
+mod strings;
+mod symbols;
+mod test_rom;
+mod theme;
+mod toast;
+mod video;
+mod wave_stats;
+mod window_size;
+mod workers;
+mod zip;
 
 fn main() -> eframe::Result<()> {
     env_logger::init();
+    let args: Vec<String> = std::env::args().collect();
+    let builtin_rom = args.iter().any(|arg| arg == "--builtin-rom");
+    let strict = args.iter().any(|arg| arg == "--strict");
+    if let Some(path) = arg_value(&args, "--ml-pipe") {
+        run_ml_pipe(path, arg_value(&args, "--ml-format").unwrap_or("bits"), builtin_rom);
+        return Ok(());
+    }
+    #[cfg(feature = "compare")]
+    if let (Some(left), Some(right)) = (arg_value(&args, "--compare-left"), arg_value(&args, "--compare-right")) {
+        return run_compare(left, right, builtin_rom);
+    }
+    if let Some(dir) = arg_value(&args, "--generate-samples") {
+        run_generate_samples(dir);
+        return Ok(());
+    }
+    if let Some(path) = arg_value(&args, "--export-listing") {
+        run_export_listing(path, arg_value(&args, "--listing-symbols"), builtin_rom);
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--measure-rack") {
+        run_measure_rack(arg_value(&args, "--rack-reference"));
+        return Ok(());
+    }
+    if let Some(frames) = arg_value(&args, "--verify-determinism") {
+        run_verify_determinism(frames, builtin_rom, args.iter().any(|arg| arg == "--idle-skip"));
+        return Ok(());
+    }
+    if let Some(minutes) = arg_value(&args, "--soak") {
+        run_soak(minutes, arg_value(&args, "--soak-seed"), builtin_rom, strict);
+        return Ok(());
+    }
+    let preferred = match arg_value(&args, "--renderer") {
+        Some(name) => match renderer::Backend::from_name(name).filter(|backend| backend.compiled()) {
+            Some(backend) => Some(backend),
+            None => {
+                let names: Vec<&str> = renderer::order(None).iter().map(|backend| backend.name()).collect();
+                eprintln!("Unknown --renderer {}, this build has {}.", name, names.join(" and "));
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+    /* Repeatable; applied in the order given */
+    let patches: Vec<String> = args.windows(2).filter(|pair| pair[0] == "--patch").map(|pair| pair[1].clone()).collect();
+    let cascade = renderer::cascade(&renderer::order(preferred), |backend| {
+        /* An error from run_native before the app exists means the backend never came up */
+        let started = std::rc::Rc::new(std::cell::Cell::new(false));
+        let started_clone = started.clone();
+        let patches = patches.clone();
+        let native_options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_inner_size([400.0, 300.0])
+                .with_min_inner_size([300.0, 220.0]),
+            renderer: eframe_renderer(backend),
+            ..Default::default()
+        };
+        let result = eframe::run_native(
+            crate::application::APP_NAME,
+            native_options,
+            Box::new(move |cc| {
+                started_clone.set(true);
+                Box::new(crate::application::App::new(cc, builtin_rom, strict, backend, &patches))
+            }),
+        );
+        match result {
+            Err(error) if !started.get() => {
+                println!("Warning: the {} renderer failed to start: {}", backend.name(), error);
+                Err(error.to_string())
+            },
+            result => Ok(result),
+        }
+    });
+    let result = match cascade {
+        Ok((_, result)) => result,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    #[cfg(feature = "tracing")]
+    if let Ok(stats) = span_stats::GLOBAL.lock() {
+        print!("{}", stats.report());
+    }
+    return result;
+}
+fn eframe_renderer(backend: renderer::Backend) -> eframe::Renderer {
+    match backend {
+        renderer::Backend::Glow => eframe::Renderer::Glow,
+        #[cfg(feature = "wgpu")]
+        renderer::Backend::Wgpu => eframe::Renderer::Wgpu,
+        /* order() only hands out compiled backends */
+        #[cfg(not(feature = "wgpu"))]
+        renderer::Backend::Wgpu => unreachable!(),
+    }
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let index = args.iter().position(|arg| arg == name)?;
+    return args.get(index + 1).map(|value| value.as_str());
+}
+
+fn run_ml_pipe(path: &str, format: &str, builtin_rom: bool) {
+    let format = match ml_export::FrameFormat::from_name(format) {
+        Some(format) => format,
+        None => {
+            eprintln!("Unknown --ml-format {}, expected bits or gray.", format);
+            std::process::exit(2);
+        }
+    };
+    let rom = if builtin_rom {
+        test_rom::rom()
+    } else {
+        match application::load_rom() {
+            Ok(rom) => rom,
+            Err(error) => {
+                eprintln!("{}", strings::Strings::new(strings::Language::English).message(&error));
+                std::process::exit(1);
+            }
+        }
+    };
+    if let Err(error) = ml_export::run_pipe(path, format, rom) {
+        eprintln!("ML pipe {}: {}", path, error);
+        std::process::exit(1);
+    }
+}
+
+fn run_generate_samples(dir: &str) {
+    match audio::synth::generate_samples(std::path::Path::new(dir)) {
+        Ok(written) => {
+            for path in written {
+                println!("{}", path.display());
+            }
+        },
+        Err(error) => {
+            eprintln!("Generating samples: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/* Writes the annotated listing of the ROM, labelled from the embedded symbols or a .sym file */
+fn run_export_listing(path: &str, symbol_file: Option<&str>, builtin_rom: bool) {
+    let rom = if builtin_rom {
+        test_rom::rom()
+    } else {
+        match application::load_rom() {
+            Ok(rom) => rom,
+            Err(error) => {
+                eprintln!("{}", strings::Strings::new(strings::Language::English).message(&error));
+                std::process::exit(1);
+            }
+        }
+    };
+    let table = match symbol_file {
+        Some(file) => match std::fs::read_to_string(file).map_err(|error| error.to_string()).and_then(|text| symbols::SymbolTable::parse(&text)) {
+            Ok(table) => Some(table),
+            Err(error) => {
+                eprintln!("Symbols {}: {}", file, error);
+                std::process::exit(2);
+            }
+        },
+        None => symbols::for_rom(&rom),
+    };
+    let options = listing::ListingOptions { symbols: table.as_ref(), coverage: None };
+    let written = std::fs::File::create(path).and_then(|file| {
+        let mut writer = std::io::BufWriter::new(file);
+        listing::export_listing(&mut writer, &rom, options)?;
+        return std::io::Write::flush(&mut writer);
+    });
+    if let Err(error) = written {
+        eprintln!("Listing {}: {}", path, error);
+        std::process::exit(1);
+    }
+}
+
+/* Prints the measured rack cadence table, or compares it against a reference and exits 1 on deviations */
+fn run_measure_rack(reference: Option<&str>) {
+    let rom = match application::load_rom() {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("{}", strings::Strings::new(strings::Language::English).message(&error));
+            std::process::exit(1);
+        }
+    };
+    let measured = rack::cadence(&rack::measure(rom, rack::MEASURE_FRAMES));
+    let reference = match reference {
+        Some(path) => match std::fs::read_to_string(path).map_err(|error| error.to_string()).and_then(|text| rack::parse_table(&text)) {
+            Ok(reference) => reference,
+            Err(error) => {
+                eprintln!("Rack reference {}: {}", path, error);
+                std::process::exit(2);
+            }
+        },
+        None => {
+            print!("{}", rack::format_table(&measured));
+            return;
+        }
+    };
+    let deviations = rack::compare(&measured, &reference, rack::DEFAULT_TOLERANCE);
+    print!("{}", rack::report(&deviations, &reference));
+    if !deviations.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/* Runs two cores on the same seeded input script and exits 1 with a state diff at the first frame they differ;
+   with idle_skip the second core skips idle loops */
+fn run_verify_determinism(frames: &str, builtin_rom: bool, idle_skip: bool) {
+    let frames: u64 = match frames.parse() {
+        Ok(frames) => frames,
+        Err(_) => {
+            eprintln!("--verify-determinism takes a number of frames, got {}.", frames);
+            std::process::exit(2);
+        }
+    };
+    let rom = if builtin_rom {
+        test_rom::rom()
+    } else {
+        match application::load_rom() {
+            Ok(rom) => rom,
+            Err(error) => {
+                eprintln!("{}", strings::Strings::new(strings::Language::English).message(&error));
+                std::process::exit(1);
+            }
+        }
+    };
+    let inputs = determinism::scripted_inputs(determinism::SEED, frames);
+    let result = if idle_skip {
+        determinism::verify_idle_skip(rom, &inputs, frames)
+    } else {
+        determinism::verify_determinism(rom, &inputs, frames)
+    };
+    match result {
+        Ok(()) => println!("{} frames, identical RAM and CPU state on both cores", frames),
+        Err(report) => {
+            eprintln!("{}", report);
+            std::process::exit(1);
+        }
+    }
+}
+
+/* Runs the headless soak and exits 1 with the seed to reproduce any invariant it saw broken; strict also fails
+   on any emulation anomaly and prints the failure as JSON on stdout */
+fn run_soak(minutes: &str, seed: Option<&str>, builtin_rom: bool, strict: bool) {
+    let minutes: f64 = match minutes.parse() {
+        Ok(minutes) if minutes > 0.0 => minutes,
+        _ => {
+            eprintln!("--soak takes a positive number of minutes, got {}.", minutes);
+            std::process::exit(2);
+        }
+    };
+    let seed = match seed.map(|seed| seed.parse::<u64>()) {
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => {
+            eprintln!("--soak-seed takes a whole number.");
+            std::process::exit(2);
+        },
+        None => std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or_default(),
+    };
+    let rom = if builtin_rom {
+        test_rom::rom()
+    } else {
+        match application::load_rom() {
+            Ok(rom) => rom,
+            Err(error) => {
+                eprintln!("{}", strings::Strings::new(strings::Language::English).message(&error));
+                std::process::exit(1);
+            }
+        }
+    };
+    let config = soak::SoakConfig { duration: std::time::Duration::from_secs_f64(minutes * 60.0), seed, memory_cap: soak::DEFAULT_MEMORY_CAP, strict };
+    println!("Soaking for {} minutes with seed {}", minutes, seed);
+    match soak::run(rom, &config) {
+        Ok(report) => println!(
+            "{} frames, {} save/load round trips, {} resets, {} mode changes, peak heap {} KiB",
+            report.frames, report.save_loads, report.resets, report.mode_changes, report.peak_memory / 1024,
+        ),
+        Err(violation) => {
+            eprintln!("Soak failed: {}", violation);
+            if strict {
+                println!("{}", violation.to_json());
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/* Each side is "default" for a fresh core with stock timing, or a variant TOML file (see src/lockstep.rs) */
+#[cfg(feature = "compare")]
+fn run_compare(left: &str, right: &str, builtin_rom: bool) -> eframe::Result<()> {
+    let rom = if builtin_rom {
+        test_rom::rom()
+    } else {
+        match application::load_rom() {
+            Ok(rom) => rom,
+            Err(error) => {
+                eprintln!("{}", strings::Strings::new(strings::Language::English).message(&error));
+                std::process::exit(1);
+            }
+        }
+    };
+    let build = |spec: &str| {
+        let variant = if spec == "default" { Ok(lockstep::CoreVariant::default()) } else { lockstep::CoreVariant::load(std::path::Path::new(spec)) };
+        match variant.and_then(|variant| variant.build(rom)) {
+            Ok(core) => core,
+            Err(error) => {
+                eprintln!("Compare variant {}: {}", spec, error);
+                std::process::exit(2);
+            }
+        }
+    };
+    let lockstep = lockstep::Lockstep::new(build(left), build(right));
+    let names = [left.to_string(), right.to_string()];
     let native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([400.0, 300.0])
-            .with_min_inner_size([300.0, 220.0]),
+        viewport: egui::ViewportBuilder::default().with_inner_size([960.0, 620.0]),
         ..Default::default()
     };
     eframe::run_native(
-        "Space Invaders Emulator",
+        crate::application::APP_NAME,
         native_options,
-        Box::new(|cc| Box::new(crate::application::App::new(cc))),
+        Box::new(|cc| Box::new(crate::compare_app::CompareApp::new(cc, lockstep, names))),
     )
-}
\ No newline at end of file
+}
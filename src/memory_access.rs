@@ -0,0 +1,94 @@
+// Memory access from outside the emulation thread. Requests wait in the control queue and are all
+// serviced together at the next frame boundary (or straight away while paused), so a batch never
+// sees the CPU part-way through a frame.
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
+use crate::emulator_core::EmulatorCore;
+
+pub enum MemoryRequest {
+    Read { range: Range<u16>, reply: Sender<Vec<u8>> },
+    Write { addr: u16, bytes: Vec<u8> },
+}
+
+impl MemoryRequest {
+    /* The receiver yields exactly one Vec, once the request has been serviced */
+    pub fn read(range: Range<u16>) -> (Self, Receiver<Vec<u8>>) {
+        let (reply, rx) = mpsc::channel();
+        (MemoryRequest::Read { range, reply }, rx)
+    }
+}
+
+/* Writes go first, so a read sees every write queued before the same frame boundary */
+pub fn service(core: &mut EmulatorCore, requests: Vec<MemoryRequest>) {
+    let (writes, reads): (Vec<MemoryRequest>, Vec<MemoryRequest>) = requests.into_iter().partition(|request| matches!(request, MemoryRequest::Write { .. }));
+    for request in writes.into_iter().chain(reads) {
+        match request {
+            MemoryRequest::Write { addr, bytes } => {
+                for (offset, byte) in bytes.iter().enumerate() {
+                    core.write_memory(addr.wrapping_add(offset as u16), *byte);
+                }
+            },
+            MemoryRequest::Read { range, reply } => {
+                /* The requester may have given up waiting */
+                reply.send(range.map(|addr| core.read_memory(addr)).collect()).ok();
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::InputState;
+    use crate::shared_control::{ControlCommand, SharedControl};
+    use crate::test_rom;
+
+    /* What the emulation thread does at a frame boundary */
+    fn frame_boundary(control: &SharedControl, core: &mut EmulatorCore) {
+        let requests = control
+            .take_commands()
+            .into_iter()
+            .filter_map(|command| match command {
+                ControlCommand::Memory(request) => Some(request),
+                _ => None,
+            })
+            .collect();
+        service(core, requests);
+    }
+
+    #[test]
+    fn test_read_reflects_writes_from_same_frame() {
+        let control = SharedControl::new();
+        let mut core = EmulatorCore::new(test_rom::rom(), InputState::default());
+        let before = control.request_read(0x2300..0x2304);
+        control.queue_write(0x2301, vec![0xAB, 0xCD]);
+        let after = control.request_read(0x2300..0x2304);
+        assert!(after.try_recv().is_err());
+        frame_boundary(&control, &mut core);
+        assert_eq!(before.try_recv().unwrap(), vec![0x00, 0xAB, 0xCD, 0x00]);
+        assert_eq!(after.try_recv().unwrap(), vec![0x00, 0xAB, 0xCD, 0x00]);
+        assert_eq!(core.read_memory(0x2302), 0xCD);
+    }
+
+    #[test]
+    fn test_requests_serviced_while_paused() {
+        let control = SharedControl::new();
+        let mut core = EmulatorCore::new(test_rom::rom(), InputState::default());
+        core.step_frame();
+        control.set_paused(true);
+        control.queue_write(0x2380, vec![0x42]);
+        let read = control.request_read(0x2380..0x2381);
+        /* A paused thread still drains the queue every pass without stepping a frame */
+        frame_boundary(&control, &mut core);
+        assert_eq!(read.try_recv().unwrap(), vec![0x42]);
+        assert_eq!(core.frame(), 1);
+    }
+
+    #[test]
+    fn test_dropped_requester_is_ignored() {
+        let control = SharedControl::new();
+        let mut core = EmulatorCore::new(test_rom::rom(), InputState::default());
+        drop(control.request_read(0x2000..0x2400));
+        frame_boundary(&control, &mut core);
+    }
+}
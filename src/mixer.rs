@@ -0,0 +1,251 @@
+pub const SAMPLE_RATE: u32 = 44_100;
+/* Above this speed looping sounds stop */
+pub const LOOP_MAX_SPEED: f32 = 1.5;
+/* Above this speed everything is muted but a soft tick once a second, so silence reads as deliberate */
+pub const MUTE_SPEED: f32 = 2.0;
+const TICK_SAMPLES: usize = 441;
+const TICK_LEVEL: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpeedAction {
+    Play,
+    Skip,
+}
+
+/* Whether a sound's trigger plays at the given emulation speed. Between 1x and 2x one-shots keep their
+   pitch and a re-trigger that would overlap the sound still playing is dropped, so the march speeds up
+   because its triggers come faster rather than piling up */
+pub fn speed_action(speed: f32, looping: bool, playing: bool) -> SpeedAction {
+    if speed > MUTE_SPEED || (looping && speed > LOOP_MAX_SPEED) || playing {
+        return SpeedAction::Skip;
+    }
+    return SpeedAction::Play;
+}
+
+struct Voice {
+    sound: usize,
+    position: usize,
+    /* Looping sounds repeat until their port bit is cleared */
+    held: bool,
+}
+
+/* Mono software mixer; every sound has its own voice so simultaneous triggers are summed instead of queued */
+pub struct Mixer {
+    samples: Vec<Option<Vec<f32>>>,
+    voices: Vec<Voice>,
+    looping: Vec<usize>,
+    volume: f32,
+    speed: f32,
+    /* Samples until the next muted-speed tick */
+    tick_countdown: usize,
+}
+
+impl Mixer {
+    pub fn new(samples: Vec<Option<Vec<f32>>>) -> Self {
+        Self { samples, voices: vec![], looping: vec![], volume: 1.0, speed: 1.0, tick_countdown: 0 }
+    }
+
+    pub fn set_looping(&mut self, sound: usize) {
+        self.looping.push(sound);
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    /* Emulated frames per real frame; voices the new speed wouldn't have started are cut off */
+    pub fn set_speed(&mut self, speed: f32) {
+        if speed > MUTE_SPEED && self.speed <= MUTE_SPEED {
+            self.tick_countdown = 0;
+        }
+        self.speed = speed;
+        let looping = &self.looping;
+        self.voices.retain(|voice| speed_action(speed, looping.contains(&voice.sound), false) == SpeedAction::Play);
+    }
+
+    /* A sound that is still playing is not restarted, matching the hardware's one-shot circuits */
+    pub fn trigger(&mut self, sound: usize) {
+        let loaded = self.samples.get(sound).map_or(false, |samples| samples.is_some());
+        let looping = self.looping.contains(&sound);
+        if loaded && speed_action(self.speed, looping, self.is_playing(sound)) == SpeedAction::Play {
+            self.voices.push(Voice { sound, position: 0, held: looping });
+        }
+    }
+
+    /* One-shot sounds ignore their falling edge; looping ones stop on it */
+    pub fn release(&mut self, sound: usize) {
+        if self.looping.contains(&sound) {
+            self.voices.retain(|voice| voice.sound != sound);
+        }
+    }
+
+    pub fn is_playing(&self, sound: usize) -> bool {
+        return self.voices.iter().any(|voice| voice.sound == sound);
+    }
+
+    pub fn render(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        for voice in self.voices.iter_mut() {
+            let samples = self.samples[voice.sound].as_ref().unwrap();
+            let mut written = 0;
+            while written < out.len() && voice.position < samples.len() {
+                let count = (out.len() - written).min(samples.len() - voice.position);
+                for (sample, source) in out[written..].iter_mut().zip(&samples[voice.position..voice.position + count]) {
+                    *sample += source;
+                }
+                written += count;
+                voice.position += count;
+                if voice.held && voice.position == samples.len() {
+                    voice.position = 0;
+                }
+            }
+        }
+        let samples = &self.samples;
+        self.voices.retain(|voice| voice.position < samples[voice.sound].as_ref().unwrap().len());
+        if self.speed > MUTE_SPEED {
+            self.add_ticks(out);
+        }
+        for sample in out.iter_mut() {
+            *sample = (*sample * self.volume).clamp(-1.0, 1.0);
+        }
+    }
+
+    /* A short decaying click at the start of every second of output */
+    fn add_ticks(&mut self, out: &mut [f32]) {
+        let second = SAMPLE_RATE as usize;
+        for sample in out.iter_mut() {
+            let age = (second - self.tick_countdown) % second;
+            if age < TICK_SAMPLES {
+                *sample += TICK_LEVEL * (1.0 - age as f32 / TICK_SAMPLES as f32);
+            }
+            self.tick_countdown = if self.tick_countdown == 0 { second - 1 } else { self.tick_countdown - 1 };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_sounds() -> Mixer {
+        Mixer::new(vec![Some(vec![0.25; 4]), Some(vec![0.5; 2]), None])
+    }
+
+    #[test]
+    fn test_simultaneous_sounds_are_summed() {
+        let mut mixer = two_sounds();
+        mixer.trigger(0);
+        mixer.trigger(1);
+        let mut out = [1.0; 6];
+        mixer.render(&mut out);
+        assert_eq!(out, [0.75, 0.75, 0.25, 0.25, 0.0, 0.0]);
+        assert!(!mixer.is_playing(0) && !mixer.is_playing(1));
+    }
+
+    #[test]
+    fn test_retrigger_while_playing_is_ignored() {
+        let mut mixer = two_sounds();
+        mixer.trigger(0);
+        let mut out = [0.0; 2];
+        mixer.render(&mut out);
+        mixer.trigger(0);
+        mixer.render(&mut out);
+        assert_eq!(out, [0.25, 0.25]);
+        mixer.render(&mut out);
+        assert_eq!(out, [0.0, 0.0]);
+        mixer.trigger(0);
+        assert!(mixer.is_playing(0));
+    }
+
+    #[test]
+    fn test_missing_sample_and_clipping() {
+        let mut mixer = Mixer::new(vec![Some(vec![0.75]), Some(vec![0.75])]);
+        mixer.trigger(0);
+        mixer.trigger(1);
+        mixer.trigger(7);
+        let mut out = [0.0; 1];
+        mixer.render(&mut out);
+        assert_eq!(out, [1.0]);
+        let mut missing = two_sounds();
+        missing.trigger(2);
+        assert!(!missing.is_playing(2));
+    }
+
+    #[test]
+    fn test_looping_sound_repeats_until_released() {
+        let mut mixer = Mixer::new(vec![Some(vec![0.25, 0.5]), Some(vec![0.5])]);
+        mixer.set_looping(0);
+        mixer.trigger(0);
+        mixer.trigger(1);
+        let mut out = [0.0; 5];
+        mixer.render(&mut out);
+        assert_eq!(out, [0.75, 0.5, 0.25, 0.5, 0.25]);
+        mixer.release(1);
+        mixer.release(0);
+        assert!(!mixer.is_playing(0));
+    }
+
+    #[test]
+    fn test_speed_action_by_speed_and_sound() {
+        use SpeedAction::{Play, Skip};
+        /* (speed, looping, already playing) */
+        let cases = [
+            ((1.0, false, false), Play),
+            ((1.0, true, false), Play),
+            ((1.0, false, true), Skip),
+            ((1.5, true, false), Play),
+            ((1.75, false, false), Play),
+            ((1.75, false, true), Skip),
+            ((1.75, true, false), Skip),
+            ((2.0, false, false), Play),
+            ((3.0, false, false), Skip),
+            ((4.0, true, false), Skip),
+        ];
+        for ((speed, looping, playing), expected) in cases {
+            assert_eq!(speed_action(speed, looping, playing), expected, "{}x looping={} playing={}", speed, looping, playing);
+        }
+    }
+
+    #[test]
+    fn test_speed_change_cuts_voices() {
+        let mut mixer = Mixer::new(vec![Some(vec![0.25; 8]), Some(vec![0.5; 8])]);
+        mixer.set_looping(0);
+        mixer.trigger(0);
+        mixer.trigger(1);
+        mixer.set_speed(1.75);
+        assert!(!mixer.is_playing(0) && mixer.is_playing(1));
+        mixer.trigger(0);
+        assert!(!mixer.is_playing(0));
+        mixer.set_speed(4.0);
+        assert!(!mixer.is_playing(1));
+        mixer.set_speed(1.0);
+        mixer.trigger(0);
+        assert!(mixer.is_playing(0));
+    }
+
+    #[test]
+    fn test_muted_speed_ticks_once_a_second() {
+        let mut mixer = two_sounds();
+        mixer.set_speed(3.0);
+        mixer.trigger(0);
+        let mut out = vec![0.0; SAMPLE_RATE as usize * 2];
+        mixer.render(&mut out);
+        let ticks: Vec<usize> = (0..out.len()).filter(|i| out[*i] == TICK_LEVEL).collect();
+        assert_eq!(ticks, vec![0, SAMPLE_RATE as usize]);
+        assert_eq!(out.iter().filter(|sample| **sample > 0.0).count(), 2 * TICK_SAMPLES);
+        mixer.set_speed(1.0);
+        mixer.render(&mut out);
+        assert!(out.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn test_volume_scales_mix() {
+        let mut mixer = two_sounds();
+        mixer.set_volume(0.5);
+        mixer.trigger(0);
+        mixer.trigger(1);
+        let mut out = [0.0; 3];
+        mixer.render(&mut out);
+        assert_eq!(out, [0.375, 0.375, 0.125]);
+    }
+}
@@ -0,0 +1,214 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use crate::emulator_core::EmulatorCore;
+use crate::machine::InputState;
+use crate::ram_map;
+
+/* Frame stream for reinforcement-learning clients (--ml-pipe). Every emulated frame is sent as one
+   packet, at full speed with no throttling. All integers are little-endian.
+
+   offset  size  field
+        0     4  magic "SIML"
+        4     1  protocol version (1)
+        5     1  payload format: 0 = VRAM bits, 1 = grayscale
+        6     2  reserved, zero
+        8     8  frame number
+       16     4  player 1 score
+       20     4  player 2 score
+       24     1  player 1 ships remaining
+       25     1  game over: 1 while no game is in progress (attract mode), else 0
+       26     2  reserved, zero
+       28     4  payload length in bytes
+       32     -  payload
+
+   VRAM bits: the 7,168 video RAM bytes as stored by the hardware, 224 scanlines of 32 bytes with the
+   least significant bit first along each scanline.
+   Grayscale: the upright 224 x 256 picture as 256 rows of 224 bytes, top row first, 0 or 255 per pixel. */
+pub const MAGIC: &[u8; 4] = b"SIML";
+pub const VERSION: u8 = 1;
+pub const HEADER_SIZE: usize = 32;
+pub const WIDTH: usize = 224;
+pub const HEIGHT: usize = 256;
+const VRAM_SIZE: usize = 7_168;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameFormat {
+    Bits,
+    Grayscale,
+}
+
+impl FrameFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bits" => Some(FrameFormat::Bits),
+            "gray" => Some(FrameFormat::Grayscale),
+            _ => None,
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            FrameFormat::Bits => 0,
+            FrameFormat::Grayscale => 1,
+        }
+    }
+
+    pub fn payload_size(&self) -> usize {
+        match self {
+            FrameFormat::Bits => VRAM_SIZE,
+            FrameFormat::Grayscale => WIDTH * HEIGHT,
+        }
+    }
+}
+
+/* Encodes packets into one buffer allocated up front and reused for every frame */
+pub struct FrameEncoder {
+    format: FrameFormat,
+    packet: Vec<u8>,
+}
+
+impl FrameEncoder {
+    pub fn new(format: FrameFormat) -> Self {
+        Self { format, packet: vec![0; HEADER_SIZE + format.payload_size()] }
+    }
+
+    pub fn encode(&mut self, frame: u64, vram: &[u8], ram: &[u8]) -> &[u8] {
+        let game_over = ram_map::read(ram, ram_map::GAME_MODE) & 0x01 == 0;
+        let header = &mut self.packet[..HEADER_SIZE];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4] = VERSION;
+        header[5] = self.format.code();
+        header[6..8].fill(0);
+        header[8..16].copy_from_slice(&frame.to_le_bytes());
+        header[16..20].copy_from_slice(&ram_map::read_score(ram, ram_map::P1_SCORE).to_le_bytes());
+        header[20..24].copy_from_slice(&ram_map::read_score(ram, ram_map::P2_SCORE).to_le_bytes());
+        header[24] = ram_map::read(ram, ram_map::P1_SHIPS);
+        header[25] = game_over as u8;
+        header[26..28].fill(0);
+        header[28..32].copy_from_slice(&(self.format.payload_size() as u32).to_le_bytes());
+
+        let payload = &mut self.packet[HEADER_SIZE..];
+        match self.format {
+            FrameFormat::Bits => payload.copy_from_slice(vram),
+            FrameFormat::Grayscale => {
+                for (index, byte) in vram.iter().enumerate() {
+                    let (x, scan_byte) = (index / 32, index % 32);
+                    for bit in 0..8 {
+                        let y = HEIGHT - 1 - (scan_byte * 8 + bit);
+                        payload[y * WIDTH + x] = if byte >> bit & 1 == 1 { 255 } else { 0 };
+                    }
+                }
+            },
+        }
+        return &self.packet;
+    }
+}
+
+/* Steps the core and writes a packet per frame until `frames` have been sent or the reader goes away */
+pub fn stream(core: &mut EmulatorCore, format: FrameFormat, writer: &mut impl Write, frames: Option<u64>) -> io::Result<()> {
+    let mut encoder = FrameEncoder::new(format);
+    let mut sent = 0;
+    while frames.map_or(true, |frames| sent < frames) {
+        core.step_frame();
+        let packet = encoder.encode(core.frame(), &core.vram(), &core.ram());
+        match writer.write_all(packet) {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
+        sent += 1;
+    }
+    return writer.flush();
+}
+
+/* Headless entry point: `path` is an existing FIFO / named pipe, or "-" for stdout */
+pub fn run_pipe(path: &str, format: FrameFormat, rom: [u8; crate::machine::ROM_SIZE]) -> io::Result<()> {
+    let mut core = EmulatorCore::new(rom, InputState::default());
+    if path == "-" {
+        return stream(&mut core, format, &mut io::stdout().lock(), None);
+    }
+    let mut pipe = OpenOptions::new().write(true).open(path)?;
+    return stream(&mut core, format, &mut pipe, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use crate::machine;
+
+    fn ram_with(values: &[(u16, u8)]) -> Vec<u8> {
+        let mut ram = vec![0; ram_map::WORK_RAM_SIZE];
+        for (addr, value) in values {
+            ram[(addr - ram_map::WORK_RAM_START) as usize] = *value;
+        }
+        return ram;
+    }
+
+    #[test]
+    fn test_header_layout() {
+        let ram = ram_with(&[(ram_map::P1_SCORE, 0x50), (ram_map::P1_SCORE + 1, 0x12), (ram_map::P2_SCORE, 0x20), (ram_map::P1_SHIPS, 3), (ram_map::GAME_MODE, 1)]);
+        let mut encoder = FrameEncoder::new(FrameFormat::Bits);
+        let packet = encoder.encode(0x0102_0304_0506_0708, &[0xAA; VRAM_SIZE], &ram);
+        assert_eq!(packet.len(), HEADER_SIZE + VRAM_SIZE);
+        assert_eq!(&packet[0..8], &[b'S', b'I', b'M', b'L', 1, 0, 0, 0]);
+        assert_eq!(&packet[8..16], &[0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(&packet[16..20], &1_250u32.to_le_bytes());
+        assert_eq!(&packet[20..24], &20u32.to_le_bytes());
+        assert_eq!(&packet[24..28], &[3, 0, 0, 0]);
+        assert_eq!(&packet[28..32], &(VRAM_SIZE as u32).to_le_bytes());
+        assert!(packet[HEADER_SIZE..].iter().all(|byte| *byte == 0xAA));
+        let attract = encoder.encode(0, &[0; VRAM_SIZE], &ram_with(&[])).to_vec();
+        assert_eq!(attract[25], 1);
+    }
+
+    #[test]
+    fn test_grayscale_is_upright() {
+        let mut vram = [0; VRAM_SIZE];
+        /* First scanline, first bit: the bottom-left pixel of the upright picture */
+        vram[0] = 0x01;
+        /* Last scanline, last bit: the top-right pixel */
+        vram[VRAM_SIZE - 1] = 0x80;
+        let mut encoder = FrameEncoder::new(FrameFormat::Grayscale);
+        let payload = encoder.encode(0, &vram, &ram_with(&[]))[HEADER_SIZE..].to_vec();
+        assert_eq!(payload.len(), WIDTH * HEIGHT);
+        assert_eq!(payload[(HEIGHT - 1) * WIDTH], 255);
+        assert_eq!(payload[WIDTH - 1], 255);
+        assert_eq!(payload.iter().filter(|pixel| **pixel == 255).count(), 2);
+    }
+
+    /* Adds 10 to the BCD player 1 score on every VBlank interrupt */
+    fn scoring_rom() -> [u8; machine::ROM_SIZE] {
+        let mut rom = [0; machine::ROM_SIZE];
+        // LXI SP,$2400; EI; JMP $0004
+        rom[0x00..0x07].copy_from_slice(&[0x31, 0x00, 0x24, 0xFB, 0xC3, 0x04, 0x00]);
+        // RST 1: EI; RET
+        rom[0x08..0x0A].copy_from_slice(&[0xFB, 0xC9]);
+        // RST 2: PUSH PSW; LDA $20F8; ADI $10; DAA; STA $20F8; POP PSW; EI; RET
+        rom[0x10..0x1E].copy_from_slice(&[0xF5, 0x3A, 0xF8, 0x20, 0xC6, 0x10, 0x27, 0x32, 0xF8, 0x20, 0xF1, 0xFB, 0xC9, 0x00]);
+        return rom;
+    }
+
+    #[test]
+    fn test_loopback_score_changes_each_frame() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut receiver, _) = listener.accept().unwrap();
+        let writer = std::thread::spawn(move || {
+            let mut core = EmulatorCore::new(scoring_rom(), InputState::default());
+            stream(&mut core, FrameFormat::Bits, &mut sender, Some(5)).unwrap();
+        });
+        let mut scores = vec![];
+        let mut packet = vec![0; HEADER_SIZE + VRAM_SIZE];
+        for frame in 1..=5u64 {
+            receiver.read_exact(&mut packet).unwrap();
+            assert_eq!(&packet[0..4], MAGIC);
+            assert_eq!(u64::from_le_bytes(packet[8..16].try_into().unwrap()), frame);
+            scores.push(u32::from_le_bytes(packet[16..20].try_into().unwrap()));
+        }
+        writer.join().unwrap();
+        for pair in scores.windows(2) {
+            assert_eq!(pair[1], pair[0] + 10);
+        }
+    }
+}
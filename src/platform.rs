@@ -0,0 +1,341 @@
+// Best-effort OS scheduling hints for the emulation thread, the one file attribute check the loaders
+// need, and the monitor list for fullscreen. All unsafe and OS calls live here; targets without an
+// implementation get the fallback, which reports Unsupported and changes nothing.
+use std::io;
+use std::path::Path;
+use crate::displays::Monitor;
+
+/* Core Graphics lays displays out in points; Windows reports physical pixels */
+pub const MONITORS_IN_POINTS: bool = cfg!(target_os = "macos");
+
+/* Slightly above normal priority for the calling thread */
+pub fn raise_thread_priority() -> io::Result<()> {
+    return imp::raise_thread_priority();
+}
+
+/* Keeps the calling thread off efficiency cores on hybrid CPUs; Ok without changes on uniform ones */
+pub fn prefer_performance_cores() -> io::Result<()> {
+    return imp::prefer_performance_cores();
+}
+
+/* Whether the file carries macOS's download quarantine attribute; always false elsewhere */
+pub fn quarantined(path: &Path) -> bool {
+    return imp::quarantined(path);
+}
+
+/* Connected displays; empty where they can't be listed (Linux, where winit's list isn't reachable through eframe), which hides the fullscreen display setting */
+pub fn monitors() -> Vec<Monitor> {
+    return imp::monitors();
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::ffi::c_void;
+    use std::io;
+
+    use crate::displays::Monitor;
+
+    const THREAD_PRIORITY_ABOVE_NORMAL: i32 = 1;
+    /* THREAD_INFORMATION_CLASS ThreadPowerThrottling */
+    const THREAD_POWER_THROTTLING: i32 = 3;
+    const THREAD_POWER_THROTTLING_CURRENT_VERSION: u32 = 1;
+    const THREAD_POWER_THROTTLING_EXECUTION_SPEED: u32 = 1;
+
+    #[repr(C)]
+    struct ThreadPowerThrottlingState {
+        version: u32,
+        control_mask: u32,
+        state_mask: u32,
+    }
+
+    const MONITORINFOF_PRIMARY: u32 = 1;
+
+    #[repr(C)]
+    struct Rect {
+        left: i32,
+        top: i32,
+        right: i32,
+        bottom: i32,
+    }
+
+    /* MONITORINFOEXW */
+    #[repr(C)]
+    struct MonitorInfo {
+        size: u32,
+        monitor: Rect,
+        work: Rect,
+        flags: u32,
+        device: [u16; 32],
+    }
+
+    pub use super::fallback::quarantined;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+        fn SetThreadInformation(thread: *mut c_void, class: i32, information: *const c_void, size: u32) -> i32;
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn EnumDisplayMonitors(dc: *mut c_void, clip: *const Rect, callback: extern "system" fn(*mut c_void, *mut c_void, *mut Rect, isize) -> i32, data: isize) -> i32;
+        fn GetMonitorInfoW(monitor: *mut c_void, info: *mut MonitorInfo) -> i32;
+    }
+
+    pub fn raise_thread_priority() -> io::Result<()> {
+        if unsafe { SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_ABOVE_NORMAL) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    /* Opting out of execution speed throttling (EcoQoS) is what steers the scheduler to performance cores */
+    pub fn prefer_performance_cores() -> io::Result<()> {
+        let state = ThreadPowerThrottlingState {
+            version: THREAD_POWER_THROTTLING_CURRENT_VERSION,
+            control_mask: THREAD_POWER_THROTTLING_EXECUTION_SPEED,
+            state_mask: 0,
+        };
+        let size = std::mem::size_of::<ThreadPowerThrottlingState>() as u32;
+        if unsafe { SetThreadInformation(GetCurrentThread(), THREAD_POWER_THROTTLING, &state as *const _ as *const c_void, size) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    extern "system" fn collect_monitor(monitor: *mut c_void, _dc: *mut c_void, _rect: *mut Rect, data: isize) -> i32 {
+        let monitors = unsafe { &mut *(data as *mut Vec<Monitor>) };
+        let mut info = MonitorInfo {
+            size: std::mem::size_of::<MonitorInfo>() as u32,
+            monitor: Rect { left: 0, top: 0, right: 0, bottom: 0 },
+            work: Rect { left: 0, top: 0, right: 0, bottom: 0 },
+            flags: 0,
+            device: [0; 32],
+        };
+        if unsafe { GetMonitorInfoW(monitor, &mut info) } != 0 {
+            let length = info.device.iter().position(|unit| *unit == 0).unwrap_or(info.device.len());
+            let area = &info.monitor;
+            monitors.push(Monitor {
+                name: String::from_utf16_lossy(&info.device[..length]),
+                position: [area.left, area.top],
+                size: [(area.right - area.left) as u32, (area.bottom - area.top) as u32],
+                primary: info.flags & MONITORINFOF_PRIMARY != 0,
+            });
+        }
+        /* Non-zero carries on with the next monitor */
+        return 1;
+    }
+
+    /* Physical pixels, since winit makes the process per-monitor DPI aware */
+    pub fn monitors() -> Vec<Monitor> {
+        let mut monitors: Vec<Monitor> = vec![];
+        unsafe { EnumDisplayMonitors(std::ptr::null_mut(), std::ptr::null(), collect_monitor, &mut monitors as *mut Vec<Monitor> as isize) };
+        return monitors;
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::ffi::{c_int, c_uint, c_ulong};
+    use std::io;
+
+    const PRIO_PROCESS: c_int = 0;
+    /* Unprivileged processes may only get here if RLIMIT_NICE allows it */
+    const NICE: c_int = -5;
+    const CPU_SET_WORDS: usize = 1_024 / 64;
+
+    pub use super::fallback::{monitors, quarantined};
+
+    extern "C" {
+        fn setpriority(which: c_int, who: c_uint, priority: c_int) -> c_int;
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const c_ulong) -> c_int;
+    }
+
+    /* On Linux the nice value is per thread, and who = 0 means the calling one */
+    pub fn raise_thread_priority() -> io::Result<()> {
+        if unsafe { setpriority(PRIO_PROCESS, 0, NICE) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    pub fn prefer_performance_cores() -> io::Result<()> {
+        let cpus = super::fastest_cpus(&max_frequencies());
+        if cpus.is_empty() {
+            return Ok(());
+        }
+        let mut mask = [0 as c_ulong; CPU_SET_WORDS];
+        for cpu in cpus.into_iter().filter(|cpu| *cpu < CPU_SET_WORDS * 64) {
+            mask[cpu / 64] |= 1 << (cpu % 64);
+        }
+        if unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    /* (cpu, kHz) from cpufreq; empty in VMs and containers without it */
+    fn max_frequencies() -> Vec<(usize, u64)> {
+        let Ok(entries) = std::fs::read_dir("/sys/devices/system/cpu") else {
+            return vec![];
+        };
+        return entries.flatten().filter_map(|entry| {
+            let cpu = entry.file_name().to_str()?.strip_prefix("cpu")?.parse().ok()?;
+            let khz = std::fs::read_to_string(entry.path().join("cpufreq/cpuinfo_max_freq")).ok()?.trim().parse().ok()?;
+            Some((cpu, khz))
+        }).collect();
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::ffi::{c_char, c_int, c_void, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use crate::displays::Monitor;
+
+    const QOS_CLASS_USER_INTERACTIVE: u32 = 0x21;
+    const QUARANTINE_ATTRIBUTE: &[u8] = b"com.apple.quarantine\0";
+    const MAX_DISPLAYS: usize = 16;
+
+    #[repr(C)]
+    struct CGRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    extern "C" {
+        fn pthread_set_qos_class_self_np(class: u32, relative_priority: c_int) -> c_int;
+        fn getxattr(path: *const c_char, name: *const c_char, value: *mut c_void, size: usize, position: u32, options: c_int) -> isize;
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGGetActiveDisplayList(max: u32, displays: *mut u32, count: *mut u32) -> i32;
+        fn CGDisplayBounds(display: u32) -> CGRect;
+        fn CGMainDisplayID() -> u32;
+    }
+
+    /* macOS has no affinity API; the top QoS class both raises priority and favours performance cores */
+    pub fn raise_thread_priority() -> io::Result<()> {
+        let error = unsafe { pthread_set_qos_class_self_np(QOS_CLASS_USER_INTERACTIVE, 0) };
+        if error != 0 {
+            return Err(io::Error::from_raw_os_error(error));
+        }
+        return Ok(());
+    }
+
+    pub fn prefer_performance_cores() -> io::Result<()> {
+        return raise_thread_priority();
+    }
+
+    /* With no buffer getxattr returns the value's size, or -1 when the attribute isn't there */
+    pub fn quarantined(path: &Path) -> bool {
+        let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        let size = unsafe { getxattr(path.as_ptr(), QUARANTINE_ATTRIBUTE.as_ptr() as *const c_char, std::ptr::null_mut(), 0, 0, 0) };
+        return size >= 0;
+    }
+
+    /* Display IDs stand in for names: they stay the same for a given screen across replugs and restarts */
+    pub fn monitors() -> Vec<Monitor> {
+        let mut displays = [0u32; MAX_DISPLAYS];
+        let mut count = 0;
+        if unsafe { CGGetActiveDisplayList(MAX_DISPLAYS as u32, displays.as_mut_ptr(), &mut count) } != 0 {
+            return vec![];
+        }
+        let main = unsafe { CGMainDisplayID() };
+        return displays[..count as usize].iter().map(|&display| {
+            let bounds = unsafe { CGDisplayBounds(display) };
+            Monitor {
+                name: format!("Display {}", display),
+                position: [bounds.x as i32, bounds.y as i32],
+                size: [bounds.width as u32, bounds.height as u32],
+                primary: display == main,
+            }
+        }).collect();
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+use fallback as imp;
+
+#[cfg_attr(any(target_os = "windows", target_os = "linux", target_os = "macos"), allow(dead_code))]
+mod fallback {
+    use std::io;
+    use std::path::Path;
+    use crate::displays::Monitor;
+
+    pub fn raise_thread_priority() -> io::Result<()> {
+        return Err(io::ErrorKind::Unsupported.into());
+    }
+
+    pub fn prefer_performance_cores() -> io::Result<()> {
+        return Err(io::ErrorKind::Unsupported.into());
+    }
+
+    pub fn quarantined(_path: &Path) -> bool {
+        return false;
+    }
+
+    pub fn monitors() -> Vec<Monitor> {
+        return vec![];
+    }
+}
+
+/* The CPUs with the highest maximum clock, or none when they all match and there is nothing to prefer */
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn fastest_cpus(max_frequencies: &[(usize, u64)]) -> Vec<usize> {
+    let Some(fastest) = max_frequencies.iter().map(|(_, khz)| *khz).max() else {
+        return vec![];
+    };
+    if max_frequencies.iter().all(|(_, khz)| *khz == fastest) {
+        return vec![];
+    }
+    let mut cpus: Vec<usize> = max_frequencies.iter().filter(|(_, khz)| *khz == fastest).map(|(cpu, _)| *cpu).collect();
+    cpus.sort();
+    return cpus;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fallback_reports_unsupported() {
+        assert_eq!(fallback::raise_thread_priority().unwrap_err().kind(), io::ErrorKind::Unsupported);
+        assert_eq!(fallback::prefer_performance_cores().unwrap_err().kind(), io::ErrorKind::Unsupported);
+        assert!(!fallback::quarantined(Path::new("Cargo.toml")));
+        assert!(fallback::monitors().is_empty());
+    }
+
+    #[test]
+    fn test_monitors_are_well_formed() {
+        let monitors = monitors();
+        assert!(monitors.iter().all(|monitor| monitor.size[0] > 0 && monitor.size[1] > 0));
+        assert!(monitors.iter().filter(|monitor| monitor.primary).count() <= 1);
+    }
+
+    /* Whether the OS allows it depends on the machine; the calls must just return */
+    #[test]
+    fn test_hints_return_on_this_target() {
+        std::thread::spawn(|| {
+            let _ = raise_thread_priority();
+            let _ = prefer_performance_cores();
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn test_fastest_cpus() {
+        assert_eq!(fastest_cpus(&[]), Vec::<usize>::new());
+        assert_eq!(fastest_cpus(&[(0, 3_000_000), (1, 3_000_000)]), Vec::<usize>::new());
+        /* A hybrid part: two performance cores listed out of order among efficiency cores */
+        let hybrid = [(3, 2_400_000), (1, 4_700_000), (2, 2_400_000), (0, 4_700_000)];
+        assert_eq!(fastest_cpus(&hybrid), vec![0, 1]);
+    }
+}
@@ -0,0 +1,279 @@
+// Rack speed-up measurement: the invaders step faster as they die, and how fast depends on interrupt
+// timing, so the movement cadence per alien count is an end-to-end timing check.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::emulator_core::EmulatorCore;
+use crate::machine::{self, InputShaper, InputState, LogicalInput};
+use crate::macros::{Macro, MacroPlayer, MacroStep};
+use crate::ram_map;
+
+pub const MEASURE_FRAMES: u64 = 60 * 60 * 5;
+/* Frames per rack step may differ from the reference by this much before it counts as a deviation */
+pub const DEFAULT_TOLERANCE: f32 = 0.5;
+
+const COIN_FRAME: u64 = 120;
+const START_FRAME: u64 = 240;
+const PLAY_FRAME: u64 = 360;
+/* One sweep of the pattern: fire every FIRE_PERIOD frames while moving right then left */
+const SWEEP_FRAMES: u64 = 96;
+const FIRE_PERIOD: u64 = 32;
+const FIRE_FRAMES: u64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RackSample {
+    pub frame: u64,
+    pub wave: u8,
+    pub aliens: u8,
+    pub x: u8,
+}
+
+/* Median frames between rack steps while this many aliens were left */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CadenceRow {
+    pub wave: u8,
+    pub aliens: u8,
+    pub frames_per_step: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deviation {
+    pub wave: u8,
+    pub aliens: u8,
+    pub expected: f32,
+    /* None when the run never saw this alien count */
+    pub measured: Option<f32>,
+}
+
+/* Coin, one player start, then a fixed sweep-and-fire pattern until the frame budget runs out */
+pub fn script(frames: u64) -> Macro {
+    let mut steps = vec![
+        MacroStep { frame: COIN_FRAME, input: LogicalInput::Coin, pressed: true },
+        MacroStep { frame: COIN_FRAME + 1, input: LogicalInput::Coin, pressed: false },
+        MacroStep { frame: START_FRAME, input: LogicalInput::P1Start, pressed: true },
+        MacroStep { frame: START_FRAME + FIRE_FRAMES, input: LogicalInput::P1Start, pressed: false },
+    ];
+    let mut frame = PLAY_FRAME;
+    while frame + SWEEP_FRAMES <= frames {
+        steps.push(MacroStep { frame, input: LogicalInput::P1Right, pressed: true });
+        steps.push(MacroStep { frame: frame + SWEEP_FRAMES / 2, input: LogicalInput::P1Right, pressed: false });
+        steps.push(MacroStep { frame: frame + SWEEP_FRAMES / 2, input: LogicalInput::P1Left, pressed: true });
+        steps.push(MacroStep { frame: frame + SWEEP_FRAMES, input: LogicalInput::P1Left, pressed: false });
+        let mut fire = frame;
+        while fire < frame + SWEEP_FRAMES {
+            steps.push(MacroStep { frame: fire, input: LogicalInput::P1Fire, pressed: true });
+            steps.push(MacroStep { frame: fire + FIRE_FRAMES, input: LogicalInput::P1Fire, pressed: false });
+            fire += FIRE_PERIOD;
+        }
+        frame += SWEEP_FRAMES;
+    }
+    steps.sort_by_key(|step| step.frame);
+    return Macro { name: "rack measurement".to_string(), key: None, steps, speed: vec![] };
+}
+
+pub fn sample(frame: u64, ram: &[u8]) -> RackSample {
+    RackSample {
+        frame,
+        wave: ram_map::read(ram, ram_map::P1_RACK_COUNT),
+        aliens: ram_map::read(ram, ram_map::NUM_ALIENS),
+        x: ram_map::read(ram, ram_map::REF_ALIEN_X),
+    }
+}
+
+/* Plays the script from reset with the same input shaping as the emulation thread */
+pub fn measure(rom: [u8; machine::ROM_SIZE], frames: u64) -> Vec<RackSample> {
+    let mut core = EmulatorCore::new(rom, InputState::default());
+    let mut player = MacroPlayer::new(&script(frames), 0);
+    let mut shaper = InputShaper::default();
+    let mut samples = Vec::with_capacity(frames as usize);
+    for frame in 0..frames {
+        core.set_input(shaper.shape(&player.input_at(frame)));
+        core.step_frame();
+        samples.push(sample(frame, &core.ram()));
+    }
+    return samples;
+}
+
+pub fn cadence(samples: &[RackSample]) -> Vec<CadenceRow> {
+    let mut intervals: BTreeMap<(u8, u8), Vec<u64>> = BTreeMap::new();
+    let mut last_step: Option<RackSample> = None;
+    for pair in samples.windows(2) {
+        let (before, after) = (pair[0], pair[1]);
+        /* A new wave redraws the rack; the first step after that has no interval */
+        if after.wave != before.wave {
+            last_step = None;
+            continue;
+        }
+        if after.x == before.x {
+            continue;
+        }
+        if let Some(step) = last_step {
+            if step.aliens == after.aliens {
+                intervals.entry((after.wave, after.aliens)).or_default().push(after.frame - step.frame);
+            }
+        }
+        last_step = Some(after);
+    }
+    return intervals
+        .into_iter()
+        .map(|((wave, aliens), mut frames)| {
+            frames.sort();
+            let middle = frames.len() / 2;
+            let frames_per_step = if frames.len() % 2 == 0 {
+                (frames[middle - 1] + frames[middle]) as f32 / 2.0
+            } else {
+                frames[middle] as f32
+            };
+            CadenceRow { wave, aliens, frames_per_step }
+        })
+        .collect();
+}
+
+/* One "wave aliens frames_per_step" row per line; # starts a comment */
+pub fn format_table(rows: &[CadenceRow]) -> String {
+    let mut table = String::from("# wave aliens frames_per_step\n");
+    for row in rows {
+        writeln!(table, "{} {} {:.1}", row.wave, row.aliens, row.frames_per_step).ok();
+    }
+    return table;
+}
+
+pub fn parse_table(text: &str) -> Result<Vec<CadenceRow>, String> {
+    let mut rows = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let row = match fields.as_slice() {
+            [wave, aliens, frames] => match (wave.parse(), aliens.parse(), frames.parse()) {
+                (Ok(wave), Ok(aliens), Ok(frames_per_step)) => Some(CadenceRow { wave, aliens, frames_per_step }),
+                _ => None,
+            },
+            _ => None,
+        };
+        rows.push(row.ok_or_else(|| format!("line {}: expected \"wave aliens frames_per_step\"", number + 1))?);
+    }
+    return Ok(rows);
+}
+
+pub fn compare(measured: &[CadenceRow], reference: &[CadenceRow], tolerance: f32) -> Vec<Deviation> {
+    return reference
+        .iter()
+        .filter_map(|expected| {
+            let found = measured.iter().find(|row| row.wave == expected.wave && row.aliens == expected.aliens);
+            let measured = found.map(|row| row.frames_per_step);
+            match measured {
+                Some(frames) if (frames - expected.frames_per_step).abs() <= tolerance => None,
+                _ => Some(Deviation { wave: expected.wave, aliens: expected.aliens, expected: expected.frames_per_step, measured }),
+            }
+        })
+        .collect();
+}
+
+pub fn report(deviations: &[Deviation], reference: &[CadenceRow]) -> String {
+    let mut report = String::new();
+    let mut waves: Vec<u8> = reference.iter().map(|row| row.wave).collect();
+    waves.dedup();
+    for wave in waves {
+        let checked = reference.iter().filter(|row| row.wave == wave).count();
+        let failed: Vec<&Deviation> = deviations.iter().filter(|deviation| deviation.wave == wave).collect();
+        writeln!(report, "Wave {}: {}/{} alien counts within tolerance", wave, checked - failed.len(), checked).ok();
+        for deviation in failed {
+            match deviation.measured {
+                Some(measured) => writeln!(report, "  {:>2} aliens: expected {:.1} frames/step, measured {:.1}", deviation.aliens, deviation.expected, measured),
+                None => writeln!(report, "  {:>2} aliens: expected {:.1} frames/step, never reached", deviation.aliens, deviation.expected),
+            }
+            .ok();
+        }
+    }
+    return report;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(wave: u8, steps: &[(u8, u64)]) -> Vec<RackSample> {
+        /* (aliens, frames until the next step) */
+        let mut samples = vec![];
+        let (mut frame, mut x) = (0, 24);
+        for (aliens, interval) in steps {
+            for _ in 0..*interval {
+                samples.push(RackSample { frame, wave, aliens: *aliens, x });
+                frame += 1;
+            }
+            x += 2;
+        }
+        return samples;
+    }
+
+    #[test]
+    fn test_script_plays_coin_then_start() {
+        let script = script(PLAY_FRAME + SWEEP_FRAMES);
+        let mut player = MacroPlayer::new(&script, 0);
+        assert!(player.input_at(COIN_FRAME).coin);
+        assert!(!player.input_at(COIN_FRAME + 1).coin);
+        assert!(player.input_at(START_FRAME).p1_start);
+        let playing = player.input_at(PLAY_FRAME);
+        assert!(playing.p1_fire && playing.p1_right && !playing.p1_start);
+        assert!(player.input_at(PLAY_FRAME + SWEEP_FRAMES / 2).p1_left);
+        assert!(!player.input_at(PLAY_FRAME + SWEEP_FRAMES).get(LogicalInput::P1Left));
+    }
+
+    #[test]
+    fn test_cadence_is_median_per_alien_count() {
+        let rows = cadence(&samples(1, &[(55, 5), (55, 5), (55, 9), (55, 5), (55, 5), (54, 4), (54, 4), (54, 4)]));
+        /* The interval spanning the 55 -> 54 kill is not attributed to either count */
+        assert_eq!(rows, vec![CadenceRow { wave: 1, aliens: 54, frames_per_step: 4.0 }, CadenceRow { wave: 1, aliens: 55, frames_per_step: 5.0 }]);
+    }
+
+    #[test]
+    fn test_new_wave_restarts_intervals() {
+        let mut run = samples(0, &[(1, 3), (1, 3), (1, 3)]);
+        let next: Vec<RackSample> = samples(1, &[(55, 6), (55, 6), (55, 6)]).into_iter().map(|s| RackSample { frame: s.frame + 100, ..s }).collect();
+        run.extend(next);
+        let rows = cadence(&run);
+        assert_eq!(rows, vec![CadenceRow { wave: 0, aliens: 1, frames_per_step: 3.0 }, CadenceRow { wave: 1, aliens: 55, frames_per_step: 6.0 }]);
+    }
+
+    #[test]
+    fn test_table_round_trip() {
+        let rows = vec![CadenceRow { wave: 0, aliens: 55, frames_per_step: 5.5 }, CadenceRow { wave: 0, aliens: 1, frames_per_step: 1.0 }];
+        assert_eq!(parse_table(&format_table(&rows)), Ok(rows));
+        assert_eq!(parse_table("0 55\n"), Err("line 1: expected \"wave aliens frames_per_step\"".to_string()));
+    }
+
+    #[test]
+    fn test_compare_reports_per_wave() {
+        let reference = vec![
+            CadenceRow { wave: 0, aliens: 55, frames_per_step: 5.0 },
+            CadenceRow { wave: 0, aliens: 10, frames_per_step: 2.0 },
+            CadenceRow { wave: 1, aliens: 55, frames_per_step: 5.0 },
+        ];
+        let measured = vec![CadenceRow { wave: 0, aliens: 55, frames_per_step: 5.4 }, CadenceRow { wave: 1, aliens: 55, frames_per_step: 6.0 }];
+        let deviations = compare(&measured, &reference, DEFAULT_TOLERANCE);
+        assert_eq!(deviations.len(), 2);
+        let report = report(&deviations, &reference);
+        assert!(report.contains("Wave 0: 1/2 alien counts within tolerance"));
+        assert!(report.contains("10 aliens: expected 2.0 frames/step, never reached"));
+        assert!(report.contains("Wave 1: 0/1"));
+        assert!(report.contains("55 aliens: expected 5.0 frames/step, measured 6.0"));
+    }
+
+    #[test]
+    fn test_reference_table_parses() {
+        let reference = parse_table(include_str!("../rack_reference.txt")).unwrap();
+        assert!(reference.iter().all(|row| row.frames_per_step == row.aliens as f32));
+    }
+
+    #[test]
+    #[ignore = "needs the arcade ROMs; run with cargo test -- --ignored"]
+    fn test_rack_cadence_matches_reference() {
+        let rom = crate::application::load_rom().expect("the arcade ROMs");
+        let reference = parse_table(include_str!("../rack_reference.txt")).unwrap();
+        let measured = cadence(&measure(rom, MEASURE_FRAMES));
+        let deviations = compare(&measured, &reference, DEFAULT_TOLERANCE);
+        assert!(deviations.is_empty(), "rack cadence differs from rack_reference.txt:\n{}", report(&deviations, &reference));
+    }
+}
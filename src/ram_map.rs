@@ -0,0 +1,59 @@
+// Work RAM addresses from the Computer Archeology Space Invaders RAM map.
+pub const WORK_RAM_START: u16 = 0x2000;
+pub const WORK_RAM_SIZE: usize = 1_024;
+
+/* X of the reference alien, the bottom-left of the rack */
+pub const REF_ALIEN_X: u16 = 0x200A;
+/* 0 while the player has no shot in flight */
+pub const PLAYER_SHOT_STATUS: u16 = 0x2025;
+pub const NUM_ALIENS: u16 = 0x2082;
+pub const CREDITS: u16 = 0x20EB;
+pub const GAME_MODE: u16 = 0x20EF;
+pub const HI_SCORE: u16 = 0x20F4;
+pub const P1_SCORE: u16 = 0x20F8;
+pub const P2_SCORE: u16 = 0x20FC;
+/* Racks player 1 has cleared */
+pub const P1_RACK_COUNT: u16 = 0x21FE;
+pub const P1_SHIPS: u16 = 0x21FF;
+pub const P2_SHIPS: u16 = 0x22FF;
+
+pub fn read(ram: &[u8], addr: u16) -> u8 {
+    return ram[(addr - WORK_RAM_START) as usize];
+}
+
+/* Scores are two BCD bytes, low byte first, e.g. $50 $01 is 150 */
+pub fn read_score(ram: &[u8], addr: u16) -> u32 {
+    return bcd_to_decimal(read(ram, addr + 1)) * 100 + bcd_to_decimal(read(ram, addr));
+}
+
+pub fn bcd_to_decimal(value: u8) -> u32 {
+    return ((value >> 4) as u32) * 10 + (value & 0x0F) as u32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read() {
+        let mut ram = [0; WORK_RAM_SIZE];
+        ram[0xEB] = 0x12;
+        assert_eq!(read(&ram, CREDITS), 0x12);
+    }
+
+    #[test]
+    fn test_read_score() {
+        let mut ram = [0; WORK_RAM_SIZE];
+        ram[0xF8] = 0x50;
+        ram[0xF9] = 0x01;
+        assert_eq!(read_score(&ram, P1_SCORE), 150);
+    }
+
+    #[test]
+    fn test_bcd_to_decimal() {
+        assert_eq!(bcd_to_decimal(0x00), 0);
+        assert_eq!(bcd_to_decimal(0x09), 9);
+        assert_eq!(bcd_to_decimal(0x10), 10);
+        assert_eq!(bcd_to_decimal(0x99), 99);
+    }
+}
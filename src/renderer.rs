@@ -0,0 +1,121 @@
+// Renderer backends and the startup cascade: try each compiled backend in turn until one starts.
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Glow,
+    Wgpu,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 2] = [Backend::Glow, Backend::Wgpu];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Glow => "glow",
+            Backend::Wgpu => "wgpu",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Backend::ALL.into_iter().find(|backend| backend.name() == name);
+    }
+
+    /* glow is always built; wgpu only with the wgpu feature */
+    pub fn compiled(&self) -> bool {
+        match self {
+            Backend::Glow => true,
+            Backend::Wgpu => cfg!(feature = "wgpu"),
+        }
+    }
+}
+
+/* Compiled backends, the preferred one first and then the default order */
+pub fn order(preferred: Option<Backend>) -> Vec<Backend> {
+    let mut backends: Vec<Backend> = Backend::ALL.into_iter().filter(|backend| backend.compiled()).collect();
+    if let Some(preferred) = preferred {
+        backends.retain(|backend| *backend != preferred);
+        backends.insert(0, preferred);
+    }
+    return backends;
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CascadeError {
+    /* Every backend tried, in order, with why it did not start */
+    pub failures: Vec<(Backend, String)>,
+}
+
+impl fmt::Display for CascadeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No renderer could start.")?;
+        for (backend, error) in &self.failures {
+            write!(f, "\n  {}: {}", backend.name(), error)?;
+        }
+        if !Backend::Wgpu.compiled() {
+            write!(f, "\nUpdating the graphics driver may help, or build with --features wgpu to try the other renderer.")?;
+        }
+        Ok(())
+    }
+}
+
+/* try_backend returns Err only when the backend failed to start; whatever it returns once started is passed through */
+pub fn cascade<T>(backends: &[Backend], mut try_backend: impl FnMut(Backend) -> Result<T, String>) -> Result<(Backend, T), CascadeError> {
+    let mut failures = vec![];
+    for backend in backends {
+        match try_backend(*backend) {
+            Ok(result) => return Ok((*backend, result)),
+            Err(error) => failures.push((*backend, error)),
+        }
+    }
+    return Err(CascadeError { failures });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order() {
+        assert_eq!(order(None)[0], Backend::Glow);
+        assert_eq!(order(Some(Backend::Glow))[0], Backend::Glow);
+        assert_eq!(order(Some(Backend::Wgpu))[0], Backend::Wgpu);
+        assert_eq!(order(None).len(), if Backend::Wgpu.compiled() { 2 } else { 1 });
+        assert_eq!(Backend::from_name("wgpu"), Some(Backend::Wgpu));
+        assert_eq!(Backend::from_name("vulkan"), None);
+    }
+
+    #[test]
+    fn test_first_working_backend_wins() {
+        let mut tried = vec![];
+        let result = cascade(&[Backend::Glow, Backend::Wgpu], |backend| {
+            tried.push(backend);
+            return Ok::<_, String>(backend.name());
+        });
+        assert_eq!(result, Ok((Backend::Glow, "glow")));
+        assert_eq!(tried, [Backend::Glow]);
+    }
+
+    #[test]
+    fn test_falls_back_in_order() {
+        let mut tried = vec![];
+        let result = cascade(&[Backend::Wgpu, Backend::Glow], |backend| {
+            tried.push(backend);
+            match backend {
+                Backend::Wgpu => Err("no adapter".to_string()),
+                Backend::Glow => Ok(7),
+            }
+        });
+        assert_eq!(result, Ok((Backend::Glow, 7)));
+        assert_eq!(tried, [Backend::Wgpu, Backend::Glow]);
+    }
+
+    #[test]
+    fn test_all_failures_are_reported() {
+        let result = cascade(&[Backend::Glow, Backend::Wgpu], |backend| Err::<(), _>(format!("{} broke", backend.name())));
+        let error = result.unwrap_err();
+        assert_eq!(error.failures, vec![(Backend::Glow, "glow broke".to_string()), (Backend::Wgpu, "wgpu broke".to_string())]);
+        assert!(error.to_string().starts_with("No renderer could start.\n  glow: glow broke\n  wgpu: wgpu broke"), "{}", error);
+        assert_eq!(cascade(&[], |_| Ok::<(), String>(())), Err(CascadeError { failures: vec![] }));
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+/* No consumer is refreshed more often than this, whatever it asks for */
+pub const MIN_INTERVAL: Duration = Duration::from_micros(8_333);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Consumer {
+    Game,
+    PausedOverlay,
+    Toasts,
+    Stats,
+    SoundTimeline,
+    Coverage,
+    Tasks,
+}
+
+impl Consumer {
+    const COUNT: usize = 7;
+}
+
+#[derive(Clone, Copy, Default)]
+struct Schedule {
+    last_refresh: Option<Instant>,
+    /* Set when the consumer registers during a UI frame, taken by next_delay */
+    due: Option<Instant>,
+}
+
+/* Coalesces the periodic refreshes every visible panel wants into one request_repaint_after per UI frame.
+   Panels register each frame they are shown and re-read their data only when told they are due; once
+   all have registered, next_delay gives the earliest time any of them is due again */
+pub struct RepaintScheduler {
+    schedules: [Schedule; Consumer::COUNT],
+}
+
+impl RepaintScheduler {
+    pub fn new() -> Self {
+        Self { schedules: [Schedule::default(); Consumer::COUNT] }
+    }
+
+    /* A consumer refreshing every interval; returns whether it should re-read its data now. Refreshes keep
+       their cadence when repaints land late, so a slow consumer is never pushed back by a fast one */
+    pub fn every(&mut self, consumer: Consumer, interval: Duration, now: Instant) -> bool {
+        let interval = interval.max(MIN_INTERVAL);
+        let schedule = &mut self.schedules[consumer as usize];
+        let refresh = schedule.last_refresh.map_or(true, |last| now >= last + interval);
+        if refresh {
+            schedule.last_refresh = match schedule.last_refresh {
+                /* More than a whole interval behind: start again from now rather than catching up in a burst */
+                Some(last) if now < last + interval * 2 => Some(last + interval),
+                _ => Some(now),
+            };
+        }
+        schedule.due = schedule.last_refresh.map(|last| last + interval);
+        return refresh;
+    }
+
+    /* A consumer with its own phase, like the game presenting a fixed delay after each VBlank */
+    pub fn at(&mut self, consumer: Consumer, due: Instant) {
+        let schedule = &mut self.schedules[consumer as usize];
+        schedule.due = Some(schedule.due.map_or(due, |other| other.min(due)));
+    }
+
+    /* Delay until the earliest consumer registered since the last call is due; None when nothing wants a repaint */
+    pub fn next_delay(&mut self, now: Instant) -> Option<Duration> {
+        let next = self.schedules.iter_mut().filter_map(|schedule| schedule.due.take()).min()?;
+        return Some(next.saturating_duration_since(now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_micros(16_667);
+
+    #[test]
+    fn test_next_delay_is_earliest_due() {
+        let start = Instant::now();
+        let mut scheduler = RepaintScheduler::new();
+        assert_eq!(scheduler.next_delay(start), None);
+        assert!(scheduler.every(Consumer::SoundTimeline, Duration::from_millis(100), start));
+        scheduler.at(Consumer::Game, start + Duration::from_millis(5));
+        assert_eq!(scheduler.next_delay(start), Some(Duration::from_millis(5)));
+        /* Registrations last one UI frame */
+        assert_eq!(scheduler.next_delay(start), None);
+        let later = start + Duration::from_millis(40);
+        assert!(!scheduler.every(Consumer::SoundTimeline, Duration::from_millis(100), later));
+        assert_eq!(scheduler.next_delay(later), Some(Duration::from_millis(60)));
+        /* Overdue consumers repaint right away */
+        scheduler.at(Consumer::Game, start);
+        assert_eq!(scheduler.next_delay(later), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_interval_is_capped() {
+        let start = Instant::now();
+        let mut scheduler = RepaintScheduler::new();
+        scheduler.every(Consumer::Toasts, Duration::ZERO, start);
+        assert_eq!(scheduler.next_delay(start), Some(MIN_INTERVAL));
+        assert!(!scheduler.every(Consumer::Toasts, Duration::ZERO, start + Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_slow_consumer_not_starved_by_fast_one() {
+        let start = Instant::now();
+        let mut scheduler = RepaintScheduler::new();
+        let (mut fast, mut slow) = (0, 0);
+        let mut now = start;
+        while now < start + Duration::from_secs(10) {
+            if scheduler.every(Consumer::Game, FRAME, now) {
+                fast += 1;
+            }
+            if scheduler.every(Consumer::Stats, Duration::from_secs(1), now) {
+                slow += 1;
+            }
+            /* Repaints always arrive a little late */
+            now += scheduler.next_delay(now).unwrap() + Duration::from_micros(700);
+        }
+        assert_eq!(slow, 10);
+        assert!((590..=600).contains(&fast), "{} fast refreshes", fast);
+    }
+
+    #[test]
+    fn test_stalled_consumer_restarts_from_now() {
+        let start = Instant::now();
+        let mut scheduler = RepaintScheduler::new();
+        scheduler.every(Consumer::Stats, Duration::from_millis(250), start);
+        let resumed = start + Duration::from_secs(5);
+        assert!(scheduler.every(Consumer::Stats, Duration::from_millis(250), resumed));
+        assert!(!scheduler.every(Consumer::Stats, Duration::from_millis(250), resumed + Duration::from_millis(10)));
+        assert_eq!(scheduler.next_delay(resumed + Duration::from_millis(10)), Some(Duration::from_millis(240)));
+    }
+}
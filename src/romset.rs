@@ -0,0 +1,620 @@
+// Known ROM sets, identified by the CRC-32 of each 2KB part as MAME lists them, the board differences
+// each one needs from the machine, and the clean-up of dumps that aren't four bare 2KB parts.
+use crate::machine::{self, InputState};
+use crate::png::crc32;
+use crate::save_slots;
+use crate::sound_latch::{self, SoundLatch};
+use crate::strings::Message;
+use crate::zip;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks(u8);
+
+impl Quirks {
+    pub const NONE: Quirks = Quirks(0);
+    /* Bootleg boards wired the coin switch active-low */
+    pub const INVERT_PORT1_BIT0: Quirks = Quirks(0b01);
+    /* Sound boards fitted the other way round: port 5 drives port 3's sounds and port 3 port 5's */
+    pub const ALT_SOUND_MAP: Quirks = Quirks(0b10);
+    /* Not Midway's program, so the documented wait loop is the built-in program's own */
+    pub const BUILTIN_PROGRAM: Quirks = Quirks(0b100);
+
+    pub fn contains(self, other: Quirks) -> bool {
+        return self.0 & other.0 == other.0;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RomSet {
+    pub name: &'static str,
+    /* CRC-32 of each 2KB part, in PART_NAMES order */
+    pub parts: [u32; 4],
+    pub quirks: Quirks,
+}
+
+/* Only sets whose part CRCs come from MAME's list or a verified dump belong here */
+pub const KNOWN_SETS: &[RomSet] = &[
+    RomSet { name: "Space Invaders (Midway)", parts: PART_CRCS, quirks: Quirks::NONE },
+    RomSet { name: "built-in test ROM", parts: [0x817D_E5F4, 0xF1E8_BA9E, 0xF1E8_BA9E, 0xF1E8_BA9E], quirks: Quirks::BUILTIN_PROGRAM },
+];
+
+pub fn part_crcs(rom: &[u8; machine::ROM_SIZE]) -> [u32; 4] {
+    let mut crcs = [0; 4];
+    for (index, part) in rom.chunks(PART_SIZE).enumerate() {
+        crcs[index] = crc32(part);
+    }
+    return crcs;
+}
+
+pub fn identify_in<'a>(sets: &'a [RomSet], rom: &[u8; machine::ROM_SIZE]) -> Option<&'a RomSet> {
+    let crcs = part_crcs(rom);
+    return sets.iter().find(|set| set.parts == crcs);
+}
+
+pub fn identify(rom: &[u8; machine::ROM_SIZE]) -> Option<&'static RomSet> {
+    return identify_in(KNOWN_SETS, rom);
+}
+
+/* Unrecognised images are treated as the original board */
+pub fn quirks_for(rom: &[u8; machine::ROM_SIZE]) -> Quirks {
+    return identify(rom).map_or(Quirks::NONE, |set| set.quirks);
+}
+
+/* A documented wait-for-interrupt loop: it only reads RAM the interrupt handlers change and writes nothing,
+   so a pass that comes back to start with no interrupt in between changed nothing */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdleLoop {
+    pub start: u16,
+    /* Checked against memory before the loop is trusted, so hacks that moved it just run normally */
+    pub bytes: &'static [u8],
+}
+
+impl IdleLoop {
+    pub fn matches(&self, read: impl Fn(u16) -> u8) -> bool {
+        return self.bytes.iter().enumerate().all(|(i, byte)| read(self.start.wrapping_add(i as u16)) == *byte);
+    }
+}
+
+/* WaitOnDelay: LDA isrDelay; ANA A; JNZ WaitOnDelay, spinning until the ISRs count the delay down */
+const WAIT_ON_DELAY: IdleLoop = IdleLoop { start: 0x0AD7, bytes: &[0x3A, 0xC0, 0x20, 0xA7, 0xC2, 0xD7, 0x0A] };
+/* wait: LDA $2002; CMP B; JZ wait, spinning until the VBlank handler bumps its counter */
+const BUILTIN_WAIT: IdleLoop = IdleLoop { start: 0x004E, bytes: &[0x3A, 0x02, 0x20, 0xB8, 0xCA, 0x4E, 0x00] };
+
+/* A JMP to itself is recognised in any set; this is the set's documented loop on top of that */
+pub fn idle_loop(quirks: Quirks) -> IdleLoop {
+    if quirks.contains(Quirks::BUILTIN_PROGRAM) {
+        return BUILTIN_WAIT;
+    }
+    return WAIT_ON_DELAY;
+}
+
+pub const PART_SIZE: usize = 2_048;
+/* In load order, $0000 first */
+pub const PART_NAMES: [&str; 4] = ["invaders.h", "invaders.g", "invaders.f", "invaders.e"];
+/* CRC-32s of Midway's parts as MAME lists them, in PART_NAMES order; also places renamed archive entries */
+pub const PART_CRCS: [u32; 4] = [0x734F_5AD8, 0x6BFA_CA4A, 0x0CCE_AD96, 0x14E5_38B0];
+/* Copier headers seen on circulating dumps */
+const HEADER_SIZES: &[usize] = &[16];
+/* Single-file dumps padded to 4KB per part */
+const PADDED_PART_SIZE: usize = 4_096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartOrder {
+    /* h at $0000, as MAME and the board's sockets number them */
+    Hgfe,
+    Efgh,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Normalization {
+    StrippedHeader { file: String, bytes: usize },
+    TrimmedPadding { file: String, bytes: usize, fill: u8 },
+    /* verified: the order was confirmed by a known set's checksum rather than assumed */
+    SplitCombined { file: String, order: PartOrder, verified: bool },
+    /* The archive entries the image was assembled from, in load order */
+    FromArchive { file: String, entries: Vec<String> },
+}
+
+impl Normalization {
+    pub fn message(&self) -> Message {
+        match self {
+            Normalization::StrippedHeader { file, bytes } => Message::new("rom.stripped_header", vec![file.clone(), bytes.to_string()]),
+            Normalization::TrimmedPadding { file, bytes, fill } => Message::new("rom.trimmed_padding", vec![file.clone(), bytes.to_string(), format!("${:02X}", fill)]),
+            Normalization::SplitCombined { file, order, verified } => {
+                let order = match order {
+                    PartOrder::Hgfe => "h-g-f-e",
+                    PartOrder::Efgh => "e-f-g-h",
+                };
+                let key = if *verified { "rom.split_combined" } else { "rom.split_unverified" };
+                Message::new(key, vec![file.clone(), order.to_string()])
+            },
+            Normalization::FromArchive { file, entries } => Message::new("rom.from_archive", vec![file.clone(), entries.join(", ")]),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Normalized {
+    pub rom: [u8; machine::ROM_SIZE],
+    pub applied: Vec<Normalization>,
+}
+
+/* One 2KB part: exact, behind a recognised header, or followed by padding that is all $00 or all $FF */
+pub fn normalize_part(file: &str, data: &[u8]) -> Result<(Vec<u8>, Option<Normalization>), Message> {
+    if data.len() == PART_SIZE {
+        return Ok((data.to_vec(), None));
+    }
+    if let Some(&bytes) = HEADER_SIZES.iter().find(|&&header| data.len() == PART_SIZE + header) {
+        return Ok((data[bytes..].to_vec(), Some(Normalization::StrippedHeader { file: file.to_string(), bytes })));
+    }
+    if data.len() > PART_SIZE {
+        let padding = &data[PART_SIZE..];
+        if let Some(fill) = [0x00, 0xFF].into_iter().find(|fill| padding.iter().all(|byte| byte == fill)) {
+            let normalization = Normalization::TrimmedPadding { file: file.to_string(), bytes: padding.len(), fill };
+            return Ok((data[..PART_SIZE].to_vec(), Some(normalization)));
+        }
+    }
+    return Err(Message::new("rom.bad_size", vec![file.to_string(), data.len().to_string(), PART_SIZE.to_string()]));
+}
+
+/* The four parts in PART_NAMES order */
+pub fn normalize_parts(parts: &[Vec<u8>; 4]) -> Result<Normalized, Message> {
+    let mut rom = [0; machine::ROM_SIZE];
+    let mut applied = vec![];
+    for (index, data) in parts.iter().enumerate() {
+        let (part, normalization) = normalize_part(PART_NAMES[index], data)?;
+        rom[index * PART_SIZE..(index + 1) * PART_SIZE].copy_from_slice(&part);
+        applied.extend(normalization);
+    }
+    return Ok(Normalized { rom, applied });
+}
+
+/* An 8KB image, or 16KB with each part padded to 4KB, in either part order. A known set's part CRCs decide
+   the order; failing that it is taken as h-g-f-e, the more common one, and reported as unverified */
+pub fn normalize_combined_in(sets: &[RomSet], file: &str, data: &[u8]) -> Result<Normalized, Message> {
+    let mut applied = vec![];
+    let mut parts: Vec<Vec<u8>> = vec![];
+    match data.len() {
+        machine::ROM_SIZE => parts.extend(data.chunks(PART_SIZE).map(|chunk| chunk.to_vec())),
+        size if size == 4 * PADDED_PART_SIZE => {
+            for (index, chunk) in data.chunks(PADDED_PART_SIZE).enumerate() {
+                let (part, normalization) = normalize_part(&format!("{} part {}", file, index + 1), chunk)?;
+                parts.push(part);
+                applied.extend(normalization);
+            }
+        },
+        size => return Err(Message::new("rom.bad_combined_size", vec![file.to_string(), size.to_string()])),
+    }
+    let assemble = |order: PartOrder| {
+        let mut rom = [0; machine::ROM_SIZE];
+        for (index, part) in parts.iter().enumerate() {
+            let slot = if order == PartOrder::Hgfe { index } else { 3 - index };
+            rom[slot * PART_SIZE..(slot + 1) * PART_SIZE].copy_from_slice(part);
+        }
+        rom
+    };
+    let known = [PartOrder::Hgfe, PartOrder::Efgh].into_iter().find(|order| identify_in(sets, &assemble(*order)).is_some());
+    let order = known.unwrap_or(PartOrder::Hgfe);
+    applied.push(Normalization::SplitCombined { file: file.to_string(), order, verified: known.is_some() });
+    return Ok(Normalized { rom: assemble(order), applied });
+}
+
+pub fn normalize_combined(file: &str, data: &[u8]) -> Result<Normalized, Message> {
+    return normalize_combined_in(KNOWN_SETS, file, data);
+}
+
+/* A zip of the set: each part by name in any folder and any case, else by size and CRC, so renamed entries
+   still load. Without all four parts a single combined dump inside will do */
+pub fn resolve_archive_in(part_crcs: &[u32; 4], file: &str, archive: &[u8]) -> Result<Normalized, Message> {
+    let entries = zip::read_entries(file, archive)?;
+    let basename = |entry: &zip::Entry| entry.name.rsplit('/').next().unwrap_or_default().to_ascii_lowercase();
+    let mut parts: [Vec<u8>; 4] = Default::default();
+    let mut used = vec![];
+    let mut missing = vec![];
+    for (index, name) in PART_NAMES.iter().enumerate() {
+        let found = entries.iter().find(|entry| basename(entry) == *name)
+            .or_else(|| entries.iter().find(|entry| entry.data.len() == PART_SIZE && crc32(&entry.data) == part_crcs[index]));
+        match found {
+            Some(entry) => {
+                parts[index] = entry.data.clone();
+                used.push(entry.name.clone());
+            },
+            None => missing.push(*name),
+        }
+    }
+    let mut normalized = if missing.is_empty() {
+        normalize_parts(&parts)?
+    } else {
+        let Some(entry) = entries.iter().find(|entry| [machine::ROM_SIZE, 4 * PADDED_PART_SIZE].contains(&entry.data.len())) else {
+            return Err(Message::new("rom.zip_missing_parts", vec![file.to_string(), missing.join(", ")]));
+        };
+        used = vec![entry.name.clone()];
+        normalize_combined(&entry.name, &entry.data)?
+    };
+    normalized.applied.insert(0, Normalization::FromArchive { file: file.to_string(), entries: used });
+    return Ok(normalized);
+}
+
+pub fn resolve_archive(file: &str, archive: &[u8]) -> Result<Normalized, Message> {
+    return resolve_archive_in(&PART_CRCS, file, archive);
+}
+
+pub fn port1(input: &InputState, quirks: Quirks) -> u8 {
+    let bits = input.port1();
+    if quirks.contains(Quirks::INVERT_PORT1_BIT0) {
+        return bits ^ 0b00000001;
+    }
+    return bits;
+}
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpsRecord {
+    Bytes { offset: usize, data: Vec<u8> },
+    /* size 0 in the file: count copies of value */
+    Run { offset: usize, count: usize, value: u8 },
+}
+
+impl IpsRecord {
+    fn span(&self) -> (usize, usize) {
+        match self {
+            IpsRecord::Bytes { offset, data } => (*offset, data.len()),
+            IpsRecord::Run { offset, count, .. } => (*offset, *count),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IpsPatch {
+    pub records: Vec<IpsRecord>,
+    /* The truncation extension: three bytes after EOF giving the patched file's length */
+    pub truncate: Option<usize>,
+}
+
+fn be(bytes: &[u8]) -> usize {
+    return bytes.iter().fold(0, |value, byte| value << 8 | *byte as usize);
+}
+
+/* "PATCH", then records of a 3-byte offset and 2-byte size (all big-endian) followed by the data, or by a
+   2-byte count and a fill byte when the size is 0, then "EOF" */
+pub fn parse_ips(file: &str, data: &[u8]) -> Result<IpsPatch, Message> {
+    if !data.starts_with(IPS_HEADER) {
+        return Err(Message::new("rom.ips_not_patch", vec![file.to_string()]));
+    }
+    let truncated = |at: usize| Message::new("rom.ips_truncated", vec![file.to_string(), at.to_string()]);
+    let mut records = vec![];
+    let mut at = IPS_HEADER.len();
+    loop {
+        let Some(offset) = data.get(at..at + 3) else {
+            return Err(truncated(at));
+        };
+        if offset == IPS_EOF {
+            at += 3;
+            break;
+        }
+        let offset = be(offset);
+        let size = be(data.get(at + 3..at + 5).ok_or_else(|| truncated(at))?);
+        if size == 0 {
+            let run = data.get(at + 5..at + 8).ok_or_else(|| truncated(at))?;
+            records.push(IpsRecord::Run { offset, count: be(&run[..2]), value: run[2] });
+            at += 8;
+        } else {
+            let bytes = data.get(at + 5..at + 5 + size).ok_or_else(|| truncated(at))?;
+            records.push(IpsRecord::Bytes { offset, data: bytes.to_vec() });
+            at += 5 + size;
+        }
+    }
+    let truncate = match data.len() - at {
+        0 => None,
+        3 => Some(be(&data[at..])),
+        extra => return Err(Message::new("rom.ips_trailing", vec![file.to_string(), extra.to_string()])),
+    };
+    return Ok(IpsPatch { records, truncate });
+}
+
+impl IpsPatch {
+    /* All or nothing: the image is only touched once every record is known to fit. Returns the record count */
+    pub fn apply(&self, file: &str, rom: &mut [u8; machine::ROM_SIZE]) -> Result<usize, Message> {
+        if let Some(length) = self.truncate.filter(|length| *length < machine::ROM_SIZE) {
+            return Err(Message::new("rom.ips_truncate", vec![file.to_string(), length.to_string(), machine::ROM_SIZE.to_string()]));
+        }
+        for record in &self.records {
+            let (offset, len) = record.span();
+            if offset + len > machine::ROM_SIZE {
+                let last = format!("${:04X}", (offset + len).max(1) - 1);
+                return Err(Message::new("rom.ips_out_of_bounds", vec![file.to_string(), format!("${:04X}", offset), last]));
+            }
+        }
+        for record in &self.records {
+            match record {
+                IpsRecord::Bytes { offset, data } => rom[*offset..*offset + data.len()].copy_from_slice(data),
+                IpsRecord::Run { offset, count, value } => rom[*offset..*offset + *count].fill(*value),
+            }
+        }
+        return Ok(self.records.len());
+    }
+}
+
+/* For the About dialog and issue reports: the set the patches were applied to, then each patch in order.
+   Images are shown by save_slots::rom_checksum, which is what save states are tied to */
+pub fn describe(base: &[u8; machine::ROM_SIZE], patches: &[String], checksum: u64) -> String {
+    let base_checksum = save_slots::rom_checksum(base);
+    let base = match identify(base) {
+        Some(set) => format!("{} ({:016X})", set.name, base_checksum),
+        None => format!("{:016X}", base_checksum),
+    };
+    if patches.is_empty() {
+        return base;
+    }
+    return format!("{} + {} = {:016X}", base, patches.join(" + "), checksum);
+}
+
+/* Latches for ports 3 and 5 */
+pub fn sound_latches(quirks: Quirks) -> (SoundLatch, SoundLatch) {
+    if quirks.contains(Quirks::ALT_SOUND_MAP) {
+        return (SoundLatch::new(&sound_latch::PORT5_SOUNDS), SoundLatch::new(&sound_latch::PORT3_SOUNDS));
+    }
+    return (SoundLatch::new(&sound_latch::PORT3_SOUNDS), SoundLatch::new(&sound_latch::PORT5_SOUNDS));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_slots;
+    use crate::test_rom;
+
+    fn image(parts: [Vec<u8>; 4]) -> [u8; machine::ROM_SIZE] {
+        return parts.concat().try_into().unwrap();
+    }
+
+    #[test]
+    fn test_identify_by_part_crcs() {
+        let original = image([part(1), part(2), part(3), part(4)]);
+        let bootleg = image([part(1), part(2), part(3), part(5)]);
+        let sets = [
+            RomSet { name: "original", parts: part_crcs(&original), quirks: Quirks::NONE },
+            RomSet { name: "bootleg", parts: part_crcs(&bootleg), quirks: Quirks(Quirks::INVERT_PORT1_BIT0.0 | Quirks::ALT_SOUND_MAP.0) },
+        ];
+        assert_eq!(part_crcs(&original), [crc32(&part(1)), crc32(&part(2)), crc32(&part(3)), crc32(&part(4))]);
+        assert_eq!(identify_in(&sets, &bootleg).map(|set| set.name), Some("bootleg"));
+        assert!(identify_in(&sets, &bootleg).unwrap().quirks.contains(Quirks::ALT_SOUND_MAP));
+        assert_eq!(identify_in(&sets, &original).unwrap().quirks, Quirks::NONE);
+        /* One changed part is enough to make it a different program */
+        assert_eq!(identify_in(&sets, &image([part(1), part(2), part(6), part(4)])), None);
+        assert_eq!(quirks_for(&original), Quirks::NONE);
+    }
+
+    #[test]
+    fn test_midway_set_uses_mames_part_crcs() {
+        assert_eq!(KNOWN_SETS[0].parts, [0x734F_5AD8, 0x6BFA_CA4A, 0x0CCE_AD96, 0x14E5_38B0]);
+        assert_eq!(KNOWN_SETS[0].quirks, Quirks::NONE);
+    }
+
+    #[test]
+    fn test_builtin_rom_recognised() {
+        assert_eq!(identify(&test_rom::rom()).map(|set| set.name), Some("built-in test ROM"));
+        assert_eq!(quirks_for(&test_rom::rom()), Quirks::BUILTIN_PROGRAM);
+    }
+
+    #[test]
+    fn test_idle_loops_match_their_programs() {
+        let rom = test_rom::rom();
+        assert!(idle_loop(quirks_for(&rom)).matches(|addr| rom[addr as usize]));
+        /* The built-in program has nothing at WaitOnDelay */
+        assert!(!idle_loop(Quirks::NONE).matches(|addr| rom[addr as usize]));
+    }
+
+    fn part(seed: u8) -> Vec<u8> {
+        return (0..PART_SIZE).map(|i| (i as u8).wrapping_mul(31) ^ seed).collect();
+    }
+
+    #[test]
+    fn test_normalize_part_header_and_padding() {
+        assert_eq!(normalize_part("invaders.h", &part(1)), Ok((part(1), None)));
+
+        let headered = [vec![0xAA; 16], part(2)].concat();
+        assert_eq!(normalize_part("invaders.g", &headered), Ok((part(2), Some(Normalization::StrippedHeader { file: "invaders.g".to_string(), bytes: 16 }))));
+
+        for fill in [0x00, 0xFF] {
+            let padded = [part(3), vec![fill; 2_048]].concat();
+            let trimmed = Normalization::TrimmedPadding { file: "invaders.f".to_string(), bytes: 2_048, fill };
+            assert_eq!(normalize_part("invaders.f", &padded), Ok((part(3), Some(trimmed))));
+        }
+    }
+
+    #[test]
+    fn test_normalize_part_rejects() {
+        let bad_size = |size: usize| Err(Message::new("rom.bad_size", vec!["invaders.e".to_string(), size.to_string(), "2048".to_string()]));
+        assert_eq!(normalize_part("invaders.e", &part(4)[..2_000]), bad_size(2_000));
+        /* Padding that isn't a single fill byte is more likely a different program */
+        let mut padded = [part(4), vec![0xFF; 2_048]].concat();
+        padded[3_000] = 0x00;
+        assert_eq!(normalize_part("invaders.e", &padded), bad_size(4_096));
+        assert_eq!(normalize_part("invaders.e", &[vec![0xAA; 15], part(4)].concat()), bad_size(2_063));
+    }
+
+    #[test]
+    fn test_normalize_parts() {
+        let parts = [part(1), [vec![0; 16], part(2)].concat(), part(3), [part(4), vec![0xFF; 2_048]].concat()];
+        let normalized = normalize_parts(&parts).unwrap();
+        assert_eq!(normalized.rom.to_vec(), [part(1), part(2), part(3), part(4)].concat());
+        assert_eq!(normalized.applied, vec![
+            Normalization::StrippedHeader { file: "invaders.g".to_string(), bytes: 16 },
+            Normalization::TrimmedPadding { file: "invaders.e".to_string(), bytes: 2_048, fill: 0xFF },
+        ]);
+        assert_eq!(normalize_parts(&[part(1), part(2), vec![], part(4)]).map(|normalized| normalized.applied).unwrap_err().key, "rom.bad_size");
+    }
+
+    #[test]
+    fn test_combined_order_decided_by_checksum() {
+        let hgfe = [part(1), part(2), part(3), part(4)].concat();
+        let efgh = [part(4), part(3), part(2), part(1)].concat();
+        let sets = [RomSet { name: "synthetic", parts: [crc32(&part(1)), crc32(&part(2)), crc32(&part(3)), crc32(&part(4))], quirks: Quirks::NONE }];
+
+        for (data, order) in [(&hgfe, PartOrder::Hgfe), (&efgh, PartOrder::Efgh)] {
+            let normalized = normalize_combined_in(&sets, "invaders.rom", data).unwrap();
+            assert_eq!(normalized.rom.to_vec(), hgfe);
+            assert_eq!(normalized.applied, vec![Normalization::SplitCombined { file: "invaders.rom".to_string(), order, verified: true }]);
+        }
+        /* No known set matches either way round: kept as h-g-f-e and flagged */
+        let normalized = normalize_combined_in(&[], "invaders.rom", &efgh).unwrap();
+        assert_eq!(normalized.rom.to_vec(), efgh);
+        assert_eq!(normalized.applied, vec![Normalization::SplitCombined { file: "invaders.rom".to_string(), order: PartOrder::Hgfe, verified: false }]);
+        assert_eq!(normalized.applied[0].message(), Message::new("rom.split_unverified", vec!["invaders.rom".to_string(), "h-g-f-e".to_string()]));
+    }
+
+    #[test]
+    fn test_combined_padded_to_16k() {
+        let padded: Vec<u8> = [part(4), part(3), part(2), part(1)].iter().flat_map(|part| [part.clone(), vec![0x00; 2_048]].concat()).collect();
+        let sets = [RomSet { name: "synthetic", parts: [crc32(&part(1)), crc32(&part(2)), crc32(&part(3)), crc32(&part(4))], quirks: Quirks::NONE }];
+        let normalized = normalize_combined_in(&sets, "invaders.rom", &padded).unwrap();
+        assert_eq!(normalized.rom.to_vec(), [part(1), part(2), part(3), part(4)].concat());
+        assert_eq!(normalized.applied.len(), 5);
+        assert_eq!(normalized.applied[0], Normalization::TrimmedPadding { file: "invaders.rom part 1".to_string(), bytes: 2_048, fill: 0x00 });
+        assert_eq!(normalized.applied[4], Normalization::SplitCombined { file: "invaders.rom".to_string(), order: PartOrder::Efgh, verified: true });
+        assert_eq!(normalize_combined("invaders.rom", &padded[..10_000]).unwrap_err().key, "rom.bad_combined_size");
+    }
+
+    fn from_archive(entries: &[&str]) -> Normalization {
+        return Normalization::FromArchive { file: "invaders.zip".to_string(), entries: entries.iter().map(|entry| entry.to_string()).collect() };
+    }
+
+    #[test]
+    fn test_archive_parts_by_name() {
+        let (h, g, f, e) = (part(1), [vec![0; 16], part(2)].concat(), part(3), part(4));
+        let zip = zip::tests::build(&[
+            ("readme.txt", 0, b"dumped 1998", b"dumped 1998"),
+            ("invaders/INVADERS.E", 0, &e, &e),
+            ("invaders/invaders.f", 0, &f, &f),
+            ("invaders/invaders.g", 0, &g, &g),
+            ("invaders/invaders.h", 0, &h, &h),
+        ], false);
+        let normalized = resolve_archive_in(&[0; 4], "invaders.zip", &zip).unwrap();
+        assert_eq!(normalized.rom.to_vec(), [part(1), part(2), part(3), part(4)].concat());
+        assert_eq!(normalized.applied, vec![
+            from_archive(&["invaders/invaders.h", "invaders/invaders.g", "invaders/invaders.f", "invaders/INVADERS.E"]),
+            Normalization::StrippedHeader { file: "invaders.g".to_string(), bytes: 16 },
+        ]);
+        assert_eq!(normalized.applied[0].message().args[1], "invaders/invaders.h, invaders/invaders.g, invaders/invaders.f, invaders/INVADERS.E");
+    }
+
+    #[test]
+    fn test_archive_renamed_parts_by_crc() {
+        let crcs = [crc32(&part(1)), crc32(&part(2)), crc32(&part(3)), crc32(&part(4))];
+        let (h, g, f, e) = (part(1), part(2), part(3), part(4));
+        let zip = zip::tests::build(&[("sv0h.36", 0, &h, &h), ("4.bin", 0, &e, &e), ("3.bin", 0, &f, &f), ("invaders.g", 0, &g, &g)], false);
+        let normalized = resolve_archive_in(&crcs, "invaders.zip", &zip).unwrap();
+        assert_eq!(normalized.rom.to_vec(), [part(1), part(2), part(3), part(4)].concat());
+        assert_eq!(normalized.applied, vec![from_archive(&["sv0h.36", "invaders.g", "3.bin", "4.bin"])]);
+        /* Without the database the renamed ones can't be placed */
+        let missing = Message::new("rom.zip_missing_parts", vec!["invaders.zip".to_string(), "invaders.h, invaders.f, invaders.e".to_string()]);
+        assert_eq!(resolve_archive_in(&[0; 4], "invaders.zip", &zip), Err(missing));
+    }
+
+    #[test]
+    fn test_archive_combined_dump_and_errors() {
+        let hgfe = [part(1), part(2), part(3), part(4)].concat();
+        let zip = zip::tests::build(&[("invaders.rom", 0, &hgfe, &hgfe)], false);
+        let normalized = resolve_archive_in(&[0; 4], "invaders.zip", &zip).unwrap();
+        assert_eq!(normalized.rom.to_vec(), hgfe);
+        assert_eq!(normalized.applied[0], from_archive(&["invaders.rom"]));
+        assert_eq!(normalized.applied[1], Normalization::SplitCombined { file: "invaders.rom".to_string(), order: PartOrder::Hgfe, verified: false });
+
+        let h = part(1);
+        let encrypted = zip::tests::build(&[("invaders.h", 0, &h, &h)], true);
+        assert_eq!(resolve_archive("invaders.zip", &encrypted).unwrap_err().key, "rom.zip_encrypted");
+        assert_eq!(resolve_archive("invaders.zip", &zip[..zip.len() - 30]).unwrap_err().key, "rom.zip_corrupt");
+    }
+
+    #[test]
+    fn test_inverted_coin_bit() {
+        let idle = InputState::default();
+        let coin = InputState { coin: true, p1_fire: true, ..Default::default() };
+        assert_eq!(port1(&idle, Quirks::NONE), 0b00001000);
+        assert_eq!(port1(&idle, Quirks::INVERT_PORT1_BIT0), 0b00001001);
+        assert_eq!(port1(&coin, Quirks::NONE), 0b00011001);
+        assert_eq!(port1(&coin, Quirks::INVERT_PORT1_BIT0), 0b00011000);
+    }
+
+    #[test]
+    fn test_alt_sound_map_swaps_banks() {
+        let (mut port3, mut port5) = sound_latches(Quirks::NONE);
+        assert_eq!(port3.write(0b00001).rising, vec![0]);
+        assert_eq!(port5.write(0b00001).rising, vec![4]);
+        let (mut port3, mut port5) = sound_latches(Quirks::ALT_SOUND_MAP);
+        assert_eq!(port3.write(0b10001).rising, vec![4, 8]);
+        assert_eq!(port5.write(0b01000).rising, vec![3]);
+    }
+
+    fn ips(records: &[&[u8]], tail: &[u8]) -> Vec<u8> {
+        let mut data = IPS_HEADER.to_vec();
+        for record in records {
+            data.extend_from_slice(record);
+        }
+        data.extend_from_slice(IPS_EOF);
+        data.extend_from_slice(tail);
+        return data;
+    }
+
+    #[test]
+    fn test_ips_normal_and_rle_records() {
+        /* Two bytes at $0010, then eight $FF from $1FF8 to the last byte */
+        let data = ips(&[&[0x00, 0x00, 0x10, 0x00, 0x02, 0xAB, 0xCD], &[0x00, 0x1F, 0xF8, 0x00, 0x00, 0x00, 0x08, 0xFF]], &[]);
+        let patch = parse_ips("hack.ips", &data).unwrap();
+        assert_eq!(patch.records, vec![
+            IpsRecord::Bytes { offset: 0x10, data: vec![0xAB, 0xCD] },
+            IpsRecord::Run { offset: 0x1FF8, count: 8, value: 0xFF },
+        ]);
+        assert_eq!(patch.truncate, None);
+        let mut rom = [0; machine::ROM_SIZE];
+        assert_eq!(patch.apply("hack.ips", &mut rom), Ok(2));
+        assert_eq!(&rom[0x0F..0x13], &[0x00, 0xAB, 0xCD, 0x00]);
+        assert_eq!(rom[0x1FF7], 0x00);
+        assert!(rom[0x1FF8..].iter().all(|byte| *byte == 0xFF));
+    }
+
+    #[test]
+    fn test_ips_eof_and_truncation_extension() {
+        assert_eq!(parse_ips("empty.ips", b"PATCHEOF").unwrap().records, vec![]);
+        /* A record at offset $454F46 would read as EOF, which is why IPS cannot address it */
+        let patch = parse_ips("t.ips", &ips(&[], &[0x00, 0x20, 0x00])).unwrap();
+        assert_eq!(patch.truncate, Some(machine::ROM_SIZE));
+        assert_eq!(patch.apply("t.ips", &mut [0; machine::ROM_SIZE]), Ok(0));
+        let short = parse_ips("t.ips", &ips(&[], &[0x00, 0x10, 0x00])).unwrap();
+        assert_eq!(short.apply("t.ips", &mut [0; machine::ROM_SIZE]), Err(Message::new("rom.ips_truncate", vec!["t.ips".to_string(), "4096".to_string(), "8192".to_string()])));
+    }
+
+    #[test]
+    fn test_ips_malformed() {
+        let error = |key: &'static str, args: &[&str]| Err(Message::new(key, args.iter().map(|arg| arg.to_string()).collect()));
+        assert_eq!(parse_ips("x.ips", b"PACTH"), error("rom.ips_not_patch", &["x.ips"]));
+        /* No EOF marker; a record cut short, whose data swallows the marker; a run without its fill byte */
+        assert_eq!(parse_ips("x.ips", b"PATCH"), error("rom.ips_truncated", &["x.ips", "5"]));
+        assert_eq!(parse_ips("x.ips", b"PATCH\x00\x00\x10\x00\x04\x01\x02EOF"), error("rom.ips_truncated", &["x.ips", "14"]));
+        assert_eq!(parse_ips("x.ips", b"PATCH\x00\x00\x10\x00\x00\x00\x04"), error("rom.ips_truncated", &["x.ips", "5"]));
+        assert_eq!(parse_ips("x.ips", &ips(&[], &[1, 2])), error("rom.ips_trailing", &["x.ips", "2"]));
+    }
+
+    #[test]
+    fn test_ips_bounds_leave_rom_untouched() {
+        /* The first record fits, the second runs one byte past $1FFF */
+        let data = ips(&[&[0x00, 0x00, 0x00, 0x00, 0x01, 0x11], &[0x00, 0x1F, 0xFF, 0x00, 0x02, 0x22, 0x33]], &[]);
+        let patch = parse_ips("big.ips", &data).unwrap();
+        let mut rom = [0; machine::ROM_SIZE];
+        let expected = Message::new("rom.ips_out_of_bounds", vec!["big.ips".to_string(), "$1FFF".to_string(), "$2000".to_string()]);
+        assert_eq!(patch.apply("big.ips", &mut rom), Err(expected));
+        assert_eq!(rom[0], 0x00);
+    }
+
+    #[test]
+    fn test_describe_patched_image() {
+        let builtin = test_rom::rom();
+        assert_eq!(describe(&builtin, &[], save_slots::rom_checksum(&builtin)), "built-in test ROM (3540168DB68AA356)");
+        let unknown = [0; machine::ROM_SIZE];
+        let patches = ["a.ips".to_string(), "b.ips".to_string()];
+        let expected = format!("{:016X} + a.ips + b.ips = 0000000000000034", save_slots::rom_checksum(&unknown));
+        assert_eq!(describe(&unknown, &patches, 0x34), expected);
+    }
+}
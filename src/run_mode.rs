@@ -0,0 +1,231 @@
+/* What the emulation thread is doing. Every mode-dependent choice in the runner (audio, input source,
+   pacing, what gets recorded) is answered by the current Mode, and the only way to change it is
+   transition(). The table:
+
+     command         | Running      Paused      Stepping     PlayingMovie  FastForward   IdlePaused
+     ----------------+-------------------------------------------------------------------------------
+     Pause           | Paused       -           Paused       Paused        Paused        Paused
+     Resume          | -            Running     Running      -             -             -
+     Step(n)         | -            Stepping{n} Stepping{+n} -             -             -
+     StepFinished    | -            -           Paused       -             -             -
+     PlayMovie       | PlayingMovie -           -            PlayingMovie  PlayingMovie  -
+     MovieFinished   | -            -           -            Running       -             -
+     FastForward(m)  | FastForward  -           -            -             FastForward   -
+     NormalSpeed     | -            -           -            -             Running       -
+     IdlePause       | IdlePaused   -           -            IdlePaused    IdlePaused    -
+     IdleResume      | -            -           -            -             -             Running
+
+   "-" is rejected with a reason. Leaving PlayingMovie abandons the movie */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Running,
+    /* Debugger pause */
+    Paused,
+    /* Runs this many instructions, then back to Paused */
+    Stepping { instructions: u32 },
+    /* Input comes from a recorded macro merged over live input */
+    PlayingMovie,
+    FastForward { multiplier: u32 },
+    /* The player walked away; see app_state::IdlePolicy */
+    IdlePaused,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeCommand {
+    Pause,
+    Resume,
+    Step(u32),
+    StepFinished,
+    PlayMovie,
+    MovieFinished,
+    FastForward(u32),
+    NormalSpeed,
+    IdlePause,
+    IdleResume,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioPolicy {
+    Play,
+    /* Sent on, for the audio thread to thin out by speed (see mixer::speed_action) */
+    Throttled,
+    Mute,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputSource {
+    Live,
+    Movie,
+    /* Nothing is sampled while the CPU is held */
+    Frozen,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchedulerPolicy {
+    /* Frames are paced to 60Hz */
+    Paced,
+    /* Nothing runs on the clock; the scheduler is reset on the way out */
+    Suspended,
+}
+
+pub fn transition(mode: Mode, command: ModeCommand) -> Result<Mode, &'static str> {
+    use Mode::*;
+    use ModeCommand as C;
+    return match (command, mode) {
+        (C::Pause, Paused) => Err("already paused"),
+        (C::Pause, _) => Ok(Paused),
+        (C::Resume, Paused | Stepping { .. }) => Ok(Running),
+        (C::Resume, _) => Err("not paused by the debugger"),
+        (C::Step(0), _) => Err("nothing to step"),
+        (C::Step(n), Paused) => Ok(Stepping { instructions: n }),
+        (C::Step(n), Stepping { instructions }) => Ok(Stepping { instructions: instructions.saturating_add(n) }),
+        (C::Step(_), _) => Err("stepping needs the debugger paused"),
+        (C::StepFinished, Stepping { .. }) => Ok(Paused),
+        (C::StepFinished, _) => Err("not stepping"),
+        (C::PlayMovie, Running | PlayingMovie | FastForward { .. }) => Ok(PlayingMovie),
+        (C::PlayMovie, _) => Err("cannot start a movie while paused"),
+        (C::MovieFinished, PlayingMovie) => Ok(Running),
+        (C::MovieFinished, _) => Err("no movie playing"),
+        (C::FastForward(multiplier), _) if multiplier < 2 => Err("fast forward needs a multiplier of at least 2"),
+        (C::FastForward(multiplier), Running | FastForward { .. }) => Ok(FastForward { multiplier }),
+        (C::FastForward(_), _) => Err("fast forward only from normal running"),
+        (C::NormalSpeed, FastForward { .. }) => Ok(Running),
+        (C::NormalSpeed, _) => Err("not fast forwarding"),
+        (C::IdlePause, Running | PlayingMovie | FastForward { .. }) => Ok(IdlePaused),
+        (C::IdlePause, _) => Err("already paused"),
+        (C::IdleResume, IdlePaused) => Ok(Running),
+        (C::IdleResume, _) => Err("not idle paused"),
+    };
+}
+
+/* Applies a command, logging why it was refused; returns whether the mode changed */
+pub fn apply(mode: &mut Mode, command: ModeCommand) -> bool {
+    match transition(*mode, command) {
+        Ok(next) => {
+            *mode = next;
+            return true;
+        },
+        Err(reason) => {
+            println!("Ignoring {:?} while {:?}: {}", command, mode, reason);
+            return false;
+        },
+    }
+}
+
+impl Mode {
+    /* Emulated frames per scheduler tick */
+    pub fn frames_per_tick(&self) -> u32 {
+        match self {
+            Mode::Running | Mode::PlayingMovie => 1,
+            Mode::FastForward { multiplier } => *multiplier,
+            Mode::Paused | Mode::Stepping { .. } | Mode::IdlePaused => 0,
+        }
+    }
+
+    pub fn scheduler(&self) -> SchedulerPolicy {
+        return if self.frames_per_tick() == 0 { SchedulerPolicy::Suspended } else { SchedulerPolicy::Paced };
+    }
+
+    pub fn audio(&self) -> AudioPolicy {
+        match self {
+            Mode::Running | Mode::PlayingMovie => AudioPolicy::Play,
+            Mode::FastForward { .. } => AudioPolicy::Throttled,
+            _ => AudioPolicy::Mute,
+        }
+    }
+
+    pub fn input_source(&self) -> InputSource {
+        match self {
+            Mode::Running | Mode::FastForward { .. } => InputSource::Live,
+            Mode::PlayingMovie => InputSource::Movie,
+            Mode::Paused | Mode::Stepping { .. } | Mode::IdlePaused => InputSource::Frozen,
+        }
+    }
+
+    /* Holding turbo paces frames faster; fast forward already runs several per tick and wins */
+    pub fn allows_turbo(&self) -> bool {
+        return matches!(self, Mode::Running | Mode::PlayingMovie);
+    }
+
+    /* The sound timeline is a per-frame debugging view; skipped frames would only smear it */
+    pub fn records_sound_timeline(&self) -> bool {
+        return self.frames_per_tick() == 1;
+    }
+
+    /* The debugger's status line is published while it holds the CPU */
+    pub fn debugger_attached(&self) -> bool {
+        return matches!(self, Mode::Paused | Mode::Stepping { .. });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Mode::*;
+
+    const MODES: [Mode; 6] = [Running, Paused, Stepping { instructions: 2 }, PlayingMovie, FastForward { multiplier: 4 }, IdlePaused];
+    const COMMANDS: [ModeCommand; 10] = [
+        ModeCommand::Pause, ModeCommand::Resume, ModeCommand::Step(1), ModeCommand::StepFinished, ModeCommand::PlayMovie,
+        ModeCommand::MovieFinished, ModeCommand::FastForward(8), ModeCommand::NormalSpeed, ModeCommand::IdlePause, ModeCommand::IdleResume,
+    ];
+
+    #[test]
+    fn test_transition_table() {
+        let r = None;
+        /* Rows follow COMMANDS, columns follow MODES; r marks a rejected transition */
+        let table: [[Option<Mode>; 6]; 10] = [
+            [Some(Paused), r, Some(Paused), Some(Paused), Some(Paused), Some(Paused)],
+            [r, Some(Running), Some(Running), r, r, r],
+            [r, Some(Stepping { instructions: 1 }), Some(Stepping { instructions: 3 }), r, r, r],
+            [r, r, Some(Paused), r, r, r],
+            [Some(PlayingMovie), r, r, Some(PlayingMovie), Some(PlayingMovie), r],
+            [r, r, r, Some(Running), r, r],
+            [Some(FastForward { multiplier: 8 }), r, r, r, Some(FastForward { multiplier: 8 }), r],
+            [r, r, r, r, Some(Running), r],
+            [Some(IdlePaused), r, r, Some(IdlePaused), Some(IdlePaused), r],
+            [r, r, r, r, r, Some(Running)],
+        ];
+        for (row, command) in COMMANDS.iter().enumerate() {
+            for (column, mode) in MODES.iter().enumerate() {
+                let result = transition(*mode, *command);
+                assert_eq!(result.ok(), table[row][column], "{:?} in {:?}", command, mode);
+                if let Err(reason) = result {
+                    assert!(!reason.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_degenerate_arguments_rejected() {
+        assert!(transition(Paused, ModeCommand::Step(0)).is_err());
+        assert!(transition(Running, ModeCommand::FastForward(1)).is_err());
+        assert_eq!(transition(Stepping { instructions: u32::MAX }, ModeCommand::Step(5)), Ok(Stepping { instructions: u32::MAX }));
+    }
+
+    #[test]
+    fn test_apply_leaves_mode_on_rejection() {
+        let mut mode = Running;
+        assert!(!apply(&mut mode, ModeCommand::Resume));
+        assert_eq!(mode, Running);
+        assert!(apply(&mut mode, ModeCommand::Pause));
+        assert_eq!(mode, Paused);
+    }
+
+    #[test]
+    fn test_policies() {
+        let policies: Vec<(u32, SchedulerPolicy, AudioPolicy, InputSource, bool, bool)> = MODES.iter()
+            .map(|mode| (mode.frames_per_tick(), mode.scheduler(), mode.audio(), mode.input_source(), mode.records_sound_timeline(), mode.debugger_attached()))
+            .collect();
+        assert_eq!(policies, vec![
+            (1, SchedulerPolicy::Paced, AudioPolicy::Play, InputSource::Live, true, false),
+            (0, SchedulerPolicy::Suspended, AudioPolicy::Mute, InputSource::Frozen, false, true),
+            (0, SchedulerPolicy::Suspended, AudioPolicy::Mute, InputSource::Frozen, false, true),
+            (1, SchedulerPolicy::Paced, AudioPolicy::Play, InputSource::Movie, true, false),
+            (4, SchedulerPolicy::Paced, AudioPolicy::Throttled, InputSource::Live, false, false),
+            (0, SchedulerPolicy::Suspended, AudioPolicy::Mute, InputSource::Frozen, false, false),
+        ]);
+        let turbo: Vec<bool> = MODES.iter().map(Mode::allows_turbo).collect();
+        assert_eq!(turbo, vec![true, false, false, true, false, false]);
+    }
+}
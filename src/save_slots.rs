@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use crate::save_state::{Reader, SaveState, SaveStateError};
+
+const MAGIC: &[u8; 4] = b"SISL";
+pub const VERSION: u16 = 1;
+pub const SLOT_COUNT: usize = 10;
+pub const THUMBNAIL_FACTOR: usize = 8;
+
+pub enum SlotCommand {
+    Save(usize),
+    Load(usize),
+}
+
+/* Slots are numbered 1..=SLOT_COUNT to match the F1..F10 hotkeys */
+pub fn slot_file_name(slot: usize) -> String {
+    format!("slot{:02}.sisl", slot)
+}
+
+pub fn slot_path(dir: &Path, slot: usize) -> PathBuf {
+    dir.join(slot_file_name(slot))
+}
+
+pub fn rom_checksum(rom: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in rom {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    return hash;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thumbnail {
+    pub width: u16,
+    pub height: u16,
+    pub rgba: Vec<u8>,
+}
+
+impl Thumbnail {
+    /* Nearest-neighbour downscale of an RGBA frame */
+    pub fn downscale(pixels: &[[u8; 4]], width: usize, height: usize, factor: usize) -> Self {
+        let (thumb_width, thumb_height) = (width / factor, height / factor);
+        let mut rgba = Vec::with_capacity(thumb_width * thumb_height * 4);
+        for y in 0..thumb_height {
+            for x in 0..thumb_width {
+                rgba.extend_from_slice(&pixels[y * factor * width + x * factor]);
+            }
+        }
+        Self { width: thumb_width as u16, height: thumb_height as u16, rgba }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotHeader {
+    /* Seconds since the Unix epoch */
+    pub timestamp: u64,
+    pub rom_checksum: u64,
+    pub thumbnail: Thumbnail,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LoadedSlot {
+    pub header: SlotHeader,
+    pub state: SaveState,
+    pub rom_mismatch: bool,
+}
+
+pub fn encode(header: &SlotHeader, state: &SaveState) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&header.timestamp.to_le_bytes());
+    bytes.extend_from_slice(&header.rom_checksum.to_le_bytes());
+    bytes.extend_from_slice(&header.thumbnail.width.to_le_bytes());
+    bytes.extend_from_slice(&header.thumbnail.height.to_le_bytes());
+    bytes.extend_from_slice(&header.thumbnail.rgba);
+    bytes.extend_from_slice(&state.to_bytes());
+    return bytes;
+}
+
+/* Reads only the header so the slot picker doesn't decode every state */
+pub fn decode_header(bytes: &[u8]) -> Result<SlotHeader, SaveStateError> {
+    let mut reader = Reader::new(bytes);
+    return read_header(&mut reader);
+}
+
+pub fn decode(bytes: &[u8], rom_checksum: u64) -> Result<LoadedSlot, SaveStateError> {
+    let mut reader = Reader::new(bytes);
+    let header = read_header(&mut reader)?;
+    let state = SaveState::from_bytes(reader.rest())?;
+    let rom_mismatch = header.rom_checksum != rom_checksum;
+    Ok(LoadedSlot { header, state, rom_mismatch })
+}
+
+fn read_header(reader: &mut Reader) -> Result<SlotHeader, SaveStateError> {
+    if reader.take(4)? != MAGIC {
+        return Err(SaveStateError::InvalidMagic);
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+    let timestamp = reader.u64()?;
+    let rom_checksum = reader.u64()?;
+    let width = reader.u16()?;
+    let height = reader.u16()?;
+    let rgba = reader.take(width as usize * height as usize * 4)?.to_vec();
+    Ok(SlotHeader { timestamp, rom_checksum, thumbnail: Thumbnail { width, height, rgba } })
+}
+
+/* Message key and count describing how long ago a slot was saved */
+pub fn age(now: u64, timestamp: u64) -> (&'static str, u64) {
+    let seconds = now.saturating_sub(timestamp);
+    match seconds {
+        0..=59 => ("age.just_now", 0),
+        60..=3_599 => ("age.minutes", seconds / 60),
+        3_600..=86_399 => ("age.hours", seconds / 3_600),
+        _ => ("age.days", seconds / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intel8080::emulator::CpuState;
+    use crate::save_state::RAM_SIZE;
+
+    fn state() -> SaveState {
+        SaveState {
+            cpu: CpuState { a: 1, b: 2, c: 3, d: 4, e: 5, h: 6, l: 7, pc: 0x1A32, sp: 0x23FE, flags: 0b01000110, interrupt_enabled: true, halted: false, wait_cycles: 0 },
+            ram: vec![0x55; RAM_SIZE],
+            shift_register: 0,
+            shift_register_offset: 0,
+            last_port3: 0,
+            last_port5: 0,
+            frame: 77,
+            seed: 1,
+            rng_state: 2,
+            scheduler: Default::default(),
+        }
+    }
+
+    fn header() -> SlotHeader {
+        let pixels: Vec<[u8; 4]> = (0..16 * 8).map(|i| [i as u8, 0, 0, 255]).collect();
+        SlotHeader { timestamp: 1_700_000_000, rom_checksum: rom_checksum(b"rom"), thumbnail: Thumbnail::downscale(&pixels, 16, 8, 4) }
+    }
+
+    #[test]
+    fn test_slot_file_naming() {
+        assert_eq!(slot_file_name(1), "slot01.sisl");
+        assert_eq!(slot_file_name(10), "slot10.sisl");
+        assert_eq!(slot_path(Path::new("data"), 3), Path::new("data").join("slot03.sisl"));
+    }
+
+    #[test]
+    fn test_downscale() {
+        let thumbnail = header().thumbnail;
+        assert_eq!((thumbnail.width, thumbnail.height), (4, 2));
+        assert_eq!(thumbnail.rgba.len(), 4 * 2 * 4);
+        assert_eq!(&thumbnail.rgba[4..8], &[4, 0, 0, 255]);
+        assert_eq!(&thumbnail.rgba[16..20], &[64, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_thumbnail_round_trip() {
+        let bytes = encode(&header(), &state());
+        assert_eq!(decode_header(&bytes), Ok(header()));
+        let loaded = decode(&bytes, rom_checksum(b"rom")).unwrap();
+        assert_eq!(loaded.header, header());
+        assert_eq!(loaded.state, state());
+        assert!(!loaded.rom_mismatch);
+    }
+
+    #[test]
+    fn test_header_without_state() {
+        let bytes = encode(&header(), &state());
+        let header_len = bytes.len() - state().to_bytes().len();
+        assert_eq!(decode_header(&bytes[..header_len]), Ok(header()));
+        assert_eq!(decode(&bytes[..header_len], 0), Err(SaveStateError::Truncated));
+    }
+
+    #[test]
+    fn test_rom_checksum_mismatch() {
+        let bytes = encode(&header(), &state());
+        let loaded = decode(&bytes, rom_checksum(b"other rom")).unwrap();
+        assert!(loaded.rom_mismatch);
+        assert_eq!(loaded.state, state());
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut bytes = encode(&header(), &state());
+        bytes[0] = b'X';
+        assert_eq!(decode_header(&bytes), Err(SaveStateError::InvalidMagic));
+    }
+
+    #[test]
+    fn test_age() {
+        assert_eq!(age(100, 100), ("age.just_now", 0));
+        assert_eq!(age(100 + 125, 100), ("age.minutes", 2));
+        assert_eq!(age(7_200, 0), ("age.hours", 2));
+        assert_eq!(age(3 * 86_400, 0), ("age.days", 3));
+        assert_eq!(age(0, 100), ("age.just_now", 0));
+    }
+}
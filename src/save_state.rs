@@ -0,0 +1,365 @@
+use std::fmt;
+use intel8080::emulator::CpuState;
+use crate::emulator_core::{CYCLES_PER_FRAME, CYCLES_PER_HALF_FRAME};
+use crate::event_scheduler::{Event, EventId, Pending, SchedulerState};
+
+const MAGIC: &[u8; 4] = b"SIST";
+/* Format history; each version is the one before plus what its migration adds, and every released
+   version has a fixture under tests/fixtures
+     1  CPU, shift register, sound latches, frame, seed, RNG state, RAM
+     2  event scheduler between the RNG state and RAM */
+pub const VERSION: u16 = 2;
+/* Rewrites the body (everything after magic and version) of a version n file as version n + 1; entry n - 1 */
+type Migration = fn(&[u8]) -> Result<Vec<u8>, SaveStateError>;
+const MIGRATIONS: [Migration; VERSION as usize - 1] = [v1_to_v2];
+/* Registers, PC, SP, interrupt and halt flags, wait cycles, shift register, offset, ports 3 and 5,
+   then frame, seed and RNG state */
+const V1_FIXED_SIZE: usize = 8 + 2 + 2 + 1 + 1 + 4 + 2 + 1 + 1 + 1 + 8 + 8 + 8;
+pub const RAM_START: u16 = 0x2000;
+pub const RAM_SIZE: usize = 8_192;
+/* RAM bytes listed by diff before the rest are only counted */
+const DIFF_RAM_LINES: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum SaveStateError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    /* Written by a newer emulator than this one */
+    FutureVersion(u16),
+    Truncated,
+    UnknownEvent(u8),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveStateError::InvalidMagic => write!(f, "not a save state file"),
+            SaveStateError::UnsupportedVersion(version) => write!(f, "unsupported save state version {}", version),
+            SaveStateError::FutureVersion(version) => write!(f, "save state version {} is from a newer emulator; this one reads up to version {}", version, VERSION),
+            SaveStateError::Truncated => write!(f, "save state file is truncated"),
+            SaveStateError::UnknownEvent(event) => write!(f, "save state schedules unknown event {}", event),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveState {
+    pub cpu: CpuState,
+    pub ram: Vec<u8>,
+    pub shift_register: u16,
+    pub shift_register_offset: u8,
+    pub last_port3: u8,
+    pub last_port5: u8,
+    pub frame: u64,
+    pub seed: u64,
+    pub rng_state: u64,
+    pub scheduler: SchedulerState,
+}
+
+impl SaveState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[self.cpu.a, self.cpu.b, self.cpu.c, self.cpu.d, self.cpu.e, self.cpu.h, self.cpu.l, self.cpu.flags]);
+        bytes.extend_from_slice(&self.cpu.pc.to_le_bytes());
+        bytes.extend_from_slice(&self.cpu.sp.to_le_bytes());
+        bytes.push(self.cpu.interrupt_enabled as u8);
+        bytes.push(self.cpu.halted as u8);
+        bytes.extend_from_slice(&(self.cpu.wait_cycles as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.shift_register.to_le_bytes());
+        bytes.push(self.shift_register_offset);
+        bytes.push(self.last_port3);
+        bytes.push(self.last_port5);
+        bytes.extend_from_slice(&self.frame.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.rng_state.to_le_bytes());
+        bytes.extend_from_slice(&self.scheduler.now.to_le_bytes());
+        bytes.extend_from_slice(&self.scheduler.next_id.to_le_bytes());
+        bytes.extend_from_slice(&(self.scheduler.pending.len() as u16).to_le_bytes());
+        for pending in self.scheduler.pending.iter() {
+            bytes.extend_from_slice(&pending.id.0.to_le_bytes());
+            bytes.extend_from_slice(&pending.due.to_le_bytes());
+            bytes.push(pending.event.to_byte());
+        }
+        bytes.extend_from_slice(&self.ram);
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SaveStateError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != MAGIC {
+            return Err(SaveStateError::InvalidMagic);
+        }
+        let version = reader.u16()?;
+        if version == 0 {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        if version > VERSION {
+            return Err(SaveStateError::FutureVersion(version));
+        }
+        let mut body = reader.rest().to_vec();
+        for migrate in &MIGRATIONS[version as usize - 1..] {
+            body = migrate(&body)?;
+        }
+        return Self::from_body(&body);
+    }
+
+    /* A body in the current format */
+    fn from_body(bytes: &[u8]) -> Result<Self, SaveStateError> {
+        let mut reader = Reader::new(bytes);
+        let registers = reader.take(8)?;
+        let cpu = CpuState {
+            a: registers[0],
+            b: registers[1],
+            c: registers[2],
+            d: registers[3],
+            e: registers[4],
+            h: registers[5],
+            l: registers[6],
+            flags: registers[7],
+            pc: reader.u16()?,
+            sp: reader.u16()?,
+            interrupt_enabled: reader.u8()? != 0,
+            halted: reader.u8()? != 0,
+            wait_cycles: reader.u32()? as usize,
+        };
+        let shift_register = reader.u16()?;
+        let shift_register_offset = reader.u8()?;
+        let last_port3 = reader.u8()?;
+        let last_port5 = reader.u8()?;
+        let frame = reader.u64()?;
+        let seed = reader.u64()?;
+        let rng_state = reader.u64()?;
+        let mut scheduler = SchedulerState { now: reader.u64()?, next_id: reader.u64()?, pending: vec![] };
+        for _ in 0..reader.u16()? {
+            let id = EventId(reader.u64()?);
+            let due = reader.u64()?;
+            let byte = reader.u8()?;
+            let event = Event::from_byte(byte).ok_or(SaveStateError::UnknownEvent(byte))?;
+            scheduler.pending.push(Pending { id, due, event });
+        }
+        let ram = reader.take(RAM_SIZE)?.to_vec();
+        Ok(Self { cpu, ram, shift_register, shift_register_offset, last_port3, last_port5, frame, seed, rng_state, scheduler })
+    }
+
+    /* One line per field that differs, "name: self != other", in save order */
+    pub fn diff(&self, other: &SaveState) -> Vec<String> {
+        let mut lines = vec![];
+        let mut field = |name: &str, left: String, right: String| {
+            if left != right {
+                lines.push(format!("{}: {} != {}", name, left, right));
+            }
+        };
+        let (a, b) = (&self.cpu, &other.cpu);
+        for (name, left, right) in [("A", a.a, b.a), ("B", a.b, b.b), ("C", a.c, b.c), ("D", a.d, b.d), ("E", a.e, b.e), ("H", a.h, b.h), ("L", a.l, b.l), ("F", a.flags, b.flags)] {
+            field(name, format!("{:02X}", left), format!("{:02X}", right));
+        }
+        field("PC", format!("{:04X}", a.pc), format!("{:04X}", b.pc));
+        field("SP", format!("{:04X}", a.sp), format!("{:04X}", b.sp));
+        field("interrupts enabled", a.interrupt_enabled.to_string(), b.interrupt_enabled.to_string());
+        field("halted", a.halted.to_string(), b.halted.to_string());
+        field("wait cycles", a.wait_cycles.to_string(), b.wait_cycles.to_string());
+        field("shift register", format!("{:04X}", self.shift_register), format!("{:04X}", other.shift_register));
+        field("shift offset", self.shift_register_offset.to_string(), other.shift_register_offset.to_string());
+        field("port 3", format!("{:02X}", self.last_port3), format!("{:02X}", other.last_port3));
+        field("port 5", format!("{:02X}", self.last_port5), format!("{:02X}", other.last_port5));
+        field("frame", self.frame.to_string(), other.frame.to_string());
+        field("seed", self.seed.to_string(), other.seed.to_string());
+        field("rng state", format!("{:016X}", self.rng_state), format!("{:016X}", other.rng_state));
+        field("scheduler", format!("{:?}", self.scheduler), format!("{:?}", other.scheduler));
+        let ram: Vec<usize> = (0..self.ram.len().max(other.ram.len())).filter(|i| self.ram.get(*i) != other.ram.get(*i)).collect();
+        for &i in ram.iter().take(DIFF_RAM_LINES) {
+            let byte = |ram: &[u8]| ram.get(i).map_or("--".to_string(), |byte| format!("{:02X}", byte));
+            lines.push(format!("${:04X}: {} != {}", RAM_START as usize + i, byte(&self.ram), byte(&other.ram)));
+        }
+        if ram.len() > DIFF_RAM_LINES {
+            lines.push(format!("and {} more RAM bytes", ram.len() - DIFF_RAM_LINES));
+        }
+        return lines;
+    }
+}
+
+/* Version 1 states were taken just after a VBlank, so the schedule is the one a core has at that point;
+   the cycle count is rebuilt from the frame, as version 1 did not keep it, and no watchdog is pending */
+fn v1_to_v2(body: &[u8]) -> Result<Vec<u8>, SaveStateError> {
+    let mut reader = Reader::new(body);
+    let fixed = reader.take(V1_FIXED_SIZE)?;
+    let frame = u64::from_le_bytes(fixed[V1_FIXED_SIZE - 24..V1_FIXED_SIZE - 16].try_into().unwrap());
+    let now = frame * CYCLES_PER_FRAME as u64;
+    /* Power-on schedules two events and every VBlank two more */
+    let next_id = 2 * frame + 2;
+    let pending = [(next_id - 2, now + CYCLES_PER_HALF_FRAME as u64, Event::MidScreen), (next_id - 1, now + CYCLES_PER_FRAME as u64, Event::VBlank)];
+    let mut migrated = fixed.to_vec();
+    migrated.extend_from_slice(&now.to_le_bytes());
+    migrated.extend_from_slice(&next_id.to_le_bytes());
+    migrated.extend_from_slice(&(pending.len() as u16).to_le_bytes());
+    for (id, due, event) in pending {
+        migrated.extend_from_slice(&id.to_le_bytes());
+        migrated.extend_from_slice(&due.to_le_bytes());
+        migrated.push(event.to_byte());
+    }
+    migrated.extend_from_slice(reader.rest());
+    return Ok(migrated);
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    pub fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.pos + len > self.bytes.len() {
+            return Err(SaveStateError::Truncated);
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator_core::EmulatorCore;
+    use crate::machine::InputState;
+    use crate::test_rom;
+
+    /* Built-in test ROM states saved after 120 frames; add one per format version as it ships */
+    const FIXTURES: [(u16, &[u8]); 2] = [
+        (1, include_bytes!("../tests/fixtures/savestate_v1.bin")),
+        (2, include_bytes!("../tests/fixtures/savestate_v2.bin")),
+    ];
+
+    fn sample() -> SaveState {
+        SaveState {
+            cpu: CpuState { a: 1, b: 2, c: 3, d: 4, e: 5, h: 6, l: 7, pc: 0x1A32, sp: 0x23FE, flags: 0b01000110, interrupt_enabled: true, halted: false, wait_cycles: 9 },
+            ram: (0..RAM_SIZE).map(|i| i as u8).collect(),
+            shift_register: 0xCDAB,
+            shift_register_offset: 3,
+            last_port3: 0x02,
+            last_port5: 0x10,
+            frame: 1_234,
+            seed: 42,
+            rng_state: 0xDEAD_BEEF,
+            scheduler: SchedulerState {
+                now: 41_000_000,
+                next_id: 7,
+                pending: vec![
+                    Pending { id: EventId(5), due: 41_016_667, event: Event::MidScreen },
+                    Pending { id: EventId(6), due: 41_033_333, event: Event::VBlank },
+                ],
+            },
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let state = sample();
+        assert_eq!(SaveState::from_bytes(&state.to_bytes()), Ok(state));
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut bytes = sample().to_bytes();
+        bytes[0] = b'X';
+        assert_eq!(SaveState::from_bytes(&bytes), Err(SaveStateError::InvalidMagic));
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut bytes = sample().to_bytes();
+        bytes[4] = 0;
+        assert_eq!(SaveState::from_bytes(&bytes), Err(SaveStateError::UnsupportedVersion(0)));
+        bytes[4] = 99;
+        assert_eq!(SaveState::from_bytes(&bytes), Err(SaveStateError::FutureVersion(99)));
+        assert_eq!(SaveStateError::FutureVersion(99).to_string(), format!("save state version 99 is from a newer emulator; this one reads up to version {}", VERSION));
+    }
+
+    #[test]
+    fn test_fixtures_load_and_run() {
+        assert_eq!(FIXTURES.last().unwrap().0, VERSION);
+        for (version, bytes) in FIXTURES {
+            assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), version);
+            let state = SaveState::from_bytes(bytes).unwrap_or_else(|error| panic!("version {}: {}", version, error));
+            assert_eq!(state.frame, 120, "version {}", version);
+            let mut core = EmulatorCore::new(test_rom::rom(), InputState::default());
+            core.load_state(&state);
+            for _ in 0..60 {
+                core.step_frame();
+            }
+            let after = core.save_state();
+            assert_eq!(after.frame, 180, "version {}", version);
+            assert!(after.scheduler.pending.iter().any(|pending| pending.event == Event::VBlank), "version {}", version);
+        }
+    }
+
+    #[test]
+    fn test_v1_migration_rebuilds_schedule() {
+        let v1 = SaveState::from_bytes(FIXTURES[0].1).unwrap();
+        let v2 = SaveState::from_bytes(FIXTURES[1].1).unwrap();
+        /* Only the schedule is new; the v2 one also has the watchdog the test ROM arms */
+        assert_eq!(v1.diff(&v2).iter().map(|line| line.split(':').next().unwrap()).collect::<Vec<_>>(), ["scheduler"]);
+        assert_eq!(v1.scheduler.now, v2.scheduler.now);
+        let events: Vec<Event> = v1.scheduler.pending.iter().map(|pending| pending.event).collect();
+        assert_eq!(events, [Event::MidScreen, Event::VBlank]);
+        assert_eq!(v1.scheduler.pending[1].due, v2.scheduler.pending.iter().find(|pending| pending.event == Event::VBlank).unwrap().due);
+    }
+
+    #[test]
+    fn test_unknown_event() {
+        let mut bytes = sample().to_bytes();
+        let event_byte = bytes.len() - RAM_SIZE - 1;
+        bytes[event_byte] = 9;
+        assert_eq!(SaveState::from_bytes(&bytes), Err(SaveStateError::UnknownEvent(9)));
+    }
+
+    #[test]
+    fn test_diff() {
+        let state = sample();
+        assert_eq!(state.diff(&state), Vec::<String>::new());
+        let mut other = sample();
+        other.cpu.pc = 0x1A35;
+        other.cpu.halted = true;
+        other.rng_state += 1;
+        for i in 0..20 {
+            other.ram[0x100 + i] ^= 0xFF;
+        }
+        let lines = state.diff(&other);
+        assert_eq!(&lines[..3], ["PC: 1A32 != 1A35", "halted: false != true", "rng state: 00000000DEADBEEF != 00000000DEADBEF0"]);
+        assert_eq!(lines[3], "$2100: 00 != FF");
+        assert_eq!(lines.len(), 3 + DIFF_RAM_LINES + 1);
+        assert_eq!(lines.last().unwrap(), "and 4 more RAM bytes");
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = sample().to_bytes();
+        assert_eq!(SaveState::from_bytes(&bytes[..bytes.len() - 1]), Err(SaveStateError::Truncated));
+    }
+}
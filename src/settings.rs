@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use crate::actions::Action;
+use crate::debug_budget;
+use crate::displays::SavedMonitor;
+use crate::hotkeys::ControlSettings;
+use crate::machine::DipSwitches;
+use crate::macros::Macro;
+use crate::strings::Language;
+use crate::video::VideoAdjust;
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub coin_blip: bool,
+    pub volume: f32,
+    pub idle_pause_minutes: u32,
+    pub dip_switches: DipSwitches,
+    pub randomize_ram: bool,
+    pub macros: Vec<Macro>,
+    pub language: Language,
+    pub high_contrast: bool,
+    /* Visual mod, off by default so the presented frame matches the cabinet */
+    pub starfield: bool,
+    pub start_lamps: bool,
+    /* Hold presses released between two emulated frames for a frame, so the game sees them */
+    pub stretch_short_presses: bool,
+    pub break_on_unexpected_reset: bool,
+    pub video: VideoAdjust,
+    /* None fullscreens on whichever monitor the window is on */
+    pub fullscreen_display: Option<SavedMonitor>,
+    pub performance: Performance,
+    /* Preset name and per-preset key overrides */
+    pub controls: ControlSettings,
+    /* Saves a PNG in the data directory whenever the session's high score is beaten */
+    pub high_score_screenshots: bool,
+    /* Only used in builds with the http-state feature */
+    pub state_server: bool,
+    pub state_server_port: u16,
+    /* Shared by the I/O log, sound timeline and input log */
+    pub debug_buffer_budget_mb: u32,
+}
+
+/* Cheaper presentation for hosts that cannot keep up; emulation always stays at 60Hz */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Performance {
+    pub auto_degrade: bool,
+    pub disable_visual_mods: bool,
+    pub monochrome: bool,
+    pub half_rate_present: bool,
+    /* Read when the emulation thread starts */
+    pub prefer_performance_cores: bool,
+    /* Fast-forward only */
+    pub idle_skip: bool,
+}
+
+impl Performance {
+    pub fn degrade(&mut self) {
+        self.disable_visual_mods = true;
+        self.monochrome = true;
+        self.half_rate_present = true;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Input,
+    Video,
+    Audio,
+    Timing,
+    Machine,
+    Interface,
+    Debug,
+}
+
+impl Section {
+    pub const ALL: [Section; 7] = [Section::Input, Section::Video, Section::Audio, Section::Timing, Section::Machine, Section::Interface, Section::Debug];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Section::Input => "settings.section.input",
+            Section::Video => "settings.section.video",
+            Section::Audio => "settings.section.audio",
+            Section::Timing => "settings.section.timing",
+            Section::Machine => "settings.section.machine",
+            Section::Interface => "settings.section.interface",
+            Section::Debug => "settings.section.debug",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Control {
+    Toggle(fn(&mut Settings) -> &mut bool),
+    Slider { field: fn(&mut Settings) -> &mut f32, min: f32, max: f32 },
+    Whole { field: fn(&mut Settings) -> &mut u32, min: u32, max: u32, suffix: &'static str },
+    Port(fn(&mut Settings) -> &mut u16),
+    Language,
+    FullscreenDisplay,
+    /* A setting with an action of its own: a checkbox for a toggle, a button for one edited in its own window */
+    Action(Action),
+}
+
+#[derive(Clone, Copy)]
+pub struct SettingInfo {
+    /* Where the setting is persisted, e.g. video.vignette; a table's id covers every field in it */
+    pub id: &'static str,
+    pub section: Section,
+    /* String keys */
+    pub label: &'static str,
+    pub description: &'static str,
+    pub control: Control,
+}
+
+/* Every persisted setting, in the order the settings window lists them */
+pub const SETTINGS: &[SettingInfo] = &[
+    SettingInfo { id: "stretch_short_presses", section: Section::Input, label: "action.options.stretch_short_presses", description: "settings.help.stretch_short_presses", control: Control::Action(Action::ToggleStretchShortPresses) },
+    SettingInfo { id: "controls", section: Section::Input, label: "action.options.controls", description: "settings.help.controls", control: Control::Action(Action::ToggleControls) },
+    SettingInfo { id: "macros", section: Section::Input, label: "action.view.macros", description: "settings.help.macros", control: Control::Action(Action::ToggleMacros) },
+    SettingInfo { id: "video.brightness", section: Section::Video, label: "video.brightness", description: "settings.help.brightness", control: Control::Slider { field: |settings| &mut settings.video.brightness, min: -1.0, max: 1.0 } },
+    SettingInfo { id: "video.contrast", section: Section::Video, label: "video.contrast", description: "settings.help.contrast", control: Control::Slider { field: |settings| &mut settings.video.contrast, min: 0.0, max: 2.0 } },
+    SettingInfo { id: "video.invert", section: Section::Video, label: "video.invert", description: "settings.help.invert", control: Control::Toggle(|settings| &mut settings.video.invert) },
+    SettingInfo { id: "video.vignette", section: Section::Video, label: "video.vignette", description: "settings.help.vignette", control: Control::Toggle(|settings| &mut settings.video.vignette) },
+    SettingInfo { id: "video.blend", section: Section::Video, label: "video.blend", description: "settings.help.blend", control: Control::Toggle(|settings| &mut settings.video.blend) },
+    SettingInfo { id: "fullscreen_display", section: Section::Video, label: "display.fullscreen_display", description: "settings.help.fullscreen_display", control: Control::FullscreenDisplay },
+    SettingInfo { id: "starfield", section: Section::Video, label: "action.options.starfield", description: "settings.help.starfield", control: Control::Action(Action::ToggleStarfield) },
+    SettingInfo { id: "performance.disable_visual_mods", section: Section::Video, label: "performance.disable_visual_mods", description: "settings.help.disable_visual_mods", control: Control::Toggle(|settings| &mut settings.performance.disable_visual_mods) },
+    SettingInfo { id: "performance.monochrome", section: Section::Video, label: "performance.monochrome", description: "settings.help.monochrome", control: Control::Toggle(|settings| &mut settings.performance.monochrome) },
+    SettingInfo { id: "performance.half_rate_present", section: Section::Video, label: "performance.half_rate_present", description: "settings.help.half_rate_present", control: Control::Toggle(|settings| &mut settings.performance.half_rate_present) },
+    SettingInfo { id: "performance.auto_degrade", section: Section::Video, label: "performance.auto_degrade", description: "settings.help.auto_degrade", control: Control::Toggle(|settings| &mut settings.performance.auto_degrade) },
+    SettingInfo { id: "volume", section: Section::Audio, label: "menu.volume", description: "settings.help.volume", control: Control::Slider { field: |settings| &mut settings.volume, min: 0.0, max: 1.0 } },
+    SettingInfo { id: "coin_blip", section: Section::Audio, label: "action.options.coin_blip", description: "settings.help.coin_blip", control: Control::Action(Action::ToggleCoinBlip) },
+    SettingInfo { id: "idle_pause_minutes", section: Section::Timing, label: "menu.idle_pause", description: "settings.help.idle_pause", control: Control::Whole { field: |settings| &mut settings.idle_pause_minutes, min: 0, max: 60, suffix: "" } },
+    SettingInfo { id: "performance.prefer_performance_cores", section: Section::Timing, label: "performance.prefer_performance_cores", description: "settings.help.prefer_performance_cores", control: Control::Toggle(|settings| &mut settings.performance.prefer_performance_cores) },
+    SettingInfo { id: "performance.idle_skip", section: Section::Timing, label: "performance.idle_skip", description: "settings.help.idle_skip", control: Control::Toggle(|settings| &mut settings.performance.idle_skip) },
+    SettingInfo { id: "dip_switches", section: Section::Machine, label: "action.options.dip_switches", description: "settings.help.dip_switches", control: Control::Action(Action::ToggleDipSwitches) },
+    SettingInfo { id: "randomize_ram", section: Section::Machine, label: "action.options.randomize_ram", description: "settings.help.randomize_ram", control: Control::Action(Action::ToggleRandomizeRam) },
+    SettingInfo { id: "language", section: Section::Interface, label: "menu.language", description: "settings.help.language", control: Control::Language },
+    SettingInfo { id: "high_contrast", section: Section::Interface, label: "action.options.high_contrast", description: "settings.help.high_contrast", control: Control::Action(Action::ToggleHighContrast) },
+    SettingInfo { id: "start_lamps", section: Section::Interface, label: "action.options.start_lamps", description: "settings.help.start_lamps", control: Control::Action(Action::ToggleStartLamps) },
+    SettingInfo { id: "high_score_screenshots", section: Section::Interface, label: "action.options.high_score_screenshots", description: "settings.help.high_score_screenshots", control: Control::Action(Action::ToggleHighScoreScreenshots) },
+    SettingInfo { id: "break_on_unexpected_reset", section: Section::Debug, label: "action.debug.break_on_reset", description: "settings.help.break_on_reset", control: Control::Action(Action::ToggleBreakOnReset) },
+    SettingInfo { id: "debug_buffer_budget_mb", section: Section::Debug, label: "settings.debug_budget", description: "settings.help.debug_budget", control: Control::Whole { field: |settings| &mut settings.debug_buffer_budget_mb, min: 1, max: 4_096, suffix: " MiB" } },
+    SettingInfo { id: "state_server", section: Section::Debug, label: "state_server.enable", description: "settings.help.state_server", control: Control::Toggle(|settings| &mut settings.state_server) },
+    SettingInfo { id: "state_server_port", section: Section::Debug, label: "settings.state_server_port", description: "settings.help.state_server_port", control: Control::Port(|settings| &mut settings.state_server_port) },
+];
+
+pub fn info(id: &str) -> &'static SettingInfo {
+    return SETTINGS.iter().find(|info| info.id == id).expect("setting not registered");
+}
+
+/* Every word of the query must match the label or the description; an empty query matches everything */
+pub fn matches(query: &str, label: &str, description: &str) -> bool {
+    let contains = |text: &str, word: &str| text.to_lowercase().contains(&word.to_lowercase());
+    return query.split_whitespace().all(|word| contains(label, word) || contains(description, word));
+}
+
+/* Rows in registry order, given a lookup for the localized label and description */
+pub fn search<'a>(query: &str, text: impl Fn(&'static str) -> &'a str) -> Vec<&'static SettingInfo> {
+    return SETTINGS.iter().filter(|info| matches(query, text(info.label), text(info.description))).collect();
+}
+
+impl Settings {
+    /* Puts back the defaults of one section's settings, through the persisted form so the registry's ids are all it takes */
+    pub fn reset_section(&mut self, section: Section) {
+        let (Ok(toml::Value::Table(mut current)), Ok(toml::Value::Table(defaults))) = (toml::Value::try_from(&*self), toml::Value::try_from(Settings::default())) else {
+            return;
+        };
+        for info in SETTINGS.iter().filter(|info| info.section == section) {
+            let (table, key) = match info.id.split_once('.') {
+                Some((table, key)) => (current.get_mut(table).and_then(|value| value.as_table_mut()), key),
+                None => (Some(&mut current), info.id),
+            };
+            let default = match info.id.split_once('.') {
+                Some((table, key)) => defaults.get(table).and_then(|value| value.get(key)),
+                None => defaults.get(info.id),
+            };
+            match (table, default) {
+                (Some(table), Some(default)) => {
+                    table.insert(key.to_string(), default.clone());
+                },
+                /* Unset options aren't persisted at all */
+                (Some(table), None) => {
+                    table.remove(key);
+                },
+                (None, _) => {},
+            }
+        }
+        if let Ok(settings) = toml::Value::Table(current).try_into() {
+            *self = settings;
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            coin_blip: false,
+            volume: 1.0,
+            idle_pause_minutes: 5,
+            dip_switches: DipSwitches::default(),
+            randomize_ram: false,
+            macros: vec![],
+            language: Language::English,
+            high_contrast: false,
+            starfield: false,
+            start_lamps: false,
+            stretch_short_presses: true,
+            break_on_unexpected_reset: false,
+            video: VideoAdjust::default(),
+            fullscreen_display: None,
+            performance: Performance::default(),
+            controls: ControlSettings::default(),
+            high_score_screenshots: false,
+            state_server: false,
+            state_server_port: 8787,
+            debug_buffer_budget_mb: debug_budget::DEFAULT_BUDGET_MB,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strings::{Language, Strings};
+
+    /* Dotted paths of every leaf the settings persist as */
+    fn persisted_paths(prefix: &str, table: &toml::Table, paths: &mut Vec<String>) {
+        for (key, value) in table {
+            let path = if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+            match value {
+                toml::Value::Table(inner) => persisted_paths(&path, inner, paths),
+                _ => paths.push(path),
+            }
+        }
+    }
+
+    #[test]
+    fn test_registry_ids_unique() {
+        for (index, a) in SETTINGS.iter().enumerate() {
+            assert!(SETTINGS[index + 1..].iter().all(|b| a.id != b.id), "{} registered twice", a.id);
+        }
+        assert_eq!(info("video.vignette").section, Section::Video);
+        assert!(Section::ALL.iter().all(|section| SETTINGS.iter().any(|info| info.section == *section)));
+    }
+
+    #[test]
+    fn test_every_persisted_field_has_metadata() {
+        /* Options are only persisted when set */
+        let settings = Settings { fullscreen_display: Some(SavedMonitor { name: "DISPLAY2".to_string(), position: [0, 0], size: [1920, 1080] }), ..Settings::default() };
+        let toml::Value::Table(table) = toml::Value::try_from(settings).unwrap() else {
+            panic!("settings should persist as a table");
+        };
+        let mut paths = vec![];
+        persisted_paths("", &table, &mut paths);
+        assert!(paths.len() > 25);
+        for path in paths {
+            let covered = SETTINGS.iter().any(|info| path == info.id || path.starts_with(&format!("{}.", info.id)));
+            assert!(covered, "{} has no registry entry", path);
+        }
+    }
+
+    #[test]
+    fn test_filter_matching() {
+        assert!(matches("", "Vignette", "Darkens the corners"));
+        assert!(matches("VIGN", "Vignette", "Darkens the corners"));
+        assert!(matches("corners dark", "Vignette", "Darkens the corners"));
+        assert!(!matches("vignette bright", "Vignette", "Darkens the corners"));
+        let strings = Strings::new(Language::English);
+        let ids = |query: &str| search(query, |key| strings.tr(key)).iter().map(|info| info.id).collect::<Vec<_>>();
+        assert_eq!(ids("vignette"), ["video.vignette"]);
+        /* Descriptions count too */
+        assert_eq!(ids("png"), ["high_score_screenshots"]);
+        assert_eq!(ids("idle"), ["idle_pause_minutes", "performance.idle_skip"]);
+        assert_eq!(ids("").len(), SETTINGS.len());
+        assert!(ids("no such setting").is_empty());
+        for info in SETTINGS {
+            assert_ne!(strings.tr(info.label), info.label);
+            assert_ne!(strings.tr(info.description), info.description);
+        }
+    }
+
+    #[test]
+    fn test_reset_section() {
+        let mut settings = Settings { volume: 0.25, starfield: true, ..Settings::default() };
+        settings.video.vignette = !settings.video.vignette;
+        settings.performance.idle_skip = true;
+        settings.dip_switches.lives = 6;
+        settings.fullscreen_display = Some(SavedMonitor { name: "Projector".to_string(), position: [1920, 0], size: [1280, 720] });
+        settings.reset_section(Section::Video);
+        assert_eq!((settings.video.vignette, settings.starfield), (VideoAdjust::default().vignette, false));
+        assert_eq!(settings.fullscreen_display, None);
+        assert_eq!((settings.volume, settings.performance.idle_skip, settings.dip_switches.lives), (0.25, true, 6));
+        settings.reset_section(Section::Machine);
+        assert_eq!(settings.dip_switches, DipSwitches::default());
+        assert!(settings.performance.idle_skip);
+    }
+}
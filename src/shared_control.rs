@@ -0,0 +1,227 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::mpsc::Receiver;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::debug::DebugCommand;
+use crate::machine::InputState;
+use crate::macros::Macro;
+use crate::memory_access::MemoryRequest;
+use crate::run_mode::ModeCommand;
+use crate::save_slots::SlotCommand;
+use crate::video::VideoOptions;
+
+/* Rare structured requests from the UI, handled in order between frames */
+pub enum ControlCommand {
+    Mode(ModeCommand),
+    Debug(DebugCommand),
+    Slot(SlotCommand),
+    PlayMacro(Macro),
+    Memory(MemoryRequest),
+    /* Starts (or, for a looping sound, stops) a sound through the game's audio channel */
+    SoundTest { sound: usize, play: bool },
+    /* Restarts the machine from power-on, e.g. to bring in a DIP switch the game only reads at start */
+    PowerCycle,
+}
+
+pub struct CommandQueue<T> {
+    queue: Mutex<VecDeque<T>>,
+}
+
+impl<T> CommandQueue<T> {
+    pub fn new() -> Self {
+        Self { queue: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn push(&self, command: T) {
+        self.queue.lock().unwrap().push_back(command);
+    }
+
+    /* Everything queued so far, oldest first */
+    pub fn drain(&self) -> VecDeque<T> {
+        return std::mem::take(&mut *self.queue.lock().unwrap());
+    }
+}
+
+/* Everything the UI tells the emulation thread, created once and shared by both sides. The per-frame
+   fields are atomics so neither side ever waits on the other for them. */
+pub struct SharedControl {
+    /* Both ports and the DIP switches in one word, so a frame never sees halves of two updates */
+    input: AtomicU32,
+    paused: AtomicBool,
+    blip_pending: AtomicBool,
+    idle_skip: AtomicBool,
+    /* Held by the player; read every tick so releasing it drops back to normal speed at once */
+    turbo: AtomicBool,
+    volume: AtomicU32,
+    video: Mutex<VideoOptions>,
+    commands: CommandQueue<ControlCommand>,
+}
+
+impl SharedControl {
+    pub fn new() -> Self {
+        Self {
+            input: AtomicU32::new(InputState::default().to_bits()),
+            paused: AtomicBool::new(false),
+            blip_pending: AtomicBool::new(false),
+            idle_skip: AtomicBool::new(false),
+            turbo: AtomicBool::new(false),
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            video: Mutex::new(VideoOptions::default()),
+            commands: CommandQueue::new(),
+        }
+    }
+
+    pub fn set_input(&self, input: &InputState) {
+        self.input.store(input.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn input(&self) -> InputState {
+        return InputState::from_bits(self.input.load(Ordering::Relaxed));
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn paused(&self) -> bool {
+        return self.paused.load(Ordering::Relaxed);
+    }
+
+    pub fn request_blip(&self) {
+        self.blip_pending.store(true, Ordering::Relaxed);
+    }
+
+    pub fn take_blip(&self) -> bool {
+        return self.blip_pending.swap(false, Ordering::Relaxed);
+    }
+
+    pub fn set_idle_skip(&self, enabled: bool) {
+        self.idle_skip.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn idle_skip(&self) -> bool {
+        return self.idle_skip.load(Ordering::Relaxed);
+    }
+
+    pub fn set_turbo(&self, held: bool) {
+        self.turbo.store(held, Ordering::Relaxed);
+    }
+
+    pub fn turbo(&self) -> bool {
+        return self.turbo.load(Ordering::Relaxed);
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn volume(&self) -> f32 {
+        return f32::from_bits(self.volume.load(Ordering::Relaxed));
+    }
+
+    pub fn set_video(&self, options: VideoOptions) {
+        *self.video.lock().unwrap() = options;
+    }
+
+    pub fn video(&self) -> VideoOptions {
+        return *self.video.lock().unwrap();
+    }
+
+    pub fn send(&self, command: ControlCommand) {
+        self.commands.push(command);
+    }
+
+    /* Serviced at the next frame boundary, after any writes queued before it */
+    pub fn request_read(&self, range: Range<u16>) -> Receiver<Vec<u8>> {
+        let (request, reply) = MemoryRequest::read(range);
+        self.send(ControlCommand::Memory(request));
+        return reply;
+    }
+
+    /* Applied at the next frame boundary, before the CPU runs again */
+    pub fn queue_write(&self, addr: u16, bytes: Vec<u8>) {
+        self.send(ControlCommand::Memory(MemoryRequest::Write { addr, bytes }));
+    }
+
+    pub fn take_commands(&self) -> VecDeque<ControlCommand> {
+        return self.commands.drain();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use crate::machine::DipSwitches;
+
+    #[test]
+    fn test_command_queue_keeps_per_producer_order() {
+        const PRODUCERS: u64 = 4;
+        const PER_PRODUCER: u64 = 5_000;
+        let queue = Arc::new(CommandQueue::new());
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|producer| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for sequence in 0..PER_PRODUCER {
+                        queue.push((producer, sequence));
+                        if sequence % 64 == 0 {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        /* Drain concurrently with the producers, as the emulation thread does */
+        let mut next = [0; PRODUCERS as usize];
+        let mut received = 0;
+        while received < PRODUCERS * PER_PRODUCER {
+            for (producer, sequence) in queue.drain() {
+                assert_eq!(sequence, next[producer as usize], "producer {} out of order", producer);
+                next[producer as usize] += 1;
+                received += 1;
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert!(queue.drain().is_empty());
+        assert_eq!(next, [PER_PRODUCER; PRODUCERS as usize]);
+    }
+
+    #[test]
+    fn test_input_is_never_torn() {
+        let control = Arc::new(SharedControl::new());
+        let left = InputState { p1_left: true, p2_left: true, ..InputState::with_dip_switches(DipSwitches { lives: 5, ..Default::default() }) };
+        let right = InputState { p1_right: true, p2_right: true, ..Default::default() };
+        let writer = {
+            let control = control.clone();
+            thread::spawn(move || {
+                for i in 0..20_000 {
+                    control.set_input(if i % 2 == 0 { &left } else { &right });
+                }
+            })
+        };
+        for _ in 0..20_000 {
+            let seen = control.input();
+            assert!(seen == left || seen == right || seen == InputState::default());
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_blip_and_volume() {
+        let control = SharedControl::new();
+        assert!(!control.take_blip());
+        control.request_blip();
+        control.request_blip();
+        assert!(control.take_blip());
+        assert!(!control.take_blip());
+        assert_eq!(control.volume(), 1.0);
+        control.set_volume(0.35);
+        assert_eq!(control.volume(), 0.35);
+    }
+}
@@ -0,0 +1,104 @@
+// The MB14241 shift register behind OUT 2, OUT 4 and IN 3.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShiftRegister {
+    value: u16,
+    offset: u8,
+}
+
+impl ShiftRegister {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_raw(value: u16, offset: u8) -> Self {
+        let mut shift_register = Self { value, offset: 0 };
+        shift_register.set_offset(offset);
+        return shift_register;
+    }
+
+    /* OUT 4: the new byte enters the high byte and the previous high byte moves to the low byte */
+    pub fn write(&mut self, data: u8) {
+        self.value = ((data as u16) << 8) | (self.value >> 8);
+    }
+
+    /* OUT 2: only the low three bits are wired to the shifter, higher bits are ignored */
+    pub fn set_offset(&mut self, offset: u8) {
+        self.offset = offset & 0x07;
+    }
+
+    /* IN 3 */
+    pub fn read(&self) -> u8 {
+        return (self.value >> (8 - self.offset)) as u8;
+    }
+
+    pub fn raw(&self) -> u16 {
+        return self.value;
+    }
+
+    pub fn offset(&self) -> u8 {
+        return self.offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Lcg(u32);
+
+    impl Lcg {
+        fn next(&mut self) -> u8 {
+            self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            return (self.0 >> 24) as u8;
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let mut shift_register = ShiftRegister::new();
+        shift_register.write(0xAB);
+        shift_register.write(0xCD);
+        assert_eq!(shift_register.raw(), 0xCDAB);
+    }
+
+    #[test]
+    fn test_read_all_offsets() {
+        let shift_register = ShiftRegister::from_raw(0b1100_1010_0101_0011, 0);
+        let expected = [0b1100_1010, 0b1001_0100, 0b0010_1001, 0b0101_0010, 0b1010_0101, 0b0100_1010, 0b1001_0100, 0b0010_1001];
+        for offset in 0..8 {
+            let mut shifted = shift_register;
+            shifted.set_offset(offset);
+            assert_eq!(shifted.read(), expected[offset as usize]);
+        }
+    }
+
+    #[test]
+    fn test_set_offset_masks_unwired_bits() {
+        let mut shift_register = ShiftRegister::new();
+        shift_register.set_offset(0xFC);
+        assert_eq!(shift_register.offset(), 4);
+    }
+
+    #[test]
+    fn test_random_sequences() {
+        let mut rng = Lcg(0x5EED);
+        let mut shift_register = ShiftRegister::new();
+        for _ in 0..10_000 {
+            match rng.next() % 3 {
+                0 => {
+                    let previous_high = (shift_register.raw() >> 8) as u8;
+                    let data = rng.next();
+                    shift_register.write(data);
+                    assert_eq!(shift_register.raw() as u8, previous_high);
+                    assert_eq!((shift_register.raw() >> 8) as u8, data);
+                },
+                1 => shift_register.set_offset(rng.next()),
+                _ => {
+                    let first = shift_register.read();
+                    assert_eq!(shift_register.read(), first);
+                },
+            }
+            assert!(shift_register.offset() < 8);
+        }
+    }
+}
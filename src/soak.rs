@@ -0,0 +1,303 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::alloc_stats;
+use crate::anomaly::{self, Anomaly};
+use crate::emulator_core::EmulatorCore;
+use crate::machine::{self, InputState, LogicalInput};
+use crate::rng::Rng;
+use crate::run_mode::{self, Mode, ModeCommand};
+use crate::save_state::SaveState;
+use crate::shared_control::{ControlCommand, SharedControl};
+
+pub const DEFAULT_MEMORY_CAP: usize = 64 << 20;
+/* Frames run after a save, once on the original core and once on a restored copy */
+const REPLAY_FRAMES: u64 = 60;
+const PROGRESS_FRAMES: u64 = 60;
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct SoakConfig {
+    pub duration: Duration,
+    pub seed: u64,
+    /* Live heap bytes, as counted by alloc_stats */
+    pub memory_cap: usize,
+    /* Every core runs in strict mode, and any anomaly is a violation */
+    pub strict: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SoakReport {
+    pub frames: u64,
+    pub save_loads: u32,
+    pub resets: u32,
+    pub mode_changes: u32,
+    pub peak_memory: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Violation {
+    pub seed: u64,
+    /* Frames run since the start of the soak, counting across resets */
+    pub frame: u64,
+    pub message: String,
+    /* What strict mode caught, when that is the violation */
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl Violation {
+    fn new(seed: u64, frame: u64, message: String) -> Self {
+        Self { seed, frame, message, anomalies: vec![] }
+    }
+
+    /* The machine-readable report --strict prints */
+    pub fn to_json(&self) -> String {
+        let anomalies: Vec<String> = self.anomalies.iter().map(Anomaly::to_json).collect();
+        return format!(
+            "{{\"seed\":{},\"frame\":{},\"message\":{},\"anomalies\":[{}]}}",
+            self.seed, self.frame, anomaly::json_string(&self.message), anomalies.join(",")
+        );
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at soak frame {}; reproduce with --soak-seed {}", self.message, self.frame, self.seed)
+    }
+}
+
+/* Runs the emulation stack headlessly for config.duration: seeded random input, save/load round trips,
+   speed changes through the run mode state machine and resets, checking invariants every frame. The
+   emulation runs on its own thread as in the app, while this one watches its progress, the progress
+   mutex and the heap. Everything but the stopping point follows from the seed, so a violation replays */
+pub fn run(rom: [u8; machine::ROM_SIZE], config: &SoakConfig) -> Result<SoakReport, Violation> {
+    let (seed, strict) = (config.seed, config.strict);
+    let progress = Arc::new(Mutex::new(SoakReport::default()));
+    let stop = Arc::new(AtomicBool::new(false));
+    let worker = {
+        let (progress, stop) = (progress.clone(), stop.clone());
+        thread::spawn(move || Soak::new(rom, seed, strict).run(&progress, &stop))
+    };
+    let started = Instant::now();
+    let mut peak_memory = 0;
+    while !worker.is_finished() {
+        let frame = match progress.lock() {
+            Ok(report) => report.frames,
+            Err(_) => {
+                stop.store(true, Ordering::Relaxed);
+                return Err(Violation::new(seed, 0, "progress mutex poisoned".to_string()));
+            },
+        };
+        peak_memory = peak_memory.max(alloc_stats::live_bytes());
+        if peak_memory > config.memory_cap {
+            stop.store(true, Ordering::Relaxed);
+            return Err(Violation::new(seed, frame, format!("{} bytes live, over the {} byte cap", peak_memory, config.memory_cap)));
+        }
+        if started.elapsed() >= config.duration {
+            stop.store(true, Ordering::Relaxed);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let frame = progress.lock().map(|report| report.frames).unwrap_or_default();
+    return match worker.join() {
+        Ok(result) => result.map(|report| SoakReport { peak_memory, ..report }),
+        Err(panic) => {
+            let message = panic.downcast_ref::<String>().cloned()
+                .or_else(|| panic.downcast_ref::<&str>().map(|message| message.to_string()))
+                .unwrap_or_default();
+            Err(Violation::new(seed, frame, format!("emulation thread panicked: {}", message)))
+        },
+    };
+}
+
+struct Soak {
+    rom: [u8; machine::ROM_SIZE],
+    seed: u64,
+    strict: bool,
+    rng: Rng,
+    core: EmulatorCore,
+    control: SharedControl,
+    mode: Mode,
+    input: InputState,
+    next_save: u64,
+    report: SoakReport,
+}
+
+impl Soak {
+    fn new(rom: [u8; machine::ROM_SIZE], seed: u64, strict: bool) -> Self {
+        let mut rng = Rng::new(seed);
+        let core = Self::power_on(rom, &mut rng, strict);
+        let next_save = 200 + rng.next_u64() % 200;
+        Self {
+            rom,
+            seed,
+            strict,
+            rng,
+            core,
+            control: SharedControl::new(),
+            mode: Mode::Running,
+            input: InputState::default(),
+            next_save,
+            report: SoakReport::default(),
+        }
+    }
+
+    fn power_on(rom: [u8; machine::ROM_SIZE], rng: &mut Rng, strict: bool) -> EmulatorCore {
+        let mut core = EmulatorCore::new(rom, InputState::default());
+        core.set_seed(rng.next_u64());
+        core.randomize_ram();
+        core.set_strict(strict);
+        return core;
+    }
+
+    fn violation(&self, message: String) -> Violation {
+        return Violation::new(self.seed, self.report.frames, message);
+    }
+
+    fn run(mut self, progress: &Mutex<SoakReport>, stop: &AtomicBool) -> Result<SoakReport, Violation> {
+        while !stop.load(Ordering::Relaxed) {
+            self.send_random_command();
+            for command in self.control.take_commands() {
+                if let ControlCommand::Mode(command) = command {
+                    /* Most random commands don't fit the current mode; skip them quietly rather than through apply's log */
+                    if let Ok(next) = run_mode::transition(self.mode, command) {
+                        self.mode = next;
+                        self.report.mode_changes += 1;
+                    }
+                }
+            }
+            if let Mode::Stepping { instructions } = self.mode {
+                for _ in 0..instructions {
+                    self.core.step_instruction();
+                }
+                self.mode = Mode::Paused;
+            }
+            for _ in 0..self.mode.frames_per_tick() {
+                self.frame()?;
+                if self.report.frames % PROGRESS_FRAMES == 0 {
+                    *progress.lock().unwrap() = self.report;
+                }
+            }
+        }
+        return Ok(self.report);
+    }
+
+    /* Roughly one command every few seconds of game time, through the same queue the UI uses */
+    fn send_random_command(&mut self) {
+        let roll = self.rng.next_u8();
+        let command = match roll {
+            0 => ModeCommand::Pause,
+            1..=4 => ModeCommand::Resume,
+            5 => ModeCommand::Step(1 + self.rng.next_u8() as u32 % 100),
+            6 => ModeCommand::FastForward(2 + self.rng.next_u8() as u32 % 7),
+            7 => ModeCommand::NormalSpeed,
+            _ => return,
+        };
+        self.control.send(ControlCommand::Mode(command));
+    }
+
+    fn random_input(&mut self) -> InputState {
+        let mut input = self.input;
+        for logical in LogicalInput::ALL {
+            /* Coins, starts and tilt are rare; the rest change every few frames */
+            let odds = match logical {
+                LogicalInput::Tilt => 4_096,
+                LogicalInput::Coin | LogicalInput::P1Start | LogicalInput::P2Start => 256,
+                _ => 8,
+            };
+            if self.rng.next_u64() % odds == 0 {
+                input.set(logical, !input.get(logical));
+            }
+        }
+        return input;
+    }
+
+    fn step(&mut self, core: Option<&mut EmulatorCore>, input: InputState) -> Result<(), Violation> {
+        let core = match core {
+            Some(core) => core,
+            None => &mut self.core,
+        };
+        let before = core.frame();
+        core.set_input(input);
+        core.step_frame();
+        if core.frame() != before + 1 {
+            return Err(Violation::new(self.seed, self.report.frames, format!("frame counter went from {} to {}", before, core.frame())));
+        }
+        /* Ahead of the reset check, which strict mode reports with more context */
+        if let Some(sink) = core.anomalies().filter(|sink| !sink.is_empty()) {
+            let count = sink.anomalies().len() as u64 + sink.overflow();
+            let message = format!("{} anomalies in strict mode, the first {}", count, sink.anomalies()[0]);
+            return Err(Violation { seed: self.seed, frame: self.report.frames, message, anomalies: sink.anomalies().to_vec() });
+        }
+        if let Some(reset) = core.take_unexpected_reset() {
+            return Err(Violation::new(self.seed, self.report.frames, format!("execution reached $0000 from ${:04X}", reset.from)));
+        }
+        return Ok(());
+    }
+
+    fn frame(&mut self) -> Result<(), Violation> {
+        self.input = self.random_input();
+        self.step(None, self.input)?;
+        self.report.frames += 1;
+        if self.report.frames >= self.next_save {
+            self.save_load_round_trip()?;
+            self.next_save = self.report.frames + 200 + self.rng.next_u64() % 200;
+        }
+        if self.rng.next_u64() % 20_000 == 0 {
+            self.core = Self::power_on(self.rom, &mut self.rng, self.strict);
+            self.report.resets += 1;
+            if self.core.frame() != 0 {
+                return Err(self.violation("reset did not restart the frame counter".to_string()));
+            }
+        }
+        return Ok(());
+    }
+
+    /* Saves, runs on, then replays the same input on a copy restored from the serialized state */
+    fn save_load_round_trip(&mut self) -> Result<(), Violation> {
+        let state = SaveState::from_bytes(&self.core.save_state().to_bytes())
+            .map_err(|error| self.violation(format!("save state did not parse back: {:?}", error)))?;
+        let mut inputs = vec![];
+        for _ in 0..REPLAY_FRAMES {
+            self.input = self.random_input();
+            inputs.push(self.input);
+            self.step(None, self.input)?;
+            self.report.frames += 1;
+        }
+        let mut restored = EmulatorCore::new(self.rom, InputState::default());
+        restored.set_strict(self.strict);
+        restored.load_state(&state);
+        for input in inputs {
+            self.step(Some(&mut restored), input)?;
+        }
+        if restored.ram_checksum() != self.core.ram_checksum() {
+            return Err(self.violation(format!("RAM diverged {} frames after a save/load round trip", REPLAY_FRAMES)));
+        }
+        self.report.save_loads += 1;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_rom;
+
+    #[test]
+    fn test_violation_names_seed() {
+        let violation = Violation::new(42, 1_234, "RAM diverged".to_string());
+        assert_eq!(violation.to_string(), "RAM diverged at soak frame 1234; reproduce with --soak-seed 42");
+        assert_eq!(violation.to_json(), "{\"seed\":42,\"frame\":1234,\"message\":\"RAM diverged\",\"anomalies\":[]}");
+    }
+
+    /* cargo test -- --ignored */
+    #[test]
+    #[ignore]
+    fn test_short_soak() {
+        let config = SoakConfig { duration: Duration::from_secs(5), seed: 1_978, memory_cap: DEFAULT_MEMORY_CAP, strict: true };
+        let report = run(test_rom::rom(), &config).unwrap();
+        assert!(report.frames > REPLAY_FRAMES, "{:?}", report);
+        assert!(report.save_loads > 0, "{:?}", report);
+    }
+}
@@ -0,0 +1,113 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundInfo {
+    /* Also the sample file it plays: 0.wav..9.wav */
+    pub index: usize,
+    pub name: &'static str,
+    /* Repeats for as long as its port bit is set, rather than playing once per rising edge */
+    pub looping: bool,
+}
+
+/* Every sound the two latches can start; see romset::sound_latches for which bit drives which */
+pub const SOUNDS: [SoundInfo; 10] = [
+    SoundInfo { index: 0, name: "UFO", looping: true },
+    SoundInfo { index: 1, name: "Shot", looping: false },
+    SoundInfo { index: 2, name: "Player death", looping: false },
+    SoundInfo { index: 3, name: "Invader killed", looping: false },
+    SoundInfo { index: 4, name: "March 1", looping: false },
+    SoundInfo { index: 5, name: "March 2", looping: false },
+    SoundInfo { index: 6, name: "March 3", looping: false },
+    SoundInfo { index: 7, name: "March 4", looping: false },
+    SoundInfo { index: 8, name: "UFO hit", looping: false },
+    SoundInfo { index: 9, name: "Extended play", looping: false },
+];
+
+pub const SOUND_COUNT: usize = SOUNDS.len();
+
+/* The sound each bit drives on the original board's two latches. Port 3's bit 4 is the extended play
+   chime, which is why its sounds aren't numbered in a run; bit 5 enables the amplifier */
+pub const PORT3_SOUNDS: [usize; 5] = [0, 1, 2, 3, 9];
+pub const PORT5_SOUNDS: [usize; 5] = [4, 5, 6, 7, 8];
+
+/* One of the sound output ports; bit n drives sound `sounds[n]`, and higher bits drive none */
+pub struct SoundLatch {
+    value: u8,
+    sounds: &'static [usize],
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct LatchEdges {
+    pub rising: Vec<usize>,
+    pub falling: Vec<usize>,
+}
+
+impl SoundLatch {
+    pub fn new(sounds: &'static [usize]) -> Self {
+        Self { value: 0, sounds }
+    }
+
+    /* Latches a full OUT value; every bit that changed is reported, so one write may start several sounds */
+    pub fn write(&mut self, value: u8) -> LatchEdges {
+        let mut edges = LatchEdges::default();
+        for (bit, sound) in self.sounds.iter().enumerate() {
+            let mask = 1 << bit;
+            match (self.value & mask != 0, value & mask != 0) {
+                (false, true) => edges.rising.push(*sound),
+                (true, false) => edges.falling.push(*sound),
+                _ => {},
+            }
+        }
+        self.value = value;
+        return edges;
+    }
+
+    pub fn value(&self) -> u8 {
+        return self.value;
+    }
+
+    /* Restores the latched value without reporting edges, e.g. after loading a state */
+    pub fn restore(&mut self, value: u8) {
+        self.value = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::romset::{self, Quirks};
+
+    #[test]
+    fn test_sound_table_covers_every_latch_bit() {
+        for (index, info) in SOUNDS.iter().enumerate() {
+            assert_eq!(info.index, index);
+        }
+        for quirks in [Quirks::NONE, Quirks::ALT_SOUND_MAP] {
+            let (mut port3, mut port5) = romset::sound_latches(quirks);
+            let mut emitted = port3.write(0xFF).rising;
+            emitted.extend(port5.write(0xFF).rising);
+            emitted.sort();
+            assert_eq!(emitted, (0..SOUNDS.len()).collect::<Vec<usize>>(), "{:?}", quirks);
+        }
+    }
+
+    #[test]
+    fn test_multiple_bits_rise_in_one_write() {
+        let mut latch = SoundLatch::new(&PORT5_SOUNDS);
+        let edges = latch.write(0b0001_0101);
+        assert_eq!(edges.rising, vec![4, 6, 8]);
+        assert!(edges.falling.is_empty());
+        let edges = latch.write(0b0000_0011);
+        assert_eq!(edges, LatchEdges { rising: vec![5], falling: vec![6, 8] });
+        assert_eq!(latch.value(), 0b0000_0011);
+    }
+
+    #[test]
+    fn test_held_and_non_sound_bits_ignored() {
+        let mut latch = SoundLatch::new(&PORT3_SOUNDS);
+        latch.write(0b0000_0001);
+        /* Bit 5 is the amplifier enable */
+        assert_eq!(latch.write(0b0010_0001), LatchEdges::default());
+        assert_eq!(latch.write(0b0011_0001).rising, vec![9]);
+        latch.restore(0);
+        assert_eq!(latch.write(0b0000_0001).rising, vec![0]);
+    }
+}
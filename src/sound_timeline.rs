@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use crate::debug_budget::{self, BudgetShare};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundEvent {
+    pub frame: u64,
+    pub sound: usize,
+    pub active: bool,
+    /* Played from Debug → Sound test rather than by the game */
+    pub sound_test: bool,
+}
+
+/* Sound port edges for the last `history_frames` frames */
+#[derive(Clone)]
+pub struct SoundTimeline {
+    events: VecDeque<SoundEvent>,
+    history_frames: u64,
+    last_frame: u64,
+    budget: Option<BudgetShare>,
+}
+
+impl SoundTimeline {
+    pub fn new(history_frames: u64) -> Self {
+        Self {
+            events: VecDeque::new(),
+            history_frames,
+            last_frame: 0,
+            budget: None,
+        }
+    }
+
+    pub fn with_budget(self, budget: BudgetShare) -> Self {
+        return Self { budget: Some(budget), ..self };
+    }
+
+    pub fn push(&mut self, event: SoundEvent) {
+        if event.sound_test {
+            return;
+        }
+        debug_budget::push_bounded(&mut self.events, event, usize::MAX, self.budget.as_ref());
+        self.advance(event.frame);
+    }
+
+    pub fn advance(&mut self, frame: u64) {
+        self.last_frame = self.last_frame.max(frame);
+        let oldest = self.oldest_frame();
+        while self.events.front().map_or(false, |event| event.frame < oldest) {
+            self.events.pop_front();
+        }
+        if let Some(budget) = &self.budget {
+            budget.record(self.events.len());
+        }
+    }
+
+    pub fn oldest_frame(&self) -> u64 {
+        return self.last_frame.saturating_sub(self.history_frames);
+    }
+
+    pub fn history_frames(&self) -> u64 {
+        return self.history_frames;
+    }
+
+    /* Frames at which the sound was triggered */
+    pub fn marks(&self, sound: usize) -> Vec<u64> {
+        self.events.iter()
+            .filter(|event| event.sound == sound && event.active)
+            .map(|event| event.frame)
+            .collect()
+    }
+
+    /* Merged (start, end) spans during which the sound's port bit was held; a span still open ends at the last frame */
+    pub fn spans(&self, sound: usize) -> Vec<(u64, u64)> {
+        let mut spans: Vec<(u64, u64)> = vec![];
+        let mut start: Option<u64> = None;
+        for event in self.events.iter().filter(|event| event.sound == sound) {
+            match (event.active, start) {
+                (true, None) => start = Some(event.frame),
+                (false, Some(from)) => {
+                    push_span(&mut spans, from, event.frame);
+                    start = None;
+                },
+                (false, None) => push_span(&mut spans, self.oldest_frame(), event.frame),
+                (true, Some(_)) => {},
+            }
+        }
+        if let Some(from) = start {
+            push_span(&mut spans, from, self.last_frame);
+        }
+        return spans;
+    }
+}
+
+fn push_span(spans: &mut Vec<(u64, u64)>, start: u64, end: u64) {
+    match spans.last_mut() {
+        Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+        _ => spans.push((start, end)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(frame: u64, sound: usize, active: bool) -> SoundEvent {
+        SoundEvent { frame, sound, active, sound_test: false }
+    }
+
+    #[test]
+    fn test_sound_test_events_are_not_recorded() {
+        let mut timeline = SoundTimeline::new(600);
+        timeline.push(SoundEvent { sound_test: true, ..event(10, 0, true) });
+        timeline.push(event(20, 1, true));
+        assert!(timeline.marks(0).is_empty());
+        assert!(timeline.spans(0).is_empty());
+        assert_eq!(timeline.marks(1), vec![20]);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut timeline = SoundTimeline::new(600);
+        for frame in 0..1_000 {
+            timeline.push(event(frame, 1, frame % 2 == 0));
+        }
+        assert_eq!(timeline.oldest_frame(), 399);
+        assert_eq!(timeline.marks(1).first(), Some(&400));
+        timeline.advance(2_000);
+        assert!(timeline.marks(1).is_empty());
+    }
+
+    #[test]
+    fn test_marks_per_sound() {
+        let mut timeline = SoundTimeline::new(600);
+        timeline.push(event(10, 4, true));
+        timeline.push(event(12, 4, false));
+        timeline.push(event(20, 5, true));
+        timeline.push(event(30, 4, true));
+        assert_eq!(timeline.marks(4), vec![10, 30]);
+        assert_eq!(timeline.marks(5), vec![20]);
+        assert!(timeline.marks(0).is_empty());
+    }
+
+    #[test]
+    fn test_spans_merge_adjacent() {
+        let mut timeline = SoundTimeline::new(600);
+        timeline.push(event(10, 0, true));
+        timeline.push(event(20, 0, false));
+        timeline.push(event(21, 0, true));
+        timeline.push(event(40, 0, false));
+        timeline.push(event(50, 0, true));
+        timeline.push(event(60, 0, false));
+        assert_eq!(timeline.spans(0), vec![(10, 40), (50, 60)]);
+    }
+
+    #[test]
+    fn test_open_span_ends_at_last_frame() {
+        let mut timeline = SoundTimeline::new(600);
+        timeline.push(event(100, 0, true));
+        timeline.advance(150);
+        assert_eq!(timeline.spans(0), vec![(100, 150)]);
+    }
+
+    #[test]
+    fn test_span_start_pruned() {
+        let mut timeline = SoundTimeline::new(100);
+        timeline.push(event(10, 0, true));
+        timeline.push(event(200, 0, false));
+        assert_eq!(timeline.spans(0), vec![(100, 200)]);
+    }
+}
@@ -0,0 +1,170 @@
+// Durations of the frame pipeline's critical sections, collected only with the `tracing` feature.
+#![cfg_attr(not(feature = "tracing"), allow(dead_code))]
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/* Per section, so one long run can't grow without bound; percentiles are over the most recent samples */
+pub const SAMPLES_PER_SECTION: usize = 4_096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectionSummary {
+    pub name: &'static str,
+    pub count: u64,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+#[derive(Default)]
+struct Section {
+    recent: Vec<Duration>,
+    next: usize,
+    count: u64,
+}
+
+#[derive(Default)]
+pub struct SpanStats {
+    sections: BTreeMap<&'static str, Section>,
+}
+
+impl SpanStats {
+    pub fn record(&mut self, name: &'static str, duration: Duration) {
+        let section = self.sections.entry(name).or_default();
+        if section.recent.len() < SAMPLES_PER_SECTION {
+            section.recent.push(duration);
+        } else {
+            section.recent[section.next] = duration;
+        }
+        section.next = (section.next + 1) % SAMPLES_PER_SECTION;
+        section.count += 1;
+    }
+
+    pub fn summary(&self) -> Vec<SectionSummary> {
+        return self
+            .sections
+            .iter()
+            .map(|(name, section)| {
+                let mut sorted = section.recent.clone();
+                sorted.sort();
+                SectionSummary {
+                    name,
+                    count: section.count,
+                    p50: percentile(&sorted, 50.0),
+                    p99: percentile(&sorted, 99.0),
+                    max: sorted.last().copied().unwrap_or_default(),
+                }
+            })
+            .collect();
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::from("section                  count      p50      p99      max\n");
+        for row in self.summary() {
+            writeln!(report, "{:<20} {:>9} {:>8.1?} {:>8.1?} {:>8.1?}", row.name, row.count, row.p50, row.p99, row.max).ok();
+        }
+        return report;
+    }
+}
+
+/* Nearest-rank percentile of an ascending slice */
+pub fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    return sorted[rank.clamp(1, sorted.len()) - 1];
+}
+
+#[cfg(feature = "tracing")]
+pub static GLOBAL: std::sync::Mutex<SpanStats> = std::sync::Mutex::new(SpanStats { sections: BTreeMap::new() });
+
+/* Times a section as a tracing span and into GLOBAL when dropped */
+#[cfg(feature = "tracing")]
+pub struct SectionGuard {
+    name: &'static str,
+    start: std::time::Instant,
+    _span: tracing::span::EnteredSpan,
+}
+
+#[cfg(feature = "tracing")]
+impl SectionGuard {
+    pub fn enter(name: &'static str) -> Self {
+        Self { name, start: std::time::Instant::now(), _span: tracing::trace_span!("section", name).entered() }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for SectionGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        if let Ok(mut stats) = GLOBAL.lock() {
+            stats.record(self.name, elapsed);
+        }
+    }
+}
+
+/* Expands to just the body unless the `tracing` feature is on */
+macro_rules! traced {
+    ($name:expr, $body:expr) => {{
+        #[cfg(feature = "tracing")]
+        let _section = crate::span_stats::SectionGuard::enter($name);
+        $body
+    }};
+}
+pub(crate) use traced;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn micros(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|value| Duration::from_micros(*value)).collect()
+    }
+
+    #[test]
+    fn test_percentiles_nearest_rank() {
+        let sorted = micros(&(1..=100).collect::<Vec<u64>>());
+        assert_eq!(percentile(&sorted, 50.0), Duration::from_micros(50));
+        assert_eq!(percentile(&sorted, 99.0), Duration::from_micros(99));
+        assert_eq!(percentile(&sorted, 100.0), Duration::from_micros(100));
+        assert_eq!(percentile(&micros(&[7]), 99.0), Duration::from_micros(7));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_summary_per_section() {
+        let mut stats = SpanStats::default();
+        /* Unsorted input with one long hold */
+        for value in [30, 10, 20, 40, 5_000, 10, 20, 30, 40, 10] {
+            stats.record("frame_slot", Duration::from_micros(value));
+        }
+        stats.record("audio_send", Duration::from_micros(3));
+        let summary = stats.summary();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0], SectionSummary { name: "audio_send", count: 1, p50: Duration::from_micros(3), p99: Duration::from_micros(3), max: Duration::from_micros(3) });
+        assert_eq!(summary[1].count, 10);
+        assert_eq!(summary[1].p50, Duration::from_micros(20));
+        assert_eq!(summary[1].p99, Duration::from_micros(5_000));
+        assert!(stats.report().contains("frame_slot"));
+    }
+
+    #[test]
+    fn test_only_recent_samples_kept() {
+        let mut stats = SpanStats::default();
+        for _ in 0..SAMPLES_PER_SECTION {
+            stats.record("commands", Duration::from_millis(9));
+        }
+        for _ in 0..SAMPLES_PER_SECTION {
+            stats.record("commands", Duration::from_micros(1));
+        }
+        let summary = stats.summary();
+        assert_eq!(summary[0].count, 2 * SAMPLES_PER_SECTION as u64);
+        assert_eq!(summary[0].max, Duration::from_micros(1));
+    }
+
+    #[test]
+    fn test_traced_returns_body() {
+        assert_eq!(traced!("test", 2 + 2), 4);
+    }
+}
@@ -0,0 +1,94 @@
+/* Non-authentic background layer: a slowly drifting starfield drawn under the game pixels */
+use crate::rng::Rng;
+use crate::video::{Rgba, BLACK};
+
+const STAR_COUNT: usize = 96;
+/* Frames per pixel of drift for the slowest layer */
+const DRIFT_FRAMES: u64 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Star {
+    column: usize,
+    row: usize,
+    /* 1..=3, nearer stars drift faster and shine brighter */
+    depth: u8,
+}
+
+pub struct Starfield {
+    width: usize,
+    stars: Vec<Star>,
+}
+
+impl Starfield {
+    pub fn new(seed: u64, width: usize, height: usize) -> Self {
+        Self::with_count(seed, width, height, STAR_COUNT)
+    }
+
+    fn with_count(seed: u64, width: usize, height: usize, count: usize) -> Self {
+        /* A private generator so the machine's own random stream is never disturbed */
+        let mut rng = Rng::new(seed);
+        let stars = (0..count)
+            .map(|_| Star {
+                column: (rng.next_u64() % width as u64) as usize,
+                row: (rng.next_u64() % height as u64) as usize,
+                depth: 1 + rng.next_u8() % 3,
+            })
+            .collect();
+        Self { width, stars }
+    }
+
+    /* Columns run along the scanline, so decreasing them moves stars down the upright screen */
+    pub fn render(&self, frame: u64, out: &mut [Rgba]) {
+        out.fill(BLACK);
+        for star in &self.stars {
+            let drift = (frame * star.depth as u64 / DRIFT_FRAMES) % self.width as u64;
+            let column = (star.column + self.width - drift as usize) % self.width;
+            let level = 40 + 40 * star.depth;
+            out[star.row * self.width + column] = [level, level, level + 30, 255];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(buffer: &[Rgba]) -> Vec<usize> {
+        buffer.iter().enumerate().filter(|(_, p)| **p != BLACK).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_sky() {
+        let (mut a, mut b) = (vec![BLACK; 64], vec![BLACK; 64]);
+        Starfield::with_count(7, 8, 8, 5).render(0, &mut a);
+        Starfield::with_count(7, 8, 8, 5).render(0, &mut b);
+        assert_eq!(a, b);
+        Starfield::with_count(8, 8, 8, 5).render(0, &mut b);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_stars_drift_and_wrap() {
+        let field = Starfield::with_count(3, 8, 4, 1);
+        let star = field.stars[0];
+        let mut buffer = vec![BLACK; 32];
+        field.render(0, &mut buffer);
+        assert_eq!(lit(&buffer), vec![star.row * 8 + star.column]);
+
+        /* A full lap brings every star back to where it started */
+        field.render(8 * DRIFT_FRAMES, &mut buffer);
+        assert_eq!(lit(&buffer), vec![star.row * 8 + star.column]);
+
+        field.render(DRIFT_FRAMES, &mut buffer);
+        let moved = (star.column + 8 - star.depth as usize) % 8;
+        assert_eq!(lit(&buffer), vec![star.row * 8 + moved]);
+    }
+
+    #[test]
+    fn test_render_clears_previous_frame() {
+        let field = Starfield::with_count(11, 8, 8, 3);
+        let mut buffer = vec![[255, 255, 255, 255]; 64];
+        field.render(0, &mut buffer);
+        assert!(lit(&buffer).len() <= 3);
+    }
+}
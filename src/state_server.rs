@@ -0,0 +1,223 @@
+// Read-only game state for stream overlays: GET /state as JSON and GET /frame.png, on localhost only.
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use crate::png;
+use crate::ram_map;
+use crate::video::Rgba;
+
+/* How often the UI hands the server a fresh snapshot */
+pub const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+/* A client that stalls mid-request or mid-response is dropped after this */
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_REQUEST: usize = 4_096;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GameSnapshot {
+    pub score: u32,
+    pub hi_score: u32,
+    pub lives: u8,
+    pub wave: u32,
+    pub credits: u32,
+    pub frame: u64,
+    pub paused: bool,
+}
+
+impl GameSnapshot {
+    /* Player 1's view; lives and wave are only meaningful while a game is running */
+    pub fn from_ram(ram: &[u8], frame: u64, paused: bool) -> Self {
+        Self {
+            score: ram_map::read_score(ram, ram_map::P1_SCORE),
+            hi_score: ram_map::read_score(ram, ram_map::HI_SCORE),
+            lives: ram_map::read(ram, ram_map::P1_SHIPS),
+            wave: ram_map::read(ram, ram_map::P1_RACK_COUNT) as u32 + 1,
+            credits: ram_map::bcd_to_decimal(ram_map::read(ram, ram_map::CREDITS)),
+            frame,
+            paused,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        return format!(
+            "{{\"score\":{},\"hi_score\":{},\"lives\":{},\"wave\":{},\"credits\":{},\"frame\":{},\"paused\":{}}}",
+            self.score, self.hi_score, self.lives, self.wave, self.credits, self.frame, self.paused
+        );
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    snapshot: GameSnapshot,
+    /* Native resolution, row-major; encoded on request by the serving thread */
+    frame: Option<(usize, usize, Arc<Vec<Rgba>>)>,
+}
+
+/* Serves from its own threads; the UI only ever swaps the shared snapshot under a short lock */
+pub struct StateServer {
+    addr: SocketAddr,
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StateServer {
+    /* Port 0 picks a free port; see addr() */
+    pub fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (shared_clone, stop_clone) = (shared.clone(), stop.clone());
+        let thread = thread::Builder::new().name("state-server".to_string()).spawn(move || {
+            for stream in listener.incoming() {
+                if stop_clone.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let shared = shared_clone.clone();
+                    thread::spawn(move || {
+                        let _ = serve(stream, &shared);
+                    });
+                }
+            }
+        })?;
+        Ok(Self { addr, shared, stop, thread: Some(thread) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn publish(&self, snapshot: GameSnapshot, frame: Option<(usize, usize, Arc<Vec<Rgba>>)>) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.snapshot = snapshot;
+        if frame.is_some() {
+            shared.frame = frame;
+        }
+    }
+}
+
+impl Drop for StateServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        /* Wake the blocked accept so the thread sees the flag */
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream, shared: &Mutex<Shared>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut request = vec![];
+    let mut buffer = [0; 512];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.lines().next().unwrap_or("").split_whitespace();
+    let (method, path) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+    /* Query strings are allowed so overlays can bust caches */
+    let path = path.split('?').next().unwrap_or("");
+    let (status, content_type, body) = if method != "GET" {
+        ("405 Method Not Allowed", "text/plain", b"GET only\n".to_vec())
+    } else {
+        match path {
+            "/state" => ("200 OK", "application/json", shared.lock().unwrap().snapshot.to_json().into_bytes()),
+            "/frame.png" => {
+                let frame = shared.lock().unwrap().frame.clone();
+                match frame {
+                    Some((width, height, pixels)) => ("200 OK", "image/png", png::encode(&pixels, width, height)),
+                    None => ("503 Service Unavailable", "text/plain", b"no frame yet\n".to_vec()),
+                }
+            },
+            _ => ("404 Not Found", "text/plain", b"try /state or /frame.png\n".to_vec()),
+        }
+    };
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(&body)?;
+    return stream.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(addr: SocketAddr, path: &str) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|window| window == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..split].to_vec()).unwrap();
+        return (head, response[split + 4..].to_vec());
+    }
+
+    /* Splits the flat object into key and raw value pairs; enough for the schema check */
+    fn json_fields(json: &str) -> Vec<(String, String)> {
+        assert!(json.starts_with('{') && json.ends_with('}'), "{}", json);
+        return json[1..json.len() - 1].split(',').map(|field| {
+            let (key, value) = field.split_once(':').unwrap();
+            assert!(key.starts_with('"') && key.ends_with('"'), "{}", key);
+            (key.trim_matches('"').to_string(), value.to_string())
+        }).collect();
+    }
+
+    #[test]
+    fn test_state_json_schema() {
+        let server = StateServer::start(0).unwrap();
+        assert!(server.addr().ip().is_loopback());
+        let mut ram = vec![0; ram_map::WORK_RAM_SIZE];
+        let mut poke = |addr: u16, value: u8| ram[(addr - ram_map::WORK_RAM_START) as usize] = value;
+        poke(ram_map::P1_SCORE, 0x50);
+        poke(ram_map::P1_SCORE + 1, 0x01);
+        poke(ram_map::HI_SCORE + 1, 0x12);
+        poke(ram_map::P1_SHIPS, 3);
+        poke(ram_map::CREDITS, 0x12);
+        server.publish(GameSnapshot::from_ram(&ram, 4_321, true), None);
+        let (head, body) = get(server.addr(), "/state");
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+        assert!(head.contains("Content-Type: application/json"), "{}", head);
+        let fields = json_fields(&String::from_utf8(body).unwrap());
+        let keys: Vec<&str> = fields.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["score", "hi_score", "lives", "wave", "credits", "frame", "paused"]);
+        for (key, value) in &fields {
+            match key.as_str() {
+                "paused" => assert!(value == "true" || value == "false", "{}", value),
+                _ => assert!(value.parse::<u64>().is_ok(), "{}: {}", key, value),
+            }
+        }
+        let value = |key: &str| fields.iter().find(|(name, _)| name == key).unwrap().1.clone();
+        assert_eq!((value("score"), value("hi_score"), value("lives")), ("150".to_string(), "1200".to_string(), "3".to_string()));
+        assert_eq!((value("wave"), value("credits"), value("frame"), value("paused")), ("1".to_string(), "12".to_string(), "4321".to_string(), "true".to_string()));
+    }
+
+    #[test]
+    fn test_frame_png_and_unknown_paths() {
+        let server = StateServer::start(0).unwrap();
+        let (head, _) = get(server.addr(), "/frame.png");
+        assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+        let pixels = Arc::new(vec![[10, 20, 30, 255]; 256 * 224]);
+        server.publish(GameSnapshot::default(), Some((256, 224, pixels)));
+        let (head, png) = get(server.addr(), "/frame.png?t=1");
+        assert!(head.contains("Content-Type: image/png"), "{}", head);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 1, 0, 0, 0, 0, 224]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+        assert!(get(server.addr(), "/").0.starts_with("HTTP/1.1 404"));
+    }
+}
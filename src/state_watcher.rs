@@ -0,0 +1,151 @@
+use crate::ram_map;
+
+/* The game copies a new high score in a digit at a time; only a value held this long counts */
+pub const HIGH_SCORE_STABLE_FRAMES: u64 = 30;
+
+#[derive(Debug, PartialEq)]
+pub enum StateEvent {
+    CreditAdded(u32),
+    NewHighScore(u32),
+}
+
+pub struct StateWatcher {
+    last_credits: Option<u32>,
+    /* The high score in RAM and the emulated frame it was first seen */
+    high_score: Option<(u32, u64)>,
+    /* Best stable high score this session; the first one is the baseline and raises no event */
+    best_high_score: Option<u32>,
+}
+
+impl StateWatcher {
+    pub fn new() -> Self {
+        Self {
+            last_credits: None,
+            high_score: None,
+            best_high_score: None,
+        }
+    }
+
+    pub fn update(&mut self, ram: &[u8], frame: u64) -> Vec<StateEvent> {
+        let mut events = vec![];
+        if let Some(score) = self.update_high_score(ram_map::read_score(ram, ram_map::HI_SCORE), frame) {
+            events.push(StateEvent::NewHighScore(score));
+        }
+        let credits = ram_map::bcd_to_decimal(ram_map::read(ram, ram_map::CREDITS));
+        if let Some(last) = self.last_credits {
+            if credits > last {
+                events.push(StateEvent::CreditAdded(credits - last));
+            }
+        }
+        self.last_credits = Some(credits);
+        return events;
+    }
+
+    fn update_high_score(&mut self, score: u32, frame: u64) -> Option<u32> {
+        match self.high_score {
+            Some((last, since)) if last == score => {
+                if frame.saturating_sub(since) < HIGH_SCORE_STABLE_FRAMES {
+                    return None;
+                }
+            },
+            _ => {
+                self.high_score = Some((score, frame));
+                return None;
+            },
+        }
+        match self.best_high_score {
+            None => self.best_high_score = Some(score),
+            Some(best) if score > best => {
+                self.best_high_score = Some(score);
+                return Some(score);
+            },
+            Some(_) => {},
+        }
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(credits: u8) -> [u8; ram_map::WORK_RAM_SIZE] {
+        let mut ram = [0; ram_map::WORK_RAM_SIZE];
+        ram[(ram_map::CREDITS - ram_map::WORK_RAM_START) as usize] = credits;
+        return ram;
+    }
+
+    #[test]
+    fn test_first_snapshot_has_no_events() {
+        let mut watcher = StateWatcher::new();
+        assert_eq!(watcher.update(&snapshot(0x05), 0), vec![]);
+    }
+
+    #[test]
+    fn test_credit_added() {
+        let mut watcher = StateWatcher::new();
+        watcher.update(&snapshot(0x00), 0);
+        assert_eq!(watcher.update(&snapshot(0x01), 0), vec![StateEvent::CreditAdded(1)]);
+        assert_eq!(watcher.update(&snapshot(0x01), 0), vec![]);
+    }
+
+    #[test]
+    fn test_credit_added_across_bcd_digit() {
+        let mut watcher = StateWatcher::new();
+        watcher.update(&snapshot(0x09), 0);
+        assert_eq!(watcher.update(&snapshot(0x10), 0), vec![StateEvent::CreditAdded(1)]);
+    }
+
+    #[test]
+    fn test_credit_spent() {
+        let mut watcher = StateWatcher::new();
+        watcher.update(&snapshot(0x02), 0);
+        assert_eq!(watcher.update(&snapshot(0x01), 0), vec![]);
+    }
+
+    /* Feeds one high score (BCD, as in RAM) per frame and collects the NewHighScore events */
+    fn high_scores(sequence: &[u16]) -> Vec<(u64, u32)> {
+        let mut watcher = StateWatcher::new();
+        let mut fired = vec![];
+        for (frame, bcd) in sequence.iter().enumerate() {
+            let mut ram = [0; ram_map::WORK_RAM_SIZE];
+            let offset = (ram_map::HI_SCORE - ram_map::WORK_RAM_START) as usize;
+            ram[offset..offset + 2].copy_from_slice(&bcd.to_le_bytes());
+            for event in watcher.update(&ram, frame as u64) {
+                if let StateEvent::NewHighScore(score) = event {
+                    fired.push((frame as u64, score));
+                }
+            }
+        }
+        return fired;
+    }
+
+    fn held(bcd: u16, frames: usize) -> Vec<u16> {
+        return vec![bcd; frames];
+    }
+
+    #[test]
+    fn test_new_high_score_waits_until_stable() {
+        let stable = HIGH_SCORE_STABLE_FRAMES as usize;
+        /* Baseline 0, then the copy routine writes 1,230 a byte at a time over two frames */
+        let sequence = [held(0x0000, stable + 5), vec![0x0030, 0x1230], held(0x1230, stable + 10)].concat();
+        assert_eq!(high_scores(&sequence), vec![((stable + 5 + 1 + stable) as u64, 1_230)]);
+    }
+
+    #[test]
+    fn test_baseline_and_lower_scores_do_not_fire() {
+        let stable = HIGH_SCORE_STABLE_FRAMES as usize;
+        assert_eq!(high_scores(&held(0x0500, stable * 3)), vec![]);
+        /* A loaded state with a lower high score, then back up to the old best */
+        let sequence = [held(0x0500, stable + 1), held(0x0200, stable + 1), held(0x0500, stable + 1)].concat();
+        assert_eq!(high_scores(&sequence), vec![]);
+    }
+
+    #[test]
+    fn test_flicker_shorter_than_window_is_ignored() {
+        let stable = HIGH_SCORE_STABLE_FRAMES as usize;
+        let sequence = [held(0x0100, stable + 1), held(0x0900, stable - 1), held(0x0100, stable + 1), held(0x0150, stable + 1), held(0x0200, stable + 1)].concat();
+        let fired = high_scores(&sequence);
+        assert_eq!(fired.iter().map(|(_, score)| *score).collect::<Vec<_>>(), vec![150, 200]);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub struct SessionStats {
+    play_time: Duration,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        Self {
+            play_time: Duration::ZERO,
+        }
+    }
+
+    pub fn add_play_time(&mut self, elapsed: Duration) {
+        self.play_time += elapsed;
+    }
+
+    pub fn play_time(&self) -> Duration {
+        return self.play_time;
+    }
+}
+
+/* Emulation loop health, written by the emulation thread */
+#[derive(Clone, Copy, Default)]
+pub struct PerfStats {
+    pub resyncs: u32,
+    pub dropped_time: Duration,
+    pub frames: u64,
+    /* Frames that finished after their deadline */
+    pub late_frames: u64,
+    /* Audio commands merged or discarded because the audio thread fell behind */
+    pub audio_coalesced: u64,
+    pub audio_dropped: u64,
+}
+
+impl PerfStats {
+    pub fn record_frame(&mut self, late: bool) {
+        self.frames += 1;
+        if late {
+            self.late_frames += 1;
+        }
+    }
+
+    pub fn record_resync(&mut self, dropped: Duration) {
+        self.resyncs += 1;
+        self.dropped_time += dropped;
+    }
+}
+
+pub const OVERRUN_WINDOW: Duration = Duration::from_secs(2);
+pub const OVERRUN_PERCENT: u64 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverrunVerdict {
+    Keep,
+    Warn,
+    Degrade,
+}
+
+/* Samples PerfStats from the UI and reacts once when too many frames in the window were late */
+pub struct OverrunPolicy {
+    samples: VecDeque<(Instant, u64, u64)>,
+    reported: bool,
+}
+
+impl OverrunPolicy {
+    pub fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+            reported: false,
+        }
+    }
+
+    pub fn sample(&mut self, now: Instant, perf: &PerfStats, auto_degrade: bool) -> OverrunVerdict {
+        self.samples.push_back((now, perf.frames, perf.late_frames));
+        /* Keep the newest sample that is at least a full window old as the baseline */
+        while self.samples.len() > 2 && now.saturating_duration_since(self.samples[1].0) >= OVERRUN_WINDOW {
+            self.samples.pop_front();
+        }
+        let (since, frames, late_frames) = self.samples[0];
+        if self.reported || now.saturating_duration_since(since) < OVERRUN_WINDOW {
+            return OverrunVerdict::Keep;
+        }
+        let frames = perf.frames - frames;
+        let late_frames = perf.late_frames - late_frames;
+        if frames == 0 || late_frames * 100 <= frames * OVERRUN_PERCENT {
+            return OverrunVerdict::Keep;
+        }
+        self.reported = true;
+        return if auto_degrade { OverrunVerdict::Degrade } else { OverrunVerdict::Warn };
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", seconds / 3_600, (seconds / 60) % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_play_time() {
+        let mut stats = SessionStats::new();
+        stats.add_play_time(Duration::from_millis(1_500));
+        stats.add_play_time(Duration::from_millis(1_500));
+        assert_eq!(stats.play_time(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_record_resync() {
+        let mut perf = PerfStats::default();
+        perf.record_resync(Duration::from_secs(3_600));
+        perf.record_resync(Duration::from_millis(300));
+        assert_eq!(perf.resyncs, 2);
+        assert_eq!(perf.dropped_time, Duration::from_millis(3_600_300));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::ZERO), "00:00:00");
+        assert_eq!(format_duration(Duration::from_secs(3_725)), "01:02:05");
+    }
+
+    fn run(policy: &mut OverrunPolicy, perf: &mut PerfStats, start: Instant, seconds: u64, late_every: u64, auto_degrade: bool) -> Vec<OverrunVerdict> {
+        let mut verdicts = vec![];
+        for tick in 0..seconds * 10 {
+            for frame in 0..6 {
+                perf.record_frame(late_every > 0 && (tick * 6 + frame) % late_every == 0);
+            }
+            verdicts.push(policy.sample(start + Duration::from_millis(100 * (tick + 1)), perf, auto_degrade));
+        }
+        return verdicts;
+    }
+
+    #[test]
+    fn test_occasional_late_frames_are_tolerated() {
+        let (mut policy, mut perf) = (OverrunPolicy::new(), PerfStats::default());
+        let verdicts = run(&mut policy, &mut perf, Instant::now(), 10, 20, false);
+        assert!(verdicts.iter().all(|v| *v == OverrunVerdict::Keep));
+    }
+
+    #[test]
+    fn test_sustained_overrun_warns_once() {
+        let (mut policy, mut perf) = (OverrunPolicy::new(), PerfStats::default());
+        let start = Instant::now();
+        /* A healthy stretch first, so the window has to slide before it trips */
+        run(&mut policy, &mut perf, start, 5, 0, false);
+        let verdicts = run(&mut policy, &mut perf, start + Duration::from_secs(5), 10, 4, false);
+        let warnings: Vec<usize> = verdicts.iter().enumerate().filter(|(_, v)| **v != OverrunVerdict::Keep).map(|(i, _)| i).collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0] > 0 && warnings[0] < 20);
+        assert_eq!(verdicts[warnings[0]], OverrunVerdict::Warn);
+    }
+
+    #[test]
+    fn test_auto_degrade_decision() {
+        let (mut policy, mut perf) = (OverrunPolicy::new(), PerfStats::default());
+        let verdicts = run(&mut policy, &mut perf, Instant::now(), 3, 2, true);
+        assert_eq!(verdicts.iter().filter(|v| **v == OverrunVerdict::Degrade).count(), 1);
+        assert!(!verdicts.contains(&OverrunVerdict::Warn));
+    }
+}
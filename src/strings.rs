@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+const ENGLISH: &str = include_str!("lang/en.toml");
+const SPANISH: &str = include_str!("lang/es.toml");
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    /* Always shown in its own language so it can be found from any other */
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Language::English => ENGLISH,
+            Language::Spanish => SPANISH,
+        }
+    }
+}
+
+/* A user-visible message built off the UI thread and translated when shown */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub key: &'static str,
+    pub args: Vec<String>,
+}
+
+impl Message {
+    pub fn new(key: &'static str, args: Vec<String>) -> Self {
+        Self { key, args }
+    }
+}
+
+pub struct Strings {
+    table: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Strings {
+    pub fn new(language: Language) -> Self {
+        Self::from_sources(language.source(), ENGLISH)
+    }
+
+    fn from_sources(source: &str, fallback: &str) -> Self {
+        Self {
+            table: parse(source),
+            fallback: parse(fallback),
+        }
+    }
+
+    /* Looks up a dotted key, falling back to English and then to the key itself */
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        self.table.get(key).or_else(|| self.fallback.get(key)).map(|text| text.as_str()).unwrap_or(key)
+    }
+
+    /* Like tr, substituting {0}, {1}, ... with the given arguments */
+    pub fn tr_with(&self, key: &str, args: &[&str]) -> String {
+        let mut text = self.tr(key).to_string();
+        for (index, arg) in args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", index), arg);
+        }
+        return text;
+    }
+
+    pub fn message(&self, message: &Message) -> String {
+        let args: Vec<&str> = message.args.iter().map(|arg| arg.as_str()).collect();
+        self.tr_with(message.key, &args)
+    }
+}
+
+/* Flattens nested TOML tables into dotted keys, e.g. [menu] file = "File" becomes menu.file */
+fn parse(source: &str) -> HashMap<String, String> {
+    let table = toml::from_str::<toml::Table>(source).expect("invalid language table");
+    let mut strings = HashMap::new();
+    flatten("", &table, &mut strings);
+    return strings;
+}
+
+fn flatten(prefix: &str, table: &toml::Table, strings: &mut HashMap<String, String>) {
+    for (key, value) in table.iter() {
+        let path = if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::String(text) => {
+                strings.insert(path, text.to_string());
+            },
+            toml::Value::Table(inner) => flatten(&path, inner, strings),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{Action, ActionRegistry};
+    use crate::sound_latch::SOUND_COUNT;
+
+    const SOURCES: [&str; 3] = [
+        include_str!("application.rs"),
+        include_str!("save_slots.rs"),
+        include_str!("file_access.rs"),
+    ];
+
+    /* Keys passed as literals to tr, tr_with and Message::new */
+    fn referenced_keys() -> Vec<String> {
+        let mut keys = vec![];
+        for source in SOURCES {
+            for pattern in ["tr(\"", "tr_with(\"", "Message::new(\""] {
+                for (start, _) in source.match_indices(pattern) {
+                    let rest = &source[start + pattern.len()..];
+                    keys.push(rest[..rest.find('"').unwrap()].to_string());
+                }
+            }
+        }
+        return keys;
+    }
+
+    #[test]
+    fn test_referenced_keys_exist_in_english() {
+        let english = parse(ENGLISH);
+        let keys = referenced_keys();
+        assert!(keys.len() > 20);
+        for key in keys {
+            assert!(english.contains_key(&key), "missing English string {}", key);
+        }
+        for info in ActionRegistry::new().all() {
+            let key = match info.action {
+                Action::ToggleSetting(_) => info.label.to_string(),
+                _ => format!("action.{}", info.id),
+            };
+            assert!(english.contains_key(&key), "missing English label for {}", info.id);
+        }
+        for sound in 0..SOUND_COUNT {
+            assert!(english.contains_key(&format!("sound.{}", sound)));
+        }
+    }
+
+    #[test]
+    fn test_languages_parse() {
+        for language in Language::ALL {
+            assert!(parse(language.source()).len() > 50, "{} table is empty", language.native_name());
+        }
+    }
+
+    #[test]
+    fn test_fallback_to_english() {
+        let strings = Strings::from_sources("[menu]\nfile = \"Archivo\"\n", ENGLISH);
+        assert_eq!(strings.tr("menu.file"), "Archivo");
+        assert_eq!(strings.tr("menu.options"), "Options");
+        assert_eq!(strings.tr("no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn test_arguments() {
+        let strings = Strings::new(Language::English);
+        assert_eq!(strings.tr_with("rom.read_error", &["invaders.h", "not found"]), "Could not read invaders.h: not found");
+        assert_eq!(strings.message(&Message::new("slots.saved", vec!["3".to_string()])), "Saved slot 3");
+        let spanish = Strings::new(Language::Spanish);
+        assert_eq!(spanish.message(&Message::new("slots.saved", vec!["3".to_string()])), "Guardado en ranura 3");
+    }
+}
@@ -0,0 +1,169 @@
+// Routine labels for the disassembly, from .sym files: one "ADDR NAME" per line, hex address, ';' comments.
+// "DATA START END" lines mark an inclusive address range as tables rather than code.
+use std::ops::RangeInclusive;
+use intel8080::disassembler;
+use crate::machine;
+use crate::romset;
+
+const ORIGINAL: &str = include_str!("symbols/invaders.sym");
+
+/* Part CRCs (romset::part_crcs) of the sets the embedded labels were written against: Midway's, as MAME
+   lists it. Hacks and unknown images get no labels rather than misleading ones */
+pub const ORIGINAL_PARTS: &[[u32; 4]] = &[romset::PART_CRCS];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub address: u16,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolTable {
+    /* Sorted by address */
+    symbols: Vec<Symbol>,
+    data: Vec<RangeInclusive<u16>>,
+}
+
+impl SymbolTable {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = vec![];
+        let mut data = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let parse_address = |address: &str| {
+                u16::from_str_radix(address.trim_start_matches('$'), 16).map_err(|_| format!("line {}: bad address {}", index + 1, address))
+            };
+            if line.starts_with("DATA ") {
+                let range = match (fields.nth(1), fields.next(), fields.next()) {
+                    (Some(start), Some(end), None) => parse_address(start)?..=parse_address(end)?,
+                    _ => return Err(format!("line {}: expected DATA, a start and an end address", index + 1)),
+                };
+                if range.is_empty() {
+                    return Err(format!("line {}: data range ends before it starts", index + 1));
+                }
+                data.push(range);
+                continue;
+            }
+            let (address, name) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(address), Some(name), None) => (address, name),
+                _ => return Err(format!("line {}: expected an address and a name", index + 1)),
+            };
+            let address = parse_address(address)?;
+            if symbols.iter().any(|symbol: &Symbol| symbol.name == name) {
+                return Err(format!("line {}: {} defined twice", index + 1, name));
+            }
+            symbols.push(Symbol { address, name: name.to_string() });
+        }
+        symbols.sort_by_key(|symbol| symbol.address);
+        data.sort_by_key(|range| *range.start());
+        return Ok(Self { symbols, data });
+    }
+
+    pub fn by_address(&self) -> &[Symbol] {
+        return &self.symbols;
+    }
+
+    pub fn by_name(&self) -> Vec<&Symbol> {
+        let mut symbols: Vec<&Symbol> = self.symbols.iter().collect();
+        symbols.sort_by_key(|symbol| symbol.name.to_lowercase());
+        return symbols;
+    }
+
+    pub fn name_at(&self, address: u16) -> Option<&str> {
+        return self.symbols.iter().find(|symbol| symbol.address == address).map(|symbol| symbol.name.as_str());
+    }
+
+    pub fn is_data(&self, address: u16) -> bool {
+        return self.data.iter().any(|range| range.contains(&address));
+    }
+}
+
+pub fn for_rom_in(sets: &[[u32; 4]], rom: &[u8; machine::ROM_SIZE]) -> Option<SymbolTable> {
+    if !sets.contains(&romset::part_crcs(rom)) {
+        return None;
+    }
+    return Some(SymbolTable::parse(ORIGINAL).expect("embedded symbols parse"));
+}
+
+/* The embedded labels, if this is an image they were written for */
+pub fn for_rom(rom: &[u8; machine::ROM_SIZE]) -> Option<SymbolTable> {
+    return for_rom_in(ORIGINAL_PARTS, rom);
+}
+
+/* Up to lines of disassembly from address, labelled where the table knows the address */
+pub fn listing(rom: &[u8], table: &SymbolTable, address: u16, lines: usize) -> Vec<String> {
+    let mut listing = vec![];
+    let mut addr = address as usize;
+    while listing.len() < lines && addr < rom.len() {
+        if let Some(name) = table.name_at(addr as u16) {
+            listing.push(format!("{}:", name));
+        }
+        let bytes = [0, 1, 2].map(|i| rom.get(addr + i).copied().unwrap_or(0));
+        let (instruction, length) = disassembler::disassemble(bytes);
+        listing.push(format!("  ${:04X}  {}", addr, instruction));
+        addr += length.max(1);
+    }
+    return listing;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_symbols_parse() {
+        let table = SymbolTable::parse(ORIGINAL).unwrap();
+        assert!(table.by_address().len() >= 10);
+        assert_eq!(table.name_at(0x0010), Some("ScanLine224"));
+        assert!(table.by_address().windows(2).all(|pair| pair[0].address < pair[1].address));
+    }
+
+    #[test]
+    fn test_parse_orders_and_rejects() {
+        let table = SymbolTable::parse("; comment\n1A32 BlockCopy\n\n$0008 ScanLine96 ; RST 1\n08F3 printMessage\n").unwrap();
+        let addresses: Vec<u16> = table.by_address().iter().map(|symbol| symbol.address).collect();
+        assert_eq!(addresses, vec![0x0008, 0x08F3, 0x1A32]);
+        let names: Vec<&str> = table.by_name().iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, vec!["BlockCopy", "printMessage", "ScanLine96"]);
+        assert_eq!(SymbolTable::parse("0008\n"), Err("line 1: expected an address and a name".to_string()));
+        assert_eq!(SymbolTable::parse("0008 A\nXYZW B\n"), Err("line 2: bad address XYZW".to_string()));
+        assert_eq!(SymbolTable::parse("0008 A\n0010 A\n"), Err("line 2: A defined twice".to_string()));
+    }
+
+    #[test]
+    fn test_parse_data_ranges() {
+        let table = SymbolTable::parse("1C00 Tables\nDATA $1E00 1FFF ; font\nDATA 1C00 1C3F\n").unwrap();
+        assert_eq!(table.data, [0x1C00..=0x1C3F, 0x1E00..=0x1FFF]);
+        assert_eq!(table.by_address().len(), 1);
+        assert!(table.is_data(0x1C3F) && table.is_data(0x1E00) && !table.is_data(0x1C40));
+        assert_eq!(SymbolTable::parse("DATA 1C00\n"), Err("line 1: expected DATA, a start and an end address".to_string()));
+        assert_eq!(SymbolTable::parse("DATA 1C00 1BFF\n"), Err("line 1: data range ends before it starts".to_string()));
+        assert_eq!(SymbolTable::parse("DATA 1C00 ZZ\n"), Err("line 1: bad address ZZ".to_string()));
+    }
+
+    #[test]
+    fn test_gated_by_part_crcs() {
+        let rom = crate::test_rom::rom();
+        let mut patched = rom;
+        patched[0x1A32] ^= 0xFF;
+        assert!(for_rom_in(&[[0; 4], romset::part_crcs(&rom)], &rom).is_some());
+        /* One changed byte in any part, as a hack would, and the labels stay off */
+        assert!(for_rom_in(&[romset::part_crcs(&rom)], &patched).is_none());
+        /* The built-in test ROM is not the original program */
+        assert!(for_rom(&rom).is_none());
+        assert_eq!(ORIGINAL_PARTS, &[romset::KNOWN_SETS[0].parts]);
+    }
+
+    #[test]
+    fn test_listing_labels_entry() {
+        let table = SymbolTable::parse("0000 Start\n0003 Loop\n").unwrap();
+        // MVI A,$01; NOP; JMP $0003
+        let rom = [0x3E, 0x01, 0x00, 0xC3, 0x03, 0x00];
+        assert_eq!(listing(&rom, &table, 0, 5), vec!["Start:", "  $0000  MVI A,$01", "  $0002  NOP", "Loop:", "  $0003  JMP $0003"]);
+        assert_eq!(listing(&rom, &table, 3, 8), vec!["Loop:", "  $0003  JMP $0003"]);
+    }
+}
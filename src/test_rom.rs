@@ -0,0 +1,194 @@
+use crate::machine;
+#[cfg(test)]
+use crate::video::Rgba;
+
+/* Original program for running without the arcade ROMs. Every vblank it moves a dot diagonally
+   through VRAM, triggers the next of the ten sounds every 32 frames, mirrors IN 1 and IN 2 as
+   bars at the top of the screen, exercises the shift register and shows both interrupt counters. */
+const PROGRAM: [u8; 226] = [
+    0xC3, 0x40, 0x00,                        // $0000  JMP $0040
+    0x00, 0x00, 0x00, 0x00, 0x00,
+    0xC3, 0x20, 0x00,                        // $0008  JMP $0020
+    0x00, 0x00, 0x00, 0x00, 0x00,
+    0xC3, 0x30, 0x00,                        // $0010  JMP $0030
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // mid_screen:
+    0xF5,                                    // $0020  PUSH PSW
+    0xE5,                                    // $0021  PUSH H
+    0x21, 0x01, 0x20,                        // $0022  LXI H,$2001
+    0x34,                                    // $0025  INR M
+    0xE1,                                    // $0026  POP H
+    0xF1,                                    // $0027  POP PSW
+    0xFB,                                    // $0028  EI
+    0xC9,                                    // $0029  RET
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // vblank:
+    0xF5,                                    // $0030  PUSH PSW
+    0xE5,                                    // $0031  PUSH H
+    0x21, 0x02, 0x20,                        // $0032  LXI H,$2002
+    0x34,                                    // $0035  INR M
+    0xE1,                                    // $0036  POP H
+    0xF1,                                    // $0037  POP PSW
+    0xFB,                                    // $0038  EI
+    0xC9,                                    // $0039  RET
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // init:
+    0x31, 0x00, 0x24,                        // $0040  LXI SP,$2400
+    0x21, 0x00, 0x24,                        // $0043  LXI H,$2400
+    0x22, 0x04, 0x20,                        // $0046  SHLD $2004
+    0xFB,                                    // $0049  EI
+    // frame:
+    0x3A, 0x02, 0x20,                        // $004A  LDA $2002
+    0x47,                                    // $004D  MOV B,A
+    // wait:
+    0x3A, 0x02, 0x20,                        // $004E  LDA $2002
+    0xB8,                                    // $0051  CMP B
+    0xCA, 0x4E, 0x00,                        // $0052  JZ $004E
+    0xD3, 0x06,                              // $0055  OUT 6
+    0x2A, 0x04, 0x20,                        // $0057  LHLD $2004
+    0x36, 0x00,                              // $005A  MVI M,$00
+    0x11, 0x21, 0x00,                        // $005C  LXI D,$0021
+    0x19,                                    // $005F  DAD D
+    0x7C,                                    // $0060  MOV A,H
+    0xFE, 0x3E,                              // $0061  CPI $3E
+    0xDA, 0x69, 0x00,                        // $0063  JC $0069
+    0x21, 0x00, 0x24,                        // $0066  LXI H,$2400
+    // draw:
+    0x36, 0xFF,                              // $0069  MVI M,$FF
+    0x22, 0x04, 0x20,                        // $006B  SHLD $2004
+    0x3A, 0x02, 0x20,                        // $006E  LDA $2002
+    0xE6, 0x1F,                              // $0071  ANI $1F
+    0xC2, 0x92, 0x00,                        // $0073  JNZ $0092
+    0x3A, 0x03, 0x20,                        // $0076  LDA $2003
+    0x3C,                                    // $0079  INR A
+    0xFE, 0x0A,                              // $007A  CPI $0A
+    0xDA, 0x80, 0x00,                        // $007C  JC $0080
+    0xAF,                                    // $007F  XRA A
+    // play:
+    0x32, 0x03, 0x20,                        // $0080  STA $2003
+    0x87,                                    // $0083  ADD A
+    0x5F,                                    // $0084  MOV E,A
+    0x16, 0x00,                              // $0085  MVI D,$00
+    0x21, 0xCE, 0x00,                        // $0087  LXI H,$00CE
+    0x19,                                    // $008A  DAD D
+    0x7E,                                    // $008B  MOV A,M
+    0xD3, 0x03,                              // $008C  OUT 3
+    0x23,                                    // $008E  INX H
+    0x7E,                                    // $008F  MOV A,M
+    0xD3, 0x05,                              // $0090  OUT 5
+    // inputs:
+    0xDB, 0x01,                              // $0092  IN 1
+    0x21, 0x10, 0x24,                        // $0094  LXI H,$2410
+    0xCD, 0xC2, 0x00,                        // $0097  CALL $00C2
+    0xDB, 0x02,                              // $009A  IN 2
+    0x21, 0x18, 0x24,                        // $009C  LXI H,$2418
+    0xCD, 0xC2, 0x00,                        // $009F  CALL $00C2
+    0x3A, 0x02, 0x20,                        // $00A2  LDA $2002
+    0xD3, 0x04,                              // $00A5  OUT 4
+    0x2F,                                    // $00A7  CMA
+    0xD3, 0x04,                              // $00A8  OUT 4
+    0x3E, 0x03,                              // $00AA  MVI A,$03
+    0xD3, 0x02,                              // $00AC  OUT 2
+    0xDB, 0x03,                              // $00AE  IN 3
+    0x32, 0x08, 0x20,                        // $00B0  STA $2008
+    0x3A, 0x01, 0x20,                        // $00B3  LDA $2001
+    0x32, 0x1E, 0x3E,                        // $00B6  STA $3E1E
+    0x3A, 0x02, 0x20,                        // $00B9  LDA $2002
+    0x32, 0x1F, 0x3E,                        // $00BC  STA $3E1F
+    0xC3, 0x4A, 0x00,                        // $00BF  JMP $004A
+    // bar:
+    0x11, 0x20, 0x00,                        // $00C2  LXI D,$0020
+    0x0E, 0x08,                              // $00C5  MVI C,$08
+    // bar_loop:
+    0x77,                                    // $00C7  MOV M,A
+    0x19,                                    // $00C8  DAD D
+    0x0D,                                    // $00C9  DCR C
+    0xC2, 0xC7, 0x00,                        // $00CA  JNZ $00C7
+    0xC9,                                    // $00CD  RET
+    // sounds:
+    0x01, 0x00, 0x02, 0x00, 0x04, 0x00,      // $00CE  port 3, port 5 pairs
+    0x08, 0x00, 0x00, 0x01, 0x00, 0x02,
+    0x00, 0x04, 0x00, 0x08, 0x00, 0x10,
+    0x10, 0x00,
+];
+
+pub fn rom() -> [u8; machine::ROM_SIZE] {
+    let mut rom = [0; machine::ROM_SIZE];
+    rom[..PROGRAM.len()].copy_from_slice(&PROGRAM);
+    return rom;
+}
+
+/* FNV-1a over every channel of every pixel, so a golden frame fits in one u64 */
+#[cfg(test)]
+pub fn frame_hash(pixels: &[Rgba]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in pixels.iter().flatten() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    return hash;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator_core::EmulatorCore;
+    use crate::machine::InputState;
+    use crate::sound_latch::SOUND_COUNT;
+    use crate::starfield::Starfield;
+    use crate::video;
+
+    /* Where the handlers count interrupts, as offsets into work RAM */
+    const MID_SCREEN_COUNT: usize = 0x01;
+    const VBLANK_COUNT: usize = 0x02;
+
+    #[test]
+    fn test_interrupts_counted() {
+        let mut core = EmulatorCore::new(rom(), InputState::default());
+        for _ in 0..10 {
+            core.step_frame();
+        }
+        assert!(core.ram()[MID_SCREEN_COUNT] >= 9);
+        assert!(core.ram()[VBLANK_COUNT] >= 9);
+    }
+
+    #[test]
+    fn test_every_sound_triggered() {
+        let mut core = EmulatorCore::new(rom(), InputState::default());
+        let mut triggered = [false; SOUND_COUNT];
+        for _ in 0..352 {
+            for event in core.step_frame().sound_events.iter().filter(|event| event.active) {
+                triggered[event.sound] = true;
+            }
+        }
+        assert_eq!(triggered, [true; SOUND_COUNT]);
+    }
+
+    #[test]
+    fn test_inputs_reflected() {
+        let mut core = EmulatorCore::new(rom(), InputState { p1_fire: true, ..Default::default() });
+        for _ in 0..3 {
+            core.step_frame();
+        }
+        assert_eq!(core.vram()[0x10] & 0b00010000, 0b00010000);
+    }
+
+    #[test]
+    fn test_golden_hash() {
+        let mut core = EmulatorCore::new(rom(), InputState::default());
+        core.set_strict(true);
+        for frame in 0..600 {
+            core.set_input(InputState { p1_left: frame % 50 < 25, ..Default::default() });
+            core.step_frame();
+        }
+        assert_eq!(core.ram_checksum(), 0x3449_B3CB_E043_AFFE);
+        assert!(core.anomalies().unwrap().is_empty(), "{:?}", core.anomalies().unwrap().anomalies());
+
+        /* The starfield mod only ever touches the presented pixels */
+        let mut pixels = video::Palette::new(&video::VideoAdjust::default(), false).convert(&core.vram());
+        let mut background = vec![video::BLACK; pixels.len()];
+        Starfield::new(core.seed(), video::WIDTH, video::HEIGHT).render(core.frame(), &mut background);
+        video::composite(&mut pixels, &background);
+        assert_eq!(frame_hash(&pixels), 0xEFD7_6A18_E489_2FAD);
+    }
+}
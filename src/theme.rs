@@ -0,0 +1,32 @@
+use eframe::egui::*;
+
+/* Visuals for the UI chrome only; the game display keeps its own palette */
+pub fn visuals(high_contrast: bool) -> Visuals {
+    let mut visuals = Visuals::dark();
+    if !high_contrast {
+        return visuals;
+    }
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.panel_fill = Color32::BLACK;
+    visuals.window_fill = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.faint_bg_color = Color32::from_gray(24);
+    visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.hyperlink_color = Color32::YELLOW;
+    visuals.selection.bg_fill = Color32::from_rgb(0, 60, 200);
+    visuals.selection.stroke = Stroke::new(2.0, Color32::WHITE);
+    for widget in [&mut visuals.widgets.noninteractive, &mut visuals.widgets.inactive] {
+        widget.bg_fill = Color32::BLACK;
+        widget.weak_bg_fill = Color32::BLACK;
+        widget.bg_stroke = Stroke::new(1.0, Color32::WHITE);
+        widget.fg_stroke = Stroke::new(1.5, Color32::WHITE);
+    }
+    /* Hovered and focused widgets get a thick yellow outline */
+    for widget in [&mut visuals.widgets.hovered, &mut visuals.widgets.active, &mut visuals.widgets.open] {
+        widget.bg_fill = Color32::from_gray(40);
+        widget.weak_bg_fill = Color32::from_gray(40);
+        widget.bg_stroke = Stroke::new(2.5, Color32::YELLOW);
+        widget.fg_stroke = Stroke::new(2.0, Color32::YELLOW);
+    }
+    return visuals;
+}
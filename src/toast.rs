@@ -0,0 +1,74 @@
+use eframe::egui::*;
+use std::time::{Duration, Instant};
+use crate::actions::Action;
+
+const TOAST_DURATION: Duration = Duration::from_millis(1_500);
+/* Long enough to reach for the mouse */
+const LINK_TOAST_DURATION: Duration = Duration::from_secs(8);
+
+struct Toast {
+    text: String,
+    created: Instant,
+    link: Option<(String, Action)>,
+}
+
+impl Toast {
+    fn duration(&self) -> Duration {
+        return if self.link.is_some() { LINK_TOAST_DURATION } else { TOAST_DURATION };
+    }
+}
+
+pub struct Toasts {
+    messages: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Self {
+            messages: vec![],
+        }
+    }
+
+    pub fn push(&mut self, message: impl Into<String>) {
+        self.messages.push(Toast { text: message.into(), created: Instant::now(), link: None });
+    }
+
+    pub fn push_link(&mut self, message: impl Into<String>, link: impl Into<String>, action: Action) {
+        self.messages.push(Toast { text: message.into(), created: Instant::now(), link: Some((link.into(), action)) });
+    }
+
+    /* Returns the action of a clicked link, if any */
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<Action> {
+        self.messages.retain(|toast| toast.created.elapsed() < toast.duration());
+        if self.messages.is_empty() {
+            return None;
+        }
+        let mut clicked = None;
+        egui::Area::new("toasts")
+            .anchor(Align2::RIGHT_TOP, Vec2::new(-10.0, 30.0))
+            .interactable(self.messages.iter().any(|toast| toast.link.is_some()))
+            .show(ctx, |ui| {
+                for toast in &self.messages {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        let response = ui.label(RichText::new(toast.text.as_str()).strong());
+                        /* A polite live region, so screen readers announce toasts without moving focus */
+                        ui.ctx().accesskit_node_builder(response.id, |node| node.set_live(accesskit::Live::Polite));
+                        if let Some((label, action)) = &toast.link {
+                            if ui.link(label.as_str()).clicked() {
+                                clicked = Some(*action);
+                            }
+                        }
+                    });
+                }
+            });
+        if clicked.is_some() {
+            self.messages.retain(|toast| toast.link.is_none());
+        }
+        return clicked;
+    }
+
+    /* Showing toasts need periodic repaints to expire */
+    pub fn is_empty(&self) -> bool {
+        return self.messages.is_empty();
+    }
+}
@@ -0,0 +1,367 @@
+/* Unscaled frame decoding, before the cabinet's 90 degree rotation is applied by the UI */
+use serde::{Deserialize, Serialize};
+use crate::machine;
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 224;
+
+pub type Rgba = [u8; 4];
+
+pub const BLACK: Rgba = [0, 0, 0, 255];
+const WHITE: Rgba = [255, 255, 255, 255];
+pub const GREEN: Rgba = [0, 255, 0, 255];
+pub const RED: Rgba = [255, 0, 0, 255];
+const VIGNETTE_BANDS: usize = 8;
+/* Distance from the centre, as a share of the corner distance, where the darkening starts */
+const VIGNETTE_START: f32 = 0.6;
+const VIGNETTE_DEPTH: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoAdjust {
+    /* -1.0..=1.0, added after contrast */
+    pub brightness: f32,
+    /* 0.0..=2.0 around mid grey */
+    pub contrast: f32,
+    /* Some bootleg boards drove the monitor with the video bit inverted */
+    pub invert: bool,
+    pub vignette: bool,
+    /* Averages each frame with the one before, so sprites the game draws on alternate frames read as a steady half-bright shape */
+    pub blend: bool,
+}
+
+impl Default for VideoAdjust {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            invert: false,
+            vignette: false,
+            blend: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VideoOptions {
+    pub starfield: bool,
+    /* Skips the overlay lookups on slow hosts */
+    pub monochrome: bool,
+    /* Present every other emulated frame */
+    pub half_rate: bool,
+    pub adjust: VideoAdjust,
+}
+
+/* A coloured strip of the cabinet overlay in native coordinates: x along the scanline (0..WIDTH, the
+   upright screen's bottom at 0), y across scanlines (0..HEIGHT) */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OverlayRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub color: Rgba,
+}
+
+/* Overlay strips in order; where they overlap the later one wins, and uncovered pixels stay white */
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverlayMap {
+    pub rects: Vec<OverlayRect>,
+}
+
+/* An OverlayMap as one region index per native pixel: 0 uncovered, i + 1 for rects[i] */
+pub struct CompiledOverlay {
+    pub regions: Vec<u8>,
+    /* Indexed by region */
+    pub colors: Vec<Rgba>,
+}
+
+impl OverlayMap {
+    pub fn compile(&self) -> CompiledOverlay {
+        assert!(self.rects.len() < u8::MAX as usize, "too many overlay regions");
+        let mut regions = vec![0; WIDTH * HEIGHT];
+        for (index, rect) in self.rects.iter().enumerate() {
+            for y in rect.y..(rect.y + rect.height).min(HEIGHT) {
+                for x in rect.x..(rect.x + rect.width).min(WIDTH) {
+                    regions[y * WIDTH + x] = index as u8 + 1;
+                }
+            }
+        }
+        let colors = std::iter::once(WHITE).chain(self.rects.iter().map(|rect| rect.color)).collect();
+        return CompiledOverlay { regions, colors };
+    }
+}
+
+/* The 1bpp source only ever produces a few colours per overlay region and vignette band, so every
+   adjustment is applied once here and conversion is table lookups only */
+pub struct Palette {
+    colors: Vec<[[Rgba; 2]; VIGNETTE_BANDS]>,
+    regions: Vec<u8>,
+    bands: Vec<u8>,
+}
+
+impl Palette {
+    pub fn new(adjust: &VideoAdjust, monochrome: bool) -> Self {
+        return Self::with_overlay(adjust, monochrome, &machine::overlay());
+    }
+
+    /* Monochrome recolours every region white; the geometry always comes from the map */
+    pub fn with_overlay(adjust: &VideoAdjust, monochrome: bool, overlay: &OverlayMap) -> Self {
+        let CompiledOverlay { regions, colors: region_colors } = overlay.compile();
+        let mut colors = vec![[[BLACK; 2]; VIGNETTE_BANDS]; region_colors.len()];
+        for (region, lit) in region_colors.iter().enumerate() {
+            let lit = if monochrome { WHITE } else { *lit };
+            for (band, pair) in colors[region].iter_mut().enumerate() {
+                let shade = 1.0 - VIGNETTE_DEPTH * band as f32 / (VIGNETTE_BANDS - 1) as f32;
+                let (off, on) = if adjust.invert { (lit, BLACK) } else { (BLACK, lit) };
+                *pair = [transform(off, adjust, shade), transform(on, adjust, shade)];
+            }
+        }
+        let bands = if adjust.vignette { vignette_bands() } else { vec![0; WIDTH * HEIGHT] };
+        Self { colors, regions, bands }
+    }
+
+    pub fn color(&self, region: usize, band: usize, lit: bool) -> Rgba {
+        return self.colors[region][band][lit as usize];
+    }
+
+    /* One pixel per VRAM bit */
+    pub fn convert(&self, vram: &[u8]) -> Vec<Rgba> {
+        let mut pixels = Vec::with_capacity(WIDTH * HEIGHT);
+        for byte in vram.iter().take(WIDTH * HEIGHT / 8) {
+            for offset in 0..8 {
+                let index = pixels.len();
+                let region = self.regions[index] as usize;
+                let band = self.bands[index] as usize;
+                pixels.push(self.color(region, band, byte >> offset & 0x1 == 1));
+            }
+        }
+        return pixels;
+    }
+}
+
+fn transform(color: Rgba, adjust: &VideoAdjust, shade: f32) -> Rgba {
+    let channel = |value: u8| {
+        let level = (value as f32 / 255.0 - 0.5) * adjust.contrast + 0.5 + adjust.brightness;
+        (level * shade).clamp(0.0, 1.0) * 255.0
+    };
+    [channel(color[0]).round() as u8, channel(color[1]).round() as u8, channel(color[2]).round() as u8, color[3]]
+}
+
+/* Symmetric in both axes, so it looks the same after the UI rotates the frame */
+fn vignette_bands() -> Vec<u8> {
+    let mut bands = Vec::with_capacity(WIDTH * HEIGHT);
+    for row in 0..HEIGHT {
+        for column in 0..WIDTH {
+            let dx = (column as f32 + 0.5) / (WIDTH as f32 / 2.0) - 1.0;
+            let dy = (row as f32 + 0.5) / (HEIGHT as f32 / 2.0) - 1.0;
+            let distance = ((dx * dx + dy * dy) / 2.0).sqrt();
+            let depth = ((distance - VIGNETTE_START) / (1.0 - VIGNETTE_START)).clamp(0.0, 1.0);
+            bands.push((depth * (VIGNETTE_BANDS - 1) as f32).round() as u8);
+        }
+    }
+    return bands;
+}
+
+/* Keeps the last native frame (after palette, overlay and starfield) so the next one can be averaged with it */
+pub struct FrameBlender {
+    previous: Vec<Rgba>,
+}
+
+impl FrameBlender {
+    pub fn new() -> Self {
+        Self { previous: vec![] }
+    }
+
+    /* Forget the last frame, e.g. when the next one doesn't follow it */
+    pub fn reset(&mut self) {
+        self.previous.clear();
+    }
+
+    /* Blends pixels 50/50 with the previous frame when enabled; disabled leaves them untouched and drops the retained frame */
+    pub fn apply(&mut self, pixels: &mut [Rgba], enabled: bool) {
+        if !enabled {
+            self.reset();
+            return;
+        }
+        if self.previous.len() != pixels.len() {
+            self.previous = pixels.to_vec();
+            return;
+        }
+        for (pixel, previous) in pixels.iter_mut().zip(self.previous.iter_mut()) {
+            let current = *pixel;
+            for channel in 0..4 {
+                pixel[channel] = ((current[channel] as u16 + previous[channel] as u16 + 1) / 2) as u8;
+            }
+            *previous = current;
+        }
+    }
+}
+
+/* Background pixels only show through where the game left the screen black */
+pub fn composite(game: &mut [Rgba], background: &[Rgba]) {
+    for (pixel, under) in game.iter_mut().zip(background) {
+        if pixel[..3] == [0, 0, 0] {
+            *pixel = *under;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_vram() -> [u8; 7_168] {
+        let mut vram = [0u8; 7_168];
+        vram[0] = 0x01;
+        vram[12] = 0x01;
+        vram[26] = 0x02;
+        vram
+    }
+
+    #[test]
+    fn test_default_palette_applies_overlay() {
+        let pixels = Palette::new(&VideoAdjust::default(), false).convert(&test_vram());
+        assert_eq!(pixels.len(), WIDTH * HEIGHT);
+        assert_eq!(pixels[0], GREEN);
+        assert_eq!(pixels[96], WHITE);
+        assert_eq!(pixels[209], RED);
+        assert_eq!(pixels[1], BLACK);
+    }
+
+    #[test]
+    fn test_overlapping_rects_later_wins() {
+        let blue = [0, 0, 255, 255];
+        let map = OverlayMap { rects: vec![
+            OverlayRect { x: 0, y: 0, width: 10, height: 10, color: GREEN },
+            OverlayRect { x: 5, y: 5, width: 10, height: 10, color: blue },
+            /* Clipped to the screen */
+            OverlayRect { x: WIDTH - 2, y: HEIGHT - 1, width: 8, height: 8, color: RED },
+        ] };
+        let compiled = map.compile();
+        assert_eq!(compiled.regions.len(), WIDTH * HEIGHT);
+        assert_eq!(compiled.colors, vec![WHITE, GREEN, blue, RED]);
+        let region = |x: usize, y: usize| compiled.regions[y * WIDTH + x];
+        assert_eq!((region(0, 0), region(4, 9), region(9, 4)), (1, 1, 1));
+        assert_eq!((region(5, 5), region(9, 9), region(14, 14)), (2, 2, 2));
+        assert_eq!((region(15, 15), region(10, 0)), (0, 0));
+        assert_eq!((region(WIDTH - 1, HEIGHT - 1), region(WIDTH - 3, HEIGHT - 1)), (3, 0));
+
+        let lit = Palette::with_overlay(&VideoAdjust::default(), false, &map).convert(&[0xFF; 7_168]);
+        assert_eq!((lit[0], lit[5 * WIDTH + 5], lit[20]), (GREEN, blue, WHITE));
+    }
+
+    #[test]
+    fn test_authentic_overlay_landmarks() {
+        let compiled = machine::overlay().compile();
+        let color = |x: usize, y: usize| compiled.colors[compiled.regions[y * WIDTH + x] as usize];
+        /* The player's base at the bottom, and the shields above it */
+        assert_eq!(color(24, HEIGHT / 2), GREEN);
+        assert_eq!(color(40, 20), GREEN);
+        /* The ships left and credit count along the very bottom */
+        assert_eq!(color(4, 200), GREEN);
+        /* The UFO's lane */
+        assert_eq!(color(210, HEIGHT / 2), RED);
+        /* The invaders' play field and the score band at the top stay white */
+        assert_eq!(color(120, HEIGHT / 2), WHITE);
+        assert_eq!(color(240, 40), WHITE);
+        assert_eq!((color(79, 0), color(80, 0), color(200, 0), color(201, 0), color(220, 0), color(221, 0)), (GREEN, WHITE, WHITE, RED, RED, WHITE));
+    }
+
+    #[test]
+    fn test_monochrome_ignores_overlay() {
+        let pixels = Palette::new(&VideoAdjust::default(), true).convert(&test_vram());
+        assert_eq!(pixels[0], WHITE);
+        assert_eq!(pixels[209], WHITE);
+        assert_eq!(pixels[1], BLACK);
+    }
+
+    #[test]
+    fn test_brightness_and_contrast() {
+        let brighter = Palette::new(&VideoAdjust { brightness: 0.2, ..Default::default() }, false);
+        assert_eq!(brighter.color(0, 0, false), [51, 51, 51, 255]);
+        assert_eq!(brighter.color(1, 0, true), [51, 255, 51, 255]);
+        let flat = Palette::new(&VideoAdjust { contrast: 0.5, ..Default::default() }, false);
+        assert_eq!(flat.color(0, 0, false), [64, 64, 64, 255]);
+        assert_eq!(flat.color(0, 0, true), [191, 191, 191, 255]);
+        let harsh = Palette::new(&VideoAdjust { contrast: 2.0, brightness: -0.25, ..Default::default() }, false);
+        assert_eq!(harsh.color(2, 0, true), [255, 0, 0, 255]);
+        assert_eq!(harsh.color(2, 0, false), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_invert_swaps_lit_and_dark() {
+        let palette = Palette::new(&VideoAdjust { invert: true, ..Default::default() }, false);
+        let pixels = palette.convert(&test_vram());
+        assert_eq!(pixels[0], BLACK);
+        assert_eq!(pixels[1], GREEN);
+        assert_eq!(pixels[96], BLACK);
+        assert_eq!(pixels[97], WHITE);
+    }
+
+    #[test]
+    fn test_vignette_darkens_corners_only() {
+        let palette = Palette::new(&VideoAdjust { vignette: true, ..Default::default() }, false);
+        let pixels = palette.convert(&[0xFF; 7_168]);
+        let centre = (HEIGHT / 2) * WIDTH + WIDTH / 2;
+        assert_eq!(pixels[centre], WHITE);
+        assert_eq!(pixels[centre - 100], GREEN);
+        assert_eq!(pixels[WIDTH - 1], [128, 128, 128, 255]);
+        assert_eq!(palette.color(0, VIGNETTE_BANDS - 1, true), [128, 128, 128, 255]);
+        /* Symmetric, so the rotated screen is darkened evenly */
+        assert_eq!(pixels[WIDTH - 1], pixels[WIDTH * HEIGHT - 1]);
+    }
+
+    #[test]
+    fn test_blend_averages_alternating_frames() {
+        let palette = Palette::new(&VideoAdjust::default(), false);
+        let mut shot = [0u8; 7_168];
+        shot[12] = 0x01;
+        let empty = [0u8; 7_168];
+        let mut blender = FrameBlender::new();
+        let mut frames = vec![];
+        for vram in [&shot, &empty, &shot] {
+            let mut pixels = palette.convert(vram);
+            blender.apply(&mut pixels, true);
+            frames.push(pixels);
+        }
+        /* The first frame has nothing to blend with */
+        assert_eq!(frames[0][96], WHITE);
+        assert_eq!(frames[1][96], [128, 128, 128, 255]);
+        assert_eq!(frames[2][96], [128, 128, 128, 255]);
+        assert_eq!(frames[1][0], BLACK);
+        /* Blends whatever the palette produced, overlay included */
+        let mut green = palette.convert(&test_vram());
+        blender.reset();
+        blender.apply(&mut green, true);
+        let mut dark = palette.convert(&empty);
+        blender.apply(&mut dark, true);
+        assert_eq!(dark[0], [0, 128, 0, 255]);
+    }
+
+    #[test]
+    fn test_blend_disabled_is_identical() {
+        let palette = Palette::new(&VideoAdjust { vignette: true, ..Default::default() }, false);
+        let mut blender = FrameBlender::new();
+        let mut first = palette.convert(&[0xFF; 7_168]);
+        blender.apply(&mut first, true);
+        for vram in [[0x00; 7_168], [0x5A; 7_168]] {
+            let unblended = palette.convert(&vram);
+            let mut pixels = unblended.clone();
+            blender.apply(&mut pixels, false);
+            assert_eq!(pixels, unblended);
+        }
+        /* Re-enabling starts afresh rather than blending with a stale frame */
+        let mut pixels = palette.convert(&[0x00; 7_168]);
+        blender.apply(&mut pixels, true);
+        assert_eq!(pixels, palette.convert(&[0x00; 7_168]));
+    }
+
+    #[test]
+    fn test_composite_keeps_lit_pixels() {
+        let star = [90, 90, 120, 255];
+        let mut game = vec![BLACK, WHITE, BLACK, GREEN];
+        composite(&mut game, &[star; 4]);
+        assert_eq!(game, vec![star, WHITE, star, GREEN]);
+    }
+}
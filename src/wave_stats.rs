@@ -0,0 +1,292 @@
+// Per-wave statistics for player 1: the watcher turns RAM samples into events, the aggregator folds
+// events into one record per wave and hands over the finished game at game over.
+use std::time::Duration;
+use crate::game_state::GameState;
+use crate::ram_map;
+
+const FRAMES_PER_SECOND: u64 = 60;
+pub const CSV_HEADER: &str = "wave,start_frame,end_frame,seconds,shots,hits,accuracy_percent,deaths,lives_remaining,cleared";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WaveEvent {
+    /* The wave the game starts on, from the rack counter, and the ships in reserve */
+    GameStarted { wave: u32, ships: u8 },
+    ShotFired,
+    AliensKilled { count: u8, remaining: u8 },
+    ShipsChanged(u8),
+    /* The rack counter moved on once */
+    WaveCleared,
+    GameOver,
+}
+
+/* The RAM a sample needs */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WaveSample {
+    pub playing: bool,
+    pub rack: u8,
+    pub ships: u8,
+    pub aliens: u8,
+    pub shot_active: bool,
+}
+
+impl WaveSample {
+    pub fn from_ram(ram: &[u8], state: GameState) -> Self {
+        Self {
+            playing: state == GameState::Playing,
+            rack: ram_map::read(ram, ram_map::P1_RACK_COUNT),
+            ships: ram_map::read(ram, ram_map::P1_SHIPS),
+            aliens: ram_map::read(ram, ram_map::NUM_ALIENS),
+            shot_active: ram_map::read(ram, ram_map::PLAYER_SHOT_STATUS) != 0,
+        }
+    }
+}
+
+pub struct WaveWatcher {
+    last: Option<WaveSample>,
+}
+
+impl WaveWatcher {
+    pub fn new() -> Self {
+        Self { last: None }
+    }
+
+    /* Samples may be several frames apart, so kills and cleared racks are counted from the difference.
+       Within one sample a kill comes before a lost ship, and both before the rack moving on, so dying on
+       the last alien is charged to the wave it happened in */
+    pub fn update(&mut self, sample: WaveSample) -> Vec<WaveEvent> {
+        let mut events = vec![];
+        let last = self.last.replace(sample).filter(|last| last.playing);
+        let Some(last) = last else {
+            if sample.playing {
+                events.push(WaveEvent::GameStarted { wave: sample.rack as u32 + 1, ships: sample.ships });
+            }
+            return events;
+        };
+        if !sample.playing {
+            events.push(WaveEvent::GameOver);
+            return events;
+        }
+        if sample.shot_active && !last.shot_active {
+            events.push(WaveEvent::ShotFired);
+        }
+        /* The counter is a byte and wraps after 255 */
+        let racks = sample.rack.wrapping_sub(last.rack);
+        /* A fresh rack refills the count, which is not a kill; whatever was left of the old one was */
+        if racks > 0 && last.aliens > 0 {
+            events.push(WaveEvent::AliensKilled { count: last.aliens, remaining: 0 });
+        } else if racks == 0 && sample.aliens < last.aliens {
+            events.push(WaveEvent::AliensKilled { count: last.aliens - sample.aliens, remaining: sample.aliens });
+        }
+        if sample.ships != last.ships {
+            events.push(WaveEvent::ShipsChanged(sample.ships));
+        }
+        for _ in 0..racks {
+            events.push(WaveEvent::WaveCleared);
+        }
+        return events;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveRecord {
+    /* Counted from the start of the game, so it keeps going past the game's 255 */
+    pub wave: u32,
+    pub start_frame: u64,
+    pub end_frame: u64,
+    pub shots: u32,
+    pub hits: u32,
+    pub deaths: u32,
+    pub lives_remaining: u8,
+    pub cleared: bool,
+    aliens_remaining: Option<u8>,
+}
+
+impl WaveRecord {
+    fn new(wave: u32, frame: u64, ships: u8) -> Self {
+        Self { wave, start_frame: frame, end_frame: frame, shots: 0, hits: 0, deaths: 0, lives_remaining: ships, cleared: false, aliens_remaining: None }
+    }
+
+    pub fn duration(&self) -> Duration {
+        return Duration::from_millis((self.end_frame - self.start_frame) * 1_000 / FRAMES_PER_SECOND);
+    }
+
+    /* Aliens hit per shot fired, as a percentage */
+    pub fn accuracy(&self) -> Option<f32> {
+        if self.shots == 0 {
+            return None;
+        }
+        return Some(self.hits as f32 * 100.0 / self.shots as f32);
+    }
+}
+
+pub struct WaveStats {
+    current: Option<WaveRecord>,
+    waves: Vec<WaveRecord>,
+}
+
+impl WaveStats {
+    pub fn new() -> Self {
+        Self { current: None, waves: vec![] }
+    }
+
+    /* Returns the finished game's waves on GameOver */
+    pub fn apply(&mut self, frame: u64, event: WaveEvent) -> Option<Vec<WaveRecord>> {
+        match event {
+            WaveEvent::GameStarted { wave, ships } => {
+                self.waves.clear();
+                self.current = Some(WaveRecord::new(wave, frame, ships));
+            },
+            WaveEvent::GameOver => {
+                let mut current = self.current.take()?;
+                /* Begun with no ships left: the last ship went down with the last alien and the rack moved
+                   on before the game noticed, so there never was a next wave */
+                let phantom = current.lives_remaining == 0 && current.shots == 0 && current.hits == 0 && current.deaths == 0 && !self.waves.is_empty();
+                if !phantom {
+                    current.end_frame = frame;
+                    current.cleared = current.aliens_remaining == Some(0);
+                    self.waves.push(current);
+                }
+                return Some(std::mem::take(&mut self.waves));
+            },
+            _ => {
+                let current = self.current.as_mut()?;
+                match event {
+                    WaveEvent::ShotFired => current.shots += 1,
+                    WaveEvent::AliensKilled { count, remaining } => {
+                        current.hits += count as u32;
+                        current.aliens_remaining = Some(remaining);
+                    },
+                    WaveEvent::ShipsChanged(ships) => {
+                        if ships < current.lives_remaining {
+                            current.deaths += (current.lives_remaining - ships) as u32;
+                        }
+                        current.lives_remaining = ships;
+                    },
+                    WaveEvent::WaveCleared => {
+                        let next = WaveRecord::new(current.wave + 1, frame, current.lives_remaining);
+                        let mut finished = std::mem::replace(current, next);
+                        finished.end_frame = frame;
+                        finished.cleared = true;
+                        self.waves.push(finished);
+                    },
+                    WaveEvent::GameStarted { .. } | WaveEvent::GameOver => {},
+                }
+            },
+        }
+        return None;
+    }
+}
+
+pub fn to_csv(waves: &[WaveRecord]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for wave in waves {
+        let accuracy = wave.accuracy().map(|accuracy| format!("{:.1}", accuracy)).unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{:.2},{},{},{},{},{},{}\n",
+            wave.wave, wave.start_frame, wave.end_frame, wave.duration().as_secs_f32(), wave.shots, wave.hits, accuracy, wave.deaths, wave.lives_remaining, wave.cleared
+        ));
+    }
+    return csv;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing(rack: u8, ships: u8, aliens: u8, shot_active: bool) -> WaveSample {
+        return WaveSample { playing: true, rack, ships, aliens, shot_active };
+    }
+
+    /* Runs samples one frame apart through a watcher and aggregator and returns the finished game */
+    fn play(samples: &[WaveSample]) -> Option<Vec<WaveRecord>> {
+        let mut watcher = WaveWatcher::new();
+        let mut stats = WaveStats::new();
+        let mut finished = None;
+        for (frame, sample) in samples.iter().enumerate() {
+            for event in watcher.update(*sample) {
+                if let Some(waves) = stats.apply(frame as u64, event) {
+                    finished = Some(waves);
+                }
+            }
+        }
+        return finished;
+    }
+
+    #[test]
+    fn test_events_from_samples() {
+        let mut watcher = WaveWatcher::new();
+        assert_eq!(watcher.update(WaveSample::default()), vec![]);
+        assert_eq!(watcher.update(playing(0, 3, 55, false)), vec![WaveEvent::GameStarted { wave: 1, ships: 3 }]);
+        assert_eq!(watcher.update(playing(0, 3, 55, true)), vec![WaveEvent::ShotFired]);
+        assert_eq!(watcher.update(playing(0, 3, 54, true)), vec![WaveEvent::AliensKilled { count: 1, remaining: 54 }]);
+        assert_eq!(watcher.update(playing(0, 3, 0, false)), vec![WaveEvent::AliensKilled { count: 54, remaining: 0 }]);
+        /* The rack refills: not a kill */
+        assert_eq!(watcher.update(playing(1, 3, 55, false)), vec![WaveEvent::WaveCleared]);
+        assert_eq!(watcher.update(playing(1, 2, 55, false)), vec![WaveEvent::ShipsChanged(2)]);
+        assert_eq!(watcher.update(WaveSample::default()), vec![WaveEvent::GameOver]);
+        assert_eq!(watcher.update(WaveSample::default()), vec![]);
+    }
+
+    #[test]
+    fn test_two_waves_and_game_over() {
+        let mut samples = vec![WaveSample::default(), playing(0, 3, 55, false)];
+        /* Wave 1: four shots, three hits, then the last alien and the next rack */
+        for aliens in [55, 54, 53, 53] {
+            samples.push(playing(0, 3, aliens, true));
+            samples.push(playing(0, 3, aliens, false));
+        }
+        samples.push(playing(0, 3, 0, false));
+        samples.push(playing(1, 3, 55, false));
+        /* Wave 2: one miss, then all three ships lost */
+        samples.push(playing(1, 3, 55, true));
+        samples.push(playing(1, 2, 55, false));
+        samples.push(playing(1, 0, 55, false));
+        samples.push(WaveSample::default());
+        let waves = play(&samples).unwrap();
+        assert_eq!(waves.len(), 2);
+        assert_eq!((waves[0].wave, waves[0].start_frame, waves[0].end_frame), (1, 1, 11));
+        assert_eq!((waves[0].shots, waves[0].hits, waves[0].deaths, waves[0].lives_remaining, waves[0].cleared), (4, 55, 0, 3, true));
+        assert_eq!((waves[1].wave, waves[1].start_frame, waves[1].end_frame), (2, 11, 15));
+        assert_eq!((waves[1].shots, waves[1].hits, waves[1].deaths, waves[1].lives_remaining, waves[1].cleared), (1, 0, 3, 0, false));
+        assert_eq!(waves[1].accuracy(), Some(0.0));
+    }
+
+    #[test]
+    fn test_dying_on_the_last_alien() {
+        /* The last alien and the ship go in the same sample; with ships left the next rack follows */
+        let samples = [WaveSample::default(), playing(0, 2, 1, false), playing(0, 2, 1, true), playing(0, 1, 0, false), playing(1, 1, 55, false), WaveSample::default()];
+        let waves = play(&samples).unwrap();
+        assert_eq!(waves.len(), 2);
+        assert_eq!((waves[0].hits, waves[0].deaths, waves[0].lives_remaining, waves[0].cleared), (1, 1, 1, true));
+        assert_eq!((waves[1].wave, waves[1].lives_remaining, waves[1].cleared), (2, 1, false));
+        /* On the last ship the game ends before the rack moves on; the wave still counts as cleared */
+        let samples = [WaveSample::default(), playing(0, 1, 1, false), playing(0, 1, 1, true), playing(0, 0, 0, false), WaveSample::default()];
+        let waves = play(&samples).unwrap();
+        assert_eq!(waves.len(), 1);
+        assert_eq!((waves[0].deaths, waves[0].lives_remaining, waves[0].cleared), (1, 0, true));
+        /* Or the rack moves on before the game ends: no empty wave 2 */
+        let samples = [WaveSample::default(), playing(0, 1, 1, false), playing(0, 1, 1, true), playing(1, 0, 55, false), playing(1, 0, 55, false), WaveSample::default()];
+        let waves = play(&samples).unwrap();
+        assert_eq!(waves.len(), 1);
+        assert_eq!((waves[0].hits, waves[0].deaths, waves[0].cleared), (1, 1, true));
+    }
+
+    #[test]
+    fn test_rack_counter_wraps() {
+        let samples = [WaveSample::default(), playing(254, 3, 55, false), playing(255, 3, 55, false), playing(0, 3, 55, false), playing(1, 3, 55, false), WaveSample::default()];
+        let waves = play(&samples).unwrap();
+        assert_eq!(waves.iter().map(|wave| wave.wave).collect::<Vec<_>>(), vec![255, 256, 257, 258]);
+        assert!(waves[..3].iter().all(|wave| wave.cleared));
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut wave = WaveRecord::new(1, 60, 3);
+        wave.end_frame = 60 + 90;
+        wave.shots = 8;
+        wave.hits = 6;
+        wave.cleared = true;
+        let no_shots = WaveRecord { end_frame: 160, ..WaveRecord::new(2, 150, 3) };
+        assert_eq!(to_csv(&[wave, no_shots]), format!("{}\n1,60,150,1.50,8,6,75.0,0,3,true\n2,150,160,0.17,0,0,,0,3,false\n", CSV_HEADER));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::video;
+
+/* The monitor is mounted sideways in the cabinet, so the game is taller than it is wide */
+pub const GAME_WIDTH: f32 = video::HEIGHT as f32;
+pub const GAME_HEIGHT: f32 = video::WIDTH as f32;
+pub const RESIZE_SCALES: [u32; 3] = [2, 3, 4];
+/* Winit reports the whole monitor; leave room for the taskbar and window decorations */
+const WORK_AREA_MARGIN: f32 = 80.0;
+
+/* Everything in the window that isn't the game: menu bar, bottom panels, side panels and margins, in points */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Chrome {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Chrome {
+    pub fn measure(window: [f32; 2], game_area: [f32; 2]) -> Self {
+        Self { width: (window[0] - game_area[0]).max(0.0), height: (window[1] - game_area[1]).max(0.0) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resize {
+    /* Physical pixels per game pixel */
+    pub scale: u32,
+    /* Points per game pixel to draw the image at */
+    pub game_pixel_points: f32,
+    /* Window inner size in points */
+    pub inner_size: [f32; 2],
+}
+
+impl Resize {
+    pub fn new(scale: u32, chrome: Chrome, pixels_per_point: f32) -> Self {
+        let game_pixel_points = scale as f32 / pixels_per_point;
+        let inner_size = [chrome.width + GAME_WIDTH * game_pixel_points, chrome.height + GAME_HEIGHT * game_pixel_points];
+        return Self { scale, game_pixel_points, inner_size };
+    }
+
+    fn fits(&self, monitor: [f32; 2]) -> bool {
+        return self.inner_size[0] <= monitor[0] - WORK_AREA_MARGIN && self.inner_size[1] <= monitor[1] - WORK_AREA_MARGIN;
+    }
+}
+
+/* The requested scale, or the largest smaller one that fits the monitor (in points); never below 1x */
+pub fn fit(requested: u32, chrome: Chrome, pixels_per_point: f32, monitor: Option<[f32; 2]>) -> Resize {
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => return Resize::new(requested.max(1), chrome, pixels_per_point),
+    };
+    return (1..=requested.max(1)).rev()
+        .map(|scale| Resize::new(scale, chrome, pixels_per_point))
+        .find(|resize| resize.fits(monitor))
+        .unwrap_or_else(|| Resize::new(1, chrome, pixels_per_point));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHROME: Chrome = Chrome { width: 16.0, height: 40.0 };
+
+    #[test]
+    fn test_inner_size_adds_chrome() {
+        let resize = Resize::new(3, CHROME, 1.0);
+        assert_eq!(resize.game_pixel_points, 3.0);
+        assert_eq!(resize.inner_size, [16.0 + 224.0 * 3.0, 40.0 + 256.0 * 3.0]);
+        assert_eq!(Resize::new(2, Chrome::default(), 1.0).inner_size, [448.0, 512.0]);
+    }
+
+    #[test]
+    fn test_inner_size_high_dpi() {
+        /* 2x on a 2.0 display is one point per game pixel: 2 physical pixels each */
+        let resize = Resize::new(2, CHROME, 2.0);
+        assert_eq!(resize.game_pixel_points, 1.0);
+        assert_eq!(resize.inner_size, [16.0 + 224.0, 40.0 + 256.0]);
+        let resize = Resize::new(3, CHROME, 1.5);
+        assert_eq!(resize.game_pixel_points, 2.0);
+        assert_eq!(resize.inner_size, [16.0 + 448.0, 40.0 + 512.0]);
+    }
+
+    #[test]
+    fn test_fit_falls_back_to_largest_fitting_scale() {
+        /* 1080p: 4x needs 1064 points of height, 3x needs 808 */
+        let monitor = Some([1920.0, 1080.0]);
+        assert_eq!(fit(4, CHROME, 1.0, monitor).scale, 3);
+        assert_eq!(fit(3, CHROME, 1.0, monitor).scale, 3);
+        assert_eq!(fit(4, CHROME, 2.0, monitor).scale, 4);
+        assert_eq!(fit(4, CHROME, 1.0, Some([3840.0, 2160.0])).scale, 4);
+        assert_eq!(fit(4, CHROME, 1.0, Some([200.0, 200.0])).scale, 1);
+        assert_eq!(fit(4, CHROME, 1.0, None).scale, 4);
+    }
+
+    #[test]
+    fn test_chrome_measure() {
+        assert_eq!(Chrome::measure([500.0, 600.0], [484.0, 560.0]), CHROME);
+        assert_eq!(Chrome::measure([100.0, 100.0], [120.0, 90.0]), Chrome { width: 0.0, height: 10.0 });
+    }
+}
@@ -0,0 +1,251 @@
+// A small pool of threads for exports too slow for the UI thread. Jobs report progress and check for
+// cancellation through their Task; the UI lists what is queued or running and collects outcomes without
+// ever waiting on a job.
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use crate::strings::Message;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TaskId(pub u64);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Done(Message),
+    Failed(Message),
+    Cancelled,
+}
+
+/* A job's handle on its own task */
+pub struct Task {
+    id: TaskId,
+    label: Message,
+    /* f32 bits, 0 to 1 */
+    progress: AtomicU32,
+    started: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl Task {
+    /* Jobs that can't tell how far along they are just never call this */
+    pub fn set_progress(&self, fraction: f32) {
+        self.progress.store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /* Checked by the job between chunks; it should stop and return Outcome::Cancelled */
+    pub fn cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::Relaxed);
+    }
+
+    fn status(&self) -> TaskStatus {
+        TaskStatus {
+            id: self.id,
+            label: self.label.clone(),
+            progress: f32::from_bits(self.progress.load(Ordering::Relaxed)),
+            started: self.started.load(Ordering::Relaxed),
+            cancelling: self.cancelled(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskStatus {
+    pub id: TaskId,
+    pub label: Message,
+    pub progress: f32,
+    pub started: bool,
+    pub cancelling: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finished {
+    pub id: TaskId,
+    pub label: Message,
+    pub outcome: Outcome,
+}
+
+type Job = Box<dyn FnOnce(&Task) -> Outcome + Send>;
+
+pub struct WorkerPool {
+    /* None once the pool is shutting down */
+    jobs: Option<Sender<(Arc<Task>, Job)>>,
+    /* Queued and running, in submission order */
+    tasks: Arc<Mutex<Vec<Arc<Task>>>>,
+    finished: Receiver<Finished>,
+    next_id: u64,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(threads: usize) -> Self {
+        let (jobs_tx, jobs_rx) = mpsc::channel::<(Arc<Task>, Job)>();
+        let (finished_tx, finished) = mpsc::channel();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        let tasks: Arc<Mutex<Vec<Arc<Task>>>> = Arc::new(Mutex::new(vec![]));
+        let threads = (0..threads.max(1)).map(|i| {
+            let jobs_rx = Arc::clone(&jobs_rx);
+            let tasks = Arc::clone(&tasks);
+            let finished_tx: Sender<Finished> = finished_tx.clone();
+            thread::Builder::new().name(format!("worker-{}", i)).spawn(move || loop {
+                let next = jobs_rx.lock().unwrap().recv();
+                let Ok((task, job)) = next else {
+                    return;
+                };
+                task.started.store(true, Ordering::Relaxed);
+                let outcome = if task.cancelled() {
+                    Outcome::Cancelled
+                } else {
+                    /* A panicking job fails its task rather than taking a worker with it */
+                    panic::catch_unwind(AssertUnwindSafe(|| job(&task))).unwrap_or_else(|_| Outcome::Failed(Message::new("tasks.panicked", vec![])))
+                };
+                tasks.lock().unwrap().retain(|other| other.id != task.id);
+                finished_tx.send(Finished { id: task.id, label: task.label.clone(), outcome }).ok();
+            }).expect("spawning a worker thread")
+        }).collect();
+        return Self { jobs: Some(jobs_tx), tasks, finished, next_id: 0, threads };
+    }
+
+    pub fn submit(&mut self, label: Message, job: impl FnOnce(&Task) -> Outcome + Send + 'static) -> TaskId {
+        let id = TaskId(self.next_id);
+        self.next_id += 1;
+        let task = Arc::new(Task {
+            id,
+            label,
+            progress: AtomicU32::new(0.0f32.to_bits()),
+            started: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+        });
+        self.tasks.lock().unwrap().push(Arc::clone(&task));
+        if let Some(jobs) = &self.jobs {
+            jobs.send((task, Box::new(job))).ok();
+        }
+        return id;
+    }
+
+    pub fn tasks(&self) -> Vec<TaskStatus> {
+        return self.tasks.lock().unwrap().iter().map(|task| task.status()).collect();
+    }
+
+    /* A queued task never starts; a running one stops at its next check. Returns whether the task was still pending */
+    pub fn cancel(&self, id: TaskId) -> bool {
+        let tasks = self.tasks.lock().unwrap();
+        let Some(task) = tasks.iter().find(|task| task.id == id) else {
+            return false;
+        };
+        task.cancelled.store(true, Ordering::Relaxed);
+        return true;
+    }
+
+    /* Outcomes since the last call, in the order the tasks finished */
+    pub fn take_finished(&self) -> Vec<Finished> {
+        return self.finished.try_iter().collect();
+    }
+}
+
+/* Cancels everything and waits for the running jobs to notice */
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        for task in self.tasks.lock().unwrap().iter() {
+            task.cancelled.store(true, Ordering::Relaxed);
+        }
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn label(name: &'static str) -> Message {
+        return Message::new(name, vec![]);
+    }
+
+    /* Blocks until every submitted task has finished */
+    fn wait_for(pool: &WorkerPool, count: usize) -> Vec<Finished> {
+        let mut finished = vec![];
+        while finished.len() < count {
+            finished.extend(pool.take_finished());
+            thread::sleep(Duration::from_millis(1));
+        }
+        return finished;
+    }
+
+    #[test]
+    fn test_jobs_run_and_report_outcomes() {
+        let mut pool = WorkerPool::new(2);
+        let done = pool.submit(label("done"), |_| Outcome::Done(Message::new("written", vec!["a.txt".to_string()])));
+        let failed = pool.submit(label("failed"), |_| Outcome::Failed(label("disk full")));
+        let mut finished = wait_for(&pool, 2);
+        finished.sort_by_key(|finished| finished.id.0);
+        assert_eq!(finished[0], Finished { id: done, label: label("done"), outcome: Outcome::Done(Message::new("written", vec!["a.txt".to_string()])) });
+        assert_eq!((finished[1].id, &finished[1].outcome), (failed, &Outcome::Failed(label("disk full"))));
+        assert!(pool.tasks().is_empty());
+        assert!(!pool.cancel(done));
+    }
+
+    #[test]
+    fn test_progress_and_cancel_while_running() {
+        let mut pool = WorkerPool::new(1);
+        let (step_tx, step_rx) = mpsc::channel::<()>();
+        let (reached_tx, reached_rx) = mpsc::channel::<()>();
+        /* Stands in for a chunked export: one chunk per step, checking for cancellation in between */
+        let id = pool.submit(label("export"), move |task| {
+            for chunk in 1..=4 {
+                step_rx.recv().unwrap();
+                if task.cancelled() {
+                    return Outcome::Cancelled;
+                }
+                task.set_progress(chunk as f32 / 4.0);
+                reached_tx.send(()).unwrap();
+            }
+            return Outcome::Done(label("exported"));
+        });
+        step_tx.send(()).unwrap();
+        reached_rx.recv().unwrap();
+        let status = pool.tasks();
+        assert_eq!((status[0].id, status[0].progress, status[0].started, status[0].cancelling), (id, 0.25, true, false));
+        assert!(pool.cancel(id));
+        assert!(pool.tasks()[0].cancelling);
+        step_tx.send(()).unwrap();
+        assert_eq!(wait_for(&pool, 1)[0].outcome, Outcome::Cancelled);
+    }
+
+    #[test]
+    fn test_queued_task_cancelled_before_it_starts() {
+        let mut pool = WorkerPool::new(1);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        pool.submit(label("blocker"), move |_| {
+            release_rx.recv().unwrap();
+            Outcome::Done(label("blocker"))
+        });
+        let ran = Arc::new(AtomicBool::new(false));
+        let ran_in_job = Arc::clone(&ran);
+        let queued = pool.submit(label("queued"), move |_| {
+            ran_in_job.store(true, Ordering::Relaxed);
+            Outcome::Done(label("queued"))
+        });
+        assert!(!pool.tasks()[1].started);
+        pool.cancel(queued);
+        release_tx.send(()).unwrap();
+        let finished = wait_for(&pool, 2);
+        assert_eq!(finished.iter().map(|finished| finished.label.key).collect::<Vec<_>>(), ["blocker", "queued"]);
+        assert_eq!(finished[1].outcome, Outcome::Cancelled);
+        assert!(!ran.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_panicking_job_fails_its_task() {
+        let mut pool = WorkerPool::new(1);
+        pool.submit(label("broken"), |_| panic!("bad export"));
+        pool.submit(label("after"), |_| Outcome::Done(label("after")));
+        let finished = wait_for(&pool, 2);
+        assert_eq!(finished[0].outcome, Outcome::Failed(label("tasks.panicked")));
+        assert_eq!(finished[1].outcome, Outcome::Done(label("after")));
+    }
+}
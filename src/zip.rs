@@ -0,0 +1,337 @@
+// Reads the .zip archives ROM sets circulate in, the way MAME expects them: stored and deflated entries only,
+// with the inflater written out here rather than taken as a dependency for four 2KB files.
+use crate::png::crc32;
+use crate::strings::Message;
+
+const LOCAL_HEADER: u32 = 0x0403_4B50;
+const CENTRAL_HEADER: u32 = 0x0201_4B50;
+const END_OF_DIRECTORY: u32 = 0x0605_4B50;
+const END_OF_DIRECTORY_SIZE: usize = 22;
+/* Entries larger than a padded combined dump can't be part of the set and aren't decompressed */
+pub const MAX_ENTRY_SIZE: usize = 16_384;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/* Every file entry small enough to be a ROM, in directory order */
+pub fn read_entries(file: &str, zip: &[u8]) -> Result<Vec<Entry>, Message> {
+    let corrupt = || Message::new("rom.zip_corrupt", vec![file.to_string()]);
+    /* The end record sits last, followed by a comment of up to 64KB */
+    let earliest = zip.len().saturating_sub(END_OF_DIRECTORY_SIZE + 0xFFFF);
+    let end = (earliest..=zip.len().saturating_sub(END_OF_DIRECTORY_SIZE)).rev().find(|&at| le(zip, at, 4) == Some(END_OF_DIRECTORY)).ok_or_else(corrupt)?;
+    let count = le(zip, end + 10, 2).ok_or_else(corrupt)?;
+    let mut at = le(zip, end + 16, 4).ok_or_else(corrupt)? as usize;
+    let mut entries = vec![];
+    for _ in 0..count {
+        if le(zip, at, 4) != Some(CENTRAL_HEADER) {
+            return Err(corrupt());
+        }
+        let field = |offset: usize, len: usize| le(zip, at + offset, len).ok_or_else(corrupt);
+        let (flags, method, crc) = (field(8, 2)?, field(10, 2)? as u16, field(16, 4)?);
+        let (compressed, size) = (field(20, 4)? as usize, field(24, 4)? as usize);
+        let (name_len, extra_len, comment_len) = (field(28, 2)? as usize, field(30, 2)? as usize, field(32, 2)? as usize);
+        let local = field(42, 4)? as usize;
+        let name = zip.get(at + 46..at + 46 + name_len).ok_or_else(corrupt)?;
+        let name = String::from_utf8_lossy(name).to_string();
+        at += 46 + name_len + extra_len + comment_len;
+        if name.ends_with('/') || size > MAX_ENTRY_SIZE {
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(Message::new("rom.zip_encrypted", vec![file.to_string(), name]));
+        }
+        if le(zip, local, 4) != Some(LOCAL_HEADER) {
+            return Err(corrupt());
+        }
+        let start = local + 30 + le(zip, local + 26, 2).ok_or_else(corrupt)? as usize + le(zip, local + 28, 2).ok_or_else(corrupt)? as usize;
+        let raw = zip.get(start..start + compressed).ok_or_else(corrupt)?;
+        let data = match method {
+            STORED => raw.to_vec(),
+            DEFLATED => inflate(raw, size).ok_or_else(corrupt)?,
+            method => return Err(Message::new("rom.zip_unsupported", vec![file.to_string(), name, method.to_string()])),
+        };
+        if data.len() != size || crc32(&data) != crc {
+            return Err(corrupt());
+        }
+        entries.push(Entry { name, data });
+    }
+    return Ok(entries);
+}
+
+fn le(bytes: &[u8], at: usize, len: usize) -> Option<u32> {
+    let bytes = bytes.get(at..at.checked_add(len)?)?;
+    return Some(bytes.iter().rev().fold(0, |value, byte| (value << 8) | *byte as u32));
+}
+
+/* Raw deflate (RFC 1951), giving up on anything malformed or longer than limit */
+pub fn inflate(input: &[u8], limit: usize) -> Option<Vec<u8>> {
+    let mut bits = Bits { input, at: 0, buffer: 0, count: 0 };
+    let mut out = vec![];
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => {
+                bits.align();
+                let len = le(input, bits.at, 2)? as usize;
+                if le(input, bits.at + 2, 2)? as usize != !len & 0xFFFF {
+                    return None;
+                }
+                out.extend_from_slice(input.get(bits.at + 4..bits.at + 4 + len)?);
+                bits.at += 4 + len;
+            },
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                codes(&mut bits, &mut out, limit, &Huffman::new(&lengths)?, &Huffman::new(&[5; 30])?)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut bits)?;
+                codes(&mut bits, &mut out, limit, &literals, &distances)?;
+            },
+            _ => return None,
+        }
+        if out.len() > limit {
+            return None;
+        }
+        if last {
+            return Some(out);
+        }
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/* The order a dynamic block sends its code length code lengths in */
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct Bits<'a> {
+    input: &'a [u8],
+    at: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    /* Deflate packs from the least significant bit up */
+    fn take(&mut self, n: u32) -> Option<u32> {
+        while self.count < n {
+            self.buffer |= (*self.input.get(self.at)? as u32) << self.count;
+            self.at += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        return Some(value);
+    }
+
+    /* Stored blocks start on a byte boundary; whatever is left of the current byte is dropped */
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/* A canonical code kept as how many codes there are of each length and the symbols in code order */
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return None;
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate().filter(|(_, length)| **length != 0) {
+            symbols[offsets[*length as usize] as usize] = symbol as u16;
+            offsets[*length as usize] += 1;
+        }
+        return Some(Self { counts, symbols });
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for count in &self.counts[1..] {
+            code |= bits.take(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        return None;
+    }
+}
+
+fn dynamic_tables(bits: &mut Bits) -> Option<(Huffman, Huffman)> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_count = bits.take(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return None;
+    }
+    let mut code_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_count) {
+        code_lengths[*index] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+    let total = literal_count + distance_count;
+    let mut lengths: Vec<u8> = Vec::with_capacity(total);
+    while lengths.len() < total {
+        let (length, repeat) = match code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, 3 + bits.take(2)? as usize),
+            17 => (0, 3 + bits.take(3)? as usize),
+            _ => (0, 11 + bits.take(7)? as usize),
+        };
+        if lengths.len() + repeat > total {
+            return None;
+        }
+        lengths.extend(std::iter::repeat(length).take(repeat));
+    }
+    /* Without an end-of-block code the block could never finish */
+    if lengths[256] == 0 {
+        return None;
+    }
+    return Some((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?));
+}
+
+fn codes(bits: &mut Bits, out: &mut Vec<u8>, limit: usize, literals: &Huffman, distances: &Huffman) -> Option<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Some(()),
+            _ => {
+                let index = symbol - 257;
+                let length = *LENGTH_BASE.get(index)? as usize + bits.take(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(bits)? as usize;
+                let distance = *DISTANCE_BASE.get(index)? as usize + bits.take(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > out.len() {
+                    return None;
+                }
+                for _ in 0..length {
+                    out.push(out[out.len() - distance]);
+                }
+            },
+        }
+        if out.len() > limit {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /* Builds an archive the way zip tools lay one out; method 8 entries take data already deflated */
+    pub fn build(entries: &[(&str, u16, &[u8], &[u8])], encrypted: bool) -> Vec<u8> {
+        let (mut zip, mut directory) = (vec![], vec![]);
+        for (name, method, data, stored) in entries {
+            let mut common = vec![];
+            common.extend_from_slice(&20u16.to_le_bytes());
+            common.extend_from_slice(&(encrypted as u16).to_le_bytes());
+            common.extend_from_slice(&method.to_le_bytes());
+            common.extend_from_slice(&[0; 4]);
+            common.extend_from_slice(&crc32(data).to_le_bytes());
+            common.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            common.extend_from_slice(&[0; 2]);
+            directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes());
+            directory.extend_from_slice(&common);
+            /* Comment length, disk, attributes */
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&(zip.len() as u32).to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+            zip.extend_from_slice(&common);
+            zip.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(stored);
+        }
+        let offset = zip.len() as u32;
+        zip.extend_from_slice(&directory);
+        zip.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]);
+        zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        zip.extend_from_slice(&offset.to_le_bytes());
+        zip.extend_from_slice(&[0; 2]);
+        return zip;
+    }
+
+    fn hex(text: &str) -> Vec<u8> {
+        return (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect();
+    }
+
+    /* Attract-screen text, which zlib sends as a dynamic block */
+    const ATTRACT: &[u8] = b"INVADERS SCORE<1> HI-SCORE SCORE<2> PLAY SPACE INVADERS *SCORE ADVANCE TABLE* =? MYSTERY =30 POINTS =20 POINTS =10 POINTS";
+    const ATTRACT_DEFLATED: &str = "45ca410a80201046e1abfceb2048db6631e9404299382278ff8b1495b4fb783c1f0a394e02b167e249cdd87cfff82b7a46dca9422259866f7bf73ee40a85bb675a77ee60161c5532a70a330e88a70f5960f44fd57801";
+
+    #[test]
+    fn test_inflate_block_types() {
+        /* Fixed codes, one long run of back-references */
+        assert_eq!(inflate(&hex("13141c05a360148c8251300a46c1480300"), 4_096), Some(vec![0x11; 2_048]));
+        assert_eq!(inflate(&hex("cb48cdc9c9d751c840a21401"), 64).as_deref(), Some(&b"hello, hello, hello!"[..]));
+        assert_eq!(inflate(&hex(ATTRACT_DEFLATED), 1_024).as_deref(), Some(ATTRACT));
+        /* Stored, final */
+        assert_eq!(inflate(&[1, 3, 0, !3, 0xFF, 7, 8, 9], 16), Some(vec![7, 8, 9]));
+    }
+
+    #[test]
+    fn test_inflate_rejects_bad_streams() {
+        /* Over the limit, cut short, a corrupt stored length and the reserved block type */
+        assert_eq!(inflate(&hex("13141c05a360148c8251300a46c1480300"), 2_047), None);
+        assert_eq!(inflate(&hex(ATTRACT_DEFLATED)[..40], 1_024), None);
+        assert_eq!(inflate(&[1, 3, 0, 3, 0xFF, 7, 8, 9], 16), None);
+        assert_eq!(inflate(&[0b111], 16), None);
+    }
+
+    #[test]
+    fn test_read_entries() {
+        let deflated = hex(ATTRACT_DEFLATED);
+        let zip = build(&[("roms/", 0, b"", b""), ("readme.txt", 8, ATTRACT, &deflated), ("invaders.h", 0, &[1, 2, 3], &[1, 2, 3])], false);
+        let entries = read_entries("set.zip", &zip).unwrap();
+        assert_eq!(entries, [Entry { name: "readme.txt".to_string(), data: ATTRACT.to_vec() }, Entry { name: "invaders.h".to_string(), data: vec![1, 2, 3] }]);
+    }
+
+    #[test]
+    fn test_read_entries_errors() {
+        let error = |key: &'static str, args: &[&str]| Err(Message::new(key, args.iter().map(|arg| arg.to_string()).collect()));
+        assert_eq!(read_entries("set.zip", &build(&[("invaders.h", 0, &[1], &[1])], true)), error("rom.zip_encrypted", &["set.zip", "invaders.h"]));
+        assert_eq!(read_entries("set.zip", &build(&[("invaders.h", 14, &[1], &[1])], false)), error("rom.zip_unsupported", &["set.zip", "invaders.h", "14"]));
+        assert_eq!(read_entries("set.zip", b"not an archive at all"), error("rom.zip_corrupt", &["set.zip"]));
+        /* A flipped byte fails the entry's CRC */
+        let mut zip = build(&[("invaders.h", 0, &[1, 2, 3], &[1, 2, 3])], false);
+        zip[30 + "invaders.h".len()] ^= 0xFF;
+        assert_eq!(read_entries("set.zip", &zip), error("rom.zip_corrupt", &["set.zip"]));
+    }
+}